@@ -66,6 +66,24 @@ pub fn out_ips_parse(ips: &Vec<String>) -> Result<Vec<(u32, u32)>, String> {
     Ok(in_ips_c)
 }
 
+/// 发布的子网，格式为 ipv4/mask 或 ipv4/mask,nat，返回全部网段和需要nat的网段
+pub fn advertise_parse(ips: &Vec<String>) -> Result<(Vec<(u32, u32)>, Vec<(u32, u32)>), String> {
+    let mut routes = vec![];
+    let mut nat_routes = vec![];
+    for x in ips {
+        let mut split = x.split(",");
+        let net = split.next().unwrap_or_default().to_string();
+        let route = out_ips_parse(&vec![net])?[0];
+        match split.next() {
+            None => {}
+            Some("nat") => nat_routes.push(route),
+            Some(_) => return Err("ipv4/mask,nat".to_string()),
+        }
+        routes.push(route);
+    }
+    Ok((routes, nat_routes))
+}
+
 pub fn to_ip(mask: &str) -> Result<u32, String> {
     if let Ok(m) = mask.parse::<u32>() {
         if m >= 32 {
@@ -80,3 +98,47 @@ pub fn to_ip(mask: &str) -> Result<u32, String> {
         Err("not netmask".to_string())
     }
 }
+
+/// 解析端口范围，如 40000-40100
+pub fn port_range_parse(range: &str) -> Result<(u16, u16), String> {
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (start.trim(), end.trim()),
+        None => return Err("not start-end".to_string()),
+    };
+    let start = start.parse::<u16>().map_err(|_| "start not port")?;
+    let end = end.parse::<u16>().map_err(|_| "end not port")?;
+    if start == 0 || start > end {
+        return Err("invalid port range".to_string());
+    }
+    Ok((start, end))
+}
+
+/// 重连退避的最小-最大间隔(秒)，两端都可以带单位，如 2-60、1-5m
+pub fn backoff_parse(backoff: &str) -> Result<(u32, u32), String> {
+    let (min, max) = match backoff.split_once('-') {
+        Some((min, max)) => (duration_parse(min)?, duration_parse(max)?),
+        None => return Err("not min-max".to_string()),
+    };
+    if min == 0 || min > max || max > u32::MAX as u64 {
+        return Err("invalid backoff".to_string());
+    }
+    Ok((min as u32, max as u32))
+}
+
+/// 解析时长，单位s/m/h/d，不带单位时为秒，如 90、10m、2h
+pub fn duration_parse(duration: &str) -> Result<u64, String> {
+    let duration = duration.trim();
+    let (num, unit) = match duration.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => duration.split_at(index),
+        None => (duration, "s"),
+    };
+    let num = num.parse::<u64>().map_err(|_| "not number".to_string())?;
+    let unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("unknown unit '{}'", unit)),
+    };
+    num.checked_mul(unit).ok_or_else(|| "too large".to_string())
+}
@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// 设备别名，保存在本地，key为别名，value为虚拟ip
+pub type Aliases = BTreeMap<String, Ipv4Addr>;
+
+fn alias_file() -> io::Result<PathBuf> {
+    Ok(crate::app_home()?.join("alias.json"))
+}
+
+pub fn load() -> Aliases {
+    let path = match alias_file() {
+        Ok(path) => path,
+        Err(_) => return Aliases::new(),
+    };
+    match std::fs::read(path) {
+        Ok(data) => match serde_json::from_slice(&data) {
+            Ok(aliases) => aliases,
+            Err(e) => {
+                log::warn!("alias.json {:?}", e);
+                Aliases::new()
+            }
+        },
+        Err(_) => Aliases::new(),
+    }
+}
+
+fn save(aliases: &Aliases) -> io::Result<()> {
+    let data =
+        serde_json::to_vec_pretty(aliases).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    std::fs::write(alias_file()?, data)
+}
+
+pub fn set(ip: &str, name: &str) -> io::Result<()> {
+    let ip = Ipv4Addr::from_str(ip.trim())
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("ip {} error:{}", ip, e)))?;
+    let name = name.trim();
+    if name.is_empty()
+        || Ipv4Addr::from_str(name).is_ok()
+        || name.contains(|c: char| c == ',' || c == '/' || c.is_whitespace())
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("invalid alias '{}'", name),
+        ));
+    }
+    let mut aliases = load();
+    // 一个ip只保留一个别名
+    aliases.retain(|_, v| *v != ip);
+    aliases.insert(name.to_string(), ip);
+    save(&aliases)
+}
+
+/// 按别名或者ip删除
+pub fn remove(name_or_ip: &str) -> io::Result<()> {
+    let name_or_ip = name_or_ip.trim();
+    let mut aliases = load();
+    match Ipv4Addr::from_str(name_or_ip) {
+        Ok(ip) => aliases.retain(|_, v| *v != ip),
+        Err(_) => {
+            aliases.remove(name_or_ip);
+        }
+    }
+    save(&aliases)
+}
+
+/// 指定设备的参数(命令行、配置文件、控制命令)都通过这里解析，可以是虚拟ip或别名
+pub fn parse_peer(name_or_ip: &str) -> io::Result<Ipv4Addr> {
+    resolve(&load(), name_or_ip).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("'{}' not ip or alias", name_or_ip.trim()),
+        )
+    })
+}
+
+/// 虚拟ip或者别名转换成虚拟ip
+fn resolve(aliases: &Aliases, name_or_ip: &str) -> Option<Ipv4Addr> {
+    let name_or_ip = name_or_ip.trim();
+    if let Ok(ip) = Ipv4Addr::from_str(name_or_ip) {
+        return Some(ip);
+    }
+    aliases.get(name_or_ip).copied()
+}
+
+pub fn alias_of(aliases: &Aliases, ip: &Ipv4Addr) -> Option<String> {
+    aliases
+        .iter()
+        .find(|(_, v)| *v == ip)
+        .map(|(k, _)| k.clone())
+}
+
+/// ip后面附加别名，用于输出
+pub fn display(aliases: &Aliases, ip: &Ipv4Addr) -> String {
+    match alias_of(aliases, ip) {
+        None => ip.to_string(),
+        Some(name) => format!("{}({})", ip, name),
+    }
+}
+
+/// 点对网入站参数 net/mask,ip 中的ip可以使用别名
+pub fn resolve_in_ips(aliases: &Aliases, in_ips: Vec<String>) -> Vec<String> {
+    in_ips
+        .into_iter()
+        .map(|item| match item.split_once(',') {
+            Some((net, name)) => match resolve(aliases, name) {
+                Some(ip) => format!("{},{}", net, ip),
+                None => item,
+            },
+            None => item,
+        })
+        .collect()
+}
+
+pub fn print_list() {
+    let aliases = load();
+    if aliases.is_empty() {
+        println!("No alias found");
+        return;
+    }
+    for (name, ip) in aliases {
+        println!("{:<16}{}", ip, name);
+    }
+}
@@ -0,0 +1,65 @@
+pub mod peer;
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use vnt::cipher::hw;
+use vnt::cipher::CipherModel;
+
+use crate::exit_code;
+
+/// 测试当前设备上各个加密模式的吞吐量
+pub fn crypto(duration: Duration) -> i32 {
+    let features = hw::cpu_features();
+    println!(
+        "cpu: {} {}",
+        std::env::consts::ARCH,
+        if features.is_empty() {
+            "-".to_string()
+        } else {
+            features.join(" ")
+        }
+    );
+    println!(
+        "aes acceleration: {}",
+        if hw::aes_accelerated() { "yes" } else { "no" }
+    );
+    let models = hw::models();
+    if models.is_empty() {
+        println!("Encryption not supported");
+        return exit_code::INVALID_ARGS;
+    }
+    let fastest = hw::fastest_model();
+    println!("{:<20}{:>16}{:>16}", "model", "encrypt", "decrypt");
+    for name in models {
+        let model = match CipherModel::from_str(name) {
+            Ok(model) => model,
+            Err(_) => continue,
+        };
+        match hw::throughput(model, duration) {
+            Ok((encrypt, decrypt)) => {
+                let line = format!(
+                    "{:<20}{:>16}{:>16}",
+                    if model == fastest {
+                        format!("{}(auto)", name)
+                    } else {
+                        name.to_string()
+                    },
+                    speed(encrypt),
+                    speed(decrypt)
+                );
+                if model == fastest {
+                    println!("{}", console::style(line).green());
+                } else {
+                    println!("{}", line);
+                }
+            }
+            Err(e) => println!("{:<20}{}", name, e),
+        }
+    }
+    exit_code::OK
+}
+
+fn speed(bytes: f64) -> String {
+    format!("{:.1} MB/s", bytes / 1024.0 / 1024.0)
+}
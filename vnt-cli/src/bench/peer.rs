@@ -0,0 +1,424 @@
+//! 两个节点之间经虚拟网络测速，类似iperf，tcp控制连接只监听在本机的虚拟ip上
+//!
+//! 1. 服务端发送随机数，客户端回复HMAC(密钥, 随机数\nbench)
+//! 2. ping：服务端回复pong，测量往返延迟
+//! 3. tcp：客户端发送若干个 长度(32位)+数据 的块，长度为0时结束，服务端回复收到的字节数
+//! 4. udp：服务端绑定一个udp端口并回复，客户端尽可能快地发送数据报，结束后在控制连接上发送done，
+//!    服务端回复收到的包数、字节数和首尾包的间隔
+
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, TcpListener, TcpStream, UdpSocket};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use serde::Serialize;
+
+use crate::command::client::CommandClient;
+use crate::remote::{hex, hmac, verify};
+use crate::{alias, exit_code};
+
+pub const PORT: u16 = 39274;
+const CHUNK: usize = 64 * 1024;
+/// udp数据报的大小，小于虚拟网卡的mtu避免分片
+const DATAGRAM: usize = 1200;
+const PINGS: usize = 20;
+
+#[derive(Serialize, Debug, Default)]
+pub struct PathResult {
+    /// p2p/relay
+    pub path: String,
+    /// 测试时使用的路径，如 p2p、server-relay
+    pub route: String,
+    pub rtt_min_ms: f64,
+    pub rtt_avg_ms: f64,
+    pub rtt_max_ms: f64,
+    pub tcp_mbps: f64,
+    pub udp_mbps: f64,
+    /// udp丢包率，0-1
+    pub udp_loss: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn read_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "connection closed",
+        ));
+    }
+    Ok(line.trim_end().to_string())
+}
+
+fn mbps(bytes: u64, elapsed: Duration) -> f64 {
+    bytes as f64 * 8.0 / elapsed.as_secs_f64().max(0.001) / 1_000_000.0
+}
+
+/// 等待其他节点来测速，一次处理一个连接，Ctrl+C退出
+pub fn listen(key: &str) -> i32 {
+    match listen0(key) {
+        Ok(_) => exit_code::OK,
+        Err(e) => {
+            println!("bench error {}", e);
+            exit_code::ERROR
+        }
+    }
+}
+
+fn listen0(key: &str) -> io::Result<()> {
+    // 只监听虚拟ip，不会暴露到物理网络
+    let info = CommandClient::new()?.info()?;
+    let ip = Ipv4Addr::from_str(&info.virtual_ip).map_err(|_| invalid("virtual ip error"))?;
+    let listener = TcpListener::bind(SocketAddr::V4(SocketAddrV4::new(ip, PORT)))?;
+    println!("waiting for bench on {}:{}", ip, PORT);
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let peer = stream.peer_addr()?;
+        println!("bench from {}", peer);
+        if let Err(e) = serve(stream, ip, key) {
+            println!("bench from {} error {}", peer, e);
+        }
+    }
+    Ok(())
+}
+
+fn serve(stream: TcpStream, ip: Ipv4Addr, key: &str) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(Duration::from_secs(60)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let nonce = hex(&rand::thread_rng().gen::<[u8; 16]>());
+    writer.write_all(format!("{}\n", nonce).as_bytes())?;
+    let mac = read_line(&mut reader)?;
+    let expected = hmac(key.as_bytes(), &[nonce.as_bytes(), b"\nbench"]);
+    if !verify(&mac, &expected) {
+        writer.write_all(b"key mismatch\n")?;
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "key mismatch",
+        ));
+    }
+    writer.write_all(b"ok\n")?;
+    loop {
+        let cmd = match read_line(&mut reader) {
+            Ok(cmd) => cmd,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        match cmd.as_str() {
+            "ping" => writer.write_all(b"pong\n")?,
+            "tcp" => {
+                let mut total = 0u64;
+                let mut buf = vec![0u8; CHUNK];
+                loop {
+                    let mut len = [0u8; 4];
+                    reader.read_exact(&mut len)?;
+                    let len = u32::from_be_bytes(len) as usize;
+                    if len == 0 {
+                        break;
+                    }
+                    if len > CHUNK {
+                        return Err(invalid("chunk too large"));
+                    }
+                    reader.read_exact(&mut buf[..len])?;
+                    total += len as u64;
+                }
+                writer.write_all(format!("bytes {}\n", total).as_bytes())?;
+            }
+            "udp" => serve_udp(&mut reader, &mut writer, ip)?,
+            "quit" => return Ok(()),
+            _ => writer.write_all(format!("unknown command {}\n", cmd).as_bytes())?,
+        }
+    }
+}
+
+fn serve_udp(
+    reader: &mut BufReader<TcpStream>,
+    writer: &mut TcpStream,
+    ip: Ipv4Addr,
+) -> io::Result<()> {
+    let socket = UdpSocket::bind(SocketAddr::V4(SocketAddrV4::new(ip, 0)))?;
+    socket.set_read_timeout(Some(Duration::from_millis(100)))?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_ = stop.clone();
+    let socket_ = socket.try_clone()?;
+    let handle = std::thread::spawn(move || {
+        let mut buf = vec![0u8; 65536];
+        let mut count = 0u64;
+        let mut bytes = 0u64;
+        let mut first: Option<Instant> = None;
+        let mut last = Instant::now();
+        // 收到done后再等一会，接收还在路上的包
+        let mut deadline: Option<Instant> = None;
+        loop {
+            if deadline.is_none() && stop_.load(Ordering::Relaxed) {
+                deadline = Some(Instant::now() + Duration::from_millis(500));
+            }
+            if deadline.map_or(false, |v| v < Instant::now()) {
+                break;
+            }
+            if let Ok(len) = socket_.recv(&mut buf) {
+                let now = Instant::now();
+                first.get_or_insert(now);
+                last = now;
+                count += 1;
+                bytes += len as u64;
+            }
+        }
+        let elapsed = first.map_or(Duration::ZERO, |first| last - first);
+        (count, bytes, elapsed)
+    });
+    writer.write_all(format!("port {}\n", socket.local_addr()?.port()).as_bytes())?;
+    let done = read_line(reader);
+    stop.store(true, Ordering::Relaxed);
+    let (count, bytes, elapsed) = handle
+        .join()
+        .map_err(|_| io::Error::new(io::ErrorKind::Other, "udp receiver panicked"))?;
+    done?;
+    writer.write_all(format!("recv {} {} {}\n", count, bytes, elapsed.as_millis()).as_bytes())?;
+    Ok(())
+}
+
+struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    ip: Ipv4Addr,
+}
+
+impl Client {
+    fn connect(ip: Ipv4Addr, key: &str) -> io::Result<Client> {
+        let stream = TcpStream::connect_timeout(
+            &SocketAddr::V4(SocketAddrV4::new(ip, PORT)),
+            Duration::from_secs(5),
+        )?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(Duration::from_secs(60)))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+        let nonce = read_line(&mut reader)?;
+        let mac = hmac(key.as_bytes(), &[nonce.as_bytes(), b"\nbench"]);
+        writer.write_all(format!("{}\n", hex(&mac)).as_bytes())?;
+        let reply = read_line(&mut reader)?;
+        if reply != "ok" {
+            return Err(io::Error::new(io::ErrorKind::Other, reply));
+        }
+        Ok(Client { reader, writer, ip })
+    }
+    /// 往返延迟的最小、平均、最大值(毫秒)
+    fn ping(&mut self) -> io::Result<(f64, f64, f64)> {
+        let mut rtts = Vec::with_capacity(PINGS);
+        for _ in 0..PINGS {
+            let start = Instant::now();
+            self.writer.write_all(b"ping\n")?;
+            if read_line(&mut self.reader)? != "pong" {
+                return Err(invalid("unexpected reply"));
+            }
+            rtts.push(start.elapsed().as_secs_f64() * 1000.0);
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        let min = rtts.iter().cloned().fold(f64::MAX, f64::min);
+        let max = rtts.iter().cloned().fold(0.0, f64::max);
+        let avg = rtts.iter().sum::<f64>() / rtts.len() as f64;
+        Ok((min, avg, max))
+    }
+    fn tcp(&mut self, duration: Duration) -> io::Result<f64> {
+        self.writer.write_all(b"tcp\n")?;
+        let mut chunk = vec![0u8; 4 + CHUNK];
+        chunk[..4].copy_from_slice(&(CHUNK as u32).to_be_bytes());
+        rand::thread_rng().fill(&mut chunk[4..]);
+        let start = Instant::now();
+        while start.elapsed() < duration {
+            self.writer.write_all(&chunk)?;
+        }
+        self.writer.write_all(&0u32.to_be_bytes())?;
+        let reply = read_line(&mut self.reader)?;
+        let bytes = reply
+            .strip_prefix("bytes ")
+            .and_then(|v| u64::from_str(v).ok())
+            .ok_or_else(|| invalid("unexpected reply"))?;
+        // 等到服务端确认收完才算结束
+        Ok(mbps(bytes, start.elapsed()))
+    }
+    /// 吞吐量和丢包率
+    fn udp(&mut self, duration: Duration) -> io::Result<(f64, f64)> {
+        self.writer.write_all(b"udp\n")?;
+        let reply = read_line(&mut self.reader)?;
+        let port = reply
+            .strip_prefix("port ")
+            .and_then(|v| u16::from_str(v).ok())
+            .ok_or_else(|| invalid("unexpected reply"))?;
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(SocketAddr::V4(SocketAddrV4::new(self.ip, port)))?;
+        let mut buf = vec![0u8; DATAGRAM];
+        let start = Instant::now();
+        let mut sent = 0u64;
+        while start.elapsed() < duration {
+            buf[..8].copy_from_slice(&sent.to_be_bytes());
+            // 发送缓冲区满时丢弃，和网络丢包一样计入丢包率
+            let _ = socket.send(&buf);
+            sent += 1;
+        }
+        self.writer.write_all(b"done\n")?;
+        let reply = read_line(&mut self.reader)?;
+        let mut fields = reply
+            .strip_prefix("recv ")
+            .ok_or_else(|| invalid("unexpected reply"))?
+            .split(' ')
+            .map(u64::from_str);
+        let (count, bytes, elapsed) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(Ok(count)), Some(Ok(bytes)), Some(Ok(elapsed))) => (count, bytes, elapsed),
+            _ => return Err(invalid("unexpected reply")),
+        };
+        let loss = if sent == 0 {
+            0.0
+        } else {
+            1.0 - (count.min(sent) as f64 / sent as f64)
+        };
+        Ok((mbps(bytes, Duration::from_millis(elapsed)), loss))
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        let _ = self.writer.write_all(b"quit\n");
+    }
+}
+
+/// 和对端测速，分别测试直连和经服务端中转的路径
+pub fn run(peer: &str, key: &str, duration: Duration, json: bool) -> i32 {
+    let ip = match alias::parse_peer(peer) {
+        Ok(ip) => ip,
+        Err(e) => {
+            println!("{}", e);
+            return exit_code::INVALID_ARGS;
+        }
+    };
+    let command = match CommandClient::new() {
+        Ok(command) => command,
+        Err(e) => {
+            println!("bench error {}", e);
+            return exit_code::NOT_RUNNING;
+        }
+    };
+    let mut results = Vec::with_capacity(2);
+    let route = current_route(&command, ip);
+    if route.contains("p2p") {
+        results.push(bench_path("p2p", &route, ip, key, duration));
+    } else {
+        results.push(PathResult {
+            path: "p2p".to_string(),
+            route,
+            error: Some("no direct path".to_string()),
+            ..Default::default()
+        });
+    }
+    // 测试期间临时改为优先中转，多留一些时间，测完或者异常退出后都会恢复
+    let hold = duration.as_secs() * 2 + 30;
+    match command.path_policy(ip, "prefer-relay", hold) {
+        Ok(reply) if reply == "ok" => {
+            // 等正在发送的数据切换到中转
+            std::thread::sleep(Duration::from_secs(1));
+            results.push(bench_path("relay", "server-relay", ip, key, duration));
+            let _ = command.path_policy(ip, "auto", 0);
+        }
+        Ok(reply) => results.push(PathResult {
+            path: "relay".to_string(),
+            error: Some(reply),
+            ..Default::default()
+        }),
+        Err(e) => results.push(PathResult {
+            path: "relay".to_string(),
+            error: Some(e.to_string()),
+            ..Default::default()
+        }),
+    }
+    let failed = results.iter().all(|v| v.error.is_some());
+    if json {
+        match serde_json::to_string_pretty(&results) {
+            Ok(out) => println!("{}", out),
+            Err(e) => println!("{:?}", e),
+        }
+    } else {
+        print_results(ip, &results);
+    }
+    if failed {
+        exit_code::ERROR
+    } else {
+        exit_code::OK
+    }
+}
+
+fn current_route(command: &CommandClient, ip: Ipv4Addr) -> String {
+    let ip = ip.to_string();
+    command
+        .list()
+        .ok()
+        .and_then(|list| list.into_iter().find(|v| v.virtual_ip == ip))
+        .map(|v| v.nat_traversal_type)
+        .unwrap_or_default()
+}
+
+fn bench_path(path: &str, route: &str, ip: Ipv4Addr, key: &str, duration: Duration) -> PathResult {
+    let mut result = PathResult {
+        path: path.to_string(),
+        route: route.to_string(),
+        ..Default::default()
+    };
+    if let Err(e) = bench_path0(&mut result, ip, key, duration) {
+        result.error = Some(e.to_string());
+    }
+    result
+}
+
+fn bench_path0(
+    result: &mut PathResult,
+    ip: Ipv4Addr,
+    key: &str,
+    duration: Duration,
+) -> io::Result<()> {
+    let mut client = Client::connect(ip, key)?;
+    let (min, avg, max) = client.ping()?;
+    result.rtt_min_ms = min;
+    result.rtt_avg_ms = avg;
+    result.rtt_max_ms = max;
+    result.tcp_mbps = client.tcp(duration)?;
+    let (udp_mbps, udp_loss) = client.udp(duration)?;
+    result.udp_mbps = udp_mbps;
+    result.udp_loss = udp_loss;
+    Ok(())
+}
+
+fn print_results(ip: Ipv4Addr, results: &[PathResult]) {
+    println!("bench {}", ip);
+    println!(
+        "{:<8}{:<16}{:>24}{:>14}{:>14}{:>10}",
+        "path", "route", "rtt min/avg/max(ms)", "tcp(Mbps)", "udp(Mbps)", "loss"
+    );
+    for result in results {
+        if let Some(error) = &result.error {
+            println!("{:<8}{:<16}{}", result.path, result.route, error);
+            continue;
+        }
+        println!(
+            "{:<8}{:<16}{:>24}{:>14.1}{:>14.1}{:>9.1}%",
+            result.path,
+            result.route,
+            format!(
+                "{:.1}/{:.1}/{:.1}",
+                result.rtt_min_ms, result.rtt_avg_ms, result.rtt_max_ms
+            ),
+            result.tcp_mbps,
+            result.udp_mbps,
+            result.udp_loss * 100.0
+        );
+    }
+}
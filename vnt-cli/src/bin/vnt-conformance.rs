@@ -0,0 +1,135 @@
+//! 协议一致性测试：输出协议文档和测试向量，或者用向量文件验证当前实现
+//!
+//! vnt-conformance doc [file]
+//! vnt-conformance emit [file]
+//! vnt-conformance check <file>
+
+use std::collections::BTreeMap;
+use std::process::exit;
+
+use serde::{Deserialize, Serialize};
+use vnt::protocol::doc;
+use vnt::protocol::vectors::{self, Vector, Verdict};
+
+#[derive(Serialize, Deserialize)]
+struct VectorFile {
+    version: String,
+    vectors: Vec<VectorItem>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct VectorItem {
+    name: String,
+    kind: String,
+    #[serde(default)]
+    description: String,
+    fields: BTreeMap<String, String>,
+    hex: String,
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: vnt-conformance doc [file] | emit [file] | check <file>");
+    exit(2)
+}
+
+fn write_out(path: Option<&String>, content: &str) {
+    match path {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, content) {
+                eprintln!("write {} failed: {}", path, e);
+                exit(1)
+            }
+        }
+        None => print!("{}", content),
+    }
+}
+
+fn emit(path: Option<&String>) {
+    let list = match vectors::generate() {
+        Ok(list) => list,
+        Err(e) => {
+            eprintln!("generate vectors failed: {}", e);
+            exit(1)
+        }
+    };
+    let file = VectorFile {
+        version: vnt::VNT_VERSION.to_string(),
+        vectors: list
+            .into_iter()
+            .map(|v| VectorItem {
+                name: v.name,
+                kind: v.kind,
+                description: v.description,
+                fields: v.fields,
+                hex: vectors::to_hex(&v.bytes),
+            })
+            .collect(),
+    };
+    match serde_json::to_string_pretty(&file) {
+        Ok(json) => write_out(path, &(json + "\n")),
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1)
+        }
+    }
+}
+
+fn check(path: &str) {
+    let file: VectorFile = match std::fs::read(path)
+        .map_err(|e| e.to_string())
+        .and_then(|data| serde_json::from_slice(&data).map_err(|e| e.to_string()))
+    {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("read {} failed: {}", path, e);
+            exit(1)
+        }
+    };
+    let (mut pass, mut skip, mut fail) = (0, 0, 0);
+    for item in file.vectors {
+        let verdict = match vectors::from_hex(&item.hex) {
+            Ok(bytes) => vectors::check(&Vector {
+                name: item.name.clone(),
+                kind: item.kind,
+                description: item.description,
+                fields: item.fields,
+                bytes,
+            }),
+            Err(e) => Verdict::Fail(e),
+        };
+        match verdict {
+            Verdict::Pass => {
+                pass += 1;
+                println!("pass {}", item.name);
+            }
+            Verdict::Skip(reason) => {
+                skip += 1;
+                println!("skip {}: {}", item.name, reason);
+            }
+            Verdict::Fail(reason) => {
+                fail += 1;
+                println!("FAIL {}: {}", item.name, reason);
+            }
+        }
+    }
+    println!(
+        "{} passed, {} skipped, {} failed (vectors from {})",
+        pass, skip, fail, file.version
+    );
+    if fail > 0 {
+        exit(1)
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(|s| s.as_str()) {
+        Some("doc") => write_out(args.get(1), &doc::document()),
+        Some("emit") => emit(args.get(1)),
+        Some("check") => match args.get(1) {
+            Some(path) => check(path),
+            None => usage(),
+        },
+        _ => usage(),
+    }
+}
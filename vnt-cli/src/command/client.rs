@@ -1,47 +1,101 @@
 use std::io;
-use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
-use std::str::FromStr;
+use std::io::{Read, Write};
+use std::net::Ipv4Addr;
+#[cfg(unix)]
+use std::path::PathBuf;
 use std::time::Duration;
 
-use crate::command::entity::{DeviceItem, Info, RouteItem};
+use crate::command::entity::{DeviceItem, EventItem, Info, PunchItem, RouteItem, TunnelItem};
+
+/// 启动组网需要握手和注册，比其他命令慢
+const TUNNEL_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub struct CommandClient {
-    udp: UdpSocket,
+    #[cfg(unix)]
+    path: PathBuf,
+    /// 查看者凭证，设置后使用只读通道
+    token: Option<String>,
+}
+
+fn viewer_token() -> Option<String> {
+    std::env::var(super::viewer::TOKEN_ENV)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
 }
 
 impl CommandClient {
+    #[cfg(unix)]
     pub fn new() -> io::Result<Self> {
-        let path_buf = crate::app_home()?.join("command-port");
-        let port = if path_buf.exists() {
-            let port = std::fs::read_to_string(path_buf)?;
-            match u16::from_str(&port) {
-                Ok(port) => port,
-                Err(_) => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        "'command-port' file error",
-                    ));
-                }
-            }
+        let token = viewer_token();
+        let path = if token.is_some() {
+            super::viewer_socket_path()?
         } else {
-            39271
+            super::socket_path()?
         };
-        let udp = UdpSocket::bind("127.0.0.1:0")?;
-        udp.set_read_timeout(Some(Duration::from_secs(5)))?;
-        udp.connect(SocketAddr::V4(SocketAddrV4::new(
-            Ipv4Addr::new(127, 0, 0, 1),
-            port,
-        )))?;
-        Ok(Self { udp })
+        if !path.exists() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "not running"));
+        }
+        Ok(Self { path, token })
+    }
+    #[cfg(windows)]
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            token: viewer_token(),
+        })
+    }
+    fn request(&self, cmd: &str) -> io::Result<Vec<u8>> {
+        self.request_timeout(cmd, Duration::from_secs(5))
+    }
+    /// 每条命令一个连接，发送以换行结尾的命令，读取到连接关闭为止
+    fn request_timeout(&self, cmd: &str, timeout: Duration) -> io::Result<Vec<u8>> {
+        let mut stream = self.connect(timeout)?;
+        if let Some(token) = &self.token {
+            stream.write_all(token.as_bytes())?;
+            stream.write_all(b" ")?;
+        }
+        stream.write_all(cmd.as_bytes())?;
+        stream.write_all(b"\n")?;
+        let mut buf = Vec::with_capacity(1024);
+        stream.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+    #[cfg(unix)]
+    fn connect(&self, timeout: Duration) -> io::Result<std::os::unix::net::UnixStream> {
+        let stream = std::os::unix::net::UnixStream::connect(&self.path)?;
+        stream.set_read_timeout(Some(timeout))?;
+        Ok(stream)
+    }
+    #[cfg(windows)]
+    fn connect(&self, _timeout: Duration) -> io::Result<std::fs::File> {
+        //ERROR_PIPE_BUSY，服务端正在处理上一个连接
+        const PIPE_BUSY: i32 = 231;
+        let name = if self.token.is_some() {
+            super::pipe_name(super::VIEWER_PIPE_NAME)
+        } else {
+            super::pipe_name(super::PIPE_NAME)
+        };
+        let mut count = 0;
+        loop {
+            match std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&name)
+            {
+                Err(e) if e.raw_os_error() == Some(PIPE_BUSY) && count < 10 => {
+                    count += 1;
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                rs => return rs,
+            }
+        }
     }
 }
 
 impl CommandClient {
     pub fn list(&self) -> io::Result<Vec<DeviceItem>> {
-        self.udp.send(b"list")?;
-        let mut buf = [0; 10240];
-        let len = self.udp.recv(&mut buf)?;
-        match serde_json::from_slice::<Vec<DeviceItem>>(&buf[..len]) {
+        let buf = self.request("list")?;
+        match serde_json::from_slice::<Vec<DeviceItem>>(&buf) {
             Ok(val) => Ok(val),
             Err(e) => {
                 log::error!("{:?}", e);
@@ -50,10 +104,18 @@ impl CommandClient {
         }
     }
     pub fn route(&self) -> io::Result<Vec<RouteItem>> {
-        self.udp.send(b"route")?;
-        let mut buf = [0; 10240];
-        let len = self.udp.recv(&mut buf)?;
-        match serde_json::from_slice::<Vec<RouteItem>>(&buf[..len]) {
+        let buf = self.request("route")?;
+        match serde_json::from_slice::<Vec<RouteItem>>(&buf) {
+            Ok(val) => Ok(val),
+            Err(e) => {
+                log::error!("{:?}", e);
+                Err(io::Error::new(io::ErrorKind::Other, "data error"))
+            }
+        }
+    }
+    pub fn punch_list(&self) -> io::Result<Vec<PunchItem>> {
+        let buf = self.request("punch-list")?;
+        match serde_json::from_slice::<Vec<PunchItem>>(&buf) {
             Ok(val) => Ok(val),
             Err(e) => {
                 log::error!("{:?}", e);
@@ -62,21 +124,68 @@ impl CommandClient {
         }
     }
     pub fn info(&self) -> io::Result<Info> {
-        self.udp.send(b"info")?;
-        let mut buf = [0; 10240];
-        let len = self.udp.recv(&mut buf)?;
-        match serde_json::from_slice::<Info>(&buf[..len]) {
+        let buf = self.request("info")?;
+        match serde_json::from_slice::<Info>(&buf) {
+            Ok(val) => Ok(val),
+            Err(e) => {
+                log::error!("{:?},{:?}", &buf, e);
+                Err(io::Error::new(io::ErrorKind::Other, "data error"))
+            }
+        }
+    }
+    /// 时间不早于since(unix秒)的连接事件
+    pub fn events(&self, since: u64) -> io::Result<Vec<EventItem>> {
+        let buf = self.request(&format!("events:{}", since))?;
+        match serde_json::from_slice::<Vec<EventItem>>(&buf) {
             Ok(val) => Ok(val),
             Err(e) => {
-                log::error!("{:?},{:?}", &buf[..len], e);
+                log::error!("{:?}", e);
                 Err(io::Error::new(io::ErrorKind::Other, "data error"))
             }
         }
     }
+    pub fn tunnels(&self) -> io::Result<Vec<TunnelItem>> {
+        let buf = self.request("tunnel-list")?;
+        match serde_json::from_slice::<Vec<TunnelItem>>(&buf) {
+            Ok(val) => Ok(val),
+            Err(e) => {
+                log::error!("{:?}", e);
+                Err(io::Error::new(io::ErrorKind::Other, "data error"))
+            }
+        }
+    }
+    /// 启停同一进程中的其他组网，返回文本结果
+    pub fn tunnel(&self, cmd: &str) -> io::Result<String> {
+        let buf = self.request_timeout(cmd, TUNNEL_TIMEOUT)?;
+        Ok(String::from_utf8_lossy(&buf).to_string())
+    }
+    /// 按程序分流的命令，返回文本结果
+    pub fn split(&self, cmd: &str) -> io::Result<String> {
+        let buf = self.request(cmd)?;
+        Ok(String::from_utf8_lossy(&buf).to_string())
+    }
+    /// 更换组网密码，返回文本结果
+    pub fn rekey(&self, password: &str, window: u64) -> io::Result<String> {
+        let buf = self.request(&format!("rekey:{}:{}", window, password))?;
+        Ok(String::from_utf8_lossy(&buf).to_string())
+    }
+    /// 临时指定和某个设备的路径策略，secs为0时恢复
+    pub fn path_policy(&self, ip: Ipv4Addr, policy: &str, secs: u64) -> io::Result<String> {
+        let buf = self.request(&format!("path:{}={}:{}", ip, policy, secs))?;
+        Ok(String::from_utf8_lossy(&buf).to_string())
+    }
+    /// 会话密钥日志，后台实例没有开启debug_keys时返回错误
+    pub fn export_keys(&self) -> io::Result<String> {
+        let buf = self.request("export-keys")?;
+        let out = String::from_utf8_lossy(&buf).to_string();
+        if out.starts_with('#') {
+            Ok(out)
+        } else {
+            Err(io::Error::new(io::ErrorKind::Other, out))
+        }
+    }
     pub fn stop(&self) -> io::Result<String> {
-        self.udp.send(b"stop")?;
-        let mut buf = [0; 10240];
-        let len = self.udp.recv(&mut buf)?;
-        Ok(String::from_utf8(buf[..len].to_vec()).unwrap())
+        let buf = self.request("stop")?;
+        Ok(String::from_utf8_lossy(&buf).to_string())
     }
 }
@@ -11,6 +11,36 @@ pub struct Info {
     pub public_ips: String,
     pub local_addr: String,
     pub ipv6_addr: String,
+    /// 服务端下发的配置版本
+    #[serde(default)]
+    pub remote_config: String,
+    /// 服务端的中转流量配额
+    #[serde(default)]
+    pub quota: String,
+    /// 解密失败次数和被隔离的地址
+    #[serde(default)]
+    pub auth_failures: String,
+    /// 按原因统计的丢弃的不合法ip报文，没有时为空
+    #[serde(default)]
+    pub malformed: String,
+    /// 广播策略和计数
+    #[serde(default)]
+    pub broadcast: String,
+    /// 和其他设备的最大时钟偏差
+    #[serde(default)]
+    pub clock_skew: String,
+    /// 定时自检的结果，未配置时为空
+    #[serde(default)]
+    pub self_test: String,
+    /// 按设备记录的状态的估算内存和淘汰次数
+    #[serde(default)]
+    pub memory: String,
+    /// 和服务端失联的时长和直连剩余的宽限时间，服务端可用时为空
+    #[serde(default)]
+    pub server_lost: String,
+    /// 服务端的维护通知，没有维护时为空
+    #[serde(default)]
+    pub maintenance: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -20,6 +50,58 @@ pub struct RouteItem {
     pub metric: String,
     pub rt: String,
     pub interface: String,
+    #[serde(default)]
+    pub alias: String,
+    /// ice提名的地址对类型，如host/srflx
+    #[serde(default)]
+    pub ice: String,
+    /// 直连路径质量，变差时显示改走中转的原因
+    #[serde(default)]
+    pub quality: String,
+    /// e2e-p2p/e2e-relay/plaintext
+    #[serde(default)]
+    pub encryption: String,
+}
+
+/// 和设备最近一次打洞的过程，--route --verbose显示
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PunchItem {
+    pub destination: String,
+    #[serde(default)]
+    pub alias: String,
+    /// unix秒
+    pub time: u64,
+    /// local/remote
+    pub initiator: String,
+    /// 本端/对端的nat类型
+    pub nat: String,
+    /// 尝试过的地址对
+    pub candidates: Vec<String>,
+    /// 按端口预测发送的检查数量
+    pub predicted: usize,
+    /// pending/succeeded/timeout/unreachable
+    pub result: String,
+    pub detail: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EventItem {
+    /// unix秒
+    pub time: u64,
+    pub kind: String,
+    pub peer: String,
+    #[serde(default)]
+    pub alias: String,
+    pub detail: String,
+}
+
+/// 同一进程中的其他组网
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TunnelItem {
+    pub name: String,
+    pub device: String,
+    pub virtual_ip: String,
+    pub status: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -35,4 +117,17 @@ pub struct DeviceItem {
     pub status: String,
     pub client_secret: bool,
     pub current_client_secret: bool,
+    #[serde(default)]
+    pub alias: String,
+    #[serde(default)]
+    pub tags: String,
+    /// e2e-p2p/e2e-relay/plaintext
+    #[serde(default)]
+    pub encryption: String,
+    /// 和对端协商的能力，旧版本或者还没有交换时为unknown
+    #[serde(default)]
+    pub capabilities: String,
+    /// 和服务端断开时为true，设备列表是断开前的(或者缓存的)，状态可能已经变化
+    #[serde(default)]
+    pub stale: bool,
 }
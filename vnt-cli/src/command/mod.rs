@@ -1,32 +1,120 @@
-use crate::command::entity::{DeviceItem, Info, RouteItem};
+use crate::command::entity::{DeviceItem, EventItem, Info, PunchItem, RouteItem};
+use crate::alias;
 use crate::console_out;
+use crate::error::{ErrorKind, UserError};
+use crate::exit_code;
+use crate::peer_cache;
 use std::io;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use vnt::channel::path_policy::PathPolicy;
+use vnt::channel::punch_record::{PunchInitiator, PunchOutcome};
 use vnt::core::Vnt;
+use vnt::handle::ConnectStatus;
 
 pub mod client;
 pub mod entity;
 pub mod server;
+pub mod viewer;
+
+/// 命令通道，只允许本机的管理员连接
+#[cfg(windows)]
+pub const PIPE_NAME: &str = r"\\.\pipe\vnt-cli";
+/// 只读通道，本机所有用户都可以连接，命令需要带上查看者凭证
+#[cfg(windows)]
+pub const VIEWER_PIPE_NAME: &str = r"\\.\pipe\vnt-cli-viewer";
+
+/// 命名管道是全局的，指定了实例名称时加上名称
+#[cfg(windows)]
+pub fn pipe_name(name: &str) -> String {
+    match crate::instance::name() {
+        Some(instance) => format!("{}-{}", name, instance),
+        None => name.to_string(),
+    }
+}
+
+/// 命令通道，权限见server
+#[cfg(unix)]
+pub fn socket_path() -> io::Result<std::path::PathBuf> {
+    Ok(crate::app_home()?.join("command.sock"))
+}
+
+/// 只读通道，见viewer
+#[cfg(unix)]
+pub fn viewer_socket_path() -> io::Result<std::path::PathBuf> {
+    Ok(crate::app_home()?.join("command-viewer.sock"))
+}
 
 pub enum CommandEnum {
-    Route,
+    /// verbose为true时同时显示打洞过程
+    Route(bool),
     List,
     All,
     Info,
     Stop,
+    SplitAdd(String),
+    SplitDel(String),
+    SplitList,
+    /// 新密码和新旧密码共存的时长(分钟)
+    Rekey(String, u64),
+    /// 最近多少秒内的连接事件，0表示全部，json为true时输出json
+    Events(u64, bool),
+    TunnelUp(String),
+    TunnelDown(String),
+    TunnelList,
+    /// 导出会话密钥到文件，用于wireshark解密
+    ExportKeys(String),
+}
+
+/// 返回退出码，无法连接后台服务时返回NOT_RUNNING
+pub fn command(cmd: CommandEnum) -> i32 {
+    match command_(&cmd) {
+        Ok(_) => exit_code::OK,
+        Err(e) => command_error(e).report(),
+    }
 }
 
-pub fn command(cmd: CommandEnum) {
-    if let Err(e) = command_(cmd) {
-        println!("cmd: {:?}", e);
+fn command_error(e: io::Error) -> UserError {
+    let kind = if e.kind() == io::ErrorKind::PermissionDenied {
+        ErrorKind::CommandDenied
+    } else {
+        ErrorKind::Command
+    };
+    UserError::new(kind).cause(e)
+}
+
+/// 是否已有实例在运行
+pub fn is_running() -> bool {
+    client::CommandClient::new()
+        .and_then(|client| client.info())
+        .is_ok()
+}
+
+/// 按间隔持续刷新，原地重绘，出错时不退出，方便观察连接抖动
+pub fn watch(cmd: CommandEnum, interval: Duration) -> i32 {
+    let term = console::Term::stdout();
+    loop {
+        let _ = term.clear_screen();
+        println!("Every {}s, press Ctrl+C to exit", interval.as_secs());
+        println!();
+        if let Err(e) = command_(&cmd) {
+            //每次刷新都会清屏，不省略重复的错误
+            command_error(e).print(0);
+        }
+        std::thread::sleep(interval);
     }
 }
 
-fn command_(cmd: CommandEnum) -> io::Result<()> {
+fn command_(cmd: &CommandEnum) -> io::Result<()> {
     let command_client = client::CommandClient::new()?;
     match cmd {
-        CommandEnum::Route => {
+        CommandEnum::Route(verbose) => {
             let list = command_client.route()?;
             console_out::console_route_table(list);
+            if *verbose {
+                println!();
+                console_out::console_punch_table(command_client.punch_list()?, now());
+            }
         }
         CommandEnum::List => {
             let list = command_client.list()?;
@@ -43,12 +131,154 @@ fn command_(cmd: CommandEnum) -> io::Result<()> {
         CommandEnum::Stop => {
             command_client.stop()?;
         }
+        CommandEnum::SplitAdd(app) => {
+            println!("{}", command_client.split(&format!("split-add:{}", app))?);
+        }
+        CommandEnum::SplitDel(app) => {
+            println!("{}", command_client.split(&format!("split-del:{}", app))?);
+        }
+        CommandEnum::SplitList => {
+            println!("{}", command_client.split("split-list")?);
+        }
+        CommandEnum::TunnelUp(name) => {
+            println!("{}", command_client.tunnel(&format!("tunnel-up:{}", name))?);
+        }
+        CommandEnum::TunnelDown(name) => {
+            println!(
+                "{}",
+                command_client.tunnel(&format!("tunnel-down:{}", name))?
+            );
+        }
+        CommandEnum::TunnelList => {
+            let list = command_client.tunnels()?;
+            console_out::console_tunnels(list);
+        }
+        CommandEnum::Rekey(password, window) => {
+            println!("{}", command_client.rekey(password, *window)?);
+        }
+        CommandEnum::ExportKeys(path) => {
+            let keylog = command_client.export_keys()?;
+            std::fs::write(path, keylog)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+            }
+            println!("{}", path);
+        }
+        CommandEnum::Events(secs, json) => {
+            let since = if *secs == 0 {
+                0
+            } else {
+                now().saturating_sub(*secs)
+            };
+            let list = command_client.events(since)?;
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&list)?);
+            } else {
+                console_out::console_events(list, now());
+            }
+        }
     }
     Ok(())
 }
 
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|v| v.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn command_events(vnt: &Vnt, since: u64) -> Vec<EventItem> {
+    let aliases = alias::load();
+    vnt.events(since)
+        .into_iter()
+        .map(|event| EventItem {
+            time: event.time,
+            kind: event.kind.to_string(),
+            peer: event.peer.map_or(String::new(), |v| v.to_string()),
+            alias: event
+                .peer
+                .and_then(|v| alias::alias_of(&aliases, &v))
+                .unwrap_or_default(),
+            detail: event.detail,
+        })
+        .collect()
+}
+
+/// 参数格式为 分钟:新密码
+pub fn command_rekey(vnt: &Vnt, arg: &str) -> String {
+    let (window, password) = match arg.split_once(':') {
+        Some((window, password)) if !password.is_empty() => (window, password),
+        _ => return "password is empty".to_string(),
+    };
+    let window = match window.parse::<u64>() {
+        Ok(window) => Duration::from_secs(window * 60),
+        Err(e) => return format!("{}", e),
+    };
+    match vnt.rekey(password.to_string(), window) {
+        Ok(_) => "ok".to_string(),
+        Err(e) => format!("{}", e),
+    }
+}
+
+/// 格式 ip=策略:秒数，ip可以是别名，秒数为0时恢复
+pub fn command_path_policy(vnt: &Vnt, arg: &str) -> String {
+    let (ip, rest) = match arg.split_once('=') {
+        Some(v) => v,
+        None => return "invalid argument".to_string(),
+    };
+    let (policy, secs) = match rest.rsplit_once(':') {
+        Some(v) => v,
+        None => return "invalid argument".to_string(),
+    };
+    let ip = match alias::parse_peer(ip) {
+        Ok(ip) => ip,
+        Err(e) => return format!("{}", e),
+    };
+    let policy = match PathPolicy::from_str(policy) {
+        Ok(policy) => policy,
+        Err(e) => return e,
+    };
+    let duration = match secs.parse::<u64>() {
+        Ok(0) => None,
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(e) => return format!("{}", e),
+    };
+    vnt.set_path_policy(ip, policy, duration);
+    "ok".to_string()
+}
+
+pub fn command_split_list(vnt: &Vnt) -> String {
+    match vnt.split_tunnel_apps() {
+        None => "split tunnel not enabled".to_string(),
+        Some((mode, apps)) => {
+            let mut out = format!("mode: {:?}\n", mode);
+            for app in apps {
+                out.push_str(&app);
+                out.push('\n');
+            }
+            out
+        }
+    }
+}
+
+/// 双方都设置了密码时数据只有两端能解密，经服务端或其他设备中转也只能看到密文
+fn encryption(encrypted: bool, p2p: bool) -> String {
+    match (encrypted, p2p) {
+        (false, _) => "plaintext",
+        (true, true) => "e2e-p2p",
+        (true, false) => "e2e-relay",
+    }
+    .to_string()
+}
+
 pub fn command_route(vnt: &Vnt) -> Vec<RouteItem> {
     let route_table = vnt.route_table();
+    let device_list = vnt.device_list();
+    let client_encrypt = vnt.client_encrypt();
+    let aliases = alias::load();
     let mut route_list = Vec::with_capacity(route_table.len());
     for (destination, route) in route_table {
         let next_hop = vnt
@@ -61,24 +291,81 @@ pub fn command_route(vnt: &Vnt) -> Vec<RouteItem> {
             route.rt.to_string()
         };
         let interface = route.addr.to_string();
+        let alias = alias::alias_of(&aliases, &destination).unwrap_or_default();
+        let ice = route.ice.map_or(String::new(), |pair| {
+            format!("{}/{}", pair.local, pair.remote.typ)
+        });
         let item = RouteItem {
             destination: destination.to_string(),
             next_hop,
             metric,
             rt,
             interface,
+            alias,
+            ice,
+            quality: if route.is_p2p() {
+                route.quality.to_string()
+            } else {
+                String::new()
+            },
+            encryption: encryption(
+                client_encrypt
+                    && device_list
+                        .iter()
+                        .any(|v| v.virtual_ip == destination && v.client_secret),
+                route.is_p2p(),
+            ),
         };
         route_list.push(item);
     }
     route_list
 }
 
+pub fn command_punch(vnt: &Vnt) -> Vec<PunchItem> {
+    let aliases = alias::load();
+    vnt.punch_records()
+        .into_iter()
+        .map(|(destination, record)| {
+            let (result, detail) = match record.outcome {
+                PunchOutcome::Pending => ("pending", String::new()),
+                PunchOutcome::Succeeded(pair) => ("succeeded", pair),
+                PunchOutcome::Timeout => ("timeout", String::new()),
+                PunchOutcome::Unreachable(reason) => ("unreachable", reason),
+            };
+            PunchItem {
+                destination: destination.to_string(),
+                alias: alias::alias_of(&aliases, &destination).unwrap_or_default(),
+                time: record.time / 1000,
+                initiator: match record.initiator {
+                    PunchInitiator::Local => "local",
+                    PunchInitiator::Remote => "remote",
+                }
+                .to_string(),
+                nat: format!("{:?}/{:?}", record.local_nat, record.peer_nat),
+                candidates: record.candidates,
+                predicted: record.predicted,
+                result: result.to_string(),
+                detail,
+            }
+        })
+        .collect()
+}
+
 pub fn command_list(vnt: &Vnt) -> Vec<DeviceItem> {
     let info = vnt.current_device();
-    let device_list = vnt.device_list();
+    let stale = vnt.connection_status() != ConnectStatus::Connected;
+    let mut device_list = vnt.device_list();
+    if device_list.is_empty() && stale {
+        //服务端不可用时使用缓存的设备列表，至少能看到直连的设备
+        if let Some((_, cached)) = peer_cache::load_directory() {
+            device_list = cached;
+        }
+    }
     let mut list = Vec::new();
     let current_client_secret = vnt.client_encrypt();
+    let aliases = alias::load();
     for peer in device_list {
+        let alias = alias::alias_of(&aliases, &peer.virtual_ip).unwrap_or_default();
         let name = peer.name;
         let virtual_ip = peer.virtual_ip.to_string();
         let (nat_type, public_ips, local_ip, ipv6) =
@@ -126,8 +413,17 @@ pub fn command_list(vnt: &Vnt) -> Vec<DeviceItem> {
         } else {
             ("relay".to_string(), "".to_string())
         };
+        let encryption = encryption(
+            current_client_secret && peer.client_secret,
+            nat_traversal_type.contains("p2p"),
+        );
         let status = format!("{:?}", peer.status);
         let client_secret = peer.client_secret;
+        let tags = peer.tags.join(",");
+        let capabilities = vnt
+            .peer_capabilities(&peer.virtual_ip)
+            .map(|v| v.to_string())
+            .unwrap_or("unknown".to_string());
         let item = DeviceItem {
             name,
             virtual_ip,
@@ -140,6 +436,11 @@ pub fn command_list(vnt: &Vnt) -> Vec<DeviceItem> {
             status,
             client_secret,
             current_client_secret,
+            alias,
+            tags,
+            encryption,
+            capabilities,
+            stale,
         };
         list.push(item);
     }
@@ -152,7 +453,11 @@ pub fn command_info(vnt: &Vnt) -> Info {
     let name = vnt.name().to_string();
     let virtual_ip = current_device.virtual_ip().to_string();
     let virtual_gateway = current_device.virtual_gateway().to_string();
-    let virtual_netmask = current_device.virtual_netmask.to_string();
+    let virtual_netmask = format!(
+        "{}(/{})",
+        current_device.virtual_netmask,
+        current_device.prefix_len()
+    );
     let connect_status = format!("{:?}", vnt.connection_status());
     let relay_server = current_device.connect_server.to_string();
     let nat_type = format!("{:?}", nat_info.nat_type);
@@ -166,6 +471,106 @@ pub fn command_info(vnt: &Vnt) -> Info {
         .ipv6()
         .map(|v| v.to_string())
         .unwrap_or("None".to_string());
+    let remote_config = vnt
+        .remote_config()
+        .map(|v| format!("version {}", v.version))
+        .unwrap_or("None".to_string());
+    let quota = vnt
+        .quota()
+        .map(|v| v.to_string())
+        .unwrap_or("None".to_string());
+    let (total, failures) = vnt.auth_failures();
+    let quarantined: Vec<String> = failures
+        .iter()
+        .filter_map(|v| {
+            v.remaining
+                .map(|remaining| format!("{}({}s)", v.addr, remaining.as_secs()))
+        })
+        .collect();
+    let auth_failures = if quarantined.is_empty() {
+        total.to_string()
+    } else {
+        format!("{}, quarantined {}", total, quarantined.join(","))
+    };
+    let malformed: Vec<String> = vnt
+        .malformed()
+        .iter()
+        .map(|(reason, count)| format!("{} {}", reason, count))
+        .collect();
+    let malformed = malformed.join(", ");
+    let stats = vnt.broadcast_stats();
+    let mut broadcast = format!(
+        "{}, sent {}, dropped {}",
+        stats.policy, stats.sent, stats.dropped
+    );
+    if !stats.received.is_empty() {
+        let top: Vec<String> = stats
+            .received
+            .iter()
+            .take(3)
+            .map(|(ip, count)| format!("{}({})", ip, count))
+            .collect();
+        broadcast.push_str(&format!(", top {}", top.join(",")));
+    }
+    let (threshold, skews) = vnt.clock_skew();
+    let clock_skew = match skews.first() {
+        None => "None".to_string(),
+        Some((ip, skew)) => {
+            let mut clock_skew = format!("max {:+.1}s({})", *skew as f64 / 1000.0, ip);
+            if let Some(threshold) = threshold {
+                if skew.unsigned_abs() > threshold.as_millis() as u64 {
+                    clock_skew.push_str(&format!(", exceeds {}s", threshold.as_secs()));
+                }
+            }
+            clock_skew
+        }
+    };
+    let self_test = match vnt.self_test() {
+        None => String::new(),
+        Some(list) if list.is_empty() => "pending".to_string(),
+        Some(list) => list
+            .iter()
+            .map(|(ip, status)| {
+                if status.alerted {
+                    format!("{} failing({})", ip, status.failures)
+                } else if status.failures > 0 {
+                    format!("{} failed({})", ip, status.failures)
+                } else {
+                    format!("{} ok {}ms", ip, status.rtt)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(", "),
+    };
+    let usage = vnt.memory_usage();
+    let tables: Vec<String> = usage
+        .tables
+        .iter()
+        .filter(|(_, len, _)| *len > 0)
+        .map(|(name, len, _)| format!("{} {}", name, len))
+        .collect();
+    let mut memory = format!("~{}KB", (usage.bytes() + 1023) / 1024);
+    if !tables.is_empty() {
+        memory.push_str(&format!(", {}", tables.join(",")));
+    }
+    if usage.evicted > 0 {
+        memory.push_str(&format!(", evicted {}", usage.evicted));
+    }
+    let server_lost = match vnt.server_lost() {
+        None => String::new(),
+        Some(lost) => match lost.remaining {
+            None => format!("{}s, direct peers kept", lost.elapsed.as_secs()),
+            Some(remaining) if remaining.is_zero() => {
+                format!("{}s, grace expired", lost.elapsed.as_secs())
+            }
+            Some(remaining) => format!(
+                "{}s, direct peers kept for {}s",
+                lost.elapsed.as_secs(),
+                remaining.as_secs()
+            ),
+        },
+    };
+    let maintenance = vnt.maintenance().map(|v| v.to_string()).unwrap_or_default();
     Info {
         name,
         virtual_ip,
@@ -177,5 +582,15 @@ pub fn command_info(vnt: &Vnt) -> Info {
         public_ips,
         local_addr,
         ipv6_addr,
+        remote_config,
+        quota,
+        auth_failures,
+        malformed,
+        broadcast,
+        clock_skew,
+        self_test,
+        memory,
+        server_lost,
+        maintenance,
     }
 }
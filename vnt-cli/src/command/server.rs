@@ -1,9 +1,13 @@
 use std::io;
-use std::io::Write;
-use tokio::net::UdpSocket;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
 
 use vnt::core::Vnt;
 
+/// 读取命令的超时时间，避免一个客户端卡住命令通道
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub struct CommandServer {}
 
 impl CommandServer {
@@ -12,44 +16,300 @@ impl CommandServer {
     }
 }
 
+#[cfg(unix)]
 impl CommandServer {
     pub async fn start(self, vnt: Vnt) -> io::Result<()> {
-        let udp = if let Ok(udp) = UdpSocket::bind("127.0.0.1:39271").await {
-            udp
+        use std::os::unix::fs::PermissionsExt;
+        let path = super::socket_path()?;
+        //上一次异常退出残留的socket文件
+        let _ = std::fs::remove_file(&path);
+        let listener = tokio::net::UnixListener::bind(&path)?;
+        let group = match std::env::var("VNT_CMD_GROUP") {
+            Ok(name) if !name.is_empty() => Some(unix_auth::Group::find(&name)?),
+            _ => None,
+        };
+        //只有root、运行用户和允许的组可以连接
+        let mode = if let Some(group) = &group {
+            unix_auth::chown_group(&path, group.gid)?;
+            0o660
         } else {
-            UdpSocket::bind("127.0.0.1:0").await?
+            0o600
         };
-        let path_buf = crate::app_home()?.join("command-port");
-        let mut file = std::fs::File::create(path_buf)?;
-        let addr = udp.local_addr()?;
-        file.write_all(addr.port().to_string().as_bytes())?;
-        file.sync_all()?;
-        log::info!("启动后台cmd:{:?}", addr);
-        let mut buf = [0u8; 64];
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))?;
+        log::info!("启动后台cmd:{:?}", path);
+        tokio::spawn(start_viewer(vnt.clone()));
         loop {
-            let (len, addr) = udp.recv_from(&mut buf).await?;
-            match std::str::from_utf8(&buf[..len]) {
-                Ok(cmd) => {
-                    log::info!("收到cmd={:?}", cmd);
-                    if let Ok(out) = command(cmd, &vnt) {
-                        if let Err(e) = udp.send_to(out.as_bytes(), addr).await {
-                            log::warn!("cmd={},err={:?}", cmd, e);
-                        }
-                        if "stopped" == &out {
-                            break;
-                        }
-                    }
+            let (stream, _) = listener.accept().await?;
+            match stream.peer_cred() {
+                Ok(cred) if unix_auth::allowed(cred.uid(), cred.gid(), group.as_ref()) => {}
+                Ok(cred) => {
+                    log::warn!("拒绝cmd连接 uid={},gid={}", cred.uid(), cred.gid());
+                    continue;
                 }
                 Err(e) => {
-                    log::warn!("{:?}", e);
+                    log::warn!("cmd peer_cred {:?}", e);
+                    continue;
+                }
+            }
+            match handle(stream, &vnt, false).await {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(e) => log::warn!("cmd {:?}", e),
+            }
+        }
+        let _ = std::fs::remove_file(&path);
+        if let Ok(path) = super::viewer_socket_path() {
+            let _ = std::fs::remove_file(path);
+        }
+        Ok(())
+    }
+}
+
+/// 只读通道，所有用户都可以连接，命令中的凭证校验通过后只执行查询
+#[cfg(unix)]
+async fn start_viewer(vnt: Vnt) {
+    use std::os::unix::fs::PermissionsExt;
+    let path = match super::viewer_socket_path() {
+        Ok(path) => path,
+        Err(e) => {
+            log::warn!("viewer cmd {:?}", e);
+            return;
+        }
+    };
+    let _ = std::fs::remove_file(&path);
+    let listener = match tokio::net::UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::warn!("viewer cmd {:?} {:?}", path, e);
+            return;
+        }
+    };
+    if let Err(e) = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o666)) {
+        log::warn!("viewer cmd {:?} {:?}", path, e);
+        return;
+    }
+    log::info!("启动只读cmd:{:?}", path);
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                if let Err(e) = handle(stream, &vnt, true).await {
+                    log::warn!("viewer cmd {:?}", e);
                 }
             }
+            Err(e) => {
+                log::warn!("viewer cmd {:?}", e);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+impl CommandServer {
+    pub async fn start(self, vnt: Vnt) -> io::Result<()> {
+        use tokio::net::windows::named_pipe::ServerOptions;
+        let pipe_name = super::pipe_name(super::PIPE_NAME);
+        //默认的安全描述符只允许管理员和SYSTEM写入，first_pipe_instance避免管道名被抢占
+        let mut server = ServerOptions::new()
+            .first_pipe_instance(true)
+            .reject_remote_clients(true)
+            .create(&pipe_name)?;
+        log::info!("启动后台cmd:{}", pipe_name);
+        tokio::spawn(start_viewer(vnt.clone()));
+        loop {
+            server.connect().await?;
+            let connected = server;
+            server = ServerOptions::new()
+                .reject_remote_clients(true)
+                .create(&pipe_name)?;
+            match handle(connected, &vnt, false).await {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(e) => log::warn!("cmd {:?}", e),
+            }
         }
         Ok(())
     }
 }
 
-fn command(cmd: &str, vnt: &Vnt) -> io::Result<String> {
+/// 只读通道，允许本机登录的用户连接，命令中的凭证校验通过后只执行查询
+#[cfg(windows)]
+async fn start_viewer(vnt: Vnt) {
+    if let Err(e) = viewer_loop(vnt).await {
+        log::warn!("viewer cmd {:?}", e);
+    }
+}
+
+#[cfg(windows)]
+async fn viewer_loop(vnt: Vnt) -> io::Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+    let pipe_name = super::pipe_name(super::VIEWER_PIPE_NAME);
+    let mut security = viewer_security()?;
+    let create = |first: bool, security: &mut winapi::um::minwinbase::SECURITY_ATTRIBUTES| unsafe {
+        ServerOptions::new()
+            .first_pipe_instance(first)
+            .reject_remote_clients(true)
+            .create_with_security_attributes_raw(
+                &pipe_name,
+                security as *mut _ as *mut std::ffi::c_void,
+            )
+    };
+    let mut server = create(true, &mut security)?;
+    log::info!("启动只读cmd:{}", pipe_name);
+    loop {
+        server.connect().await?;
+        let connected = server;
+        server = create(false, &mut security)?;
+        if let Err(e) = handle(connected, &vnt, true).await {
+            log::warn!("viewer cmd {:?}", e);
+        }
+    }
+}
+
+/// SYSTEM和管理员完全控制，登录用户可以读写(连接)，安全描述符随进程存在，不释放
+#[cfg(windows)]
+fn viewer_security() -> io::Result<winapi::um::minwinbase::SECURITY_ATTRIBUTES> {
+    use winapi::shared::sddl::{
+        ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1,
+    };
+    let sddl: Vec<u16> = "D:(A;;GA;;;SY)(A;;GA;;;BA)(A;;GRGW;;;AU)"
+        .encode_utf16()
+        .chain(Some(0))
+        .collect();
+    let mut descriptor = std::ptr::null_mut();
+    let ok = unsafe {
+        ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            sddl.as_ptr(),
+            SDDL_REVISION_1 as u32,
+            &mut descriptor,
+            std::ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(winapi::um::minwinbase::SECURITY_ATTRIBUTES {
+        nLength: std::mem::size_of::<winapi::um::minwinbase::SECURITY_ATTRIBUTES>() as u32,
+        lpSecurityDescriptor: descriptor,
+        bInheritHandle: 0,
+    })
+}
+
+/// 一次连接处理一条命令，命令以换行结尾，返回是否已停止；
+/// 只读通道的命令格式为`<凭证> <命令>`
+async fn handle<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
+    vnt: &Vnt,
+    viewer: bool,
+) -> io::Result<bool> {
+    let mut reader = BufReader::new(stream);
+    let mut cmd = String::new();
+    tokio::time::timeout(READ_TIMEOUT, reader.read_line(&mut cmd))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "read timeout"))??;
+    let cmd = cmd.trim();
+    let out = if viewer {
+        let (token, cmd) = cmd.split_once(' ').unwrap_or((cmd, ""));
+        match super::viewer::check(token) {
+            Some(name) if super::viewer::allowed(cmd) => {
+                log::info!("收到只读cmd={:?},viewer={}", cmd, name);
+                command(cmd, vnt).await?
+            }
+            Some(name) => {
+                log::warn!("拒绝只读cmd={:?},viewer={}", cmd, name);
+                "permission denied: viewer is read-only".to_string()
+            }
+            None => {
+                log::warn!("拒绝只读cmd,凭证无效");
+                "permission denied: invalid viewer token".to_string()
+            }
+        }
+    } else {
+        log::info!("收到cmd={:?}", cmd);
+        command(cmd, vnt).await?
+    };
+    let mut stream = reader.into_inner();
+    stream.write_all(out.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(!viewer && "stopped" == out)
+}
+
+#[cfg(unix)]
+mod unix_auth {
+    use std::ffi::{CStr, CString};
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    pub struct Group {
+        pub gid: u32,
+        members: Vec<String>,
+    }
+
+    impl Group {
+        pub fn find(name: &str) -> io::Result<Group> {
+            let c_name =
+                CString::new(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            unsafe {
+                let group = libc::getgrnam(c_name.as_ptr());
+                if group.is_null() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("group '{}' not found", name),
+                    ));
+                }
+                let mut members = Vec::new();
+                let mut mem = (*group).gr_mem;
+                while !mem.is_null() && !(*mem).is_null() {
+                    members.push(CStr::from_ptr(*mem).to_string_lossy().to_string());
+                    mem = mem.add(1);
+                }
+                Ok(Group {
+                    gid: (*group).gr_gid,
+                    members,
+                })
+            }
+        }
+    }
+
+    fn user_name(uid: u32) -> Option<String> {
+        unsafe {
+            let passwd = libc::getpwuid(uid);
+            if passwd.is_null() {
+                return None;
+            }
+            Some(
+                CStr::from_ptr((*passwd).pw_name)
+                    .to_string_lossy()
+                    .to_string(),
+            )
+        }
+    }
+
+    pub fn allowed(uid: u32, gid: u32, group: Option<&Group>) -> bool {
+        if uid == 0 || uid == unsafe { libc::geteuid() } {
+            return true;
+        }
+        match group {
+            None => false,
+            Some(group) => {
+                gid == group.gid
+                    || user_name(uid).map_or(false, |name| group.members.contains(&name))
+            }
+        }
+    }
+
+    pub fn chown_group(path: &Path, gid: u32) -> io::Result<()> {
+        let c_path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        if unsafe { libc::chown(c_path.as_ptr(), u32::MAX, gid) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+async fn command(cmd: &str, vnt: &Vnt) -> io::Result<String> {
     let out_str = match cmd {
         "route" => match serde_json::to_string(&crate::command::command_route(vnt)) {
             Ok(str) => str,
@@ -57,6 +317,12 @@ fn command(cmd: &str, vnt: &Vnt) -> io::Result<String> {
                 format!("{:?}", e)
             }
         },
+        "punch-list" => match serde_json::to_string(&crate::command::command_punch(vnt)) {
+            Ok(str) => str,
+            Err(e) => {
+                format!("{:?}", e)
+            }
+        },
         "list" => match serde_json::to_string(&crate::command::command_list(vnt)) {
             Ok(str) => str,
             Err(e) => {
@@ -70,9 +336,61 @@ fn command(cmd: &str, vnt: &Vnt) -> io::Result<String> {
             }
         },
         "stop" => {
+            crate::tunnels::stop_all();
+            let _ = crate::peer_cache::save(vnt);
             vnt.stop()?;
             "stopped".to_string()
         }
+        "split-list" => crate::command::command_split_list(vnt),
+        "export-keys" => match vnt.export_keys() {
+            Ok(keylog) => keylog,
+            Err(e) => format!("{}", e),
+        },
+        "tunnel-list" => match serde_json::to_string(&crate::tunnels::list()) {
+            Ok(str) => str,
+            Err(e) => {
+                format!("{:?}", e)
+            }
+        },
+        _ if cmd.starts_with("events:") => {
+            let since = cmd["events:".len()..].parse::<u64>().unwrap_or(0);
+            match serde_json::to_string(&crate::command::command_events(vnt, since)) {
+                Ok(str) => str,
+                Err(e) => {
+                    format!("{:?}", e)
+                }
+            }
+        }
+        _ if cmd.starts_with("split-add:") => {
+            match vnt.split_tunnel_add(&cmd["split-add:".len()..]) {
+                Ok(_) => "ok".to_string(),
+                Err(e) => format!("{}", e),
+            }
+        }
+        _ if cmd.starts_with("split-del:") => {
+            match vnt.split_tunnel_remove(&cmd["split-del:".len()..]) {
+                Ok(_) => "ok".to_string(),
+                Err(e) => format!("{}", e),
+            }
+        }
+        _ if cmd.starts_with("rekey:") => {
+            crate::command::command_rekey(vnt, &cmd["rekey:".len()..])
+        }
+        _ if cmd.starts_with("tunnel-up:") => {
+            match crate::tunnels::up(&cmd["tunnel-up:".len()..]).await {
+                Ok(str) => str,
+                Err(e) => format!("{}", e),
+            }
+        }
+        _ if cmd.starts_with("tunnel-down:") => {
+            match crate::tunnels::down(&cmd["tunnel-down:".len()..]).await {
+                Ok(str) => str,
+                Err(e) => format!("{}", e),
+            }
+        }
+        _ if cmd.starts_with("path:") => {
+            crate::command::command_path_policy(vnt, &cmd["path:".len()..])
+        }
         _ => {
             format!("command '{}' not found. \n Try to enter: 'help'\n", cmd)
         }
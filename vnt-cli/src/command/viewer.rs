@@ -0,0 +1,99 @@
+//! 只读的查看者凭证，持有凭证的用户可以查询状态(info、list、route等)，不能停止、修改分流或更换密码，
+//! 方便监控面板和非管理员用户观察运行状态
+//!
+//! 凭证文件在程序目录下的env/viewer-tokens，每行一个`<名称> <哈希>`，只保存哈希，
+//! 每次连接时重新读取，吊销后立即生效
+
+use std::io;
+use std::path::PathBuf;
+
+use rand::Rng;
+
+use crate::remote::{hex, hmac, verify};
+
+/// 客户端从环境变量读取凭证，设置后使用只读通道
+pub const TOKEN_ENV: &str = "VNT_VIEWER_TOKEN";
+
+fn tokens_path() -> io::Result<PathBuf> {
+    Ok(crate::app_home()?.join("viewer-tokens"))
+}
+
+fn token_hash(token: &str) -> [u8; 32] {
+    hmac(b"vnt-viewer", &[token.trim().as_bytes()])
+}
+
+fn load() -> io::Result<Vec<(String, String)>> {
+    let content = match std::fs::read_to_string(tokens_path()?) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(content
+        .lines()
+        .filter_map(|line| {
+            let (name, hash) = line.trim().split_once(' ')?;
+            Some((name.to_string(), hash.trim().to_string()))
+        })
+        .collect())
+}
+
+fn save(tokens: &[(String, String)]) -> io::Result<()> {
+    let path = tokens_path()?;
+    let content: String = tokens
+        .iter()
+        .map(|(name, hash)| format!("{} {}\n", name, hash))
+        .collect();
+    std::fs::write(&path, content)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+/// 生成新凭证，同名的旧凭证失效，返回的凭证只显示这一次
+pub fn issue(name: &str) -> io::Result<String> {
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "name must not be empty or contain spaces",
+        ));
+    }
+    let token = hex(&rand::thread_rng().gen::<[u8; 16]>());
+    let mut tokens = load()?;
+    tokens.retain(|(v, _)| v != name);
+    tokens.push((name.to_string(), hex(&token_hash(&token))));
+    save(&tokens)?;
+    Ok(token)
+}
+
+/// 吊销凭证，返回是否存在
+pub fn revoke(name: &str) -> io::Result<bool> {
+    let mut tokens = load()?;
+    let len = tokens.len();
+    tokens.retain(|(v, _)| v != name);
+    if tokens.len() == len {
+        return Ok(false);
+    }
+    save(&tokens)?;
+    Ok(true)
+}
+
+/// 校验凭证，返回凭证名称
+pub fn check(token: &str) -> Option<String> {
+    let expected = token_hash(token);
+    load()
+        .ok()?
+        .into_iter()
+        .find(|(_, hash)| verify(hash, &expected))
+        .map(|(name, _)| name)
+}
+
+/// 查看者只能执行的命令
+pub fn allowed(cmd: &str) -> bool {
+    matches!(
+        cmd,
+        "route" | "punch-list" | "list" | "info" | "split-list" | "tunnel-list"
+    ) || cmd.starts_with("events:")
+}
@@ -0,0 +1,175 @@
+use getopts::Options;
+
+pub const SHELLS: [&str; 4] = ["bash", "zsh", "fish", "powershell"];
+
+struct Opt {
+    short: Option<String>,
+    long: Option<String>,
+    has_arg: bool,
+    desc: String,
+}
+
+/// 从getopts的帮助信息中解析出参数定义，保证补全和实际参数一致
+fn parse(opts: &Options) -> Vec<Opt> {
+    let usage = opts.usage("");
+    let mut list = Vec::new();
+    for line in usage.lines() {
+        let line = line.trim();
+        if !line.starts_with('-') {
+            continue;
+        }
+        let mut opt = Opt {
+            short: None,
+            long: None,
+            has_arg: false,
+            desc: String::new(),
+        };
+        let mut rest = line;
+        loop {
+            let (token, tail) = rest.split_once(' ').unwrap_or((rest, ""));
+            let token = token.trim_end_matches(',');
+            if let Some(long) = token.strip_prefix("--") {
+                opt.long = Some(long.to_string());
+            } else if let Some(short) = token.strip_prefix('-') {
+                opt.short = Some(short.to_string());
+            } else if token.starts_with('<') || token.starts_with('[') {
+                opt.has_arg = true;
+            } else {
+                break;
+            }
+            rest = tail.trim_start();
+        }
+        opt.desc = rest.trim().replace('\'', "");
+        list.push(opt);
+    }
+    list
+}
+
+fn words(list: &[Opt]) -> String {
+    let mut words = Vec::new();
+    for opt in list {
+        if let Some(short) = &opt.short {
+            words.push(format!("-{}", short));
+        }
+        if let Some(long) = &opt.long {
+            words.push(format!("--{}", long));
+        }
+    }
+    words.join(" ")
+}
+
+/// 生成补全脚本，别名通过--complete-aliases动态获取
+pub fn generate(shell: &str, program: &str, opts: &Options) -> Option<String> {
+    let list = parse(opts);
+    let func = format!("_{}", program.replace('-', "_"));
+    let script = match shell {
+        "bash" => bash(program, &func, &list),
+        "zsh" => format!(
+            "#compdef {program}\nautoload -U +X bashcompinit && bashcompinit\n{}",
+            bash(program, &func, &list),
+            program = program
+        ),
+        "fish" => fish(program, &list),
+        "powershell" => powershell(program, &list),
+        _ => return None,
+    };
+    Some(script)
+}
+
+fn bash(program: &str, func: &str, list: &[Opt]) -> String {
+    format!(
+        r#"{func}() {{
+    local cur prev
+    cur="${{COMP_WORDS[COMP_CWORD]}}"
+    prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    case "$prev" in
+        --alias-del)
+            COMPREPLY=($(compgen -W "$({program} --complete-aliases 2>/dev/null)" -- "$cur"))
+            return 0
+            ;;
+        -f)
+            COMPREPLY=($(compgen -f -- "$cur"))
+            return 0
+            ;;
+        --completions)
+            COMPREPLY=($(compgen -W "{shells}" -- "$cur"))
+            return 0
+            ;;
+        --punch)
+            COMPREPLY=($(compgen -W "ipv4 ipv6" -- "$cur"))
+            return 0
+            ;;
+    esac
+    COMPREPLY=($(compgen -W "{words}" -- "$cur"))
+}}
+complete -F {func} {program}
+"#,
+        func = func,
+        program = program,
+        shells = SHELLS.join(" "),
+        words = words(list)
+    )
+}
+
+fn fish(program: &str, list: &[Opt]) -> String {
+    let mut script = String::new();
+    for opt in list {
+        let mut line = format!("complete -c {}", program);
+        if let Some(short) = &opt.short {
+            line.push_str(&format!(" -s {}", short));
+        }
+        if let Some(long) = &opt.long {
+            line.push_str(&format!(" -l {}", long));
+        }
+        match opt.long.as_deref() {
+            Some("alias-del") => {
+                line.push_str(&format!(" -x -a '({} --complete-aliases)'", program));
+            }
+            Some("completions") => {
+                line.push_str(&format!(" -x -a '{}'", SHELLS.join(" ")));
+            }
+            Some("punch") => line.push_str(" -x -a 'ipv4 ipv6'"),
+            _ => {
+                if opt.short.as_deref() == Some("f") {
+                    line.push_str(" -r -F");
+                } else if opt.has_arg {
+                    line.push_str(" -x");
+                }
+            }
+        }
+        line.push_str(&format!(" -d '{}'\n", opt.desc));
+        script.push_str(&line);
+    }
+    script
+}
+
+fn powershell(program: &str, list: &[Opt]) -> String {
+    let words: Vec<String> = words(list)
+        .split(' ')
+        .map(|v| format!("'{}'", v))
+        .collect();
+    format!(
+        r#"Register-ArgumentCompleter -Native -CommandName '{program}' -ScriptBlock {{
+    param($wordToComplete, $commandAst, $cursorPosition)
+    $elements = $commandAst.CommandElements | ForEach-Object {{ $_.ToString() }}
+    $prev = if ($wordToComplete) {{ $elements[-2] }} else {{ $elements[-1] }}
+    $candidates = switch ($prev) {{
+        '--alias-del' {{ & '{program}' --complete-aliases 2>$null }}
+        '--completions' {{ {shells} }}
+        '--punch' {{ 'ipv4', 'ipv6' }}
+        default {{ {words} }}
+    }}
+    $candidates | Where-Object {{ $_ -like "$wordToComplete*" }} | ForEach-Object {{
+        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)
+    }}
+}}
+"#,
+        program = program,
+        shells = SHELLS
+            .iter()
+            .map(|v| format!("'{}'", v))
+            .collect::<Vec<_>>()
+            .join(", "),
+        words = words.join(", ")
+    )
+}
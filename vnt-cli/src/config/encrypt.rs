@@ -0,0 +1,359 @@
+//! 配置文件加密，token、password、mgmt_key等都在配置文件中，整个文件一起加密
+//!
+//! 文件格式(文本)：
+//! ```text
+//! vnt-config-encrypted: v2
+//! kdf: pbkdf2-sha256,<迭代次数>,<盐> 或者 keystore,<系统密钥库中的名称>
+//! nonce: <随机数(12字节)>
+//! data: <密文，末尾16字节为认证标签>
+//! ```
+//! 密钥由口令经PBKDF2-HMAC-SHA256派生，或者是保存在系统密钥库中的随机数；
+//! 使用ChaCha20-Poly1305加密，kdf一行作为附加数据参与认证，解密只在内存中进行
+
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use parking_lot::Mutex;
+
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use rand::Rng;
+
+use crate::config::keystore;
+use crate::remote::{hex, hmac};
+
+const MAGIC_PREFIX: &str = "vnt-config-encrypted:";
+const MAGIC: &str = "vnt-config-encrypted: v2";
+const NONCE_LEN: usize = 12;
+const ITERATIONS: u32 = 200_000;
+/// 迭代次数上限，被修改的kdf不能让启动长时间卡住
+const MAX_ITERATIONS: u32 = 10 * ITERATIONS;
+/// 从环境变量读取口令，非交互运行(服务)时使用，读取后从环境变量中删除
+pub const PASSPHRASE_ENV: &str = "VNT_CONFIG_PASSPHRASE";
+/// --daemon的子进程从这个管道读取口令，环境变量中只有文件描述符的编号
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const PASSPHRASE_FD_ENV: &str = "VNT_CONFIG_PASSPHRASE_FD";
+
+/// 解密成功的口令只保存在内存中，避免出现在/proc/<pid>/environ以及被执行的命令继承，
+/// 组网配置(profile)和--daemon的子进程从这里取得
+static PASSPHRASE: Mutex<Option<String>> = parking_lot::const_mutex(None);
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn unhex(s: &str) -> io::Result<Vec<u8>> {
+    if s.len() % 2 != 0 || !s.is_ascii() {
+        return Err(invalid("invalid hex"));
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| invalid("invalid hex")))
+        .collect()
+}
+
+/// PBKDF2-HMAC-SHA256，只取第一块(32字节)
+fn pbkdf2(passphrase: &[u8], salt: &[u8], iterations: u32) -> [u8; 32] {
+    let mut u = hmac(passphrase, &[salt, &1u32.to_be_bytes()]);
+    let mut out = u;
+    for _ in 1..iterations {
+        u = hmac(passphrase, &[&u]);
+        for (o, v) in out.iter_mut().zip(u.iter()) {
+            *o ^= v;
+        }
+    }
+    out
+}
+
+pub fn is_encrypted(content: &str) -> bool {
+    content.trim_start().starts_with(MAGIC_PREFIX)
+}
+
+/// 读取配置文件，加密过的在内存中解密，interactive为false时不在终端输入口令
+pub fn read(path: &str, interactive: bool) -> io::Result<String> {
+    let content = std::fs::read_to_string(path)?;
+    if is_encrypted(&content) {
+        decrypt(&content, interactive)
+    } else {
+        Ok(content)
+    }
+}
+
+fn encrypt(plain: &str, kdf: &str, master: &[u8; 32]) -> io::Result<String> {
+    let nonce: [u8; NONCE_LEN] = rand::thread_rng().gen();
+    let payload = Payload {
+        msg: plain.as_bytes(),
+        aad: kdf.as_bytes(),
+    };
+    let data = ChaCha20Poly1305::new(Key::from_slice(master))
+        .encrypt(Nonce::from_slice(&nonce), payload)
+        .map_err(|_| invalid("encrypt config failed"))?;
+    Ok(format!(
+        "{}\nkdf: {}\nnonce: {}\ndata: {}\n",
+        MAGIC,
+        kdf,
+        hex(&nonce),
+        hex(&data)
+    ))
+}
+
+/// 文件中的kdf、随机数和密文
+fn fields(content: &str) -> io::Result<(&str, Vec<u8>, Vec<u8>)> {
+    if content.trim_start().lines().next().map(|v| v.trim()) != Some(MAGIC) {
+        return Err(invalid("unsupported encrypted config version"));
+    }
+    let field = |name: &str| -> io::Result<&str> {
+        content
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .map(|v| v.trim())
+            .ok_or_else(|| invalid(&format!("encrypted config missing {}", name)))
+    };
+    let kdf = field("kdf")?;
+    let nonce = unhex(field("nonce")?)?;
+    if nonce.len() != NONCE_LEN {
+        return Err(invalid("invalid nonce"));
+    }
+    Ok((kdf, nonce, unhex(field("data")?)?))
+}
+
+fn open(kdf: &str, nonce: &[u8], data: &[u8], master: &[u8; 32]) -> io::Result<String> {
+    let payload = Payload {
+        msg: data,
+        aad: kdf.as_bytes(),
+    };
+    let plain = ChaCha20Poly1305::new(Key::from_slice(master))
+        .decrypt(Nonce::from_slice(nonce), payload)
+        .map_err(|_| invalid("wrong passphrase or corrupted config"))?;
+    String::from_utf8(plain).map_err(|_| invalid("config is not utf-8"))
+}
+
+fn decrypt(content: &str, interactive: bool) -> io::Result<String> {
+    let (kdf, nonce, data) = fields(content)?;
+    let (master, passphrase) = master_key(kdf, interactive)?;
+    let plain = open(kdf, &nonce, &data, &master)?;
+    // 解密成功才记住口令，输错的不会影响之后的读取
+    if passphrase.is_some() {
+        *PASSPHRASE.lock() = passphrase;
+    }
+    Ok(plain)
+}
+
+/// 返回密钥以及派生密钥使用的口令
+fn master_key(kdf: &str, interactive: bool) -> io::Result<([u8; 32], Option<String>)> {
+    let mut parts = kdf.split(',');
+    match parts.next() {
+        Some("pbkdf2-sha256") => {
+            let iterations = parts
+                .next()
+                .and_then(|v| v.parse::<u32>().ok())
+                .filter(|v| *v > 0 && *v <= MAX_ITERATIONS)
+                .ok_or_else(|| invalid("invalid kdf"))?;
+            let salt = unhex(parts.next().ok_or_else(|| invalid("invalid kdf"))?)?;
+            let passphrase = passphrase(false, interactive)?;
+            let master = pbkdf2(passphrase.as_bytes(), &salt, iterations);
+            Ok((master, Some(passphrase)))
+        }
+        Some("keystore") => {
+            let name = parts.next().ok_or_else(|| invalid("invalid kdf"))?;
+            let key = unhex(&keystore::load(name)?)?;
+            let master = key
+                .try_into()
+                .map_err(|_| invalid("invalid keystore key"))?;
+            Ok((master, None))
+        }
+        _ => Err(invalid("unsupported kdf")),
+    }
+}
+
+/// 口令优先使用已经取得的，其次从环境变量读取，否则在终端输入(不回显)
+fn passphrase(confirm: bool, interactive: bool) -> io::Result<String> {
+    if !confirm {
+        if let Some(passphrase) = cached() {
+            return Ok(passphrase);
+        }
+    }
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        // 不让路由命令等子进程继承
+        std::env::remove_var(PASSPHRASE_ENV);
+        if !passphrase.is_empty() {
+            return Ok(passphrase);
+        }
+    }
+    if !interactive {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "config is encrypted, set VNT_CONFIG_PASSPHRASE",
+        ));
+    }
+    let passphrase = read_hidden("config passphrase: ")?;
+    if passphrase.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "passphrase is empty",
+        ));
+    }
+    if confirm && read_hidden("confirm passphrase: ")? != passphrase {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "passphrases do not match",
+        ));
+    }
+    Ok(passphrase)
+}
+
+fn cached() -> Option<String> {
+    let mut guard = PASSPHRASE.lock();
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    if guard.is_none() {
+        *guard = inherited();
+    }
+    guard.clone()
+}
+
+/// --daemon的子进程读取父进程通过管道传来的口令
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn inherited() -> Option<String> {
+    use std::io::Read;
+    use std::os::fd::FromRawFd;
+    let fd = std::env::var(PASSPHRASE_FD_ENV).ok()?;
+    std::env::remove_var(PASSPHRASE_FD_ENV);
+    let fd = fd.parse::<i32>().ok().filter(|v| *v > 2)?;
+    // 只读取一次，读完关闭
+    let mut file = unsafe { std::fs::File::from_raw_fd(fd) };
+    let mut passphrase = String::new();
+    if let Err(e) = file.read_to_string(&mut passphrase) {
+        log::warn!("read passphrase from fd {} {:?}", fd, e);
+        return None;
+    }
+    Some(passphrase).filter(|v| !v.is_empty())
+}
+
+/// --daemon重新启动自身时，把已经取得的口令写入管道交给子进程，不经过环境变量和命令行。
+/// 返回管道的读端，子进程启动后再关闭
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn pass_to_child(cmd: &mut std::process::Command) -> io::Result<Option<std::os::fd::OwnedFd>> {
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    let passphrase = match PASSPHRASE.lock().clone() {
+        Some(passphrase) => passphrase,
+        None => return Ok(None),
+    };
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+    // 写端不能被子进程继承，否则子进程读不到结束
+    if unsafe { libc::fcntl(write.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // 口令远小于管道的缓冲区，写入不会阻塞
+    std::fs::File::from(write).write_all(passphrase.as_bytes())?;
+    cmd.env(PASSPHRASE_FD_ENV, read.as_raw_fd().to_string());
+    Ok(Some(read))
+}
+
+fn read_hidden(msg: &str) -> io::Result<String> {
+    print!("{}", msg);
+    io::stdout().flush()?;
+    let echo = keystore::EchoGuard::disable();
+    let mut line = String::new();
+    let len = io::stdin().read_line(&mut line);
+    drop(echo);
+    println!();
+    if len? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "stdin closed, set VNT_CONFIG_PASSPHRASE",
+        ));
+    }
+    Ok(line.trim_end_matches(&['\r', '\n'][..]).to_string())
+}
+
+/// 加密配置文件，use_keystore为true时密钥保存在系统密钥库中，否则使用口令
+pub fn encrypt_file(path: &Path, use_keystore: bool) -> io::Result<()> {
+    let content = std::fs::read_to_string(path)?;
+    if is_encrypted(&content) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "config already encrypted",
+        ));
+    }
+    // 先确认是有效的配置，避免加密后才发现写错
+    crate::config::migrate::parse(&content)?;
+    let (kdf, master) = if use_keystore {
+        let master: [u8; 32] = rand::thread_rng().gen();
+        let name = hex(&rand::thread_rng().gen::<[u8; 8]>());
+        keystore::store(&name, &hex(&master))?;
+        (format!("keystore,{}", name), master)
+    } else {
+        let salt: [u8; 16] = rand::thread_rng().gen();
+        let passphrase = passphrase(true, true)?;
+        (
+            format!("pbkdf2-sha256,{},{}", ITERATIONS, hex(&salt)),
+            pbkdf2(passphrase.as_bytes(), &salt, ITERATIONS),
+        )
+    };
+    write(path, &encrypt(&content, &kdf, &master)?)
+}
+
+/// 解密配置文件用于编辑，编辑后需要重新加密
+pub fn decrypt_file(path: &Path) -> io::Result<()> {
+    let content = std::fs::read_to_string(path)?;
+    if !is_encrypted(&content) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "config not encrypted",
+        ));
+    }
+    write(path, &decrypt(&content, true)?)
+}
+
+/// 先写临时文件再改名，避免写到一半时中断损坏配置。
+/// 临时文件创建时就只有所有者可以读写，解密出的明文不会被其他用户读到
+fn write(path: &Path, content: &str) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    // 上次中断留下的临时文件权限不确定，删除后重新创建
+    match std::fs::remove_file(&tmp) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let mut file = options.open(&tmp)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&tmp, path)
+}
+
+#[test]
+fn test_encrypt() {
+    let master: [u8; 32] = rand::thread_rng().gen();
+    let kdf = "keystore,0011223344556677";
+    let content = encrypt("token: abc\n", kdf, &master).unwrap();
+    assert!(is_encrypted(&content));
+    let (kdf, nonce, data) = fields(&content).unwrap();
+    assert_eq!(
+        open(kdf, &nonce, &data, &master).unwrap(),
+        "token: abc\n".to_string()
+    );
+    // kdf参与认证，被修改时解密失败
+    assert!(open("keystore,other", &nonce, &data, &master).is_err());
+    let mut tampered = data.clone();
+    tampered[0] ^= 1;
+    assert!(open(kdf, &nonce, &tampered, &master).is_err());
+    assert!(open(kdf, &nonce, &data, &[0u8; 32]).is_err());
+    // 随机数长度固定
+    let short = content.replace(&format!("nonce: {}", hex(&nonce)), "nonce: 0011");
+    assert!(fields(&short).is_err());
+    // 不支持的版本
+    assert!(fields(&content.replace(MAGIC, "vnt-config-encrypted: v1")).is_err());
+}
@@ -0,0 +1,247 @@
+//! 系统密钥库，保存配置文件的主密钥
+//!
+//! macos使用钥匙串(security命令)，linux使用Secret Service(secret-tool命令，需要桌面会话或者gnome-keyring)，
+//! windows使用DPAPI按本机加密后保存在程序目录下，只有本机能解密
+
+use std::io;
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::process::{Command, Stdio};
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const SERVICE: &str = "vnt-cli";
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn output(cmd: &mut Command) -> io::Result<String> {
+    let output = cmd
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| io::Error::new(e.kind(), format!("{:?} {}", cmd.get_program(), e)))?;
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "{:?} {}",
+                cmd.get_program(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// 密钥是随机生成的，只在命令行参数中短暂出现
+#[cfg(target_os = "macos")]
+pub fn store(name: &str, secret: &str) -> io::Result<()> {
+    output(
+        Command::new("security")
+            .args([
+                "add-generic-password",
+                "-U",
+                "-s",
+                SERVICE,
+                "-a",
+                name,
+                "-w",
+            ])
+            .arg(secret),
+    )
+    .map(|_| ())
+}
+
+#[cfg(target_os = "macos")]
+pub fn load(name: &str) -> io::Result<String> {
+    output(Command::new("security").args([
+        "find-generic-password",
+        "-s",
+        SERVICE,
+        "-a",
+        name,
+        "-w",
+    ]))
+}
+
+#[cfg(target_os = "linux")]
+pub fn store(name: &str, secret: &str) -> io::Result<()> {
+    use std::io::Write;
+    let mut child = Command::new("secret-tool")
+        .args([
+            "store", "--label", SERVICE, "service", SERVICE, "account", name,
+        ])
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| io::Error::new(e.kind(), format!("secret-tool {}", e)))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(secret.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "secret-tool {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+pub fn load(name: &str) -> io::Result<String> {
+    output(Command::new("secret-tool").args(["lookup", "service", SERVICE, "account", name]))
+}
+
+#[cfg(target_os = "windows")]
+fn key_file(name: &str) -> io::Result<std::path::PathBuf> {
+    let dir = crate::app_home()?.join("keystore");
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir.join(name))
+}
+
+/// 按本机加密，服务以SYSTEM运行时也能解密
+#[cfg(target_os = "windows")]
+fn dpapi(data: &[u8], protect: bool) -> io::Result<Vec<u8>> {
+    use winapi::um::dpapi::{
+        CryptProtectData, CryptUnprotectData, CRYPTPROTECT_LOCAL_MACHINE, CRYPTPROTECT_UI_FORBIDDEN,
+    };
+    use winapi::um::winbase::LocalFree;
+    use winapi::um::wincrypt::DATA_BLOB;
+    let mut input = DATA_BLOB {
+        cbData: data.len() as u32,
+        pbData: data.as_ptr() as *mut u8,
+    };
+    let mut out = DATA_BLOB {
+        cbData: 0,
+        pbData: std::ptr::null_mut(),
+    };
+    let flags = CRYPTPROTECT_UI_FORBIDDEN | CRYPTPROTECT_LOCAL_MACHINE;
+    let ok = unsafe {
+        if protect {
+            CryptProtectData(
+                &mut input,
+                std::ptr::null(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                flags,
+                &mut out,
+            )
+        } else {
+            CryptUnprotectData(
+                &mut input,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                flags,
+                &mut out,
+            )
+        }
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let rs = unsafe { std::slice::from_raw_parts(out.pbData, out.cbData as usize).to_vec() };
+    unsafe {
+        LocalFree(out.pbData as _);
+    }
+    Ok(rs)
+}
+
+#[cfg(target_os = "windows")]
+pub fn store(name: &str, secret: &str) -> io::Result<()> {
+    std::fs::write(key_file(name)?, dpapi(secret.as_bytes(), true)?)
+}
+
+#[cfg(target_os = "windows")]
+pub fn load(name: &str) -> io::Result<String> {
+    let data = dpapi(&std::fs::read(key_file(name)?)?, false)?;
+    String::from_utf8(data).map_err(|_| io::Error::from(io::ErrorKind::InvalidData))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn store(_name: &str, _secret: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "keystore not supported",
+    ))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn load(_name: &str) -> io::Result<String> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "keystore not supported",
+    ))
+}
+
+/// 输入口令时关闭终端回显，drop时恢复
+pub struct EchoGuard {
+    #[cfg(unix)]
+    termios: Option<libc::termios>,
+    #[cfg(target_os = "windows")]
+    mode: Option<u32>,
+}
+
+impl EchoGuard {
+    #[cfg(unix)]
+    pub fn disable() -> Self {
+        unsafe {
+            let mut termios: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+                return Self { termios: None };
+            }
+            let mut hidden = termios;
+            hidden.c_lflag &= !libc::ECHO;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &hidden) != 0 {
+                return Self { termios: None };
+            }
+            Self {
+                termios: Some(termios),
+            }
+        }
+    }
+    #[cfg(target_os = "windows")]
+    pub fn disable() -> Self {
+        use winapi::um::consoleapi::{GetConsoleMode, SetConsoleMode};
+        use winapi::um::processenv::GetStdHandle;
+        use winapi::um::winbase::STD_INPUT_HANDLE;
+        use winapi::um::wincon::ENABLE_ECHO_INPUT;
+        unsafe {
+            let handle = GetStdHandle(STD_INPUT_HANDLE);
+            let mut mode = 0;
+            if GetConsoleMode(handle, &mut mode) == 0
+                || SetConsoleMode(handle, mode & !ENABLE_ECHO_INPUT) == 0
+            {
+                return Self { mode: None };
+            }
+            Self { mode: Some(mode) }
+        }
+    }
+    #[cfg(not(any(unix, target_os = "windows")))]
+    pub fn disable() -> Self {
+        Self {}
+    }
+}
+
+impl Drop for EchoGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(termios) = self.termios.as_ref() {
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, termios);
+            }
+        }
+        #[cfg(target_os = "windows")]
+        if let Some(mode) = self.mode {
+            use winapi::um::consoleapi::SetConsoleMode;
+            use winapi::um::processenv::GetStdHandle;
+            use winapi::um::winbase::STD_INPUT_HANDLE;
+            unsafe {
+                SetConsoleMode(GetStdHandle(STD_INPUT_HANDLE), mode);
+            }
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! 配置文件版本迁移，配置格式变化时旧文件不再直接解析失败
+//!
+//! 配置文件中的version表示格式版本，没有时为1。读取时按版本依次执行迁移后再解析，并提示执行--config-migrate；
+//! --config-migrate把迁移结果写回文件，原文件备份为`<file>`.bak。
+//! 格式变化时增加CONFIG_VERSION，并在STEPS末尾加上从上一版本迁移的函数
+
+use std::io;
+use std::path::Path;
+
+use serde_yaml::{Mapping, Value};
+
+/// 当前的配置格式版本
+pub const CONFIG_VERSION: u32 = 2;
+
+/// STEPS[i]把版本i+1迁移到i+2，返回修改的说明
+const STEPS: [fn(&mut Mapping) -> Vec<String>; 1] = [v1_to_v2];
+
+/// 字符串类型的配置项
+const STRING_KEYS: &[&str] = &[
+    "token",
+    "device_id",
+    "name",
+    "server_address",
+    "password",
+    "ip",
+    "cipher_model",
+    "punch_model",
+    "port_range",
+    "local_ip",
+    "bind_dev",
+    "proxy",
+    "mimicry",
+    "resolver",
+    "retry_backoff",
+    "dns_server",
+    "turn",
+    "mgmt_key",
+    "health_addr",
+    "socks5",
+    "bridge",
+    "broadcast",
+    "route_conflict",
+    "subnet_alias",
+    "device_name",
+    "tun_owner",
+    "tun_group",
+    "run_as",
+];
+const BOOL_KEYS: &[&str] = &[
+    "tap",
+    "simulate_multicast",
+    "tcp",
+    "tcp_control",
+    "relay",
+    "no_proxy",
+    "server_encrypt",
+    "finger",
+    "cmd",
+    "first_latency",
+    "small",
+    "kill_switch",
+    "require_encryption",
+    "private_network",
+    "multi_queue",
+    "drop_caps",
+    "seccomp",
+    "debug_keys",
+];
+const NUMBER_KEYS: &[&str] = &[
+    "mtu",
+    "parallel",
+    "port",
+    "threads",
+    "blocking_threads",
+    "channel_capacity",
+    "metric",
+    "route_table",
+    "session_idle",
+    "max_clock_skew",
+    "server_grace",
+    "self_test_interval",
+];
+/// 列表类型的配置项，第二项表示写成一个字符串时是否按逗号拆分(in_ips等的每一项本身包含逗号)
+const LIST_KEYS: &[(&str, bool)] = &[
+    ("stun_server", true),
+    ("in_ips", false),
+    ("out_ips", true),
+    ("split_include", false),
+    ("split_exclude", false),
+    ("dns_domains", true),
+    ("advertise", false),
+    ("accept_routes", true),
+    ("path_policy", true),
+    ("self_test", true),
+    ("peer_addr", true),
+];
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn key(name: &str) -> Value {
+    Value::String(name.to_string())
+}
+
+fn scalar_str(value: &Value) -> Option<String> {
+    match value {
+        Value::String(v) => Some(v.clone()),
+        Value::Number(v) => Some(v.to_string()),
+        Value::Bool(v) => Some(v.to_string()),
+        _ => None,
+    }
+}
+
+/// 版本1是没有version的手写配置，常见的问题：
+/// 只写了key没有值、列表写成了一个字符串、数字和布尔值加了引号、密码或名称写成纯数字
+fn v1_to_v2(map: &mut Mapping) -> Vec<String> {
+    let mut notes = Vec::new();
+    let empty: Vec<Value> = map
+        .iter()
+        .filter(|(_, v)| v.is_null())
+        .map(|(k, _)| k.clone())
+        .collect();
+    for k in empty {
+        map.remove(&k);
+        notes.push(format!(
+            "{}: empty, use default",
+            scalar_str(&k).unwrap_or_default()
+        ));
+    }
+    for &(name, split) in LIST_KEYS {
+        let value = match map.get_mut(&key(name)) {
+            Some(value) => value,
+            None => continue,
+        };
+        if let Some(str) = scalar_str(value) {
+            let items: Vec<&str> = if split {
+                str.split(',').collect()
+            } else {
+                vec![str.as_str()]
+            };
+            let items = items
+                .into_iter()
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+                .map(|v| Value::String(v.to_string()))
+                .collect();
+            *value = Value::Sequence(items);
+            notes.push(format!("{}: string -> list", name));
+        }
+    }
+    for &name in BOOL_KEYS {
+        if let Some(value) = map.get_mut(&key(name)) {
+            if let Value::String(str) = value {
+                let b = match str.trim().to_ascii_lowercase().as_str() {
+                    "true" | "yes" | "on" | "1" => true,
+                    "false" | "no" | "off" | "0" | "" => false,
+                    _ => continue,
+                };
+                *value = Value::Bool(b);
+                notes.push(format!("{}: quoted bool", name));
+            }
+        }
+    }
+    for &name in NUMBER_KEYS {
+        if let Some(value) = map.get_mut(&key(name)) {
+            if let Value::String(str) = value {
+                if let Ok(v) = str.trim().parse::<u64>() {
+                    *value = Value::Number(v.into());
+                    notes.push(format!("{}: quoted number", name));
+                }
+            }
+        }
+    }
+    for &name in STRING_KEYS {
+        if let Some(value) = map.get_mut(&key(name)) {
+            if matches!(value, Value::Number(_) | Value::Bool(_)) {
+                *value = Value::String(scalar_str(value).unwrap_or_default());
+                notes.push(format!("{}: converted to string", name));
+            }
+        }
+    }
+    notes
+}
+
+/// 迁移的结果
+pub struct Migrated {
+    pub from: u32,
+    pub notes: Vec<String>,
+}
+
+/// 按版本依次迁移，已经是当前版本时返回None
+pub fn migrate(value: &mut Value) -> io::Result<Option<Migrated>> {
+    if value.is_null() {
+        *value = Value::Mapping(Mapping::new());
+    }
+    let map = match value {
+        Value::Mapping(map) => map,
+        _ => return Err(invalid("config must be a yaml mapping".to_string())),
+    };
+    let from = match map.get(&key("version")) {
+        None => 1,
+        Some(v) => v
+            .as_u64()
+            .filter(|v| *v > 0)
+            .ok_or_else(|| invalid(format!("invalid config version {:?}", v)))?
+            as u32,
+    };
+    if from > CONFIG_VERSION {
+        return Err(invalid(format!(
+            "config version {} is newer than this vnt-cli supports ({}), please upgrade",
+            from, CONFIG_VERSION
+        )));
+    }
+    if from == CONFIG_VERSION {
+        return Ok(None);
+    }
+    let mut notes = Vec::new();
+    for step in &STEPS[(from - 1) as usize..] {
+        notes.extend(step(map));
+    }
+    map.insert(
+        key("version"),
+        Value::Number((CONFIG_VERSION as u64).into()),
+    );
+    Ok(Some(Migrated { from, notes }))
+}
+
+/// 解析配置内容，旧版本在内存中迁移
+pub fn parse(content: &str) -> io::Result<(super::FileConfig, Option<Migrated>)> {
+    let mut value: Value = serde_yaml::from_str(content).map_err(|e| invalid(format!("{}", e)))?;
+    let migrated = migrate(&mut value)?;
+    let file_conf = serde_yaml::from_value(value).map_err(|e| invalid(format!("{}", e)))?;
+    Ok((file_conf, migrated))
+}
+
+/// 迁移配置文件，原文件备份为`<file>`.bak，加密的配置需要先解密
+pub fn migrate_file(path: &Path) -> io::Result<Option<Migrated>> {
+    let content = std::fs::read_to_string(path)?;
+    if super::encrypt::is_encrypted(&content) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "config is encrypted, run --config-decrypt first",
+        ));
+    }
+    let mut value: Value = serde_yaml::from_str(&content).map_err(|e| invalid(format!("{}", e)))?;
+    let migrated = match migrate(&mut value)? {
+        Some(migrated) => migrated,
+        None => return Ok(None),
+    };
+    // 写回前确认迁移后可以解析
+    serde_yaml::from_value::<super::FileConfig>(value.clone())
+        .map_err(|e| invalid(format!("{}", e)))?;
+    let out = serde_yaml::to_string(&value).map_err(|e| invalid(format!("{}", e)))?;
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    std::fs::copy(path, &backup)?;
+    std::fs::write(path, out)?;
+    Ok(Some(migrated))
+}
@@ -1,16 +1,29 @@
 use std::io;
-use std::net::{Ipv4Addr, ToSocketAddrs};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
 
+use vnt::channel::broadcast::BroadcastPolicy;
+use vnt::channel::path_policy::PathPolicy;
 use vnt::channel::punch::PunchModel;
+use vnt::channel::turn::TurnConfig;
 use vnt::cipher::CipherModel;
-use vnt::core::Config;
+use vnt::core::{Config, ConfigOptions};
+use vnt::route_conflict::RouteConflictPolicy;
+use vnt::split_tunnel::SplitMode;
+use vnt::util::mimicry::Mimicry;
+use vnt::util::proxy::ProxyConfig;
 
-#[derive(Serialize, Deserialize, Debug)]
+pub mod encrypt;
+mod keystore;
+pub mod migrate;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(default)]
 pub struct FileConfig {
+    /// 配置格式版本，见migrate
+    pub version: u32,
     pub tap: bool,
     pub token: String,
     pub device_id: String,
@@ -23,6 +36,7 @@ pub struct FileConfig {
     pub simulate_multicast: bool,
     pub mtu: Option<u16>,
     pub tcp: bool,
+    pub tcp_control: bool,
     pub ip: Option<String>,
     pub relay: bool,
     pub no_proxy: bool,
@@ -32,13 +46,59 @@ pub struct FileConfig {
     pub finger: bool,
     pub punch_model: String,
     pub port: u16,
+    pub port_range: Option<String>,
+    pub local_ip: Option<String>,
+    pub bind_dev: Option<String>,
+    pub proxy: Option<String>,
     pub cmd: bool,
     pub first_latency: bool,
+    pub threads: usize,
+    pub blocking_threads: usize,
+    pub channel_capacity: usize,
+    pub small: bool,
+    pub metric: u16,
+    pub route_table: u32,
+    pub kill_switch: bool,
+    pub split_include: Vec<String>,
+    pub split_exclude: Vec<String>,
+    pub dns_server: Option<String>,
+    pub dns_domains: Vec<String>,
+    pub session_idle: u32,
+    pub turn: Option<String>,
+    pub mgmt_key: Option<String>,
+    pub health_addr: Option<String>,
+    pub socks5: Option<String>,
+    pub advertise: Vec<String>,
+    pub accept_routes: Vec<String>,
+    pub bridge: Option<String>,
+    pub path_policy: Vec<String>,
+    pub require_encryption: bool,
+    pub broadcast: String,
+    pub max_clock_skew: u32,
+    pub self_test: Vec<String>,
+    pub self_test_interval: u32,
+    pub route_conflict: String,
+    pub subnet_alias: Option<String>,
+    pub device_name: Option<String>,
+    pub private_network: bool,
+    pub multi_queue: bool,
+    pub tun_owner: Option<String>,
+    pub tun_group: Option<String>,
+    pub drop_caps: bool,
+    pub run_as: Option<String>,
+    pub seccomp: bool,
+    pub mimicry: Option<String>,
+    pub resolver: Option<String>,
+    pub server_grace: u32,
+    pub peer_addr: Vec<String>,
+    pub retry_backoff: Option<String>,
+    pub debug_keys: bool,
 }
 
 impl Default for FileConfig {
     fn default() -> Self {
         Self {
+            version: migrate::CONFIG_VERSION,
             tap: false,
             token: "".to_string(),
             device_id: get_device_id(),
@@ -55,6 +115,7 @@ impl Default for FileConfig {
             simulate_multicast: false,
             mtu: None,
             tcp: false,
+            tcp_control: false,
             ip: None,
             relay: false,
             no_proxy: false,
@@ -64,35 +125,116 @@ impl Default for FileConfig {
             finger: false,
             punch_model: "".to_string(),
             port: 0,
+            port_range: None,
+            local_ip: None,
+            bind_dev: None,
+            proxy: None,
             cmd: false,
             first_latency: false,
+            threads: 0,
+            blocking_threads: 0,
+            channel_capacity: 0,
+            small: false,
+            metric: 0,
+            route_table: 0,
+            kill_switch: false,
+            split_include: vec![],
+            split_exclude: vec![],
+            dns_server: None,
+            dns_domains: vec![],
+            session_idle: 0,
+            turn: None,
+            mgmt_key: None,
+            health_addr: None,
+            socks5: None,
+            advertise: vec![],
+            accept_routes: vec![],
+            bridge: None,
+            path_policy: vec![],
+            require_encryption: false,
+            broadcast: "".to_string(),
+            max_clock_skew: 30,
+            self_test: vec![],
+            self_test_interval: 5,
+            route_conflict: "".to_string(),
+            subnet_alias: None,
+            device_name: None,
+            private_network: false,
+            multi_queue: false,
+            tun_owner: None,
+            tun_group: None,
+            drop_caps: false,
+            run_as: None,
+            seccomp: false,
+            mimicry: None,
+            resolver: None,
+            server_grace: 0,
+            peer_addr: vec![],
+            retry_backoff: None,
+            debug_keys: false,
         }
     }
 }
 
-pub fn read_config(file_path: &str) -> io::Result<(Config, bool)> {
-    let conf = std::fs::read_to_string(file_path)?;
-    let file_conf = match serde_yaml::from_str::<FileConfig>(&conf) {
+/// interactive为false时，加密的配置只从环境变量读取口令
+pub fn read_config(
+    file_path: &str,
+    interactive: bool,
+) -> io::Result<(
+    Config,
+    bool,
+    Option<String>,
+    Option<SocketAddr>,
+    Option<SocketAddr>,
+)> {
+    let conf = encrypt::read(file_path, interactive)?;
+    let (file_conf, migrated) = match migrate::parse(&conf) {
         Ok(val) => val,
         Err(e) => {
             log::error!("{:?}", e);
             return Err(io::Error::new(io::ErrorKind::Other, format!("{}", e)));
         }
     };
+    //没有需要修改的内容时不提示，只是缺少version
+    if let Some(migrated) = migrated.filter(|v| !v.notes.is_empty()) {
+        log::warn!(
+            "config {} version {} migrated {:?}",
+            file_path,
+            migrated.from,
+            migrated.notes
+        );
+        println!(
+            "warning: {} is an old config format (version {}), migrated in memory: {}",
+            file_path,
+            migrated.from,
+            migrated.notes.join("; ")
+        );
+        println!("run 'vnt-cli --config-migrate {}' to update it", file_path);
+    }
+    build_config(file_conf)
+}
+
+/// 校验并转换成运行配置，返回值的第二项表示是否开启控制台输入，第三项为远程管理密钥，
+/// 后两项为健康检查和socks5代理的监听地址
+pub fn build_config(
+    file_conf: FileConfig,
+) -> io::Result<(
+    Config,
+    bool,
+    Option<String>,
+    Option<SocketAddr>,
+    Option<SocketAddr>,
+)> {
     if file_conf.token.is_empty() {
         return Err(io::Error::new(io::ErrorKind::Other, "token is_empty"));
     }
-    let server_address = match file_conf.server_address.to_socket_addrs() {
-        Ok(mut addr) => {
-            if let Some(addr) = addr.next() {
-                addr
-            } else {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("server_address {:?} error", &file_conf.server_address),
-                ));
-            }
-        }
+    let resolver = match file_conf.resolver.as_ref() {
+        None => None,
+        Some(v) => Some(vnt::util::resolve::parse_server(v)?),
+    };
+    vnt::util::resolve::set(resolver);
+    let server_address = match vnt::util::resolve::lookup_one(&file_conf.server_address) {
+        Ok(addr) => addr,
         Err(e) => {
             return Err(io::Error::new(
                 io::ErrorKind::Other,
@@ -100,7 +242,8 @@ pub fn read_config(file_path: &str) -> io::Result<(Config, bool)> {
             ));
         }
     };
-    let in_ips = match common::args_parse::ips_parse(&file_conf.in_ips) {
+    let in_ips = crate::alias::resolve_in_ips(&crate::alias::load(), file_conf.in_ips.clone());
+    let in_ips = match common::args_parse::ips_parse(&in_ips) {
         Ok(in_ips) => in_ips,
         Err(e) => {
             return Err(io::Error::new(
@@ -118,6 +261,93 @@ pub fn read_config(file_path: &str) -> io::Result<(Config, bool)> {
             ));
         }
     };
+    let path_policy = match path_policy_parse(&file_conf.path_policy) {
+        Ok(path_policy) => path_policy,
+        Err(e) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("path_policy {:?} error:{}", &file_conf.path_policy, e),
+            ));
+        }
+    };
+    let self_test = match self_test_parse(&file_conf.self_test) {
+        Ok(self_test) => self_test,
+        Err(e) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("self_test {:?} error:{}", &file_conf.self_test, e),
+            ));
+        }
+    };
+    let peer_addrs = match peer_addr_parse(&file_conf.peer_addr) {
+        Ok(peer_addrs) => peer_addrs,
+        Err(e) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("peer_addr {:?} error:{}", &file_conf.peer_addr, e),
+            ));
+        }
+    };
+    let (advertise, masquerade) = match common::args_parse::advertise_parse(&file_conf.advertise) {
+        Ok(advertise) => advertise,
+        Err(e) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("advertise {:?} error:{}", &file_conf.advertise, e),
+            ));
+        }
+    };
+    let accept_routes = match common::args_parse::out_ips_parse(&file_conf.accept_routes) {
+        Ok(accept_routes) => accept_routes,
+        Err(e) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("accept_routes {:?} error:{}", &file_conf.accept_routes, e),
+            ));
+        }
+    };
+    let subnet_alias = match file_conf.subnet_alias.clone() {
+        None => None,
+        Some(alias) => match common::args_parse::out_ips_parse(&vec![alias]) {
+            Ok(alias) => Some(alias[0]),
+            Err(e) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("subnet_alias {:?} error:{}", &file_conf.subnet_alias, e),
+                ));
+            }
+        },
+    };
+    let tun_owner = match file_conf.tun_owner.as_deref().map(user_id) {
+        None => None,
+        Some(Ok(id)) => Some(id),
+        Some(Err(e)) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("tun_owner error:{}", e),
+            ));
+        }
+    };
+    let tun_group = match file_conf.tun_group.as_deref().map(group_id) {
+        None => None,
+        Some(Ok(id)) => Some(id),
+        Some(Err(e)) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("tun_group error:{}", e),
+            ));
+        }
+    };
+    let run_as = match file_conf.run_as.as_deref().map(user_ids) {
+        None => None,
+        Some(Ok(ids)) => Some(ids),
+        Some(Err(e)) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("run_as error:{}", e),
+            ));
+        }
+    };
     let virtual_ip = match file_conf.ip.clone().map(|v| Ipv4Addr::from_str(&v)) {
         None => None,
         Some(r) => Some(r.map_err(|e| {
@@ -127,53 +357,319 @@ pub fn read_config(file_path: &str) -> io::Result<(Config, bool)> {
             )
         })?),
     };
+    let dns_server = match file_conf
+        .dns_server
+        .clone()
+        .map(|v| Ipv4Addr::from_str(&v))
+    {
+        None => None,
+        Some(r) => Some(r.map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("dns_server {:?} error:{}", &file_conf.dns_server, e),
+            )
+        })?),
+    };
+    let health_addr = match file_conf
+        .health_addr
+        .clone()
+        .map(|v| SocketAddr::from_str(&v))
+    {
+        None => None,
+        Some(r) => Some(r.map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("health_addr {:?} error:{}", &file_conf.health_addr, e),
+            )
+        })?),
+    };
+    let socks5 = match file_conf.socks5.clone().map(|v| SocketAddr::from_str(&v)) {
+        None => None,
+        Some(r) => Some(r.map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("socks5 {:?} error:{}", &file_conf.socks5, e),
+            )
+        })?),
+    };
+
+    let turn = match file_conf.turn.as_ref().map(|v| TurnConfig::from_str(v)) {
+        None => None,
+        Some(r) => Some(r.map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("turn {:?} error:{}", &file_conf.turn, e),
+            )
+        })?),
+    };
+    let proxy = match file_conf.proxy.as_ref().map(|v| ProxyConfig::from_str(v)) {
+        None => None,
+        Some(r) => Some(r.map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("proxy {:?} error:{}", file_conf.proxy, e),
+            )
+        })?),
+    };
+    let mimicry = match file_conf.mimicry.as_ref().map(|v| Mimicry::from_str(v)) {
+        None => None,
+        Some(r) => Some(r.map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("mimicry {:?} error:{}", file_conf.mimicry, e),
+            )
+        })?),
+    };
+    let port_range = match file_conf.port_range.as_ref() {
+        None => None,
+        Some(v) => Some(common::args_parse::port_range_parse(v).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("port_range {:?} error:{}", v, e),
+            )
+        })?),
+    };
+    let retry_backoff = match file_conf.retry_backoff.as_ref() {
+        None => None,
+        Some(v) => Some(common::args_parse::backoff_parse(v).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("retry_backoff {:?} error:{}", v, e),
+            )
+        })?),
+    };
+    let local_ip = match file_conf.local_ip.clone().map(|v| IpAddr::from_str(&v)) {
+        None => None,
+        Some(r) => Some(r.map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("local_ip {:?} error:{}", &file_conf.local_ip, e),
+            )
+        })?),
+    };
 
     let cipher_model = CipherModel::from_str(&file_conf.cipher_model)
         .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
 
     let punch_model = PunchModel::from_str(&file_conf.punch_model)
         .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-    let config = Config::new(
-        file_conf.tap,
-        file_conf.token,
-        file_conf.device_id,
-        file_conf.name,
+    let broadcast = BroadcastPolicy::from_str(&file_conf.broadcast)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let route_conflict = RouteConflictPolicy::from_str(&file_conf.route_conflict)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let (split_mode, split_apps) = if !file_conf.split_include.is_empty() {
+        if !file_conf.split_exclude.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "split_include and split_exclude cannot be used together",
+            ));
+        }
+        (Some(SplitMode::Include), file_conf.split_include)
+    } else if !file_conf.split_exclude.is_empty() {
+        (Some(SplitMode::Exclude), file_conf.split_exclude)
+    } else {
+        (None, vec![])
+    };
+    let config = ConfigOptions {
+        tap: file_conf.tap,
+        token: file_conf.token,
+        device_id: file_conf.device_id,
+        name: file_conf.name,
         server_address,
-        file_conf.server_address,
-        file_conf.stun_server,
+        server_address_str: file_conf.server_address,
+        stun_server: file_conf.stun_server,
         in_ips,
         out_ips,
-        file_conf.password,
-        file_conf.simulate_multicast,
-        file_conf.mtu,
-        file_conf.tcp,
-        virtual_ip,
-        file_conf.relay,
+        password: file_conf.password,
+        simulate_multicast: file_conf.simulate_multicast,
+        mtu: file_conf.mtu,
+        tcp: file_conf.tcp,
+        ip: virtual_ip,
+        relay: file_conf.relay,
         #[cfg(feature = "ip_proxy")]
-        file_conf.no_proxy,
-        file_conf.server_encrypt,
-        file_conf.parallel,
+        no_proxy: file_conf.no_proxy,
+        server_encrypt: file_conf.server_encrypt,
+        parallel: file_conf.parallel,
         cipher_model,
-        file_conf.finger,
+        finger: file_conf.finger,
         punch_model,
-        file_conf.port,
-        file_conf.first_latency,
-    )
-    .unwrap();
-    Ok((config, file_conf.cmd))
+        port: file_conf.port,
+        first_latency: file_conf.first_latency,
+        threads: file_conf.threads,
+        blocking_threads: file_conf.blocking_threads,
+        channel_capacity: file_conf.channel_capacity,
+        small: file_conf.small,
+        metric: file_conf.metric,
+        route_table: file_conf.route_table,
+        kill_switch: file_conf.kill_switch,
+        split_mode,
+        split_apps,
+        dns_server,
+        dns_domains: file_conf.dns_domains,
+        session_idle: file_conf.session_idle,
+        tcp_control: file_conf.tcp_control,
+        turn,
+        local_ip,
+        port_range,
+        bind_device: file_conf.bind_dev,
+        proxy,
+        advertise_routes: advertise,
+        masquerade_routes: masquerade,
+        accept_routes,
+        bridge: file_conf.bridge,
+        path_policy,
+        require_encryption: file_conf.require_encryption,
+        broadcast,
+        max_clock_skew: file_conf.max_clock_skew,
+        self_test,
+        self_test_interval: file_conf.self_test_interval,
+        route_conflict,
+        subnet_alias,
+        device_name: file_conf.device_name,
+        private_network: file_conf.private_network,
+        multi_queue: file_conf.multi_queue,
+        tun_owner,
+        tun_group,
+        drop_caps: file_conf.drop_caps,
+        run_as,
+        seccomp: file_conf.seccomp,
+        mimicry,
+        resolver,
+        server_grace: file_conf.server_grace,
+        peer_addrs,
+        retry_backoff,
+        debug_keys: file_conf.debug_keys,
+    }
+    .build()
+    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+    Ok((
+        config,
+        file_conf.cmd,
+        file_conf.mgmt_key,
+        health_addr,
+        socks5,
+    ))
+}
+
+/// 格式为 ip=策略，ip可以是别名，为*时对所有设备生效，如 10.26.0.5=never-relay
+pub fn path_policy_parse(list: &[String]) -> Result<Vec<(Ipv4Addr, PathPolicy)>, String> {
+    let mut rs = Vec::with_capacity(list.len());
+    for item in list {
+        let (ip, policy) = item
+            .split_once('=')
+            .ok_or_else(|| format!("'{}' not ip=policy", item))?;
+        let ip = match ip.trim() {
+            "*" => Ipv4Addr::UNSPECIFIED,
+            ip => crate::alias::parse_peer(ip).map_err(|e| e.to_string())?,
+        };
+        rs.push((ip, PathPolicy::from_str(policy)?));
+    }
+    Ok(rs)
+}
+
+/// 自检的设备，可以是虚拟ip或别名，多个用逗号分隔
+pub fn self_test_parse(list: &[String]) -> Result<Vec<Ipv4Addr>, String> {
+    let mut rs = Vec::with_capacity(list.len());
+    for item in list.iter().flat_map(|v| v.split(',')) {
+        let item = item.trim();
+        if item.is_empty() {
+            continue;
+        }
+        let ip = crate::alias::parse_peer(item).map_err(|e| e.to_string())?;
+        if !rs.contains(&ip) {
+            rs.push(ip);
+        }
+    }
+    Ok(rs)
+}
+
+/// 设备的静态地址，格式为 虚拟ip或别名=地址:端口，多个用逗号分隔
+pub fn peer_addr_parse(list: &[String]) -> Result<Vec<(Ipv4Addr, SocketAddr)>, String> {
+    let mut rs = Vec::with_capacity(list.len());
+    for item in list.iter().flat_map(|v| v.split(',')) {
+        let item = item.trim();
+        if item.is_empty() {
+            continue;
+        }
+        let (peer, addr) = item
+            .split_once('=')
+            .ok_or_else(|| format!("'{}' not peer=addr", item))?;
+        let ip = crate::alias::parse_peer(peer).map_err(|e| e.to_string())?;
+        let addr = SocketAddr::from_str(addr.trim()).map_err(|e| format!("'{}' {}", addr, e))?;
+        rs.push((ip, addr));
+    }
+    Ok(rs)
+}
+
+/// 用户名或uid转换为uid
+pub fn user_id(user: &str) -> Result<u32, String> {
+    if let Ok(uid) = user.parse::<u32>() {
+        return Ok(uid);
+    }
+    #[cfg(unix)]
+    if let Ok(name) = std::ffi::CString::new(user) {
+        unsafe {
+            let passwd = libc::getpwnam(name.as_ptr());
+            if !passwd.is_null() {
+                return Ok((*passwd).pw_uid);
+            }
+        }
+    }
+    Err(format!("user '{}' not found", user))
+}
+
+/// 用户名或uid转换为(uid,主组gid)
+pub fn user_ids(user: &str) -> Result<(u32, u32), String> {
+    let uid = user_id(user)?;
+    #[cfg(unix)]
+    unsafe {
+        let passwd = libc::getpwuid(uid);
+        if !passwd.is_null() {
+            return Ok((uid, (*passwd).pw_gid));
+        }
+    }
+    //没有对应账户的uid，组使用相同的值
+    Ok((uid, uid))
+}
+
+/// 组名或gid转换为gid
+pub fn group_id(group: &str) -> Result<u32, String> {
+    if let Ok(gid) = group.parse::<u32>() {
+        return Ok(gid);
+    }
+    #[cfg(unix)]
+    if let Ok(name) = std::ffi::CString::new(group) {
+        unsafe {
+            let group = libc::getgrnam(name.as_ptr());
+            if !group.is_null() {
+                return Ok((*group).gr_gid);
+            }
+        }
+    }
+    Err(format!("group '{}' not found", group))
 }
 
 pub fn get_device_id() -> String {
+    let path_buf = crate::app_home().unwrap().join("device-id");
+    //重置过的id优先，克隆的机器硬件标识相同
+    if let Ok(id) = std::fs::read_to_string(path_buf.as_path()) {
+        let id = id.trim().to_string();
+        if !id.is_empty() {
+            return id;
+        }
+    }
     if let Some(id) = common::identifier::get_unique_identifier() {
         id
     } else {
-        let path_buf = crate::app_home().unwrap().join("device-id");
-        if let Ok(id) = std::fs::read_to_string(path_buf.as_path()) {
-            id
-        } else {
-            let id = uuid::Uuid::new_v4().to_string();
-            let _ = std::fs::write(path_buf, &id);
-            id
-        }
+        let id = uuid::Uuid::new_v4().to_string();
+        let _ = std::fs::write(path_buf, &id);
+        id
     }
 }
+
+/// 重新生成device_id，用于解决克隆虚拟机导致的id冲突
+pub fn reset_device_id() -> io::Result<String> {
+    let id = uuid::Uuid::new_v4().to_string();
+    std::fs::write(crate::app_home()?.join("device-id"), &id)?;
+    Ok(id)
+}
@@ -0,0 +1,60 @@
+//! 单次连接服务器的流程，命令行之外的入口(组网、安装向导)共用，失败原因统一描述
+
+use std::io;
+
+use vnt::core::{Config, VntUtil};
+use vnt::handle::handshake_handler::HandshakeEnum;
+use vnt::handle::registration_handler::ReqEnum;
+
+fn other<E: ToString>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e.to_string())
+}
+
+pub fn handshake_error(e: HandshakeEnum) -> io::Error {
+    match e {
+        HandshakeEnum::NotSecret => other("server does not support encryption"),
+        HandshakeEnum::KeyError => other("server key error"),
+        HandshakeEnum::Timeout => other("handshake timeout"),
+        HandshakeEnum::RateLimited(retry_after) => {
+            other(format!("rate limited, retry after {}s", retry_after))
+        }
+        HandshakeEnum::ServerError(str) | HandshakeEnum::Other(str) => other(str),
+    }
+}
+
+pub fn register_error(e: ReqEnum) -> io::Error {
+    match e {
+        ReqEnum::TokenError => other("token error"),
+        ReqEnum::AddressExhausted => other("address exhausted"),
+        ReqEnum::IpAlreadyExists => other("ip already exists"),
+        ReqEnum::InvalidIp => other("invalid ip"),
+        ReqEnum::DeviceIdConflict => other("device id conflict"),
+        ReqEnum::RateLimited(retry_after) => {
+            other(format!("rate limited, retry after {}s", retry_after))
+        }
+        ReqEnum::DeviceLimit => other("device limit reached"),
+        ReqEnum::Maintenance(maintenance) => other(format!("maintenance: {}", maintenance)),
+        ReqEnum::Timeout => other("register timeout"),
+        ReqEnum::ServerError(str) | ReqEnum::Other(str) => other(str),
+    }
+}
+
+/// 连接服务器并完成握手(开启服务端加密时包括加密握手)
+pub fn handshake(config: Config) -> io::Result<VntUtil> {
+    let server_encrypt = config.server_encrypt;
+    let mut vnt_util = VntUtil::new(config)?;
+    vnt_util.connect()?;
+    vnt_util.handshake().map_err(handshake_error)?;
+    if server_encrypt {
+        vnt_util.secret_handshake().map_err(handshake_error)?;
+    }
+    Ok(vnt_util)
+}
+
+/// 连接、注册并创建网卡，只尝试一次，失败时把原因返回给调用方
+pub fn connect(config: Config) -> io::Result<VntUtil> {
+    let mut vnt_util = handshake(config)?;
+    vnt_util.register().map_err(register_error)?;
+    vnt_util.create_iface()?;
+    Ok(vnt_util)
+}
@@ -1,6 +1,6 @@
 use console::{style, Style};
 
-use crate::command::entity::{DeviceItem, Info, RouteItem};
+use crate::command::entity::{DeviceItem, EventItem, Info, PunchItem, RouteItem, TunnelItem};
 
 pub mod table;
 
@@ -9,15 +9,145 @@ pub fn console_info(status: Info) {
     println!("Virtual ip: {}", style(status.virtual_ip).green());
     println!("Virtual gateway: {}", style(status.virtual_gateway).green());
     println!("Virtual netmask: {}", style(status.virtual_netmask).green());
-    println!(
-        "Connection status: {}",
-        style(status.connect_status).green()
-    );
+    if status.server_lost.is_empty() {
+        println!(
+            "Connection status: {}",
+            style(status.connect_status).green()
+        );
+    } else {
+        println!(
+            "Connection status: {}",
+            style(status.connect_status).yellow()
+        );
+        println!("Server lost: {}", style(status.server_lost).yellow());
+    }
+    if !status.maintenance.is_empty() {
+        println!("Maintenance: {}", style(status.maintenance).yellow());
+    }
     println!("NAT type: {}", style(status.nat_type).green());
     println!("Relay server: {}", style(status.relay_server).green());
     println!("Public ips: {}", style(status.public_ips).green());
     println!("Local addr: {}", style(status.local_addr).green());
     println!("IPv6: {}", style(status.ipv6_addr).green());
+    println!("Remote config: {}", style(status.remote_config).green());
+    if status.quota.contains("throttled") || status.quota.contains("exceeded") {
+        println!("Relay quota: {}", style(status.quota).yellow());
+    } else {
+        println!("Relay quota: {}", style(status.quota).green());
+    }
+    if status.auth_failures.contains("quarantined") {
+        println!("Auth failures: {}", style(status.auth_failures).yellow());
+    } else {
+        println!("Auth failures: {}", style(status.auth_failures).green());
+    }
+    if !status.malformed.is_empty() {
+        println!("Malformed packets: {}", style(status.malformed).yellow());
+    }
+    if !status.broadcast.is_empty() {
+        println!("Broadcast: {}", style(status.broadcast).green());
+    }
+    if status.clock_skew.contains("exceeds") {
+        println!("Clock skew: {}", style(status.clock_skew).red());
+    } else if !status.clock_skew.is_empty() {
+        println!("Clock skew: {}", style(status.clock_skew).green());
+    }
+    if status.self_test.contains("failing") {
+        println!("Self-test: {}", style(status.self_test).red());
+    } else if !status.self_test.is_empty() {
+        println!("Self-test: {}", style(status.self_test).green());
+    }
+    if status.memory.contains("evicted") {
+        println!("Peer state: {}", style(status.memory).yellow());
+    } else if !status.memory.is_empty() {
+        println!("Peer state: {}", style(status.memory).green());
+    }
+}
+
+/// 有别名时显示为 别名(原名称)
+fn with_alias(name: String, alias: &str) -> String {
+    if alias.is_empty() {
+        name
+    } else {
+        format!("{}({})", alias, name)
+    }
+}
+
+/// 按时间先后输出，时间显示为距今多久
+pub fn console_events(list: Vec<EventItem>, now: u64) {
+    if list.is_empty() {
+        println!("No event found");
+        return;
+    }
+    let mut out_list = Vec::with_capacity(list.len() + 1);
+    out_list.push(vec![
+        ("Time".to_string(), Style::new()),
+        ("Event".to_string(), Style::new()),
+        ("Peer".to_string(), Style::new()),
+        ("Detail".to_string(), Style::new()),
+    ]);
+    for item in list {
+        let style = match item.kind.as_str() {
+            "error" => Style::new().red(),
+            "disconnected" | "peer-down" => Style::new().yellow(),
+            _ => Style::new().green(),
+        };
+        out_list.push(vec![
+            (format_age(now.saturating_sub(item.time)), style.clone()),
+            (item.kind, style.clone()),
+            (with_alias(item.peer, &item.alias), style.clone()),
+            (item.detail, style),
+        ]);
+    }
+    table::println_table(out_list)
+}
+
+pub fn console_tunnels(list: Vec<TunnelItem>) {
+    if list.is_empty() {
+        println!("No profile found");
+        return;
+    }
+    let mut out_list = Vec::with_capacity(list.len() + 1);
+    out_list.push(vec![
+        ("Name".to_string(), Style::new()),
+        ("Device".to_string(), Style::new()),
+        ("Virtual Ip".to_string(), Style::new()),
+        ("Status".to_string(), Style::new()),
+    ]);
+    for item in list {
+        let style = match item.status.as_str() {
+            "Connected" => Style::new().green(),
+            "down" => Style::new(),
+            _ => Style::new().yellow(),
+        };
+        out_list.push(vec![
+            (item.name, style.clone()),
+            (item.device, style.clone()),
+            (item.virtual_ip, style.clone()),
+            (item.status, style),
+        ]);
+    }
+    table::println_table(out_list)
+}
+
+fn format_age(secs: u64) -> String {
+    if secs < 60 {
+        format!("{}s ago", secs)
+    } else if secs < 60 * 60 {
+        format!("{}m{}s ago", secs / 60, secs % 60)
+    } else if secs < 24 * 60 * 60 {
+        format!("{}h{}m ago", secs / 3600, secs % 3600 / 60)
+    } else {
+        format!("{}d{}h ago", secs / 86400, secs % 86400 / 3600)
+    }
+}
+
+/// 明文通信标红
+fn encryption_style(encryption: &str) -> Style {
+    if encryption == "plaintext" {
+        Style::new().red()
+    } else {
+        Style::new().green()
+    }
 }
 
 pub fn console_route_table(mut list: Vec<RouteItem>) {
@@ -34,25 +164,104 @@ pub fn console_route_table(mut list: Vec<RouteItem>) {
         ("Metric".to_string(), Style::new()),
         ("Rt".to_string(), Style::new()),
         ("Interface".to_string(), Style::new()),
+        ("Ice".to_string(), Style::new()),
+        ("Quality".to_string(), Style::new()),
+        ("Encryption".to_string(), Style::new()),
     ]);
     for item in list {
         out_list.push(vec![
-            (item.destination, Style::new().green()),
+            (with_alias(item.destination, &item.alias), Style::new().green()),
             (item.next_hop, Style::new().green()),
             (item.metric, Style::new().green()),
             (item.rt, Style::new().green()),
             (item.interface, Style::new().green()),
+            (item.ice, Style::new().green()),
+            (
+                item.quality.clone(),
+                if item.quality.starts_with("relay") {
+                    Style::new().yellow()
+                } else {
+                    Style::new().green()
+                },
+            ),
+            (item.encryption.clone(), encryption_style(&item.encryption)),
         ]);
     }
 
     table::println_table(out_list)
 }
 
+/// 各设备最近一次打洞的过程，表格下方列出尝试过的地址对
+pub fn console_punch_table(mut list: Vec<PunchItem>, now: u64) {
+    if list.is_empty() {
+        println!("No punch attempt found");
+        return;
+    }
+    list.sort_by(|t1, t2| t1.destination.cmp(&t2.destination));
+    let mut out_list = Vec::with_capacity(list.len() + 1);
+    out_list.push(vec![
+        ("Destination".to_string(), Style::new()),
+        ("Last Punch".to_string(), Style::new()),
+        ("Initiator".to_string(), Style::new()),
+        ("NAT(Local/Peer)".to_string(), Style::new()),
+        ("Result".to_string(), Style::new()),
+        ("Detail".to_string(), Style::new()),
+    ]);
+    for item in &list {
+        let style = match item.result.as_str() {
+            "succeeded" => Style::new().green(),
+            "pending" => Style::new(),
+            _ => Style::new().yellow(),
+        };
+        out_list.push(vec![
+            (
+                with_alias(item.destination.clone(), &item.alias),
+                style.clone(),
+            ),
+            (format_age(now.saturating_sub(item.time)), style.clone()),
+            (item.initiator.clone(), style.clone()),
+            (item.nat.clone(), style.clone()),
+            (item.result.clone(), style.clone()),
+            (item.detail.clone(), style),
+        ]);
+    }
+    table::println_table(out_list);
+    for item in list {
+        let mut tried = item.candidates.join(", ");
+        if item.predicted > 0 {
+            if !tried.is_empty() {
+                tried.push_str(", ");
+            }
+            tried.push_str(&format!("{} predicted ports", item.predicted));
+        }
+        println!("{} tried: {}", item.destination, tried);
+    }
+}
+
+/// 和服务端断开时只有直连的设备状态可信，其他设备显示为Stale，
+/// 用于区分服务端不可用(直连的设备仍然在线)和本机断网(所有设备都不可达)
+fn mark_stale(list: &mut [DeviceItem]) {
+    if !list.iter().any(|item| item.stale) {
+        return;
+    }
+    println!(
+        "{}",
+        style("Server unreachable, the device list may be out of date, only p2p devices are reachable")
+            .yellow()
+    );
+    for item in list.iter_mut() {
+        if item.stale && !item.nat_traversal_type.contains("p2p") {
+            item.status = "Stale".to_string();
+        }
+    }
+}
+
 pub fn console_device_list(mut list: Vec<DeviceItem>) {
     if list.is_empty() {
         println!("No other devices found");
         return;
     }
+    mark_stale(&mut list);
     list.sort_by(|t1, t2| t1.virtual_ip.cmp(&t2.virtual_ip));
     list.sort_by(|t1, t2| t1.status.cmp(&t2.status));
     let mut out_list = Vec::with_capacity(list.len());
@@ -63,8 +272,10 @@ pub fn console_device_list(mut list: Vec<DeviceItem>) {
         ("Status".to_string(), Style::new()),
         ("P2P/Relay".to_string(), Style::new()),
         ("Rt".to_string(), Style::new()),
+        ("Encryption".to_string(), Style::new()),
     ]);
-    for item in list {
+    for mut item in list {
+        item.name = with_alias(item.name, &item.alias);
         if &item.status == "Online" {
             if item.client_secret != item.current_client_secret {
                 //加密状态不一致，无法通信的
@@ -74,6 +285,7 @@ pub fn console_device_list(mut list: Vec<DeviceItem>) {
                     (item.status, Style::new().red()),
                     ("".to_string(), Style::new().red()),
                     ("".to_string(), Style::new().red()),
+                    ("".to_string(), Style::new().red()),
                 ]);
             } else {
                 if item.nat_traversal_type.contains("p2p") {
@@ -83,6 +295,7 @@ pub fn console_device_list(mut list: Vec<DeviceItem>) {
                         (item.status, Style::new().green()),
                         (item.nat_traversal_type, Style::new().green()),
                         (item.rt, Style::new().green()),
+                        (item.encryption.clone(), encryption_style(&item.encryption)),
                     ]);
                 } else {
                     out_list.push(vec![
@@ -91,6 +304,7 @@ pub fn console_device_list(mut list: Vec<DeviceItem>) {
                         (item.status, Style::new().yellow()),
                         (item.nat_traversal_type, Style::new().yellow()),
                         (item.rt, Style::new().yellow()),
+                        (item.encryption.clone(), encryption_style(&item.encryption)),
                     ]);
                 }
             }
@@ -101,6 +315,7 @@ pub fn console_device_list(mut list: Vec<DeviceItem>) {
                 (item.status, Style::new().color256(102)),
                 ("".to_string(), Style::new().color256(102)),
                 ("".to_string(), Style::new().color256(102)),
+                ("".to_string(), Style::new().color256(102)),
             ]);
         }
     }
@@ -112,6 +327,7 @@ pub fn console_device_list_all(mut list: Vec<DeviceItem>) {
         println!("No other devices found");
         return;
     }
+    mark_stale(&mut list);
     list.sort_by(|t1, t2| t1.virtual_ip.cmp(&t2.virtual_ip));
     list.sort_by(|t1, t2| t1.status.cmp(&t2.status));
     let mut out_list = Vec::with_capacity(list.len());
@@ -126,8 +342,11 @@ pub fn console_device_list_all(mut list: Vec<DeviceItem>) {
         ("Public Ips".to_string(), Style::new()),
         ("Local Ip".to_string(), Style::new()),
         ("IPv6".to_string(), Style::new()),
+        ("Tags".to_string(), Style::new()),
+        ("Capabilities".to_string(), Style::new()),
     ]);
-    for item in list {
+    for mut item in list {
+        item.name = with_alias(item.name, &item.alias);
         if &item.status == "Online" {
             if &item.nat_traversal_type == "p2p" {
                 out_list.push(vec![
@@ -140,6 +359,8 @@ pub fn console_device_list_all(mut list: Vec<DeviceItem>) {
                     (item.public_ips, Style::new().green()),
                     (item.local_ip, Style::new().green()),
                     (item.ipv6, Style::new().green()),
+                    (item.tags, Style::new().green()),
+                    (item.capabilities, Style::new().green()),
                 ]);
             } else {
                 out_list.push(vec![
@@ -152,6 +373,8 @@ pub fn console_device_list_all(mut list: Vec<DeviceItem>) {
                     (item.public_ips, Style::new().yellow()),
                     (item.local_ip, Style::new().yellow()),
                     (item.ipv6, Style::new().yellow()),
+                    (item.tags, Style::new().yellow()),
+                    (item.capabilities, Style::new().yellow()),
                 ]);
             }
         } else {
@@ -165,6 +388,8 @@ pub fn console_device_list_all(mut list: Vec<DeviceItem>) {
                 ("".to_string(), Style::new().color256(102)),
                 ("".to_string(), Style::new().color256(102)),
                 ("".to_string(), Style::new().color256(102)),
+                (item.tags, Style::new().color256(102)),
+                ("".to_string(), Style::new().color256(102)),
             ]);
         }
     }
@@ -0,0 +1,54 @@
+/// 环境变量和参数的对应关系，(环境变量,参数,是否为开关,是否可以有多个值)
+const ENV_ARGS: &[(&str, &str, bool, bool)] = &[
+    ("VNT_CONFIG", "-f", false, false),
+    ("VNT_TOKEN", "-k", false, false),
+    ("VNT_NAME", "-n", false, false),
+    ("VNT_DEVICE_ID", "-d", false, false),
+    ("VNT_SERVER", "-s", false, false),
+    ("VNT_STUN", "-e", false, true),
+    ("VNT_IN_IPS", "-i", false, true),
+    ("VNT_OUT_IPS", "-o", false, true),
+    ("VNT_PASSWORD", "-w", false, false),
+    ("VNT_SERVER_ENCRYPT", "-W", true, false),
+    ("VNT_MTU", "-u", false, false),
+    ("VNT_IP", "--ip", false, false),
+    ("VNT_MODEL", "--model", false, false),
+    ("VNT_TCP", "--tcp", true, false),
+    ("VNT_RELAY", "--relay", true, false),
+    ("VNT_HEALTH_ADDR", "--health-addr", false, false),
+    ("VNT_SOCKS5", "--socks5", false, false),
+    ("VNT_ADVERTISE", "--advertise", false, true),
+    ("VNT_ACCEPT_ROUTES", "--accept-routes", false, true),
+];
+
+/// 容器中通常只用环境变量配置，命令行中没有给出的参数从VNT_*环境变量补充，
+/// 多个值用空格分隔，开关类的参数值为1或true时生效
+pub fn env_args(args: &[String]) -> Vec<String> {
+    let mut extra = Vec::new();
+    for (env, arg, flag, multi) in ENV_ARGS {
+        let value = match std::env::var(env) {
+            Ok(value) if !value.trim().is_empty() => value,
+            _ => continue,
+        };
+        let given = args
+            .iter()
+            .any(|v| v == arg || v.starts_with(&format!("{}=", arg)));
+        if given {
+            continue;
+        }
+        if *flag {
+            if value == "1" || value.eq_ignore_ascii_case("true") {
+                extra.push(arg.to_string());
+            }
+        } else if *multi {
+            for value in value.split_whitespace() {
+                extra.push(arg.to_string());
+                extra.push(value.to_string());
+            }
+        } else {
+            extra.push(arg.to_string());
+            extra.push(value.trim().to_string());
+        }
+    }
+    extra
+}
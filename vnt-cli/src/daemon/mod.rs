@@ -0,0 +1,152 @@
+//! 没有服务管理器时的后台运行，pid记录在程序目录下的env/vnt-cli.pid
+
+use std::fs::OpenOptions;
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::exit_code;
+
+/// 后台启动后等待可以响应命令的时间
+const START_TIMEOUT: Duration = Duration::from_secs(30);
+/// 发送SIGTERM后等待退出的时间
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn pid_file() -> io::Result<PathBuf> {
+    Ok(crate::app_home()?.join("vnt-cli.pid"))
+}
+
+fn log_file() -> io::Result<PathBuf> {
+    Ok(crate::app_home()?.join("daemon.log"))
+}
+
+pub fn write_pid() -> io::Result<()> {
+    std::fs::write(pid_file()?, std::process::id().to_string())
+}
+
+pub fn remove_pid() {
+    if let Ok(path) = pid_file() {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+fn is_alive(pid: u32) -> bool {
+    unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+}
+
+/// pid文件中的进程还存在时返回pid
+pub fn running_pid() -> Option<u32> {
+    let pid = std::fs::read_to_string(pid_file().ok()?).ok()?;
+    let pid = pid.trim().parse::<u32>().ok()?;
+    if is_alive(pid) {
+        Some(pid)
+    } else {
+        None
+    }
+}
+
+/// 去掉--daemon后在新会话中重新启动自身，输出写入env/daemon.log，
+/// 等到可以响应命令再返回
+pub fn start(args: &[String]) -> i32 {
+    match start0(args) {
+        Ok(code) => code,
+        Err(e) => {
+            println!("daemon error {}", e);
+            exit_code::ERROR
+        }
+    }
+}
+
+fn start0(args: &[String]) -> io::Result<i32> {
+    let log = log_file()?;
+    let out = OpenOptions::new().create(true).append(true).open(&log)?;
+    let mut cmd = Command::new(std::env::current_exe()?);
+    cmd.args(
+        args.iter()
+            .filter(|v| v.as_str() != "--daemon" && v.as_str() != "--quiet"),
+    )
+    .arg("--quiet")
+    .stdin(Stdio::null())
+    .stdout(out.try_clone()?)
+    .stderr(out);
+    unsafe {
+        cmd.pre_exec(|| {
+            // 脱离终端，关闭终端不会影响后台进程
+            if libc::setsid() == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    // 加密配置的口令通过管道传给子进程，不放在环境变量中
+    let passphrase_fd = crate::config::encrypt::pass_to_child(&mut cmd)?;
+    let mut child = cmd.spawn()?;
+    drop(passphrase_fd);
+    let start = Instant::now();
+    while start.elapsed() < START_TIMEOUT {
+        if let Some(status) = child.try_wait()? {
+            println!("vnt-cli exited {},{}:", status, log.display());
+            print_log_tail(&log);
+            return Ok(status.code().unwrap_or(exit_code::ERROR));
+        }
+        if crate::command::is_running() {
+            println!("vnt-cli started in background,pid:{}", child.id());
+            return Ok(exit_code::OK);
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    }
+    println!(
+        "vnt-cli did not start in {}s,{}:",
+        START_TIMEOUT.as_secs(),
+        log.display()
+    );
+    print_log_tail(&log);
+    Ok(exit_code::ERROR)
+}
+
+fn print_log_tail(log: &Path) {
+    if let Ok(content) = std::fs::read_to_string(log) {
+        let lines: Vec<&str> = content.lines().collect();
+        for line in &lines[lines.len().saturating_sub(20)..] {
+            println!("  {}", line);
+        }
+    }
+}
+
+/// 命令端口无响应时按pid停止
+pub fn stop(pid: u32) -> i32 {
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+        println!("stop error {}", io::Error::last_os_error());
+        return exit_code::ERROR;
+    }
+    let start = Instant::now();
+    while start.elapsed() < STOP_TIMEOUT {
+        if !is_alive(pid) {
+            remove_pid();
+            println!("stopped");
+            return exit_code::OK;
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+    println!("pid {} did not exit in {}s", pid, STOP_TIMEOUT.as_secs());
+    exit_code::ERROR
+}
+
+pub fn status() -> i32 {
+    let pid = running_pid();
+    if crate::command::is_running() {
+        match pid {
+            Some(pid) => println!("running,pid:{}", pid),
+            None => println!("running"),
+        }
+        exit_code::OK
+    } else if let Some(pid) = pid {
+        println!("pid {} exists but not responding", pid);
+        exit_code::ERROR
+    } else {
+        println!("not running");
+        exit_code::NOT_RUNNING
+    }
+}
@@ -0,0 +1,229 @@
+//! 展示给用户的错误：错误编号、本地化的说明和解决建议，原始错误写入日志
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use console::style;
+
+use crate::exit_code;
+use crate::i18n::{t, t_args};
+
+/// 相同的错误在该时间内只输出一次，避免重连时刷屏
+const REPEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+static REPORTED: OnceLock<Mutex<HashMap<ErrorKind, (Instant, u32)>>> = OnceLock::new();
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ErrorKind {
+    /// 创建异步运行时失败
+    Runtime,
+    /// 配置文件错误
+    Config,
+    AlreadyRunning,
+    /// 连接服务端失败
+    Connect,
+    HandshakeTimeout,
+    /// 服务端不支持加密
+    ServerNotSecret,
+    Token,
+    AddressExhausted,
+    RegisterTimeout,
+    /// 服务端返回的其他错误
+    Server,
+    IpConflict,
+    InvalidIp,
+    DeviceIdConflict,
+    DeviceLimit,
+    CreateIface,
+    /// 网卡创建后启动失败
+    Start,
+    /// 和后台实例的命令通道通信失败
+    Command,
+    /// 没有权限使用命令通道
+    CommandDenied,
+    /// 命令行参数取值无效或互相冲突
+    Args,
+}
+
+impl ErrorKind {
+    /// 错误编号，不随语言变化，便于搜索文档和提交问题
+    pub fn id(&self) -> u16 {
+        match self {
+            ErrorKind::Runtime => 1,
+            ErrorKind::Config => 2,
+            ErrorKind::AlreadyRunning => 3,
+            ErrorKind::Connect => 4,
+            ErrorKind::HandshakeTimeout => 5,
+            ErrorKind::ServerNotSecret => 6,
+            ErrorKind::Token => 7,
+            ErrorKind::AddressExhausted => 8,
+            ErrorKind::RegisterTimeout => 9,
+            ErrorKind::Server => 10,
+            ErrorKind::IpConflict => 11,
+            ErrorKind::InvalidIp => 12,
+            ErrorKind::DeviceIdConflict => 13,
+            ErrorKind::DeviceLimit => 14,
+            ErrorKind::CreateIface => 15,
+            ErrorKind::Start => 16,
+            ErrorKind::Command => 17,
+            ErrorKind::CommandDenied => 18,
+            ErrorKind::Args => 19,
+        }
+    }
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ErrorKind::Runtime | ErrorKind::Server | ErrorKind::Start => exit_code::ERROR,
+            ErrorKind::Config | ErrorKind::InvalidIp | ErrorKind::Args => exit_code::INVALID_ARGS,
+            ErrorKind::AlreadyRunning => exit_code::ALREADY_RUNNING,
+            ErrorKind::Connect | ErrorKind::HandshakeTimeout | ErrorKind::RegisterTimeout => {
+                exit_code::CONNECT_FAILED
+            }
+            ErrorKind::ServerNotSecret | ErrorKind::Token => exit_code::AUTH_FAILED,
+            ErrorKind::AddressExhausted => exit_code::ADDRESS_EXHAUSTED,
+            ErrorKind::IpConflict => exit_code::IP_CONFLICT,
+            ErrorKind::DeviceIdConflict => exit_code::DEVICE_ID_CONFLICT,
+            ErrorKind::DeviceLimit => exit_code::DEVICE_LIMIT,
+            ErrorKind::CreateIface => exit_code::NOT_INSTALLED,
+            ErrorKind::Command => exit_code::NOT_RUNNING,
+            ErrorKind::CommandDenied => exit_code::NOT_ELEVATED,
+        }
+    }
+    /// 翻译文件中的 error-<key> 和 hint-<key>
+    fn key(&self) -> &'static str {
+        match self {
+            ErrorKind::Runtime => "runtime",
+            ErrorKind::Config => "config",
+            ErrorKind::AlreadyRunning => "already-running",
+            ErrorKind::Connect => "connect",
+            ErrorKind::HandshakeTimeout => "handshake-timeout",
+            ErrorKind::ServerNotSecret => "server-not-secret",
+            ErrorKind::Token => "token",
+            ErrorKind::AddressExhausted => "address-exhausted",
+            ErrorKind::RegisterTimeout => "register-timeout",
+            ErrorKind::Server => "server",
+            ErrorKind::IpConflict => "ip-conflict",
+            ErrorKind::InvalidIp => "invalid-ip",
+            ErrorKind::DeviceIdConflict => "device-id-conflict",
+            ErrorKind::DeviceLimit => "device-limit",
+            ErrorKind::CreateIface => "create-iface",
+            ErrorKind::Start => "start",
+            ErrorKind::Command => "command",
+            ErrorKind::CommandDenied => "command-denied",
+            ErrorKind::Args => "args",
+        }
+    }
+}
+
+pub struct UserError {
+    kind: ErrorKind,
+    args: Vec<(&'static str, String)>,
+    cause: Option<String>,
+}
+
+impl UserError {
+    pub fn new(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            args: Vec::new(),
+            cause: None,
+        }
+    }
+    /// 翻译中的参数 { $name }
+    pub fn arg(mut self, name: &'static str, value: impl fmt::Display) -> Self {
+        self.args.push((name, value.to_string()));
+        self
+    }
+    /// 底层的错误，显示在说明之后
+    pub fn cause(mut self, cause: impl fmt::Display) -> Self {
+        self.cause = Some(cause.to_string());
+        self
+    }
+    fn translate(&self, prefix: &str) -> Option<String> {
+        let key = format!("{}-{}", prefix, self.kind.key());
+        if t(&key) == key {
+            return None;
+        }
+        let args: Vec<(&str, &str)> = self.args.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        Some(t_args(&key, &args))
+    }
+    pub fn message(&self) -> String {
+        self.translate("error")
+            .unwrap_or_else(|| format!("{:?}", self.kind))
+    }
+    pub fn hint(&self) -> Option<String> {
+        self.translate("hint")
+    }
+    /// 输出错误并返回对应的退出码，相同的错误短时间内只输出一次
+    pub fn report(&self) -> i32 {
+        let id = self.kind.id();
+        log::error!(
+            "E{:03} {}{}",
+            id,
+            self.message(),
+            self.cause
+                .as_ref()
+                .map(|v| format!(" ({})", v))
+                .unwrap_or_default()
+        );
+        if let Some(repeated) = suppress(self.kind) {
+            self.print(repeated);
+        }
+        self.kind.exit_code()
+    }
+    /// 输出错误，repeated为之前被省略的次数
+    pub fn print(&self, repeated: u32) {
+        println!(
+            "{} {}",
+            style(format!("error[E{:03}]:", self.kind.id())).red(),
+            self.message()
+        );
+        if let Some(cause) = &self.cause {
+            println!("  {} {}", t("error-cause"), cause);
+        }
+        if let Some(hint) = self.hint() {
+            println!("  {} {}", style(t("error-hint")).yellow(), hint);
+        }
+        if repeated > 0 {
+            println!(
+                "  {}",
+                t_args("error-repeated", &[("count", &repeated.to_string())])
+            );
+        }
+    }
+}
+
+/// 需要输出时返回期间被省略的次数
+fn suppress(kind: ErrorKind) -> Option<u32> {
+    let mut reported = REPORTED
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    match reported.get_mut(&kind) {
+        Some((time, count)) if time.elapsed() < REPEAT_INTERVAL => {
+            *count += 1;
+            None
+        }
+        Some((time, count)) => {
+            let repeated = *count;
+            *time = Instant::now();
+            *count = 0;
+            Some(repeated)
+        }
+        None => {
+            reported.insert(kind, (Instant::now(), 0));
+            Some(0)
+        }
+    }
+}
+
+/// vnt库的错误没有携带详细信息的Display，这里取出内部的说明
+pub fn describe(e: &vnt::error::Error) -> String {
+    match e {
+        vnt::error::Error::Io(e) => e.to_string(),
+        vnt::error::Error::Protobuf(e) => e.to_string(),
+        vnt::error::Error::Stop(s) | vnt::error::Error::Warn(s) => s.clone(),
+        e => e.to_string(),
+    }
+}
@@ -0,0 +1,28 @@
+//! 进程退出码，便于脚本判断失败原因
+
+/// 正常退出
+pub const OK: i32 = 0;
+/// 其他错误
+pub const ERROR: i32 = 1;
+/// 参数或配置文件错误
+pub const INVALID_ARGS: i32 = 2;
+/// 没有管理员/root权限
+pub const NOT_ELEVATED: i32 = 3;
+/// 后台服务未运行，查询类命令无法连接
+pub const NOT_RUNNING: i32 = 4;
+/// 已经有实例在运行
+pub const ALREADY_RUNNING: i32 = 5;
+/// token错误或者加密握手失败
+pub const AUTH_FAILED: i32 = 6;
+/// 连接服务器失败
+pub const CONNECT_FAILED: i32 = 7;
+/// 虚拟网卡驱动未安装或者创建网卡失败
+pub const NOT_INSTALLED: i32 = 8;
+/// 虚拟ip已被占用
+pub const IP_CONFLICT: i32 = 9;
+/// 服务端地址已分配完
+pub const ADDRESS_EXHAUSTED: i32 = 10;
+/// device_id和其他设备重复
+pub const DEVICE_ID_CONFLICT: i32 = 11;
+/// token下的设备数达到服务端上限
+pub const DEVICE_LIMIT: i32 = 12;
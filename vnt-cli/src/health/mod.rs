@@ -0,0 +1,91 @@
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch::Receiver;
+
+use vnt::core::Vnt;
+use vnt::handle::ConnectStatus;
+
+/// 读取请求的超时时间，避免一个连接卡住
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 供Docker、Kubernetes等探测的http接口，
+/// /live在进程正常响应时返回200，/ready在注册到服务端且虚拟网卡已经创建后返回200，
+/// /self-test在定时自检没有设备连续失败时返回200，
+/// vnt在启动完成后才会出现在receiver中
+pub async fn start(addr: SocketAddr, vnt: Receiver<Option<Vnt>>) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    log::info!("启动健康检查:{}", addr);
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let vnt = vnt.borrow().clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, vnt).await {
+                log::debug!("health {:?}", e);
+            }
+        });
+    }
+}
+
+async fn handle(stream: TcpStream, vnt: Option<Vnt>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    tokio::time::timeout(READ_TIMEOUT, reader.read_line(&mut line))
+        .await
+        .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+    //GET /ready HTTP/1.1
+    let path = line.split_whitespace().nth(1).unwrap_or("");
+    let path = path.split('?').next().unwrap_or("");
+    let (status, body) = match path {
+        "/live" => live(vnt.as_ref()),
+        "/ready" => ready(vnt.as_ref()),
+        "/self-test" => self_test(vnt.as_ref()),
+        _ => ("404 Not Found", "not found"),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let mut stream = reader.into_inner();
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn live(vnt: Option<&Vnt>) -> (&'static str, &'static str) {
+    match vnt {
+        Some(vnt) if vnt.is_stopped() => ("503 Service Unavailable", "stopped"),
+        //启动过程中(连接服务端、创建网卡)也认为是存活的
+        _ => ("200 OK", "ok"),
+    }
+}
+
+fn ready(vnt: Option<&Vnt>) -> (&'static str, &'static str) {
+    match vnt {
+        None => ("503 Service Unavailable", "starting"),
+        Some(vnt) if vnt.is_stopped() => ("503 Service Unavailable", "stopped"),
+        Some(vnt) => match (vnt.connection_status(), vnt.maintenance().is_some()) {
+            //计划内的维护，和故障区分开
+            (ConnectStatus::Connecting, true) => ("503 Service Unavailable", "maintenance"),
+            (_, true) => ("200 OK", "maintenance"),
+            (ConnectStatus::Connected, false) => ("200 OK", "ok"),
+            //直连仍然可用
+            (ConnectStatus::Degraded, false) => ("200 OK", "degraded"),
+            (ConnectStatus::Connecting, false) => ("503 Service Unavailable", "connecting"),
+        },
+    }
+}
+
+fn self_test(vnt: Option<&Vnt>) -> (&'static str, &'static str) {
+    match vnt {
+        None => ("503 Service Unavailable", "starting"),
+        Some(vnt) if vnt.is_stopped() => ("503 Service Unavailable", "stopped"),
+        Some(vnt) if vnt.self_test_alerting() => ("503 Service Unavailable", "failing"),
+        Some(vnt) if vnt.self_test().is_none() => ("200 OK", "disabled"),
+        Some(_) => ("200 OK", "ok"),
+    }
+}
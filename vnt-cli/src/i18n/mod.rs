@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// 内置的翻译，程序目录下env/lang/<lang>.ftl中的同名条目会覆盖内置翻译，
+/// 也可以放入新的语言文件，不需要重新编译
+const EMBEDDED: [(&str, &str); 2] = [
+    ("zh", include_str!("../../lang/zh.ftl")),
+    ("en", include_str!("../../lang/en.ftl")),
+];
+const DEFAULT_LANG: &str = "zh";
+
+static CATALOG: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// 格式为 key = value，#开头为注释，参数使用 { $name }
+fn parse(text: &str, catalog: &mut HashMap<String, String>) {
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            catalog.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+}
+
+fn embedded(lang: &str) -> Option<&'static str> {
+    EMBEDDED.iter().find(|(l, _)| *l == lang).map(|(_, v)| *v)
+}
+
+/// 优先级：--lang参数、VNT_LANG环境变量、系统语言
+fn detect(lang: Option<String>) -> String {
+    let lang = lang
+        .or_else(|| std::env::var("VNT_LANG").ok())
+        .or_else(|| std::env::var("LC_ALL").ok())
+        .or_else(|| std::env::var("LANG").ok())
+        .unwrap_or(DEFAULT_LANG.to_string());
+    // zh_CN.UTF-8 -> zh
+    let lang = lang
+        .split(|c| c == '_' || c == '-' || c == '.')
+        .next()
+        .unwrap_or(DEFAULT_LANG)
+        .to_lowercase();
+    if lang.is_empty() || lang == "c" || lang == "posix" {
+        DEFAULT_LANG.to_string()
+    } else {
+        lang
+    }
+}
+
+/// 启动时调用一次，缺少的条目依次回退到默认语言和key本身
+pub fn init(lang: Option<String>) {
+    let lang = detect(lang);
+    let mut catalog = HashMap::new();
+    if let Some(text) = embedded(DEFAULT_LANG) {
+        parse(text, &mut catalog);
+    }
+    if let Some(text) = embedded(&lang) {
+        parse(text, &mut catalog);
+    }
+    if let Ok(path) = crate::app_home() {
+        let path = path.join("lang").join(format!("{}.ftl", lang));
+        if let Ok(text) = std::fs::read_to_string(&path) {
+            parse(&text, &mut catalog);
+        }
+    }
+    let _ = CATALOG.set(catalog);
+}
+
+pub fn t(key: &str) -> String {
+    CATALOG
+        .get()
+        .and_then(|catalog| catalog.get(key))
+        .cloned()
+        .unwrap_or_else(|| key.to_string())
+}
+
+pub fn t_args(key: &str, args: &[(&str, &str)]) -> String {
+    let mut value = t(key);
+    for (name, arg) in args {
+        value = value.replace(&format!("{{ ${} }}", name), arg);
+    }
+    value
+}
@@ -0,0 +1,143 @@
+//! 多实例隔离，--instance或者环境变量VNT_INSTANCE指定实例名称后，
+//! 数据目录(pid、命令通道、设备id、缓存等)改为数据目录下的instances/`<name>`，
+//! 同一台机器或者共享数据目录的多个容器可以运行多个实例
+//!
+//! 运行中的实例持有数据目录下的vnt-cli.lock，进程退出(包括异常退出)时系统自动释放，
+//! 文件中记录持有者的信息，冲突时显示
+
+use std::fs::File;
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const INSTANCE_ENV: &str = "VNT_INSTANCE";
+
+/// 实例名称用作目录名
+pub fn check_name(name: &str) -> io::Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && !name.starts_with('.');
+    if valid {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "invalid instance name '{}', use up to 32 letters, digits, '-', '_' or '.'",
+                name
+            ),
+        ))
+    }
+}
+
+/// 当前实例名称，未指定时为None
+pub fn name() -> Option<String> {
+    std::env::var(INSTANCE_ENV)
+        .ok()
+        .filter(|v| check_name(v).is_ok())
+}
+
+fn lock_file() -> io::Result<PathBuf> {
+    Ok(crate::app_home()?.join("vnt-cli.lock"))
+}
+
+/// 持有期间其他进程无法再获取，drop或进程退出时释放
+pub struct InstanceLock {
+    _file: File,
+}
+
+/// 获取锁失败时的错误信息中包含持有者
+pub fn lock(config: Option<&str>) -> io::Result<InstanceLock> {
+    let path = lock_file()?;
+    let mut file = match open(&path) {
+        Ok(file) => file,
+        Err(e) => return Err(conflict(&path, e)),
+    };
+    if let Err(e) = try_lock(&file) {
+        return Err(conflict(&path, e));
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|v| v.as_secs())
+        .unwrap_or(0);
+    let info = format!(
+        "pid: {}\ninstance: {}\nconfig: {}\nstarted: {}\n",
+        std::process::id(),
+        name().unwrap_or_default(),
+        config.unwrap_or_default(),
+        now
+    );
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(info.as_bytes())?;
+    file.flush()?;
+    Ok(InstanceLock { _file: file })
+}
+
+/// 锁被占用时读出持有者的信息
+fn conflict(path: &PathBuf, e: io::Error) -> io::Error {
+    let mut content = String::new();
+    if let Ok(mut file) = File::open(path) {
+        let _ = file.read_to_string(&mut content);
+    }
+    let field = |name: &str| {
+        content
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+    };
+    let holder = match field("pid") {
+        Some(pid) => format!(
+            "held by pid {}, instance '{}', config '{}'",
+            pid,
+            field("instance").unwrap_or("default"),
+            field("config").unwrap_or("-")
+        ),
+        None => format!("{}", e),
+    };
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("{} {}", path.display(), holder),
+    )
+}
+
+#[cfg(unix)]
+fn open(path: &PathBuf) -> io::Result<File> {
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(path)
+}
+
+#[cfg(unix)]
+fn try_lock(file: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// windows下独占写打开，其他进程只能读取持有者信息
+#[cfg(windows)]
+fn open(path: &PathBuf) -> io::Result<File> {
+    use std::os::windows::fs::OpenOptionsExt;
+    //FILE_SHARE_READ
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .share_mode(0x00000001)
+        .open(path)
+}
+
+#[cfg(windows)]
+fn try_lock(_file: &File) -> io::Result<()> {
+    Ok(())
+}
@@ -1,40 +1,148 @@
 use std::io;
-use std::net::{Ipv4Addr, ToSocketAddrs};
-use std::path::PathBuf;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
 
 use console::style;
 use getopts::Options;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::signal;
 
-use common::args_parse::{ips_parse, out_ips_parse};
+use common::args_parse::{
+    advertise_parse, backoff_parse, duration_parse, ips_parse, out_ips_parse, port_range_parse,
+};
+use vnt::channel::broadcast::BroadcastPolicy;
 use vnt::channel::punch::PunchModel;
+use vnt::channel::turn::TurnConfig;
 use vnt::cipher::CipherModel;
-use vnt::core::{Config, Vnt, VntUtil};
+use vnt::core::{Config, ConfigOptions, Vnt, VntUtil};
 use vnt::handle::handshake_handler::HandshakeEnum;
 use vnt::handle::registration_handler::ReqEnum;
+use vnt::route_conflict::RouteConflictPolicy;
+use vnt::split_tunnel::SplitMode;
+use vnt::util::mimicry::Mimicry;
+use vnt::util::proxy::ProxyConfig;
 
+use crate::error::{ErrorKind, UserError};
+use crate::i18n::{t, t_args};
+
+mod alias;
+mod bench;
 mod command;
+mod completions;
 mod config;
+mod connector;
+mod container;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod daemon;
+mod error;
+mod exit_code;
+mod health;
+mod i18n;
+mod instance;
 mod console_out;
+#[cfg(target_os = "linux")]
+mod openwrt;
+mod peer_cache;
+mod preflight;
+mod recorder;
+mod remote;
 mod root_check;
+#[cfg(feature = "desktop")]
+mod service;
+#[cfg(feature = "desktop")]
+mod setup;
+mod socks5;
+mod traceroute;
+mod transfer;
+mod tunnels;
+mod watchdog;
 
+/// 数据目录，--data-dir或者环境变量VNT_HOME指定，默认是程序目录下的env，
+/// 程序目录只读时(如msix安装在WindowsApps下)使用用户目录；
+/// 指定了实例名称时为其下的instances/`<name>`
 pub fn app_home() -> io::Result<PathBuf> {
-    let path = std::env::current_exe()
-        .expect("current_exe error")
-        .parent()
-        .expect("current_exe parent error")
-        .join("env");
+    let home = data_home()?;
+    match instance::name() {
+        Some(name) => {
+            let path = home.join("instances").join(name);
+            if !path.exists() {
+                std::fs::create_dir_all(&path)?;
+            }
+            Ok(path)
+        }
+        None => Ok(home),
+    }
+}
+
+fn data_home() -> io::Result<PathBuf> {
+    if let Some(home) = std::env::var_os("VNT_HOME") {
+        let path = PathBuf::from(home);
+        if !path.exists() {
+            std::fs::create_dir_all(&path)?;
+        }
+        return Ok(path);
+    }
+    let dir = exe_dir();
+    if !exe_dir_writable() {
+        if let Some(path) = user_home() {
+            if !path.exists() {
+                std::fs::create_dir_all(&path)?;
+            }
+            return Ok(path);
+        }
+    }
+    let path = dir.join("env");
     if !path.exists() {
         std::fs::create_dir_all(&path)?;
     }
     Ok(path)
 }
 
+pub fn exe_dir() -> PathBuf {
+    std::env::current_exe()
+        .expect("current_exe error")
+        .parent()
+        .expect("current_exe parent error")
+        .to_path_buf()
+}
+
+/// 在程序目录下创建临时文件判断是否可写，只判断一次
+pub fn exe_dir_writable() -> bool {
+    static WRITABLE: OnceLock<bool> = OnceLock::new();
+    *WRITABLE.get_or_init(|| {
+        let probe = exe_dir().join(format!(".vnt-probe-{}", std::process::id()));
+        match std::fs::File::create(&probe) {
+            Ok(_) => {
+                let _ = std::fs::remove_file(&probe);
+                true
+            }
+            Err(_) => false,
+        }
+    })
+}
+
+#[cfg(target_os = "windows")]
+fn user_home() -> Option<PathBuf> {
+    std::env::var_os("LOCALAPPDATA").map(|v| PathBuf::from(v).join("vnt"))
+}
+
+#[cfg(not(target_os = "windows"))]
+fn user_home() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|v| PathBuf::from(v).join(".vnt"))
+}
+
 fn main() {
+    std::process::exit(run());
+}
+
+fn run() -> i32 {
     let _ = log4rs::init_file("log4rs.yaml", Default::default());
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+    let extra = container::env_args(&args[1..]);
+    args.extend(extra);
     let program = args[0].clone();
     let mut opts = Options::new();
     opts.optopt("k", "", "组网标识", "<token>");
@@ -51,14 +159,98 @@ fn main() {
     opts.optflag("m", "", "模拟组播");
     opts.optopt("u", "", "自定义mtu(默认为1430)", "<mtu>");
     opts.optflag("", "tcp", "tcp");
+    opts.optflag("", "tcp-control", "控制消息使用tcp,数据使用udp");
     opts.optopt("", "ip", "指定虚拟ip", "<ip>");
     opts.optflag("", "relay", "仅使用服务器转发");
     opts.optopt("", "par", "任务并行度(必须为正整数)", "<parallel>");
-    opts.optopt("", "thread", "线程数(必须为正整数)", "<thread>");
+    opts.optopt("", "thread", "工作线程数(必须为正整数)", "<thread>");
+    opts.optopt("", "blocking-thread", "阻塞线程数上限(必须为正整数)", "<thread>");
+    opts.optopt("", "channel-cap", "并行任务的队列长度(必须为正整数)", "<cap>");
+    opts.optflag("", "small", "低资源模式");
+    opts.optopt("", "metric", "网卡跃点(仅windows)", "<metric>");
+    opts.optopt("", "nic", "虚拟网卡名称", "<name>");
+    opts.optflag("", "private-network", "网卡设为专用网络(仅windows)");
+    opts.optflag("", "multi-queue", "多队列网卡(仅linux)");
+    opts.optopt("", "tun-owner", "网卡所属用户(仅linux)", "<user>");
+    opts.optopt("", "tun-group", "网卡所属组(仅linux)", "<group>");
+    opts.optflag("", "drop-caps", "初始化后降低权限(仅linux)");
+    opts.optopt("", "run-as", "降权后切换到该用户(仅linux)", "<user>");
+    opts.optflag("", "seccomp", "收包线程启用seccomp(仅linux)");
+    opts.optopt(
+        "",
+        "privileged-helper",
+        "运行特权辅助进程(仅linux)",
+        "<socket>",
+    );
+    opts.optopt(
+        "",
+        "helper-socket",
+        "通过特权辅助进程创建网卡(仅linux)",
+        "<socket>",
+    );
+    opts.optopt("", "route-table", "使用独立的路由表(仅linux)", "<table>");
+    opts.optopt(
+        "",
+        "route-conflict",
+        "路由和系统路由重叠时的处理方式",
+        "<warn|skip|split>",
+    );
+    opts.optflag("", "kill-switch", "防泄漏,只允许流量经过虚拟网卡");
+    opts.optmulti("", "split-include", "只有指定的程序使用虚拟网卡", "<app>");
+    opts.optmulti("", "split-exclude", "指定的程序不使用虚拟网卡", "<app>");
+    opts.optopt("", "dns-server", "隧道内的dns服务器", "<ip>");
+    opts.optmulti("", "dns-domain", "使用隧道内dns解析的域名后缀", "<domain>");
+    opts.optopt("", "session-idle", "按需连接的空闲时间(秒)", "<seconds>");
+    opts.optopt("", "turn", "外部turn服务器", "<user:password@host:port>");
+    opts.optopt("", "mgmt-key", "远程管理密钥", "<key>");
+    opts.optopt("", "health-addr", "健康检查http地址", "<addr>");
+    opts.optopt("", "socks5", "socks5代理监听地址", "<addr>");
+    opts.optmulti("", "advertise", "发布本机子网(如pod网段)", "<cidr>");
+    opts.optmulti("", "accept-routes", "允许接收的其他设备发布的子网", "<cidr>");
+    opts.optopt(
+        "",
+        "subnet-alias",
+        "虚拟网段和局域网冲突时映射到该网段",
+        "<cidr>",
+    );
+    opts.optopt("", "bridge", "tap网卡和物理网卡桥接", "<nic>");
+    opts.optmulti("", "path-policy", "设备的路径策略", "<ip=policy>");
+    opts.optflag("", "require-encryption", "只和加密的设备通信");
+    opts.optopt("", "broadcast", "广播策略", "<full|limited:pps|disable>");
+    opts.optopt("", "max-clock-skew", "时钟偏差告警阈值(秒)", "<sec>");
+    opts.optmulti("", "self-test", "定时自检的设备", "<peer>");
+    opts.optopt("", "self-test-interval", "自检间隔(分钟)", "<min>");
+    opts.optopt("", "remote", "查询其他节点", "<peer>");
+    opts.optopt("", "send", "发送文件到其他节点", "<peer>");
+    opts.optflagopt("", "recv", "接收其他节点发送的文件", "<dir>");
+    opts.optopt("", "bench", "和其他节点测速", "<peer>");
+    opts.optflag("", "bench-listen", "等待其他节点测速");
+    opts.optopt("", "bench-time", "每项测速时长(秒)", "<sec>");
+    opts.optopt("", "traceroute", "经虚拟网络traceroute", "<peer>");
     opts.optopt("", "model", "加密模式", "<model>");
     opts.optflag("", "finger", "指纹校验");
     opts.optopt("", "punch", "取值ipv4/ipv6", "<punch>");
     opts.optopt("", "port", "监听的端口", "<port>");
+    opts.optopt("", "port-range", "监听的端口范围", "<start-end>");
+    opts.optopt("", "local-ip", "本地监听地址", "<ip>");
+    opts.optopt("", "bind-dev", "底层socket绑定的网卡", "<name>");
+    opts.optopt("", "proxy", "经socks5或http代理连接服务端", "<url>");
+    opts.optopt(
+        "",
+        "mimicry",
+        "和服务端的udp报文伪装成dns或ntp",
+        "<dns|ntp>",
+    );
+    opts.optopt("", "resolver", "解析服务端域名的dns服务器", "<ip[:port]>");
+    opts.optopt(
+        "",
+        "server-grace",
+        "和服务端失联后直连继续使用的时间(秒)",
+        "<sec>",
+    );
+    opts.optopt("", "retry-backoff", "重连的最小-最大间隔", "<min-max>");
+    opts.optmulti("", "peer-addr", "设备的静态地址", "<peer=addr>");
+    opts.optflag("", "debug-keys", "允许导出会话密钥");
     opts.optflag("", "cmd", "开启窗口输入");
     opts.optflag("", "no-proxy", "关闭内置代理");
     opts.optflag("", "first-latency", "优先延迟");
@@ -68,56 +260,492 @@ fn main() {
     opts.optflag("", "all", "后台运行时,查看其他设备完整信息");
     opts.optflag("", "info", "后台运行时,查看当前设备信息");
     opts.optflag("", "route", "后台运行时,查看数据转发路径");
+    opts.optflag("", "verbose", "配合--route显示打洞过程");
+    opts.optflagopt("", "events", "后台运行时,查看最近的连接事件", "<since>");
+    opts.optflag("", "json", "配合--events输出json");
     opts.optflag("", "stop", "停止后台运行");
+    opts.optflag("", "status", "查看是否在后台运行");
+    opts.optflag("", "daemon", "后台运行(仅linux/macos)");
+    opts.optflagopt("", "watch", "配合--list/--all/--info/--route持续刷新", "<sec>");
+    opts.optopt("", "alias-set", "设置设备别名", "<ip,alias>");
+    opts.optopt("", "alias-del", "删除设备别名", "<alias>");
+    opts.optflag("", "alias-list", "查看设备别名");
+    opts.optflag("", "id-reset", "重新生成设备id");
+    opts.optflagopt("", "debug-dump", "打包诊断信息用于提交问题", "<file>");
+    opts.optflagopt("", "debug-export-keys", "后台运行时,导出会话密钥", "<file>");
+    opts.optflagopt("", "debug-dissector", "生成wireshark的lua插件", "<file>");
+    opts.optopt("", "split-add", "后台运行时,添加分流程序", "<app>");
+    opts.optopt("", "split-del", "后台运行时,删除分流程序", "<app>");
+    opts.optflag("", "split-list", "后台运行时,查看分流程序");
+    opts.optopt("", "rekey", "后台运行时,更换组网密码", "<password>");
+    opts.optopt("", "rekey-window", "新旧密码共存时长(分钟)", "<minutes>");
+    opts.optopt("", "tunnel-up", "后台运行时,启动其他组网", "<profile>");
+    opts.optopt("", "tunnel-down", "后台运行时,停止其他组网", "<profile>");
+    opts.optflag("", "tunnel-list", "后台运行时,查看其他组网");
+    opts.optflag("", "install", "安装为OpenWrt服务");
+    opts.optflag("", "quiet", "非交互模式");
+    opts.optflag("", "setup", "交互式配置向导");
+    opts.optopt("", "config-encrypt", "加密配置文件", "<file>");
+    opts.optopt("", "config-decrypt", "解密配置文件", "<file>");
+    opts.optflag("", "keystore", "配合--config-encrypt,密钥保存在系统密钥库");
+    opts.optopt("", "config-migrate", "升级旧版本的配置文件", "<file>");
+    opts.optopt("", "viewer-issue", "生成只读的查看者凭证", "<name>");
+    opts.optopt("", "viewer-revoke", "吊销查看者凭证", "<name>");
+    opts.optflag("", "preflight", "只做启动前检查");
+    opts.optopt("", "lang", "界面语言", "<lang>");
+    opts.optopt("", "data-dir", "数据目录", "<dir>");
+    opts.optopt("", "instance", "实例名称,隔离数据目录", "<name>");
+    opts.optopt("", "completions", "生成shell补全脚本", "<shell>");
+    opts.optflag("", "complete-aliases", "输出设备别名,供补全脚本使用");
+    opts.optflagopt("", "bench-crypto", "测试各加密模式的速度", "<sec>");
     opts.optflag("h", "help", "帮助");
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
         Err(f) => {
+            i18n::init(None);
             print_usage(&program, opts);
             println!("{}", f.to_string());
-            return;
+            return exit_code::INVALID_ARGS;
         }
     };
+    if let Some(dir) = matches.opt_str("data-dir") {
+        //后续通过app_home读取，子进程(如--daemon)也会继承
+        std::env::set_var("VNT_HOME", dir);
+    }
+    if let Some(name) = matches.opt_str("instance") {
+        if let Err(e) = instance::check_name(&name) {
+            println!("'--instance' invalid,{}", e);
+            return exit_code::INVALID_ARGS;
+        }
+        //和--data-dir一样通过环境变量传给app_home和子进程
+        std::env::set_var(instance::INSTANCE_ENV, name);
+    } else if let Ok(name) = std::env::var(instance::INSTANCE_ENV) {
+        if let Err(e) = instance::check_name(&name) {
+            println!("'{}' invalid,{}", instance::INSTANCE_ENV, e);
+            return exit_code::INVALID_ARGS;
+        }
+    }
+    i18n::init(matches.opt_str("lang"));
+    //补全不需要权限，放在权限检查之前
+    if let Some(shell) = matches.opt_str("completions") {
+        let name = PathBuf::from(&program)
+            .file_stem()
+            .map(|v| v.to_string_lossy().to_string())
+            .unwrap_or("vnt-cli".to_string());
+        return match completions::generate(&shell, &name, &opts) {
+            Some(script) => {
+                print!("{}", script);
+                exit_code::OK
+            }
+            None => {
+                println!(
+                    "'--completions {}' invalid,enum: {}",
+                    shell,
+                    completions::SHELLS.join("/")
+                );
+                exit_code::INVALID_ARGS
+            }
+        };
+    }
+    if matches.opt_present("complete-aliases") {
+        for name in alias::load().keys() {
+            println!("{}", name);
+        }
+        return exit_code::OK;
+    }
+    if matches.opt_present("bench-crypto") {
+        let secs = match matches.opt_str("bench-crypto").map(|v| v.parse::<u64>()) {
+            None => 1,
+            Some(Ok(sec)) if sec > 0 => sec,
+            _ => {
+                println!("'--bench-crypto' invalid,must be a positive integer");
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        return bench::crypto(Duration::from_secs(secs));
+    }
     if matches.opt_present("h") || args.len() == 1 {
         print_usage(&program, opts);
-        return;
-    }
-    if !root_check::is_app_elevated() {
-        println!("Please run it with administrator or root privileges");
-        #[cfg(any(target_os = "linux", target_os = "macos"))]
-        sudo::escalate_if_needed().unwrap();
-        return;
+        return exit_code::OK;
     }
-    if matches.opt_present("list") {
-        command::command(command::CommandEnum::List);
-        return;
+    //非交互模式下不等待任何输入，适合脚本调用
+    let quiet = matches.opt_present("quiet");
+    let watch = if matches.opt_present("watch") {
+        match matches.opt_str("watch").map(|v| v.parse::<u64>()) {
+            None => Some(Duration::from_secs(2)),
+            Some(Ok(sec)) if sec > 0 => Some(Duration::from_secs(sec)),
+            _ => {
+                println!("'--watch' invalid,must be a positive integer");
+                return exit_code::INVALID_ARGS;
+            }
+        }
+    } else {
+        None
+    };
+    let query = if matches.opt_present("list") {
+        Some(command::CommandEnum::List)
     } else if matches.opt_present("info") {
-        command::command(command::CommandEnum::Info);
-        return;
-    } else if matches.opt_present("stop") {
-        command::command(command::CommandEnum::Stop);
-        return;
+        Some(command::CommandEnum::Info)
     } else if matches.opt_present("route") {
-        command::command(command::CommandEnum::Route);
-        return;
+        Some(command::CommandEnum::Route(matches.opt_present("verbose")))
     } else if matches.opt_present("all") {
-        command::command(command::CommandEnum::All);
-        return;
+        Some(command::CommandEnum::All)
+    } else if matches.opt_present("events") {
+        let since = match matches.opt_str("events").map(|v| duration_parse(&v)) {
+            None => 0,
+            Some(Ok(secs)) => secs,
+            Some(Err(e)) => {
+                println!("'--events' invalid,{},example: --events=10m", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        Some(command::CommandEnum::Events(
+            since,
+            matches.opt_present("json"),
+        ))
+    } else {
+        None
+    };
+    let mgmt_key = matches
+        .opt_str("mgmt-key")
+        .or_else(|| std::env::var("VNT_MGMT_KEY").ok())
+        .filter(|v| !v.is_empty());
+    if let Some(peer) = matches.opt_str("send") {
+        let file = match matches.free.first() {
+            Some(file) => file,
+            None => {
+                println!("'--send' requires a file,example: --send 10.26.0.5 ./a.zip");
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let key = match mgmt_key {
+            Some(key) => key,
+            None => {
+                println!("'--send' requires '--mgmt-key'");
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        return transfer::send(&peer, file, &key);
+    }
+    if matches.opt_present("recv") {
+        let dir = matches.opt_str("recv").unwrap_or(".".to_string());
+        //没有密钥时生成一次性的，发送端使用该值作为--mgmt-key
+        let key = mgmt_key.unwrap_or_else(|| {
+            let code = remote::hex(&rand::random::<[u8; 4]>());
+            println!("key:{}", code);
+            code
+        });
+        return transfer::recv(&dir, &key);
+    }
+    if matches.opt_present("bench-listen") {
+        let key = mgmt_key.unwrap_or_else(|| {
+            let code = remote::hex(&rand::random::<[u8; 4]>());
+            println!("key:{}", code);
+            code
+        });
+        return bench::peer::listen(&key);
+    }
+    if let Some(peer) = matches.opt_str("traceroute") {
+        return traceroute::run(&peer);
+    }
+    if let Some(peer) = matches.opt_str("bench") {
+        let secs = match matches.opt_str("bench-time").map(|v| v.parse::<u64>()) {
+            None => 5,
+            Some(Ok(sec)) if sec > 0 => sec,
+            _ => {
+                println!("'--bench-time' invalid,must be a positive integer");
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let key = match mgmt_key {
+            Some(key) => key,
+            None => {
+                println!("'--bench' requires '--mgmt-key'");
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        return bench::peer::run(
+            &peer,
+            &key,
+            Duration::from_secs(secs),
+            matches.opt_present("json"),
+        );
+    }
+    if let Some(peer) = matches.opt_str("remote") {
+        let key = match mgmt_key {
+            Some(key) => key,
+            _ => {
+                println!("'--remote' requires '--mgmt-key'");
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        return match query {
+            Some(query) => remote::query(&peer, query, &key),
+            None => {
+                println!("'--remote' must be used with --info/--list/--all/--route");
+                exit_code::INVALID_ARGS
+            }
+        };
+    }
+    if let Some(query) = query {
+        return match watch {
+            None => command::command(query),
+            Some(interval) => command::watch(query, interval),
+        };
+    }
+    if matches.opt_present("stop") {
+        //命令端口无响应时按pid停止
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        if !command::is_running() {
+            if let Some(pid) = daemon::running_pid() {
+                return daemon::stop(pid);
+            }
+        }
+        return command::command(command::CommandEnum::Stop);
+    } else if matches.opt_present("status") {
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        return daemon::status();
+        #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+        return if command::is_running() {
+            println!("running");
+            exit_code::OK
+        } else {
+            println!("not running");
+            exit_code::NOT_RUNNING
+        };
+    } else if let Some(app) = matches.opt_str("split-add") {
+        return command::command(command::CommandEnum::SplitAdd(app));
+    } else if let Some(app) = matches.opt_str("split-del") {
+        return command::command(command::CommandEnum::SplitDel(app));
+    } else if matches.opt_present("split-list") {
+        return command::command(command::CommandEnum::SplitList);
+    } else if let Some(password) = matches.opt_str("rekey") {
+        let window = match matches.opt_get_default("rekey-window", 10u64) {
+            Ok(window) => window,
+            Err(e) => {
+                println!("'--rekey-window' invalid,{},example: --rekey-window 10", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        return command::command(command::CommandEnum::Rekey(password, window));
+    } else if let Some(name) = matches.opt_str("tunnel-up") {
+        return command::command(command::CommandEnum::TunnelUp(name));
+    } else if let Some(name) = matches.opt_str("tunnel-down") {
+        return command::command(command::CommandEnum::TunnelDown(name));
+    } else if matches.opt_present("tunnel-list") {
+        return command::command(command::CommandEnum::TunnelList);
+    } else if matches.opt_present("debug-export-keys") {
+        let out = matches
+            .opt_str("debug-export-keys")
+            .unwrap_or_else(|| format!("vnt-keys-{}.log", command::now()));
+        return command::command(command::CommandEnum::ExportKeys(out));
+    } else if matches.opt_present("debug-dissector") {
+        let out = matches
+            .opt_str("debug-dissector")
+            .unwrap_or_else(|| "vnt.lua".to_string());
+        return match std::fs::write(&out, vnt::protocol::wireshark::dissector()) {
+            Ok(_) => {
+                println!("{}", out);
+                exit_code::OK
+            }
+            Err(e) => {
+                println!("debug dissector error {}", e);
+                exit_code::ERROR
+            }
+        };
+    }
+    //和后台实例通信的命令由命令通道鉴权，其余操作需要管理员权限，
+    //使用特权辅助进程时主进程以普通用户运行
+    if !root_check::is_app_elevated() && !matches.opt_present("helper-socket") {
+        println!("Please run it with administrator or root privileges");
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        if !quiet {
+            sudo::escalate_if_needed().unwrap();
+        }
+        return exit_code::NOT_ELEVATED;
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(path) = matches.opt_str("privileged-helper") {
+        return privileged_helper(&path, &matches);
+    }
+    if let Some(alias) = matches.opt_str("alias-set") {
+        return match alias.split_once(',') {
+            Some((ip, name)) => {
+                if let Err(e) = alias::set(ip, name) {
+                    println!("alias error {}", e);
+                    exit_code::INVALID_ARGS
+                } else {
+                    exit_code::OK
+                }
+            }
+            None => {
+                println!("'--alias-set' invalid,example: --alias-set 10.26.0.7,nas");
+                exit_code::INVALID_ARGS
+            }
+        };
+    } else if let Some(alias) = matches.opt_str("alias-del") {
+        if let Err(e) = alias::remove(&alias) {
+            println!("alias error {}", e);
+            return exit_code::ERROR;
+        }
+        return exit_code::OK;
+    } else if matches.opt_present("alias-list") {
+        alias::print_list();
+        return exit_code::OK;
+    } else if matches.opt_present("id-reset") {
+        return match config::reset_device_id() {
+            Ok(id) => {
+                println!("new device id:{}", id);
+                exit_code::OK
+            }
+            Err(e) => {
+                println!("id reset error {}", e);
+                exit_code::ERROR
+            }
+        };
+    } else if matches.opt_present("debug-dump") {
+        let out = matches
+            .opt_str("debug-dump")
+            .unwrap_or_else(|| format!("vnt-debug-{}.zip", command::now()));
+        return match recorder::dump(&PathBuf::from(&out)) {
+            Ok(_) => {
+                println!("{}", out);
+                exit_code::OK
+            }
+            Err(e) => {
+                println!("debug dump error {}", e);
+                exit_code::ERROR
+            }
+        };
+    } else if matches.opt_present("setup") {
+        if quiet {
+            println!("'--setup' cannot be used with '--quiet'");
+            return exit_code::INVALID_ARGS;
+        }
+        #[cfg(feature = "desktop")]
+        return match setup::setup() {
+            Ok(_) => exit_code::OK,
+            Err(e) => {
+                println!("setup error {}", e);
+                exit_code::ERROR
+            }
+        };
+        #[cfg(not(feature = "desktop"))]
+        {
+            println!("'--setup' is not included in this build");
+            return exit_code::INVALID_ARGS;
+        }
+    } else if let Some(file) = matches.opt_str("config-encrypt") {
+        return match config::encrypt::encrypt_file(
+            Path::new(&file),
+            matches.opt_present("keystore"),
+        ) {
+            Ok(_) => {
+                println!("{} encrypted", file);
+                exit_code::OK
+            }
+            Err(e) => {
+                println!("encrypt error {}", e);
+                exit_code::ERROR
+            }
+        };
+    } else if let Some(file) = matches.opt_str("config-decrypt") {
+        return match config::encrypt::decrypt_file(Path::new(&file)) {
+            Ok(_) => {
+                println!("{} decrypted", file);
+                exit_code::OK
+            }
+            Err(e) => {
+                println!("decrypt error {}", e);
+                exit_code::ERROR
+            }
+        };
+    } else if let Some(file) = matches.opt_str("config-migrate") {
+        return match config::migrate::migrate_file(Path::new(&file)) {
+            Ok(Some(migrated)) => {
+                for note in &migrated.notes {
+                    println!("  {}", note);
+                }
+                println!(
+                    "{} migrated from version {} to {}, backup saved as {}.bak",
+                    file,
+                    migrated.from,
+                    config::migrate::CONFIG_VERSION,
+                    file
+                );
+                exit_code::OK
+            }
+            Ok(None) => {
+                println!("{} is already up to date", file);
+                exit_code::OK
+            }
+            Err(e) => {
+                println!("migrate error {}", e);
+                exit_code::ERROR
+            }
+        };
+    } else if let Some(name) = matches.opt_str("viewer-issue") {
+        return match command::viewer::issue(&name) {
+            Ok(token) => {
+                println!("{}", token);
+                println!(
+                    "viewer '{}' issued, use it with {}=<token> vnt-cli --info",
+                    name,
+                    command::viewer::TOKEN_ENV
+                );
+                exit_code::OK
+            }
+            Err(e) => {
+                println!("viewer issue error {}", e);
+                exit_code::ERROR
+            }
+        };
+    } else if let Some(name) = matches.opt_str("viewer-revoke") {
+        return match command::viewer::revoke(&name) {
+            Ok(true) => {
+                println!("viewer '{}' revoked", name);
+                exit_code::OK
+            }
+            Ok(false) => {
+                println!("viewer '{}' not found", name);
+                exit_code::ERROR
+            }
+            Err(e) => {
+                println!("viewer revoke error {}", e);
+                exit_code::ERROR
+            }
+        };
+    } else if matches.opt_present("install") {
+        #[cfg(target_os = "linux")]
+        return match openwrt::install() {
+            Ok(_) => {
+                println!("installed,edit /etc/config/vnt and run '/etc/init.d/vnt start'");
+                exit_code::OK
+            }
+            Err(e) => {
+                println!("install error {}", e);
+                exit_code::ERROR
+            }
+        };
+        #[cfg(not(target_os = "linux"))]
+        {
+            println!("'--install' only supports OpenWrt");
+            return exit_code::ERROR;
+        }
     }
     let conf = matches.opt_str("f");
-    let (config, cmd) = if conf.is_some() {
-        match config::read_config(&conf.unwrap()) {
+    let (config, cmd, mgmt_key, health_addr, socks5) = if conf.is_some() {
+        match config::read_config(&conf.unwrap(), !quiet) {
             Ok(c) => c,
             Err(e) => {
-                println!("conf err {}", e);
-                return;
+                return UserError::new(ErrorKind::Config).cause(e).report();
             }
         }
     } else {
         if !matches.opt_present("k") {
             print_usage(&program, opts);
             println!("parameter -k not found .");
-            return;
+            return exit_code::INVALID_ARGS;
         }
         let tap = matches.opt_present("a");
         let token: String = matches.opt_get("k").unwrap().unwrap();
@@ -130,7 +758,7 @@ fn main() {
         if device_id.is_empty() {
             print_usage(&program, opts);
             println!("parameter -d not found .");
-            return;
+            return exit_code::INVALID_ARGS;
         }
         let name = matches
             .opt_get_default("n", os_info::get().to_string())
@@ -138,18 +766,23 @@ fn main() {
         let server_address_str = matches
             .opt_get_default("s", "nat1.wherewego.top:29872".to_string())
             .unwrap();
-        let server_address = match server_address_str.to_socket_addrs() {
-            Ok(mut addr) => {
-                if let Some(addr) = addr.next() {
-                    addr
-                } else {
-                    println!("parameter '-s {}' error .", server_address_str);
-                    return;
-                }
+        let resolver = match matches
+            .opt_str("resolver")
+            .map(|v| vnt::util::resolve::parse_server(&v))
+        {
+            None => None,
+            Some(Ok(resolver)) => Some(resolver),
+            Some(Err(e)) => {
+                println!("'--resolver' invalid,{}", e);
+                return exit_code::INVALID_ARGS;
             }
+        };
+        vnt::util::resolve::set(resolver);
+        let server_address = match vnt::util::resolve::lookup_one(&server_address_str) {
+            Ok(addr) => addr,
             Err(e) => {
                 println!("parameter '-s {}' error {}.", server_address_str, e);
-                return;
+                return exit_code::INVALID_ARGS;
             }
         };
         let mut stun_server = matches.opt_strs("e");
@@ -159,7 +792,7 @@ fn main() {
             stun_server.push("stun.qq.com:3478".to_string());
         }
 
-        let in_ip = matches.opt_strs("i");
+        let in_ip = alias::resolve_in_ips(&alias::load(), matches.opt_strs("i"));
         let in_ip = match ips_parse(&in_ip) {
             Ok(in_ip) => in_ip,
             Err(e) => {
@@ -167,7 +800,7 @@ fn main() {
                 println!();
                 println!("-i: {:?} {}", in_ip, e);
                 println!("example: -i 192.168.0.0/24,10.26.0.3");
-                return;
+                return exit_code::INVALID_ARGS;
             }
         };
         let out_ip = matches.opt_strs("o");
@@ -178,16 +811,106 @@ fn main() {
                 println!();
                 println!("-o: {:?} {}", out_ip, e);
                 println!("example: -o 0.0.0.0/0");
-                return;
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let advertise = matches.opt_strs("advertise");
+        let (advertise, masquerade) = match advertise_parse(&advertise) {
+            Ok(advertise) => advertise,
+            Err(e) => {
+                println!(
+                    "'--advertise' invalid,{},example: --advertise 10.244.1.0/24",
+                    e
+                );
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let accept_routes = match out_ips_parse(&matches.opt_strs("accept-routes")) {
+            Ok(accept_routes) => accept_routes,
+            Err(e) => {
+                println!(
+                    "'--accept-routes' invalid,{},example: --accept-routes 203.0.113.0/24",
+                    e
+                );
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let tun_owner = match matches.opt_str("tun-owner").map(|v| config::user_id(&v)) {
+            None => None,
+            Some(Ok(uid)) => Some(uid),
+            Some(Err(e)) => {
+                println!("'--tun-owner' invalid,{}", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let tun_group = match matches.opt_str("tun-group").map(|v| config::group_id(&v)) {
+            None => None,
+            Some(Ok(gid)) => Some(gid),
+            Some(Err(e)) => {
+                println!("'--tun-group' invalid,{}", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let run_as = match matches.opt_str("run-as").map(|v| config::user_ids(&v)) {
+            None => None,
+            Some(Ok(ids)) => Some(ids),
+            Some(Err(e)) => {
+                println!("'--run-as' invalid,{}", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let subnet_alias = match matches.opt_str("subnet-alias") {
+            None => None,
+            Some(alias) => match out_ips_parse(&vec![alias]) {
+                Ok(alias) => Some(alias[0]),
+                Err(e) => {
+                    println!(
+                        "'--subnet-alias' invalid,{},example: --subnet-alias 10.99.0.0/24",
+                        e
+                    );
+                    return exit_code::INVALID_ARGS;
+                }
+            },
+        };
+        let path_policy = match config::path_policy_parse(&matches.opt_strs("path-policy")) {
+            Ok(path_policy) => path_policy,
+            Err(e) => {
+                println!(
+                    "'--path-policy' invalid,{},example: --path-policy 10.26.0.5=never-relay",
+                    e
+                );
+                return exit_code::INVALID_ARGS;
             }
         };
+        let bridge = matches.opt_str("bridge");
+        if bridge.is_some() && !tap {
+            println!("'--bridge' requires '-a',example: -a --bridge eth0");
+            return exit_code::INVALID_ARGS;
+        }
         let password: Option<String> = matches.opt_get("w").unwrap();
+        let require_encryption = matches.opt_present("require-encryption");
+        if require_encryption && password.is_none() {
+            println!(
+                "'--require-encryption' requires '-w',example: -w <password> --require-encryption"
+            );
+            return exit_code::INVALID_ARGS;
+        }
+        let broadcast = match matches.opt_get_default("broadcast", BroadcastPolicy::Full) {
+            Ok(broadcast) => broadcast,
+            Err(e) => {
+                println!(
+                    "'--broadcast' invalid,{},example: --broadcast limited:100",
+                    e
+                );
+                return exit_code::INVALID_ARGS;
+            }
+        };
         let server_encrypt = matches.opt_present("W");
         #[cfg(not(feature = "server_encrypt"))]
         {
             if server_encrypt {
                 println!("Server encryption not supported");
-                return;
+                return exit_code::INVALID_ARGS;
             }
         }
         let simulate_multicast = matches.opt_present("m");
@@ -199,7 +922,7 @@ fn main() {
                     print_usage(&program, opts);
                     println!();
                     println!("'-u {}' {}", mtu, e);
-                    return;
+                    return exit_code::INVALID_ARGS;
                 }
             }
         } else {
@@ -212,16 +935,38 @@ fn main() {
             if virtual_ip.is_unspecified() || virtual_ip.is_broadcast() || virtual_ip.is_multicast()
             {
                 println!("'--ip {}' invalid", virtual_ip);
-                return;
+                return exit_code::INVALID_ARGS;
             }
         }
         let tcp_channel = matches.opt_present("tcp");
+        let tcp_control = matches.opt_present("tcp-control");
         let relay = matches.opt_present("relay");
         let parallel = matches.opt_get::<usize>("par").unwrap().unwrap_or(1);
         if parallel == 0 {
             println!("'--par {}' invalid", parallel);
-            return;
+            return exit_code::INVALID_ARGS;
         }
+        let threads = match matches.opt_get::<usize>("thread") {
+            Ok(threads) => threads.unwrap_or(0),
+            Err(e) => {
+                println!("'--thread' invalid,{}", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let blocking_threads = match matches.opt_get::<usize>("blocking-thread") {
+            Ok(threads) => threads.unwrap_or(0),
+            Err(e) => {
+                println!("'--blocking-thread' invalid,{}", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let channel_capacity = match matches.opt_get::<usize>("channel-cap") {
+            Ok(cap) => cap.unwrap_or(0),
+            Err(e) => {
+                println!("'--channel-cap' invalid,{}", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
 
         let cipher_model = match matches.opt_get::<CipherModel>("model") {
             Ok(model) => {
@@ -230,19 +975,20 @@ fn main() {
                     feature = "server_encrypt",
                     feature = "aes_cbc",
                     feature = "aes_ecb",
-                    feature = "sm4_cbc"
+                    feature = "sm4_cbc",
+                    feature = "chacha20_poly1305"
                 )))]
                 {
                     if password.is_some() && model.is_none() {
                         println!("Encryption not supported");
-                        return;
+                        return exit_code::INVALID_ARGS;
                     }
                 }
                 #[cfg(not(any(feature = "aes_gcm", feature = "server_encrypt")))]
                 {
                     if password.is_some() && model.is_none() {
                         println!("'--model ' undefined");
-                        return;
+                        return exit_code::INVALID_ARGS;
                     }
                     model.unwrap_or(CipherModel::None)
                 }
@@ -251,7 +997,7 @@ fn main() {
             }
             Err(e) => {
                 println!("'--model ' invalid,{}", e);
-                return;
+                return exit_code::INVALID_ARGS;
             }
         };
 
@@ -261,11 +1007,155 @@ fn main() {
             .unwrap()
             .unwrap_or(PunchModel::All);
         let port = matches.opt_get::<u16>("port").unwrap_or(None).unwrap_or(0);
+        let port_range = match matches.opt_str("port-range").map(|v| port_range_parse(&v)) {
+            None => None,
+            Some(Ok(port_range)) => Some(port_range),
+            Some(Err(e)) => {
+                println!("'--port-range' invalid,{}", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let local_ip = match matches.opt_get::<IpAddr>("local-ip") {
+            Ok(local_ip) => local_ip,
+            Err(e) => {
+                println!("'--local-ip' invalid,{}", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
         let cmd = matches.opt_present("cmd");
         #[cfg(feature = "ip_proxy")]
         let no_proxy = matches.opt_present("no-proxy");
         let first_latency = matches.opt_present("first-latency");
-        let config = Config::new(
+        let small = matches.opt_present("small");
+        let metric = match matches.opt_get::<u16>("metric") {
+            Ok(metric) => metric.unwrap_or(0),
+            Err(e) => {
+                println!("'--metric' invalid,{}", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let route_table = match matches.opt_get::<u32>("route-table") {
+            Ok(route_table) => route_table.unwrap_or(0),
+            Err(e) => {
+                println!("'--route-table' invalid,{}", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let route_conflict =
+            match matches.opt_get_default("route-conflict", RouteConflictPolicy::Warn) {
+                Ok(route_conflict) => route_conflict,
+                Err(e) => {
+                    println!(
+                        "'--route-conflict' invalid,{},example: --route-conflict skip",
+                        e
+                    );
+                    return exit_code::INVALID_ARGS;
+                }
+            };
+        let kill_switch = matches.opt_present("kill-switch");
+        let split_include = matches.opt_strs("split-include");
+        let split_exclude = matches.opt_strs("split-exclude");
+        let (split_mode, split_apps) = if !split_include.is_empty() {
+            if !split_exclude.is_empty() {
+                println!("'--split-include' and '--split-exclude' cannot be used together");
+                return exit_code::INVALID_ARGS;
+            }
+            (Some(SplitMode::Include), split_include)
+        } else if !split_exclude.is_empty() {
+            (Some(SplitMode::Exclude), split_exclude)
+        } else {
+            (None, vec![])
+        };
+        let dns_server = match matches.opt_get::<Ipv4Addr>("dns-server") {
+            Ok(dns_server) => dns_server,
+            Err(e) => {
+                println!("'--dns-server' invalid,{}", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let dns_domains = matches.opt_strs("dns-domain");
+        let session_idle = match matches.opt_get::<u32>("session-idle") {
+            Ok(session_idle) => session_idle.unwrap_or(0),
+            Err(e) => {
+                println!("'--session-idle' invalid,{}", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let server_grace = match matches.opt_get::<u32>("server-grace") {
+            Ok(server_grace) => server_grace.unwrap_or(0),
+            Err(e) => {
+                println!("'--server-grace' invalid,{}", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let peer_addrs = match config::peer_addr_parse(&matches.opt_strs("peer-addr")) {
+            Ok(peer_addrs) => peer_addrs,
+            Err(e) => {
+                println!(
+                    "'--peer-addr' invalid,{},example: --peer-addr 10.26.0.5=192.168.1.5:29872",
+                    e
+                );
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let retry_backoff = match matches.opt_str("retry-backoff").map(|v| backoff_parse(&v)) {
+            None => None,
+            Some(Ok(retry_backoff)) => Some(retry_backoff),
+            Some(Err(e)) => {
+                println!(
+                    "'--retry-backoff' invalid,{},example: --retry-backoff 2-60",
+                    e
+                );
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let max_clock_skew = match matches.opt_get_default("max-clock-skew", 30u32) {
+            Ok(max_clock_skew) => max_clock_skew,
+            Err(e) => {
+                println!("'--max-clock-skew' invalid,{}", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let self_test = match config::self_test_parse(&matches.opt_strs("self-test")) {
+            Ok(self_test) => self_test,
+            Err(e) => {
+                println!("'--self-test' invalid,{},example: --self-test 10.26.0.5", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let self_test_interval = match matches.opt_get_default("self-test-interval", 5u32) {
+            Ok(interval) if interval > 0 => interval,
+            Ok(_) => {
+                println!("'--self-test-interval' invalid,must be a positive integer");
+                return exit_code::INVALID_ARGS;
+            }
+            Err(e) => {
+                println!("'--self-test-interval' invalid,{}", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let proxy = match matches.opt_get::<ProxyConfig>("proxy") {
+            Ok(proxy) => proxy,
+            Err(e) => {
+                println!("'--proxy' invalid,{}", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let mimicry = match matches.opt_get::<Mimicry>("mimicry") {
+            Ok(mimicry) => mimicry,
+            Err(e) => {
+                println!("'--mimicry' invalid,{}", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let turn = match matches.opt_get::<TurnConfig>("turn") {
+            Ok(turn) => turn,
+            Err(e) => {
+                println!("'--turn' invalid,{}", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let options = ConfigOptions {
             tap,
             token,
             device_id,
@@ -273,13 +1163,13 @@ fn main() {
             server_address,
             server_address_str,
             stun_server,
-            in_ip,
-            out_ip,
+            in_ips: in_ip,
+            out_ips: out_ip,
             password,
             simulate_multicast,
             mtu,
-            tcp_channel,
-            virtual_ip,
+            tcp: tcp_channel,
+            ip: virtual_ip,
             relay,
             #[cfg(feature = "ip_proxy")]
             no_proxy,
@@ -290,29 +1180,250 @@ fn main() {
             punch_model,
             port,
             first_latency,
+            threads,
+            blocking_threads,
+            channel_capacity,
+            small,
+            metric,
+            route_table,
+            kill_switch,
+            split_mode,
+            split_apps,
+            dns_server,
+            dns_domains,
+            session_idle,
+            tcp_control,
+            turn,
+            local_ip,
+            port_range,
+            bind_device: matches.opt_str("bind-dev"),
+            proxy,
+            advertise_routes: advertise,
+            masquerade_routes: masquerade,
+            accept_routes,
+            bridge,
+            path_policy,
+            require_encryption,
+            broadcast,
+            max_clock_skew,
+            self_test,
+            self_test_interval,
+            route_conflict,
+            subnet_alias,
+            device_name: matches.opt_str("nic"),
+            private_network: matches.opt_present("private-network"),
+            multi_queue: matches.opt_present("multi-queue"),
+            tun_owner,
+            tun_group,
+            drop_caps: matches.opt_present("drop-caps"),
+            run_as,
+            seccomp: matches.opt_present("seccomp"),
+            mimicry,
+            resolver,
+            server_grace,
+            peer_addrs,
+            retry_backoff,
+            debug_keys: matches.opt_present("debug-keys"),
+        };
+        let config = match options.build() {
+            Ok(config) => config,
+            Err(e) => {
+                return UserError::new(ErrorKind::Args).cause(e).report();
+            }
+        };
+        let health_addr = match matches.opt_get::<SocketAddr>("health-addr") {
+            Ok(health_addr) => health_addr,
+            Err(e) => {
+                println!(
+                    "'--health-addr' invalid,{},example: --health-addr 0.0.0.0:8080",
+                    e
+                );
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        let socks5 = match matches.opt_get::<SocketAddr>("socks5") {
+            Ok(socks5) => socks5,
+            Err(e) => {
+                println!("'--socks5' invalid,{},example: --socks5 0.0.0.0:1080", e);
+                return exit_code::INVALID_ARGS;
+            }
+        };
+        (
+            config,
+            cmd,
+            matches.opt_str("mgmt-key"),
+            health_addr,
+            socks5,
         )
-        .unwrap();
-        (config, cmd)
+    };
+    #[cfg(target_os = "linux")]
+    let config = {
+        let mut config = config;
+        config.helper_socket = matches.opt_str("helper-socket");
+        config
     };
     println!("version {}", vnt::VNT_VERSION);
+    if command::is_running() {
+        return UserError::new(ErrorKind::AlreadyRunning).report();
+    }
+    if preflight::report(&preflight::check(&config)) {
+        return exit_code::NOT_INSTALLED;
+    }
+    if matches.opt_present("preflight") {
+        println!("preflight ok");
+        return exit_code::OK;
+    }
+    if matches.opt_present("daemon") {
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        return daemon::start(&args[1..]);
+        #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+        {
+            println!("'--daemon' only supports linux and macos");
+            return exit_code::INVALID_ARGS;
+        }
+    }
+    //持有到进程退出，避免两个进程共用同一个数据目录
+    let _lock = match instance::lock(matches.opt_str("f").as_deref()) {
+        Ok(lock) => lock,
+        Err(e) => {
+            return UserError::new(ErrorKind::AlreadyRunning).cause(e).report();
+        }
+    };
+    main0(config, cmd && !quiet, mgmt_key, health_addr, socks5)
+}
 
-    main0(config, cmd);
-    std::process::exit(0);
+/// 以root运行特权辅助进程，只负责创建网卡和修改路由
+#[cfg(target_os = "linux")]
+fn privileged_helper(path: &str, matches: &getopts::Matches) -> i32 {
+    let owner = match matches.opt_str("tun-owner").map(|v| config::user_id(&v)) {
+        None => None,
+        Some(Ok(uid)) => Some(uid),
+        Some(Err(e)) => {
+            println!("'--tun-owner' invalid,{}", e);
+            return exit_code::INVALID_ARGS;
+        }
+    };
+    let group = match matches.opt_str("tun-group").map(|v| config::group_id(&v)) {
+        None => None,
+        Some(Ok(gid)) => Some(gid),
+        Some(Err(e)) => {
+            println!("'--tun-group' invalid,{}", e);
+            return exit_code::INVALID_ARGS;
+        }
+    };
+    println!("privileged helper listening on {}", path);
+    match vnt::tun_tap_device::helper::serve(path, owner, group) {
+        Ok(_) => exit_code::OK,
+        Err(e) => {
+            println!("privileged helper error {}", e);
+            exit_code::ERROR
+        }
+    }
 }
 
-#[tokio::main]
-async fn main0(config: Config, show_cmd: bool) {
+fn main0(
+    config: Config,
+    show_cmd: bool,
+    mgmt_key: Option<String>,
+    health_addr: Option<SocketAddr>,
+    socks5: Option<SocketAddr>,
+) -> i32 {
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    if let Err(e) = daemon::write_pid() {
+        log::warn!("pid file {:?}", e);
+    }
+    let code = loop {
+        //看门狗触发时丢弃整个运行时重新启动，卡住的任务不会影响新的实例
+        let runtime = match config.build_runtime() {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                break UserError::new(ErrorKind::Runtime).cause(e).report();
+            }
+        };
+        let code = runtime.block_on(main1(
+            config.clone(),
+            show_cmd,
+            mgmt_key.clone(),
+            health_addr,
+            socks5,
+        ));
+        if !watchdog::take_restart() {
+            break code;
+        }
+        runtime.shutdown_timeout(std::time::Duration::from_secs(3));
+        log::warn!("看门狗重启vnt");
+    };
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    daemon::remove_pid();
+    code
+}
+
+async fn main1(
+    config: Config,
+    show_cmd: bool,
+    mgmt_key: Option<String>,
+    health_addr: Option<SocketAddr>,
+    socks5: Option<SocketAddr>,
+) -> i32 {
     let server_encrypt = config.server_encrypt;
-    let mut vnt_util = VntUtil::new(config).unwrap();
+    let in_ips: Vec<(u32, u32)> = config
+        .in_ips
+        .iter()
+        .map(|(dest, mask, _)| (*dest, *mask))
+        .collect();
+    //启动过程中也要能响应存活探测，vnt启动完成后再放入
+    let (health_sender, health_receiver) = tokio::sync::watch::channel(None);
+    if let Some(addr) = health_addr {
+        tokio::spawn(async move {
+            if let Err(e) = health::start(addr, health_receiver).await {
+                log::warn!("health:{:?}", e);
+                println!("health check error :{}", e);
+            }
+        });
+    }
+    if let Err(e) = recorder::save_config(&config) {
+        log::warn!("flight-config.txt {:?}", e);
+    }
+    let mut backoff = config.backoff();
+    let mut vnt_util = match VntUtil::new(config) {
+        Ok(vnt_util) => vnt_util,
+        Err(e) => {
+            return UserError::new(ErrorKind::Start).cause(e).report();
+        }
+    };
     let mut conn_count = 0;
+    //被服务端限流时服务端要求等待的秒数
+    let mut rate_limited = None;
+    //服务端维护中时通知中建议等待的秒数
+    let mut maintenance = None;
     let response = loop {
         if conn_count > 0 {
-            tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+            //带抖动的指数退避，服务端重启后大量设备不会同时重连
+            let delay = if let Some(retry_after) = rate_limited.take() {
+                let delay = backoff.next_delay_after(retry_after);
+                println!(
+                    "{}",
+                    t_args("rate-limited", &[("seconds", &delay.as_secs().to_string())])
+                );
+                delay
+            } else if let Some(retry_after) = maintenance.take() {
+                let delay = backoff.next_delay_after(retry_after);
+                println!(
+                    "{}",
+                    t_args(
+                        "maintenance-retry",
+                        &[("seconds", &delay.as_secs().to_string())]
+                    )
+                );
+                delay
+            } else {
+                backoff.next_delay()
+            };
+            tokio::time::sleep(delay).await;
         }
         conn_count += 1;
         if let Err(e) = vnt_util.connect() {
-            println!("connect server failed {}", e);
-            return;
+            return UserError::new(ErrorKind::Connect).cause(e).report();
         }
         match vnt_util.handshake() {
             Ok(response) => {
@@ -326,13 +1437,13 @@ async fn main0(config: Config, show_cmd: bool) {
                                 HandshakeEnum::NotSecret => {}
                                 HandshakeEnum::KeyError => {}
                                 HandshakeEnum::Timeout => {
-                                    println!("handshake timeout")
+                                    UserError::new(ErrorKind::HandshakeTimeout).report();
                                 }
-                                HandshakeEnum::ServerError(str) => {
-                                    println!("error:{}", str);
+                                HandshakeEnum::RateLimited(retry_after) => {
+                                    rate_limited = Some(retry_after);
                                 }
-                                HandshakeEnum::Other(str) => {
-                                    println!("error:{}", str);
+                                HandshakeEnum::ServerError(str) | HandshakeEnum::Other(str) => {
+                                    UserError::new(ErrorKind::Server).cause(str).report();
                                 }
                             }
                             continue;
@@ -345,47 +1456,55 @@ async fn main0(config: Config, show_cmd: bool) {
                     }
                     Err(e) => match e {
                         ReqEnum::TokenError => {
-                            println!("token error");
-                            return;
+                            return UserError::new(ErrorKind::Token).report();
                         }
                         ReqEnum::AddressExhausted => {
-                            println!("address exhausted");
-                            return;
+                            return UserError::new(ErrorKind::AddressExhausted).report();
                         }
                         ReqEnum::Timeout => {
-                            println!("timeout...");
-                        }
-                        ReqEnum::ServerError(str) => {
-                            println!("error:{}", str);
+                            UserError::new(ErrorKind::RegisterTimeout).report();
                         }
-                        ReqEnum::Other(str) => {
-                            println!("error:{}", str);
+                        ReqEnum::ServerError(str) | ReqEnum::Other(str) => {
+                            UserError::new(ErrorKind::Server).cause(str).report();
                         }
                         ReqEnum::IpAlreadyExists => {
-                            println!("ip already exists");
-                            return;
+                            return UserError::new(ErrorKind::IpConflict).report();
                         }
                         ReqEnum::InvalidIp => {
-                            println!("invalid ip");
-                            return;
+                            return UserError::new(ErrorKind::InvalidIp).report();
+                        }
+                        ReqEnum::DeviceIdConflict => {
+                            return UserError::new(ErrorKind::DeviceIdConflict).report();
+                        }
+                        ReqEnum::RateLimited(retry_after) => {
+                            rate_limited = Some(retry_after);
+                        }
+                        ReqEnum::DeviceLimit => {
+                            return UserError::new(ErrorKind::DeviceLimit).report();
+                        }
+                        ReqEnum::Maintenance(notice) => {
+                            println!(
+                                "{}",
+                                yellow(t_args("maintenance", &[("notice", &notice.to_string())]))
+                            );
+                            maintenance = Some(notice.retry_after);
                         }
                     },
                 }
             }
             Err(e) => match e {
                 HandshakeEnum::NotSecret => {
-                    println!("The server does not support encryption");
-                    return;
+                    return UserError::new(ErrorKind::ServerNotSecret).report();
                 }
                 HandshakeEnum::KeyError => {}
                 HandshakeEnum::Timeout => {
-                    println!("handshake timeout")
+                    UserError::new(ErrorKind::HandshakeTimeout).report();
                 }
-                HandshakeEnum::ServerError(str) => {
-                    println!("error:{}", str);
+                HandshakeEnum::RateLimited(retry_after) => {
+                    rate_limited = Some(retry_after);
                 }
-                HandshakeEnum::Other(str) => {
-                    println!("error:{}", str);
+                HandshakeEnum::ServerError(str) | HandshakeEnum::Other(str) => {
+                    UserError::new(ErrorKind::Server).cause(str).report();
                 }
             },
         }
@@ -393,18 +1512,36 @@ async fn main0(config: Config, show_cmd: bool) {
     println!(" ====== Connect Successfully ====== ");
     println!("virtual_gateway:{}", response.virtual_gateway);
     println!("virtual_ip:{}", green(response.virtual_ip.to_string()));
-    let driver_info = vnt_util.create_iface().unwrap();
+    let driver_info = match vnt_util.create_iface() {
+        Ok(driver_info) => driver_info,
+        Err(e) => {
+            return UserError::new(ErrorKind::CreateIface).cause(e).report();
+        }
+    };
     println!(" ====== Create Network Interface Successfully ====== ");
     println!("name:{}", driver_info.name);
     println!("version:{}", driver_info.version);
+    for x in vnt_util.subnet_collisions() {
+        println!(
+            "{}",
+            yellow(format!(
+                "warning: virtual network overlaps local network {}, \
+                 devices in the overlapping range are unreachable, use --subnet-alias <cidr>",
+                x
+            ))
+        );
+    }
     let mut vnt = match vnt_util.build().await {
         Ok(vnt) => vnt,
         Err(e) => {
-            println!("error:{}", e);
-            return;
+            return UserError::new(ErrorKind::Start)
+                .cause(error::describe(&e))
+                .report();
         }
     };
     println!(" ====== Start Successfully ====== ");
+    health_sender.send_replace(Some(vnt.clone()));
+    let watchdog = watchdog::start(vnt.clone());
     let vnt_c = vnt.clone();
     tokio::spawn(async {
         if let Err(e) = command::server::CommandServer::new().start(vnt_c).await {
@@ -412,6 +1549,39 @@ async fn main0(config: Config, show_cmd: bool) {
             println!("command error :{}", e);
         }
     });
+    tokio::spawn(peer_cache::start(vnt.clone()));
+    tokio::spawn(recorder::start(vnt.clone()));
+    if let Some(addr) = socks5 {
+        let vnt_c = vnt.clone();
+        tokio::spawn(async move {
+            if let Err(e) = socks5::start(addr, vnt_c, in_ips).await {
+                log::warn!("socks5:{:?}", e);
+                println!("socks5 error :{}", e);
+            }
+        });
+    }
+    if let Some(key) = mgmt_key.filter(|v| !v.is_empty()) {
+        let vnt_c = vnt.clone();
+        tokio::spawn(async move {
+            if let Err(e) = remote::start(vnt_c, key).await {
+                log::warn!("remote:{:?}", e);
+                println!("remote management error :{}", e);
+            }
+        });
+    }
+    //kill或者systemctl stop时正常退出
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    {
+        let vnt_c = vnt.clone();
+        tokio::spawn(async move {
+            if let Ok(mut term) = signal::unix::signal(signal::unix::SignalKind::terminate()) {
+                term.recv().await;
+                tunnels::stop_all();
+                let _ = peer_cache::save(&vnt_c);
+                let _ = vnt_c.stop();
+            }
+        });
+    }
     if show_cmd {
         let stdin = tokio::io::stdin();
         let mut cmd = String::new();
@@ -421,9 +1591,17 @@ async fn main0(config: Config, show_cmd: bool) {
             println!("input:list,info,route,all,stop");
             tokio::select! {
                 _ = vnt.wait_stop()=>{
-                    return;
+                    return exit_code::OK;
+                }
+                _ = watchdog.wedged()=>{
+                    tunnels::stop_all();
+                    let _ = vnt.stop();
+                    vnt.wait_stop_ms(std::time::Duration::from_secs(3)).await;
+                    return exit_code::OK;
                 }
                 _ = signal::ctrl_c()=>{
+                    tunnels::stop_all();
+                    let _ = peer_cache::save(&vnt);
                     let _ = vnt.stop();
                     vnt.wait_stop_ms(std::time::Duration::from_secs(3)).await;
                     std::process::exit(0);
@@ -444,13 +1622,27 @@ async fn main0(config: Config, show_cmd: bool) {
             }
         }
     }
-    vnt.wait_stop().await;
+    tokio::select! {
+        _ = vnt.wait_stop() => {}
+        _ = watchdog.wedged() => {
+            tunnels::stop_all();
+            let _ = vnt.stop();
+            vnt.wait_stop_ms(std::time::Duration::from_secs(3)).await;
+        }
+    }
+    exit_code::OK
 }
 
 fn command(cmd: &str, vnt: &Vnt) -> bool {
     if cmd.is_empty() {
         return false;
     }
+    //别名区分大小写，单独处理
+    if let Some(args) = cmd.trim().strip_prefix("alias") {
+        command_alias(args);
+        println!();
+        return true;
+    }
     match cmd.to_lowercase().trim() {
         "list" => {
             let list = command::command_list(&vnt);
@@ -468,7 +1660,12 @@ fn command(cmd: &str, vnt: &Vnt) -> bool {
             let list = command::command_list(&vnt);
             console_out::console_device_list_all(list);
         }
+        "events" => {
+            let list = command::command_events(&vnt, 0);
+            console_out::console_events(list, command::now());
+        }
         "stop" => {
+            let _ = peer_cache::save(vnt);
             let _ = vnt.stop();
             return false;
         }
@@ -478,30 +1675,50 @@ fn command(cmd: &str, vnt: &Vnt) -> bool {
     return true;
 }
 
+fn command_alias(args: &str) {
+    let args: Vec<&str> = args.split_whitespace().collect();
+    let rs = match args.as_slice() {
+        ["set", ip, name] => alias::set(ip, name),
+        ["del", name] => alias::remove(name),
+        [] | ["list"] => {
+            alias::print_list();
+            Ok(())
+        }
+        _ => {
+            println!("alias set <ip> <alias> | alias del <alias> | alias list");
+            Ok(())
+        }
+    };
+    if let Err(e) = rs {
+        println!("alias error {}", e);
+    }
+}
+
 fn print_usage(program: &str, _opts: Options) {
     println!("Usage: {} [options]", program);
     println!("version:{}", vnt::VNT_VERSION);
     println!("Options:");
     println!(
         "  -k <token>          {}",
-        green("使用相同的token,就能组建一个局域网络".to_string())
+        green(t("usage-k"))
     );
-    println!("  -n <name>           给设备一个名字,便于区分不同设备,默认使用系统版本");
-    println!("  -d <id>             设备唯一标识符,不使用--ip参数时,服务端凭此参数分配虚拟ip");
-    println!("  -s <server>         注册和中继服务器地址");
-    println!("  -e <stun-server>    stun服务器,用于探测NAT类型,可多次指定,如-e addr1 -e addr2");
-    println!("  -a                  使用tap模式,默认使用tun模式");
-    println!("  -i <in-ip>          配置点对网(IP代理)时使用,-i 192.168.0.0/24,10.26.0.3表示允许接收网段192.168.0.0/24的数据");
-    println!("                      并转发到10.26.0.3,可指定多个网段");
-    println!("  -o <out-ip>         配置点对网时使用,-o 192.168.0.0/24表示允许将数据转发到192.168.0.0/24,可指定多个网段");
+    println!("  -n <name>           {}", t("usage-n"));
+    println!("  -d <id>             {}", t("usage-d"));
+    println!("  -s <server>         {}", t("usage-s"));
+    println!("  -e <stun-server>    {}", t("usage-e"));
+    println!("  -a                  {}", t("usage-a"));
+    println!("  -i <in-ip>          {}", t("usage-i"));
+    println!("                      {}", t("usage-i-more"));
+    println!("  -o <out-ip>         {}", t("usage-o"));
     #[cfg(not(feature = "ip_proxy"))]
-    println!("                      注意需要在系统配置ip转发才可正常使用");
+    println!("                      {}", t("usage-ip-forward"));
     #[cfg(not(any(
         feature = "aes_gcm",
         feature = "server_encrypt",
         feature = "aes_cbc",
         feature = "aes_ecb",
-        feature = "sm4_cbc"
+        feature = "sm4_cbc",
+        feature = "chacha20_poly1305"
     )))]
     let enums = String::new();
     #[cfg(any(
@@ -509,7 +1726,8 @@ fn print_usage(program: &str, _opts: Options) {
         feature = "server_encrypt",
         feature = "aes_cbc",
         feature = "aes_ecb",
-        feature = "sm4_cbc"
+        feature = "sm4_cbc",
+        feature = "chacha20_poly1305"
     ))]
     let mut enums = String::new();
     #[cfg(any(feature = "aes_gcm", feature = "server_encrypt"))]
@@ -520,57 +1738,245 @@ fn print_usage(program: &str, _opts: Options) {
     enums.push_str("/aes_ecb");
     #[cfg(feature = "sm4_cbc")]
     enums.push_str("/sm4_cbc");
+    #[cfg(feature = "chacha20_poly1305")]
+    enums.push_str("/chacha20_poly1305");
+    #[cfg(any(
+        feature = "aes_gcm",
+        feature = "server_encrypt",
+        feature = "chacha20_poly1305"
+    ))]
+    enums.push_str("/auto");
     if !enums.is_empty() {
-        println!("  -w <password>       使用该密码生成的密钥对客户端数据进行加密,并且服务端无法解密,使用相同密码的客户端才能通信");
+        println!("  -w <password>       {}", t("usage-w"));
     }
     #[cfg(feature = "server_encrypt")]
-    println!("  -W                  加密当前客户端和服务端通信的数据,请留意服务端指纹是否正确");
-    println!("  -m                  模拟组播,默认情况下组播数据会被当作广播发送,开启后会模拟真实组播的数据发送");
-    println!("  -u <mtu>            自定义mtu(不加密默认为1450，加密默认为1410)");
-    println!("  -f <conf_file>      读取配置文件中的配置");
+    println!("  -W                  {}", t("usage-W"));
+    println!("  -m                  {}", t("usage-m"));
+    println!("  -u <mtu>            {}", t("usage-u"));
+    println!("  -f <conf_file>      {}", t("usage-f"));
 
-    println!("  --tcp               和服务端使用tcp通信,默认使用udp,遇到udp qos时可指定使用tcp");
-    println!("  --ip <ip>           指定虚拟ip,指定的ip不能和其他设备重复,必须有效并且在服务端所属网段下,默认情况由服务端分配");
-    println!("  --relay             仅使用服务器转发,不使用p2p,默认情况允许使用p2p");
-    println!("  --par <parallel>    任务并行度(必须为正整数),默认值为1");
+    println!("  --tcp               {}", t("usage-tcp"));
+    println!("  --tcp-control       {}", t("usage-tcp-control"));
+    println!("  --ip <ip>           {}", t("usage-ip"));
+    println!("  --relay             {}", t("usage-relay"));
+    println!("  --par <parallel>    {}", t("usage-par"));
+    println!("  --thread <thread>   {}", t("usage-thread"));
+    println!("  --blocking-thread <thread> {}", t("usage-blocking-thread"));
+    println!("  --channel-cap <cap> {}", t("usage-channel-cap"));
+    println!("  --small             {}", t("usage-small"));
+    #[cfg(target_os = "windows")]
+    println!("  --metric <metric>   {}", t("usage-metric"));
+    println!("  --nic <name>        {}", t("usage-nic"));
+    #[cfg(target_os = "windows")]
+    println!("  --private-network   {}", t("usage-private-network"));
+    #[cfg(target_os = "linux")]
+    println!("  --multi-queue       {}", t("usage-multi-queue"));
+    #[cfg(target_os = "linux")]
+    println!("  --tun-owner <user>  {}", t("usage-tun-owner"));
+    #[cfg(target_os = "linux")]
+    println!("  --tun-group <group> {}", t("usage-tun-group"));
+    #[cfg(target_os = "linux")]
+    println!("  --drop-caps         {}", t("usage-drop-caps"));
+    #[cfg(target_os = "linux")]
+    println!("  --run-as <user>     {}", t("usage-run-as"));
+    #[cfg(target_os = "linux")]
+    println!("  --seccomp           {}", t("usage-seccomp"));
+    #[cfg(target_os = "linux")]
+    println!(
+        "  --privileged-helper <socket> {}",
+        t("usage-privileged-helper")
+    );
+    #[cfg(target_os = "linux")]
+    println!("  --helper-socket <socket> {}", t("usage-helper-socket"));
+    #[cfg(target_os = "linux")]
+    println!("  --route-table <table> {}", t("usage-route-table"));
+    println!("  --route-conflict <policy> {}", t("usage-route-conflict"));
+    #[cfg(any(target_os = "linux", target_os = "windows"))]
+    println!("  --kill-switch       {}", t("usage-kill-switch"));
+    #[cfg(target_os = "linux")]
+    println!("  --split-include <app> {}", t("usage-split-include-linux"));
+    #[cfg(target_os = "linux")]
+    println!("  --split-exclude <app> {}", t("usage-split-exclude-linux"));
+    #[cfg(target_os = "windows")]
+    println!("  --split-include <app> {}", t("usage-split-include-windows"));
+    #[cfg(target_os = "windows")]
+    println!("  --split-exclude <app> {}", t("usage-split-exclude-windows"));
+    #[cfg(any(target_os = "linux", target_os = "windows"))]
+    println!("  --dns-server <ip>   {}", t("usage-dns-server"));
+    #[cfg(any(target_os = "linux", target_os = "windows"))]
+    println!("  --dns-domain <domain> {}", t("usage-dns-domain"));
+    println!("  --session-idle <seconds> {}", t("usage-session-idle"));
+    println!("  --turn <user:password@host:port> {}", t("usage-turn"));
+    println!("  --mgmt-key <key>    {}", t("usage-mgmt-key"));
+    println!("  --health-addr <addr> {}", t("usage-health-addr"));
+    println!("  --socks5 <addr>     {}", t("usage-socks5"));
+    println!("  --advertise <cidr>  {}", t("usage-advertise"));
+    println!("  --accept-routes <cidr> {}", t("usage-accept-routes"));
+    println!("  --subnet-alias <cidr> {}", t("usage-subnet-alias"));
+    #[cfg(any(target_os = "linux", target_os = "windows"))]
+    println!("  --bridge <nic>      {}", t("usage-bridge"));
+    println!("  --path-policy <ip=policy> {}", t("usage-path-policy"));
+    println!("  --require-encryption {}", t("usage-require-encryption"));
+    println!("  --broadcast <policy> {}", t("usage-broadcast"));
+    println!("  --max-clock-skew <sec> {}", t("usage-max-clock-skew"));
+    println!("  --self-test <peer>  {}", t("usage-self-test"));
+    println!(
+        "  --self-test-interval <min> {}",
+        t("usage-self-test-interval")
+    );
     if !enums.is_empty() {
         println!(
-            "  --model <model>     加密模式(默认aes_gcm)，可选值{}",
-            &enums[1..]
+            "  --model <model>     {}",
+            t_args("usage-model", &[("enums", &enums[1..])])
         );
     }
     if !enums.is_empty() {
-        println!("  --finger            增加数据指纹校验，可增加安全性，如果服务端开启指纹校验，则客户端也必须开启");
+        println!("  --finger            {}", t("usage-finger"));
+        println!("  --bench-crypto[=sec] {}", t("usage-bench-crypto"));
     }
-    println!("  --punch <punch>     取值ipv4/ipv6，ipv4表示仅使用ipv4打洞");
-    println!("  --port <port>       取值0~65535，指定本地监听的端口，默认取随机端口");
-    println!("  --cmd               开启交互式命令，使用此参数开启控制台输入");
+    println!("  --punch <punch>     {}", t("usage-punch"));
+    println!("  --port <port>       {}", t("usage-port"));
+    println!("  --port-range <start-end> {}", t("usage-port-range"));
+    println!("  --local-ip <ip>     {}", t("usage-local-ip"));
+    println!("  --bind-dev <name>   {}", t("usage-bind-dev"));
+    println!("  --proxy <url>       {}", t("usage-proxy"));
+    println!("  --mimicry <dns|ntp> {}", t("usage-mimicry"));
+    println!("  --resolver <ip>     {}", t("usage-resolver"));
+    println!("  --server-grace <sec> {}", t("usage-server-grace"));
+    println!("  --peer-addr <peer=addr> {}", t("usage-peer-addr"));
+    println!("  --retry-backoff <min-max> {}", t("usage-retry-backoff"));
+    println!("  --debug-keys        {}", t("usage-debug-keys"));
+    println!("  --cmd               {}", t("usage-cmd"));
     #[cfg(feature = "ip_proxy")]
-    println!("  --no-proxy          关闭内置代理，如需点对网则需要配置网卡NAT转发");
-    println!("  --first-latency     优先低延迟的通道，默认情况优先使用p2p通道");
+    println!("  --no-proxy          {}", t("usage-no-proxy"));
+    println!("  --first-latency     {}", t("usage-first-latency"));
 
     println!();
     println!(
         "  --list              {}",
-        yellow("后台运行时,查看其他设备列表".to_string())
+        yellow(t("usage-list"))
     );
     println!(
         "  --all               {}",
-        yellow("后台运行时,查看其他设备完整信息".to_string())
+        yellow(t("usage-all"))
     );
     println!(
         "  --info              {}",
-        yellow("后台运行时,查看当前设备信息".to_string())
+        yellow(t("usage-info"))
     );
     println!(
         "  --route             {}",
-        yellow("后台运行时,查看数据转发路径".to_string())
+        yellow(t("usage-route"))
+    );
+    println!(
+        "  --verbose           {}",
+        yellow(t("usage-verbose"))
+    );
+    println!(
+        "  --events[=since]    {}",
+        yellow(t("usage-events"))
+    );
+    println!(
+        "  --json              {}",
+        yellow(t("usage-json"))
+    );
+    println!(
+        "  --watch[=sec]       {}",
+        yellow(t("usage-watch"))
     );
     println!(
         "  --stop              {}",
-        yellow("停止后台运行".to_string())
+        yellow(t("usage-stop"))
+    );
+    println!(
+        "  --status            {}",
+        yellow(t("usage-status"))
+    );
+    println!(
+        "  --remote <peer>     {}",
+        yellow(t("usage-remote"))
+    );
+    println!(
+        "  --send <peer> <file> {}",
+        yellow(t("usage-send"))
+    );
+    println!(
+        "  --recv[=dir]        {}",
+        yellow(t("usage-recv"))
+    );
+    println!(
+        "  --bench <peer>      {}",
+        yellow(t("usage-bench"))
+    );
+    println!(
+        "  --bench-listen      {}",
+        yellow(t("usage-bench-listen"))
+    );
+    println!(
+        "  --bench-time <sec>  {}",
+        yellow(t("usage-bench-time"))
+    );
+    println!(
+        "  --traceroute <peer> {}",
+        yellow(t("usage-traceroute"))
+    );
+    #[cfg(any(target_os = "linux", target_os = "windows"))]
+    {
+        println!(
+            "  --split-add <app>   {}",
+            yellow(t("usage-split-add"))
+        );
+        println!(
+            "  --split-del <app>   {}",
+            yellow(t("usage-split-del"))
+        );
+        println!(
+            "  --split-list        {}",
+            yellow(t("usage-split-list"))
+        );
+    }
+    println!(
+        "  --rekey <password>  {}",
+        yellow(t("usage-rekey"))
+    );
+    println!(
+        "  --rekey-window <minutes> {}",
+        yellow(t("usage-rekey-window"))
+    );
+    println!("  --tunnel-up <profile> {}", yellow(t("usage-tunnel-up")));
+    println!(
+        "  --tunnel-down <profile> {}",
+        yellow(t("usage-tunnel-down"))
+    );
+    println!("  --tunnel-list       {}", yellow(t("usage-tunnel-list")));
+    println!("  --alias-set <ip,alias> {}", t("usage-alias-set"));
+    println!("  --alias-del <alias> {}", t("usage-alias-del"));
+    println!("  --alias-list        {}", t("usage-alias-list"));
+    println!("  --id-reset          {}", t("usage-id-reset"));
+    println!("  --debug-dump[=file] {}", t("usage-debug-dump"));
+    println!(
+        "  --debug-export-keys[=file] {}",
+        yellow(t("usage-debug-export-keys"))
     );
-    println!("  -h, --help          帮助");
+    println!("  --debug-dissector[=file] {}", t("usage-debug-dissector"));
+    #[cfg(target_os = "linux")]
+    println!("  --install           {}", t("usage-install"));
+    println!("  --setup             {}", t("usage-setup"));
+    println!("  --config-encrypt <file> {}", t("usage-config-encrypt"));
+    println!("  --config-decrypt <file> {}", t("usage-config-decrypt"));
+    println!("  --keystore          {}", t("usage-keystore"));
+    println!("  --config-migrate <file> {}", t("usage-config-migrate"));
+    println!("  --viewer-issue <name> {}", t("usage-viewer-issue"));
+    println!("  --viewer-revoke <name> {}", t("usage-viewer-revoke"));
+    println!("  --preflight         {}", t("usage-preflight"));
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    println!("  --daemon            {}", t("usage-daemon"));
+    println!("  --completions <shell> {}", t("usage-completions"));
+    println!("  --quiet             {}", t("usage-quiet"));
+    println!("  --lang <lang>       {}", t("usage-lang"));
+    println!("  --data-dir <dir>    {}", t("usage-data-dir"));
+    println!("  --instance <name>   {}", t("usage-instance"));
+    println!("  -h, --help          {}", t("usage-help"));
 }
 
 fn green(str: String) -> impl std::fmt::Display {
@@ -0,0 +1,168 @@
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+const OPENWRT_RELEASE: &str = "/etc/openwrt_release";
+const INIT_SCRIPT_PATH: &str = "/etc/init.d/vnt";
+const UCI_CONFIG_PATH: &str = "/etc/config/vnt";
+
+/// procd启动脚本，从uci配置(/etc/config/vnt)中读取参数
+const INIT_SCRIPT: &str = r#"#!/bin/sh /etc/rc.common
+
+USE_PROCD=1
+START=99
+STOP=10
+
+append_arg() {
+	local cfg="$1" option="$2" arg="$3" value
+	config_get value "$cfg" "$option"
+	[ -n "$value" ] && procd_append_param command "$arg" "$value"
+}
+
+append_bool() {
+	local cfg="$1" option="$2" arg="$3" value
+	config_get_bool value "$cfg" "$option" 0
+	[ "$value" -eq 1 ] && procd_append_param command "$arg"
+}
+
+append_list_item() {
+	procd_append_param command "$2" "$1"
+}
+
+start_instance() {
+	local cfg="$1" enabled
+	config_get_bool enabled "$cfg" enabled 0
+	[ "$enabled" -eq 1 ] || return 0
+
+	procd_open_instance "$cfg"
+	procd_set_param command {exe}
+	append_arg "$cfg" token -k
+	append_arg "$cfg" name -n
+	append_arg "$cfg" device_id -d
+	append_arg "$cfg" server_address -s
+	config_list_foreach "$cfg" stun_server append_list_item -e
+	config_list_foreach "$cfg" in_ips append_list_item -i
+	config_list_foreach "$cfg" out_ips append_list_item -o
+	config_list_foreach "$cfg" split_include append_list_item --split-include
+	config_list_foreach "$cfg" split_exclude append_list_item --split-exclude
+	config_list_foreach "$cfg" dns_domains append_list_item --dns-domain
+	append_arg "$cfg" password -w
+	append_arg "$cfg" mtu -u
+	append_arg "$cfg" ip --ip
+	append_arg "$cfg" parallel --par
+	append_arg "$cfg" cipher_model --model
+	append_arg "$cfg" punch_model --punch
+	append_arg "$cfg" port --port
+	append_arg "$cfg" port_range --port-range
+	append_arg "$cfg" local_ip --local-ip
+	append_arg "$cfg" bind_dev --bind-dev
+	append_arg "$cfg" proxy --proxy
+	append_arg "$cfg" threads --thread
+	append_arg "$cfg" route_table --route-table
+	append_arg "$cfg" dns_server --dns-server
+	append_arg "$cfg" session_idle --session-idle
+	append_arg "$cfg" turn --turn
+	append_arg "$cfg" mgmt_key --mgmt-key
+	append_arg "$cfg" health_addr --health-addr
+	append_arg "$cfg" socks5 --socks5
+	append_bool "$cfg" tap -a
+	append_bool "$cfg" server_encrypt -W
+	append_bool "$cfg" simulate_multicast -m
+	append_bool "$cfg" tcp --tcp
+	append_bool "$cfg" tcp_control --tcp-control
+	append_bool "$cfg" relay --relay
+	append_bool "$cfg" finger --finger
+	append_bool "$cfg" no_proxy --no-proxy
+	append_bool "$cfg" first_latency --first-latency
+	append_bool "$cfg" small --small
+	append_bool "$cfg" kill_switch --kill-switch
+	procd_set_param respawn
+	procd_set_param stdout 1
+	procd_set_param stderr 1
+	procd_close_instance
+}
+
+start_service() {
+	config_load vnt
+	config_foreach start_instance vnt
+}
+
+service_triggers() {
+	procd_add_reload_trigger vnt
+}
+"#;
+
+/// 默认的uci配置，需要修改token并将enabled置为1
+const UCI_CONFIG: &str = r#"config vnt 'main'
+	option enabled '0'
+	option token ''
+	option server_address 'nat1.wherewego.top:29872'
+	option small '1'
+	# list in_ips '192.168.1.0/24,10.26.0.3'
+	# list out_ips '0.0.0.0/0'
+"#;
+
+pub fn is_openwrt() -> bool {
+    Path::new(OPENWRT_RELEASE).exists()
+}
+
+/// 安装为OpenWrt服务，生成procd启动脚本，并在不存在时生成默认的uci配置
+pub fn install() -> io::Result<()> {
+    if !is_openwrt() {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "not openwrt"));
+    }
+    let exe = std::env::current_exe()?;
+    let script = INIT_SCRIPT.replace("{exe}", &shell_quote(&exe.to_string_lossy()));
+    std::fs::write(INIT_SCRIPT_PATH, script)?;
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(INIT_SCRIPT_PATH, std::fs::Permissions::from_mode(0o755))?;
+    }
+    if !Path::new(UCI_CONFIG_PATH).exists() {
+        std::fs::write(UCI_CONFIG_PATH, UCI_CONFIG)?;
+    }
+    let status = Command::new(INIT_SCRIPT_PATH).arg("enable").status()?;
+    if !status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("{} enable failed {}", INIT_SCRIPT_PATH, status),
+        ));
+    }
+    Ok(())
+}
+
+/// 启动脚本中的路径用单引号包裹，路径中的单引号替换为'\''
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// 写入uci配置并启用
+pub fn configure(token: &str, server_address: &str, name: &str) -> io::Result<()> {
+    for (option, value) in [
+        ("token", token),
+        ("server_address", server_address),
+        ("name", name),
+        ("enabled", "1"),
+    ] {
+        uci(&["set", &format!("vnt.main.{}={}", option, value)])?;
+    }
+    uci(&["commit", "vnt"])
+}
+
+fn uci(args: &[&str]) -> io::Result<()> {
+    let status = Command::new("uci").args(args).status()?;
+    if !status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("uci {:?} failed {}", args, status),
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_shell_quote() {
+    assert_eq!(shell_quote("/usr/bin/vnt-cli"), "'/usr/bin/vnt-cli'");
+    assert_eq!(shell_quote("/opt/my vnt/vnt-cli"), "'/opt/my vnt/vnt-cli'");
+    assert_eq!(shell_quote("/opt/a'b;$(x)"), "'/opt/a'\\''b;$(x)'");
+}
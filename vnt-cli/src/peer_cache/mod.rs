@@ -0,0 +1,189 @@
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use vnt::channel::punch::{NatInfo, NatType};
+use vnt::core::Vnt;
+use vnt::handle::{PeerDeviceInfo, PeerDeviceStatus};
+
+/// 缓存保存间隔
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+/// 超过这个时间的缓存认为nat映射已经失效
+const EXPIRE_SECS: u64 = 24 * 60 * 60;
+
+/// 上一次打通的设备地址，启动时直接用这些地址打洞，不用等服务端转发nat信息；
+/// 以及服务端下发的设备列表，服务端不可用时用于查看设备
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct PeerCache {
+    time: u64,
+    peers: Vec<PeerItem>,
+    /// 保存设备列表的时间
+    #[serde(default)]
+    directory_time: u64,
+    #[serde(default)]
+    directory: Vec<DirectoryItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct DirectoryItem {
+    virtual_ip: Ipv4Addr,
+    name: String,
+    online: bool,
+    client_secret: bool,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct PeerItem {
+    virtual_ip: Ipv4Addr,
+    public_ips: Vec<Ipv4Addr>,
+    public_port: u16,
+    public_port_range: u16,
+    cone: bool,
+    local_ipv4: Option<Ipv4Addr>,
+    ipv6: Option<Ipv6Addr>,
+    udp_port: u16,
+    tcp_port: u16,
+}
+
+impl PeerItem {
+    fn new(virtual_ip: Ipv4Addr, nat_info: &NatInfo) -> Self {
+        Self {
+            virtual_ip,
+            public_ips: nat_info.public_ips.clone(),
+            public_port: nat_info.public_port,
+            public_port_range: nat_info.public_port_range,
+            cone: nat_info.nat_type == NatType::Cone,
+            local_ipv4: nat_info.local_ipv4(),
+            ipv6: nat_info.ipv6(),
+            udp_port: nat_info.udp_port(),
+            tcp_port: nat_info.tcp_port,
+        }
+    }
+    fn into_nat_info(self) -> (Ipv4Addr, NatInfo) {
+        let nat_type = if self.cone {
+            NatType::Cone
+        } else {
+            NatType::Symmetric
+        };
+        let nat_info = NatInfo::new(
+            self.public_ips,
+            self.public_port,
+            self.public_port_range,
+            self.local_ipv4,
+            self.ipv6,
+            self.udp_port,
+            self.tcp_port,
+            nat_type,
+        );
+        (self.virtual_ip, nat_info)
+    }
+}
+
+fn cache_file() -> io::Result<PathBuf> {
+    Ok(crate::app_home()?.join("peer-cache.json"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|v| v.as_secs())
+        .unwrap_or(0)
+}
+
+fn read() -> Option<PeerCache> {
+    let data = cache_file().and_then(std::fs::read).ok()?;
+    match serde_json::from_slice(&data) {
+        Ok(cache) => Some(cache),
+        Err(e) => {
+            log::warn!("peer-cache.json {:?}", e);
+            None
+        }
+    }
+}
+
+fn load() -> Vec<(Ipv4Addr, NatInfo)> {
+    let cache = match read() {
+        Some(cache) => cache,
+        None => return Vec::new(),
+    };
+    if now().saturating_sub(cache.time) > EXPIRE_SECS {
+        return Vec::new();
+    }
+    cache.peers.into_iter().map(|v| v.into_nat_info()).collect()
+}
+
+/// 上次保存的设备列表和保存时间(unix秒)，设备列表不会过期，只用于展示
+pub fn load_directory() -> Option<(u64, Vec<PeerDeviceInfo>)> {
+    let cache = read()?;
+    if cache.directory.is_empty() {
+        return None;
+    }
+    let list = cache
+        .directory
+        .into_iter()
+        .map(|v| {
+            let status = if v.online { 0 } else { 1 };
+            PeerDeviceInfo::new(
+                v.virtual_ip,
+                v.name,
+                status,
+                v.client_secret,
+                v.tags,
+                Vec::new(),
+            )
+        })
+        .collect();
+    Some((cache.directory_time, list))
+}
+
+pub fn save(vnt: &Vnt) -> io::Result<()> {
+    let peers = vnt.peer_cache();
+    let directory = vnt.device_list();
+    if peers.is_empty() && directory.is_empty() {
+        return Ok(());
+    }
+    // 没有直连的设备或者设备列表为空时保留旧的缓存
+    let mut cache = read().unwrap_or_default();
+    if !peers.is_empty() {
+        cache.time = now();
+        cache.peers = peers
+            .iter()
+            .map(|(ip, nat_info)| PeerItem::new(*ip, nat_info))
+            .collect();
+    }
+    if !directory.is_empty() {
+        cache.directory_time = now();
+        cache.directory = directory
+            .into_iter()
+            .map(|v| DirectoryItem {
+                virtual_ip: v.virtual_ip,
+                name: v.name,
+                online: v.status == PeerDeviceStatus::Online,
+                client_secret: v.client_secret,
+                tags: v.tags,
+            })
+            .collect();
+    }
+    let data =
+        serde_json::to_vec_pretty(&cache).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    std::fs::write(cache_file()?, data)
+}
+
+/// 启动后用缓存立即打洞，之后定时保存
+pub async fn start(vnt: Vnt) {
+    let peers = load();
+    if !peers.is_empty() {
+        log::info!("使用缓存的设备地址打洞 {}", peers.len());
+        vnt.punch_peers(peers).await;
+    }
+    loop {
+        tokio::time::sleep(SAVE_INTERVAL).await;
+        if let Err(e) = save(&vnt) {
+            log::warn!("peer-cache.json {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use vnt::core::Config;
+
+/// 检查发现的问题和对应的解决方法
+pub struct Issue {
+    pub problem: String,
+    /// 可以直接执行的命令或者操作步骤
+    pub fix: Vec<String>,
+    /// 为false时只是提示，不影响启动
+    pub fatal: bool,
+}
+
+impl Issue {
+    fn fatal(problem: impl Into<String>, fix: Vec<String>) -> Self {
+        Self {
+            problem: problem.into(),
+            fix,
+            fatal: true,
+        }
+    }
+    fn warn(problem: impl Into<String>, fix: Vec<String>) -> Self {
+        Self {
+            problem: problem.into(),
+            fix,
+            fatal: false,
+        }
+    }
+}
+
+/// 启动前检查驱动、权限和冲突的服务，避免在创建网卡时才失败
+pub fn check(config: &Config) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    #[cfg(target_os = "linux")]
+    linux::check(config, &mut issues);
+    #[cfg(target_os = "windows")]
+    windows::check(config, &mut issues);
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    let _ = config;
+    issues
+}
+
+/// 打印问题和解决方法，有阻止启动的问题时返回true
+pub fn report(issues: &[Issue]) -> bool {
+    for issue in issues {
+        if issue.fatal {
+            println!("{} {}", console::style("[error]").red(), issue.problem);
+        } else {
+            println!("{} {}", console::style("[warn]").yellow(), issue.problem);
+        }
+        for fix in &issue.fix {
+            println!("    {}", fix);
+        }
+    }
+    issues.iter().any(|v| v.fatal)
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::path::Path;
+    use std::process::Command;
+
+    use vnt::core::Config;
+
+    use super::Issue;
+
+    pub fn check(config: &Config, issues: &mut Vec<Issue>) {
+        if let Some(path) = &config.helper_socket {
+            //网卡和路由由特权辅助进程操作，本进程不需要NET_ADMIN
+            if !Path::new(path).exists() {
+                issues.push(Issue::fatal(
+                    format!("helper socket {} not found", path),
+                    vec![format!(
+                        "sudo {} --privileged-helper {} --tun-owner $USER",
+                        exe_path(),
+                        path
+                    )],
+                ));
+            }
+            return;
+        }
+        let container = in_container();
+        if let Err(issue) = check_tun(container) {
+            issues.push(issue);
+        }
+        if !has_net_admin() {
+            let fix = if container {
+                vec!["docker run --cap-add NET_ADMIN --device /dev/net/tun ...".to_string()]
+            } else {
+                vec![format!(
+                    "sudo setcap cap_net_admin,cap_net_raw+ep {}",
+                    exe_path()
+                )]
+            };
+            issues.push(Issue::fatal("missing NET_ADMIN capability", fix));
+        }
+        if !container && service_active() {
+            issues.push(Issue::warn(
+                "systemd service 'vnt-cli' is active,two instances will fight over the routes",
+                vec!["sudo systemctl stop vnt-cli".to_string()],
+            ));
+        }
+    }
+
+    fn exe_path() -> String {
+        std::env::current_exe()
+            .map(|v| v.display().to_string())
+            .unwrap_or("vnt-cli".to_string())
+    }
+
+    fn in_container() -> bool {
+        Path::new("/.dockerenv").exists()
+            || Path::new("/run/.containerenv").exists()
+            || std::env::var_os("KUBERNETES_SERVICE_HOST").is_some()
+    }
+
+    /// 需要/dev/net/tun，不存在时尝试创建，和docker --device /dev/net/tun效果相同
+    fn check_tun(container: bool) -> Result<(), Issue> {
+        let tun = Path::new("/dev/net/tun");
+        if tun.exists() {
+            return Ok(());
+        }
+        let _ = std::fs::create_dir_all("/dev/net");
+        let rs = unsafe {
+            libc::mknod(
+                b"/dev/net/tun\0".as_ptr() as *const libc::c_char,
+                libc::S_IFCHR | 0o666,
+                libc::makedev(10, 200),
+            )
+        };
+        if rs == 0 {
+            return Ok(());
+        }
+        let fix = if container {
+            vec!["docker run --cap-add NET_ADMIN --device /dev/net/tun ...".to_string()]
+        } else {
+            vec![
+                "sudo modprobe tun".to_string(),
+                "echo tun | sudo tee /etc/modules-load.d/tun.conf".to_string(),
+            ]
+        };
+        Err(Issue::fatal("/dev/net/tun not found", fix))
+    }
+
+    /// 读取/proc/self/status中的有效权限集合，CAP_NET_ADMIN为第12位
+    fn has_net_admin() -> bool {
+        const CAP_NET_ADMIN: u32 = 12;
+        let status = match std::fs::read_to_string("/proc/self/status") {
+            Ok(status) => status,
+            //无法判断时不拦截，由创建网卡时报错
+            Err(_) => return true,
+        };
+        for line in status.lines() {
+            if let Some(value) = line.strip_prefix("CapEff:") {
+                return match u64::from_str_radix(value.trim(), 16) {
+                    Ok(caps) => caps & (1 << CAP_NET_ADMIN) != 0,
+                    Err(_) => true,
+                };
+            }
+        }
+        true
+    }
+
+    /// 由systemd启动时(INVOCATION_ID)就是服务本身，不算冲突
+    fn service_active() -> bool {
+        if std::env::var_os("INVOCATION_ID").is_some() {
+            return false;
+        }
+        Command::new("systemctl")
+            .args(["is-active", "--quiet", "vnt-cli"])
+            .status()
+            .map(|v| v.success())
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+
+    use vnt::core::Config;
+    use vnt::tun_tap_device::DeviceType;
+
+    use super::Issue;
+
+    pub fn check(config: &Config, issues: &mut Vec<Issue>) {
+        if config.tap {
+            match vnt::tun_tap_device::check_driver(DeviceType::Tap) {
+                Ok(driver) => log::info!("{}", driver),
+                Err(e) => issues.push(Issue::fatal(
+                    format!("tap driver: {}", e),
+                    vec![
+                        "install tap-windows 9.24.7: https://build.openvpn.net/downloads/releases/tap-windows-9.24.7-I601-Win10.exe".to_string(),
+                        "or remove '-a' to use the wintun driver".to_string(),
+                    ],
+                )),
+            }
+        } else {
+            match vnt::tun_tap_device::check_driver(DeviceType::Tun) {
+                Ok(driver) => log::info!("{}", driver),
+                Err(e) => issues.push(Issue::fatal(
+                    format!("wintun: {}", e),
+                    vec![
+                        "download https://www.wintun.net/builds/wintun-0.14.1.zip".to_string(),
+                        format!(
+                            "extract it next to {}, wintun/bin/<arch>/wintun.dll is found automatically",
+                            std::env::current_exe()
+                                .map(|v| v.display().to_string())
+                                .unwrap_or("vnt-cli.exe".to_string())
+                        ),
+                    ],
+                )),
+            }
+        }
+        if task_running() {
+            issues.push(Issue::warn(
+                "scheduled task 'vnt-cli' is running,two instances will fight over the routes",
+                vec!["schtasks /end /tn vnt-cli".to_string()],
+            ));
+        }
+    }
+
+    /// 计划任务以SYSTEM运行，此时就是任务本身，不算冲突
+    fn task_running() -> bool {
+        if std::env::var("USERNAME").map_or(false, |v| v == "SYSTEM" || v.ends_with('$')) {
+            return false;
+        }
+        match Command::new("schtasks")
+            .creation_flags(0x08000000)
+            .args(["/query", "/tn", "vnt-cli", "/fo", "csv", "/nh"])
+            .output()
+        {
+            Ok(out) => {
+                out.status.success() && String::from_utf8_lossy(&out.stdout).contains("Running")
+            }
+            Err(_) => false,
+        }
+    }
+}
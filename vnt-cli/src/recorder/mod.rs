@@ -0,0 +1,326 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::sync::broadcast::error::RecvError;
+use vnt::core::{Config, Vnt};
+use vnt::event::{CounterSnapshot, Event, EventKind};
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+/// 采样间隔
+const INTERVAL: Duration = Duration::from_secs(1);
+const MAGIC: &[u8; 6] = b"VNTFR1";
+const HEAD_LEN: usize = 16;
+/// 每条记录定长，写满后从头覆盖
+const RECORD_LEN: usize = 64;
+/// 最多保留的记录数，文件大小固定为1M
+const CAPACITY: u32 = 16384;
+const DETAIL_LEN: usize = RECORD_LEN - 14;
+/// 打包时每个日志文件只取最后这么多字节
+const LOG_TAIL: u64 = 1024 * 1024;
+
+const KIND_COUNTERS: u8 = 0;
+const KIND_START: u8 = 1;
+
+fn kind_code(kind: EventKind) -> u8 {
+    match kind {
+        EventKind::Connected => 2,
+        EventKind::Disconnected => 3,
+        EventKind::PeerUp => 4,
+        EventKind::PeerDown => 5,
+        EventKind::PathChanged => 6,
+        EventKind::Error => 7,
+        EventKind::SelfTest => 8,
+        EventKind::IpAssigned => 9,
+        EventKind::Maintenance => 10,
+    }
+}
+
+fn kind_name(code: u8) -> &'static str {
+    match code {
+        KIND_COUNTERS => "counters",
+        KIND_START => "start",
+        2 => "connected",
+        3 => "disconnected",
+        4 => "peer-up",
+        5 => "peer-down",
+        6 => "path",
+        7 => "error",
+        8 => "self-test",
+        9 => "ip-assigned",
+        10 => "maintenance",
+        _ => "unknown",
+    }
+}
+
+fn record_file() -> io::Result<PathBuf> {
+    Ok(crate::app_home()?.join("flight.bin"))
+}
+
+fn config_file() -> io::Result<PathBuf> {
+    Ok(crate::app_home()?.join("flight-config.txt"))
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|v| v.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// 环形的二进制记录文件，头部记录下一条的位置和是否已经写满过
+struct Recorder {
+    file: File,
+    next: u32,
+    wrapped: bool,
+}
+
+impl Recorder {
+    fn open() -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(record_file()?)?;
+        let mut head = [0u8; HEAD_LEN];
+        let (next, wrapped) = match file.read_exact(&mut head) {
+            Ok(_) if &head[..6] == MAGIC => {
+                let next = u32::from_be_bytes(head[8..12].try_into().unwrap());
+                (next % CAPACITY, head[12] != 0)
+            }
+            //不存在或格式不对时重新开始
+            _ => {
+                file.set_len(0)?;
+                (0, false)
+            }
+        };
+        Ok(Self {
+            file,
+            next,
+            wrapped,
+        })
+    }
+    fn write(&mut self, kind: u8, peer: Option<Ipv4Addr>, payload: &[u8]) -> io::Result<()> {
+        let mut record = [0u8; RECORD_LEN];
+        record[..8].copy_from_slice(&now_ms().to_be_bytes());
+        record[8] = kind;
+        let len = payload.len().min(DETAIL_LEN);
+        record[9] = len as u8;
+        if let Some(peer) = peer {
+            record[10..14].copy_from_slice(&peer.octets());
+        }
+        record[14..14 + len].copy_from_slice(&payload[..len]);
+        self.file.seek(SeekFrom::Start(
+            (HEAD_LEN + self.next as usize * RECORD_LEN) as u64,
+        ))?;
+        self.file.write_all(&record)?;
+        self.next += 1;
+        if self.next >= CAPACITY {
+            self.next = 0;
+            self.wrapped = true;
+        }
+        let mut head = [0u8; HEAD_LEN];
+        head[..6].copy_from_slice(MAGIC);
+        head[8..12].copy_from_slice(&self.next.to_be_bytes());
+        head[12] = self.wrapped as u8;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&head)
+    }
+    fn write_counters(&mut self, delta: &CounterSnapshot) -> io::Result<()> {
+        let mut payload = Vec::with_capacity(20);
+        for v in [
+            delta.tx_packets,
+            delta.tx_bytes,
+            delta.rx_packets,
+            delta.rx_bytes,
+            delta.punch,
+        ] {
+            payload.extend_from_slice(&(v.min(u32::MAX as u64) as u32).to_be_bytes());
+        }
+        self.write(KIND_COUNTERS, None, &payload)
+    }
+    fn write_event(&mut self, event: &Event) -> io::Result<()> {
+        self.write(
+            kind_code(event.kind),
+            event.peer,
+            truncate(&event.detail).as_bytes(),
+        )
+    }
+}
+
+/// 截断到字符边界
+fn truncate(detail: &str) -> &str {
+    let mut end = detail.len().min(DETAIL_LEN);
+    while !detail.is_char_boundary(end) {
+        end -= 1;
+    }
+    &detail[..end]
+}
+
+/// 保存去掉了密钥的配置，打包时一起带上
+pub fn save_config(config: &Config) -> io::Result<()> {
+    let mut config = config.clone();
+    config.token = "***".to_string();
+    if config.password.is_some() {
+        config.password = Some("***".to_string());
+    }
+    if let Some(turn) = config.turn.as_mut() {
+        turn.password = "***".to_string();
+    }
+    if let Some(proxy) = config.proxy.as_mut() {
+        if let Some((_, password)) = proxy.auth.as_mut() {
+            *password = "***".to_string();
+        }
+    }
+    std::fs::write(config_file()?, format!("{:#?}", config))
+}
+
+/// 一直运行，每秒记录收发计数(有变化时)，连接事件发生时立即记录，写入env/flight.bin
+pub async fn start(vnt: Vnt) {
+    let mut recorder = match Recorder::open() {
+        Ok(recorder) => recorder,
+        Err(e) => {
+            log::warn!("flight.bin {:?}", e);
+            return;
+        }
+    };
+    if let Err(e) = recorder.write(KIND_START, None, vnt::VNT_VERSION.as_bytes()) {
+        log::warn!("flight.bin {:?}", e);
+    }
+    //先订阅再取启动时已经发生的事件
+    let mut events = vnt.subscribe();
+    for event in vnt.events(0) {
+        if let Err(e) = recorder.write_event(&event) {
+            log::warn!("flight.bin {:?}", e);
+        }
+    }
+    let mut last = CounterSnapshot::default();
+    let mut interval = tokio::time::interval(INTERVAL);
+    loop {
+        let rs = tokio::select! {
+            _ = interval.tick() => {
+                let current = vnt.counters();
+                let delta = CounterSnapshot {
+                    tx_packets: current.tx_packets - last.tx_packets,
+                    tx_bytes: current.tx_bytes - last.tx_bytes,
+                    rx_packets: current.rx_packets - last.rx_packets,
+                    rx_bytes: current.rx_bytes - last.rx_bytes,
+                    punch: current.punch - last.punch,
+                };
+                last = current;
+                if delta != CounterSnapshot::default() {
+                    recorder.write_counters(&delta)
+                } else {
+                    Ok(())
+                }
+            }
+            event = events.recv() => match event {
+                Ok(event) => recorder.write_event(&event),
+                Err(RecvError::Lagged(n)) => {
+                    log::warn!("flight.bin 丢弃了{}条事件", n);
+                    Ok(())
+                }
+                Err(RecvError::Closed) => return,
+            }
+        };
+        if let Err(e) = rs {
+            log::warn!("flight.bin {:?}", e);
+        }
+    }
+}
+
+/// 按时间先后解析成文本
+fn decode(data: &[u8]) -> String {
+    let mut out = String::new();
+    if data.len() < HEAD_LEN || &data[..6] != MAGIC {
+        return out;
+    }
+    let next = u32::from_be_bytes(data[8..12].try_into().unwrap()) % CAPACITY;
+    let wrapped = data[12] != 0;
+    let order: Vec<u32> = if wrapped {
+        (next..CAPACITY).chain(0..next).collect()
+    } else {
+        (0..next).collect()
+    };
+    for index in order {
+        let start = HEAD_LEN + index as usize * RECORD_LEN;
+        let record = match data.get(start..start + RECORD_LEN) {
+            Some(record) => record,
+            None => break,
+        };
+        let time = u64::from_be_bytes(record[..8].try_into().unwrap());
+        let len = (record[9] as usize).min(DETAIL_LEN);
+        let payload = &record[14..14 + len];
+        out.push_str(&format!(
+            "{}.{:03} {}",
+            time / 1000,
+            time % 1000,
+            kind_name(record[8])
+        ));
+        if record[10..14] != [0; 4] {
+            let peer = Ipv4Addr::new(record[10], record[11], record[12], record[13]);
+            out.push_str(&format!(" {}", peer));
+        }
+        if record[8] == KIND_COUNTERS {
+            let v: Vec<u32> = payload
+                .chunks_exact(4)
+                .map(|v| u32::from_be_bytes(v.try_into().unwrap()))
+                .collect();
+            if v.len() == 5 {
+                out.push_str(&format!(
+                    " tx={}/{}B rx={}/{}B punch={}",
+                    v[0], v[1], v[2], v[3], v[4]
+                ));
+            }
+        } else {
+            out.push_str(&format!(" {}", String::from_utf8_lossy(payload)));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn read_tail(path: &Path) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(LOG_TAIL)))?;
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// 把记录文件、去掉密钥的配置和最近的日志打包成zip，用于提交问题
+pub fn dump(out: &Path) -> io::Result<()> {
+    let home = crate::app_home()?;
+    let mut zip = ZipWriter::new(File::create(out)?);
+    let options = FileOptions::default();
+    zip.start_file("version.txt", options)?;
+    zip.write_all(format!("vnt-cli {}\n{}\n", vnt::VNT_VERSION, os_info::get()).as_bytes())?;
+    if let Ok(data) = std::fs::read(record_file()?) {
+        zip.start_file("flight.txt", options)?;
+        zip.write_all(decode(&data).as_bytes())?;
+        zip.start_file("flight.bin", options)?;
+        zip.write_all(&data)?;
+    }
+    if let Ok(data) = std::fs::read(config_file()?) {
+        zip.start_file("config.txt", options)?;
+        zip.write_all(&data)?;
+    }
+    for entry in std::fs::read_dir(&home)? {
+        let path = entry?.path();
+        if path.extension().map_or(true, |v| v != "log") {
+            continue;
+        }
+        if let Ok(data) = read_tail(&path) {
+            let name = path.file_name().unwrap().to_string_lossy();
+            zip.start_file(format!("logs/{}", name), options)?;
+            zip.write_all(&data)?;
+        }
+    }
+    zip.finish()?;
+    Ok(())
+}
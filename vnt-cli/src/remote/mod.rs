@@ -0,0 +1,348 @@
+//! 通过虚拟网络查询其他节点，请求和响应都用管理密钥做HMAC-SHA256签名
+//!
+//! 请求: 时间戳\n随机数\n命令\n签名，
+//! 响应按长度拆成多个报文，每个报文: 签名\n序号/总数\n内容
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use vnt::core::Vnt;
+
+use crate::command::entity::{DeviceItem, Info, RouteItem};
+use crate::command::CommandEnum;
+use crate::{alias, console_out, exit_code};
+
+/// 监听在所有地址，只处理来自虚拟网络的请求
+pub const PORT: u16 = 39272;
+/// 请求时间和本地时间的最大偏差
+const MAX_SKEW: u64 = 30;
+/// 单个响应报文的内容长度，小于虚拟网卡的mtu，避免分片
+const CHUNK_LEN: usize = 1200;
+/// 响应报文数量上限
+const MAX_CHUNKS: usize = 4096;
+
+pub fn hmac(key: &[u8], data: &[&[u8]]) -> [u8; 32] {
+    let mut k = [0u8; 64];
+    if key.len() > 64 {
+        k[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        k[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha256::new();
+    inner.update(k.map(|b| b ^ 0x36));
+    for x in data {
+        inner.update(x);
+    }
+    let mut outer = Sha256::new();
+    outer.update(k.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+pub fn hex(buf: &[u8]) -> String {
+    buf.iter().map(|v| format!("{:02x}", v)).collect()
+}
+
+/// 逐字节比较，避免通过耗时猜测签名
+pub fn verify(mac: &str, expected: &[u8; 32]) -> bool {
+    let expected = hex(expected);
+    mac.len() == expected.len()
+        && mac
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|v| v.as_secs())
+        .unwrap_or(0)
+}
+
+/// 处理其他节点的查询，只支持只读命令
+pub async fn start(vnt: Vnt, key: String) -> io::Result<()> {
+    let udp = tokio::net::UdpSocket::bind(SocketAddr::V4(SocketAddrV4::new(
+        Ipv4Addr::UNSPECIFIED,
+        PORT,
+    )))
+    .await?;
+    log::info!("启动远程管理 port={}", PORT);
+    //防重放，记录有效期内用过的随机数
+    let mut nonces: HashMap<String, u64> = HashMap::new();
+    let mut buf = [0u8; 1024];
+    loop {
+        let (len, addr) = match udp.recv_from(&mut buf).await {
+            Ok(rs) => rs,
+            Err(e) => {
+                //windows上之前的响应收到icmp不可达时会返回WSAECONNRESET，不能因此退出
+                log::warn!("远程管理接收失败 {:?}", e);
+                continue;
+            }
+        };
+        let ip = match addr {
+            SocketAddr::V4(addr) => *addr.ip(),
+            SocketAddr::V6(_) => continue,
+        };
+        let current = vnt.current_device();
+        if u32::from(ip) & u32::from(current.virtual_netmask) != u32::from(current.virtual_network)
+        {
+            continue;
+        }
+        let req = String::from_utf8_lossy(&buf[..len]).to_string();
+        let (nonce, cmd) = match check_request(&req, key.as_bytes(), &mut nonces) {
+            Some(v) => v,
+            None => {
+                log::warn!("远程管理请求校验失败 {}", addr);
+                continue;
+            }
+        };
+        log::info!("远程管理 {} cmd={}", addr, cmd);
+        let body = match cmd {
+            "info" => serde_json::to_string(&crate::command::command_info(&vnt)),
+            "list" => serde_json::to_string(&crate::command::command_list(&vnt)),
+            "route" => serde_json::to_string(&crate::command::command_route(&vnt)),
+            _ => continue,
+        };
+        let body = match body {
+            Ok(body) => body,
+            Err(e) => {
+                log::warn!("远程管理 {} {:?}", addr, e);
+                continue;
+            }
+        };
+        let packets = match response_packets(key.as_bytes(), nonce, body.as_bytes()) {
+            Some(packets) => packets,
+            None => {
+                log::warn!("远程管理 {} 响应过大 len={}", addr, body.len());
+                continue;
+            }
+        };
+        for packet in packets {
+            if let Err(e) = udp.send_to(&packet, addr).await {
+                log::warn!("远程管理 {} {:?}", addr, e);
+                break;
+            }
+        }
+    }
+}
+
+fn check_request<'a>(
+    req: &'a str,
+    key: &[u8],
+    nonces: &mut HashMap<String, u64>,
+) -> Option<(&'a str, &'a str)> {
+    let mut split = req.splitn(4, '\n');
+    let time = split.next()?;
+    let nonce = split.next()?;
+    let cmd = split.next()?;
+    let mac = split.next()?;
+    let expected = hmac(
+        key,
+        &[
+            time.as_bytes(),
+            b"\n",
+            nonce.as_bytes(),
+            b"\n",
+            cmd.as_bytes(),
+        ],
+    );
+    if !verify(mac.trim(), &expected) {
+        return None;
+    }
+    let time = time.parse::<u64>().ok()?;
+    let now = now();
+    if time.abs_diff(now) > MAX_SKEW {
+        return None;
+    }
+    nonces.retain(|_, t| t.abs_diff(now) <= MAX_SKEW);
+    if nonces.insert(nonce.to_string(), time).is_some() {
+        return None;
+    }
+    Some((nonce, cmd))
+}
+
+/// 把响应拆成多个签名的报文，超过报文数量上限时返回None
+fn response_packets(key: &[u8], nonce: &str, body: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let chunks: Vec<&[u8]> = if body.is_empty() {
+        vec![body]
+    } else {
+        body.chunks(CHUNK_LEN).collect()
+    };
+    if chunks.len() > MAX_CHUNKS {
+        return None;
+    }
+    let total = chunks.len();
+    let packets = chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let head = format!("{}/{}", index, total);
+            let mac = hmac(
+                key,
+                &[nonce.as_bytes(), b"\n", head.as_bytes(), b"\n", chunk],
+            );
+            let mut packet = format!("{}\n{}\n", hex(&mac), head).into_bytes();
+            packet.extend_from_slice(chunk);
+            packet
+        })
+        .collect();
+    Some(packets)
+}
+
+/// 校验响应报文，返回(序号,总数,内容)
+fn parse_packet<'a>(
+    key: &[u8],
+    nonce: &str,
+    packet: &'a [u8],
+) -> io::Result<(usize, usize, &'a [u8])> {
+    let data_err = || io::Error::new(io::ErrorKind::InvalidData, "response error");
+    let mut split = packet.splitn(3, |v| *v == b'\n');
+    let mac = std::str::from_utf8(split.next().ok_or_else(data_err)?).map_err(|_| data_err())?;
+    let head = split.next().ok_or_else(data_err)?;
+    let chunk = split.next().ok_or_else(data_err)?;
+    let expected = hmac(key, &[nonce.as_bytes(), b"\n", head, b"\n", chunk]);
+    if !verify(mac, &expected) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "response signature mismatch,check --mgmt-key",
+        ));
+    }
+    let (index, total) = std::str::from_utf8(head)
+        .ok()
+        .and_then(|v| v.split_once('/'))
+        .and_then(|(index, total)| {
+            Some((index.parse::<usize>().ok()?, total.parse::<usize>().ok()?))
+        })
+        .ok_or_else(data_err)?;
+    if total == 0 || total > MAX_CHUNKS || index >= total {
+        return Err(data_err());
+    }
+    Ok((index, total, chunk))
+}
+
+/// 通过隧道查询其他节点，本机需要已经在运行
+pub fn query(peer: &str, cmd: CommandEnum, key: &str) -> i32 {
+    match query0(peer, cmd, key) {
+        Ok(_) => exit_code::OK,
+        Err(e) => {
+            println!("remote {}: {}", peer, e);
+            exit_code::ERROR
+        }
+    }
+}
+
+fn query0(peer: &str, cmd: CommandEnum, key: &str) -> io::Result<()> {
+    let ip = alias::parse_peer(peer)?;
+    let name = match cmd {
+        CommandEnum::Info => "info",
+        CommandEnum::List | CommandEnum::All => "list",
+        CommandEnum::Route(_) => "route",
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "only --info/--list/--all/--route are supported",
+            ))
+        }
+    };
+    let body = request(ip, name, key.as_bytes())?;
+    let data_err = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+    match cmd {
+        CommandEnum::Info => {
+            console_out::console_info(serde_json::from_str::<Info>(&body).map_err(data_err)?)
+        }
+        CommandEnum::List => console_out::console_device_list(
+            serde_json::from_str::<Vec<DeviceItem>>(&body).map_err(data_err)?,
+        ),
+        CommandEnum::All => console_out::console_device_list_all(
+            serde_json::from_str::<Vec<DeviceItem>>(&body).map_err(data_err)?,
+        ),
+        _ => console_out::console_route_table(
+            serde_json::from_str::<Vec<RouteItem>>(&body).map_err(data_err)?,
+        ),
+    }
+    Ok(())
+}
+
+fn request(ip: Ipv4Addr, cmd: &str, key: &[u8]) -> io::Result<String> {
+    let time = now().to_string();
+    let nonce = hex(&rand::thread_rng().gen::<[u8; 16]>());
+    let mac = hmac(
+        key,
+        &[
+            time.as_bytes(),
+            b"\n",
+            nonce.as_bytes(),
+            b"\n",
+            cmd.as_bytes(),
+        ],
+    );
+    let req = format!("{}\n{}\n{}\n{}", time, nonce, cmd, hex(&mac));
+    let udp = UdpSocket::bind("0.0.0.0:0")?;
+    udp.set_read_timeout(Some(Duration::from_secs(5)))?;
+    udp.connect(SocketAddr::V4(SocketAddrV4::new(ip, PORT)))?;
+    udp.send(req.as_bytes())?;
+    let mut buf = vec![0u8; 65536];
+    let mut chunks: Vec<Option<Vec<u8>>> = Vec::new();
+    let mut received = 0;
+    loop {
+        let len = udp.recv(&mut buf).map_err(|e| match e.kind() {
+            //签名错误时对端不响应
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut if chunks.is_empty() => {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "no response,check that the peer is running with the same --mgmt-key",
+                )
+            }
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("incomplete response,{}/{}", received, chunks.len()),
+            ),
+            _ => e,
+        })?;
+        let (index, total, chunk) = parse_packet(key, &nonce, &buf[..len])?;
+        if chunks.is_empty() {
+            chunks.resize(total, None);
+        } else if chunks.len() != total {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "response error"));
+        }
+        if chunks[index].is_none() {
+            chunks[index] = Some(chunk.to_vec());
+            received += 1;
+        }
+        if received == total {
+            break;
+        }
+    }
+    //按字节拼接后再解码，拆分位置可能在多字节字符中间
+    let body: Vec<u8> = chunks.into_iter().flatten().flatten().collect();
+    String::from_utf8(body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[test]
+fn test_response_packets() {
+    let key = b"key";
+    let body = "设备".repeat(CHUNK_LEN);
+    let packets = response_packets(key, "nonce", body.as_bytes()).unwrap();
+    assert_eq!(packets.len(), body.len().div_ceil(CHUNK_LEN));
+    let mut rs = Vec::new();
+    for (i, packet) in packets.iter().enumerate() {
+        let (index, total, chunk) = parse_packet(key, "nonce", packet).unwrap();
+        assert_eq!((index, total), (i, packets.len()));
+        rs.extend_from_slice(chunk);
+    }
+    assert_eq!(rs, body.as_bytes());
+    assert!(parse_packet(b"other", "nonce", &packets[0]).is_err());
+    assert!(parse_packet(key, "other", &packets[0]).is_err());
+    let packets = response_packets(key, "nonce", b"").unwrap();
+    assert_eq!(
+        parse_packet(key, "nonce", &packets[0]).unwrap(),
+        (0, 1, &b""[..])
+    );
+    assert!(response_packets(key, "nonce", &vec![0; CHUNK_LEN * MAX_CHUNKS + 1]).is_none());
+}
@@ -0,0 +1,44 @@
+use std::io;
+use std::path::Path;
+
+use crate::config::FileConfig;
+
+/// systemd服务，OpenWrt下使用procd脚本
+pub fn install(file_conf: &FileConfig, path: &Path) -> io::Result<()> {
+    if crate::openwrt::is_openwrt() {
+        // OpenWrt使用uci配置，配置文件仅作备份
+        crate::openwrt::install()?;
+        crate::openwrt::configure(&file_conf.token, &file_conf.server_address, &file_conf.name)?;
+        println!("installed,run '/etc/init.d/vnt start' to start");
+        return Ok(());
+    }
+    let unit = format!(
+        "[Unit]
+Description=vnt
+After=network-online.target
+Wants=network-online.target
+
+[Service]
+ExecStart={} -f {} --data-dir {} --quiet
+Restart=on-failure
+
+[Install]
+WantedBy=multi-user.target
+",
+        std::env::current_exe()?.display(),
+        path.display(),
+        crate::app_home()?.display()
+    );
+    std::fs::write("/etc/systemd/system/vnt-cli.service", unit)?;
+    let status = std::process::Command::new("systemctl")
+        .args(["enable", "--now", "vnt-cli"])
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("systemctl enable failed {}", status),
+        ));
+    }
+    println!("installed as systemd service 'vnt-cli'");
+    Ok(())
+}
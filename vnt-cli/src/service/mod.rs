@@ -0,0 +1,28 @@
+//! 开机启动服务的安装，各平台只实现install，交互流程和配置生成在setup中共用
+
+use std::io;
+use std::path::Path;
+
+use crate::config::FileConfig;
+
+#[cfg(target_os = "linux")]
+mod linux;
+
+#[cfg(target_os = "linux")]
+pub use linux::install;
+
+#[cfg(target_os = "windows")]
+mod windows;
+
+#[cfg(target_os = "windows")]
+pub use windows::install;
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub fn install(_file_conf: &FileConfig, path: &Path) -> io::Result<()> {
+    println!(
+        "start on boot is not supported on this platform,run '{} -f {}' to start",
+        std::env::current_exe()?.display(),
+        path.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::config::FileConfig;
+
+/// 启动后等待可以响应命令的时间
+const START_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+fn service_log() -> io::Result<PathBuf> {
+    Ok(crate::app_home()?.join("service.log"))
+}
+
+/// 程序目录只读时(如msix包)计划任务不能直接运行包内的程序，
+/// 把程序和wintun.dll复制到数据目录下的bin中
+fn service_exe() -> io::Result<PathBuf> {
+    let exe = std::env::current_exe()?;
+    let target = if crate::exe_dir_writable() {
+        exe.clone()
+    } else {
+        let bin = crate::app_home()?.join("bin");
+        std::fs::create_dir_all(&bin)?;
+        let target = bin.join(exe.file_name().unwrap_or("vnt-cli.exe".as_ref()));
+        std::fs::copy(&exe, &target)?;
+        #[cfg(not(feature = "bundle-wintun"))]
+        copy_wintun(&bin)?;
+        println!("copied {} to {}", exe.display(), target.display());
+        target
+    };
+    //内置了wintun时安装时就释放并校验，启动时不再因为缺少dll失败
+    #[cfg(feature = "bundle-wintun")]
+    {
+        let dir = target.parent().unwrap_or(Path::new("."));
+        let dll =
+            vnt::tun_tap_device::extract_wintun(&dir.join("wintun").join("bin").join(WINTUN_ARCH))?;
+        println!("extracted {}", dll.display());
+    }
+    Ok(target)
+}
+
+/// 和vnt内置、wintun压缩包中的目录名一致
+#[cfg(all(target_arch = "aarch64"))]
+const WINTUN_ARCH: &str = "arm64";
+#[cfg(all(target_arch = "x86_64"))]
+const WINTUN_ARCH: &str = "amd64";
+#[cfg(all(target_arch = "x86"))]
+const WINTUN_ARCH: &str = "x86";
+#[cfg(all(target_arch = "arm"))]
+const WINTUN_ARCH: &str = "arm";
+
+#[cfg(not(feature = "bundle-wintun"))]
+fn copy_wintun(bin: &Path) -> io::Result<()> {
+    let dir = crate::exe_dir();
+    for dll in [
+        dir.join("wintun.dll"),
+        dir.join("wintun")
+            .join("bin")
+            .join(WINTUN_ARCH)
+            .join("wintun.dll"),
+    ] {
+        if dll.exists() {
+            std::fs::copy(&dll, bin.join("wintun.dll"))?;
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// 开机运行的计划任务，安装后立即启动
+pub fn install(_file_conf: &FileConfig, path: &Path) -> io::Result<()> {
+    use std::os::windows::process::CommandExt;
+    // 输出重定向到日志文件，启动失败时可以看到原因
+    let task = format!(
+        "cmd /c \"\"{}\" -f \"{}\" --data-dir \"{}\" --quiet > \"{}\" 2>&1\"",
+        service_exe()?.display(),
+        path.display(),
+        crate::app_home()?.display(),
+        service_log()?.display()
+    );
+    let status = std::process::Command::new("schtasks")
+        .creation_flags(0x08000000)
+        .args([
+            "/create", "/f", "/tn", "vnt-cli", "/sc", "onstart", "/ru", "SYSTEM", "/rl", "highest",
+            "/tr", &task,
+        ])
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("schtasks failed {}", status),
+        ));
+    }
+    println!("installed as scheduled task 'vnt-cli'");
+    start_task()
+}
+
+/// 立即运行计划任务，轮询直到vnt可以响应命令，超时则输出日志末尾
+fn start_task() -> io::Result<()> {
+    use std::os::windows::process::CommandExt;
+    let status = std::process::Command::new("schtasks")
+        .creation_flags(0x08000000)
+        .args(["/run", "/tn", "vnt-cli"])
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("schtasks run failed {}", status),
+        ));
+    }
+    print!("starting");
+    let start = std::time::Instant::now();
+    while start.elapsed() < START_TIMEOUT {
+        if crate::command::is_running() {
+            println!();
+            println!("vnt-cli started");
+            return Ok(());
+        }
+        print!(".");
+        io::stdout().flush()?;
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    println!();
+    let log = service_log()?;
+    println!(
+        "vnt-cli did not start in {}s,{}:",
+        START_TIMEOUT.as_secs(),
+        log.display()
+    );
+    if let Ok(content) = std::fs::read_to_string(&log) {
+        let lines: Vec<&str> = content.lines().collect();
+        for line in &lines[lines.len().saturating_sub(20)..] {
+            println!("  {}", line);
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::TimedOut, "start timeout"))
+}
@@ -0,0 +1,95 @@
+use std::io;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::config::FileConfig;
+
+/// 首次运行的交互式配置，生成配置文件，并可选安装为开机启动的服务
+pub fn setup() -> io::Result<()> {
+    println!(" ====== vnt setup ====== ");
+    let mut file_conf = FileConfig::default();
+    file_conf.token = loop {
+        let token = prompt("token", None)?;
+        if token.is_empty() || token.len() > 128 {
+            println!("token length must be 1~128");
+            continue;
+        }
+        break token;
+    };
+    file_conf.server_address = prompt("server address", Some(&file_conf.server_address))?;
+    file_conf.name = prompt("device name", Some(&file_conf.name))?;
+    let start_on_boot = confirm("start on boot", true)?;
+
+    println!("checking server {} ...", file_conf.server_address);
+    if let Err(e) = check_server(&file_conf) {
+        println!("server check failed: {}", e);
+        if !confirm("save the config anyway", false)? {
+            return Err(io::Error::new(io::ErrorKind::Other, "setup cancelled"));
+        }
+    } else {
+        println!("server ok");
+    }
+
+    //程序目录只读时配置文件放到数据目录
+    let default_path = if crate::exe_dir_writable() {
+        crate::exe_dir().join("config.yaml")
+    } else {
+        crate::app_home()?.join("config.yaml")
+    };
+    let path = prompt("config file", Some(&default_path.to_string_lossy()))?;
+    let path = PathBuf::from(path);
+    if path.exists() && !confirm(&format!("{} exists, overwrite", path.display()), false)? {
+        return Err(io::Error::new(io::ErrorKind::Other, "setup cancelled"));
+    }
+    let yaml = serde_yaml::to_string(&file_conf)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    std::fs::write(&path, yaml)?;
+    println!("config saved to {}", path.display());
+
+    if start_on_boot {
+        crate::service::install(&file_conf, &path)?;
+    } else {
+        println!(
+            "run '{} -f {}' to start",
+            std::env::current_exe()?.display(),
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+fn prompt(msg: &str, default: Option<&str>) -> io::Result<String> {
+    match default {
+        None => print!("{}: ", msg),
+        Some(default) => print!("{} [{}]: ", msg, default),
+    }
+    io::stdout().flush()?;
+    let mut line = String::new();
+    if io::stdin().read_line(&mut line)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "stdin closed"));
+    }
+    let line = line.trim();
+    Ok(match default {
+        Some(default) if line.is_empty() => default.to_string(),
+        _ => line.to_string(),
+    })
+}
+
+fn confirm(msg: &str, default: bool) -> io::Result<bool> {
+    let hint = if default { "Y/n" } else { "y/N" };
+    loop {
+        let answer = prompt(&format!("{}? ({})", msg, hint), None)?;
+        match answer.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => {}
+        }
+    }
+}
+
+/// 连接服务器并握手，确认地址可用
+fn check_server(file_conf: &FileConfig) -> io::Result<()> {
+    let (config, ..) = crate::config::build_config(file_conf.clone())?;
+    crate::connector::handshake(config).map(|_| ())
+}
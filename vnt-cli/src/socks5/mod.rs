@@ -0,0 +1,154 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use vnt::core::Vnt;
+
+/// 握手和连接目标的超时时间
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+const VERSION: u8 = 5;
+const NO_AUTH: u8 = 0;
+const NO_ACCEPTABLE: u8 = 0xFF;
+const CMD_CONNECT: u8 = 1;
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+const REP_SUCCEEDED: u8 = 0;
+const REP_NOT_ALLOWED: u8 = 2;
+const REP_HOST_UNREACHABLE: u8 = 4;
+const REP_CMD_NOT_SUPPORTED: u8 = 7;
+const REP_ATYP_NOT_SUPPORTED: u8 = 8;
+
+/// 容器边车模式下给其他容器使用的socks5代理，应用容器不需要特权即可访问虚拟网络，
+/// 只允许连接虚拟网段和点对网(-i)的网段，不会成为通往外网的开放代理
+pub async fn start(addr: SocketAddr, vnt: Vnt, in_ips: Vec<(u32, u32)>) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    log::info!("启动socks5代理:{}", addr);
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let vnt = vnt.clone();
+        let in_ips = in_ips.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, &vnt, &in_ips).await {
+                log::debug!("socks5 {} {:?}", peer, e);
+            }
+        });
+    }
+}
+
+fn allowed(vnt: &Vnt, in_ips: &[(u32, u32)], ip: Ipv4Addr) -> bool {
+    let device = vnt.current_device();
+    let ip = u32::from(ip);
+    let netmask = u32::from(device.virtual_netmask);
+    if ip & netmask == u32::from(device.virtual_network) {
+        return true;
+    }
+    in_ips.iter().any(|(dest, mask)| ip & mask == dest & mask)
+}
+
+async fn handle(mut stream: TcpStream, vnt: &Vnt, in_ips: &[(u32, u32)]) -> io::Result<()> {
+    let target = match tokio::time::timeout(TIMEOUT, handshake(&mut stream)).await {
+        Ok(rs) => rs?,
+        Err(_) => return Err(io::Error::from(io::ErrorKind::TimedOut)),
+    };
+    let target = match target {
+        Some(target) => target,
+        None => return Ok(()),
+    };
+    let ipv4 = match target.ip() {
+        IpAddr::V4(ip) => ip,
+        IpAddr::V6(_) => {
+            reply(&mut stream, REP_ATYP_NOT_SUPPORTED).await?;
+            return Ok(());
+        }
+    };
+    if !allowed(vnt, in_ips, ipv4) {
+        log::warn!("socks5 拒绝连接虚拟网络以外的地址 {}", target);
+        reply(&mut stream, REP_NOT_ALLOWED).await?;
+        return Ok(());
+    }
+    //虚拟网卡已经配置了路由，直接连接即可
+    let mut remote = match tokio::time::timeout(TIMEOUT, TcpStream::connect(target)).await {
+        Ok(Ok(remote)) => remote,
+        Ok(Err(e)) => {
+            reply(&mut stream, REP_HOST_UNREACHABLE).await?;
+            return Err(e);
+        }
+        Err(_) => {
+            reply(&mut stream, REP_HOST_UNREACHABLE).await?;
+            return Err(io::Error::from(io::ErrorKind::TimedOut));
+        }
+    };
+    reply(&mut stream, REP_SUCCEEDED).await?;
+    tokio::io::copy_bidirectional(&mut stream, &mut remote).await?;
+    Ok(())
+}
+
+/// 协商认证方式并读取CONNECT请求，不支持的请求已经回复错误，返回None
+async fn handshake(stream: &mut TcpStream) -> io::Result<Option<SocketAddr>> {
+    let mut head = [0u8; 2];
+    stream.read_exact(&mut head).await?;
+    if head[0] != VERSION {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not socks5"));
+    }
+    let mut methods = vec![0u8; head[1] as usize];
+    stream.read_exact(&mut methods).await?;
+    if !methods.contains(&NO_AUTH) {
+        stream.write_all(&[VERSION, NO_ACCEPTABLE]).await?;
+        return Ok(None);
+    }
+    stream.write_all(&[VERSION, NO_AUTH]).await?;
+    let mut request = [0u8; 4];
+    stream.read_exact(&mut request).await?;
+    if request[1] != CMD_CONNECT {
+        reply(stream, REP_CMD_NOT_SUPPORTED).await?;
+        return Ok(None);
+    }
+    let ip = match request[3] {
+        ATYP_IPV4 => {
+            let mut ip = [0u8; 4];
+            stream.read_exact(&mut ip).await?;
+            IpAddr::V4(Ipv4Addr::from(ip))
+        }
+        ATYP_DOMAIN => {
+            let len = stream.read_u8().await?;
+            let mut domain = vec![0u8; len as usize];
+            stream.read_exact(&mut domain).await?;
+            let port = stream.read_u16().await?;
+            let domain = String::from_utf8_lossy(&domain).to_string();
+            return match tokio::net::lookup_host((domain.as_str(), port))
+                .await
+                .ok()
+                .and_then(|mut addrs| addrs.find(|addr| addr.is_ipv4()))
+            {
+                Some(addr) => Ok(Some(addr)),
+                None => {
+                    reply(stream, REP_HOST_UNREACHABLE).await?;
+                    Ok(None)
+                }
+            };
+        }
+        ATYP_IPV6 => {
+            let mut ip = [0u8; 16];
+            stream.read_exact(&mut ip).await?;
+            IpAddr::from(ip)
+        }
+        _ => {
+            reply(stream, REP_ATYP_NOT_SUPPORTED).await?;
+            return Ok(None);
+        }
+    };
+    let port = stream.read_u16().await?;
+    Ok(Some(SocketAddr::new(ip, port)))
+}
+
+async fn reply(stream: &mut TcpStream, rep: u8) -> io::Result<()> {
+    //绑定地址对CONNECT没有意义，统一返回0.0.0.0:0
+    stream
+        .write_all(&[VERSION, rep, 0, ATYP_IPV4, 0, 0, 0, 0, 0, 0])
+        .await
+}
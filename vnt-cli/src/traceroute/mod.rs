@@ -0,0 +1,99 @@
+//! 经虚拟网络的traceroute，调用系统的traceroute/tracert，在每一跳后面标注虚拟网络中的名称：
+//! 虚拟网关为服务端中转(没有直连时服务端算作一跳)，其他虚拟ip为设备名称和别名
+
+use std::collections::HashMap;
+use std::io;
+use std::io::{BufRead, BufReader};
+use std::net::Ipv4Addr;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+use crate::alias;
+use crate::command::client::CommandClient;
+use crate::exit_code;
+
+pub fn run(peer: &str) -> i32 {
+    let command = match CommandClient::new() {
+        Ok(command) => command,
+        Err(e) => {
+            println!("traceroute error {}", e);
+            return exit_code::NOT_RUNNING;
+        }
+    };
+    match run0(&command, peer) {
+        Ok(true) => exit_code::OK,
+        Ok(false) => exit_code::ERROR,
+        Err(e) => {
+            println!("traceroute error {}", e);
+            exit_code::ERROR
+        }
+    }
+}
+
+fn run0(command: &CommandClient, peer: &str) -> io::Result<bool> {
+    let info = command.info()?;
+    let list = command.list()?;
+    let mut names: HashMap<Ipv4Addr, String> = HashMap::new();
+    if let Ok(gateway) = Ipv4Addr::from_str(&info.virtual_gateway) {
+        names.insert(gateway, "relay server".to_string());
+    }
+    if let Ok(ip) = Ipv4Addr::from_str(&info.virtual_ip) {
+        names.insert(ip, format!("{} (this device)", info.name));
+    }
+    for item in &list {
+        if let Ok(ip) = Ipv4Addr::from_str(&item.virtual_ip) {
+            let name = if item.alias.is_empty() {
+                item.name.clone()
+            } else {
+                format!("{}({})", item.name, item.alias)
+            };
+            names.insert(ip, name);
+        }
+    }
+    //别名、虚拟ip或者设备名称
+    let ip = alias::parse_peer(peer).or_else(|e| {
+        list.iter()
+            .find(|v| v.name == peer)
+            .and_then(|v| Ipv4Addr::from_str(&v.virtual_ip).ok())
+            .ok_or(e)
+    })?;
+    let mut child = trace_command(ip)
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| io::Error::new(e.kind(), format!("failed to run traceroute: {}", e)))?;
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines() {
+            let line = line?;
+            match annotate(&line, &names) {
+                Some(name) => println!("{}  <- {}", line, name),
+                None => println!("{}", line),
+            }
+        }
+    }
+    Ok(child.wait()?.success())
+}
+
+/// 不解析域名，最多16跳
+#[cfg(windows)]
+fn trace_command(ip: Ipv4Addr) -> Command {
+    let mut cmd = Command::new("tracert");
+    cmd.args(["-d", "-w", "2000", "-h", "16"])
+        .arg(ip.to_string());
+    cmd
+}
+
+/// 不解析域名，每跳只探测一次，最多16跳
+#[cfg(not(windows))]
+fn trace_command(ip: Ipv4Addr) -> Command {
+    let mut cmd = Command::new("traceroute");
+    cmd.args(["-n", "-q", "1", "-w", "2", "-m", "16"])
+        .arg(ip.to_string());
+    cmd
+}
+
+/// 行中出现的虚拟网络地址对应的名称
+fn annotate(line: &str, names: &HashMap<Ipv4Addr, String>) -> Option<String> {
+    line.split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .filter_map(|v| Ipv4Addr::from_str(v).ok())
+        .find_map(|ip| names.get(&ip).cloned())
+}
@@ -0,0 +1,282 @@
+//! 通过虚拟网络在节点间传文件，tcp连接，只监听在本机的虚拟ip上
+//!
+//! 1. 接收端发送随机数
+//! 2. 发送端发送 文件名\n大小\n签名，签名为HMAC(密钥, 随机数\n文件名\n大小)
+//! 3. 接收端回复已有的长度(断点续传)，发送端从该位置继续发送
+//! 4. 发送端发送 HMAC(密钥, 随机数\n整个文件的sha256)，接收端校验后回复ok
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+use crate::remote::{hex, hmac, verify};
+use crate::{alias, exit_code};
+
+pub const PORT: u16 = 39273;
+const BUF_SIZE: usize = 64 * 1024;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn read_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "connection closed",
+        ));
+    }
+    Ok(line.trim_end().to_string())
+}
+
+fn file_sha256(file: &mut File) -> io::Result<[u8; 32]> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; BUF_SIZE];
+    loop {
+        let len = file.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&buf[..len]);
+    }
+    Ok(hasher.finalize().into())
+}
+
+struct Progress {
+    name: String,
+    total: u64,
+    start: Instant,
+    start_offset: u64,
+    last: Instant,
+}
+
+impl Progress {
+    fn new(name: &str, total: u64, offset: u64) -> Self {
+        let now = Instant::now();
+        Self {
+            name: name.to_string(),
+            total,
+            start: now,
+            start_offset: offset,
+            last: now - Duration::from_secs(1),
+        }
+    }
+    fn update(&mut self, done: u64) {
+        if self.last.elapsed() < Duration::from_millis(200) && done != self.total {
+            return;
+        }
+        self.last = Instant::now();
+        let percent = if self.total == 0 {
+            100
+        } else {
+            done * 100 / self.total
+        };
+        let secs = self.start.elapsed().as_secs_f64().max(0.001);
+        let speed = (done - self.start_offset) as f64 / secs / 1024.0;
+        print!(
+            "\r{} {}/{} {}% {:.1}KB/s   ",
+            self.name, done, self.total, percent, speed
+        );
+        let _ = io::stdout().flush();
+    }
+}
+
+/// 发送文件到其他节点，对端需要先运行--recv
+pub fn send(peer: &str, path: &str, key: &str) -> i32 {
+    match send0(peer, Path::new(path), key) {
+        Ok(_) => exit_code::OK,
+        Err(e) => {
+            println!();
+            println!("send error {}", e);
+            exit_code::ERROR
+        }
+    }
+}
+
+fn send0(peer: &str, path: &Path, key: &str) -> io::Result<()> {
+    let ip = alias::parse_peer(peer)?;
+    let name = path
+        .file_name()
+        .map(|v| v.to_string_lossy().to_string())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid file"))?;
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let stream = TcpStream::connect_timeout(
+        &SocketAddr::V4(SocketAddrV4::new(ip, PORT)),
+        Duration::from_secs(5),
+    )?;
+    stream.set_read_timeout(Some(Duration::from_secs(60)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let nonce = read_line(&mut reader)?;
+    let size_str = size.to_string();
+    let mac = hmac(
+        key.as_bytes(),
+        &[
+            nonce.as_bytes(),
+            b"\n",
+            name.as_bytes(),
+            b"\n",
+            size_str.as_bytes(),
+        ],
+    );
+    writer.write_all(format!("{}\n{}\n{}\n", name, size, hex(&mac)).as_bytes())?;
+    let reply = read_line(&mut reader)?;
+    let offset = match reply.strip_prefix("offset ") {
+        Some(offset) => u64::from_str(offset).map_err(|_| invalid("offset error"))?,
+        None => return Err(io::Error::new(io::ErrorKind::Other, reply)),
+    };
+    if offset > 0 {
+        println!("resume from {}", offset);
+    }
+    let mut progress = Progress::new(&name, size, offset);
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buf = vec![0u8; BUF_SIZE];
+    let mut done = offset;
+    while done < size {
+        let len = file.read(&mut buf)?;
+        if len == 0 {
+            return Err(invalid("file changed while sending"));
+        }
+        writer.write_all(&buf[..len])?;
+        done += len as u64;
+        progress.update(done);
+    }
+    println!();
+    let digest = file_sha256(&mut file)?;
+    let mac = hmac(key.as_bytes(), &[nonce.as_bytes(), b"\n", &digest]);
+    writer.write_all(format!("{}\n", hex(&mac)).as_bytes())?;
+    let reply = read_line(&mut reader)?;
+    if reply != "ok" {
+        return Err(io::Error::new(io::ErrorKind::Other, reply));
+    }
+    println!("sent {} to {}", name, ip);
+    Ok(())
+}
+
+/// 接收其他节点发来的文件，一次处理一个连接，Ctrl+C退出
+pub fn recv(dir: &str, key: &str) -> i32 {
+    match recv0(Path::new(dir), key) {
+        Ok(_) => exit_code::OK,
+        Err(e) => {
+            println!("recv error {}", e);
+            exit_code::ERROR
+        }
+    }
+}
+
+fn recv0(dir: &Path, key: &str) -> io::Result<()> {
+    // 只监听虚拟ip，不会暴露到物理网络
+    let info = crate::command::client::CommandClient::new()?.info()?;
+    let ip = Ipv4Addr::from_str(&info.virtual_ip).map_err(|_| invalid("virtual ip error"))?;
+    let listener = TcpListener::bind(SocketAddr::V4(SocketAddrV4::new(ip, PORT)))?;
+    std::fs::create_dir_all(dir)?;
+    println!(
+        "waiting for files on {}:{},save to {}",
+        ip,
+        PORT,
+        dir.display()
+    );
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let peer = stream.peer_addr()?;
+        if let Err(e) = recv_file(stream, dir, key) {
+            println!();
+            println!("receive from {} error {}", peer, e);
+        }
+    }
+    Ok(())
+}
+
+fn recv_file(stream: TcpStream, dir: &Path, key: &str) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(60)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let nonce = hex(&rand::thread_rng().gen::<[u8; 16]>());
+    writer.write_all(format!("{}\n", nonce).as_bytes())?;
+    let name = read_line(&mut reader)?;
+    let size_str = read_line(&mut reader)?;
+    let mac = read_line(&mut reader)?;
+    let expected = hmac(
+        key.as_bytes(),
+        &[
+            nonce.as_bytes(),
+            b"\n",
+            name.as_bytes(),
+            b"\n",
+            size_str.as_bytes(),
+        ],
+    );
+    if !verify(&mac, &expected) {
+        writer.write_all(b"key mismatch\n")?;
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "key mismatch",
+        ));
+    }
+    //只保留文件名，不能写到目录之外
+    if name.is_empty()
+        || name == "."
+        || name == ".."
+        || name.contains(|c| c == '/' || c == '\\' || c == ':')
+    {
+        writer.write_all(b"invalid file name\n")?;
+        return Err(invalid("invalid file name"));
+    }
+    let size = u64::from_str(&size_str).map_err(|_| invalid("size error"))?;
+    let target = dir.join(&name);
+    let part: PathBuf = dir.join(format!("{}.part", name));
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(&part)?;
+    let mut offset = file.metadata()?.len();
+    if offset > size {
+        file.set_len(0)?;
+        offset = 0;
+    }
+    file.seek(SeekFrom::Start(offset))?;
+    writer.write_all(format!("offset {}\n", offset).as_bytes())?;
+    let mut progress = Progress::new(&name, size, offset);
+    let mut buf = vec![0u8; BUF_SIZE];
+    let mut done = offset;
+    while done < size {
+        let max = ((size - done) as usize).min(buf.len());
+        let len = reader.read(&mut buf[..max])?;
+        if len == 0 {
+            // 保留.part，下次发送时续传
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "connection closed",
+            ));
+        }
+        file.write_all(&buf[..len])?;
+        done += len as u64;
+        progress.update(done);
+    }
+    println!();
+    let mac = read_line(&mut reader)?;
+    let digest = file_sha256(&mut file)?;
+    let expected = hmac(key.as_bytes(), &[nonce.as_bytes(), b"\n", &digest]);
+    if !verify(&mac, &expected) {
+        drop(file);
+        let _ = std::fs::remove_file(&part);
+        writer.write_all(b"checksum mismatch\n")?;
+        return Err(invalid("checksum mismatch"));
+    }
+    drop(file);
+    std::fs::rename(&part, &target)?;
+    writer.write_all(b"ok\n")?;
+    println!("received {}", target.display());
+    Ok(())
+}
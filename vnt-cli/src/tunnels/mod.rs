@@ -0,0 +1,160 @@
+//! 一个进程中运行多个组网，每个组网对应一个配置文件(profile)，有独立的网卡、配置和虚拟ip，
+//! 后台运行时通过命令通道启停，不用为每个组网单独起进程，也不会抢同一个网卡名
+//!
+//! 配置文件在程序目录下的env/profiles/`<profile>`.yaml，格式和-f相同，
+//! 在当前进程的运行时中启动，配置中的线程数等运行时参数不生效；
+//! route_table、kill_switch、bind_device、proxy、mimicry、resolver和seccomp是进程内共用的，
+//! 需要和运行中的组网相同
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use vnt::core::Vnt;
+
+use crate::command::entity::TunnelItem;
+
+/// 运行中的组网，按名称排序
+static TUNNELS: Mutex<BTreeMap<String, Vnt>> = parking_lot::const_mutex(BTreeMap::new());
+
+fn profiles_dir() -> io::Result<PathBuf> {
+    Ok(crate::app_home()?.join("profiles"))
+}
+
+/// 名称用于网卡名，linux下网卡名最长15个字符
+fn check_name(name: &str) -> io::Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 11
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        && !["tun", "tap"].contains(&name.to_ascii_lowercase().as_str());
+    if valid {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "invalid profile name '{}', use up to 11 letters, digits, '-' or '_'",
+                name
+            ),
+        ))
+    }
+}
+
+/// 主组网使用默认网卡名，其他组网的网卡名带上配置名称
+fn device_name(name: &str) -> String {
+    format!("vnt-{}", name)
+}
+
+fn other<E: ToString>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e.to_string())
+}
+
+/// 启动一个组网，命令通道逐个处理连接，不会同时启动同名的组网
+pub async fn up(name: &str) -> io::Result<String> {
+    check_name(name)?;
+    if let Some(vnt) = TUNNELS.lock().get(name) {
+        if !vnt.is_stopped() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("tunnel '{}' is already up", name),
+            ));
+        }
+    }
+    let path = profiles_dir()?.join(format!("{}.yaml", name));
+    if !path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("profile not found: {}", path.display()),
+        ));
+    }
+    let (mut config, ..) = crate::config::read_config(&path.to_string_lossy(), false)?;
+    config.device_name = Some(device_name(name));
+    //socket的fwmark、代理等是进程内共用的，不同时会改变运行中组网的设置
+    vnt::core::check_process_settings(&config)?;
+    //握手和注册是阻塞的，不占用其他任务的工作线程
+    let vnt_util = tokio::task::block_in_place(|| crate::connector::connect(config))?;
+    let vnt = vnt_util
+        .build()
+        .await
+        .map_err(|e| other(crate::error::describe(&e)))?;
+    let virtual_ip = vnt.current_device().virtual_ip();
+    log::info!("tunnel {} up,virtual_ip={}", name, virtual_ip);
+    TUNNELS.lock().insert(name.to_string(), vnt);
+    Ok(format!("tunnel '{}' up, virtual_ip:{}", name, virtual_ip))
+}
+
+/// 停止一个组网，等待网卡和路由清理完成，之后可以马上重新启动
+pub async fn down(name: &str) -> io::Result<String> {
+    let vnt = TUNNELS.lock().remove(name);
+    match vnt {
+        Some(mut vnt) => {
+            vnt.stop()?;
+            vnt.wait_stop_ms(Duration::from_secs(3)).await;
+            log::info!("tunnel {} down", name);
+            Ok(format!("tunnel '{}' down", name))
+        }
+        None => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("tunnel '{}' is not up", name),
+        )),
+    }
+}
+
+/// 主组网停止时一起停止
+pub fn stop_all() {
+    let tunnels = std::mem::take(&mut *TUNNELS.lock());
+    for (name, vnt) in tunnels {
+        if let Err(e) = vnt.stop() {
+            log::warn!("tunnel {} stop {:?}", name, e);
+        }
+    }
+}
+
+/// 所有配置文件和运行状态，包括没有启动的
+pub fn list() -> Vec<TunnelItem> {
+    let mut names: Vec<String> = std::fs::read_dir(profiles_dir().unwrap_or_default())
+        .map(|dir| {
+            dir.flatten()
+                .filter_map(|entry| {
+                    entry
+                        .file_name()
+                        .to_str()?
+                        .strip_suffix(".yaml")
+                        .map(|v| v.to_string())
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let tunnels = TUNNELS.lock();
+    for name in tunnels.keys() {
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+    names.sort();
+    names
+        .into_iter()
+        .map(|name| match tunnels.get(&name) {
+            Some(vnt) => TunnelItem {
+                device: device_name(&name),
+                virtual_ip: vnt.current_device().virtual_ip().to_string(),
+                status: if vnt.is_stopped() {
+                    "stopped".to_string()
+                } else {
+                    format!("{:?}", vnt.connection_status())
+                },
+                name,
+            },
+            None => TunnelItem {
+                device: String::new(),
+                virtual_ip: String::new(),
+                status: "down".to_string(),
+                name,
+            },
+        })
+        .collect()
+}
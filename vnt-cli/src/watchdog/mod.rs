@@ -0,0 +1,132 @@
+//! 看门狗，事件循环或者tun读取卡住时在进程内重启vnt，不依赖systemd等外部服务重启
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tokio::sync::Notify;
+
+use vnt::core::Vnt;
+
+/// 检查间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// 事件循环的打点间隔
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+/// 事件循环超过这个时间没有打点，认为运行时卡死
+const TICK_TIMEOUT: Duration = Duration::from_secs(30);
+/// tun读取线程处理一个包超过这个时间，认为卡死
+const TUN_TIMEOUT: Duration = Duration::from_secs(30);
+/// 主心跳超过几个间隔没有执行，认为卡死
+const HEARTBEAT_MISSES: u32 = 4;
+/// 主心跳的最小超时，避免间隔很小时误判
+const HEARTBEAT_MIN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 看门狗触发过，主流程退出后需要重新启动
+static RESTART: AtomicBool = AtomicBool::new(false);
+
+pub struct Watchdog {
+    wedged: Arc<Notify>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Watchdog {
+    /// 检测到卡死时返回，此时诊断信息已经写入日志
+    pub async fn wedged(&self) {
+        self.wedged.notified().await;
+    }
+}
+
+/// 取出重启标记
+pub fn take_restart() -> bool {
+    RESTART.swap(false, Ordering::Relaxed)
+}
+
+/// 在运行时中打点，另起一个线程检查，运行时卡住时检查线程不受影响
+pub fn start(vnt: Vnt) -> Watchdog {
+    let start = Instant::now();
+    let tick = Arc::new(AtomicU64::new(0));
+    let wedged = Arc::new(Notify::new());
+    let stopped = Arc::new(AtomicBool::new(false));
+    {
+        let tick = tick.clone();
+        let stopped = stopped.clone();
+        tokio::spawn(async move {
+            while !stopped.load(Ordering::Relaxed) {
+                tick.store(start.elapsed().as_millis() as u64, Ordering::Relaxed);
+                tokio::time::sleep(TICK_INTERVAL).await;
+            }
+        });
+    }
+    {
+        let wedged = wedged.clone();
+        let stopped = stopped.clone();
+        let rs = thread::Builder::new()
+            .name("watchdog".into())
+            .spawn(move || loop {
+                thread::sleep(CHECK_INTERVAL);
+                if stopped.load(Ordering::Relaxed) || vnt.is_stopped() {
+                    return;
+                }
+                let tick_age = Duration::from_millis(
+                    (start.elapsed().as_millis() as u64)
+                        .saturating_sub(tick.load(Ordering::Relaxed)),
+                );
+                if let Some(reason) = check(&vnt, tick_age) {
+                    snapshot(&vnt, tick_age, &reason);
+                    RESTART.store(true, Ordering::Relaxed);
+                    //没有等待者时保留许可，主流程稍后进入select也能收到
+                    wedged.notify_one();
+                    return;
+                }
+            });
+        if let Err(e) = rs {
+            log::warn!("看门狗启动失败:{:?}", e);
+        }
+    }
+    Watchdog { wedged, stopped }
+}
+
+fn check(vnt: &Vnt, tick_age: Duration) -> Option<String> {
+    if tick_age > TICK_TIMEOUT {
+        return Some(format!("event loop stalled for {:?}", tick_age));
+    }
+    let liveness = vnt.liveness();
+    if let Some(busy) = liveness.tun_busy {
+        if busy > TUN_TIMEOUT {
+            return Some(format!("tun reader stalled for {:?}", busy));
+        }
+    }
+    //还没有发送过心跳时间隔为0
+    if !liveness.heartbeat_interval.is_zero() {
+        let timeout = (liveness.heartbeat_interval * HEARTBEAT_MISSES).max(HEARTBEAT_MIN_TIMEOUT);
+        if liveness.heartbeat_age > timeout {
+            return Some(format!(
+                "heartbeat stalled for {:?}",
+                liveness.heartbeat_age
+            ));
+        }
+    }
+    None
+}
+
+/// 重启前记录诊断信息，只读取原子变量，避免在卡住的锁上再次阻塞
+fn snapshot(vnt: &Vnt, tick_age: Duration, reason: &str) {
+    let liveness = vnt.liveness();
+    let counters = vnt.counters();
+    log::error!(
+        "看门狗触发，重启vnt:{} status:{:?} event_loop_age:{:?} liveness:{:?} counters:{:?}",
+        reason,
+        vnt.connection_status(),
+        tick_age,
+        liveness,
+        counters
+    );
+    println!("watchdog: {}, restarting", reason);
+}
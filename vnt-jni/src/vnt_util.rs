@@ -8,10 +8,9 @@ use jni::objects::{JClass, JObject, JString, JValue};
 use jni::sys::jboolean;
 use jni::sys::{jint, jlong, jobject};
 use jni::JNIEnv;
-use vnt::channel::punch::PunchModel;
 use vnt::cipher::CipherModel;
 use vnt::core::sync::VntUtilSync;
-use vnt::core::Config;
+use vnt::core::ConfigOptions;
 use vnt::handle::registration_handler::{RegResponse, ReqEnum};
 #[cfg(not(target_os = "android"))]
 use vnt::tun_tap_device::DriverInfo;
@@ -130,8 +129,7 @@ fn new_sync(env: &mut JNIEnv, config: JObject) -> Result<VntUtilSync, Error> {
     for addr in stun_server_str.split(",") {
         stun_server.push(addr.trim().to_string());
     }
-    let config = match Config::new(
-        false,
+    let options = ConfigOptions {
         token,
         device_id,
         name,
@@ -141,20 +139,14 @@ fn new_sync(env: &mut JNIEnv, config: JObject) -> Result<VntUtilSync, Error> {
         in_ips,
         out_ips,
         password,
-        false,
-        None,
         tcp,
-        None,
-        false,
-        false,
-        false,
-        1,
         cipher_model,
         finger,
-        PunchModel::All,
         port,
         first_latency,
-    ) {
+        ..Default::default()
+    };
+    let config = match options.build() {
         Ok(config) => config,
         Err(e) => {
             env.throw_new(
@@ -280,6 +272,37 @@ pub unsafe extern "C" fn Java_top_wherewego_vnt_jni_VntUtil_register0(
                 )
                 .expect("throw");
             }
+            ReqEnum::DeviceIdConflict => {
+                env.throw_new(
+                    "java/lang/RuntimeException",
+                    "vnt register error device id conflict",
+                )
+                .expect("throw");
+            }
+            ReqEnum::RateLimited(retry_after) => {
+                env.throw_new(
+                    "java/lang/RuntimeException",
+                    format!(
+                        "vnt register error rate limited, retry after {}s",
+                        retry_after
+                    ),
+                )
+                .expect("throw");
+            }
+            ReqEnum::DeviceLimit => {
+                env.throw_new(
+                    "java/lang/RuntimeException",
+                    "vnt register error device limit reached",
+                )
+                .expect("throw");
+            }
+            ReqEnum::Maintenance(maintenance) => {
+                env.throw_new(
+                    "java/lang/RuntimeException",
+                    format!("vnt register error maintenance: {}", maintenance),
+                )
+                .expect("throw");
+            }
         },
     }
     return ptr::null_mut();
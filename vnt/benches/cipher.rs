@@ -0,0 +1,72 @@
+use std::str::FromStr;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use vnt::cipher::{Cipher, CipherModel};
+use vnt::protocol::body::ENCRYPTION_RESERVED;
+use vnt::protocol::{NetPacket, Protocol, Version, MAX_TTL};
+
+const PAYLOAD_LEN: usize = 1400;
+
+fn models() -> Vec<&'static str> {
+    let mut models = Vec::new();
+    #[cfg(any(feature = "aes_gcm", feature = "server_encrypt"))]
+    models.push("aes_gcm");
+    #[cfg(feature = "chacha20_poly1305")]
+    models.push("chacha20_poly1305");
+    #[cfg(feature = "aes_cbc")]
+    models.push("aes_cbc");
+    #[cfg(feature = "aes_ecb")]
+    models.push("aes_ecb");
+    #[cfg(feature = "sm4_cbc")]
+    models.push("sm4_cbc");
+    models
+}
+
+fn packet() -> NetPacket<Vec<u8>> {
+    let mut packet =
+        NetPacket::new_encrypt(vec![0u8; 12 + PAYLOAD_LEN + ENCRYPTION_RESERVED]).unwrap();
+    packet.set_version(Version::V1);
+    packet.set_protocol(Protocol::IpTurn);
+    packet.first_set_ttl(MAX_TTL);
+    packet.payload_mut().fill(0x5a);
+    packet
+}
+
+fn encrypt_decrypt(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cipher");
+    group.throughput(Throughput::Bytes(PAYLOAD_LEN as u64));
+    for model in models() {
+        let cipher = Cipher::new_password(
+            CipherModel::from_str(model).unwrap(),
+            Some("password".to_string()),
+            Some("token".to_string()),
+        );
+        group.bench_with_input(BenchmarkId::new("encrypt", model), &cipher, |b, cipher| {
+            b.iter_batched(
+                packet,
+                |mut packet| {
+                    cipher.encrypt_ipv4(&mut packet).unwrap();
+                    packet
+                },
+                criterion::BatchSize::SmallInput,
+            )
+        });
+        let mut encrypted = packet();
+        cipher.encrypt_ipv4(&mut encrypted).unwrap();
+        group.bench_with_input(BenchmarkId::new("decrypt", model), &cipher, |b, cipher| {
+            b.iter_batched(
+                || encrypted.clone(),
+                |mut packet| {
+                    cipher.decrypt_ipv4(&mut packet).unwrap();
+                    packet
+                },
+                criterion::BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, encrypt_decrypt);
+criterion_main!(benches);
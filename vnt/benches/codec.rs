@@ -0,0 +1,50 @@
+use std::hint::black_box;
+use std::net::Ipv4Addr;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use vnt::protocol::{ip_turn_packet, NetPacket, Protocol, Version, MAX_TTL};
+
+fn encode(c: &mut Criterion) {
+    let mut buf = [0u8; 12 + 1400];
+    c.bench_function("codec/encode", |b| {
+        b.iter(|| {
+            let mut packet = NetPacket::new(&mut buf[..]).unwrap();
+            packet.set_version(Version::V1);
+            packet.set_protocol(Protocol::IpTurn);
+            packet.set_transport_protocol(ip_turn_packet::Protocol::Ipv4.into());
+            packet.first_set_ttl(MAX_TTL);
+            packet.set_source(black_box(Ipv4Addr::new(10, 26, 0, 2)));
+            packet.set_destination(black_box(Ipv4Addr::new(10, 26, 0, 3)));
+            packet.data_len()
+        })
+    });
+}
+
+fn decode(c: &mut Criterion) {
+    let mut buf = [0u8; 12 + 1400];
+    {
+        let mut packet = NetPacket::new(&mut buf[..]).unwrap();
+        packet.set_version(Version::V1);
+        packet.set_protocol(Protocol::IpTurn);
+        packet.first_set_ttl(MAX_TTL);
+        packet.set_source(Ipv4Addr::new(10, 26, 0, 2));
+        packet.set_destination(Ipv4Addr::new(10, 26, 0, 3));
+    }
+    c.bench_function("codec/decode", |b| {
+        b.iter(|| {
+            let packet = NetPacket::new(black_box(&buf[..])).unwrap();
+            (
+                packet.version(),
+                packet.protocol(),
+                packet.ttl(),
+                packet.source(),
+                packet.destination(),
+                packet.payload().len(),
+            )
+        })
+    });
+}
+
+criterion_group!(benches, encode, decode);
+criterion_main!(benches);
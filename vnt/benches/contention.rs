@@ -0,0 +1,113 @@
+use std::hint::black_box;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crossbeam_utils::atomic::AtomicCell;
+
+use vnt::channel::channel::Context;
+use vnt::channel::Route;
+use vnt::handle::CurrentDeviceInfo;
+
+/* 路由表(Context中的route_table)在多线程转发时的争用
+   每个线程模拟转发热路径：route_one查路由、update_read_time更新最近读取时间，
+   每64次操作有一次add_route(打洞成功或路由变化)
+   路由表曾改为DashMap，在1个vCPU上测得DashMap比RwLock<HashMap>慢约15%，没有多核上有收益的数据，
+   因此仍使用RwLock<HashMap>。更换路由表的实现前后在多核机器上分别运行对比：
+   cargo bench -p vnt --bench contention
+*/
+
+const PEERS: u32 = 256;
+const WRITE_EVERY: u64 = 64;
+
+fn peer(i: u64) -> Ipv4Addr {
+    Ipv4Addr::from(u32::from(Ipv4Addr::new(10, 26, 1, 0)) + (i % PEERS as u64) as u32)
+}
+
+fn route(ip: Ipv4Addr, rt: i64) -> Route {
+    let addr = SocketAddr::new(ip.into(), 10000);
+    Route::new(false, 1, addr, 1, rt)
+}
+
+fn context() -> Context {
+    let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server: SocketAddr = "127.0.0.1:29872".parse().unwrap();
+    let current_device = Arc::new(AtomicCell::new(CurrentDeviceInfo::new(
+        Ipv4Addr::new(10, 26, 0, 2),
+        Ipv4Addr::new(10, 26, 0, 1),
+        Ipv4Addr::new(255, 255, 0, 0),
+        server,
+    )));
+    let context = Context::new(
+        udp,
+        None,
+        current_device,
+        1,
+        false,
+        0,
+        None,
+        false,
+        None,
+        Default::default(),
+        false,
+        Default::default(),
+        Default::default(),
+        None,
+        None,
+        None,
+        Default::default(),
+    );
+    for i in 0..PEERS as u64 {
+        context.add_route(peer(i), route(peer(i), 10));
+    }
+    context
+}
+
+/// threads个线程一共执行iters次操作，返回耗时
+fn run(context: &Context, threads: u64, iters: u64) -> Duration {
+    let barrier = Arc::new(Barrier::new(threads as usize + 1));
+    let handles: Vec<_> = (0..threads)
+        .map(|t| {
+            let context = context.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                barrier.wait();
+                let mut seed = t * 7919;
+                for i in 0..(iters + threads - 1) / threads {
+                    seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                    let id = peer(seed >> 33);
+                    if i % WRITE_EVERY == 0 {
+                        context.add_route(id, route(id, 10 + (seed % 3) as i64));
+                    } else if let Some(route) = context.route_one(&id) {
+                        context.update_read_time(&id, &black_box(route).route_key());
+                    }
+                }
+            })
+        })
+        .collect();
+    barrier.wait();
+    let start = Instant::now();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    start.elapsed()
+}
+
+fn contention(c: &mut Criterion) {
+    let context = context();
+    let mut group = c.benchmark_group("route_contention");
+    group.throughput(Throughput::Elements(1));
+    for threads in [1u64, 4, 8] {
+        group.bench_with_input(
+            BenchmarkId::new("context", threads),
+            &threads,
+            |b, &threads| b.iter_custom(|iters| run(&context, threads, iters)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, contention);
+criterion_main!(benches);
@@ -0,0 +1,105 @@
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, Criterion, Throughput};
+
+use vnt::cipher::{Cipher, CipherModel};
+use vnt::protocol::body::ENCRYPTION_RESERVED;
+use vnt::protocol::{ip_turn_packet, NetPacket, Protocol, Version, HEAD_LEN, MAX_TTL};
+
+/* 单个数据包的封包加密和解密拆包：
+   ip数据 -> NetPacket封包 -> 加密 -> 解密 -> 取出ip数据
+   只包含NetPacket和加密，不经过网卡、收发线程和tun_handler/recv_handler中的路由、过滤等处理
+*/
+
+const PAYLOAD_LEN: usize = 1400;
+/// 吞吐量低于这个值(MiB/s)时判定为性能回退，可以用环境变量VNT_BENCH_MIN_MIBS调整
+const MIN_MIBS: f64 = 50.0;
+const CHECK_TIME: Duration = Duration::from_secs(3);
+
+struct PacketCrypto {
+    cipher: Cipher,
+    ip_packet: Vec<u8>,
+    buf: Vec<u8>,
+}
+
+impl PacketCrypto {
+    fn new() -> PacketCrypto {
+        #[cfg(any(feature = "aes_gcm", feature = "server_encrypt"))]
+        let model = CipherModel::AesGcm;
+        #[cfg(not(any(feature = "aes_gcm", feature = "server_encrypt")))]
+        let model = CipherModel::None;
+        PacketCrypto {
+            cipher: Cipher::new_password(model, Some("password".to_string()), None),
+            ip_packet: vec![0x5a; PAYLOAD_LEN],
+            buf: vec![0u8; HEAD_LEN + PAYLOAD_LEN + ENCRYPTION_RESERVED],
+        }
+    }
+    /// 返回解密后的ip数据长度
+    fn round_trip(&mut self) -> usize {
+        self.buf[HEAD_LEN..HEAD_LEN + PAYLOAD_LEN].copy_from_slice(&self.ip_packet);
+        let mut packet = NetPacket::new_encrypt(&mut self.buf[..]).unwrap();
+        packet.set_version(Version::V1);
+        packet.set_protocol(Protocol::IpTurn);
+        packet.set_transport_protocol(ip_turn_packet::Protocol::Ipv4.into());
+        packet.first_set_ttl(MAX_TTL);
+        packet.set_source(Ipv4Addr::new(10, 26, 0, 2));
+        packet.set_destination(Ipv4Addr::new(10, 26, 0, 3));
+        self.cipher.encrypt_ipv4(&mut packet).unwrap();
+        let len = packet.buffer().len();
+
+        let mut packet = NetPacket::new(&mut self.buf[..len]).unwrap();
+        self.cipher.decrypt_ipv4(&mut packet).unwrap();
+        packet.payload().len()
+    }
+}
+
+fn packet_crypto(c: &mut Criterion) {
+    let mut packet_crypto = PacketCrypto::new();
+    let mut group = c.benchmark_group("packet_crypto");
+    group.throughput(Throughput::Bytes(PAYLOAD_LEN as u64));
+    group.bench_function("round_trip", |b| b.iter(|| packet_crypto.round_trip()));
+    group.finish();
+}
+
+/// criterion只记录结果，不会因为变慢而失败，这里单独测一次吞吐量和阈值比较
+fn regression_check() {
+    let min = std::env::var("VNT_BENCH_MIN_MIBS")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .unwrap_or(MIN_MIBS);
+    let mut packet_crypto = PacketCrypto::new();
+    for _ in 0..1000 {
+        assert_eq!(packet_crypto.round_trip(), PAYLOAD_LEN);
+    }
+    let start = Instant::now();
+    let mut bytes = 0usize;
+    while start.elapsed() < CHECK_TIME {
+        for _ in 0..1000 {
+            bytes += packet_crypto.round_trip();
+        }
+    }
+    let mibs = bytes as f64 / 1024.0 / 1024.0 / start.elapsed().as_secs_f64();
+    println!(
+        "packet_crypto throughput: {:.1} MiB/s (threshold {:.1} MiB/s)",
+        mibs, min
+    );
+    if mibs < min {
+        eprintln!(
+            "packet_crypto throughput regression: {:.1} MiB/s < {:.1} MiB/s",
+            mibs, min
+        );
+        std::process::exit(1);
+    }
+}
+
+criterion_group!(benches, packet_crypto);
+
+fn main() {
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+    //cargo test --benches只验证能运行，不做阈值检查
+    if std::env::args().any(|arg| arg == "--bench") {
+        regression_check();
+    }
+}
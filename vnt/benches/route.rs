@@ -0,0 +1,64 @@
+use std::hint::black_box;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use crossbeam_utils::atomic::AtomicCell;
+
+use vnt::channel::channel::Context;
+use vnt::channel::Route;
+use vnt::handle::CurrentDeviceInfo;
+
+fn context(peers: u32) -> Context {
+    let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server: SocketAddr = "127.0.0.1:29872".parse().unwrap();
+    let current_device = Arc::new(AtomicCell::new(CurrentDeviceInfo::new(
+        Ipv4Addr::new(10, 26, 0, 2),
+        Ipv4Addr::new(10, 26, 0, 1),
+        Ipv4Addr::new(255, 255, 0, 0),
+        server,
+    )));
+    let context = Context::new(
+        udp,
+        None,
+        current_device,
+        1,
+        false,
+        0,
+        None,
+        false,
+        None,
+        Default::default(),
+        false,
+        Default::default(),
+        Default::default(),
+        None,
+        None,
+        None,
+        Default::default(),
+    );
+    for i in 0..peers {
+        let ip = Ipv4Addr::from(u32::from(Ipv4Addr::new(10, 26, 1, 0)) + i);
+        let addr = SocketAddr::new(ip.into(), 10000 + i as u16);
+        context.add_route(ip, Route::new(false, 1, addr, 1, 10));
+    }
+    context
+}
+
+fn lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("route");
+    for peers in [16u32, 256, 1024] {
+        let context = context(peers);
+        let target = Ipv4Addr::from(u32::from(Ipv4Addr::new(10, 26, 1, 0)) + peers / 2);
+        group.bench_with_input(BenchmarkId::new("route_one", peers), &target, |b, ip| {
+            b.iter(|| context.route_one(black_box(ip)))
+        });
+        group.bench_with_input(BenchmarkId::new("need_punch", peers), &target, |b, ip| {
+            b.iter(|| context.need_punch(black_box(ip)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, lookup);
+criterion_main!(benches);
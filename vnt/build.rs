@@ -11,4 +11,39 @@ fn main() {
         // )
         .run()
         .expect("Codegen failed.");
+    bundle_wintun();
+}
+
+/// 开启bundle-wintun时内置wintun.dll，从WINTUN_DIR(wintun压缩包解压后的目录，默认为vnt/wintun)中取和目标架构一致的dll
+fn bundle_wintun() {
+    if std::env::var_os("CARGO_FEATURE_BUNDLE_WINTUN").is_none()
+        || std::env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("windows")
+    {
+        return;
+    }
+    let arch = match std::env::var("CARGO_CFG_TARGET_ARCH").unwrap().as_str() {
+        "x86_64" => "amd64",
+        "x86" => "x86",
+        "aarch64" => "arm64",
+        "arm" => "arm",
+        arch => panic!("wintun does not support {}", arch),
+    };
+    let dir = std::env::var("WINTUN_DIR").unwrap_or("wintun".to_string());
+    let dll = std::path::Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap())
+        .join(dir)
+        .join("bin")
+        .join(arch)
+        .join("wintun.dll");
+    if !dll.exists() {
+        panic!(
+            "{} not found,download https://www.wintun.net/builds/wintun-0.14.1.zip and set WINTUN_DIR to the extracted directory",
+            dll.display()
+        );
+    }
+    //指定了rerun-if后不再默认监听包内所有文件，proto也要加上
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=proto/message.proto");
+    println!("cargo:rerun-if-env-changed=WINTUN_DIR");
+    println!("cargo:rerun-if-changed={}", dll.display());
+    println!("cargo:rustc-env=VNT_WINTUN_DLL={}", dll.display());
 }
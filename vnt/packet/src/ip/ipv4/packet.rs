@@ -78,6 +78,9 @@ impl<B: AsRef<[u8]> + AsMut<[u8]>> IpV4Packet<B> {
     pub fn set_destination_ip(&mut self, value: Ipv4Addr) {
         self.header_mut()[16..20].copy_from_slice(&value.octets());
     }
+    pub fn set_ttl(&mut self, ttl: u8) {
+        self.header_mut()[8] = ttl;
+    }
     pub fn set_flags(&mut self, flags: u8) {
         self.buffer.as_mut()[6] = (self.buffer.as_ref()[6] & 0b11100000) | (flags << 5)
     }
@@ -16,7 +16,7 @@ use std::ffi::{CStr, CString};
 use std::io;
 use std::mem;
 use std::net::Ipv4Addr;
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::ptr;
 use std::sync::Arc;
 use std::vec::Vec;
@@ -81,7 +81,11 @@ impl Device {
                 } else {
                     IFF_NO_PI
                 }
-                | if queues_num > 1 { IFF_MULTI_QUEUE } else { 0 };
+                | if queues_num > 1 || config.platform.multi_queue {
+                    IFF_MULTI_QUEUE
+                } else {
+                    0
+                };
 
             for _ in 0..queues_num {
                 let tun = Fd::new(libc::open(b"/dev/net/tun\0".as_ptr() as *const _, O_RDWR))
@@ -97,6 +101,17 @@ impl Device {
                 });
             }
 
+            if let Some(owner) = config.platform.owner {
+                if tunsetowner(queues[0].tun.0, owner) < 0 {
+                    return Err(io::Error::last_os_error().into());
+                }
+            }
+            if let Some(group) = config.platform.group {
+                if tunsetgroup(queues[0].tun.0, group) < 0 {
+                    return Err(io::Error::last_os_error().into());
+                }
+            }
+
             let ctl = Fd::new(libc::socket(AF_INET, SOCK_DGRAM, 0))
                 .map_err(|_| io::Error::last_os_error())?;
 
@@ -114,6 +129,23 @@ impl Device {
         Ok(device)
     }
 
+    /// Wrap a TUN/TAP descriptor that was opened by another (privileged)
+    /// process and passed to us, e.g. over a unix socket.
+    pub fn from_fd(fd: RawFd, name: &str, packet_information: bool) -> Result<Self> {
+        let tun = Fd::new(fd)?;
+        let ctl = unsafe {
+            Fd::new(libc::socket(AF_INET, SOCK_DGRAM, 0)).map_err(|_| io::Error::last_os_error())?
+        };
+        Ok(Device {
+            name: name.to_string(),
+            queues: vec![Queue {
+                tun: Arc::new(tun),
+                pi_enabled: packet_information,
+            }],
+            ctl,
+        })
+    }
+
     /// Prepare a new request.
     unsafe fn request(&self) -> ifreq {
         let mut req: ifreq = mem::zeroed();
@@ -137,27 +169,6 @@ impl Device {
     //     }
     // }
 
-    // /// Set the owner of the device.
-    // pub fn user(&mut self, value: i32) -> Result<()> {
-    //     unsafe {
-    //         if tunsetowner(self.as_raw_fd(), &value) < 0 {
-    //             Err(io::Error::last_os_error().into())
-    //         } else {
-    //             Ok(())
-    //         }
-    //     }
-    // }
-    //
-    // /// Set the group of the device.
-    // pub fn group(&mut self, value: i32) -> Result<()> {
-    //     unsafe {
-    //         if tunsetgroup(self.as_raw_fd(), &value) < 0 {
-    //             Err(io::Error::last_os_error().into())
-    //         } else {
-    //             Ok(())
-    //         }
-    //     }
-    // }
     /// Return whether the device has packet information
     pub fn has_packet_information(&self) -> bool {
         self.queues[0].has_packet_information()
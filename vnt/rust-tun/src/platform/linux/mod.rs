@@ -26,6 +26,9 @@ use crate::error::*;
 #[derive(Copy, Clone, Default, Debug)]
 pub struct Configuration {
     pub(crate) packet_information: bool,
+    pub(crate) multi_queue: bool,
+    pub(crate) owner: Option<u32>,
+    pub(crate) group: Option<u32>,
 }
 
 impl Configuration {
@@ -35,6 +38,25 @@ impl Configuration {
         self.packet_information = value;
         self
     }
+
+    /// Create the device with IFF_MULTI_QUEUE even if only one queue is
+    /// opened, so that other processes can attach more queues later.
+    pub fn multi_queue(&mut self, value: bool) -> &mut Self {
+        self.multi_queue = value;
+        self
+    }
+
+    /// Set the user allowed to attach to the device.
+    pub fn owner(&mut self, uid: u32) -> &mut Self {
+        self.owner = Some(uid);
+        self
+    }
+
+    /// Set the group allowed to attach to the device.
+    pub fn group(&mut self, gid: u32) -> &mut Self {
+        self.group = Some(gid);
+        self
+    }
 }
 
 /// Create a TUN device with the given name.
@@ -107,5 +107,20 @@ ioctl!(bad write siocsifname with 0x8923; ifreq);
 
 ioctl!(write tunsetiff with b'T', 202; c_int);
 ioctl!(write tunsetpersist with b'T', 203; c_int);
-ioctl!(write tunsetowner with b'T', 204; c_int);
-ioctl!(write tunsetgroup with b'T', 206; c_int);
+
+/// TUNSETOWNER and TUNSETGROUP take the id itself as the argument, not a pointer.
+pub unsafe fn tunsetowner(fd: c_int, owner: c_uint) -> c_int {
+    libc::ioctl(
+        fd,
+        iow!(b'T', 204, std::mem::size_of::<c_int>()) as _,
+        owner as c_ulong,
+    )
+}
+
+pub unsafe fn tunsetgroup(fd: c_int, group: c_uint) -> c_int {
+    libc::ioctl(
+        fd,
+        iow!(b'T', 206, std::mem::size_of::<c_int>()) as _,
+        group as c_ulong,
+    )
+}
@@ -0,0 +1,117 @@
+use std::io;
+use std::process::Command;
+
+const BRIDGE_NAME: &str = "vnt-br0";
+
+/// 使用ip命令创建网桥，物理网卡的地址和默认路由转移到网桥上
+pub struct LinuxBridge {
+    tap: String,
+    nic: String,
+    addrs: Vec<String>,
+    gateway: Option<String>,
+}
+
+impl LinuxBridge {
+    pub fn enable(tap: &str, nic: &str) -> io::Result<LinuxBridge> {
+        // 清理非正常退出时残留的网桥
+        let _ = ip(&format!("link del {}", BRIDGE_NAME));
+        let addrs = nic_addrs(nic)?;
+        let gateway = default_gateway(nic)?;
+        let mac = std::fs::read_to_string(format!("/sys/class/net/{}/address", nic))?;
+        let bridge = LinuxBridge {
+            tap: tap.to_string(),
+            nic: nic.to_string(),
+            addrs,
+            gateway,
+        };
+        ip(&format!("link add name {} type bridge", BRIDGE_NAME))?;
+        // 使用物理网卡的mac，dhcp分配的地址和局域网设备的arp缓存不受影响
+        ip(&format!("link set {} address {}", BRIDGE_NAME, mac.trim()))?;
+        ip(&format!("link set {} master {}", nic, BRIDGE_NAME))?;
+        ip(&format!("link set {} up", BRIDGE_NAME))?;
+        for addr in &bridge.addrs {
+            ip(&format!("addr del {} dev {}", addr, nic))?;
+            ip(&format!("addr add {} dev {}", addr, BRIDGE_NAME))?;
+        }
+        if let Some(gateway) = &bridge.gateway {
+            ip(&format!(
+                "route replace default via {} dev {}",
+                gateway, BRIDGE_NAME
+            ))?;
+        }
+        ip(&format!("link set {} master {}", tap, BRIDGE_NAME))?;
+        // 本机发往虚拟网络的帧目的mac就是tap自己，以此和网桥上其他设备的帧区分
+        ip(&format!("link set {} arp off", tap))?;
+        // 回包可能从网桥进来，使用宽松的反向路径检查
+        for dev in [tap, BRIDGE_NAME] {
+            let _ = std::fs::write(format!("/proc/sys/net/ipv4/conf/{}/rp_filter", dev), "2");
+        }
+        log::info!(
+            "bridge enabled,{} <-> {},addrs={:?},gateway={:?}",
+            tap,
+            nic,
+            bridge.addrs,
+            bridge.gateway
+        );
+        Ok(bridge)
+    }
+}
+
+impl Drop for LinuxBridge {
+    fn drop(&mut self) {
+        let _ = ip(&format!("link set {} nomaster", self.tap));
+        let _ = ip(&format!("link set {} arp on", self.tap));
+        let _ = ip(&format!("link set {} nomaster", self.nic));
+        let _ = ip(&format!("link del {}", BRIDGE_NAME));
+        for addr in &self.addrs {
+            let _ = ip(&format!("addr add {} dev {}", addr, self.nic));
+        }
+        if let Some(gateway) = &self.gateway {
+            let _ = ip(&format!(
+                "route replace default via {} dev {}",
+                gateway, self.nic
+            ));
+        }
+        log::info!("bridge disabled");
+    }
+}
+
+/// 物理网卡上的ipv4地址，格式为 192.168.1.2/24
+fn nic_addrs(nic: &str) -> io::Result<Vec<String>> {
+    let out = ip(&format!("-4 -o addr show dev {}", nic))?;
+    Ok(out
+        .lines()
+        .filter_map(|line| {
+            let mut iter = line.split_whitespace();
+            iter.find(|v| *v == "inet")?;
+            iter.next().map(|v| v.to_string())
+        })
+        .collect())
+}
+
+fn default_gateway(nic: &str) -> io::Result<Option<String>> {
+    let out = ip(&format!("-4 route show default dev {}", nic))?;
+    let mut iter = out.split_whitespace();
+    if iter.any(|v| v == "via") {
+        return Ok(iter.next().map(|v| v.to_string()));
+    }
+    Ok(None)
+}
+
+fn ip(args: &str) -> io::Result<String> {
+    let out = Command::new("sh")
+        .arg("-c")
+        .arg(format!("ip {}", args))
+        .output()?;
+    if !out.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "cmd:ip {},error: {}",
+                args,
+                String::from_utf8_lossy(&out.stderr)
+            ),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
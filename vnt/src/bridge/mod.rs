@@ -0,0 +1,38 @@
+use std::io;
+
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "windows")]
+mod windows;
+
+/// 桥接模式，把tap网卡和物理网卡放到同一个网桥上，虚拟网络的二层和局域网打通，drop时还原
+pub struct Bridge {
+    #[cfg(target_os = "linux")]
+    inner: linux::LinuxBridge,
+}
+
+impl Bridge {
+    /// tap为虚拟网卡名称，nic为要桥接的物理网卡
+    #[cfg(target_os = "linux")]
+    pub fn enable(tap: &str, nic: &str) -> io::Result<Bridge> {
+        let inner = linux::LinuxBridge::enable(tap, nic)?;
+        Ok(Bridge { inner })
+    }
+    /// windows没有公开的创建网桥的接口，只检查tap网卡是否已经在网桥上
+    #[cfg(target_os = "windows")]
+    pub fn enable(tap: &str, nic: &str) -> io::Result<Bridge> {
+        windows::check(tap, nic)?;
+        Ok(Bridge {})
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    pub fn enable(_tap: &str, _nic: &str) -> io::Result<Bridge> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "bridge not supported",
+        ))
+    }
+    pub fn disable(self) {
+        #[cfg(target_os = "linux")]
+        drop(self.inner);
+    }
+}
@@ -0,0 +1,27 @@
+use std::io;
+use std::os::windows::process::CommandExt;
+use std::process::Command;
+
+/// 网桥需要在“网络连接”(ncpa.cpl)中同时选中tap网卡和物理网卡，右键“桥接”创建，
+/// 这里检查tap网卡是否绑定了网桥协议(ms_bridge)
+pub fn check(tap: &str, nic: &str) -> io::Result<()> {
+    let cmd = format!(
+        "(Get-NetAdapterBinding -Name '{}' -ComponentID ms_bridge).Enabled",
+        tap
+    );
+    let out = Command::new("powershell")
+        .creation_flags(0x08000000) //CREATE_NO_WINDOW
+        .args(["-NoProfile", "-Command", &cmd])
+        .output()?;
+    if String::from_utf8_lossy(&out.stdout).trim() == "True" {
+        log::info!("bridge enabled,{} <-> {}", tap, nic);
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        format!(
+            "'{}' is not bridged,open ncpa.cpl,select '{}' and '{}',right click 'Bridge Connections'",
+            tap, tap, nic
+        ),
+    ))
+}
@@ -0,0 +1,139 @@
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use dashmap::DashMap;
+use parking_lot::Mutex;
+
+/// 虚拟网络中广播/组播的处理方式
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum BroadcastPolicy {
+    /// 全部转发
+    #[default]
+    Full,
+    /// 本机发出的广播限制为每秒N个包，超出的丢弃
+    Limited(u32),
+    /// 不发送也不接收广播
+    Disabled,
+}
+
+impl FromStr for BroadcastPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        match s.trim() {
+            "" | "full" => Ok(BroadcastPolicy::Full),
+            "disable" | "disabled" => Ok(BroadcastPolicy::Disabled),
+            v => match v.strip_prefix("limited:").map(|pps| pps.parse::<u32>()) {
+                Some(Ok(pps)) if pps > 0 => Ok(BroadcastPolicy::Limited(pps)),
+                _ => Err(format!(
+                    "not match '{}', enum: full/limited:<pps>/disable",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+impl fmt::Display for BroadcastPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BroadcastPolicy::Full => f.write_str("full"),
+            BroadcastPolicy::Limited(pps) => write!(f, "limited:{}", pps),
+            BroadcastPolicy::Disabled => f.write_str("disable"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BroadcastStats {
+    pub policy: BroadcastPolicy,
+    /// 本机发出的广播包数
+    pub sent: u64,
+    /// 超出限制被丢弃的包数
+    pub dropped: u64,
+    /// 收到的广播包数，按来源设备统计，多的在前
+    pub received: Vec<(Ipv4Addr, u64)>,
+}
+
+/// 按策略限制广播，同时统计广播流量的来源
+#[derive(Default)]
+pub struct BroadcastControl {
+    policy: BroadcastPolicy,
+    /// 令牌桶，(剩余令牌,上次补充时间)
+    bucket: Mutex<Option<(f64, Instant)>>,
+    sent: AtomicU64,
+    dropped: AtomicU64,
+    received: DashMap<Ipv4Addr, AtomicU64>,
+}
+
+impl BroadcastControl {
+    pub fn new(policy: BroadcastPolicy) -> Self {
+        Self {
+            policy,
+            ..Default::default()
+        }
+    }
+    pub fn policy(&self) -> BroadcastPolicy {
+        self.policy
+    }
+    /// 本机发出的广播是否可以发送
+    pub fn allow_send(&self) -> bool {
+        let allow = match self.policy {
+            BroadcastPolicy::Full => true,
+            BroadcastPolicy::Disabled => false,
+            BroadcastPolicy::Limited(pps) => {
+                let pps = pps as f64;
+                let now = Instant::now();
+                let mut bucket = self.bucket.lock();
+                let (tokens, last) = bucket.get_or_insert((pps, now));
+                *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * pps).min(pps);
+                *last = now;
+                if *tokens >= 1.0 {
+                    *tokens -= 1.0;
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+        if allow {
+            self.sent.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        allow
+    }
+    /// 收到其他设备的广播，返回false时丢弃
+    pub fn on_received(&self, source: Ipv4Addr) -> bool {
+        if self.policy == BroadcastPolicy::Disabled {
+            return false;
+        }
+        if let Some(count) = self.received.get(&source) {
+            count.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.received
+                .entry(source)
+                .or_default()
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        true
+    }
+    pub fn stats(&self) -> BroadcastStats {
+        let mut received: Vec<(Ipv4Addr, u64)> = self
+            .received
+            .iter()
+            .map(|v| (*v.key(), v.value().load(Ordering::Relaxed)))
+            .collect();
+        received.sort_by(|a, b| b.1.cmp(&a.1));
+        BroadcastStats {
+            policy: self.policy,
+            sent: self.sent.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            received,
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::Ipv4Addr;
+
+use parking_lot::RwLock;
+
+use crate::cipher::CipherModel;
+use crate::proto::message::Capabilities as CapabilitiesProto;
+use crate::protocol::extension;
+
+/// 本版本支持的协议扩展，新增可选功能时在这里登记，对端也支持时才启用
+pub const EXTENSIONS: &[&str] = &["ice", "turn", "stream", extension::CAPABILITY];
+
+/// 设备的能力，打洞信息交换时携带，旧版本没有
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Capabilities {
+    /// 接受的加密模式，auto模式时有多个，按本机偏好排序
+    pub ciphers: Vec<String>,
+    /// 支持的压缩算法，当前版本没有压缩
+    pub compression: Vec<String>,
+    /// 能接收的最大负载，即虚拟网卡的mtu，0表示未知
+    pub max_payload: u32,
+    /// 支持的协议扩展
+    pub extensions: Vec<String>,
+}
+
+impl Capabilities {
+    pub fn local(max_payload: u32, cipher_model: CipherModel) -> Self {
+        Self {
+            ciphers: crate::cipher::hw::accepted(cipher_model)
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            compression: Vec::new(),
+            max_payload,
+            extensions: EXTENSIONS.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+    /// 双方都支持的部分，负载取较小值
+    pub fn negotiate(&self, peer: &Capabilities) -> Capabilities {
+        let intersect = |a: &[String], b: &[String]| -> Vec<String> {
+            a.iter().filter(|v| b.contains(v)).cloned().collect()
+        };
+        let max_payload = match (self.max_payload, peer.max_payload) {
+            (0, v) | (v, 0) => v,
+            (a, b) => a.min(b),
+        };
+        Capabilities {
+            ciphers: intersect(&self.ciphers, &peer.ciphers),
+            compression: intersect(&self.compression, &peer.compression),
+            max_payload,
+            extensions: intersect(&self.extensions, &peer.extensions),
+        }
+    }
+    pub fn to_proto(&self) -> CapabilitiesProto {
+        let mut capabilities = CapabilitiesProto::new();
+        capabilities.ciphers = self.ciphers.clone();
+        capabilities.compression = self.compression.clone();
+        capabilities.max_payload = self.max_payload;
+        capabilities.extensions = self.extensions.clone();
+        capabilities
+    }
+    pub fn from_proto(capabilities: &CapabilitiesProto) -> Self {
+        Self {
+            ciphers: capabilities.ciphers.clone(),
+            compression: capabilities.compression.clone(),
+            max_payload: capabilities.max_payload,
+            extensions: capabilities.extensions.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |v: &[String]| {
+            if v.is_empty() {
+                "none".to_string()
+            } else {
+                v.join("/")
+            }
+        };
+        write!(
+            f,
+            "mtu={} cipher={} compress={} ext={}",
+            self.max_payload,
+            join(&self.ciphers),
+            join(&self.compression),
+            join(&self.extensions)
+        )
+    }
+}
+
+/// 本机的能力，以及和各设备协商后的结果
+pub struct CapabilityTable {
+    local: Capabilities,
+    negotiated: RwLock<HashMap<Ipv4Addr, Capabilities>>,
+}
+
+impl CapabilityTable {
+    pub fn new(local: Capabilities) -> Self {
+        Self {
+            local,
+            negotiated: RwLock::new(HashMap::with_capacity(16)),
+        }
+    }
+    pub fn local(&self) -> &Capabilities {
+        &self.local
+    }
+    /// 收到对端的能力，返回协商结果
+    pub fn update(&self, peer_ip: Ipv4Addr, peer: &Capabilities) -> Capabilities {
+        let negotiated = self.local.negotiate(peer);
+        self.negotiated.write().insert(peer_ip, negotiated.clone());
+        negotiated
+    }
+    /// 没有交换过能力(旧版本或者还没打洞)时为None
+    pub fn get(&self, peer_ip: &Ipv4Addr) -> Option<Capabilities> {
+        self.negotiated.read().get(peer_ip).cloned()
+    }
+    /// 对端是否支持某个扩展，旧版本一律不支持
+    pub fn supports(&self, peer_ip: &Ipv4Addr, extension: &str) -> bool {
+        self.negotiated
+            .read()
+            .get(peer_ip)
+            .map(|v| v.extensions.iter().any(|e| e == extension))
+            .unwrap_or(false)
+    }
+}
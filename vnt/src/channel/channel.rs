@@ -1,7 +1,8 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::{Read, Write};
-use std::net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
-use std::net::{SocketAddrV6, TcpStream};
+use std::mem::size_of;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
+use std::net::{SocketAddrV4, SocketAddrV6, TcpStream};
 use std::net::{TcpListener, UdpSocket as StdUdpSocket};
 #[cfg(any(unix))]
 use std::os::fd::AsRawFd;
@@ -13,15 +14,41 @@ use std::time::{Duration, Instant};
 use std::{io, thread};
 
 use crossbeam_utils::atomic::AtomicCell;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
 use parking_lot::{Mutex, RwLock};
 use tokio::net::UdpSocket;
 use tokio::sync::watch::{channel, Receiver, Sender};
+use tokio::sync::Notify;
 
+use crate::channel::broadcast::{BroadcastControl, BroadcastPolicy};
+use crate::channel::capability::{Capabilities, CapabilityTable};
+use crate::channel::clock::ClockSkew;
+use crate::channel::ice::{Candidate, CandidatePair, CandidateType, CheckList};
+use crate::channel::loop_guard::LoopGuard;
+use crate::channel::malformed::MalformedStats;
+use crate::channel::mem_limit::{self, MemoryUsage, MAX_PEERS};
+use crate::channel::path_policy::{PathPolicy, PathPolicyTable};
 use crate::channel::punch::NatType;
-use crate::channel::{Route, RouteKey, Status, TCP_ID, UDP_ID};
+use crate::channel::punch_limit::PunchLimiter;
+use crate::channel::punch_record::PunchRecords;
+use crate::channel::quality::PathQuality;
+use crate::channel::quarantine::Quarantine;
+use crate::channel::rendezvous::Rendezvous;
+use crate::channel::turn::TurnClient;
+use crate::channel::{Route, RouteKey, Status, TCP_ID, TURN_ID, UDP_ID};
 use crate::core::status::VntWorker;
+use crate::event::{Counters, EventKind, EventLog, Liveness};
 use crate::handle::recv_handler::ChannelDataHandler;
-use crate::handle::CurrentDeviceInfo;
+use crate::handle::{CurrentDeviceInfo, PeerDeviceInfo, PeerDeviceStatus, ServerLost};
+use crate::protocol::{NetPacket, Protocol};
+use crate::subnet_alias;
+
+/// 超过这个时间没有收到服务端的数据，认为服务端中转不可用
+const SERVER_TIMEOUT: Duration = Duration::from_secs(15);
+/// 数据经服务端中转时和打洞赛跑的间隔，一直打不通时逐步拉长
+const RACE_MIN_INTERVAL: Duration = Duration::from_secs(2);
+const RACE_MAX_INTERVAL: Duration = Duration::from_secs(60);
 
 pub struct ContextInner {
     //udp用于打洞、服务端通信(可选)
@@ -38,6 +65,62 @@ pub struct ContextInner {
     first_latency: bool,
     is_close: AtomicBool,
     tcp_port: u16,
+    //混合模式，和服务端的控制消息走tcp，经服务端中转的数据仍走udp
+    tcp_control: bool,
+    //服务端中转不可用时经turn中转
+    pub(crate) turn: Option<Arc<TurnClient>>,
+    //最近一次收到服务端数据的时间
+    server_time: AtomicCell<Instant>,
+    //和服务端失联后直连继续使用的时间，None表示不限制
+    server_grace: Option<Duration>,
+    //和各设备的ice检查表
+    ice_table: DashMap<Ipv4Addr, CheckList>,
+    //数据正在经服务端中转的设备，(上次发起打洞的时间,下次间隔,是否等待打洞)
+    race_table: DashMap<Ipv4Addr, (Instant, Duration, bool)>,
+    race_notify: Notify,
+    //按需建立连接时，和设备最近一次的数据通信时间
+    session_idle: Option<Duration>,
+    active_table: DashMap<Ipv4Addr, AtomicCell<Instant>>,
+    active_notify: Notify,
+    //最近的连接事件
+    events: EventLog,
+    //收发计数
+    counters: Counters,
+    //心跳和tun读取的推进时间
+    liveness: Liveness,
+    //解密失败统计和隔离的地址
+    quarantine: Quarantine,
+    //丢弃的不合法ip报文统计
+    malformed: MalformedStats,
+    //in_ips配置错误导致的路由环路检测
+    loop_guard: LoopGuard,
+    //各设备的路径策略
+    path_policy: PathPolicyTable,
+    //开启require_encryption时，没有设置密码的设备，不和它们通信
+    plaintext_peers: Option<RwLock<HashSet<Ipv4Addr>>>,
+    //设备列表中离线的设备，发往它们的数据回应icmp不可达
+    offline_peers: RwLock<HashSet<Ipv4Addr>>,
+    //广播策略和统计
+    broadcast: BroadcastControl,
+    //本机和各设备协商的能力
+    capabilities: CapabilityTable,
+    //和各设备的时钟偏差
+    clock_skew: ClockSkew,
+    //最近一次收到各设备pong的时间
+    pong_table: DashMap<Ipv4Addr, Instant>,
+    //各设备最近一次打洞的记录
+    punch_records: PunchRecords,
+    //经服务端转发的打洞请求限速
+    punch_limiter: PunchLimiter,
+    //虚拟网段和局域网冲突时，本机网卡使用的映射网段
+    subnet_alias: Option<Ipv4Addr>,
+    //不经服务端交换打洞信息的设备地址
+    rendezvous: Rendezvous,
+    //io_uring发送udp，关闭时唤醒阻塞在io_uring上的接收
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    uring_writer: Option<crate::util::uring::UringWriter>,
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    uring_cancel: Mutex<Vec<crate::util::uring::UringCanceler>>,
 }
 
 #[derive(Clone)]
@@ -53,6 +136,17 @@ impl Context {
         _channel_num: usize,
         first_latency: bool,
         tcp_port: u16,
+        session_idle: Option<Duration>,
+        tcp_control: bool,
+        turn: Option<Arc<TurnClient>>,
+        path_policy: PathPolicyTable,
+        require_encryption: bool,
+        broadcast: BroadcastPolicy,
+        capabilities: Capabilities,
+        max_clock_skew: Option<Duration>,
+        subnet_alias: Option<Ipv4Addr>,
+        server_grace: Option<Duration>,
+        rendezvous: Rendezvous,
     ) -> Self {
         //当前版本只支持一个通道
         let channel_num = 1;
@@ -71,12 +165,210 @@ impl Context {
             first_latency,
             is_close: AtomicBool::new(false),
             tcp_port,
+            tcp_control,
+            turn,
+            server_time: AtomicCell::new(Instant::now()),
+            server_grace,
+            ice_table: DashMap::with_capacity(16),
+            race_table: DashMap::with_capacity(16),
+            race_notify: Notify::new(),
+            session_idle,
+            active_table: DashMap::with_capacity(16),
+            active_notify: Notify::new(),
+            events: EventLog::default(),
+            counters: Counters::default(),
+            liveness: Liveness::default(),
+            quarantine: Quarantine::default(),
+            malformed: MalformedStats::default(),
+            loop_guard: LoopGuard::default(),
+            path_policy,
+            plaintext_peers: if require_encryption {
+                Some(RwLock::new(HashSet::new()))
+            } else {
+                None
+            },
+            offline_peers: RwLock::new(HashSet::new()),
+            broadcast: BroadcastControl::new(broadcast),
+            capabilities: CapabilityTable::new(capabilities),
+            clock_skew: ClockSkew::new(max_clock_skew),
+            pong_table: DashMap::with_capacity(16),
+            punch_records: PunchRecords::default(),
+            punch_limiter: PunchLimiter::default(),
+            subnet_alias,
+            rendezvous,
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            uring_writer: crate::util::uring::UringWriter::new(64),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            uring_cancel: Mutex::new(Vec::new()),
         });
         Self { inner }
     }
 }
 
 impl Context {
+    pub fn events(&self) -> &EventLog {
+        &self.inner.events
+    }
+    pub fn counters(&self) -> &Counters {
+        &self.inner.counters
+    }
+    pub fn liveness(&self) -> &Liveness {
+        &self.inner.liveness
+    }
+    pub fn rendezvous(&self) -> &Rendezvous {
+        &self.inner.rendezvous
+    }
+    pub fn broadcast(&self) -> &BroadcastControl {
+        &self.inner.broadcast
+    }
+    pub fn capabilities(&self) -> &CapabilityTable {
+        &self.inner.capabilities
+    }
+    pub fn clock_skew(&self) -> &ClockSkew {
+        &self.inner.clock_skew
+    }
+    pub fn punch_records(&self) -> &PunchRecords {
+        &self.inner.punch_records
+    }
+    pub fn punch_limiter(&self) -> &PunchLimiter {
+        &self.inner.punch_limiter
+    }
+    /// 按设备记录的各个表的大小，每项按键值的大小估算，不含路由等内部的堆内存
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let mut usage = MemoryUsage::default();
+        usage.add(
+            "route",
+            self.inner.route_table.read().len(),
+            size_of::<Ipv4Addr>()
+                + size_of::<Vec<(Route, AtomicCell<Instant>)>>()
+                + size_of::<(Route, AtomicCell<Instant>)>() * (self.inner.channel_num + 1),
+        );
+        usage.add(
+            "ice",
+            self.inner.ice_table.len(),
+            size_of::<Ipv4Addr>() + size_of::<CheckList>(),
+        );
+        usage.add(
+            "race",
+            self.inner.race_table.len(),
+            size_of::<Ipv4Addr>() + size_of::<(Instant, Duration, bool)>(),
+        );
+        usage.add(
+            "active",
+            self.inner.active_table.len(),
+            size_of::<Ipv4Addr>() + size_of::<AtomicCell<Instant>>(),
+        );
+        usage.add(
+            "pong",
+            self.inner.pong_table.len(),
+            size_of::<Ipv4Addr>() + size_of::<Instant>(),
+        );
+        usage.evicted = mem_limit::evicted();
+        usage
+    }
+    /// 收到设备的pong，不区分路径
+    pub fn on_pong(&self, id: Ipv4Addr) {
+        mem_limit::make_room(&self.inner.pong_table, &id, MAX_PEERS, |time| *time);
+        self.inner.pong_table.insert(id, Instant::now());
+    }
+    /// 最近一次收到设备pong的时间
+    pub fn last_pong(&self, id: &Ipv4Addr) -> Option<Instant> {
+        self.inner.pong_table.get(id).map(|v| *v)
+    }
+    pub fn quarantine(&self) -> &Quarantine {
+        &self.inner.quarantine
+    }
+    pub fn malformed(&self) -> &MalformedStats {
+        &self.inner.malformed
+    }
+    pub fn loop_guard(&self) -> &LoopGuard {
+        &self.inner.loop_guard
+    }
+    pub fn subnet_alias(&self) -> Option<Ipv4Addr> {
+        self.inner.subnet_alias
+    }
+    /// 网卡读到的报文，映射网段转换回虚拟网段
+    pub fn alias_to_virtual(&self, ipv4: &mut [u8]) {
+        if let Some(alias) = self.inner.subnet_alias {
+            let device = self.inner.current_device.load();
+            subnet_alias::translate(
+                ipv4,
+                alias.into(),
+                device.virtual_network.into(),
+                device.virtual_netmask.into(),
+            );
+        }
+    }
+    /// 写入网卡的报文，虚拟网段转换为映射网段
+    pub fn virtual_to_alias(&self, ipv4: &mut [u8]) {
+        if let Some(alias) = self.inner.subnet_alias {
+            let device = self.inner.current_device.load();
+            subnet_alias::translate(
+                ipv4,
+                device.virtual_network.into(),
+                alias.into(),
+                device.virtual_netmask.into(),
+            );
+        }
+    }
+    pub fn path_policy(&self) -> &PathPolicyTable {
+        &self.inner.path_policy
+    }
+    /// 设备列表更新时记录没有加密的设备，新出现的记录一条事件
+    pub fn update_plaintext_peers(&self, device_list: &[PeerDeviceInfo]) {
+        let plaintext_peers = match &self.inner.plaintext_peers {
+            None => return,
+            Some(plaintext_peers) => plaintext_peers,
+        };
+        let list: HashSet<Ipv4Addr> = device_list
+            .iter()
+            .filter(|v| !v.client_secret)
+            .map(|v| v.virtual_ip)
+            .collect();
+        let mut guard = plaintext_peers.write();
+        for ip in list.difference(&guard) {
+            self.inner.events.push(
+                EventKind::Error,
+                Some(*ip),
+                "peer not encrypted,refused by require_encryption".to_string(),
+            );
+        }
+        *guard = list;
+    }
+    /// 设备列表更新时记录离线的设备
+    pub fn update_offline_peers(&self, device_list: &[PeerDeviceInfo]) {
+        let list: HashSet<Ipv4Addr> = device_list
+            .iter()
+            .filter(|v| v.status == PeerDeviceStatus::Offline)
+            .map(|v| v.virtual_ip)
+            .collect();
+        *self.inner.offline_peers.write() = list;
+    }
+    pub fn is_offline(&self, id: &Ipv4Addr) -> bool {
+        self.inner.offline_peers.read().contains(id)
+    }
+    /// 是否有可用的直连路径，和try_send_by_id的选择规则一致
+    pub fn has_route(&self, id: &Ipv4Addr) -> bool {
+        self.get_route_by_id(id).is_ok()
+    }
+    /// 发往设备使用的直连路由，没有可用的直连路径时返回错误
+    pub fn route_by_id(&self, id: &Ipv4Addr) -> io::Result<Route> {
+        self.get_route_by_id(id)
+    }
+    /// 开启require_encryption时拒绝和没有加密的设备通信
+    pub fn is_plaintext_refused(&self, id: &Ipv4Addr) -> bool {
+        self.inner
+            .plaintext_peers
+            .as_ref()
+            .map_or(false, |v| v.read().contains(id))
+    }
+    #[inline]
+    fn count_tx(&self, rs: io::Result<usize>) -> io::Result<usize> {
+        if let Ok(len) = &rs {
+            self.inner.counters.tx(*len);
+        }
+        rs
+    }
     pub fn is_close(&self) -> bool {
         self.inner.is_close.load(Ordering::Relaxed)
     }
@@ -87,20 +379,34 @@ impl Context {
         let last = self.is_close();
         self.inner.is_close.store(true, Ordering::Release);
         let _ = self.inner.status_sender.send(Status::Close);
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        {
+            for canceler in self.inner.uring_cancel.lock().iter() {
+                canceler.cancel();
+            }
+            if let Some(writer) = &self.inner.uring_writer {
+                writer.close();
+            }
+        }
+        let bind_ip = self.bind_ip();
         if let Ok(port) = self.main_local_udp_port() {
-            match StdUdpSocket::bind("127.0.0.1:0") {
+            let addr = SocketAddr::new(bind_ip.unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST)), port);
+            let local = if addr.is_ipv4() {
+                "0.0.0.0:0"
+            } else {
+                "[::]:0"
+            };
+            //可能在受限的收发线程中关闭，创建socket交给控制线程
+            crate::util::seccomp::unrestricted(move || match StdUdpSocket::bind(local) {
                 Ok(udp) => {
-                    if let Err(e) = udp.send_to(
-                        b"stop",
-                        SocketAddr::V4(std::net::SocketAddrV4::new(Ipv4Addr::LOCALHOST, port)),
-                    ) {
+                    if let Err(e) = udp.send_to(b"stop", addr) {
                         log::error!("发送停止消息到udp失败:{:?}", e);
                     }
                 }
                 Err(e) => {
                     log::error!("发送停止-绑定udp失败:{:?}", e);
                 }
-            }
+            });
         }
         if let Some(tcp) = &self.inner.main_tcp_channel {
             if let Err(e) = tcp.lock().shutdown(Shutdown::Both) {
@@ -113,13 +419,9 @@ impl Context {
                     log::error!("发送停止消息到tcp失败:{:?}", e);
                 }
             }
+            let ip = bind_ip.unwrap_or(IpAddr::V6(Ipv6Addr::LOCALHOST));
             if let Err(e) = TcpStream::connect_timeout(
-                &SocketAddr::V6(SocketAddrV6::new(
-                    Ipv6Addr::LOCALHOST,
-                    self.inner.tcp_port,
-                    0,
-                    0,
-                )),
+                &SocketAddr::new(ip, self.inner.tcp_port),
                 Duration::from_secs(1),
             ) {
                 log::error!("发送停止消息到tcp_listener失败:{:?}", e);
@@ -127,6 +429,172 @@ impl Context {
         }
         Ok(())
     }
+    /// 指定了本地监听地址时返回该地址，关闭时用它唤醒阻塞的接收
+    fn bind_ip(&self) -> Option<IpAddr> {
+        let ip = self.inner.main_channel.local_addr().ok()?.ip();
+        if ip.is_unspecified() {
+            return None;
+        }
+        match ip {
+            IpAddr::V6(ipv6) => Some(ipv6.to_ipv4_mapped().map_or(ip, IpAddr::V4)),
+            IpAddr::V4(_) => Some(ip),
+        }
+    }
+    /// 是否按需建立连接，开启后只对有数据通信的设备打洞和发送心跳
+    pub fn is_lazy(&self) -> bool {
+        self.inner.session_idle.is_some()
+    }
+    /// 记录和设备的数据通信，空闲的设备重新有数据时立即唤醒打洞任务
+    pub fn mark_active(&self, id: &Ipv4Addr) {
+        let idle = match self.inner.session_idle {
+            None => return,
+            Some(idle) => idle,
+        };
+        if let Some(time) = self.inner.active_table.get(id) {
+            let last = time.swap(Instant::now());
+            if last.elapsed() < idle {
+                return;
+            }
+        } else {
+            mem_limit::make_room(&self.inner.active_table, id, MAX_PEERS, |time| time.load());
+            self.inner
+                .active_table
+                .insert(*id, AtomicCell::new(Instant::now()));
+        }
+        self.inner.active_notify.notify_one();
+    }
+    /// 未开启按需连接时所有设备都是活跃的
+    pub fn is_active(&self, id: &Ipv4Addr) -> bool {
+        match self.inner.session_idle {
+            None => true,
+            Some(idle) => self
+                .inner
+                .active_table
+                .get(id)
+                .map_or(false, |time| time.load().elapsed() < idle),
+        }
+    }
+    /// 等待空闲的设备重新有数据通信
+    pub async fn wait_active(&self) {
+        self.inner.active_notify.notified().await
+    }
+    /// 数据经服务端中转时调用，中转和打洞同时进行，不等打洞结果，
+    /// 直连打通后自动切换，打不通时按逐步拉长的间隔持续重试
+    pub fn race(&self, id: &Ipv4Addr) {
+        let policy = self.inner.path_policy.get(id);
+        if policy == PathPolicy::PreferRelay && !self.is_server_timeout() {
+            return;
+        }
+        let now = Instant::now();
+        mem_limit::make_room(&self.inner.race_table, id, MAX_PEERS, |(last, _, _)| *last);
+        match self.inner.race_table.entry(*id) {
+            Entry::Occupied(mut entry) => {
+                let (last, interval, _) = *entry.get();
+                let elapsed = now.duration_since(last);
+                if elapsed < interval {
+                    return;
+                }
+                //中断了很久的通信重新开始时从最小间隔开始，prefer-direct一直使用最小间隔
+                let interval =
+                    if elapsed > RACE_MAX_INTERVAL * 2 || policy == PathPolicy::PreferDirect {
+                        RACE_MIN_INTERVAL
+                    } else {
+                        (interval * 2).min(RACE_MAX_INTERVAL)
+                    };
+                entry.insert((now, interval, true));
+            }
+            Entry::Vacant(entry) => {
+                entry.insert((now, RACE_MIN_INTERVAL, true));
+            }
+        }
+        self.inner.race_notify.notify_one();
+    }
+    /// 取出等待打洞的设备
+    pub fn take_racing(&self) -> Vec<Ipv4Addr> {
+        let mut list = Vec::new();
+        for mut item in self.inner.race_table.iter_mut() {
+            if item.2 {
+                item.2 = false;
+                list.push(*item.key());
+            }
+        }
+        list
+    }
+    /// 等待有数据经服务端中转
+    pub async fn wait_race(&self) {
+        self.inner.race_notify.notified().await
+    }
+    /// 回收空闲设备的直连路由，返回被回收的设备
+    pub fn reap_idle(&self) -> Vec<Ipv4Addr> {
+        let idle = match self.inner.session_idle {
+            None => return Vec::new(),
+            Some(idle) => idle,
+        };
+        let mut list = Vec::new();
+        self.inner.active_table.retain(|ip, time| {
+            if time.load().elapsed() < idle {
+                return true;
+            }
+            list.push(*ip);
+            false
+        });
+        if !list.is_empty() {
+            let mut route_table = self.inner.route_table.write();
+            for ip in &list {
+                if let Some(routes) = route_table.get_mut(ip) {
+                    routes.retain(|(route, _)| !route.is_p2p());
+                }
+            }
+        }
+        list
+    }
+    /// 收到服务端的数据，服务端长时间没有数据时经turn中转
+    pub fn update_server_time(&self) {
+        self.inner.server_time.store(Instant::now());
+    }
+    /// 服务端中转不可用
+    pub fn is_server_timeout(&self) -> bool {
+        self.inner.server_time.load().elapsed() > SERVER_TIMEOUT
+    }
+    /// 和服务端失联的状态，服务端可用时为None
+    pub fn server_lost(&self) -> Option<ServerLost> {
+        let elapsed = self.inner.server_time.load().elapsed();
+        if elapsed <= SERVER_TIMEOUT {
+            return None;
+        }
+        let elapsed = elapsed - SERVER_TIMEOUT;
+        Some(ServerLost {
+            elapsed,
+            remaining: self
+                .inner
+                .server_grace
+                .map(|grace| grace.saturating_sub(elapsed)),
+        })
+    }
+    /// 和服务端失联超过宽限期，停止和其他设备通信，直到服务端恢复
+    pub fn is_grace_expired(&self) -> bool {
+        self.server_lost()
+            .map_or(false, |lost| lost.remaining == Some(Duration::ZERO))
+    }
+    pub(crate) fn send_turn(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        match &self.inner.turn {
+            Some(turn) => self.count_tx(turn.try_send_to(buf, addr)),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "turn not found")),
+        }
+    }
+    /// 在turn服务器上分配的中继地址
+    pub fn turn_relayed_addr(&self) -> Option<SocketAddrV4> {
+        self.inner
+            .turn
+            .as_ref()
+            .and_then(|turn| turn.relayed_addr())
+    }
+    /// 记录设备在turn上的中继地址
+    pub fn update_turn_peer(&self, virtual_ip: Ipv4Addr, addr: SocketAddrV4) {
+        if let Some(turn) = &self.inner.turn {
+            turn.update_peer(virtual_ip, addr);
+        }
+    }
     pub fn is_main_tcp(&self) -> bool {
         self.inner.main_tcp_channel.is_some()
     }
@@ -168,12 +636,28 @@ impl Context {
                 0,
             ));
         }
-        self.inner.main_channel.send_to(buf, addr)
+        if crate::util::mimicry::get().is_some() && self.is_server_addr(addr) {
+            return self.count_tx(crate::util::mimicry::send_to(
+                &self.inner.main_channel,
+                buf,
+                addr,
+            ));
+        }
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let Some(writer) = &self.inner.uring_writer {
+            if writer.send_to(self.inner.main_channel.as_raw_fd(), buf, addr) {
+                return self.count_tx(Ok(buf.len()));
+            }
+        }
+        self.count_tx(self.inner.main_channel.send_to(buf, addr))
+    }
+    fn is_server_addr(&self, addr: SocketAddr) -> bool {
+        crate::util::mimicry::same_addr(addr, self.inner.current_device.load().connect_server)
     }
     #[inline]
     pub fn send_main_tcp(&self, buf: &[u8]) -> io::Result<usize> {
         if let Some(sender) = &self.inner.main_tcp_channel {
-            Self::send_tcp(sender, buf)
+            self.count_tx(Self::send_tcp(sender, buf))
         } else {
             return Err(io::Error::new(io::ErrorKind::NotFound, "tcp not found"));
         }
@@ -184,6 +668,38 @@ impl Context {
     }
 
     pub fn send_main(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        if let Ok(packet) = NetPacket::new(buf) {
+            //禁止中转的设备，数据不经服务端和turn，打洞等控制消息不受限制
+            if !packet.is_gateway() && packet.protocol() == Protocol::IpTurn {
+                let destination = packet.destination();
+                if self.inner.path_policy.get(&destination) == PathPolicy::NeverRelay {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        "never relay",
+                    ));
+                }
+                if self.is_plaintext_refused(&destination) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        "peer not encrypted",
+                    ));
+                }
+            }
+        }
+        if let Some(turn) = &self.inner.turn {
+            if self.is_server_timeout() && !self.is_grace_expired() {
+                if let Ok(packet) = NetPacket::new(buf) {
+                    if !packet.is_gateway() {
+                        if let Some(peer) = turn.peer_addr(&packet.destination()) {
+                            return self.count_tx(turn.try_send_to(buf, SocketAddr::V4(peer)));
+                        }
+                    }
+                }
+            }
+        }
+        if self.inner.tcp_control && !NetPacket::new(buf).map_or(true, |p| p.is_gateway()) {
+            return self.send_main_udp(buf, addr);
+        }
         if let Some(sender) = &self.inner.main_tcp_channel {
             let mut stream = sender.lock();
             let mut head = [0; 4];
@@ -192,6 +708,7 @@ impl Context {
             head[3] = (len & 0xFF) as u8;
             stream.write_all(&head)?;
             stream.write_all(buf)?;
+            self.inner.counters.tx(len);
             Ok(len)
         } else {
             self.send_main_udp(buf, addr)
@@ -222,15 +739,47 @@ impl Context {
         self.try_send_by_key(buf, &route.route_key())
     }
     fn get_route_by_id(&self, id: &Ipv4Addr) -> io::Result<Route> {
+        if self.is_grace_expired() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "server lost, grace expired",
+            ));
+        }
+        if self.is_plaintext_refused(id) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "peer not encrypted",
+            ));
+        }
         if let Some(v) = self.inner.route_table.read().get(id) {
             if v.is_empty() {
                 return Err(io::Error::new(io::ErrorKind::NotFound, "route not found"));
             }
-            let (route, time) = &v[0];
+            let policy = self.inner.path_policy.get(id);
+            if policy == PathPolicy::PreferRelay && !self.is_server_timeout() {
+                return Err(io::Error::new(io::ErrorKind::NotFound, "prefer relay"));
+            }
+            //质量变差的直连路径不使用，都不可用时走服务端中转
+            let found = v.iter().find(|(route, _)| {
+                if policy == PathPolicy::NeverRelay && (!route.is_p2p() || route.index == TURN_ID) {
+                    return false;
+                }
+                policy == PathPolicy::PreferDirect || !route.quality.is_degraded()
+            });
+            let (route, time) = match found {
+                Some(v) => v,
+                None => {
+                    return Err(io::Error::new(io::ErrorKind::NotFound, "route degraded"));
+                }
+            };
             if route.rt == 199 {
                 //这通常是刚加入路由，直接放弃使用,避免抖动
                 return Err(io::Error::new(io::ErrorKind::NotFound, "route not found"));
             }
+            if route.index == TURN_ID && !self.is_server_timeout() {
+                //服务端中转可用时不经turn
+                return Err(io::Error::new(io::ErrorKind::NotFound, "route not found"));
+            }
             if !route.is_p2p() {
                 //借道传输时，长时间不通信的通道不使用
                 if time.load().elapsed() > Duration::from_secs(6) {
@@ -246,14 +795,15 @@ impl Context {
         match route_key.index {
             TCP_ID => self.send_main_tcp(buf),
             UDP_ID => self.send_main_udp(buf, route_key.addr),
+            TURN_ID => self.send_turn(buf, route_key.addr),
             _ => {
                 if route_key.is_tcp {
                     if let Some(tcp) = self.get_tcp_by_route(route_key) {
-                        return Self::send_tcp(&tcp, buf);
+                        return self.count_tx(Self::send_tcp(&tcp, buf));
                     }
                 } else {
                     if let Some(udp) = self.get_udp_by_route(route_key) {
-                        return udp.send_to(buf, route_key.addr).await;
+                        return self.count_tx(udp.send_to(buf, route_key.addr).await);
                     }
                 }
                 Err(io::Error::new(io::ErrorKind::NotFound, "route not found"))
@@ -264,14 +814,15 @@ impl Context {
         match route_key.index {
             TCP_ID => self.send_main_tcp(buf),
             UDP_ID => self.send_main_udp(buf, route_key.addr),
+            TURN_ID => self.send_turn(buf, route_key.addr),
             _ => {
                 if route_key.is_tcp {
                     if let Some(tcp) = self.get_tcp_by_route(route_key) {
-                        return Self::send_tcp(&tcp, buf);
+                        return self.count_tx(Self::send_tcp(&tcp, buf));
                     }
                 } else {
                     if let Some(udp) = self.get_udp_by_route(route_key) {
-                        return udp.try_send_to(buf, route_key.addr);
+                        return self.count_tx(udp.try_send_to(buf, route_key.addr));
                     }
                 }
                 Err(io::Error::new(io::ErrorKind::NotFound, "route not found"))
@@ -296,6 +847,12 @@ impl Context {
     fn add_route_(&self, id: Ipv4Addr, route: Route, only_if_absent: bool) {
         let key = route.route_key();
         let mut route_table = self.inner.route_table.write();
+        mem_limit::make_room_locked(&mut *route_table, &id, MAX_PEERS, |list| {
+            list.iter()
+                .map(|(_, time)| time.load())
+                .max()
+                .unwrap_or_else(Instant::now)
+        });
         let list = route_table
             .entry(id)
             .or_insert_with(|| Vec::with_capacity(4));
@@ -372,7 +929,7 @@ impl Context {
         let table = self.inner.route_table.read();
         table
             .iter()
-            .map(|(k, v)| (k.clone(), v.iter().map(|(i, _)| *i).collect()))
+            .map(|(k, v)| (*k, v.iter().map(|(i, _)| *i).collect()))
             .collect()
     }
     pub fn route_table_one(&self) -> Vec<(Ipv4Addr, Route)> {
@@ -405,6 +962,164 @@ impl Context {
             return;
         }
     }
+    /// 收到设备的候选地址，更新检查表
+    pub fn ice_update_remote(&self, id: Ipv4Addr, controlling: bool, remote: Vec<Candidate>) {
+        let has_relay = self.turn_relayed_addr().is_some();
+        self.inner
+            .ice_table
+            .entry(id)
+            .or_default()
+            .update_remote(controlling, remote, has_relay);
+    }
+    /// 开始一轮连通性检查，返回按优先级排列的地址对，服务端中转可用时不检查中继地址
+    pub fn ice_start_checks(&self, id: &Ipv4Addr) -> Vec<CandidatePair> {
+        let has_route = self
+            .inner
+            .route_table
+            .read()
+            .get(id)
+            .map_or(false, |routes| {
+                routes.iter().any(|(route, _)| route.ice.is_some())
+            });
+        let relay = self.is_server_timeout();
+        match self.inner.ice_table.get_mut(id) {
+            Some(mut list) => {
+                if !has_route {
+                    //提名的路由已经超时移除，重新提名
+                    list.clear_nomination();
+                }
+                list.start_checks(relay)
+            }
+            None => Vec::new(),
+        }
+    }
+    /// 连通性检查成功，优先级更高时替换之前提名的路由，返回是否加入了新路由
+    pub fn ice_check_succeeded(&self, id: Ipv4Addr, route_key: &RouteKey) -> bool {
+        let rs = if route_key.is_tcp {
+            None
+        } else {
+            self.inner
+                .ice_table
+                .get_mut(&id)
+                .map(|mut list| list.on_success(route_key.addr, route_key.index == TURN_ID))
+        };
+        match rs {
+            None => {
+                //对端是旧版本或者是tcp连接
+                let exists = self
+                    .inner
+                    .route_table
+                    .read()
+                    .get(&id)
+                    .map_or(false, |routes| {
+                        routes
+                            .iter()
+                            .any(|(route, _)| &route.route_key() == route_key)
+                    });
+                if exists {
+                    return false;
+                }
+                self.inner.race_table.remove(&id);
+                let route = Route::from(*route_key, 1, 199);
+                self.add_route_if_absent(id, route);
+                self.inner
+                    .punch_records
+                    .succeeded(&id, route_key.addr.to_string());
+                true
+            }
+            Some(None) => false,
+            Some(Some((pair, old))) => {
+                log::info!(
+                    "ice提名 {},{}/{} {},priority={}",
+                    id,
+                    pair.local,
+                    pair.remote.typ,
+                    pair.remote.addr,
+                    pair.priority
+                );
+                if let Some(old) = old {
+                    if let Some(routes) = self.inner.route_table.write().get_mut(&id) {
+                        routes.retain(|(route, _)| {
+                            route.ice.map_or(true, |v| {
+                                v.local != old.local || v.remote.addr != old.remote.addr
+                            })
+                        });
+                    }
+                }
+                //经turn中转的路径不算直连
+                let metric = if pair.local == CandidateType::Relay {
+                    2
+                } else {
+                    1
+                };
+                if metric == 1 {
+                    self.inner.race_table.remove(&id);
+                }
+                let mut route = Route::from(*route_key, metric, 199);
+                route.ice = Some(pair);
+                self.add_route_if_absent(id, route);
+                self.inner.punch_records.succeeded(
+                    &id,
+                    format!("{}/{} {}", pair.local, pair.remote.typ, pair.remote.addr),
+                );
+                self.inner.events.push(
+                    EventKind::PathChanged,
+                    Some(id),
+                    format!(
+                        "nominated {}/{} {}",
+                        pair.local, pair.remote.typ, pair.remote.addr
+                    ),
+                );
+                true
+            }
+        }
+    }
+    /// 和设备的ice检查表
+    pub fn ice_check_list(&self, id: &Ipv4Addr) -> Vec<CandidatePair> {
+        self.inner
+            .ice_table
+            .get(id)
+            .map_or(Vec::new(), |list| list.pairs().to_vec())
+    }
+    /// 发送了直连路径的质量探测
+    pub fn on_probe_sent(&self, id: &Ipv4Addr, route_key: &RouteKey) {
+        self.update_quality(id, route_key, |quality| quality.on_sent())
+    }
+    /// 收到直连路径的探测回应
+    pub fn on_probe_reply(&self, id: &Ipv4Addr, route_key: &RouteKey, rt: i64) {
+        self.update_quality(id, route_key, |quality| quality.on_reply(rt))
+    }
+    fn update_quality(
+        &self,
+        id: &Ipv4Addr,
+        route_key: &RouteKey,
+        f: impl FnOnce(&mut PathQuality) -> bool,
+    ) {
+        if let Some(routes) = self.inner.route_table.write().get_mut(id) {
+            if let Some((route, _)) = routes
+                .iter_mut()
+                .find(|(route, _)| &route.route_key() == route_key && route.is_p2p())
+            {
+                if f(&mut route.quality) {
+                    let detail = if route.quality.is_degraded() {
+                        log::warn!(
+                            "直连路径质量变差,改走中转 {},{},{}",
+                            id,
+                            route.addr,
+                            route.quality
+                        );
+                        format!("direct {} degraded, {}", route.addr, route.quality)
+                    } else {
+                        log::info!("直连路径恢复 {},{},{}", id, route.addr, route.quality);
+                        format!("direct {} recovered, {}", route.addr, route.quality)
+                    };
+                    self.inner
+                        .events
+                        .push(EventKind::PathChanged, Some(*id), detail);
+                }
+            }
+        }
+    }
     pub fn update_read_time(&self, id: &Ipv4Addr, route_key: &RouteKey) {
         if let Some(routes) = self.inner.route_table.read().get(id) {
             for (route, time) in routes {
@@ -457,7 +1172,7 @@ impl Channel {
                     return;
                 }
                 let device_info = current_device.load();
-                match TcpStream::connect(device_info.connect_server) {
+                match crate::util::proxy::server_connect(&device_info.connect_server, None) {
                     Ok(tcp) => {
                         tcp_stream = tcp.try_clone().unwrap();
                         let mut guard = context.inner.main_tcp_channel.as_ref().unwrap().lock();
@@ -547,6 +1262,10 @@ impl Channel {
                 })
                 .unwrap();
         }
+        if let Some(turn) = context.inner.turn.clone() {
+            log::info!("启动turn客户端");
+            tokio::spawn(turn.start(worker.worker("turn"), context.clone(), handler.clone()));
+        }
         if relay {
             worker.stop_wait().await;
             return;
@@ -588,6 +1307,9 @@ impl Channel {
                                     for _ in 0..symmetric_channel_num - channel_num {
                                         match UdpSocket::bind("0.0.0.0:0").await {
                                             Ok(udp) => {
+                                                if let Err(e) = crate::util::fwmark::mark(&udp) {
+                                                    log::warn!("fwmark {:?}", e);
+                                                }
                                                 let udp = Arc::new(udp);
                                                 let context = context.clone();
                                                 tokio::spawn(Self::start_(worker.worker("symmetric_channel"),context, udp,handler.clone()));
@@ -621,10 +1343,35 @@ impl Channel {
     ) {
         let mut buf = [0; 4096];
         let head_reserve = handler.head_reserve;
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        let mut uring = crate::util::uring::UringReader::udp(udp.as_raw_fd(), 8);
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let Some(uring) = &uring {
+            context.inner.uring_cancel.lock().push(uring.canceler());
+        }
+        //创建io_uring之后再限制
+        crate::util::seccomp::restrict_thread();
         loop {
-            match udp.recv_from(&mut buf[head_reserve..]) {
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            let rs = if let Some(uring) = &mut uring {
+                uring.recv_from(&mut buf[head_reserve..])
+            } else {
+                udp.recv_from(&mut buf[head_reserve..])
+            };
+            #[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+            let rs = udp.recv_from(&mut buf[head_reserve..]);
+            match rs {
                 Ok((len, addr)) => {
-                    let end = head_reserve + len;
+                    let mut end = head_reserve + len;
+                    if crate::util::mimicry::get().is_some() && context.is_server_addr(addr) {
+                        match crate::util::mimicry::strip(&mut buf[head_reserve..end]) {
+                            Some(len) => end = head_reserve + len,
+                            None => {
+                                log::debug!("服务端报文伪装头部不匹配 {}", addr);
+                                continue;
+                            }
+                        }
+                    }
                     if &buf[head_reserve..end] == b"stop" {
                         if context.is_close() {
                             break;
@@ -639,6 +1386,12 @@ impl Channel {
                     );
                 }
                 Err(e) => {
+                    if context.is_close() {
+                        break;
+                    }
+                    if e.kind() == io::ErrorKind::ConnectionReset {
+                        context.punch_records().on_icmp_unreachable();
+                    }
                     log::error!("udp :{:?}", e);
                 }
             }
@@ -670,6 +1423,9 @@ impl Channel {
                             handler.handle(&mut buf, head_reserve, head_reserve + len, RouteKey::new(false,id, addr), &context);
                         }
                         Err(e) => {
+                            if e.kind() == io::ErrorKind::ConnectionReset {
+                                context.punch_records().on_icmp_unreachable();
+                            }
                             log::error!("{:?}",e)
                         }
                     }
@@ -720,6 +1476,7 @@ pub fn start_tcp_handle(
         .tcp_map
         .write()
         .insert(id, Arc::new(Mutex::new(writer)));
+    crate::util::seccomp::restrict_thread();
     if let Err(e) = tcp_handle(id, &mut stream, &context, &handler) {
         log::error!("tcp_handle {:?}", e);
     }
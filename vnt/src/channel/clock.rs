@@ -0,0 +1,61 @@
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use dashmap::DashMap;
+
+/// 设备之间的时钟偏差，打洞信息交换时携带发送时间，和本机时间比较得到
+/// 没有扣除单向延迟，误差在一个rtt以内
+pub struct ClockSkew {
+    /// 超过该值时告警，None表示不告警
+    threshold: Option<Duration>,
+    /// 对端时间减本机时间(毫秒)，正数表示对端的时钟快
+    peers: DashMap<Ipv4Addr, i64>,
+    /// 已经告警过的设备，恢复正常后再次超出才重新告警
+    warned: DashMap<Ipv4Addr, ()>,
+}
+
+impl ClockSkew {
+    pub fn new(threshold: Option<Duration>) -> Self {
+        Self {
+            threshold,
+            peers: DashMap::new(),
+            warned: DashMap::new(),
+        }
+    }
+    pub fn threshold(&self) -> Option<Duration> {
+        self.threshold
+    }
+    /// 记录对端的发送时间(unix毫秒)，新超出阈值时返回偏差
+    pub fn update(&self, peer_ip: Ipv4Addr, peer_time: u64) -> Option<i64> {
+        let now = crate::handle::now_time();
+        if peer_time == 0 || now == 0 {
+            return None;
+        }
+        let skew = peer_time as i64 - now as i64;
+        self.peers.insert(peer_ip, skew);
+        let threshold = self.threshold?;
+        if skew.unsigned_abs() > threshold.as_millis() as u64 {
+            if self.warned.insert(peer_ip, ()).is_none() {
+                return Some(skew);
+            }
+        } else {
+            self.warned.remove(&peer_ip);
+        }
+        None
+    }
+    /// 对端相对本机的时钟偏差(毫秒)，未测量时为None
+    pub fn get(&self, peer_ip: &Ipv4Addr) -> Option<i64> {
+        self.peers.get(peer_ip).map(|v| *v)
+    }
+    /// 把对端的时间换算成本机时间，需要按时间判断的地方用它比较，不受时钟偏差影响
+    pub fn to_local(&self, peer_ip: &Ipv4Addr, peer_time: u64) -> u64 {
+        let skew = self.get(peer_ip).unwrap_or(0);
+        (peer_time as i64 - skew).max(0) as u64
+    }
+    /// 各设备的偏差，绝对值大的在前
+    pub fn list(&self) -> Vec<(Ipv4Addr, i64)> {
+        let mut list: Vec<(Ipv4Addr, i64)> = self.peers.iter().map(|v| (*v.key(), *v)).collect();
+        list.sort_by(|a, b| b.1.unsigned_abs().cmp(&a.1.unsigned_abs()));
+        list
+    }
+}
@@ -0,0 +1,357 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4};
+
+use crate::channel::punch::NatInfo;
+use crate::proto::message::IceCandidate;
+
+/// 一个地址对连续检查这么多轮都没有回应则认为失败
+const MAX_CHECKS: u32 = 5;
+
+/// 候选地址类型
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum CandidateType {
+    /// 本地网卡地址
+    Host,
+    /// stun探测到的nat映射地址
+    ServerReflexive,
+    /// 连通性检查中才发现的对端地址，通常是对称网络预测到的端口
+    PeerReflexive,
+    /// turn服务器上分配的中继地址
+    Relay,
+}
+
+impl CandidateType {
+    /// RFC 8445 推荐的类型优先级
+    fn preference(&self) -> u32 {
+        match self {
+            CandidateType::Host => 126,
+            CandidateType::PeerReflexive => 110,
+            CandidateType::ServerReflexive => 100,
+            CandidateType::Relay => 0,
+        }
+    }
+}
+
+impl From<CandidateType> for u32 {
+    fn from(value: CandidateType) -> Self {
+        match value {
+            CandidateType::Host => 0,
+            CandidateType::ServerReflexive => 1,
+            CandidateType::PeerReflexive => 2,
+            CandidateType::Relay => 3,
+        }
+    }
+}
+
+impl TryFrom<u32> for CandidateType {
+    type Error = u32;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(CandidateType::Host),
+            1 => Ok(CandidateType::ServerReflexive),
+            2 => Ok(CandidateType::PeerReflexive),
+            3 => Ok(CandidateType::Relay),
+            v => Err(v),
+        }
+    }
+}
+
+impl fmt::Display for CandidateType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            CandidateType::Host => "host",
+            CandidateType::ServerReflexive => "srflx",
+            CandidateType::PeerReflexive => "prflx",
+            CandidateType::Relay => "relay",
+        };
+        f.write_str(s)
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Candidate {
+    pub typ: CandidateType,
+    pub addr: SocketAddr,
+    pub priority: u32,
+}
+
+impl Candidate {
+    /// local_preference越大越优先，同类型有多个地址时区分先后
+    pub fn new(typ: CandidateType, addr: SocketAddr, local_preference: u16) -> Self {
+        Self {
+            typ,
+            addr: normalize(addr),
+            priority: priority(typ, local_preference),
+        }
+    }
+    pub fn to_proto(&self) -> IceCandidate {
+        let mut candidate = IceCandidate::new();
+        candidate.kind = self.typ.into();
+        candidate.ip = match self.addr.ip() {
+            IpAddr::V4(ip) => ip.octets().to_vec(),
+            IpAddr::V6(ip) => ip.octets().to_vec(),
+        };
+        candidate.port = self.addr.port() as u32;
+        candidate.priority = self.priority;
+        candidate
+    }
+    pub fn from_proto(candidate: &IceCandidate) -> Option<Self> {
+        let typ = CandidateType::try_from(candidate.kind).ok()?;
+        let ip = match candidate.ip.len() {
+            4 => IpAddr::V4(Ipv4Addr::from(
+                <[u8; 4]>::try_from(candidate.ip.as_slice()).unwrap(),
+            )),
+            16 => IpAddr::V6(Ipv6Addr::from(
+                <[u8; 16]>::try_from(candidate.ip.as_slice()).unwrap(),
+            )),
+            _ => return None,
+        };
+        if ip.is_unspecified() || ip.is_multicast() || candidate.port == 0 || candidate.port > 65535
+        {
+            return None;
+        }
+        Some(Self {
+            typ,
+            addr: normalize(SocketAddr::new(ip, candidate.port as u16)),
+            priority: candidate.priority,
+        })
+    }
+}
+
+/// priority = 2^24*类型优先级 + 2^8*本地优先级 + (256 - 组件id)，只有一个组件
+fn priority(typ: CandidateType, local_preference: u16) -> u32 {
+    (typ.preference() << 24) | ((local_preference as u32) << 8) | 255
+}
+
+/// 主udp是双栈的，收到的ipv4地址是映射到ipv6的形式
+fn normalize(addr: SocketAddr) -> SocketAddr {
+    if let SocketAddr::V6(ipv6) = addr {
+        if let Some(ipv4) = ipv6.ip().to_ipv4_mapped() {
+            return SocketAddr::V4(SocketAddrV4::new(ipv4, ipv6.port()));
+        }
+    }
+    addr
+}
+
+/// 收集候选地址，对端是旧版本时也用它从打洞信息推导对端的候选地址
+pub fn gather(nat_info: &NatInfo, relayed: Option<SocketAddrV4>) -> Vec<Candidate> {
+    let mut list = Vec::with_capacity(4 + nat_info.public_ips.len());
+    if let Some(addr) = nat_info.local_udp_ipv4addr() {
+        list.push(Candidate::new(CandidateType::Host, addr, 65535));
+    }
+    if let Some(addr) = nat_info.local_udp_ipv6addr() {
+        list.push(Candidate::new(CandidateType::Host, addr, 65534));
+    }
+    if nat_info.public_port != 0 {
+        for (index, ip) in nat_info.public_ips.iter().enumerate() {
+            let addr = SocketAddr::V4(SocketAddrV4::new(*ip, nat_info.public_port));
+            list.push(Candidate::new(
+                CandidateType::ServerReflexive,
+                addr,
+                65535u16.saturating_sub(index as u16),
+            ));
+        }
+    }
+    if let Some(addr) = relayed {
+        list.push(Candidate::new(
+            CandidateType::Relay,
+            SocketAddr::V4(addr),
+            65535,
+        ));
+    }
+    list
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum CheckState {
+    /// 还没有发送过检查
+    Waiting,
+    /// 已发送检查，等待回应
+    InProgress,
+    Succeeded,
+    /// 多轮检查都没有回应，候选地址都失败时整体重新检查
+    Failed,
+}
+
+impl fmt::Display for CheckState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            CheckState::Waiting => "waiting",
+            CheckState::InProgress => "in-progress",
+            CheckState::Succeeded => "succeeded",
+            CheckState::Failed => "failed",
+        };
+        f.write_str(s)
+    }
+}
+
+/// 候选地址对，本端只从主udp(及对称网络的附加udp)或者turn发出，
+/// 所以本端候选地址只用类型表示
+#[derive(Copy, Clone, Debug)]
+pub struct CandidatePair {
+    pub local: CandidateType,
+    pub remote: Candidate,
+    pub priority: u64,
+    pub state: CheckState,
+    /// 已经发送的检查次数，成功后清零
+    pub checks: u32,
+    pub nominated: bool,
+}
+
+impl CandidatePair {
+    fn new(controlling: bool, remote: Candidate) -> Self {
+        let local = if remote.typ == CandidateType::Relay {
+            CandidateType::Relay
+        } else {
+            CandidateType::Host
+        };
+        let local_priority = priority(local, 65535) as u64;
+        let remote_priority = remote.priority as u64;
+        //RFC 8445 6.1.2.3，G为控制方的优先级，D为受控方的
+        let (g, d) = if controlling {
+            (local_priority, remote_priority)
+        } else {
+            (remote_priority, local_priority)
+        };
+        Self {
+            local,
+            remote,
+            priority: (g.min(d) << 32) + 2 * g.max(d) + u64::from(g > d),
+            state: CheckState::Waiting,
+            checks: 0,
+            nominated: false,
+        }
+    }
+    fn is_same(&self, other: &CandidatePair) -> bool {
+        self.local == other.local && self.remote.addr == other.remote.addr
+    }
+}
+
+/// 和一个设备之间的检查表，按优先级排列
+#[derive(Default)]
+pub struct CheckList {
+    controlling: bool,
+    pairs: Vec<CandidatePair>,
+}
+
+impl CheckList {
+    pub fn pairs(&self) -> &[CandidatePair] {
+        &self.pairs
+    }
+    pub fn nominated(&self) -> Option<CandidatePair> {
+        self.pairs.iter().find(|pair| pair.nominated).copied()
+    }
+    pub fn clear_nomination(&mut self) {
+        for pair in self.pairs.iter_mut() {
+            pair.nominated = false;
+        }
+    }
+    /// 收到对端的候选地址，已有地址对保留检查状态，检查中发现的对端地址也保留
+    pub fn update_remote(&mut self, controlling: bool, remote: Vec<Candidate>, has_relay: bool) {
+        let mut pairs: Vec<CandidatePair> = Vec::with_capacity(remote.len());
+        for candidate in remote {
+            if candidate.typ == CandidateType::Relay && !has_relay {
+                continue;
+            }
+            let mut pair = CandidatePair::new(controlling, candidate);
+            if pairs.iter().any(|v| v.is_same(&pair)) {
+                continue;
+            }
+            if let Some(old) = self.pairs.iter().find(|v| v.is_same(&pair)) {
+                pair.state = old.state;
+                pair.checks = old.checks;
+                pair.nominated = old.nominated;
+            }
+            pairs.push(pair);
+        }
+        for old in &self.pairs {
+            if old.remote.typ == CandidateType::PeerReflexive
+                && !pairs.iter().any(|v| v.is_same(old))
+            {
+                let mut pair = CandidatePair::new(controlling, old.remote);
+                pair.state = old.state;
+                pair.checks = old.checks;
+                pair.nominated = old.nominated;
+                pairs.push(pair);
+            }
+        }
+        pairs.sort_by(|a, b| b.priority.cmp(&a.priority));
+        self.controlling = controlling;
+        self.pairs = pairs;
+    }
+    /// 开始一轮检查，返回需要发送检查的地址对，relay为false时不检查中继地址
+    pub fn start_checks(&mut self, relay: bool) -> Vec<CandidatePair> {
+        if !self.pairs.is_empty()
+            && self
+                .pairs
+                .iter()
+                .all(|pair| pair.state == CheckState::Failed)
+        {
+            //所有地址都失败了，可能是对端网络变化，整体重新检查
+            for pair in self.pairs.iter_mut() {
+                pair.state = CheckState::Waiting;
+                pair.checks = 0;
+            }
+        }
+        let mut list = Vec::with_capacity(self.pairs.len());
+        for pair in self.pairs.iter_mut() {
+            if pair.local == CandidateType::Relay && !relay {
+                continue;
+            }
+            if pair.state == CheckState::Failed {
+                continue;
+            }
+            if pair.checks >= MAX_CHECKS {
+                pair.state = CheckState::Failed;
+                continue;
+            }
+            pair.checks += 1;
+            pair.state = CheckState::InProgress;
+            list.push(*pair);
+        }
+        list
+    }
+    /// 从addr收到了检查或者回应，返回新提名的地址对和被替换的地址对，
+    /// 成功的地址对优先级比当前提名的高时才重新提名
+    pub fn on_success(
+        &mut self,
+        addr: SocketAddr,
+        relay: bool,
+    ) -> Option<(CandidatePair, Option<CandidatePair>)> {
+        let addr = normalize(addr);
+        let local = if relay {
+            CandidateType::Relay
+        } else {
+            CandidateType::Host
+        };
+        let index = match self
+            .pairs
+            .iter()
+            .position(|pair| pair.local == local && pair.remote.addr == addr)
+        {
+            Some(index) => index,
+            None => {
+                if relay {
+                    return None;
+                }
+                let candidate = Candidate::new(CandidateType::PeerReflexive, addr, 65535);
+                let pair = CandidatePair::new(self.controlling, candidate);
+                self.pairs.push(pair);
+                self.pairs.sort_by(|a, b| b.priority.cmp(&a.priority));
+                self.pairs.iter().position(|v| v.is_same(&pair)).unwrap()
+            }
+        };
+        self.pairs[index].state = CheckState::Succeeded;
+        self.pairs[index].checks = 0;
+        let old = self.nominated();
+        if let Some(old) = &old {
+            if old.priority >= self.pairs[index].priority {
+                return None;
+            }
+        }
+        self.clear_nomination();
+        self.pairs[index].nominated = true;
+        Some((self.pairs[index], old))
+    }
+}
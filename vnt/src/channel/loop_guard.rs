@@ -0,0 +1,89 @@
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+
+use crate::channel::mem_limit::{self, MAX_SUPPRESSED};
+
+/// 记录的报文数，按哈希直接映射，冲突时覆盖
+const SLOTS: usize = 4096;
+/// 检测到环路后丢弃该路由数据的时长
+pub const SUPPRESS: Duration = Duration::from_secs(60);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LoopState {
+    None,
+    /// 刚检测到环路，开始抑制该路由
+    Detected,
+    /// 路由处于抑制期
+    Suppressed,
+}
+
+/// 路由环路检测
+///
+/// in_ips配置错误时(如对端的out_ips又把同一网段路由回虚拟网卡)，报文会在隧道中来回转发直到ttl耗尽，
+/// 大量占用cpu。从其他设备收到、要转发到子网的报文写入网卡前做标记，
+/// 同一个报文又从网卡读到并且要经in_ips发回虚拟网络时判定为环路，一段时间内丢弃经该路由的数据
+pub struct LoopGuard {
+    slots: Vec<AtomicU64>,
+    /// 被抑制的路由(目标,掩码,网关)和恢复时间
+    suppressed: DashMap<(u32, u32, Ipv4Addr), Instant>,
+}
+
+impl Default for LoopGuard {
+    fn default() -> Self {
+        Self {
+            slots: (0..SLOTS).map(|_| AtomicU64::new(0)).collect(),
+            suppressed: DashMap::new(),
+        }
+    }
+}
+
+/// 源地址、目的地址、协议、标识和数据的前8字节(端口等)，不包含转发时会变化的ttl和校验和
+fn fingerprint(ipv4: &[u8]) -> Option<u64> {
+    if ipv4.len() < 20 {
+        return None;
+    }
+    let header_len = (ipv4[0] & 0x0f) as usize * 4;
+    let payload = ipv4.get(header_len..).unwrap_or_default();
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in ipv4[4..6]
+        .iter()
+        .chain(&ipv4[9..10])
+        .chain(&ipv4[12..20])
+        .chain(&payload[..payload.len().min(8)])
+    {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    //0表示空槽
+    Some(hash | 1)
+}
+
+impl LoopGuard {
+    /// 从其他设备收到、要转发到子网的报文
+    pub fn mark(&self, ipv4: &[u8]) {
+        if let Some(hash) = fingerprint(ipv4) {
+            self.slots[hash as usize % SLOTS].store(hash, Ordering::Relaxed);
+        }
+    }
+    /// 要经route发回虚拟网络的报文是否形成环路，Detected和Suppressed都应该丢弃
+    pub fn check(&self, ipv4: &[u8], route: (u32, u32, Ipv4Addr)) -> LoopState {
+        if let Some(until) = self.suppressed.get(&route) {
+            if *until > Instant::now() {
+                return LoopState::Suppressed;
+            }
+        }
+        let hash = match fingerprint(ipv4) {
+            Some(hash) => hash,
+            None => return LoopState::None,
+        };
+        if self.slots[hash as usize % SLOTS].load(Ordering::Relaxed) != hash {
+            return LoopState::None;
+        }
+        mem_limit::make_room(&self.suppressed, &route, MAX_SUPPRESSED, |until| *until);
+        self.suppressed.insert(route, Instant::now() + SUPPRESS);
+        LoopState::Detected
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use packet::cal_checksum;
+
+/// 其他设备发来的ip报文头部不合法的原因
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Malformed {
+    /// 不足20字节
+    TooShort,
+    /// 不是ipv4
+    Version,
+    /// 头部长度小于20字节或者超出报文
+    HeaderLength,
+    /// 总长度小于头部或者超出报文
+    TotalLength,
+    /// 头部校验和错误
+    Checksum,
+    /// 选项长度错误，或者是源路由选项
+    Options,
+    /// 分片重组后超过65535字节
+    Fragment,
+}
+
+impl Malformed {
+    const ALL: [Malformed; 7] = [
+        Malformed::TooShort,
+        Malformed::Version,
+        Malformed::HeaderLength,
+        Malformed::TotalLength,
+        Malformed::Checksum,
+        Malformed::Options,
+        Malformed::Fragment,
+    ];
+}
+
+impl fmt::Display for Malformed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let str = match self {
+            Malformed::TooShort => "too-short",
+            Malformed::Version => "version",
+            Malformed::HeaderLength => "header-length",
+            Malformed::TotalLength => "total-length",
+            Malformed::Checksum => "checksum",
+            Malformed::Options => "options",
+            Malformed::Fragment => "fragment",
+        };
+        f.write_str(str)
+    }
+}
+
+/// 宽松源路由和严格源路由，转发时会绕过路由策略
+const OPTION_LSRR: u8 = 131;
+const OPTION_SSRR: u8 = 137;
+
+/// 校验其他设备发来的ipv4报文头部，转发或者写入网卡前调用，
+/// buf为ip报文，长度可以大于总长度(加密填充等)
+pub fn check_ipv4(buf: &[u8]) -> Result<(), Malformed> {
+    if buf.len() < 20 {
+        return Err(Malformed::TooShort);
+    }
+    if buf[0] >> 4 != 4 {
+        return Err(Malformed::Version);
+    }
+    let header_len = (buf[0] & 0x0f) as usize * 4;
+    if header_len < 20 || header_len > buf.len() {
+        return Err(Malformed::HeaderLength);
+    }
+    let total_len = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+    if total_len < header_len || total_len > buf.len() {
+        return Err(Malformed::TotalLength);
+    }
+    // 校验和正确时对整个头部求和结果为0
+    if cal_checksum(&buf[..header_len]) != 0 {
+        return Err(Malformed::Checksum);
+    }
+    let offset = (u16::from_be_bytes([buf[6], buf[7]]) & 0x1fff) as usize * 8;
+    if offset + total_len - header_len > 65535 {
+        return Err(Malformed::Fragment);
+    }
+    check_options(&buf[20..header_len])
+}
+
+/// 选项最多40字节，每个选项至少1字节，循环次数有上限
+fn check_options(mut options: &[u8]) -> Result<(), Malformed> {
+    while let Some(&kind) = options.first() {
+        match kind {
+            //选项结束
+            0 => return Ok(()),
+            //填充
+            1 => options = &options[1..],
+            OPTION_LSRR | OPTION_SSRR => return Err(Malformed::Options),
+            _ => {
+                let len = *options.get(1).ok_or(Malformed::Options)? as usize;
+                if len < 2 || len > options.len() {
+                    return Err(Malformed::Options);
+                }
+                options = &options[len..];
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 按原因统计丢弃的不合法报文
+#[derive(Default)]
+pub struct MalformedStats {
+    counts: [AtomicU64; 7],
+}
+
+impl MalformedStats {
+    pub fn add(&self, reason: Malformed) {
+        let index = Malformed::ALL.iter().position(|v| *v == reason).unwrap();
+        self.counts[index].fetch_add(1, Ordering::Relaxed);
+    }
+    /// 出现过的原因和次数
+    pub fn list(&self) -> Vec<(Malformed, u64)> {
+        Malformed::ALL
+            .iter()
+            .zip(self.counts.iter())
+            .map(|(reason, count)| (*reason, count.load(Ordering::Relaxed)))
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use dashmap::DashMap;
+
+/// 按设备记录的状态(路由、pong、活跃时间、打洞赛跑)最多保留的设备数，
+/// 超过时淘汰最久没有使用的设备，被淘汰的设备数据经服务端中转，重新通信时再建立直连
+pub const MAX_PEERS: usize = 4096;
+/// ip代理最多同时记录的会话数
+pub const MAX_PROXY_SESSIONS: usize = 8192;
+/// 网桥最多学习的mac地址数
+pub const MAX_BRIDGE_MACS: usize = 4096;
+/// 环路抑制最多记录的路由数
+pub const MAX_SUPPRESSED: usize = 1024;
+
+/// 启动以来因为达到上限被淘汰的记录数
+static EVICTED: AtomicU64 = AtomicU64::new(0);
+
+pub fn evicted() -> u64 {
+    EVICTED.load(Ordering::Relaxed)
+}
+
+/// 表已满且key不存在时，淘汰last_used最早的一项，
+/// 只在满的时候遍历一次，调用时不能持有map中任何项的引用
+pub fn make_room<K, V, F>(map: &DashMap<K, V>, key: &K, cap: usize, last_used: F)
+where
+    K: Eq + Hash + Clone,
+    F: Fn(&V) -> Instant,
+{
+    if map.len() < cap || map.contains_key(key) {
+        return;
+    }
+    let oldest = map
+        .iter()
+        .min_by_key(|item| last_used(item.value()))
+        .map(|item| item.key().clone());
+    if let Some(oldest) = oldest {
+        map.remove(&oldest);
+        EVICTED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// 同make_room，用于调用方已经持有写锁的HashMap
+pub fn make_room_locked<K, V, F>(map: &mut HashMap<K, V>, key: &K, cap: usize, last_used: F)
+where
+    K: Eq + Hash + Clone,
+    F: Fn(&V) -> Instant,
+{
+    if map.len() < cap || map.contains_key(key) {
+        return;
+    }
+    let oldest = map
+        .iter()
+        .min_by_key(|(_, v)| last_used(v))
+        .map(|(k, _)| k.clone());
+    if let Some(oldest) = oldest {
+        map.remove(&oldest);
+        EVICTED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// 没有使用时间的表，满了时随意淘汰一项
+pub fn make_room_any<K, V>(map: &DashMap<K, V>, key: &K, cap: usize)
+where
+    K: Eq + Hash + Clone,
+{
+    if map.len() < cap || map.contains_key(key) {
+        return;
+    }
+    let any = map.iter().next().map(|item| item.key().clone());
+    if let Some(any) = any {
+        map.remove(&any);
+        EVICTED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// 各个表的条目数和估算的内存占用
+#[derive(Clone, Debug, Default)]
+pub struct MemoryUsage {
+    /// (表名,条目数,估算字节数)
+    pub tables: Vec<(&'static str, usize, usize)>,
+    pub evicted: u64,
+}
+
+impl MemoryUsage {
+    pub(crate) fn add(&mut self, name: &'static str, len: usize, entry_size: usize) {
+        self.tables.push((name, len, len * entry_size));
+    }
+    pub fn bytes(&self) -> usize {
+        self.tables.iter().map(|(_, _, bytes)| *bytes).sum()
+    }
+}
@@ -1,12 +1,32 @@
 use std::net::SocketAddr;
 
+use crate::channel::ice::CandidatePair;
+use crate::channel::quality::PathQuality;
+
+pub mod broadcast;
+pub mod capability;
 pub mod channel;
+pub mod clock;
+pub mod ice;
 pub mod idle;
+pub mod loop_guard;
+pub mod malformed;
+pub mod mem_limit;
+pub mod path_policy;
 pub mod punch;
+pub mod punch_limit;
+pub mod punch_record;
+pub mod quality;
+pub mod quarantine;
+pub mod rendezvous;
+pub mod self_test;
 pub mod sender;
+pub mod turn;
 
 const TCP_ID: usize = 0;
 const UDP_ID: usize = 1;
+/// 经turn服务器中转
+const TURN_ID: usize = 2;
 
 #[derive(Copy, Clone, Eq, PartialEq)]
 pub enum Status {
@@ -22,6 +42,10 @@ pub struct Route {
     pub addr: SocketAddr,
     pub metric: u8,
     pub rt: i64,
+    /// 经ice连通性检查提名的地址对，用于排查打洞问题
+    pub ice: Option<CandidatePair>,
+    /// 直连路径的质量，变差时数据改走中转
+    pub quality: PathQuality,
 }
 
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
@@ -38,6 +62,8 @@ impl Route {
             addr,
             metric,
             rt,
+            ice: None,
+            quality: PathQuality::default(),
         }
     }
     pub fn from(route_key: RouteKey, metric: u8, rt: i64) -> Self {
@@ -47,6 +73,8 @@ impl Route {
             addr: route_key.addr,
             metric,
             rt,
+            ice: None,
+            quality: PathQuality::default(),
         }
     }
     pub fn route_key(&self) -> RouteKey {
@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+
+use crate::handle::PeerDeviceInfo;
+
+/// 服务端给设备打的路径策略标签，如 path:never-relay
+pub const TAG_PREFIX: &str = "path:";
+
+/// 和设备通信时的路径选择策略
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum PathPolicy {
+    /// 优先直连，打不通时经服务端中转
+    #[default]
+    Auto,
+    /// 直连质量变差也不切换到中转，持续以最短间隔打洞
+    PreferDirect,
+    /// 服务端中转可用时不打洞，适合直连不稳定的网络
+    PreferRelay,
+    /// 只允许直连，不经服务端、turn或其他设备中转，打不通时丢弃数据
+    NeverRelay,
+}
+
+impl FromStr for PathPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().trim() {
+            "auto" => Ok(PathPolicy::Auto),
+            "prefer-direct" => Ok(PathPolicy::PreferDirect),
+            "prefer-relay" => Ok(PathPolicy::PreferRelay),
+            "never-relay" => Ok(PathPolicy::NeverRelay),
+            _ => Err(format!(
+                "not match '{}', enum: auto/prefer-direct/prefer-relay/never-relay",
+                s
+            )),
+        }
+    }
+}
+
+/// 各设备的路径策略，本地配置优先于服务端标签
+#[derive(Default)]
+pub struct PathPolicyTable {
+    local: HashMap<Ipv4Addr, PathPolicy>,
+    /// 本地配置中0.0.0.0对应的策略，对所有设备生效
+    default: PathPolicy,
+    tagged: DashMap<Ipv4Addr, PathPolicy>,
+    /// 临时指定的策略和到期时间，如测速时强制走中转，优先于其他配置
+    temporary: DashMap<Ipv4Addr, (PathPolicy, Instant)>,
+    temporary_len: AtomicUsize,
+}
+
+impl PathPolicyTable {
+    pub fn new(local: &[(Ipv4Addr, PathPolicy)]) -> Self {
+        let mut table = PathPolicyTable::default();
+        for (ip, policy) in local {
+            if ip.is_unspecified() {
+                table.default = *policy;
+            } else {
+                table.local.insert(*ip, *policy);
+            }
+        }
+        table
+    }
+    pub fn get(&self, ip: &Ipv4Addr) -> PathPolicy {
+        if self.temporary_len.load(Ordering::Relaxed) > 0 {
+            if let Some(policy) = self.get_temporary(ip) {
+                return policy;
+            }
+        }
+        if let Some(policy) = self.local.get(ip) {
+            return *policy;
+        }
+        if let Some(policy) = self.tagged.get(ip) {
+            return *policy;
+        }
+        self.default
+    }
+    fn get_temporary(&self, ip: &Ipv4Addr) -> Option<PathPolicy> {
+        let (policy, until) = *self.temporary.get(ip)?;
+        if until > Instant::now() {
+            return Some(policy);
+        }
+        if self
+            .temporary
+            .remove_if(ip, |_, (_, until)| *until <= Instant::now())
+            .is_some()
+        {
+            self.temporary_len.fetch_sub(1, Ordering::Relaxed);
+        }
+        None
+    }
+    /// 临时指定和某个设备的路径策略，到期后自动恢复，duration为None时立即恢复
+    pub fn set_temporary(&self, ip: Ipv4Addr, policy: PathPolicy, duration: Option<Duration>) {
+        match duration {
+            Some(duration) => {
+                if self
+                    .temporary
+                    .insert(ip, (policy, Instant::now() + duration))
+                    .is_none()
+                {
+                    self.temporary_len.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            None => {
+                if self.temporary.remove(&ip).is_some() {
+                    self.temporary_len.fetch_sub(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+    pub fn default_policy(&self) -> PathPolicy {
+        self.default
+    }
+    /// 明确禁止中转的设备，广播时告知服务端不要转发给这些设备
+    pub fn never_relay(&self) -> Vec<Ipv4Addr> {
+        let mut list: Vec<Ipv4Addr> = self
+            .local
+            .iter()
+            .filter(|(_, policy)| **policy == PathPolicy::NeverRelay)
+            .map(|(ip, _)| *ip)
+            .collect();
+        for item in self.tagged.iter() {
+            if *item.value() == PathPolicy::NeverRelay && !self.local.contains_key(item.key()) {
+                list.push(*item.key());
+            }
+        }
+        list
+    }
+    /// 设备列表更新时读取设备上的path:标签
+    pub fn update_tags(&self, device_list: &[PeerDeviceInfo]) {
+        self.tagged.clear();
+        for peer in device_list {
+            for tag in &peer.tags {
+                if let Some(policy) = tag.strip_prefix(TAG_PREFIX) {
+                    match PathPolicy::from_str(policy) {
+                        Ok(policy) => {
+                            self.tagged.insert(peer.virtual_ip, policy);
+                        }
+                        Err(e) => {
+                            log::warn!("设备{}的标签{}无效 {}", peer.virtual_ip, tag, e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
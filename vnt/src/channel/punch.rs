@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, TcpStream};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 use std::str::FromStr;
 use std::time::Duration;
 use std::{io, thread};
@@ -7,6 +7,7 @@ use std::{io, thread};
 use rand::prelude::SliceRandom;
 
 use crate::channel::channel::{send_tcp, start_tcp_handle, Context};
+use crate::channel::ice::CandidateType;
 use crate::handle::recv_handler::ChannelDataHandler;
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -108,6 +109,9 @@ impl NatInfo {
     pub fn ipv6(&self) -> Option<Ipv6Addr> {
         self.ipv6
     }
+    pub fn udp_port(&self) -> u16 {
+        self.udp_port
+    }
     pub fn local_udp_ipv4addr(&self) -> Option<SocketAddr> {
         if self.udp_port == 0 {
             return None;
@@ -185,7 +189,7 @@ impl Punch {
 
 impl Punch {
     fn connect_tcp(&self, buf: &[u8], addr: &SocketAddr) -> bool {
-        match TcpStream::connect_timeout(&addr, Duration::from_secs(1)) {
+        match crate::util::fwmark::tcp_connect(addr, Some(Duration::from_secs(1))) {
             Ok(mut tcp_stream) => {
                 let context = self.context.clone();
                 let handler = self.handler.clone();
@@ -213,6 +217,15 @@ impl Punch {
         if !self.context.need_punch(&id) {
             return Ok(());
         }
+        self.context.counters().punch();
+        let local_nat = if self.context.is_cone() {
+            NatType::Cone
+        } else {
+            NatType::Symmetric
+        };
+        self.context
+            .punch_records()
+            .start(id, local_nat, nat_info.nat_type);
         if self.is_tcp {
             //向tcp发起连接
             if let Some(ipv6_addr) = nat_info.local_tcp_ipv6addr() {
@@ -235,84 +248,89 @@ impl Punch {
                 }
             }
         }
-        if let Some(ipv4_addr) = nat_info.local_udp_ipv4addr() {
-            let _ = self.context.send_main_udp(buf, ipv4_addr);
-        }
-        if self.punch_model != PunchModel::IPv4 {
-            if let Some(ipv6_addr) = nat_info.local_udp_ipv6addr() {
-                let rs = self.context.send_main_udp(buf, ipv6_addr);
-                log::info!("发送到ipv6地址:{:?},rs={:?}", ipv6_addr, rs);
-                if rs.is_ok() && self.punch_model == PunchModel::IPv6 {
-                    return Ok(());
+        //按优先级对候选地址对做连通性检查，回应由recv_handler交给Context提名
+        let is_cone = self.context.is_cone();
+        for pair in self.context.ice_start_checks(&id) {
+            let addr = pair.remote.addr;
+            match self.punch_model {
+                PunchModel::IPv4 if addr.is_ipv6() => continue,
+                PunchModel::IPv6 if addr.is_ipv4() && pair.remote.typ != CandidateType::Host => {
+                    continue
                 }
+                _ => {}
+            }
+            log::debug!("ice检查 {},{}/{} {}", id, pair.local, pair.remote.typ, addr);
+            self.context
+                .punch_records()
+                .tried(&id, format!("{}/{} {}", pair.local, pair.remote.typ, addr));
+            if pair.local == CandidateType::Relay {
+                let _ = self.context.send_turn(buf, addr);
+                continue;
+            }
+            if let Err(e) = self.context.send_main_udp(buf, addr) {
+                log::warn!("ice检查发送失败 {},{:?}", addr, e);
+                self.context.punch_records().unreachable(&id, addr, &e);
             }
+            if !is_cone && addr.is_ipv4() && pair.remote.typ != CandidateType::Host {
+                //只有一方是对称，则对称方要使用全部端口发送数据，符合下述计算的概率
+                self.context.try_send_all(buf, addr)?;
+            }
+            tokio::time::sleep(Duration::from_millis(2)).await;
         }
-        match nat_info.nat_type {
-            NatType::Symmetric => {
-                // 假设对方绑定n个端口，通过NAT对外映射出n个 公网ip:公网端口，自己随机尝试k次的情况下
-                // 猜中的概率 p = 1-((65535-n)/65535)*((65535-n-1)/(65535-1))*...*((65535-n-k+1)/(65535-k+1))
-                // n取76，k取600，猜中的概率就超过50%了
-                // 前提 自己是锥形网络，否则猜中了也通信不了
+        if nat_info.nat_type == NatType::Symmetric && self.punch_model != PunchModel::IPv6 {
+            // 对方是对称网络时映射端口无法预先得知，srflx候选地址大概率不通，按端口预测继续发送检查，
+            // 猜中的地址回应后作为prflx候选地址提名
+            // 假设对方绑定n个端口，通过NAT对外映射出n个 公网ip:公网端口，自己随机尝试k次的情况下
+            // 猜中的概率 p = 1-((65535-n)/65535)*((65535-n-1)/(65535-1))*...*((65535-n-k+1)/(65535-k+1))
+            // n取76，k取600，猜中的概率就超过50%了
+            // 前提 自己是锥形网络，否则猜中了也通信不了
 
-                //预测范围内最多发送max_k1个包
-                let max_k1 = 60;
-                //全局最多发送max_k2个包
-                let max_k2 = 800;
-                if nat_info.public_port_range < max_k1 * 3 {
-                    //端口变化不大时，在预测的范围内随机发送
-                    let min_port = if nat_info.public_port > nat_info.public_port_range {
-                        nat_info.public_port - nat_info.public_port_range
-                    } else {
-                        1
-                    };
-                    let (max_port, overflow) = nat_info
-                        .public_port
-                        .overflowing_add(nat_info.public_port_range);
-                    let max_port = if overflow { 65535 } else { max_port };
-                    let k = if max_port - min_port + 1 > max_k1 {
-                        max_k1 as usize
-                    } else {
-                        (max_port - min_port + 1) as usize
-                    };
-                    let mut nums: Vec<u16> = (min_port..max_port).collect();
-                    nums.push(max_port);
-                    {
-                        let mut rng = rand::thread_rng();
-                        nums.shuffle(&mut rng);
-                    }
-                    self.punch_symmetric(&nums[..k], buf, &nat_info.public_ips, max_k1 as usize)
-                        .await?;
-                }
-                let start = *self.port_index.entry(id.clone()).or_insert(0);
-                let mut end = start + max_k2;
-                let mut index = end;
-                if end >= self.port_vec.len() {
-                    end = self.port_vec.len();
-                    index = 0
+            //预测范围内最多发送max_k1个包
+            let max_k1 = 60;
+            //全局最多发送max_k2个包
+            let max_k2 = 800;
+            if nat_info.public_port_range < max_k1 * 3 {
+                //端口变化不大时，在预测的范围内随机发送
+                let min_port = if nat_info.public_port > nat_info.public_port_range {
+                    nat_info.public_port - nat_info.public_port_range
+                } else {
+                    1
+                };
+                let (max_port, overflow) = nat_info
+                    .public_port
+                    .overflowing_add(nat_info.public_port_range);
+                let max_port = if overflow { 65535 } else { max_port };
+                let k = if max_port - min_port + 1 > max_k1 {
+                    max_k1 as usize
+                } else {
+                    (max_port - min_port + 1) as usize
+                };
+                let mut nums: Vec<u16> = (min_port..max_port).collect();
+                nums.push(max_port);
+                {
+                    let mut rng = rand::thread_rng();
+                    nums.shuffle(&mut rng);
                 }
-                self.punch_symmetric(
-                    &self.port_vec[start..end],
-                    buf,
-                    &nat_info.public_ips,
-                    max_k2,
-                )
-                .await?;
-                self.port_index.insert(id, index);
+                self.context.punch_records().predicted(&id, k);
+                self.punch_symmetric(&nums[..k], buf, &nat_info.public_ips, max_k1 as usize)
+                    .await?;
             }
-            NatType::Cone => {
-                if nat_info.public_port != 0 {
-                    let is_cone = self.context.is_cone();
-                    for ip in nat_info.public_ips {
-                        let addr = SocketAddr::V4(SocketAddrV4::new(ip, nat_info.public_port));
-                        self.context.send_main_udp(buf, addr)?;
-                        if !is_cone {
-                            //只有一方是对称，则对称方要使用全部端口发送数据，符合上述计算的概率
-                            self.context.try_send_all(buf, addr)?;
-                        }
-                        tokio::time::sleep(Duration::from_millis(2)).await;
-                    }
-                }
+            let start = *self.port_index.entry(id.clone()).or_insert(0);
+            let mut end = start + max_k2;
+            let mut index = end;
+            if end >= self.port_vec.len() {
+                end = self.port_vec.len();
+                index = 0
             }
+            self.context.punch_records().predicted(&id, end - start);
+            self.punch_symmetric(
+                &self.port_vec[start..end],
+                buf,
+                &nat_info.public_ips,
+                max_k2,
+            )
+            .await?;
+            self.port_index.insert(id, index);
         }
         Ok(())
     }
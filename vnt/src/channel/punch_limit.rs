@@ -0,0 +1,87 @@
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+/// 令牌桶容量，允许短时间内集中发起几次
+const CAPACITY: f64 = 8.0;
+/// 每秒补充的令牌数
+const RATE: f64 = 0.5;
+/// 服务端没有给出等待时间时的初始退避，连续被限流时翻倍
+const BACKOFF_MIN: Duration = Duration::from_secs(10);
+const BACKOFF_MAX: Duration = Duration::from_secs(320);
+/// 距上次被限流超过该时长，退避重新计算
+const BACKOFF_RESET: Duration = Duration::from_secs(600);
+
+struct State {
+    tokens: f64,
+    last: Instant,
+    /// 服务端要求暂停到这个时间
+    backoff_until: Option<Instant>,
+    /// 上次被限流的时间和使用的退避时长
+    last_limited: Option<(Instant, Duration)>,
+}
+
+/// 经服务端转发的打洞请求限速，本机令牌桶避免异常情况下刷屏，
+/// 收到服务端的限流错误后在要求的时间内不再发起
+pub struct PunchLimiter {
+    state: Mutex<State>,
+}
+
+impl Default for PunchLimiter {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(State {
+                tokens: CAPACITY,
+                last: Instant::now(),
+                backoff_until: None,
+                last_limited: None,
+            }),
+        }
+    }
+}
+
+impl PunchLimiter {
+    /// 发起一次打洞请求前调用，返回false时本轮不发送
+    pub fn try_acquire(&self) -> bool {
+        let mut state = self.state.lock();
+        let now = Instant::now();
+        if let Some(until) = state.backoff_until {
+            if now < until {
+                return false;
+            }
+            state.backoff_until = None;
+        }
+        let elapsed = now.duration_since(state.last).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * RATE).min(CAPACITY);
+        state.last = now;
+        if state.tokens < 1.0 {
+            return false;
+        }
+        state.tokens -= 1.0;
+        true
+    }
+    /// 服务端限流，retry_after为服务端要求等待的秒数，0表示未指定。返回实际等待的时长
+    pub fn on_server_limited(&self, retry_after: u32) -> Duration {
+        let mut state = self.state.lock();
+        let now = Instant::now();
+        let backoff = match state.last_limited {
+            Some((time, last)) if now.duration_since(time) < BACKOFF_RESET => {
+                (last * 2).min(BACKOFF_MAX)
+            }
+            _ => BACKOFF_MIN,
+        };
+        let backoff = backoff.max(Duration::from_secs(retry_after as u64));
+        state.last_limited = Some((now, backoff));
+        state.backoff_until = Some(now + backoff);
+        //恢复后从空桶开始，避免立即集中发送
+        state.tokens = 0.0;
+        backoff
+    }
+    /// 是否处于服务端要求的暂停期内
+    pub fn is_limited(&self) -> bool {
+        self.state
+            .lock()
+            .backoff_until
+            .map_or(false, |until| Instant::now() < until)
+    }
+}
@@ -0,0 +1,139 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use dashmap::DashMap;
+
+use crate::channel::punch::NatType;
+
+/// 检查发出后超过该时长没有打通，视为超时
+const PUNCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 打洞由哪一方发起
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PunchInitiator {
+    /// 本机向对端发送了打洞信息，对端回应后开始打洞
+    Local,
+    /// 收到对端的打洞信息
+    Remote,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PunchOutcome {
+    /// 检查已发出，还在等待回应
+    Pending,
+    /// 打通，记录提名的地址对
+    Succeeded(String),
+    /// 没有收到回应
+    Timeout,
+    /// 发送失败或者收到icmp不可达
+    Unreachable(String),
+}
+
+/// 和一个设备最近一次打洞的过程
+#[derive(Clone, Debug)]
+pub struct PunchRecord {
+    /// unix毫秒
+    pub time: u64,
+    pub initiator: PunchInitiator,
+    pub local_nat: NatType,
+    pub peer_nat: NatType,
+    /// 尝试过的地址对，如host/srflx 1.2.3.4:5
+    pub candidates: Vec<String>,
+    /// 按端口预测发送的检查数量
+    pub predicted: usize,
+    pub outcome: PunchOutcome,
+}
+
+/// 各设备最近一次打洞的记录，用于排查打不通的原因
+///
+/// icmp不可达：linux下未连接的udp socket收不到icmp错误，只能从发送失败得知，
+/// windows下icmp端口不可达表现为接收时的WSAECONNRESET，没有地址，记到所有等待中的记录上
+#[derive(Default)]
+pub struct PunchRecords {
+    records: DashMap<Ipv4Addr, PunchRecord>,
+    /// 收到打洞信息时记录发起方，开始打洞时取出
+    initiators: DashMap<Ipv4Addr, PunchInitiator>,
+}
+
+impl PunchRecords {
+    /// 收到对端的打洞信息，reply表示是对本机请求的回应
+    pub fn on_punch_info(&self, peer_ip: Ipv4Addr, reply: bool) {
+        let initiator = if reply {
+            PunchInitiator::Local
+        } else {
+            PunchInitiator::Remote
+        };
+        self.initiators.insert(peer_ip, initiator);
+    }
+    /// 开始一轮打洞，覆盖之前的记录
+    pub fn start(&self, peer_ip: Ipv4Addr, local_nat: NatType, peer_nat: NatType) {
+        let initiator = self
+            .initiators
+            .remove(&peer_ip)
+            .map_or(PunchInitiator::Local, |(_, v)| v);
+        self.records.insert(
+            peer_ip,
+            PunchRecord {
+                time: crate::handle::now_time(),
+                initiator,
+                local_nat,
+                peer_nat,
+                candidates: Vec::new(),
+                predicted: 0,
+                outcome: PunchOutcome::Pending,
+            },
+        );
+    }
+    pub fn tried(&self, peer_ip: &Ipv4Addr, candidate: String) {
+        if let Some(mut record) = self.records.get_mut(peer_ip) {
+            record.candidates.push(candidate);
+        }
+    }
+    pub fn predicted(&self, peer_ip: &Ipv4Addr, count: usize) {
+        if let Some(mut record) = self.records.get_mut(peer_ip) {
+            record.predicted += count;
+        }
+    }
+    /// 发送检查失败，已经打通的不覆盖
+    pub fn unreachable(&self, peer_ip: &Ipv4Addr, addr: SocketAddr, err: &std::io::Error) {
+        if let Some(mut record) = self.records.get_mut(peer_ip) {
+            if record.outcome == PunchOutcome::Pending {
+                record.outcome = PunchOutcome::Unreachable(format!("{} {}", addr, err));
+            }
+        }
+    }
+    /// 接收时收到icmp端口不可达
+    pub fn on_icmp_unreachable(&self) {
+        for mut record in self.records.iter_mut() {
+            if record.outcome == PunchOutcome::Pending {
+                record.outcome = PunchOutcome::Unreachable("icmp port unreachable".to_string());
+            }
+        }
+    }
+    pub fn succeeded(&self, peer_ip: &Ipv4Addr, pair: String) {
+        if let Some(mut record) = self.records.get_mut(peer_ip) {
+            record.outcome = PunchOutcome::Succeeded(pair);
+        }
+    }
+    /// 等待超过PUNCH_TIMEOUT的显示为超时
+    pub fn get(&self, peer_ip: &Ipv4Addr) -> Option<PunchRecord> {
+        self.records
+            .get(peer_ip)
+            .map(|v| Self::check_timeout(v.clone()))
+    }
+    pub fn list(&self) -> Vec<(Ipv4Addr, PunchRecord)> {
+        self.records
+            .iter()
+            .map(|v| (*v.key(), Self::check_timeout(v.clone())))
+            .collect()
+    }
+    fn check_timeout(mut record: PunchRecord) -> PunchRecord {
+        if record.outcome == PunchOutcome::Pending
+            && crate::handle::now_time().saturating_sub(record.time)
+                > PUNCH_TIMEOUT.as_millis() as u64
+        {
+            record.outcome = PunchOutcome::Timeout;
+        }
+        record
+    }
+}
@@ -0,0 +1,112 @@
+use std::fmt;
+
+/// 参与统计的最近探测数，不含最新一个还在等待回应的
+const WINDOW: u32 = 6;
+/// 窗口内丢失这么多个探测则切换到中转
+const MAX_LOST: u32 = 3;
+/// 连续这么多次延迟异常则切换到中转
+const MAX_SLOW: u8 = 2;
+/// 恢复直连需要最近连续成功的探测数
+const RECOVER: u32 = 4;
+
+/// 直连路径被放弃的原因
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DegradeReason {
+    /// 丢包严重
+    Loss,
+    /// 延迟远超过基准延迟
+    Latency,
+}
+
+impl fmt::Display for DegradeReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DegradeReason::Loss => f.write_str("loss"),
+            DegradeReason::Latency => f.write_str("latency"),
+        }
+    }
+}
+
+/// 直连路径的质量，由定时探测统计，质量变差时数据改走服务端中转，
+/// 同时继续探测，恢复后切回直连
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PathQuality {
+    /// 每一位表示一次探测是否收到回应，最低位是最新的探测
+    window: u16,
+    /// 已发送的探测数，不满窗口时不判断丢包
+    sent: u32,
+    /// 连续的延迟异常次数
+    slow: u8,
+    /// 路径正常时的基准延迟
+    pub base_rt: i64,
+    /// 不为None时表示已放弃这条直连路径
+    pub degraded: Option<DegradeReason>,
+}
+
+impl PathQuality {
+    /// 窗口内丢失的探测数和统计的探测数
+    pub fn loss(&self) -> (u32, u32) {
+        let total = self.sent.saturating_sub(1).min(WINDOW);
+        let mask = ((1u16 << total) - 1) << 1;
+        (total - (self.window & mask).count_ones(), total)
+    }
+    pub fn is_degraded(&self) -> bool {
+        self.degraded.is_some()
+    }
+    /// 发送了一次探测，返回状态是否变化
+    pub fn on_sent(&mut self) -> bool {
+        self.window <<= 1;
+        self.sent = self.sent.saturating_add(1);
+        self.update()
+    }
+    /// 收到探测回应，返回状态是否变化
+    pub fn on_reply(&mut self, rt: i64) -> bool {
+        self.window |= 1;
+        if self.base_rt <= 0 || rt < self.base_rt {
+            self.base_rt = rt;
+        } else {
+            //路径变化后基准延迟也会变化，缓慢抬高
+            self.base_rt += 1;
+        }
+        if rt > (self.base_rt * 4).max(self.base_rt + 300) {
+            self.slow = self.slow.saturating_add(1);
+        } else {
+            self.slow = 0;
+        }
+        self.update()
+    }
+    fn update(&mut self) -> bool {
+        let (lost, total) = self.loss();
+        let old = self.degraded;
+        match self.degraded {
+            None => {
+                if total >= WINDOW && lost >= MAX_LOST {
+                    self.degraded = Some(DegradeReason::Loss);
+                } else if self.slow >= MAX_SLOW {
+                    self.degraded = Some(DegradeReason::Latency);
+                }
+            }
+            Some(_) => {
+                let mask = (1u16 << RECOVER) - 1;
+                if self.slow == 0 && self.window & mask == mask {
+                    self.degraded = None;
+                }
+            }
+        }
+        old != self.degraded
+    }
+}
+
+impl fmt::Display for PathQuality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (lost, total) = self.loss();
+        match self.degraded {
+            None => write!(f, "ok loss={}/{}", lost, total),
+            Some(reason) => write!(
+                f,
+                "relay({}) loss={}/{} base_rt={}",
+                reason, lost, total, self.base_rt
+            ),
+        }
+    }
+}
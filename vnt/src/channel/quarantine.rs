@@ -0,0 +1,135 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+
+/// 统计失败次数的时间窗口
+const WINDOW: Duration = Duration::from_secs(10);
+/// 窗口内连续失败达到该次数就隔离，期间有解密成功的数据会重新计数
+const THRESHOLD: u32 = 64;
+/// 第一次隔离的时长，再次隔离时翻倍
+const MIN_QUARANTINE: Duration = Duration::from_secs(60);
+const MAX_QUARANTINE: Duration = Duration::from_secs(30 * 60);
+/// 最多记录的地址数，避免伪造源地址耗尽内存
+const CAPACITY: usize = 4096;
+
+/// 某个地址的解密/校验失败统计
+#[derive(Copy, Clone, Debug)]
+pub struct AuthFailure {
+    pub addr: SocketAddr,
+    /// 累计失败次数
+    pub failures: u64,
+    /// 被隔离的次数
+    pub times: u32,
+    /// 剩余的隔离时间，未隔离时为None
+    pub remaining: Option<Duration>,
+}
+
+struct Entry {
+    failures: u64,
+    recent: u32,
+    window_start: Instant,
+    times: u32,
+    until: Option<Instant>,
+}
+
+/// 持续发送无法解密数据的地址，在一段时间内直接丢弃其数据，不再尝试解密
+#[derive(Default)]
+pub struct Quarantine {
+    table: DashMap<SocketAddr, Entry>,
+    blocked: AtomicUsize,
+    total: AtomicU64,
+}
+
+impl Quarantine {
+    /// 是否处于隔离期
+    #[inline]
+    pub fn is_blocked(&self, addr: &SocketAddr) -> bool {
+        if self.blocked.load(Ordering::Relaxed) == 0 {
+            return false;
+        }
+        let mut entry = match self.table.get_mut(addr) {
+            None => return false,
+            Some(entry) => entry,
+        };
+        match entry.until {
+            Some(until) if until > Instant::now() => true,
+            Some(_) => {
+                entry.until = None;
+                entry.recent = 0;
+                entry.window_start = Instant::now();
+                self.blocked.fetch_sub(1, Ordering::Relaxed);
+                false
+            }
+            None => false,
+        }
+    }
+    /// 记录一次失败，开始隔离时返回隔离时长
+    pub fn failure(&self, addr: SocketAddr) -> Option<Duration> {
+        self.total.fetch_add(1, Ordering::Relaxed);
+        if self.table.len() >= CAPACITY && !self.table.contains_key(&addr) {
+            self.table
+                .retain(|_, entry| entry.until.is_some() || entry.window_start.elapsed() < WINDOW);
+            if self.table.len() >= CAPACITY {
+                return None;
+            }
+        }
+        let now = Instant::now();
+        let mut entry = self.table.entry(addr).or_insert_with(|| Entry {
+            failures: 0,
+            recent: 0,
+            window_start: now,
+            times: 0,
+            until: None,
+        });
+        entry.failures += 1;
+        if entry.until.is_some() {
+            return None;
+        }
+        if now.duration_since(entry.window_start) > WINDOW {
+            entry.window_start = now;
+            entry.recent = 0;
+        }
+        entry.recent += 1;
+        if entry.recent < THRESHOLD {
+            return None;
+        }
+        let duration = MIN_QUARANTINE
+            .saturating_mul(1 << entry.times.min(5))
+            .min(MAX_QUARANTINE);
+        entry.times += 1;
+        entry.until = Some(now + duration);
+        self.blocked.fetch_add(1, Ordering::Relaxed);
+        Some(duration)
+    }
+    /// 收到能正常处理的数据，重新计数
+    #[inline]
+    pub fn success(&self, addr: &SocketAddr) {
+        if let Some(mut entry) = self.table.get_mut(addr) {
+            entry.recent = 0;
+        }
+    }
+    /// 启动以来的失败总数
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+    pub fn list(&self) -> Vec<AuthFailure> {
+        let now = Instant::now();
+        let mut list: Vec<AuthFailure> = self
+            .table
+            .iter()
+            .map(|entry| AuthFailure {
+                addr: *entry.key(),
+                failures: entry.failures,
+                times: entry.times,
+                remaining: entry
+                    .until
+                    .filter(|until| *until > now)
+                    .map(|until| until - now),
+            })
+            .collect();
+        list.sort_by(|a, b| b.failures.cmp(&a.failures));
+        list
+    }
+}
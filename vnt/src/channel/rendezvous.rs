@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+
+use crate::channel::punch::NatInfo;
+
+/// 不经服务端交换打洞信息：把本机的打洞信息直接发到设备可能的地址，
+/// 对端收到后回应并开始打洞，用于服务端不可达时，或者局域网内配置了静态地址的设备
+#[derive(Default)]
+pub struct Rendezvous {
+    /// 配置的设备静态地址
+    static_addrs: HashMap<Ipv4Addr, Vec<SocketAddr>>,
+}
+
+impl Rendezvous {
+    pub fn new(list: &[(Ipv4Addr, SocketAddr)]) -> Self {
+        let mut static_addrs: HashMap<Ipv4Addr, Vec<SocketAddr>> = HashMap::new();
+        for (ip, addr) in list {
+            let addrs = static_addrs.entry(*ip).or_default();
+            if !addrs.contains(addr) {
+                addrs.push(*addr);
+            }
+        }
+        Self { static_addrs }
+    }
+    /// 配置了静态地址的设备，服务端的设备列表中可能没有
+    pub fn static_peers(&self) -> Vec<Ipv4Addr> {
+        self.static_addrs.keys().copied().collect()
+    }
+    pub fn has_static(&self, ip: &Ipv4Addr) -> bool {
+        self.static_addrs.contains_key(ip)
+    }
+    /// 设备可能的地址，静态地址在前，然后是最近一次打洞信息中的局域网、ipv6和公网地址
+    pub fn addrs(&self, ip: &Ipv4Addr, nat_info: Option<&NatInfo>) -> Vec<SocketAddr> {
+        let mut list = self.static_addrs.get(ip).cloned().unwrap_or_default();
+        if let Some(nat_info) = nat_info {
+            let mut push = |addr: SocketAddr| {
+                if !list.contains(&addr) {
+                    list.push(addr);
+                }
+            };
+            if let Some(addr) = nat_info.local_udp_ipv4addr() {
+                push(addr);
+            }
+            if let Some(addr) = nat_info.local_udp_ipv6addr() {
+                push(addr);
+            }
+            if nat_info.public_port != 0 {
+                for public_ip in &nat_info.public_ips {
+                    push(SocketAddr::V4(SocketAddrV4::new(
+                        *public_ip,
+                        nat_info.public_port,
+                    )));
+                }
+            }
+        }
+        list
+    }
+}
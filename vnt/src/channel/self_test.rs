@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+
+/// 等待pong的时间
+pub const REPLY_TIMEOUT: Duration = Duration::from_secs(3);
+/// 连续失败多少次后告警
+pub const FAILURE_THRESHOLD: u32 = 3;
+
+/// 单个设备的自检结果
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SelfTestStatus {
+    /// 连续失败次数
+    pub failures: u32,
+    /// 最近一次成功的时间，unix秒，0表示还没有成功过
+    pub last_ok: u64,
+    /// 最近一次成功的延迟(毫秒)
+    pub rtt: u64,
+    /// 连续失败达到阈值后置位，恢复后清除
+    pub alerted: bool,
+}
+
+/// 自检结果的变化，需要记录事件
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SelfTestChange {
+    /// 连续失败达到阈值
+    Failed(u32),
+    /// 告警后恢复
+    Recovered,
+}
+
+/// 定时ping指定的设备，连续失败时告警，适合无人值守的站点网关
+pub struct SelfTest {
+    peers: Vec<Ipv4Addr>,
+    interval: Duration,
+    /// 连续失败多少次后告警
+    threshold: u32,
+    status: Mutex<HashMap<Ipv4Addr, SelfTestStatus>>,
+}
+
+impl SelfTest {
+    pub fn new(peers: Vec<Ipv4Addr>, interval: Duration, threshold: u32) -> Self {
+        Self {
+            peers,
+            interval,
+            threshold: threshold.max(1),
+            status: Mutex::new(HashMap::with_capacity(8)),
+        }
+    }
+    pub fn peers(&self) -> &[Ipv4Addr] {
+        &self.peers
+    }
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+    /// 记录一次自检结果，rtt为None表示失败
+    pub fn update(&self, peer_ip: Ipv4Addr, rtt: Option<Duration>) -> Option<SelfTestChange> {
+        let mut guard = self.status.lock();
+        let status = guard.entry(peer_ip).or_default();
+        match rtt {
+            Some(rtt) => {
+                status.failures = 0;
+                status.last_ok = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|v| v.as_secs())
+                    .unwrap_or(0);
+                status.rtt = rtt.as_millis() as u64;
+                if status.alerted {
+                    status.alerted = false;
+                    return Some(SelfTestChange::Recovered);
+                }
+            }
+            None => {
+                status.failures += 1;
+                if !status.alerted && status.failures >= self.threshold {
+                    status.alerted = true;
+                    return Some(SelfTestChange::Failed(status.failures));
+                }
+            }
+        }
+        None
+    }
+    /// 各设备的结果，按配置顺序排列，还没测过的不返回
+    pub fn list(&self) -> Vec<(Ipv4Addr, SelfTestStatus)> {
+        let guard = self.status.lock();
+        self.peers
+            .iter()
+            .filter_map(|ip| guard.get(ip).map(|status| (*ip, *status)))
+            .collect()
+    }
+    /// 是否有设备处于告警状态
+    pub fn is_alerting(&self) -> bool {
+        self.status.lock().values().any(|v| v.alerted)
+    }
+}
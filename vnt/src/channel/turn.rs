@@ -0,0 +1,468 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crossbeam_utils::atomic::AtomicCell;
+use dashmap::DashMap;
+use hmac::{Hmac, Mac};
+use md5::{Digest, Md5};
+use parking_lot::Mutex;
+use sha1::Sha1;
+use tokio::net::UdpSocket;
+
+use crate::channel::channel::Context;
+use crate::channel::{RouteKey, TURN_ID};
+use crate::core::status::VntWorker;
+use crate::handle::recv_handler::ChannelDataHandler;
+
+const MAGIC_COOKIE: u32 = 0x2112A442;
+
+const ALLOCATE_REQUEST: u16 = 0x0003;
+const ALLOCATE_SUCCESS: u16 = 0x0103;
+const ALLOCATE_ERROR: u16 = 0x0113;
+const REFRESH_REQUEST: u16 = 0x0004;
+const REFRESH_SUCCESS: u16 = 0x0104;
+const REFRESH_ERROR: u16 = 0x0114;
+const CREATE_PERMISSION_REQUEST: u16 = 0x0008;
+const CREATE_PERMISSION_ERROR: u16 = 0x0118;
+const SEND_INDICATION: u16 = 0x0016;
+const DATA_INDICATION: u16 = 0x0017;
+
+const ATTR_USERNAME: u16 = 0x0006;
+const ATTR_MESSAGE_INTEGRITY: u16 = 0x0008;
+const ATTR_ERROR_CODE: u16 = 0x0009;
+const ATTR_LIFETIME: u16 = 0x000D;
+const ATTR_XOR_PEER_ADDRESS: u16 = 0x0012;
+const ATTR_DATA: u16 = 0x0013;
+const ATTR_REALM: u16 = 0x0014;
+const ATTR_NONCE: u16 = 0x0015;
+const ATTR_XOR_RELAYED_ADDRESS: u16 = 0x0016;
+const ATTR_REQUESTED_TRANSPORT: u16 = 0x0019;
+
+/// turn的许可有效期是5分钟，提前刷新
+const PERMISSION_REFRESH: Duration = Duration::from_secs(240);
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// turn服务器和长期凭证，格式 user:password@host:port
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TurnConfig {
+    pub server: String,
+    pub username: String,
+    pub password: String,
+}
+
+impl FromStr for TurnConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (credential, server) = s
+            .rsplit_once('@')
+            .ok_or_else(|| "format user:password@host:port".to_string())?;
+        let (username, password) = credential
+            .split_once(':')
+            .ok_or_else(|| "format user:password@host:port".to_string())?;
+        let mut server = server.trim().to_string();
+        if !server.contains(':') {
+            server.push_str(":3478");
+        }
+        Ok(TurnConfig {
+            server,
+            username: username.to_string(),
+            password: password.to_string(),
+        })
+    }
+}
+
+struct Auth {
+    realm: String,
+    nonce: String,
+    key: [u8; 16],
+}
+
+/// turn客户端，服务端中转不可用时经turn服务器和设备通信，
+/// 双方通过打洞消息交换各自在turn上分配的中继地址
+pub struct TurnClient {
+    config: TurnConfig,
+    server: SocketAddr,
+    socket: UdpSocket,
+    auth: Mutex<Option<Auth>>,
+    relayed: AtomicCell<Option<SocketAddrV4>>,
+    /// 设备虚拟ip -> 设备的中继地址
+    peers: DashMap<Ipv4Addr, SocketAddrV4>,
+    /// 已经创建许可的地址
+    permissions: Mutex<HashMap<Ipv4Addr, Instant>>,
+}
+
+impl TurnClient {
+    pub fn new(config: TurnConfig) -> io::Result<Self> {
+        let server = config
+            .server
+            .to_socket_addrs()?
+            .find(|addr| addr.is_ipv4())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "turn server not found"))?;
+        let socket = std::net::UdpSocket::bind("0.0.0.0:0")?;
+        crate::util::fwmark::mark(&socket)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            config,
+            server,
+            socket: UdpSocket::from_std(socket)?,
+            auth: Mutex::new(None),
+            relayed: AtomicCell::new(None),
+            peers: DashMap::new(),
+            permissions: Mutex::new(HashMap::new()),
+        })
+    }
+    /// 在turn服务器上分配的中继地址
+    pub fn relayed_addr(&self) -> Option<SocketAddrV4> {
+        self.relayed.load()
+    }
+    /// 记录设备的中继地址，并允许对方的数据经turn转发过来
+    pub fn update_peer(&self, virtual_ip: Ipv4Addr, addr: SocketAddrV4) {
+        if self.peers.insert(virtual_ip, addr) != Some(addr) {
+            self.create_permission(*addr.ip());
+        }
+    }
+    pub fn peer_addr(&self, virtual_ip: &Ipv4Addr) -> Option<SocketAddrV4> {
+        self.peers.get(virtual_ip).map(|v| *v)
+    }
+    /// 通过send indication发送到对端
+    pub fn try_send_to(&self, buf: &[u8], peer: SocketAddr) -> io::Result<usize> {
+        if self.relayed.load().is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "turn not allocated",
+            ));
+        }
+        let peer = match peer {
+            SocketAddr::V4(peer) => peer,
+            SocketAddr::V6(_) => {
+                return Err(io::Error::new(io::ErrorKind::Unsupported, "turn ipv6"));
+            }
+        };
+        let mut msg = new_message(SEND_INDICATION, rand::random());
+        add_attr(&mut msg, ATTR_XOR_PEER_ADDRESS, &xor_addr(peer));
+        add_attr(&mut msg, ATTR_DATA, buf);
+        self.socket.try_send_to(&msg, self.server)?;
+        Ok(buf.len())
+    }
+    fn create_permission(&self, ip: Ipv4Addr) {
+        let mut msg = new_message(CREATE_PERMISSION_REQUEST, rand::random());
+        add_attr(
+            &mut msg,
+            ATTR_XOR_PEER_ADDRESS,
+            &xor_addr(SocketAddrV4::new(ip, 0)),
+        );
+        if !self.sign(&mut msg) {
+            return;
+        }
+        if let Err(e) = self.socket.try_send_to(&msg, self.server) {
+            log::warn!("turn创建许可失败 {},{:?}", ip, e);
+            return;
+        }
+        self.permissions.lock().insert(ip, Instant::now());
+    }
+    /// 添加长期凭证，还没有拿到realm和nonce时返回false
+    fn sign(&self, msg: &mut Vec<u8>) -> bool {
+        let auth = self.auth.lock();
+        let auth = match auth.as_ref() {
+            None => return false,
+            Some(auth) => auth,
+        };
+        add_attr(msg, ATTR_USERNAME, self.config.username.as_bytes());
+        add_attr(msg, ATTR_REALM, auth.realm.as_bytes());
+        add_attr(msg, ATTR_NONCE, auth.nonce.as_bytes());
+        // 长度包含MESSAGE-INTEGRITY属性本身
+        let len = (msg.len() - 20 + 24) as u16;
+        msg[2..4].copy_from_slice(&len.to_be_bytes());
+        let mut mac = Hmac::<Sha1>::new_from_slice(&auth.key).unwrap();
+        mac.update(msg);
+        let integrity = mac.finalize().into_bytes();
+        add_attr(msg, ATTR_MESSAGE_INTEGRITY, &integrity);
+        true
+    }
+    fn send_allocate(&self) -> io::Result<()> {
+        let mut msg = new_message(ALLOCATE_REQUEST, rand::random());
+        // udp
+        add_attr(&mut msg, ATTR_REQUESTED_TRANSPORT, &[17, 0, 0, 0]);
+        self.sign(&mut msg);
+        self.socket.try_send_to(&msg, self.server)?;
+        Ok(())
+    }
+    fn send_refresh(&self) -> io::Result<()> {
+        let mut msg = new_message(REFRESH_REQUEST, rand::random());
+        self.sign(&mut msg);
+        self.socket.try_send_to(&msg, self.server)?;
+        Ok(())
+    }
+    /// 更新realm和nonce，返回是否需要重新发起请求
+    fn update_auth(&self, attrs: &Attrs) -> bool {
+        let (realm, nonce) = match (attrs.get(ATTR_REALM), attrs.get(ATTR_NONCE)) {
+            (Some(realm), Some(nonce)) => (
+                String::from_utf8_lossy(realm).to_string(),
+                String::from_utf8_lossy(nonce).to_string(),
+            ),
+            (None, Some(nonce)) => match self.auth.lock().as_ref() {
+                Some(auth) => (
+                    auth.realm.clone(),
+                    String::from_utf8_lossy(nonce).to_string(),
+                ),
+                None => return false,
+            },
+            _ => return false,
+        };
+        let key: [u8; 16] = Md5::digest(format!(
+            "{}:{}:{}",
+            self.config.username, realm, self.config.password
+        ))
+        .into();
+        let mut auth = self.auth.lock();
+        let retry = auth.as_ref().map_or(true, |v| v.nonce != nonce);
+        *auth = Some(Auth { realm, nonce, key });
+        retry
+    }
+    pub async fn start(
+        self: Arc<Self>,
+        mut worker: VntWorker,
+        context: Context,
+        handler: ChannelDataHandler,
+    ) {
+        let mut buf = [0; 4096];
+        let head_reserve = handler.head_reserve;
+        let mut next_allocate = Instant::now();
+        let mut refresh_at: Option<Instant> = None;
+        loop {
+            let now = Instant::now();
+            if self.relayed.load().is_none() && now >= next_allocate {
+                if let Err(e) = self.send_allocate() {
+                    log::warn!("turn分配请求失败 {:?}", e);
+                }
+                next_allocate = now + RETRY_INTERVAL;
+            }
+            if let Some(at) = refresh_at {
+                if now >= at {
+                    if let Err(e) = self.send_refresh() {
+                        log::warn!("turn刷新失败 {:?}", e);
+                    }
+                    refresh_at = Some(now + RETRY_INTERVAL);
+                }
+            }
+            if self.relayed.load().is_some() {
+                let expired: Vec<Ipv4Addr> = self
+                    .permissions
+                    .lock()
+                    .iter()
+                    .filter(|(_, time)| time.elapsed() > PERMISSION_REFRESH)
+                    .map(|(ip, _)| *ip)
+                    .collect();
+                for ip in expired {
+                    self.create_permission(ip);
+                }
+            }
+            tokio::select! {
+                _ = worker.stop_wait() => {
+                    break;
+                }
+                _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+                rs = self.socket.recv_from(&mut buf[head_reserve..]) => {
+                    let len = match rs {
+                        Ok((len, addr)) if addr == self.server => len,
+                        Ok(_) => continue,
+                        Err(e) => {
+                            log::warn!("turn接收失败 {:?}", e);
+                            continue;
+                        }
+                    };
+                    let end = head_reserve + len;
+                    let (typ, attrs) = match parse_message(&buf[head_reserve..end]) {
+                        Some(v) => v,
+                        None => continue,
+                    };
+                    match typ {
+                        DATA_INDICATION => {
+                            let peer = attrs.get(ATTR_XOR_PEER_ADDRESS).and_then(parse_xor_addr);
+                            let data = attrs.get_range(ATTR_DATA);
+                            if let (Some(peer), Some((start, data_end))) = (peer, data) {
+                                //数据移到预留头部之后，和udp通道的处理方式一致
+                                let start = head_reserve + start;
+                                let data_end = head_reserve + data_end;
+                                buf.copy_within(start..data_end, head_reserve);
+                                handler.handle(
+                                    &mut buf,
+                                    head_reserve,
+                                    head_reserve + data_end - start,
+                                    RouteKey::new(false, TURN_ID, SocketAddr::V4(peer)),
+                                    &context,
+                                );
+                            }
+                        }
+                        ALLOCATE_SUCCESS => {
+                            if let Some(relayed) =
+                                attrs.get(ATTR_XOR_RELAYED_ADDRESS).and_then(parse_xor_addr)
+                            {
+                                log::info!("turn中继地址 {}", relayed);
+                                self.relayed.store(Some(relayed));
+                                refresh_at = Some(Instant::now() + lifetime(&attrs) / 2);
+                                let peers: Vec<Ipv4Addr> =
+                                    self.peers.iter().map(|v| *v.value().ip()).collect();
+                                for ip in peers {
+                                    self.create_permission(ip);
+                                }
+                            }
+                        }
+                        ALLOCATE_ERROR => {
+                            let code = error_code(&attrs);
+                            if (code == 401 || code == 438) && self.update_auth(&attrs) {
+                                next_allocate = Instant::now();
+                            } else {
+                                log::warn!("turn分配失败 code={}", code);
+                            }
+                        }
+                        REFRESH_SUCCESS => {
+                            refresh_at = Some(Instant::now() + lifetime(&attrs) / 2);
+                        }
+                        REFRESH_ERROR => {
+                            let code = error_code(&attrs);
+                            if code == 438 && self.update_auth(&attrs) {
+                                refresh_at = Some(Instant::now());
+                            } else {
+                                log::warn!("turn刷新失败 code={}", code);
+                                self.relayed.store(None);
+                                self.permissions.lock().clear();
+                                refresh_at = None;
+                            }
+                        }
+                        CREATE_PERMISSION_ERROR => {
+                            if error_code(&attrs) == 438 && self.update_auth(&attrs) {
+                                //下次循环时重建许可
+                                for (_, time) in self.permissions.lock().iter_mut() {
+                                    *time = Instant::now() - PERMISSION_REFRESH * 2;
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        if self.relayed.load().is_some() {
+            // lifetime为0表示释放分配
+            let mut msg = new_message(REFRESH_REQUEST, rand::random());
+            add_attr(&mut msg, ATTR_LIFETIME, &0u32.to_be_bytes());
+            if self.sign(&mut msg) {
+                let _ = self.socket.try_send_to(&msg, self.server);
+            }
+            self.relayed.store(None);
+        }
+        worker.stop_all();
+    }
+}
+
+fn new_message(typ: u16, tid: [u8; 12]) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(1500);
+    msg.extend_from_slice(&typ.to_be_bytes());
+    msg.extend_from_slice(&[0, 0]);
+    msg.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    msg.extend_from_slice(&tid);
+    msg
+}
+
+fn add_attr(msg: &mut Vec<u8>, typ: u16, value: &[u8]) {
+    msg.extend_from_slice(&typ.to_be_bytes());
+    msg.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    msg.extend_from_slice(value);
+    //属性按4字节对齐
+    while msg.len() % 4 != 0 {
+        msg.push(0);
+    }
+    let len = (msg.len() - 20) as u16;
+    msg[2..4].copy_from_slice(&len.to_be_bytes());
+}
+
+fn xor_addr(addr: SocketAddrV4) -> [u8; 8] {
+    let mut value = [0u8; 8];
+    value[1] = 0x01;
+    value[2..4].copy_from_slice(&(addr.port() ^ (MAGIC_COOKIE >> 16) as u16).to_be_bytes());
+    value[4..8].copy_from_slice(&(u32::from(*addr.ip()) ^ MAGIC_COOKIE).to_be_bytes());
+    value
+}
+
+fn parse_xor_addr(value: &[u8]) -> Option<SocketAddrV4> {
+    if value.len() < 8 || value[1] != 0x01 {
+        return None;
+    }
+    let port = u16::from_be_bytes([value[2], value[3]]) ^ (MAGIC_COOKIE >> 16) as u16;
+    let ip = u32::from_be_bytes(value[4..8].try_into().unwrap()) ^ MAGIC_COOKIE;
+    Some(SocketAddrV4::new(Ipv4Addr::from(ip), port))
+}
+
+/// 属性在消息中的位置 (类型,起始,结束)
+struct Attrs<'a> {
+    msg: &'a [u8],
+    list: Vec<(u16, usize, usize)>,
+}
+
+impl<'a> Attrs<'a> {
+    fn get(&self, typ: u16) -> Option<&'a [u8]> {
+        self.get_range(typ)
+            .map(|(start, end)| &self.msg[start..end])
+    }
+    fn get_range(&self, typ: u16) -> Option<(usize, usize)> {
+        self.list
+            .iter()
+            .find(|(t, _, _)| *t == typ)
+            .map(|(_, start, end)| (*start, *end))
+    }
+}
+
+fn parse_message(msg: &[u8]) -> Option<(u16, Attrs)> {
+    if msg.len() < 20 || msg[4..8] != MAGIC_COOKIE.to_be_bytes() {
+        return None;
+    }
+    let typ = u16::from_be_bytes([msg[0], msg[1]]);
+    let len = u16::from_be_bytes([msg[2], msg[3]]) as usize;
+    if msg.len() < 20 + len {
+        return None;
+    }
+    let mut list = Vec::new();
+    let mut index = 20;
+    while index + 4 <= 20 + len {
+        let attr_typ = u16::from_be_bytes([msg[index], msg[index + 1]]);
+        let attr_len = u16::from_be_bytes([msg[index + 2], msg[index + 3]]) as usize;
+        let start = index + 4;
+        if start + attr_len > 20 + len {
+            break;
+        }
+        list.push((attr_typ, start, start + attr_len));
+        index = start + ((attr_len + 3) & !3);
+    }
+    Some((typ, Attrs { msg, list }))
+}
+
+fn error_code(attrs: &Attrs) -> u16 {
+    match attrs.get(ATTR_ERROR_CODE) {
+        Some(value) if value.len() >= 4 => (value[2] & 0x07) as u16 * 100 + value[3] as u16,
+        _ => 0,
+    }
+}
+
+fn lifetime(attrs: &Attrs) -> Duration {
+    match attrs.get(ATTR_LIFETIME) {
+        Some(value) if value.len() >= 4 => {
+            Duration::from_secs(u32::from_be_bytes(value[..4].try_into().unwrap()) as u64)
+        }
+        // 默认10分钟
+        _ => Duration::from_secs(600),
+    }
+}
+
+/// 打洞消息中的中继地址，没有使用turn时为None
+pub fn turn_addr(ip: u32, port: u32) -> Option<SocketAddrV4> {
+    if ip == 0 || port == 0 || port > u16::MAX as u32 {
+        return None;
+    }
+    Some(SocketAddrV4::new(Ipv4Addr::from(ip), port as u16))
+}
@@ -0,0 +1,172 @@
+use std::collections::HashSet;
+use std::io;
+use std::net::Ipv4Addr;
+
+use parking_lot::RwLock;
+
+use crate::cipher::{Cipher, CipherModel};
+use crate::protocol::NetPacket;
+
+/// auto模式的加密器，同一个密码同时生成aes_gcm和chacha20_poly1305两个加密器，
+/// 按和各设备交换的能力选择使用哪个，没有交换过能力的设备(旧版本或者还没打洞)使用aes_gcm
+pub struct AutoCipher {
+    aes_gcm: Cipher,
+    chacha: Cipher,
+    /// 协商为chacha20_poly1305的设备
+    chacha_peers: RwLock<HashSet<Ipv4Addr>>,
+}
+
+/// 和对端使用的加密模式，双方用各自的能力按同样的规则计算，结果一致，和哪一端先收到能力无关：
+/// 对端只接受其中一种时用那一种，都接受时两边都偏好chacha20_poly1305才用它，否则用aes_gcm
+pub fn negotiate(local: &[String], peer: &[String]) -> CipherModel {
+    let position = |ciphers: &[String], name: &str| ciphers.iter().position(|v| v == name);
+    let prefer_chacha = |ciphers: &[String]| match (
+        position(ciphers, "chacha20_poly1305"),
+        position(ciphers, "aes_gcm"),
+    ) {
+        (Some(chacha), Some(aes)) => chacha < aes,
+        (Some(_), None) => true,
+        _ => false,
+    };
+    let peer_aes = position(peer, "aes_gcm").is_some();
+    let peer_chacha = position(peer, "chacha20_poly1305").is_some();
+    if peer_chacha && (!peer_aes || (prefer_chacha(local) && prefer_chacha(peer))) {
+        CipherModel::ChaCha20Poly1305
+    } else {
+        CipherModel::AesGcm
+    }
+}
+
+impl AutoCipher {
+    pub fn new(aes_gcm: Cipher, chacha: Cipher) -> Self {
+        Self {
+            aes_gcm,
+            chacha,
+            chacha_peers: RwLock::new(HashSet::with_capacity(16)),
+        }
+    }
+    /// 更换密码时沿用之前的协商结果
+    pub fn inherit(&self, other: &AutoCipher) {
+        let peers = other.chacha_peers.read().clone();
+        *self.chacha_peers.write() = peers;
+    }
+    pub fn set_peer_model(&self, peer_ip: Ipv4Addr, model: CipherModel) {
+        if model == CipherModel::ChaCha20Poly1305 {
+            self.chacha_peers.write().insert(peer_ip);
+        } else {
+            self.chacha_peers.write().remove(&peer_ip);
+        }
+    }
+    fn select(&self, peer_ip: &Ipv4Addr) -> (&Cipher, &Cipher) {
+        if self.chacha_peers.read().contains(peer_ip) {
+            (&self.chacha, &self.aes_gcm)
+        } else {
+            (&self.aes_gcm, &self.chacha)
+        }
+    }
+    pub fn keys(&self) -> Vec<(CipherModel, Vec<u8>)> {
+        let mut keys = self.aes_gcm.keys();
+        keys.extend(self.chacha.keys());
+        keys
+    }
+    pub fn decrypt_ipv4<B: AsRef<[u8]> + AsMut<[u8]>>(
+        &self,
+        net_packet: &mut NetPacket<B>,
+    ) -> io::Result<()> {
+        // 对端可能还没收到本端的能力，仍在使用另一种，失败时再试另一种
+        let (first, second) = self.select(&net_packet.source());
+        // 解密失败时数据可能已被修改，需要先备份
+        let data_len = net_packet.data_len();
+        let backup = net_packet.buffer().to_vec();
+        if first.decrypt_ipv4(net_packet).is_ok() {
+            return Ok(());
+        }
+        net_packet.set_data_len(data_len)?;
+        net_packet.buffer_mut().copy_from_slice(&backup);
+        second.decrypt_ipv4(net_packet)
+    }
+    pub fn encrypt_ipv4<B: AsRef<[u8]> + AsMut<[u8]>>(
+        &self,
+        net_packet: &mut NetPacket<B>,
+    ) -> io::Result<()> {
+        self.select(&net_packet.destination())
+            .0
+            .encrypt_ipv4(net_packet)
+    }
+    pub fn check_finger<B: AsRef<[u8]>>(&self, net_packet: &NetPacket<B>) -> io::Result<()> {
+        // 指纹只和token有关，两个加密器相同
+        self.aes_gcm.check_finger(net_packet)
+    }
+}
+
+#[test]
+fn test_negotiate() {
+    let list = |v: &[&str]| v.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+    let aes_first = list(&["aes_gcm", "chacha20_poly1305"]);
+    let chacha_first = list(&["chacha20_poly1305", "aes_gcm"]);
+    // auto和auto，两端结果相同
+    for (a, b) in [
+        (&aes_first, &aes_first),
+        (&aes_first, &chacha_first),
+        (&chacha_first, &chacha_first),
+    ] {
+        assert_eq!(negotiate(a, b), negotiate(b, a));
+    }
+    assert_eq!(
+        negotiate(&chacha_first, &chacha_first),
+        CipherModel::ChaCha20Poly1305
+    );
+    assert_eq!(negotiate(&aes_first, &chacha_first), CipherModel::AesGcm);
+    // 对端显式指定了加密模式
+    assert_eq!(
+        negotiate(&chacha_first, &list(&["aes_gcm"])),
+        CipherModel::AesGcm
+    );
+    assert_eq!(
+        negotiate(&aes_first, &list(&["chacha20_poly1305"])),
+        CipherModel::ChaCha20Poly1305
+    );
+    // 旧版本没有能力
+    assert_eq!(negotiate(&chacha_first, &[]), CipherModel::AesGcm);
+}
+
+#[test]
+fn test_auto_cipher() {
+    use crate::protocol::body::ENCRYPTION_RESERVED;
+    use crate::protocol::{Protocol, Version, MAX_TTL};
+
+    let new = || {
+        AutoCipher::new(
+            Cipher::new_password(CipherModel::AesGcm, Some("password".to_string()), None),
+            Cipher::new_password(
+                CipherModel::ChaCha20Poly1305,
+                Some("password".to_string()),
+                None,
+            ),
+        )
+    };
+    let (a_ip, b_ip) = (Ipv4Addr::new(10, 26, 0, 2), Ipv4Addr::new(10, 26, 0, 3));
+    let (a, b) = (new(), new());
+    let packet = |source: Ipv4Addr, destination: Ipv4Addr| {
+        let buf = vec![0u8; 12 + 100 + ENCRYPTION_RESERVED];
+        let mut packet = NetPacket::new_encrypt(buf).unwrap();
+        packet.set_version(Version::V1);
+        packet.set_protocol(Protocol::IpTurn);
+        packet.first_set_ttl(MAX_TTL);
+        packet.set_source(source);
+        packet.set_destination(destination);
+        packet.payload_mut().fill(0x5a);
+        packet
+    };
+    // a已经协商为chacha20_poly1305，b还没收到能力，双方仍能互相解密
+    a.set_peer_model(b_ip, CipherModel::ChaCha20Poly1305);
+    let mut p = packet(a_ip, b_ip);
+    a.encrypt_ipv4(&mut p).unwrap();
+    assert!(a.chacha.decrypt_ipv4(&mut p.clone()).is_ok());
+    b.decrypt_ipv4(&mut p).unwrap();
+    assert!(p.payload().iter().all(|v| *v == 0x5a));
+    let mut p = packet(b_ip, a_ip);
+    b.encrypt_ipv4(&mut p).unwrap();
+    a.decrypt_ipv4(&mut p).unwrap();
+    assert!(p.payload().iter().all(|v| *v == 0x5a));
+}
@@ -0,0 +1,113 @@
+use std::io;
+
+use chacha20poly1305::aead::generic_array::GenericArray;
+use chacha20poly1305::{AeadInPlace, ChaCha20Poly1305, Key, KeyInit, Nonce, Tag};
+use rand::RngCore;
+
+use crate::cipher::finger::Finger;
+use crate::protocol::{body::SecretBody, body::AES_GCM_ENCRYPTION_RESERVED, NetPacket};
+
+/// 数据体格式和aes_gcm相同，没有aes硬件加速的设备上更快
+#[derive(Clone)]
+pub struct ChaCha20Poly1305Cipher {
+    pub(crate) cipher: ChaCha20Poly1305,
+    pub(crate) key: [u8; 32],
+    pub(crate) finger: Option<Finger>,
+}
+
+impl ChaCha20Poly1305Cipher {
+    pub fn new_256(key: [u8; 32], finger: Option<Finger>) -> Self {
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+        Self {
+            cipher,
+            key,
+            finger,
+        }
+    }
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn decrypt_ipv4<B: AsRef<[u8]> + AsMut<[u8]>>(
+        &self,
+        net_packet: &mut NetPacket<B>,
+    ) -> io::Result<()> {
+        if !net_packet.is_encrypt() {
+            //未加密的数据直接丢弃
+            return Err(io::Error::new(io::ErrorKind::Other, "not encrypt"));
+        }
+        if net_packet.payload().len() < AES_GCM_ENCRYPTION_RESERVED {
+            log::error!("数据异常,长度小于{}", AES_GCM_ENCRYPTION_RESERVED);
+            return Err(io::Error::new(io::ErrorKind::Other, "data err"));
+        }
+        let nonce_raw = nonce_raw(net_packet);
+        let nonce = Nonce::from_slice(&nonce_raw);
+
+        let mut secret_body = SecretBody::new(net_packet.payload_mut(), self.finger.is_some())?;
+        let tag = secret_body.tag();
+        if let Some(finger) = &self.finger {
+            let finger = finger.calculate_finger(&nonce_raw, secret_body.en_body());
+            if &finger != secret_body.finger() {
+                return Err(io::Error::new(io::ErrorKind::Other, "finger err"));
+            }
+        }
+        let tag: Tag = GenericArray::clone_from_slice(tag);
+        let rs = self
+            .cipher
+            .decrypt_in_place_detached(nonce, &[], secret_body.body_mut(), &tag);
+        if let Err(e) = rs {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("解密失败:{}", e),
+            ));
+        }
+        net_packet.set_encrypt_flag(false);
+        net_packet.set_data_len(net_packet.data_len() - AES_GCM_ENCRYPTION_RESERVED)?;
+        Ok(())
+    }
+    /// net_packet 必须预留足够长度
+    /// data_len是有效载荷的长度
+    pub fn encrypt_ipv4<B: AsRef<[u8]> + AsMut<[u8]>>(
+        &self,
+        net_packet: &mut NetPacket<B>,
+    ) -> io::Result<()> {
+        if net_packet.reserve() < AES_GCM_ENCRYPTION_RESERVED {
+            return Err(io::Error::new(io::ErrorKind::Other, "too short"));
+        }
+        let nonce_raw = nonce_raw(net_packet);
+        let nonce = Nonce::from_slice(&nonce_raw);
+        let data_len = net_packet.data_len() + AES_GCM_ENCRYPTION_RESERVED;
+        net_packet.set_data_len(data_len)?;
+        let mut secret_body = SecretBody::new(net_packet.payload_mut(), self.finger.is_some())?;
+        secret_body.set_random(rand::thread_rng().next_u32());
+        match self
+            .cipher
+            .encrypt_in_place_detached(nonce, &[], secret_body.body_mut())
+        {
+            Ok(tag) => {
+                secret_body.set_tag(tag.as_slice())?;
+                if let Some(finger) = &self.finger {
+                    let finger = finger.calculate_finger(&nonce_raw, secret_body.en_body());
+                    secret_body.set_finger(&finger)?;
+                }
+                net_packet.set_encrypt_flag(true);
+                Ok(())
+            }
+            Err(e) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("加密失败:{}", e),
+            )),
+        }
+    }
+}
+
+fn nonce_raw<B: AsRef<[u8]>>(net_packet: &NetPacket<B>) -> [u8; 12] {
+    let mut nonce_raw = [0; 12];
+    nonce_raw[0..4].copy_from_slice(&net_packet.source().octets());
+    nonce_raw[4..8].copy_from_slice(&net_packet.destination().octets());
+    nonce_raw[8] = net_packet.protocol().into();
+    nonce_raw[9] = net_packet.transport_protocol();
+    nonce_raw[10] = net_packet.is_gateway() as u8;
+    nonce_raw[11] = net_packet.source_ttl();
+    nonce_raw
+}
@@ -7,12 +7,28 @@ use crate::cipher::aes_cbc::AesCbcCipher;
 #[cfg(any(feature = "aes_gcm", feature = "server_encrypt"))]
 #[cfg(not(feature = "ring-cipher"))]
 use crate::cipher::aes_gcm_cipher::AesGcmCipher;
+#[cfg(all(
+    any(feature = "aes_gcm", feature = "server_encrypt"),
+    feature = "chacha20_poly1305"
+))]
+use crate::cipher::auto::AutoCipher;
+#[cfg(feature = "chacha20_poly1305")]
+use crate::cipher::chacha20_poly1305::ChaCha20Poly1305Cipher;
 #[cfg(feature = "aes_ecb")]
 #[cfg(any(feature = "openssl-vendored", feature = "openssl"))]
 use crate::cipher::openssl_aes_ecb::AesEcbCipher;
 #[cfg(any(feature = "aes_gcm", feature = "server_encrypt"))]
 #[cfg(feature = "ring-cipher")]
 use crate::cipher::ring_aes_gcm_cipher::AesGcmCipher;
+#[cfg(any(
+    feature = "aes_gcm",
+    feature = "server_encrypt",
+    feature = "aes_cbc",
+    feature = "aes_ecb",
+    feature = "sm4_cbc",
+    feature = "chacha20_poly1305"
+))]
+use crate::cipher::rotating::RotatingCipher;
 #[cfg(feature = "sm4_cbc")]
 use crate::cipher::sm4_cbc::Sm4CbcCipher;
 #[cfg(any(
@@ -20,7 +36,8 @@ use crate::cipher::sm4_cbc::Sm4CbcCipher;
     feature = "server_encrypt",
     feature = "aes_cbc",
     feature = "aes_ecb",
-    feature = "sm4_cbc"
+    feature = "sm4_cbc",
+    feature = "chacha20_poly1305"
 ))]
 use crate::cipher::Finger;
 use crate::protocol::NetPacket;
@@ -29,11 +46,23 @@ use crate::protocol::NetPacket;
     feature = "server_encrypt",
     feature = "aes_cbc",
     feature = "aes_ecb",
-    feature = "sm4_cbc"
+    feature = "sm4_cbc",
+    feature = "chacha20_poly1305"
 ))]
 use sha2::Digest;
 use std::io;
+use std::net::Ipv4Addr;
 use std::str::FromStr;
+#[cfg(any(
+    feature = "aes_gcm",
+    feature = "server_encrypt",
+    feature = "aes_cbc",
+    feature = "aes_ecb",
+    feature = "sm4_cbc",
+    feature = "chacha20_poly1305"
+))]
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum CipherModel {
@@ -45,6 +74,14 @@ pub enum CipherModel {
     AesEcb,
     #[cfg(feature = "sm4_cbc")]
     Sm4Cbc,
+    #[cfg(feature = "chacha20_poly1305")]
+    ChaCha20Poly1305,
+    /// 按和每个设备交换的能力协商使用aes_gcm或chacha20_poly1305
+    #[cfg(all(
+        any(feature = "aes_gcm", feature = "server_encrypt"),
+        feature = "chacha20_poly1305"
+    ))]
+    Auto,
     None,
 }
 
@@ -57,7 +94,8 @@ impl FromStr for CipherModel {
             feature = "server_encrypt",
             feature = "aes_cbc",
             feature = "aes_ecb",
-            feature = "sm4_cbc"
+            feature = "sm4_cbc",
+            feature = "chacha20_poly1305"
         )))]
         return Err(format!("not match '{}', no encrypt", s));
         #[cfg(any(
@@ -65,7 +103,8 @@ impl FromStr for CipherModel {
             feature = "server_encrypt",
             feature = "aes_cbc",
             feature = "aes_ecb",
-            feature = "sm4_cbc"
+            feature = "sm4_cbc",
+            feature = "chacha20_poly1305"
         ))]
         match s.to_lowercase().trim() {
             #[cfg(any(feature = "aes_gcm", feature = "server_encrypt"))]
@@ -76,6 +115,26 @@ impl FromStr for CipherModel {
             "aes_ecb" => Ok(CipherModel::AesEcb),
             #[cfg(feature = "sm4_cbc")]
             "sm4_cbc" => Ok(CipherModel::Sm4Cbc),
+            #[cfg(feature = "chacha20_poly1305")]
+            "chacha20_poly1305" => Ok(CipherModel::ChaCha20Poly1305),
+            #[cfg(all(
+                any(feature = "aes_gcm", feature = "server_encrypt"),
+                feature = "chacha20_poly1305"
+            ))]
+            "auto" => Ok(CipherModel::Auto),
+            // 只编译了一种aead算法时，auto就是这一种
+            #[cfg(all(
+                any(
+                    feature = "aes_gcm",
+                    feature = "server_encrypt",
+                    feature = "chacha20_poly1305"
+                ),
+                not(all(
+                    any(feature = "aes_gcm", feature = "server_encrypt"),
+                    feature = "chacha20_poly1305"
+                ))
+            ))]
+            "auto" => Ok(crate::cipher::hw::fastest_model()),
             _ => {
                 let mut enums = String::new();
                 #[cfg(any(feature = "aes_gcm", feature = "server_encrypt"))]
@@ -86,6 +145,14 @@ impl FromStr for CipherModel {
                 enums.push_str("/aes_ecb");
                 #[cfg(feature = "sm4_cbc")]
                 enums.push_str("/sm4_cbc");
+                #[cfg(feature = "chacha20_poly1305")]
+                enums.push_str("/chacha20_poly1305");
+                #[cfg(any(
+                    feature = "aes_gcm",
+                    feature = "server_encrypt",
+                    feature = "chacha20_poly1305"
+                ))]
+                enums.push_str("/auto");
                 let str = if enums.is_empty() {
                     "no encrypt"
                 } else {
@@ -107,6 +174,24 @@ pub enum Cipher {
     AesEcb(AesEcbCipher),
     #[cfg(feature = "sm4_cbc")]
     Sm4Cbc(Sm4CbcCipher),
+    #[cfg(feature = "chacha20_poly1305")]
+    ChaCha20Poly1305(ChaCha20Poly1305Cipher),
+    /// auto模式，按设备选择加密器
+    #[cfg(all(
+        any(feature = "aes_gcm", feature = "server_encrypt"),
+        feature = "chacha20_poly1305"
+    ))]
+    Auto(Arc<AutoCipher>),
+    /// 可平滑更换密码
+    #[cfg(any(
+        feature = "aes_gcm",
+        feature = "server_encrypt",
+        feature = "aes_cbc",
+        feature = "aes_ecb",
+        feature = "sm4_cbc",
+        feature = "chacha20_poly1305"
+    ))]
+    Rotating(Arc<RotatingCipher>),
     None,
 }
 impl Cipher {
@@ -115,7 +200,8 @@ impl Cipher {
         feature = "server_encrypt",
         feature = "aes_cbc",
         feature = "aes_ecb",
-        feature = "sm4_cbc"
+        feature = "sm4_cbc",
+        feature = "chacha20_poly1305"
     )))]
     pub fn new_password(
         _model: CipherModel,
@@ -129,14 +215,15 @@ impl Cipher {
         feature = "server_encrypt",
         feature = "aes_cbc",
         feature = "aes_ecb",
-        feature = "sm4_cbc"
+        feature = "sm4_cbc",
+        feature = "chacha20_poly1305"
     ))]
     pub fn new_password(
         model: CipherModel,
         password: Option<String>,
         token: Option<String>,
     ) -> Self {
-        let finger = token.map(|token| Finger::new(&token));
+        let finger = token.as_ref().map(|token| Finger::new(token));
         if let Some(password) = password {
             let mut hasher = sha2::Sha256::new();
             hasher.update(password.as_bytes());
@@ -177,6 +264,22 @@ impl Cipher {
                     let aes = Sm4CbcCipher::new_128(key[..16].try_into().unwrap(), finger);
                     Cipher::Sm4Cbc(aes)
                 }
+                #[cfg(feature = "chacha20_poly1305")]
+                CipherModel::ChaCha20Poly1305 => {
+                    Cipher::ChaCha20Poly1305(ChaCha20Poly1305Cipher::new_256(key, finger))
+                }
+                #[cfg(all(
+                    any(feature = "aes_gcm", feature = "server_encrypt"),
+                    feature = "chacha20_poly1305"
+                ))]
+                CipherModel::Auto => Cipher::Auto(Arc::new(AutoCipher::new(
+                    Cipher::new_password(
+                        CipherModel::AesGcm,
+                        Some(password.clone()),
+                        token.clone(),
+                    ),
+                    Cipher::new_password(CipherModel::ChaCha20Poly1305, Some(password), token),
+                ))),
                 CipherModel::None => Cipher::None,
             }
         } else {
@@ -188,7 +291,8 @@ impl Cipher {
         feature = "server_encrypt",
         feature = "aes_cbc",
         feature = "aes_ecb",
-        feature = "sm4_cbc"
+        feature = "sm4_cbc",
+        feature = "chacha20_poly1305"
     )))]
     pub fn new_key(_key: [u8; 32], _token: String) -> io::Result<Self> {
         Err(io::Error::new(io::ErrorKind::Other, "key error"))
@@ -198,7 +302,8 @@ impl Cipher {
         feature = "server_encrypt",
         feature = "aes_cbc",
         feature = "aes_ecb",
-        feature = "sm4_cbc"
+        feature = "sm4_cbc",
+        feature = "chacha20_poly1305"
     ))]
     pub fn new_key(key: [u8; 32], token: String) -> io::Result<Self> {
         let finger = Some(Finger::new(&token));
@@ -229,6 +334,22 @@ impl Cipher {
             Cipher::AesEcb(aes_ecb) => aes_ecb.decrypt_ipv4(net_packet),
             #[cfg(feature = "sm4_cbc")]
             Cipher::Sm4Cbc(sm4_cbc) => sm4_cbc.decrypt_ipv4(net_packet),
+            #[cfg(feature = "chacha20_poly1305")]
+            Cipher::ChaCha20Poly1305(chacha) => chacha.decrypt_ipv4(net_packet),
+            #[cfg(all(
+                any(feature = "aes_gcm", feature = "server_encrypt"),
+                feature = "chacha20_poly1305"
+            ))]
+            Cipher::Auto(auto) => auto.decrypt_ipv4(net_packet),
+            #[cfg(any(
+                feature = "aes_gcm",
+                feature = "server_encrypt",
+                feature = "aes_cbc",
+                feature = "aes_ecb",
+                feature = "sm4_cbc",
+                feature = "chacha20_poly1305"
+            ))]
+            Cipher::Rotating(rotating) => rotating.decrypt_ipv4(net_packet),
             Cipher::None => {
                 if net_packet.is_encrypt() {
                     return Err(io::Error::new(io::ErrorKind::Other, "not key"));
@@ -242,7 +363,8 @@ impl Cipher {
         feature = "server_encrypt",
         feature = "aes_cbc",
         feature = "aes_ecb",
-        feature = "sm4_cbc"
+        feature = "sm4_cbc",
+        feature = "chacha20_poly1305"
     )))]
     pub fn encrypt_ipv4<B: AsRef<[u8]> + AsMut<[u8]>>(
         &self,
@@ -255,7 +377,8 @@ impl Cipher {
         feature = "server_encrypt",
         feature = "aes_cbc",
         feature = "aes_ecb",
-        feature = "sm4_cbc"
+        feature = "sm4_cbc",
+        feature = "chacha20_poly1305"
     ))]
     pub fn encrypt_ipv4<B: AsRef<[u8]> + AsMut<[u8]>>(
         &self,
@@ -270,6 +393,14 @@ impl Cipher {
             Cipher::AesEcb(aes_ecb) => aes_ecb.encrypt_ipv4(net_packet),
             #[cfg(feature = "sm4_cbc")]
             Cipher::Sm4Cbc(sm4_cbc) => sm4_cbc.encrypt_ipv4(net_packet),
+            #[cfg(feature = "chacha20_poly1305")]
+            Cipher::ChaCha20Poly1305(chacha) => chacha.encrypt_ipv4(net_packet),
+            #[cfg(all(
+                any(feature = "aes_gcm", feature = "server_encrypt"),
+                feature = "chacha20_poly1305"
+            ))]
+            Cipher::Auto(auto) => auto.encrypt_ipv4(net_packet),
+            Cipher::Rotating(rotating) => rotating.encrypt_ipv4(net_packet),
             Cipher::None => Ok(()),
         }
     }
@@ -278,7 +409,8 @@ impl Cipher {
         feature = "server_encrypt",
         feature = "aes_cbc",
         feature = "aes_ecb",
-        feature = "sm4_cbc"
+        feature = "sm4_cbc",
+        feature = "chacha20_poly1305"
     )))]
     pub fn check_finger<B: AsRef<[u8]> + AsMut<[u8]>>(
         &self,
@@ -291,7 +423,8 @@ impl Cipher {
         feature = "server_encrypt",
         feature = "aes_cbc",
         feature = "aes_ecb",
-        feature = "sm4_cbc"
+        feature = "sm4_cbc",
+        feature = "chacha20_poly1305"
     ))]
     pub fn check_finger<B: AsRef<[u8]>>(&self, net_packet: &NetPacket<B>) -> io::Result<()> {
         match self {
@@ -319,6 +452,18 @@ impl Cipher {
                 .as_ref()
                 .map(|f| f.check_finger(net_packet))
                 .unwrap_or(Ok(())),
+            #[cfg(feature = "chacha20_poly1305")]
+            Cipher::ChaCha20Poly1305(chacha) => chacha
+                .finger
+                .as_ref()
+                .map(|f| f.check_finger(net_packet))
+                .unwrap_or(Ok(())),
+            #[cfg(all(
+                any(feature = "aes_gcm", feature = "server_encrypt"),
+                feature = "chacha20_poly1305"
+            ))]
+            Cipher::Auto(auto) => auto.check_finger(net_packet),
+            Cipher::Rotating(rotating) => rotating.check_finger(net_packet),
             Cipher::None => Ok(()),
         }
     }
@@ -332,7 +477,164 @@ impl Cipher {
             Cipher::AesEcb(aes_ecb) => Some(aes_ecb.key()),
             #[cfg(feature = "sm4_cbc")]
             Cipher::Sm4Cbc(sm4_cbc) => Some(sm4_cbc.key()),
+            #[cfg(feature = "chacha20_poly1305")]
+            Cipher::ChaCha20Poly1305(chacha) => Some(chacha.key()),
+            #[cfg(all(
+                any(feature = "aes_gcm", feature = "server_encrypt"),
+                feature = "chacha20_poly1305"
+            ))]
+            Cipher::Auto(_) => None,
+            #[cfg(any(
+                feature = "aes_gcm",
+                feature = "server_encrypt",
+                feature = "aes_cbc",
+                feature = "aes_ecb",
+                feature = "sm4_cbc",
+                feature = "chacha20_poly1305"
+            ))]
+            Cipher::Rotating(_) => None,
             Cipher::None => None,
         }
     }
+    /// 单一加密器的加密模式，auto和可更换密码的加密器为None
+    fn model(&self) -> CipherModel {
+        match self {
+            #[cfg(any(feature = "aes_gcm", feature = "server_encrypt"))]
+            Cipher::AesGcm(_) => CipherModel::AesGcm,
+            #[cfg(feature = "aes_cbc")]
+            Cipher::AesCbc(_) => CipherModel::AesCbc,
+            #[cfg(feature = "aes_ecb")]
+            Cipher::AesEcb(_) => CipherModel::AesEcb,
+            #[cfg(feature = "sm4_cbc")]
+            Cipher::Sm4Cbc(_) => CipherModel::Sm4Cbc,
+            #[cfg(feature = "chacha20_poly1305")]
+            Cipher::ChaCha20Poly1305(_) => CipherModel::ChaCha20Poly1305,
+            _ => CipherModel::None,
+        }
+    }
+    /// 数据是否带校验，aead模式或者开启了finger，
+    /// cbc、ecb只有填充校验，用错误的密钥解密时约1/256的概率能通过
+    pub(crate) fn authenticated(&self) -> bool {
+        match self {
+            #[cfg(feature = "aes_cbc")]
+            Cipher::AesCbc(aes_cbc) => aes_cbc.finger.is_some(),
+            #[cfg(feature = "aes_ecb")]
+            Cipher::AesEcb(aes_ecb) => aes_ecb.finger.is_some(),
+            #[cfg(feature = "sm4_cbc")]
+            Cipher::Sm4Cbc(sm4_cbc) => sm4_cbc.finger.is_some(),
+            _ => true,
+        }
+    }
+    /// 当前接受的密钥及其加密模式，更换密码期间包含新旧两个，auto模式包含两种算法的
+    pub fn keys(&self) -> Vec<(CipherModel, Vec<u8>)> {
+        match self {
+            #[cfg(any(
+                feature = "aes_gcm",
+                feature = "server_encrypt",
+                feature = "aes_cbc",
+                feature = "aes_ecb",
+                feature = "sm4_cbc",
+                feature = "chacha20_poly1305"
+            ))]
+            Cipher::Rotating(rotating) => rotating.keys(),
+            #[cfg(all(
+                any(feature = "aes_gcm", feature = "server_encrypt"),
+                feature = "chacha20_poly1305"
+            ))]
+            Cipher::Auto(auto) => auto.keys(),
+            cipher => cipher
+                .key()
+                .map(|key| vec![(cipher.model(), key.to_vec())])
+                .unwrap_or_default(),
+        }
+    }
+    /// 和对端交换能力后调用，auto模式据此选择和该设备使用的算法，其他模式忽略
+    pub fn update_peer(
+        &self,
+        peer_ip: Ipv4Addr,
+        local_ciphers: &[String],
+        peer_ciphers: &[String],
+    ) {
+        match self {
+            #[cfg(all(
+                any(feature = "aes_gcm", feature = "server_encrypt"),
+                feature = "chacha20_poly1305"
+            ))]
+            Cipher::Auto(auto) => auto.set_peer_model(
+                peer_ip,
+                crate::cipher::auto::negotiate(local_ciphers, peer_ciphers),
+            ),
+            #[cfg(any(
+                feature = "aes_gcm",
+                feature = "server_encrypt",
+                feature = "aes_cbc",
+                feature = "aes_ecb",
+                feature = "sm4_cbc",
+                feature = "chacha20_poly1305"
+            ))]
+            Cipher::Rotating(rotating) => {
+                rotating.update_peer(peer_ip, local_ciphers, peer_ciphers)
+            }
+            _ => {
+                let _ = (peer_ip, local_ciphers, peer_ciphers);
+            }
+        }
+    }
+    #[cfg(not(any(
+        feature = "aes_gcm",
+        feature = "server_encrypt",
+        feature = "aes_cbc",
+        feature = "aes_ecb",
+        feature = "sm4_cbc",
+        feature = "chacha20_poly1305"
+    )))]
+    pub fn into_rotating(self) -> Cipher {
+        self
+    }
+    /// 包装成可更换密码的加密器
+    #[cfg(any(
+        feature = "aes_gcm",
+        feature = "server_encrypt",
+        feature = "aes_cbc",
+        feature = "aes_ecb",
+        feature = "sm4_cbc",
+        feature = "chacha20_poly1305"
+    ))]
+    pub fn into_rotating(self) -> Cipher {
+        match self {
+            Cipher::None | Cipher::Rotating(_) => self,
+            cipher => Cipher::Rotating(Arc::new(RotatingCipher::new(cipher))),
+        }
+    }
+    #[cfg(not(any(
+        feature = "aes_gcm",
+        feature = "server_encrypt",
+        feature = "aes_cbc",
+        feature = "aes_ecb",
+        feature = "sm4_cbc",
+        feature = "chacha20_poly1305"
+    )))]
+    pub fn rekey(&self, _cipher: Cipher, _window: Duration) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Other, "not key"))
+    }
+    /// 更换密码，window时间内仍使用旧密钥加密，之后再过window时间不再接受旧密钥
+    #[cfg(any(
+        feature = "aes_gcm",
+        feature = "server_encrypt",
+        feature = "aes_cbc",
+        feature = "aes_ecb",
+        feature = "sm4_cbc",
+        feature = "chacha20_poly1305"
+    ))]
+    pub fn rekey(&self, cipher: Cipher, window: Duration) -> io::Result<()> {
+        match self {
+            Cipher::Rotating(rotating) => {
+                if let Cipher::None = cipher {
+                    return Err(io::Error::new(io::ErrorKind::Other, "not key"));
+                }
+                rotating.rekey(cipher, window)
+            }
+            _ => Err(io::Error::new(io::ErrorKind::Other, "not key")),
+        }
+    }
 }
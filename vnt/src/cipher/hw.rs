@@ -0,0 +1,150 @@
+use std::io;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::cipher::{Cipher, CipherModel};
+use crate::protocol::body::ENCRYPTION_RESERVED;
+use crate::protocol::{NetPacket, Protocol, Version, MAX_TTL};
+
+/// 测速使用的载荷长度，接近常见mtu
+const PAYLOAD_LEN: usize = 1400;
+
+/// 当前构建支持的加密模式
+pub fn models() -> Vec<&'static str> {
+    let mut models = Vec::new();
+    #[cfg(any(feature = "aes_gcm", feature = "server_encrypt"))]
+    models.push("aes_gcm");
+    #[cfg(feature = "chacha20_poly1305")]
+    models.push("chacha20_poly1305");
+    #[cfg(feature = "aes_cbc")]
+    models.push("aes_cbc");
+    #[cfg(feature = "aes_ecb")]
+    models.push("aes_ecb");
+    #[cfg(feature = "sm4_cbc")]
+    models.push("sm4_cbc");
+    models
+}
+
+/// cpu是否支持aes硬件加速(x86的AES-NI，arm的aes扩展)
+/// aes相关的库会在运行时自动使用硬件指令
+pub fn aes_accelerated() -> bool {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        std::arch::is_x86_feature_detected!("aes")
+            && std::arch::is_x86_feature_detected!("pclmulqdq")
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        std::arch::is_aarch64_feature_detected!("aes")
+            && std::arch::is_aarch64_feature_detected!("pmull")
+    }
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        false
+    }
+}
+
+/// 检测到的和加密相关的cpu特性
+pub fn cpu_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if std::arch::is_x86_feature_detected!("aes") {
+            features.push("aes");
+        }
+        if std::arch::is_x86_feature_detected!("pclmulqdq") {
+            features.push("pclmulqdq");
+        }
+        if std::arch::is_x86_feature_detected!("avx2") {
+            features.push("avx2");
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            features.push("neon");
+        }
+        if std::arch::is_aarch64_feature_detected!("aes") {
+            features.push("aes");
+        }
+        if std::arch::is_aarch64_feature_detected!("pmull") {
+            features.push("pmull");
+        }
+    }
+    features
+}
+
+/// 按硬件排序的aead算法，有aes加速时aes_gcm在前，否则chacha20_poly1305在前
+fn preferred() -> [&'static str; 2] {
+    if aes_accelerated() {
+        ["aes_gcm", "chacha20_poly1305"]
+    } else {
+        ["chacha20_poly1305", "aes_gcm"]
+    }
+}
+
+/// 本机最快的aead算法，auto模式会把它排在能力的最前面，
+/// 实际使用哪种由双方的能力决定(见cipher::auto)
+pub fn fastest_model() -> CipherModel {
+    preferred()
+        .iter()
+        .find_map(|name| CipherModel::from_str(name).ok())
+        .unwrap_or(CipherModel::None)
+}
+
+/// 该加密模式下能解密的算法，交换能力时告知对端
+pub fn accepted(model: CipherModel) -> Vec<&'static str> {
+    match model {
+        CipherModel::None => Vec::new(),
+        #[cfg(all(
+            any(feature = "aes_gcm", feature = "server_encrypt"),
+            feature = "chacha20_poly1305"
+        ))]
+        CipherModel::Auto => preferred().to_vec(),
+        #[cfg(any(
+            feature = "aes_gcm",
+            feature = "server_encrypt",
+            feature = "aes_cbc",
+            feature = "aes_ecb",
+            feature = "sm4_cbc",
+            feature = "chacha20_poly1305"
+        ))]
+        model => vec![crate::cipher::keylog::model_name(model)],
+    }
+}
+
+fn packet() -> io::Result<NetPacket<Vec<u8>>> {
+    let mut packet = NetPacket::new_encrypt(vec![0u8; 12 + PAYLOAD_LEN + ENCRYPTION_RESERVED])?;
+    packet.set_version(Version::V1);
+    packet.set_protocol(Protocol::IpTurn);
+    packet.first_set_ttl(MAX_TTL);
+    packet.payload_mut().fill(0x5a);
+    Ok(packet)
+}
+
+/// 测试加密模式的吞吐量，返回(加密,解密)每秒处理的字节数
+pub fn throughput(model: CipherModel, duration: Duration) -> io::Result<(f64, f64)> {
+    let cipher = Cipher::new_password(model, Some("vnt-bench-crypto".to_string()), None);
+    let plain = packet()?;
+    let mut encrypted = plain.clone();
+    cipher.encrypt_ipv4(&mut encrypted)?;
+
+    let mut count = 0u64;
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        let mut packet = plain.clone();
+        cipher.encrypt_ipv4(&mut packet)?;
+        count += 1;
+    }
+    let encrypt = (count * PAYLOAD_LEN as u64) as f64 / start.elapsed().as_secs_f64();
+
+    let mut count = 0u64;
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        let mut packet = encrypted.clone();
+        cipher.decrypt_ipv4(&mut packet)?;
+        count += 1;
+    }
+    let decrypt = (count * PAYLOAD_LEN as u64) as f64 / start.elapsed().as_secs_f64();
+    Ok((encrypt, decrypt))
+}
@@ -0,0 +1,58 @@
+use std::fmt::Write;
+use std::net::Ipv4Addr;
+
+use crate::cipher::{Cipher, CipherModel};
+
+/* 会话密钥日志，用于wireshark解密抓包(dissector见protocol::wireshark)
+   每行一个密钥：<CLIENT|SERVER> <算法> <密钥hex>，#开头的行为注释
+   CLIENT为组网密码的密钥，用于设备之间的数据，更换密码期间有新旧两个；
+   SERVER为和服务端握手协商的密钥，只用于带服务端标志的数据包，每次启动随机生成
+*/
+
+pub fn model_name(model: CipherModel) -> &'static str {
+    match model {
+        #[cfg(any(feature = "aes_gcm", feature = "server_encrypt"))]
+        CipherModel::AesGcm => "aes_gcm",
+        #[cfg(feature = "aes_cbc")]
+        CipherModel::AesCbc => "aes_cbc",
+        #[cfg(feature = "aes_ecb")]
+        CipherModel::AesEcb => "aes_ecb",
+        #[cfg(feature = "sm4_cbc")]
+        CipherModel::Sm4Cbc => "sm4_cbc",
+        #[cfg(feature = "chacha20_poly1305")]
+        CipherModel::ChaCha20Poly1305 => "chacha20_poly1305",
+        #[cfg(all(
+            any(feature = "aes_gcm", feature = "server_encrypt"),
+            feature = "chacha20_poly1305"
+        ))]
+        CipherModel::Auto => "auto",
+        CipherModel::None => "none",
+    }
+}
+
+fn push(out: &mut String, label: &str, model: &str, key: &[u8]) {
+    let _ = write!(out, "{} {} ", label, model);
+    for v in key {
+        let _ = write!(out, "{:02x}", v);
+    }
+    out.push('\n');
+}
+
+pub fn keylog(virtual_ip: Ipv4Addr, client_cipher: &Cipher, server_cipher: &Cipher) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "# vnt {} keylog virtual_ip={}",
+        crate::VNT_VERSION,
+        virtual_ip
+    );
+    //auto模式两种算法的密钥都会导出
+    for (model, key) in client_cipher.keys() {
+        push(&mut out, "CLIENT", model_name(model), &key);
+    }
+    //服务端加密固定为aes_gcm
+    for (_, key) in server_cipher.keys() {
+        push(&mut out, "SERVER", "aes_gcm", &key);
+    }
+    out
+}
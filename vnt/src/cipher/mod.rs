@@ -6,21 +6,40 @@ mod aes_ecb;
 #[cfg(any(feature = "aes_gcm", feature = "server_encrypt"))]
 #[cfg(not(feature = "ring-cipher"))]
 mod aes_gcm_cipher;
+#[cfg(all(
+    any(feature = "aes_gcm", feature = "server_encrypt"),
+    feature = "chacha20_poly1305"
+))]
+pub mod auto;
+#[cfg(feature = "chacha20_poly1305")]
+mod chacha20_poly1305;
 mod cipher;
 #[cfg(any(
     feature = "aes_gcm",
     feature = "server_encrypt",
     feature = "aes_cbc",
     feature = "aes_ecb",
-    feature = "sm4_cbc"
+    feature = "sm4_cbc",
+    feature = "chacha20_poly1305"
 ))]
 mod finger;
+pub mod hw;
+pub mod keylog;
 #[cfg(feature = "aes_ecb")]
 #[cfg(any(feature = "openssl-vendored", feature = "openssl"))]
 mod openssl_aes_ecb;
 #[cfg(any(feature = "aes_gcm", feature = "server_encrypt"))]
 #[cfg(feature = "ring-cipher")]
 mod ring_aes_gcm_cipher;
+#[cfg(any(
+    feature = "aes_gcm",
+    feature = "server_encrypt",
+    feature = "aes_cbc",
+    feature = "aes_ecb",
+    feature = "sm4_cbc",
+    feature = "chacha20_poly1305"
+))]
+mod rotating;
 mod rsa_cipher;
 #[cfg(feature = "sm4_cbc")]
 mod sm4_cbc;
@@ -31,7 +50,8 @@ pub use cipher::CipherModel;
     feature = "server_encrypt",
     feature = "aes_cbc",
     feature = "aes_ecb",
-    feature = "sm4_cbc"
+    feature = "sm4_cbc",
+    feature = "chacha20_poly1305"
 ))]
 pub use finger::Finger;
 pub use rsa_cipher::RsaCipher;
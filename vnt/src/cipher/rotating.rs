@@ -0,0 +1,271 @@
+use crate::cipher::{Cipher, CipherModel};
+use crate::protocol::NetPacket;
+use parking_lot::{Mutex, RwLock};
+use std::collections::HashMap;
+use std::io;
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+/// 支持平滑更换密码的加密器
+/// 更换后的第一个窗口期内仍使用旧密钥加密，同时接受新旧两个密钥；
+/// 第二个窗口期使用新密钥加密，仍接受旧密钥；之后只使用新密钥
+pub struct RotatingCipher {
+    state: RwLock<State>,
+    /// 新旧密钥共存期间，各设备发送数据使用的密钥
+    peers: Mutex<HashMap<Ipv4Addr, PeerKey>>,
+}
+
+/// 设备使用的密钥，true为新密钥
+#[derive(Copy, Clone)]
+enum PeerKey {
+    /// 上次用这个密钥解密成功
+    Known(bool),
+    /// 上次解密失败，下次先尝试这个密钥，失败时再尝试另一个
+    Unsure(bool),
+}
+
+struct State {
+    old: Cipher,
+    new: Option<(Cipher, Instant, Duration)>,
+}
+
+enum Phase {
+    /// 只有一个密钥
+    Single,
+    /// 旧密钥加密，新旧都接受
+    Old,
+    /// 新密钥加密，新旧都接受
+    New,
+    /// 兼容期已过
+    Expired,
+}
+
+impl State {
+    fn phase(&self) -> Phase {
+        match &self.new {
+            None => Phase::Single,
+            Some((_, time, window)) => {
+                let elapsed = time.elapsed();
+                if elapsed < *window {
+                    Phase::Old
+                } else if elapsed < *window * 2 {
+                    Phase::New
+                } else {
+                    Phase::Expired
+                }
+            }
+        }
+    }
+    fn collapse(&mut self) {
+        if let Some((new, _, _)) = self.new.take() {
+            self.old = new;
+        }
+    }
+}
+
+impl RotatingCipher {
+    pub fn new(cipher: Cipher) -> Self {
+        Self {
+            state: RwLock::new(State {
+                old: cipher,
+                new: None,
+            }),
+            peers: Mutex::new(HashMap::new()),
+        }
+    }
+    /// 设置新密钥，window为每个阶段的时长
+    pub fn rekey(&self, cipher: Cipher, window: Duration) -> io::Result<()> {
+        //用错误的密钥解密cbc、ecb时约1/256的概率能通过填充校验，需要finger校验数据
+        if !cipher.authenticated() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "rekey requires finger for aes_cbc/aes_ecb/sm4_cbc",
+            ));
+        }
+        let mut state = self.state.write();
+        if let Phase::New | Phase::Expired = state.phase() {
+            // 上一次更换已经切到新密钥了，以新密钥作为旧密钥
+            state.collapse();
+        }
+        #[cfg(all(
+            any(feature = "aes_gcm", feature = "server_encrypt"),
+            feature = "chacha20_poly1305"
+        ))]
+        if let (Cipher::Auto(new), Cipher::Auto(old)) = (&cipher, &state.old) {
+            new.inherit(old);
+        }
+        state.new = Some((cipher, Instant::now(), window));
+        self.peers.lock().clear();
+        Ok(())
+    }
+    /// 是否处于新旧密钥共存期
+    pub fn rotating(&self) -> bool {
+        match self.state.read().phase() {
+            Phase::Old | Phase::New => true,
+            Phase::Single | Phase::Expired => false,
+        }
+    }
+    /// 当前接受的密钥，旧密钥在前
+    pub fn keys(&self) -> Vec<(CipherModel, Vec<u8>)> {
+        let state = self.state.read();
+        let mut keys = Vec::new();
+        if !matches!(state.phase(), Phase::Expired) {
+            keys.extend(state.old.keys());
+        }
+        if let Some((new, _, _)) = &state.new {
+            keys.extend(new.keys());
+        }
+        keys
+    }
+    pub fn update_peer(
+        &self,
+        peer_ip: Ipv4Addr,
+        local_ciphers: &[String],
+        peer_ciphers: &[String],
+    ) {
+        let state = self.state.read();
+        state.old.update_peer(peer_ip, local_ciphers, peer_ciphers);
+        if let Some((new, _, _)) = &state.new {
+            new.update_peer(peer_ip, local_ciphers, peer_ciphers);
+        }
+    }
+    fn expire(&self) {
+        let mut state = self.state.write();
+        if let Phase::Expired = state.phase() {
+            state.collapse();
+            self.peers.lock().clear();
+        }
+    }
+    pub fn decrypt_ipv4<B: AsRef<[u8]> + AsMut<[u8]>>(
+        &self,
+        net_packet: &mut NetPacket<B>,
+    ) -> io::Result<()> {
+        let state = self.state.read();
+        let (phase, new) = match (state.phase(), &state.new) {
+            (phase @ (Phase::Old | Phase::New), Some((new, _, _))) => (phase, new),
+            (Phase::Expired, _) => {
+                drop(state);
+                self.expire();
+                return self.decrypt_ipv4(net_packet);
+            }
+            _ => return state.old.decrypt_ipv4(net_packet),
+        };
+        let cipher = |is_new: bool| if is_new { new } else { &state.old };
+        let source = net_packet.source();
+        //没有记录时认为发送方和本机处于同一阶段
+        let peer = self
+            .peers
+            .lock()
+            .get(&source)
+            .copied()
+            .unwrap_or(PeerKey::Known(matches!(phase, Phase::New)));
+        let (rs, peer) = match peer {
+            PeerKey::Known(is_new) => {
+                //解密失败时数据可能已被修改，不备份直接丢弃这个包，
+                //发送方切换密钥后只会丢失一个包，下一个包再尝试另一个密钥
+                let rs = cipher(is_new).decrypt_ipv4(net_packet);
+                match rs {
+                    Ok(_) => (rs, PeerKey::Known(is_new)),
+                    Err(_) => (rs, PeerKey::Unsure(!is_new)),
+                }
+            }
+            PeerKey::Unsure(is_new) => {
+                let data_len = net_packet.data_len();
+                let backup = net_packet.buffer().to_vec();
+                if cipher(is_new).decrypt_ipv4(net_packet).is_ok() {
+                    (Ok(()), PeerKey::Known(is_new))
+                } else {
+                    net_packet.set_data_len(data_len)?;
+                    net_packet.buffer_mut().copy_from_slice(&backup);
+                    let rs = cipher(!is_new).decrypt_ipv4(net_packet);
+                    match rs {
+                        Ok(_) => (rs, PeerKey::Known(!is_new)),
+                        Err(_) => (rs, PeerKey::Unsure(is_new)),
+                    }
+                }
+            }
+        };
+        self.peers.lock().insert(source, peer);
+        rs
+    }
+    pub fn encrypt_ipv4<B: AsRef<[u8]> + AsMut<[u8]>>(
+        &self,
+        net_packet: &mut NetPacket<B>,
+    ) -> io::Result<()> {
+        let state = self.state.read();
+        match (state.phase(), &state.new) {
+            (Phase::New, Some((new, _, _))) => new.encrypt_ipv4(net_packet),
+            (Phase::Expired, _) => {
+                drop(state);
+                self.expire();
+                self.encrypt_ipv4(net_packet)
+            }
+            _ => state.old.encrypt_ipv4(net_packet),
+        }
+    }
+    pub fn check_finger<B: AsRef<[u8]>>(&self, net_packet: &NetPacket<B>) -> io::Result<()> {
+        let state = self.state.read();
+        match (state.phase(), &state.new) {
+            (Phase::Old | Phase::New, Some((new, _, _))) => state
+                .old
+                .check_finger(net_packet)
+                .or_else(|_| new.check_finger(net_packet)),
+            (Phase::Expired, Some((new, _, _))) => new.check_finger(net_packet),
+            _ => state.old.check_finger(net_packet),
+        }
+    }
+}
+
+#[cfg(any(feature = "aes_gcm", feature = "server_encrypt"))]
+#[test]
+fn test_rotating_peer_key() {
+    use crate::protocol::body::ENCRYPTION_RESERVED;
+    use crate::protocol::{Protocol, Version, MAX_TTL};
+    let cipher = |password: &str| {
+        Cipher::new_password(CipherModel::AesGcm, Some(password.to_string()), None)
+    };
+    let (peer_ip, local_ip) = (Ipv4Addr::new(10, 26, 0, 2), Ipv4Addr::new(10, 26, 0, 3));
+    let packet = |cipher: &Cipher| {
+        let buf = vec![0u8; 12 + 100 + ENCRYPTION_RESERVED];
+        let mut packet = NetPacket::new_encrypt(buf).unwrap();
+        packet.set_version(Version::V1);
+        packet.set_protocol(Protocol::IpTurn);
+        packet.first_set_ttl(MAX_TTL);
+        packet.set_source(peer_ip);
+        packet.set_destination(local_ip);
+        packet.payload_mut().fill(0x5a);
+        cipher.encrypt_ipv4(&mut packet).unwrap();
+        packet
+    };
+    let rotating = RotatingCipher::new(cipher("password1"));
+    rotating
+        .rekey(cipher("password2"), Duration::from_secs(60))
+        .unwrap();
+    let (old, new) = (cipher("password1"), cipher("password2"));
+    // 第一个窗口期先尝试旧密钥
+    let mut p = packet(&old);
+    rotating.decrypt_ipv4(&mut p).unwrap();
+    assert!(p.payload().iter().all(|v| *v == 0x5a));
+    // 对端切换到新密钥后丢弃一个包，之后使用新密钥
+    assert!(rotating.decrypt_ipv4(&mut packet(&new)).is_err());
+    let mut p = packet(&new);
+    rotating.decrypt_ipv4(&mut p).unwrap();
+    assert!(p.payload().iter().all(|v| *v == 0x5a));
+    rotating.decrypt_ipv4(&mut packet(&new)).unwrap();
+    // 不确定时两个密钥都尝试
+    assert!(rotating.decrypt_ipv4(&mut packet(&old)).is_err());
+    let mut p = packet(&new);
+    rotating.decrypt_ipv4(&mut p).unwrap();
+    assert!(p.payload().iter().all(|v| *v == 0x5a));
+    #[cfg(feature = "aes_cbc")]
+    {
+        let cbc = |finger: Option<String>| {
+            Cipher::new_password(CipherModel::AesCbc, Some("password".to_string()), finger)
+        };
+        let rotating = RotatingCipher::new(cbc(None));
+        assert!(rotating.rekey(cbc(None), Duration::from_secs(60)).is_err());
+        let token = Some("token".to_string());
+        let rotating = RotatingCipher::new(cbc(token.clone()));
+        assert!(rotating.rekey(cbc(token), Duration::from_secs(60)).is_ok());
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::io;
 use std::net::UdpSocket;
-use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 use std::net::{TcpListener, TcpStream};
 use std::sync::Arc;
 use std::time::Duration;
@@ -9,35 +9,73 @@ use std::time::Duration;
 use crossbeam_utils::atomic::AtomicCell;
 use parking_lot::{Mutex, RwLock};
 use rand::Rng;
-use tokio::sync::mpsc::channel;
+use tokio::sync::mpsc::{channel, Sender};
 
+use crate::bridge::Bridge;
+use crate::channel::broadcast::{BroadcastPolicy, BroadcastStats};
+use crate::channel::capability::Capabilities;
 use crate::channel::channel::{Channel, Context};
+use crate::channel::ice::{self, CandidatePair};
 use crate::channel::idle::Idle;
-use crate::channel::punch::{NatInfo, Punch, PunchModel};
+use crate::channel::malformed::Malformed;
+use crate::channel::mem_limit::MemoryUsage;
+use crate::channel::path_policy::{PathPolicy, PathPolicyTable};
+use crate::channel::punch::{NatInfo, NatType, Punch, PunchModel};
+use crate::channel::punch_record::PunchRecord;
+use crate::channel::quarantine::AuthFailure;
+use crate::channel::rendezvous::Rendezvous;
+use crate::channel::self_test::{SelfTest, SelfTestStatus, FAILURE_THRESHOLD};
 use crate::channel::sender::ChannelSender;
+use crate::channel::turn::{TurnClient, TurnConfig};
 use crate::channel::{Route, RouteKey};
-use crate::cipher::{Cipher, CipherModel, RsaCipher};
+use crate::cipher::{keylog, Cipher, CipherModel, RsaCipher};
 use crate::core::status::VntStatusManger;
 use crate::error::Error;
+use crate::event::{CounterSnapshot, Event, EventKind, LivenessSnapshot};
 use crate::external_route::{AllowExternalRoute, ExternalRoute};
 use crate::handle::handshake_handler::HandshakeEnum;
 use crate::handle::recv_handler::ChannelDataHandler;
 use crate::handle::registration_handler::{RegResponse, ReqEnum};
+use crate::handle::tun_tap::bridge::MacTable;
 #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
 use crate::handle::tun_tap::tap_handler;
 use crate::handle::tun_tap::tun_handler;
 use crate::handle::{
     handshake_handler, heartbeat_handler, punch_handler, registration_handler, ConnectStatus,
-    CurrentDeviceInfo, PeerDeviceInfo,
+    CurrentDeviceInfo, PeerDeviceInfo, PeerDeviceStatus, ServerLost,
 };
 use crate::igmp_server::IgmpServer;
+use crate::kill_switch::KillSwitch;
+use crate::maintenance::Maintenance;
+use crate::masquerade::Masquerade;
 use crate::nat::NatTest;
+use crate::proto::message::HandshakeResponse;
+use crate::quota::Quota;
+use crate::remote_config::{format_cidr, RemoteConfig, RemoteConfigHandler, DEFAULT_KEEPALIVE};
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+use crate::route_conflict;
+use crate::route_conflict::RouteConflictPolicy;
+use crate::split_dns::SplitDns;
+use crate::split_tunnel::{SplitMode, SplitTunnel};
+use crate::stream::{StreamManager, VntListener, VntStream};
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+use crate::subnet_alias;
 use crate::tun_tap_device;
 use crate::tun_tap_device::{DeviceReader, DeviceWriter};
+use crate::util::backoff::{self, Backoff};
+use crate::util::mimicry::Mimicry;
+use crate::util::proxy::ProxyConfig;
 
 pub mod status;
 pub mod sync;
 
+/// 选择服务端时单个服务端的测量超时
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+/// 最多测量这么多个其他服务端
+const MAX_PROBE_SERVERS: usize = 8;
+/// 其他服务端的延迟要低这么多才切换
+const SWITCH_MARGIN: Duration = Duration::from_millis(20);
+
 #[derive(Clone)]
 pub struct Vnt {
     config: Config,
@@ -52,6 +90,20 @@ pub struct Vnt {
     nat_test: NatTest,
     connect_status: Arc<AtomicCell<ConnectStatus>>,
     peer_nat_info_map: Arc<RwLock<HashMap<Ipv4Addr, NatInfo>>>,
+    kill_switch: Arc<Mutex<Option<KillSwitch>>>,
+    split_tunnel: Arc<Mutex<Option<SplitTunnel>>>,
+    split_dns: Arc<Mutex<Option<SplitDns>>>,
+    bridge: Arc<Mutex<Option<Bridge>>>,
+    masquerade: Arc<Mutex<Option<Masquerade>>>,
+    remote_config: RemoteConfigHandler,
+    quota: Arc<AtomicCell<Option<Quota>>>,
+    maintenance: Arc<Mutex<Option<Maintenance>>>,
+    cone_sender: Sender<(Ipv4Addr, NatInfo)>,
+    symmetric_sender: Sender<(Ipv4Addr, NatInfo)>,
+    stream_manager: StreamManager,
+    client_cipher: Cipher,
+    server_cipher: Cipher,
+    self_test: Option<Arc<SelfTest>>,
 }
 
 pub struct VntUtil {
@@ -60,18 +112,116 @@ pub struct VntUtil {
     main_tcp_channel: Option<TcpStream>,
     response: Option<RegResponse>,
     iface: Option<(DeviceWriter, DeviceReader)>,
+    device_name: String,
     server_cipher: Cipher,
     rsa_cipher: Option<RsaCipher>,
+    kill_switch: Option<KillSwitch>,
+    split_tunnel: Option<SplitTunnel>,
+    split_dns: Option<SplitDns>,
+    bridge: Option<Bridge>,
+    masquerade: Option<Masquerade>,
+    /// 只在第一次握手成功后选择一次服务端
+    server_selected: bool,
+    /// 服务端同时监听的其他地址，连接中断时轮流尝试
+    listen_addresses: Vec<String>,
+    /// 虚拟网段和本机已有网段的冲突，创建网卡时检查
+    subnet_collisions: Vec<String>,
+}
+
+/// 保存在全局变量中的设置，socket创建时读取，同一进程中的所有实例共用
+#[derive(Clone, Debug, PartialEq)]
+struct ProcessSettings {
+    fwmark: u32,
+    bind_device: Option<String>,
+    proxy: Option<ProxyConfig>,
+    mimicry: Option<Mimicry>,
+    resolver: Option<SocketAddr>,
+    seccomp: bool,
+}
+
+/// 最近一次启动的实例使用的设置
+static PROCESS_SETTINGS: Mutex<Option<ProcessSettings>> = parking_lot::const_mutex(None);
+
+impl ProcessSettings {
+    fn new(config: &Config) -> ProcessSettings {
+        let fwmark = if config.route_table != 0 {
+            config.route_table
+        } else if config.kill_switch {
+            crate::kill_switch::DEFAULT_MARK
+        } else {
+            0
+        };
+        ProcessSettings {
+            fwmark: if cfg!(target_os = "linux") { fwmark } else { 0 },
+            bind_device: config.bind_device.clone(),
+            proxy: config.proxy.clone(),
+            mimicry: config.mimicry,
+            resolver: config.resolver,
+            seccomp: config.seccomp,
+        }
+    }
+    fn apply(&self) -> io::Result<()> {
+        #[cfg(target_os = "linux")]
+        crate::util::fwmark::set(self.fwmark);
+        crate::util::fwmark::set_device(self.bind_device.clone())?;
+        crate::util::proxy::set(self.proxy.clone());
+        crate::util::mimicry::set(self.mimicry);
+        crate::util::resolve::set(self.resolver);
+        *PROCESS_SETTINGS.lock() = Some(self.clone());
+        Ok(())
+    }
+    /// 和other不同的设置项
+    fn diff(&self, other: &ProcessSettings) -> Vec<&'static str> {
+        let mut diff = Vec::new();
+        if self.fwmark != other.fwmark {
+            diff.push("route_table/kill_switch");
+        }
+        if self.bind_device != other.bind_device {
+            diff.push("bind_device");
+        }
+        if self.proxy != other.proxy {
+            diff.push("proxy");
+        }
+        if self.mimicry != other.mimicry {
+            diff.push("mimicry");
+        }
+        if self.resolver != other.resolver {
+            diff.push("resolver");
+        }
+        if self.seccomp != other.seccomp {
+            diff.push("seccomp");
+        }
+        diff
+    }
+}
+
+/// fwmark、绑定网卡、代理、伪装、dns服务器和seccomp是进程内共用的，
+/// 在已有实例运行时启动另一个实例(组网)前检查，不同时会改变运行中实例的设置，返回错误
+pub fn check_process_settings(config: &Config) -> io::Result<()> {
+    let current = match PROCESS_SETTINGS.lock().clone() {
+        Some(current) => current,
+        None => return Ok(()),
+    };
+    let diff = ProcessSettings::new(config).diff(&current);
+    if diff.is_empty() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} must be the same as the running instance, they are shared by the whole process",
+                diff.join(",")
+            ),
+        ))
+    }
 }
 
 impl VntUtil {
     pub fn new(config: Config) -> io::Result<VntUtil> {
-        let address: SocketAddr = format!("[::]:{}", config.port).parse().unwrap();
+        ProcessSettings::new(&config).apply()?;
         //单个udp用同步的性能更好，但是代理和多端口监听用异步更方便，这里将两者结合起来
-        let socket = socket2::Socket::new(socket2::Domain::IPV6, socket2::Type::DGRAM, None)?;
-        socket.set_only_v6(false)?;
-        socket.bind(&address.into())?;
-        let main_channel: UdpSocket = socket.into();
+        let main_channel = bind_udp(&config)?;
+        log::info!("本地udp监听地址 {:?}", main_channel.local_addr()?);
         main_channel.set_write_timeout(Some(Duration::from_secs(5)))?;
         main_channel.set_read_timeout(Some(Duration::from_secs(2)))?;
         let server_cipher = if config.server_encrypt {
@@ -87,14 +237,39 @@ impl VntUtil {
             main_tcp_channel: None,
             response: None,
             iface: None,
+            device_name: String::new(),
             server_cipher,
             rsa_cipher: None,
+            kill_switch: None,
+            split_tunnel: None,
+            split_dns: None,
+            bridge: None,
+            masquerade: None,
+            server_selected: false,
+            listen_addresses: Vec::new(),
+            subnet_collisions: Vec::new(),
         })
     }
-    ///链接
+    ///链接，每次都重新解析服务端域名，ddns的地址变化后重连能连上新地址
     pub fn connect(&mut self) -> io::Result<()> {
-        if self.config.tcp {
-            let tcp = TcpStream::connect(self.config.server_address)?;
+        match crate::util::resolve::lookup_one(&self.config.server_address_str) {
+            Ok(addr) => {
+                if addr != self.config.server_address {
+                    log::info!(
+                        "服务端{}地址变化 {}->{}",
+                        self.config.server_address_str,
+                        self.config.server_address,
+                        addr
+                    );
+                    self.config.server_address = addr;
+                }
+            }
+            Err(e) => {
+                log::warn!("服务端地址解析失败 {} {:?}", self.config.server_address_str, e);
+            }
+        }
+        if self.config.tcp || self.config.tcp_control {
+            let tcp = crate::util::proxy::server_connect(&self.config.server_address, None)?;
             tcp.set_read_timeout(Some(Duration::from_secs(10)))?;
             let _ = self.main_tcp_channel.insert(tcp);
         }
@@ -103,15 +278,91 @@ impl VntUtil {
 
     ///握手 用于获取公钥
     pub fn handshake(&mut self) -> Result<Option<RsaCipher>, HandshakeEnum> {
-        let rsa_cipher = handshake_handler::handshake(
+        let (rsa_cipher, response) = handshake_handler::handshake(
             &self.main_channel,
             self.main_tcp_channel.as_mut(),
             self.config.server_address,
             self.config.server_encrypt,
         )?;
+        if !self.server_selected {
+            self.server_selected = true;
+            if let Some((address, addr)) = self.select_server(&response) {
+                //换到延迟更低的服务端重新握手
+                self.config.server_address_str = address;
+                self.config.server_address = addr;
+                self.main_tcp_channel = None;
+                self.connect()
+                    .map_err(|e| HandshakeEnum::Other(format!("connect {}", e)))?;
+                return self.handshake();
+            }
+        }
+        self.listen_addresses = response.listen_addresses.to_vec();
         self.rsa_cipher = rsa_cipher.clone();
         Ok(rsa_cipher)
     }
+    /// 服务端给出了同一集群的其他服务端时，测量延迟并选择最近的，
+    /// tcp和代理模式下udp的延迟不代表实际的路径，不做选择
+    fn select_server(&self, response: &HandshakeResponse) -> Option<(String, SocketAddr)> {
+        if response.servers.is_empty()
+            || response.pinned
+            || self.config.tcp
+            || self.config.proxy.is_some()
+        {
+            return None;
+        }
+        let current = match handshake_handler::probe_rtt(self.config.server_address, PROBE_TIMEOUT)
+        {
+            Ok(rt) => rt,
+            Err(e) => {
+                log::warn!("测量服务端{}延迟失败 {:?}", self.config.server_address, e);
+                return None;
+            }
+        };
+        let mut best: Option<(String, SocketAddr, Duration)> = None;
+        for server in response.servers.iter().take(MAX_PROBE_SERVERS) {
+            let addr = match crate::util::resolve::lookup_one(&server.address) {
+                Ok(addr) => addr,
+                _ => {
+                    log::warn!("服务端地址解析失败 {}", server.address);
+                    continue;
+                }
+            };
+            if addr == self.config.server_address {
+                continue;
+            }
+            match handshake_handler::probe_rtt(addr, PROBE_TIMEOUT) {
+                Ok(rt) => {
+                    log::info!(
+                        "服务端{}({}) region={} rtt={:?}",
+                        server.address,
+                        addr,
+                        server.region,
+                        rt
+                    );
+                    if best.as_ref().map_or(true, |(_, _, best_rt)| rt < *best_rt) {
+                        best = Some((server.address.clone(), addr, rt));
+                    }
+                }
+                Err(e) => {
+                    log::info!("服务端{}无响应 {:?}", server.address, e);
+                }
+            }
+        }
+        let (address, addr, rt) = best?;
+        //差距不大时留在当前服务端，避免每次启动在延迟接近的服务端之间来回切换
+        if rt + SWITCH_MARGIN >= current {
+            return None;
+        }
+        log::info!(
+            "选择延迟更低的服务端{}({}) rtt={:?},原服务端{} rtt={:?}",
+            address,
+            addr,
+            rt,
+            self.config.server_address,
+            current
+        );
+        Some((address, addr))
+    }
     /// 加密握手 用于同步密钥
     pub fn secret_handshake(&mut self) -> Result<(), HandshakeEnum> {
         handshake_handler::secret_handshake(
@@ -135,6 +386,7 @@ impl VntUtil {
             self.config.name.clone(),
             self.config.ip.unwrap_or(Ipv4Addr::UNSPECIFIED),
             self.config.password.is_some(),
+            &self.config.advertise_routes,
         ) {
             Ok(res) => {
                 let _ = self.response.insert(res.clone());
@@ -162,25 +414,88 @@ impl VntUtil {
         let device_type = if self.config.tap {
             {
                 //删除tun网卡避免ip冲突，因为非正常退出会保留网卡
-                tun_tap_device::delete_device(tun_tap_device::DeviceType::Tun);
+                tun_tap_device::delete_device(
+                    tun_tap_device::DeviceType::Tun,
+                    self.config.device_name.as_deref(),
+                );
             }
             tun_tap_device::DeviceType::Tap
         } else {
             {
                 //删除tap网卡避免ip冲突，非正常退出会保留网卡
-                tun_tap_device::delete_device(tun_tap_device::DeviceType::Tap);
+                tun_tap_device::delete_device(
+                    tun_tap_device::DeviceType::Tap,
+                    self.config.device_name.as_deref(),
+                );
             }
             tun_tap_device::DeviceType::Tun
         };
-        let mtu = match self.config.mtu {
+        #[cfg(target_os = "linux")]
+        if self.config.helper_socket.is_some() {
+            //特权辅助进程只负责网卡和路由，这些功能仍需要root权限
+            let unsupported = [
+                ("kill_switch", self.config.kill_switch),
+                ("split_mode", self.config.split_mode.is_some()),
+                ("dns_server", self.config.dns_server.is_some()),
+                ("bridge", self.config.bridge.is_some()),
+                ("masquerade", !self.config.masquerade_routes.is_empty()),
+            ];
+            if let Some((name, _)) = unsupported.iter().find(|(_, enabled)| *enabled) {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("{} requires root, not available with helper_socket", name),
+                ));
+            }
+        }
+        let mtu = self.config.device_mtu();
+        let system_routes = route_conflict::system_routes(
+            self.config.device_name.as_deref().unwrap_or_default(),
+            response.virtual_gateway,
+        );
+        //虚拟网段和局域网冲突时，配置了映射网段则网卡使用映射后的地址
+        let (address, gateway) = match self.config.subnet_alias {
+            Some((alias, alias_mask)) => {
+                if alias_mask != u32::from(response.virtual_netmask) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "subnet_alias must be /{} like the virtual network",
+                            u32::from(response.virtual_netmask).count_ones()
+                        ),
+                    ));
+                }
+                let collisions = subnet_alias::collisions(alias, alias_mask, &system_routes);
+                if !collisions.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AddrInUse,
+                        format!(
+                            "subnet_alias {} overlaps {:?}",
+                            format_cidr(alias, alias_mask),
+                            collisions
+                        ),
+                    ));
+                }
+                let alias = Ipv4Addr::from(alias);
+                (
+                    subnet_alias::map(response.virtual_ip, alias, response.virtual_netmask),
+                    subnet_alias::map(response.virtual_gateway, alias, response.virtual_netmask),
+                )
+            }
             None => {
-                if self.config.password.is_none() {
-                    1450
-                } else {
-                    1410
+                self.subnet_collisions = subnet_alias::collisions(
+                    response.virtual_ip.into(),
+                    response.virtual_netmask.into(),
+                    &system_routes,
+                );
+                for x in &self.subnet_collisions {
+                    log::error!(
+                        "虚拟网段{}和本机网段{}冲突，可以使用subnet_alias映射到其他网段",
+                        format_cidr(response.virtual_ip.into(), response.virtual_netmask.into()),
+                        x
+                    );
                 }
+                (response.virtual_ip, response.virtual_gateway)
             }
-            Some(mtu) => mtu,
         };
         let in_ips = self
             .config
@@ -188,18 +503,90 @@ impl VntUtil {
             .iter()
             .map(|(dest, mask, _)| (Ipv4Addr::from(*dest & *mask), Ipv4Addr::from(*mask)))
             .collect::<Vec<(Ipv4Addr, Ipv4Addr)>>();
+        let in_ips = route_conflict::resolve(in_ips, &system_routes, self.config.route_conflict);
 
         let (device_writer, device_reader, driver_info) = tun_tap_device::create_device(
             device_type,
-            response.virtual_ip,
+            self.config.device_name.as_deref(),
+            address,
             response.virtual_netmask,
-            response.virtual_gateway,
+            gateway,
             in_ips,
             mtu,
+            #[cfg(target_os = "windows")]
+            self.config.metric,
+            #[cfg(target_os = "windows")]
+            self.config.private_network,
+            #[cfg(target_os = "linux")]
+            self.config.route_table,
+            #[cfg(target_os = "linux")]
+            self.config.multi_queue,
+            #[cfg(target_os = "linux")]
+            self.config.tun_owner,
+            #[cfg(target_os = "linux")]
+            self.config.tun_group,
+            #[cfg(target_os = "linux")]
+            self.config.helper_socket.as_deref(),
         )?;
         let _ = self.iface.insert((device_writer, device_reader));
+        self.device_name = driver_info.name.clone();
+        if self.config.kill_switch {
+            let _ = self
+                .kill_switch
+                .insert(KillSwitch::enable(&driver_info.name)?);
+        }
+        if let Some(mode) = self.config.split_mode {
+            #[cfg(target_os = "linux")]
+            if self.config.route_table == 0 {
+                //需要策略路由才能让打了标记的流量绕过vnt的路由
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "split tunnel requires route_table",
+                ));
+            }
+            let _ = self.split_tunnel.insert(SplitTunnel::new(
+                mode,
+                self.config.split_apps.clone(),
+                &driver_info.name,
+            )?);
+        }
+        if let Some(dns_server) = self.config.dns_server {
+            if !self.config.dns_domains.is_empty() {
+                let _ = self.split_dns.insert(SplitDns::enable(
+                    &driver_info.name,
+                    dns_server,
+                    &self.config.dns_domains,
+                )?);
+            }
+        }
+        if let Some(nic) = &self.config.bridge {
+            let _ = self.bridge.insert(Bridge::enable(&driver_info.name, nic)?);
+        }
+        if !self.config.masquerade_routes.is_empty() {
+            let _ = self.masquerade.insert(Masquerade::enable(
+                &driver_info.name,
+                address,
+                response.virtual_netmask,
+                &self.config.masquerade_routes,
+            )?);
+        }
+        #[cfg(target_os = "linux")]
+        if self.config.drop_caps {
+            //网卡和路由由辅助进程操作时不需要保留任何能力
+            let keep: &[u32] = if self.config.helper_socket.is_some() {
+                &[]
+            } else {
+                &crate::util::privilege::KEEP
+            };
+            crate::util::privilege::drop_caps(keep, self.config.run_as)?;
+        }
         Ok(driver_info)
     }
+    /// 虚拟网段和本机已有网段(局域网、其他vpn)的冲突，create_iface之后有效，
+    /// 冲突时访问这部分虚拟ip的流量会被路由到局域网
+    pub fn subnet_collisions(&self) -> &[String] {
+        &self.subnet_collisions
+    }
     pub async fn build(self) -> crate::Result<Vnt> {
         //将读的超时时间清空
         self.main_channel.set_read_timeout(None)?;
@@ -216,6 +603,8 @@ impl VntUtil {
             Some(res) => res,
         };
         let config = self.config.clone();
+        //收发线程启动前开启，线程启动时安装过滤器
+        crate::util::seccomp::set_enabled(config.seccomp)?;
         let vnt_status_manager = VntStatusManger::new();
         let finger = if config.finger {
             Some(config.token.clone())
@@ -223,7 +612,8 @@ impl VntUtil {
             None
         };
         let client_cipher =
-            Cipher::new_password(config.cipher_model, config.password.clone(), finger);
+            Cipher::new_password(config.cipher_model, config.password.clone(), finger)
+                .into_rotating();
         let virtual_ip = response.virtual_ip;
         let virtual_gateway = response.virtual_gateway;
         let virtual_netmask = response.virtual_netmask;
@@ -241,8 +631,16 @@ impl VntUtil {
         } else {
             (None, None)
         };
-        let tcp_listener = TcpListener::bind(format!("[::]:{}", config.port))?;
+        let tcp_listener = bind_tcp(&config, self.main_channel.local_addr()?.port())?;
         let local_tcp_port = tcp_listener.local_addr()?.port();
+        let turn = match config.turn.clone().map(TurnClient::new) {
+            None => None,
+            Some(Ok(turn)) => Some(Arc::new(turn)),
+            Some(Err(e)) => {
+                log::warn!("turn客户端创建失败 {:?}", e);
+                None
+            }
+        };
         let context = Context::new(
             self.main_channel,
             tcp_sender,
@@ -250,7 +648,58 @@ impl VntUtil {
             1,
             config.first_latency,
             local_tcp_port,
+            if config.session_idle == 0 {
+                None
+            } else {
+                Some(Duration::from_secs(config.session_idle as u64))
+            },
+            config.tcp_control && !config.tcp,
+            turn,
+            PathPolicyTable::new(&config.path_policy),
+            config.require_encryption,
+            config.broadcast,
+            Capabilities::local(
+                config.device_mtu() as u32,
+                if config.password.is_some() {
+                    config.cipher_model
+                } else {
+                    CipherModel::None
+                },
+            ),
+            if config.max_clock_skew == 0 {
+                None
+            } else {
+                Some(Duration::from_secs(config.max_clock_skew as u64))
+            },
+            config.subnet_alias.map(|(alias, _)| Ipv4Addr::from(alias)),
+            if config.server_grace == 0 {
+                None
+            } else {
+                Some(Duration::from_secs(config.server_grace as u64))
+            },
+            Rendezvous::new(&config.peer_addrs),
+        );
+        context.events().push(
+            EventKind::Connected,
+            None,
+            format!("registered {} via {}", virtual_ip, config.server_address),
         );
+        context.events().push(
+            EventKind::IpAssigned,
+            None,
+            format!("{}/{}", virtual_ip, virtual_netmask),
+        );
+        for x in &self.subnet_collisions {
+            context.events().push(
+                EventKind::Error,
+                None,
+                format!(
+                    "virtual network {} overlaps local network {}, use subnet_alias",
+                    format_cidr(virtual_ip.into(), response.virtual_netmask.into()),
+                    x
+                ),
+            );
+        }
         let idle = Idle::new(Duration::from_secs(16), context.clone());
         let channel_sender = ChannelSender::new(context.clone());
 
@@ -262,7 +711,12 @@ impl VntUtil {
             config.device_id.clone(),
             config.name.clone(),
             config.password.is_some(),
+            config.advertise_routes.clone(),
+            config.backoff(),
         ));
+        if let Some((ticket, ttl)) = response.resume_ticket.clone() {
+            register.set_ticket(ticket, ttl);
+        }
         let device_list: Arc<Mutex<(u16, Vec<PeerDeviceInfo>)>> =
             Arc::new(Mutex::new((response.epoch, response.device_info_list)));
         let peer_nat_info_map: Arc<RwLock<HashMap<Ipv4Addr, NatInfo>>> =
@@ -283,12 +737,36 @@ impl VntUtil {
             ipv6,
             local_udp_port,
             local_tcp_port,
+            config.local_ip,
         );
-        let in_external_route = if config.in_ips.is_empty() {
-            None
-        } else {
-            Some(ExternalRoute::new(config.in_ips))
-        };
+        // 服务端可能下发路由，所以始终创建
+        let external_route = ExternalRoute::new(config.in_ips.clone());
+        let in_external_route = Some(external_route.clone());
+        let keepalive = Arc::new(AtomicCell::new(DEFAULT_KEEPALIVE));
+        let split_dns = Arc::new(Mutex::new(self.split_dns));
+        let remote_config = RemoteConfigHandler::new(
+            config.in_ips,
+            external_route,
+            device_writer.clone(),
+            self.device_name,
+            config.dns_server.is_some(),
+            split_dns.clone(),
+            keepalive.clone(),
+            config.advertise_routes.clone(),
+            config.accept_routes.clone(),
+            config.route_conflict,
+        );
+        if let Some(client_config) = response.client_config {
+            if let Err(e) = remote_config.apply(client_config, virtual_gateway) {
+                log::warn!("应用服务端下发的配置失败 {:?}", e);
+            }
+        }
+        context.path_policy().update_tags(&device_list.lock().1);
+        context.update_plaintext_peers(&device_list.lock().1);
+        context.update_offline_peers(&device_list.lock().1);
+        if let Err(e) = remote_config.apply_peer_routes(&device_list.lock().1, virtual_gateway) {
+            log::warn!("添加其他设备发布的路由失败 {:?}", e);
+        }
         #[cfg(feature = "ip_proxy")]
         let (tcp_proxy, udp_proxy, ip_proxy_map) = if config.out_ips.is_empty() || config.no_proxy {
             (None, None, None)
@@ -311,6 +789,8 @@ impl VntUtil {
         } else {
             None
         };
+        //桥接模式下学习到的mac地址，收发两个方向共用
+        let mac_table = config.bridge.as_ref().map(|_| MacTable::default());
         #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
         if config.tap {
             tap_handler::start(
@@ -323,9 +803,12 @@ impl VntUtil {
                 in_external_route,
                 #[cfg(feature = "ip_proxy")]
                 ip_proxy_map.clone(),
+                mac_table.clone(),
                 client_cipher.clone(),
                 self.server_cipher.clone(),
                 config.parallel,
+                config.channel_capacity,
+                config.packet_buf_len(),
             );
         } else {
             tun_handler::start(
@@ -341,6 +824,8 @@ impl VntUtil {
                 client_cipher.clone(),
                 self.server_cipher.clone(),
                 config.parallel,
+                config.channel_capacity,
+                config.packet_buf_len(),
             );
         }
         #[cfg(any(target_os = "android"))]
@@ -357,9 +842,27 @@ impl VntUtil {
             client_cipher.clone(),
             self.server_cipher.clone(),
             config.parallel,
+            config.channel_capacity,
+            config.packet_buf_len(),
         );
 
+        let stream_manager = StreamManager::new(
+            channel_sender.clone(),
+            current_device.clone(),
+            client_cipher.clone(),
+        );
         //外部数据接收处理
+        let quota = Arc::new(AtomicCell::new(None));
+        let maintenance = Arc::new(Mutex::new(None));
+        let self_test = if config.self_test.is_empty() {
+            None
+        } else {
+            Some(Arc::new(SelfTest::new(
+                config.self_test.clone(),
+                Duration::from_secs(config.self_test_interval as u64 * 60),
+                FAILURE_THRESHOLD,
+            )))
+        };
         let channel_recv_handler = ChannelDataHandler::new(
             current_device.clone(),
             device_list.clone(),
@@ -372,13 +875,18 @@ impl VntUtil {
             #[cfg(feature = "ip_proxy")]
             ip_proxy_map,
             out_external_route,
-            cone_sender,
-            symmetric_sender,
+            remote_config.clone(),
+            quota.clone(),
+            maintenance.clone(),
+            cone_sender.clone(),
+            symmetric_sender.clone(),
             client_cipher.clone(),
             self.server_cipher.clone(),
             self.rsa_cipher.clone(),
             config.relay,
             config.token.clone(),
+            stream_manager.clone(),
+            mac_table,
             14,
         );
         let punch = Punch::new(
@@ -405,9 +913,10 @@ impl VntUtil {
                 channel_sender.clone(),
                 device_list.clone(),
                 current_device.clone(),
-                config.server_address_str,
+                server_addresses(&config.server_address_str, &self.listen_addresses),
                 client_cipher.clone(),
                 self.server_cipher.clone(),
+                keepalive,
             );
             heartbeat_handler::start_heartbeat(
                 vnt_status_manager.worker("heartbeat"),
@@ -417,6 +926,27 @@ impl VntUtil {
                 client_cipher.clone(),
                 self.server_cipher.clone(),
             );
+            // 直连路径质量探测
+            heartbeat_handler::start_probe(
+                vnt_status_manager.worker("probe"),
+                channel_sender.clone(),
+                device_list.clone(),
+                current_device.clone(),
+                client_cipher.clone(),
+                self.server_cipher.clone(),
+            );
+            // 定时自检
+            if let Some(self_test) = self_test.clone() {
+                heartbeat_handler::start_self_test(
+                    vnt_status_manager.worker("self_test"),
+                    channel_sender.clone(),
+                    self_test,
+                    device_list.clone(),
+                    current_device.clone(),
+                    client_cipher.clone(),
+                    self.server_cipher.clone(),
+                );
+            }
             // 空闲检查
             heartbeat_handler::start_idle(
                 vnt_status_manager.worker("idle"),
@@ -443,6 +973,7 @@ impl VntUtil {
                     vnt_status_manager.worker("punch_handler"),
                     nat_test,
                     device_list,
+                    peer_nat_info_map.clone(),
                     channel_sender,
                     current_device,
                     client_cipher.clone(),
@@ -486,6 +1017,20 @@ impl VntUtil {
             device_list,
             connect_status,
             peer_nat_info_map,
+            kill_switch: Arc::new(Mutex::new(self.kill_switch)),
+            split_tunnel: Arc::new(Mutex::new(self.split_tunnel)),
+            bridge: Arc::new(Mutex::new(self.bridge)),
+            masquerade: Arc::new(Mutex::new(self.masquerade)),
+            split_dns,
+            remote_config,
+            quota,
+            maintenance,
+            cone_sender,
+            symmetric_sender,
+            stream_manager,
+            client_cipher,
+            server_cipher: self.server_cipher,
+            self_test,
         })
     }
 }
@@ -506,8 +1051,27 @@ impl Vnt {
     pub fn peer_nat_info(&self, ip: &Ipv4Addr) -> Option<NatInfo> {
         self.peer_nat_info_map.read().get(ip).cloned()
     }
+    /// 和对端协商的能力，对端是旧版本或者还没有交换打洞信息时为None
+    pub fn peer_capabilities(&self, ip: &Ipv4Addr) -> Option<Capabilities> {
+        self.context.capabilities().get(ip)
+    }
     pub fn connection_status(&self) -> ConnectStatus {
-        self.connect_status.load()
+        match self.connect_status.load() {
+            ConnectStatus::Connected => match self.context.server_lost() {
+                None => ConnectStatus::Connected,
+                Some(lost) if lost.remaining == Some(Duration::ZERO) => ConnectStatus::Connecting,
+                Some(_) => ConnectStatus::Degraded,
+            },
+            status => status,
+        }
+    }
+    /// 和服务端失联的时长和直连剩余的宽限时间，服务端可用时为None
+    pub fn server_lost(&self) -> Option<ServerLost> {
+        self.context.server_lost()
+    }
+    /// 已经调用过stop
+    pub fn is_stopped(&self) -> bool {
+        self.context.is_close()
     }
     pub fn nat_info(&self) -> NatInfo {
         self.nat_test.nat_info()
@@ -518,6 +1082,152 @@ impl Vnt {
         drop(device_list_lock);
         device_list
     }
+    /// 带有指定标签的设备，用于按标签配置策略，不受ip分配变化的影响
+    pub fn peers_by_tag(&self, tag: &str) -> Vec<Ipv4Addr> {
+        self.device_list
+            .lock()
+            .1
+            .iter()
+            .filter(|v| v.has_tag(tag))
+            .map(|v| v.virtual_ip)
+            .collect()
+    }
+    /// 连接对端设备在隧道内监听的端口，数据不经过虚拟网卡
+    pub async fn connect(&self, peer: Ipv4Addr, port: u16) -> io::Result<VntStream> {
+        self.stream_manager.connect(peer, port).await
+    }
+    /// 在隧道内监听端口，和系统端口无关
+    pub fn listen(&self, port: u16) -> io::Result<VntListener> {
+        self.stream_manager.listen(port)
+    }
+    /// 服务端下发的配置，未下发时为None
+    pub fn remote_config(&self) -> Option<RemoteConfig> {
+        self.remote_config.current()
+    }
+    /// 服务端推送的中转流量配额，服务端未开启配额时为None
+    pub fn quota(&self) -> Option<Quota> {
+        self.quota.load()
+    }
+    /// 服务端的维护通知，没有维护时为None
+    pub fn maintenance(&self) -> Option<Maintenance> {
+        self.maintenance.lock().clone()
+    }
+    /// 时间不早于since(unix秒)的连接事件
+    pub fn events(&self, since: u64) -> Vec<Event> {
+        self.context.events().list(since)
+    }
+    /// 订阅连接事件(路径变化、和服务端断开、分配ip、错误等)，只推送订阅之后的事件，
+    /// 处理太慢时会丢弃最早的事件并返回Lagged
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<Event> {
+        self.context.events().subscribe()
+    }
+    /// 广播策略，以及本机发出和各设备发来的广播数量
+    pub fn broadcast_stats(&self) -> BroadcastStats {
+        self.context.broadcast().stats()
+    }
+    /// 启动以来解密/校验失败的总数，以及各来源地址的统计
+    pub fn auth_failures(&self) -> (u64, Vec<AuthFailure>) {
+        let quarantine = self.context.quarantine();
+        (quarantine.total(), quarantine.list())
+    }
+    /// 按原因统计的丢弃的不合法ip报文，只包含出现过的原因
+    pub fn malformed(&self) -> Vec<(Malformed, u64)> {
+        self.context.malformed().list()
+    }
+    /// 和各设备的时钟偏差(毫秒)，绝对值大的在前，以及告警阈值
+    pub fn clock_skew(&self) -> (Option<Duration>, Vec<(Ipv4Addr, i64)>) {
+        let clock_skew = self.context.clock_skew();
+        (clock_skew.threshold(), clock_skew.list())
+    }
+    /// 各自检设备的结果，没有配置自检时为None
+    pub fn self_test(&self) -> Option<Vec<(Ipv4Addr, SelfTestStatus)>> {
+        self.self_test.as_ref().map(|v| v.list())
+    }
+    /// 是否有自检设备连续失败
+    pub fn self_test_alerting(&self) -> bool {
+        self.self_test.as_ref().map_or(false, |v| v.is_alerting())
+    }
+    /// 临时指定和某个设备的路径策略，如测速时强制走中转，到期或duration为None时恢复
+    pub fn set_path_policy(&self, ip: Ipv4Addr, policy: PathPolicy, duration: Option<Duration>) {
+        self.context
+            .path_policy()
+            .set_temporary(ip, policy, duration)
+    }
+    /// 启动以来的收发包数、字节数和打洞次数
+    pub fn counters(&self) -> CounterSnapshot {
+        self.context.counters().snapshot()
+    }
+    /// 按设备记录的状态的条目数和估算内存，以及达到上限后淘汰的次数
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let mut usage = self.context.memory_usage();
+        usage.add(
+            "device",
+            self.device_list.lock().1.len(),
+            std::mem::size_of::<PeerDeviceInfo>(),
+        );
+        usage.add(
+            "nat",
+            self.peer_nat_info_map.read().len(),
+            std::mem::size_of::<(Ipv4Addr, NatInfo)>(),
+        );
+        usage
+    }
+    /// 主心跳任务和tun读取线程的推进情况，用于判断是否卡死
+    pub fn liveness(&self) -> LivenessSnapshot {
+        self.context.liveness().snapshot()
+    }
+    /// 已经打通p2p的设备的nat信息，地址使用当前连通的地址，用于本地缓存
+    pub fn peer_cache(&self) -> Vec<(Ipv4Addr, NatInfo)> {
+        let peer_nat_info_map = self.peer_nat_info_map.read();
+        let mut list = Vec::with_capacity(peer_nat_info_map.len());
+        for (ip, nat_info) in peer_nat_info_map.iter() {
+            if let Some(route) = self.context.route_one(ip) {
+                if !route.is_p2p() || route.is_tcp {
+                    continue;
+                }
+                let mut nat_info = nat_info.clone();
+                if let SocketAddr::V4(addr) = route.addr {
+                    nat_info.update_addr(*addr.ip(), addr.port());
+                }
+                list.push((*ip, nat_info));
+            }
+        }
+        list
+    }
+    /// 使用缓存的nat信息立即打洞，跳过不在线和已经直连的设备
+    pub async fn punch_peers(&self, peers: Vec<(Ipv4Addr, NatInfo)>) {
+        if self.config.relay {
+            return;
+        }
+        let current_device = self.current_device.load();
+        let online: Vec<Ipv4Addr> = self
+            .device_list()
+            .iter()
+            .filter(|v| v.status == PeerDeviceStatus::Online)
+            .map(|v| v.virtual_ip)
+            .collect();
+        for (ip, nat_info) in peers {
+            if ip == current_device.virtual_ip || !online.contains(&ip) {
+                continue;
+            }
+            if self.context.route_one(&ip).map_or(false, |r| r.is_p2p()) {
+                continue;
+            }
+            //缓存的地址作为候选地址，收到对端的打洞信息后会更新
+            self.context.ice_update_remote(
+                ip,
+                current_device.virtual_ip < ip,
+                ice::gather(&nat_info, None),
+            );
+            let rs = match nat_info.nat_type {
+                NatType::Symmetric => self.symmetric_sender.send((ip, nat_info)).await,
+                NatType::Cone => self.cone_sender.send((ip, nat_info)).await,
+            };
+            if rs.is_err() {
+                break;
+            }
+        }
+    }
     pub fn route(&self, ip: &Ipv4Addr) -> Option<Route> {
         self.context.route_one(ip)
     }
@@ -527,15 +1237,90 @@ impl Vnt {
     pub fn route_table(&self) -> Vec<(Ipv4Addr, Route)> {
         self.context.route_table_one()
     }
+    /// 和设备之间的ice候选地址对及检查状态，按优先级排列
+    pub fn ice_check_list(&self, ip: &Ipv4Addr) -> Vec<CandidatePair> {
+        self.context.ice_check_list(ip)
+    }
+    /// 各设备最近一次打洞的过程和结果
+    pub fn punch_records(&self) -> Vec<(Ipv4Addr, PunchRecord)> {
+        self.context.punch_records().list()
+    }
+    /// 按程序分流的模式和程序列表
+    pub fn split_tunnel_apps(&self) -> Option<(SplitMode, Vec<String>)> {
+        self.split_tunnel
+            .lock()
+            .as_ref()
+            .map(|split| (split.mode(), split.apps()))
+    }
+    pub fn split_tunnel_add(&self, app: &str) -> io::Result<()> {
+        match self.split_tunnel.lock().as_ref() {
+            None => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "split tunnel not enabled",
+            )),
+            Some(split) => split.add(app),
+        }
+    }
+    pub fn split_tunnel_remove(&self, app: &str) -> io::Result<()> {
+        match self.split_tunnel.lock().as_ref() {
+            None => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "split tunnel not enabled",
+            )),
+            Some(split) => split.remove(app),
+        }
+    }
+    /// 更换组网密码，window时间内继续使用旧密码加密，再过window时间后不再接受旧密码
+    pub fn rekey(&self, password: String, window: Duration) -> io::Result<()> {
+        let finger = if self.config.finger {
+            Some(self.config.token.clone())
+        } else {
+            None
+        };
+        let cipher = Cipher::new_password(self.config.cipher_model, Some(password), finger);
+        self.client_cipher.rekey(cipher, window)
+    }
+    /// 导出会话密钥用于wireshark解密抓包，需要启动时开启debug_keys
+    pub fn export_keys(&self) -> io::Result<String> {
+        if !self.config.debug_keys {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "key export not enabled, restart with debug_keys",
+            ));
+        }
+        log::warn!("导出会话密钥");
+        Ok(keylog::keylog(
+            self.current_device.load().virtual_ip(),
+            &self.client_cipher,
+            &self.server_cipher,
+        ))
+    }
     pub fn stop(&self) -> io::Result<()> {
+        self.stream_manager.close();
         let _ = self.context.close();
         self.vnt_status_manager.stop_all();
         let _ = self.device_writer.close();
+        if let Some(kill_switch) = self.kill_switch.lock().take() {
+            kill_switch.disable();
+        }
+        self.split_tunnel.lock().take();
+        if let Some(split_dns) = self.split_dns.lock().take() {
+            split_dns.disable();
+        }
+        if let Some(bridge) = self.bridge.lock().take() {
+            bridge.disable();
+        }
+        if let Some(masquerade) = self.masquerade.lock().take() {
+            masquerade.disable();
+        }
         let virtual_gateway = self.current_device.load().virtual_gateway;
-        let _ = UdpSocket::bind("0.0.0.0:0")?.send_to(
-            b"stop",
-            SocketAddr::V4(SocketAddrV4::new(virtual_gateway, 10000)),
-        );
+        crate::util::seccomp::unrestricted(move || -> io::Result<()> {
+            let _ = UdpSocket::bind("0.0.0.0:0")?.send_to(
+                b"stop",
+                SocketAddr::V4(SocketAddrV4::new(virtual_gateway, 10000)),
+            );
+            Ok(())
+        })?;
 
         Ok(())
     }
@@ -556,6 +1341,88 @@ impl Vnt {
     }
 }
 
+/// 主udp是双栈的，指定ipv4地址时绑定映射的ipv6地址
+fn listen_addr(local_ip: Option<IpAddr>, port: u16) -> SocketAddr {
+    match local_ip {
+        None => SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, port, 0, 0)),
+        Some(IpAddr::V4(ip)) => SocketAddr::V6(SocketAddrV6::new(ip.to_ipv6_mapped(), port, 0, 0)),
+        Some(IpAddr::V6(ip)) => SocketAddr::V6(SocketAddrV6::new(ip, port, 0, 0)),
+    }
+}
+
+/// 依次尝试的端口，指定了端口范围时从范围内随机位置开始轮一圈，避免多个实例争抢同一个端口
+fn listen_ports(config: &Config) -> Vec<u16> {
+    match config.port_range {
+        Some((start, end)) => {
+            let offset = rand::thread_rng().gen_range(0..=end - start);
+            (start..=end)
+                .cycle()
+                .skip(offset as usize)
+                .take((end - start) as usize + 1)
+                .collect()
+        }
+        None => vec![config.port],
+    }
+}
+
+/// 配置的服务端地址在前，然后是服务端通告的其他监听地址
+fn server_addresses(server_address_str: &str, listen_addresses: &[String]) -> Vec<String> {
+    let mut list = vec![server_address_str.to_string()];
+    for address in listen_addresses {
+        if !list.contains(address) {
+            list.push(address.clone());
+        }
+    }
+    list
+}
+
+fn bind_udp(config: &Config) -> io::Result<UdpSocket> {
+    let mut last_err = None;
+    for port in listen_ports(config) {
+        let socket = socket2::Socket::new(socket2::Domain::IPV6, socket2::Type::DGRAM, None)?;
+        socket.set_only_v6(false)?;
+        crate::util::fwmark::mark(&socket)?;
+        match socket.bind(&listen_addr(config.local_ip, port).into()) {
+            Ok(_) => return Ok(socket.into()),
+            Err(e) => {
+                if e.kind() != io::ErrorKind::AddrInUse {
+                    return Err(e);
+                }
+                last_err = Some(e);
+            }
+        }
+    }
+    Err(last_err.unwrap_or_else(|| io::Error::from(io::ErrorKind::AddrInUse)))
+}
+
+/// tcp优先使用和udp相同的端口，方便防火墙放行
+fn bind_tcp(config: &Config, udp_port: u16) -> io::Result<TcpListener> {
+    let bind = |port: u16| match config.local_ip {
+        //tcp不使用双栈，直接绑定ipv4地址
+        Some(IpAddr::V4(ip)) => TcpListener::bind(SocketAddrV4::new(ip, port)),
+        local_ip => TcpListener::bind(listen_addr(local_ip, port)),
+    };
+    if config.port == 0 && config.port_range.is_none() {
+        return bind(0);
+    }
+    let mut ports = listen_ports(config);
+    ports.retain(|port| *port != udp_port);
+    ports.insert(0, udp_port);
+    let mut last_err = None;
+    for port in ports {
+        match bind(port) {
+            Ok(listener) => return Ok(listener),
+            Err(e) => {
+                if e.kind() != io::ErrorKind::AddrInUse {
+                    return Err(e);
+                }
+                last_err = Some(e);
+            }
+        }
+    }
+    Err(last_err.unwrap_or_else(|| io::Error::from(io::ErrorKind::AddrInUse)))
+}
+
 impl Drop for Vnt {
     fn drop(&mut self) {
         let _ = self.stop();
@@ -588,34 +1455,333 @@ pub struct Config {
     pub punch_model: PunchModel,
     pub port: u16,
     pub first_latency: bool,
+    /// 工作线程数，传0时按cpu核数选择
+    pub threads: usize,
+    /// 阻塞线程池的线程上限，传0时按cpu核数选择
+    pub blocking_threads: usize,
+    /// 并行处理网卡数据时每个任务的队列长度，传0时使用默认值1
+    pub channel_capacity: usize,
+    /// 低资源模式，用于内存较小的路由器等设备
+    pub small: bool,
+    /// 网卡跃点，仅windows使用，传0时使用默认值1
+    pub metric: u16,
+    /// 仅linux使用，非0时路由添加到该路由表，并使用同值的fwmark做策略路由，0表示使用main表
+    pub route_table: u32,
+    /// 防泄漏，只允许流量经过虚拟网卡出站，linux和windows可用
+    pub kill_switch: bool,
+    /// 按程序分流，None表示不分流
+    pub split_mode: Option<SplitMode>,
+    pub split_apps: Vec<String>,
+    /// 隧道内的dns服务器，只解析dns_domains中的域名后缀
+    pub dns_server: Option<Ipv4Addr>,
+    pub dns_domains: Vec<String>,
+    /// 按需连接，和设备没有数据通信超过这个时间(秒)后不再打洞和发送心跳，0表示关闭
+    pub session_idle: u32,
+    /// 混合模式，和服务端的注册、心跳等控制消息使用tcp，数据仍使用udp，
+    /// 用于udp映射很快过期但tcp能长时间保持的nat
+    pub tcp_control: bool,
+    /// 外部turn服务器，服务端中转不可用时通过它中转数据
+    pub turn: Option<TurnConfig>,
+    /// 本地监听地址，None表示监听所有地址
+    pub local_ip: Option<IpAddr>,
+    /// port为0时在这个范围内选择可用端口，udp和tcp尽量使用相同端口
+    pub port_range: Option<(u16, u16)>,
+    /// 底层socket绑定的物理网卡，避免和其他vpn形成路由环路
+    pub bind_device: Option<String>,
+    /// 经socks5或http代理连接服务端，设置后和服务端的通信都使用tcp
+    pub proxy: Option<ProxyConfig>,
+    /// 本设备发布的子网(如k8s节点的pod网段)，由服务端转发给其他设备自动添加路由
+    pub advertise_routes: Vec<(u32, u32)>,
+    /// 发布的子网中需要做源地址转换的部分，局域网设备不需要回程路由
+    pub masquerade_routes: Vec<(u32, u32)>,
+    /// 允许接收的其他设备发布的子网，为空时只接收私有网段中不和系统路由重叠的部分
+    pub accept_routes: Vec<(u32, u32)>,
+    /// 各设备的路径策略，0.0.0.0表示所有设备，优先于服务端的path:标签
+    pub path_policy: Vec<(Ipv4Addr, PathPolicy)>,
+    /// 只和加密的设备通信，需要设置密码
+    pub require_encryption: bool,
+    /// 桥接的物理网卡，tap网卡和它组成网桥，虚拟网络和局域网在二层互通
+    pub bridge: Option<String>,
+    /// 虚拟网络广播/组播的处理方式
+    pub broadcast: BroadcastPolicy,
+    /// 和其他设备的时钟偏差超过该秒数时告警，0表示不告警
+    pub max_clock_skew: u32,
+    /// 定时自检的设备，为空时不自检
+    pub self_test: Vec<Ipv4Addr>,
+    /// 自检间隔(分钟)
+    pub self_test_interval: u32,
+    /// 虚拟网卡名称，None时使用默认名称，同一进程中运行多个组网时各自使用不同的名称
+    pub device_name: Option<String>,
+    /// 仅windows使用，把虚拟网卡的网络配置文件设为专用网络
+    pub private_network: bool,
+    /// 仅linux使用，以IFF_MULTI_QUEUE方式创建网卡
+    pub multi_queue: bool,
+    /// 仅linux使用，网卡的所属用户和组(uid,gid)
+    pub tun_owner: Option<u32>,
+    pub tun_group: Option<u32>,
+    /// 仅linux使用，以普通用户运行时特权辅助进程的socket路径，网卡和路由由辅助进程操作
+    pub helper_socket: Option<String>,
+    /// 仅linux使用，创建网卡和添加路由后去掉不再需要的能力
+    pub drop_caps: bool,
+    /// 仅linux使用，降权时切换到该用户(uid,gid)
+    pub run_as: Option<(u32, u32)>,
+    /// 仅linux使用，收包线程安装seccomp白名单
+    pub seccomp: bool,
+    /// 和服务端的udp报文伪装成dns或ntp
+    pub mimicry: Option<Mimicry>,
+    /// 添加的路由和系统中已有路由(如其他vpn)重叠时的处理方式
+    pub route_conflict: RouteConflictPolicy,
+    /// 虚拟网段和局域网冲突时，本机网卡使用的映射网段(网段,掩码)，掩码需要和虚拟网段一致
+    pub subnet_alias: Option<(u32, u32)>,
+    /// 解析服务端域名使用的dns服务器，None时使用系统解析
+    pub resolver: Option<SocketAddr>,
+    /// 和服务端失联后已经打通的直连继续使用的秒数，超过后停止和其他设备通信，0表示不限制
+    pub server_grace: u32,
+    /// 设备的静态地址，不经服务端直接交换打洞信息
+    pub peer_addrs: Vec<(Ipv4Addr, SocketAddr)>,
+    /// 连接和注册失败后重试的最小、最大间隔(秒)，实际间隔带随机抖动
+    pub retry_backoff: (u32, u32),
+    /// 允许通过命令通道导出会话密钥，用于抓包调试
+    pub debug_keys: bool,
 }
 
-impl Config {
-    pub fn new(
-        tap: bool,
-        token: String,
-        device_id: String,
-        name: String,
-        server_address: SocketAddr,
-        server_address_str: String,
-        mut stun_server: Vec<String>,
-        in_ips: Vec<(u32, u32, Ipv4Addr)>,
-        out_ips: Vec<(u32, u32)>,
-        password: Option<String>,
-        simulate_multicast: bool,
-        mtu: Option<u16>,
-        tcp: bool,
-        ip: Option<Ipv4Addr>,
-        relay: bool,
-        #[cfg(feature = "ip_proxy")] no_proxy: bool,
-        server_encrypt: bool,
-        parallel: usize,
-        cipher_model: CipherModel,
-        finger: bool,
-        punch_model: PunchModel,
-        port: u16,
-        first_latency: bool,
-    ) -> Result<Self, Error> {
+/// 创建Config的参数，未设置的字段使用Default中的值，build时统一校验，
+/// 字段含义见Config中的同名字段
+#[derive(Clone, Debug)]
+pub struct ConfigOptions {
+    pub tap: bool,
+    pub token: String,
+    pub device_id: String,
+    pub name: String,
+    /// 解析后的服务端地址，必须设置
+    pub server_address: SocketAddr,
+    /// 服务端地址原文，重连时重新解析
+    pub server_address_str: String,
+    pub stun_server: Vec<String>,
+    pub in_ips: Vec<(u32, u32, Ipv4Addr)>,
+    pub out_ips: Vec<(u32, u32)>,
+    pub password: Option<String>,
+    pub simulate_multicast: bool,
+    pub mtu: Option<u16>,
+    pub tcp: bool,
+    pub ip: Option<Ipv4Addr>,
+    pub relay: bool,
+    #[cfg(feature = "ip_proxy")]
+    pub no_proxy: bool,
+    pub server_encrypt: bool,
+    pub parallel: usize,
+    pub cipher_model: CipherModel,
+    pub finger: bool,
+    pub punch_model: PunchModel,
+    pub port: u16,
+    pub first_latency: bool,
+    /// 为0时按cpu核数选择，低资源模式下为1
+    pub threads: usize,
+    /// 为0时按cpu核数选择，低资源模式下为2
+    pub blocking_threads: usize,
+    /// 为0时使用1
+    pub channel_capacity: usize,
+    pub small: bool,
+    /// 为0时使用1
+    pub metric: u16,
+    pub route_table: u32,
+    pub kill_switch: bool,
+    pub split_mode: Option<SplitMode>,
+    pub split_apps: Vec<String>,
+    pub dns_server: Option<Ipv4Addr>,
+    pub dns_domains: Vec<String>,
+    pub session_idle: u32,
+    pub tcp_control: bool,
+    pub turn: Option<TurnConfig>,
+    pub local_ip: Option<IpAddr>,
+    pub port_range: Option<(u16, u16)>,
+    pub bind_device: Option<String>,
+    pub proxy: Option<ProxyConfig>,
+    pub advertise_routes: Vec<(u32, u32)>,
+    pub masquerade_routes: Vec<(u32, u32)>,
+    pub accept_routes: Vec<(u32, u32)>,
+    pub bridge: Option<String>,
+    pub path_policy: Vec<(Ipv4Addr, PathPolicy)>,
+    pub require_encryption: bool,
+    pub broadcast: BroadcastPolicy,
+    pub max_clock_skew: u32,
+    pub self_test: Vec<Ipv4Addr>,
+    /// 为0时使用5分钟
+    pub self_test_interval: u32,
+    pub route_conflict: RouteConflictPolicy,
+    pub subnet_alias: Option<(u32, u32)>,
+    pub device_name: Option<String>,
+    pub private_network: bool,
+    pub multi_queue: bool,
+    pub tun_owner: Option<u32>,
+    pub tun_group: Option<u32>,
+    pub drop_caps: bool,
+    /// 设置后同时开启drop_caps
+    pub run_as: Option<(u32, u32)>,
+    pub seccomp: bool,
+    pub mimicry: Option<Mimicry>,
+    pub resolver: Option<SocketAddr>,
+    pub server_grace: u32,
+    pub peer_addrs: Vec<(Ipv4Addr, SocketAddr)>,
+    /// None时使用默认的重试间隔
+    pub retry_backoff: Option<(u32, u32)>,
+    pub debug_keys: bool,
+}
+
+impl Default for ConfigOptions {
+    fn default() -> Self {
+        Self {
+            tap: false,
+            token: String::new(),
+            device_id: String::new(),
+            name: String::new(),
+            server_address: SocketAddr::from(([0, 0, 0, 0], 0)),
+            server_address_str: String::new(),
+            stun_server: Vec::new(),
+            in_ips: Vec::new(),
+            out_ips: Vec::new(),
+            password: None,
+            simulate_multicast: false,
+            mtu: None,
+            tcp: false,
+            ip: None,
+            relay: false,
+            #[cfg(feature = "ip_proxy")]
+            no_proxy: false,
+            server_encrypt: false,
+            parallel: 1,
+            #[cfg(any(feature = "aes_gcm", feature = "server_encrypt"))]
+            cipher_model: CipherModel::AesGcm,
+            #[cfg(not(any(feature = "aes_gcm", feature = "server_encrypt")))]
+            cipher_model: CipherModel::None,
+            finger: false,
+            punch_model: PunchModel::All,
+            port: 0,
+            first_latency: false,
+            threads: 0,
+            blocking_threads: 0,
+            channel_capacity: 0,
+            small: false,
+            metric: 0,
+            route_table: 0,
+            kill_switch: false,
+            split_mode: None,
+            split_apps: Vec::new(),
+            dns_server: None,
+            dns_domains: Vec::new(),
+            session_idle: 0,
+            tcp_control: false,
+            turn: None,
+            local_ip: None,
+            port_range: None,
+            bind_device: None,
+            proxy: None,
+            advertise_routes: Vec::new(),
+            masquerade_routes: Vec::new(),
+            accept_routes: Vec::new(),
+            bridge: None,
+            path_policy: Vec::new(),
+            require_encryption: false,
+            broadcast: BroadcastPolicy::default(),
+            max_clock_skew: 30,
+            self_test: Vec::new(),
+            self_test_interval: 5,
+            route_conflict: RouteConflictPolicy::default(),
+            subnet_alias: None,
+            device_name: None,
+            private_network: false,
+            multi_queue: false,
+            tun_owner: None,
+            tun_group: None,
+            drop_caps: false,
+            run_as: None,
+            seccomp: false,
+            mimicry: None,
+            resolver: None,
+            server_grace: 0,
+            peer_addrs: Vec::new(),
+            retry_backoff: None,
+            debug_keys: false,
+        }
+    }
+}
+
+impl ConfigOptions {
+    /// 校验参数并补全默认值
+    pub fn build(self) -> Result<Config, Error> {
+        let ConfigOptions {
+            tap,
+            token,
+            device_id,
+            name,
+            server_address,
+            server_address_str,
+            mut stun_server,
+            in_ips,
+            mut out_ips,
+            password,
+            simulate_multicast,
+            mtu,
+            tcp,
+            ip,
+            relay,
+            #[cfg(feature = "ip_proxy")]
+            no_proxy,
+            server_encrypt,
+            parallel,
+            cipher_model,
+            finger,
+            punch_model,
+            port,
+            first_latency,
+            threads,
+            blocking_threads,
+            channel_capacity,
+            small,
+            metric,
+            route_table,
+            kill_switch,
+            split_mode,
+            split_apps,
+            dns_server,
+            dns_domains,
+            session_idle,
+            tcp_control,
+            turn,
+            local_ip,
+            port_range,
+            bind_device,
+            proxy,
+            advertise_routes,
+            masquerade_routes,
+            accept_routes,
+            bridge,
+            path_policy,
+            require_encryption,
+            broadcast,
+            max_clock_skew,
+            self_test,
+            self_test_interval,
+            route_conflict,
+            subnet_alias,
+            device_name,
+            private_network,
+            multi_queue,
+            tun_owner,
+            tun_group,
+            drop_caps,
+            run_as,
+            seccomp,
+            mimicry,
+            resolver,
+            server_grace,
+            peer_addrs,
+            retry_backoff,
+            debug_keys,
+        } = self;
+        if server_address.port() == 0 {
+            return Err(Error::Stop(String::from("server_address invalid")));
+        }
         for x in stun_server.iter_mut() {
             if !x.contains(":") {
                 x.push_str(":3478");
@@ -630,7 +1796,72 @@ impl Config {
         if name.is_empty() || name.len() > 128 {
             return Err(Error::Stop(String::from("name too long")));
         }
-        Ok(Self {
+        if let Some(device_name) = &device_name {
+            if device_name.trim().is_empty() || device_name.len() > 64 {
+                return Err(Error::Stop(String::from("device_name invalid")));
+            }
+        }
+        if !dns_domains.is_empty() && dns_server.is_none() {
+            return Err(Error::Stop(String::from("dns_domains requires dns_server")));
+        }
+        if let Some(domain) = dns_domains
+            .iter()
+            .find(|v| !crate::split_dns::is_valid_domain(v))
+        {
+            return Err(Error::Stop(format!("dns_domains invalid: {:?}", domain)));
+        }
+        if let Some((min, max)) = retry_backoff {
+            if min == 0 || min > max {
+                return Err(Error::Stop(String::from("retry_backoff invalid")));
+            }
+        }
+        if let Some((start, end)) = port_range {
+            if start == 0 || start > end {
+                return Err(Error::Stop(String::from("port_range invalid")));
+            }
+            if port != 0 {
+                return Err(Error::Stop(String::from(
+                    "port and port_range cannot be used together",
+                )));
+            }
+        }
+        if require_encryption && password.is_none() {
+            return Err(Error::Stop(String::from(
+                "require_encryption requires password",
+            )));
+        }
+        if bridge.is_some() && !tap {
+            return Err(Error::Stop(String::from("bridge requires tap")));
+        }
+        if subnet_alias.is_some() && tap {
+            return Err(Error::Stop(String::from("subnet_alias requires tun")));
+        }
+        if seccomp && !cfg!(target_os = "linux") {
+            return Err(Error::Stop(String::from("seccomp only supports linux")));
+        }
+        if mimicry.is_some() && (tcp || proxy.is_some()) {
+            return Err(Error::Stop(String::from("mimicry requires udp")));
+        }
+        for route in masquerade_routes.iter() {
+            if !advertise_routes.contains(route) {
+                return Err(Error::Stop(String::from(
+                    "masquerade route must be advertised",
+                )));
+            }
+        }
+        //发布的子网需要允许转发
+        for (dest, mask) in advertise_routes.iter() {
+            if !out_ips.contains(&(*dest, *mask)) {
+                out_ips.push((*dest, *mask));
+            }
+        }
+        //代理只能转发tcp，中转数据也要走tcp
+        let tcp = tcp || proxy.is_some();
+        let tcp_control = tcp_control && !tcp;
+        //低资源模式下关闭内置代理，点对网使用系统的ip转发
+        #[cfg(feature = "ip_proxy")]
+        let no_proxy = no_proxy || small;
+        Ok(Config {
             tap,
             token,
             device_id,
@@ -655,6 +1886,133 @@ impl Config {
             punch_model,
             port,
             first_latency,
+            threads: if threads != 0 {
+                threads
+            } else if small {
+                1
+            } else {
+                default_threads()
+            },
+            blocking_threads: if blocking_threads != 0 {
+                blocking_threads
+            } else if small {
+                2
+            } else {
+                default_blocking_threads()
+            },
+            channel_capacity: if channel_capacity == 0 {
+                1
+            } else {
+                channel_capacity
+            },
+            small,
+            metric: if metric == 0 { 1 } else { metric },
+            route_table,
+            kill_switch,
+            split_mode,
+            split_apps,
+            dns_server,
+            dns_domains,
+            session_idle,
+            tcp_control,
+            turn,
+            local_ip,
+            port_range,
+            bind_device,
+            proxy,
+            advertise_routes,
+            masquerade_routes,
+            accept_routes,
+            bridge,
+            path_policy,
+            require_encryption,
+            broadcast,
+            max_clock_skew,
+            self_test,
+            self_test_interval: if self_test_interval == 0 {
+                5
+            } else {
+                self_test_interval
+            },
+            device_name,
+            private_network,
+            multi_queue,
+            tun_owner,
+            tun_group,
+            helper_socket: None,
+            drop_caps: drop_caps || run_as.is_some(),
+            run_as,
+            seccomp,
+            mimicry,
+            route_conflict,
+            subnet_alias,
+            resolver,
+            server_grace,
+            peer_addrs,
+            retry_backoff: retry_backoff.unwrap_or((backoff::DEFAULT_MIN, backoff::DEFAULT_MAX)),
+            debug_keys,
         })
     }
 }
+
+impl Config {
+    /// 虚拟网卡的mtu，未指定时加密需要预留空间
+    pub fn device_mtu(&self) -> u16 {
+        match self.mtu {
+            None => {
+                let mtu = if self.password.is_none() { 1450 } else { 1410 };
+                //伪装头部占用的空间
+                mtu - self.mimicry.map_or(0, |v| v.header_len() as u16)
+            }
+            Some(mtu) => mtu,
+        }
+    }
+    /// 按配置的间隔创建重试退避
+    pub fn backoff(&self) -> Backoff {
+        Backoff::new(
+            Duration::from_secs(self.retry_backoff.0 as u64),
+            Duration::from_secs(self.retry_backoff.1 as u64),
+        )
+    }
+    /// 按配置的线程数构建运行时
+    pub fn build_runtime(&self) -> io::Result<tokio::runtime::Runtime> {
+        let mut builder = tokio::runtime::Builder::new_multi_thread();
+        builder
+            .worker_threads(self.threads)
+            .max_blocking_threads(self.blocking_threads)
+            .thread_name("vnt-worker")
+            .enable_all();
+        if self.small {
+            //默认栈大小为2M，低资源模式下减小栈空间
+            builder.thread_stack_size(SMALL_STACK_SIZE);
+        }
+        builder.build()
+    }
+    /// 读取网卡的缓冲区大小，低资源模式下按mtu分配，
+    /// 并行处理时每个排队的数据包都占用一个缓冲区
+    pub fn packet_buf_len(&self) -> usize {
+        if self.small {
+            self.device_mtu() as usize + PACKET_OVERHEAD
+        } else {
+            4096
+        }
+    }
+}
+
+const SMALL_STACK_SIZE: usize = 512 * 1024;
+/// 协议头、以太网帧头、加密和扩展区预留
+const PACKET_OVERHEAD: usize = 256;
+
+fn cpu_count() -> usize {
+    std::thread::available_parallelism().map_or(1, |v| v.get())
+}
+
+/// 默认工作线程数等于cpu核数，最多8个，再多对转发性能没有帮助
+pub fn default_threads() -> usize {
+    cpu_count().min(8)
+}
+
+/// 默认阻塞线程上限为cpu核数的4倍，限制在4~64之间，阻塞线程只在dns解析等少数场景使用
+pub fn default_blocking_threads() -> usize {
+    (cpu_count() * 4).clamp(4, 64)
+}
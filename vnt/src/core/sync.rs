@@ -19,9 +19,7 @@ pub struct VntSync {
 
 impl VntUtilSync {
     pub fn new(config: Config) -> io::Result<VntUtilSync> {
-        let runtime = tokio::runtime::Builder::new_multi_thread()
-            .enable_all()
-            .build()?;
+        let runtime = config.build_runtime()?;
         let vnt_util = VntUtil::new(config)?;
         Ok(VntUtilSync { vnt_util, runtime })
     }
@@ -12,7 +12,7 @@ pub enum Error {
     InvalidPacket,
     #[error("Not support")]
     NotSupport,
-    #[error("Stop")]
+    #[error("{0}")]
     Stop(String),
     #[error("Warn")]
     Warn(String),
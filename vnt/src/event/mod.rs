@@ -0,0 +1,233 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+use tokio::sync::broadcast;
+
+/// 最多保留的事件数，超过后丢弃最早的
+const CAPACITY: usize = 512;
+/// 订阅者来不及处理时最多缓存的事件数，超过后订阅者会收到Lagged
+const SUBSCRIBE_CAPACITY: usize = 128;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EventKind {
+    /// 注册到服务端
+    Connected,
+    /// 和服务端断开，正在重连
+    Disconnected,
+    /// 服务端分配了新的虚拟ip
+    IpAssigned,
+    /// 设备上线
+    PeerUp,
+    /// 设备下线
+    PeerDown,
+    /// 和设备之间的路径变化，如打通直连、质量变差改走中转
+    PathChanged,
+    /// 定时自检连续失败或者恢复
+    SelfTest,
+    /// 服务端开始或结束维护
+    Maintenance,
+    Error,
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            EventKind::Connected => "connected",
+            EventKind::Disconnected => "disconnected",
+            EventKind::IpAssigned => "ip-assigned",
+            EventKind::PeerUp => "peer-up",
+            EventKind::PeerDown => "peer-down",
+            EventKind::PathChanged => "path",
+            EventKind::SelfTest => "self-test",
+            EventKind::Maintenance => "maintenance",
+            EventKind::Error => "error",
+        };
+        f.write_str(s)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Event {
+    /// unix秒
+    pub time: u64,
+    pub kind: EventKind,
+    /// 和设备相关的事件
+    pub peer: Option<Ipv4Addr>,
+    pub detail: String,
+}
+
+/// 最近发生的连接事件，方便事后查看而不用翻日志，
+/// 同时推送给订阅者，命令行和界面不用轮询
+pub struct EventLog {
+    events: Mutex<VecDeque<Event>>,
+    sender: broadcast::Sender<Event>,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(SUBSCRIBE_CAPACITY);
+        Self {
+            events: Mutex::new(VecDeque::with_capacity(CAPACITY)),
+            sender,
+        }
+    }
+}
+
+impl EventLog {
+    pub fn push(&self, kind: EventKind, peer: Option<Ipv4Addr>, detail: String) {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|v| v.as_secs())
+            .unwrap_or(0);
+        let mut events = self.events.lock();
+        if events.len() >= CAPACITY {
+            events.pop_front();
+        }
+        let event = Event {
+            time,
+            kind,
+            peer,
+            detail,
+        };
+        //没有订阅者时发送失败，忽略
+        let _ = self.sender.send(event.clone());
+        events.push_back(event);
+    }
+    /// 订阅之后发生的事件，之前的事件用list获取
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+    /// 时间不早于since(unix秒)的事件，按时间先后排列
+    pub fn list(&self, since: u64) -> Vec<Event> {
+        self.events
+            .lock()
+            .iter()
+            .filter(|event| event.time >= since)
+            .cloned()
+            .collect()
+    }
+}
+
+/// 启动以来的收发计数，供外部按秒采样
+#[derive(Default)]
+pub struct Counters {
+    tx_packets: AtomicU64,
+    tx_bytes: AtomicU64,
+    rx_packets: AtomicU64,
+    rx_bytes: AtomicU64,
+    punch: AtomicU64,
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct CounterSnapshot {
+    pub tx_packets: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_bytes: u64,
+    /// 发起打洞的次数
+    pub punch: u64,
+}
+
+impl Counters {
+    #[inline]
+    pub fn tx(&self, len: usize) {
+        self.tx_packets.fetch_add(1, Ordering::Relaxed);
+        self.tx_bytes.fetch_add(len as u64, Ordering::Relaxed);
+    }
+    #[inline]
+    pub fn rx(&self, len: usize) {
+        self.rx_packets.fetch_add(1, Ordering::Relaxed);
+        self.rx_bytes.fetch_add(len as u64, Ordering::Relaxed);
+    }
+    pub fn punch(&self) {
+        self.punch.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn snapshot(&self) -> CounterSnapshot {
+        CounterSnapshot {
+            tx_packets: self.tx_packets.load(Ordering::Relaxed),
+            tx_bytes: self.tx_bytes.load(Ordering::Relaxed),
+            rx_packets: self.rx_packets.load(Ordering::Relaxed),
+            rx_bytes: self.rx_bytes.load(Ordering::Relaxed),
+            punch: self.punch.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 各任务最近一次推进的时间，供外部的看门狗判断是否卡死，时间都是相对start的毫秒
+pub struct Liveness {
+    start: Instant,
+    /// 主心跳任务最近一次发送心跳的时间
+    heartbeat: AtomicU64,
+    /// 主心跳任务当前的间隔(秒)
+    heartbeat_interval: AtomicU64,
+    /// tun读取线程开始处理数据的时间加1，0表示正在等待读取
+    tun_busy: AtomicU64,
+    tun_reads: AtomicU64,
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct LivenessSnapshot {
+    /// 距离上次主心跳的时间
+    pub heartbeat_age: Duration,
+    pub heartbeat_interval: Duration,
+    /// tun读取线程已经持续处理了多久，None表示正在等待读取
+    pub tun_busy: Option<Duration>,
+    /// 启动以来从tun读取的包数
+    pub tun_reads: u64,
+}
+
+impl Default for Liveness {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            heartbeat: AtomicU64::new(0),
+            heartbeat_interval: AtomicU64::new(0),
+            tun_busy: AtomicU64::new(0),
+            tun_reads: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Liveness {
+    fn now(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+    pub fn heartbeat(&self, interval: u32) {
+        self.heartbeat.store(self.now(), Ordering::Relaxed);
+        self.heartbeat_interval
+            .store(interval as u64, Ordering::Relaxed);
+    }
+    /// 读到数据，开始处理
+    #[inline]
+    pub fn tun_busy(&self) {
+        self.tun_reads.fetch_add(1, Ordering::Relaxed);
+        self.tun_busy.store(self.now() + 1, Ordering::Relaxed);
+    }
+    /// 处理完成，回到读取
+    #[inline]
+    pub fn tun_idle(&self) {
+        self.tun_busy.store(0, Ordering::Relaxed);
+    }
+    pub fn snapshot(&self) -> LivenessSnapshot {
+        let now = self.now();
+        let tun_busy = self.tun_busy.load(Ordering::Relaxed);
+        LivenessSnapshot {
+            heartbeat_age: Duration::from_millis(
+                now.saturating_sub(self.heartbeat.load(Ordering::Relaxed)),
+            ),
+            heartbeat_interval: Duration::from_secs(
+                self.heartbeat_interval.load(Ordering::Relaxed),
+            ),
+            tun_busy: if tun_busy == 0 {
+                None
+            } else {
+                Some(Duration::from_millis(now.saturating_sub(tun_busy - 1)))
+            },
+            tun_reads: self.tun_reads.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -1,24 +1,34 @@
 use std::net::Ipv4Addr;
 use std::sync::Arc;
 
+use parking_lot::RwLock;
+
 // 目标ip，子网掩码，网关
 
 #[derive(Clone)]
 pub struct ExternalRoute {
-    route_table: Arc<Vec<(u32, u32, Ipv4Addr)>>,
+    route_table: Arc<RwLock<Vec<(u32, u32, Ipv4Addr)>>>,
 }
 
 impl ExternalRoute {
     pub fn new(route_table: Vec<(u32, u32, Ipv4Addr)>) -> Self {
         Self {
-            route_table: Arc::new(route_table),
+            route_table: Arc::new(RwLock::new(route_table)),
         }
     }
+    /// 替换路由表，用于服务端下发配置
+    pub fn update(&self, route_table: Vec<(u32, u32, Ipv4Addr)>) {
+        *self.route_table.write() = route_table;
+    }
     pub fn route(&self, ip: &Ipv4Addr) -> Option<Ipv4Addr> {
+        self.entry(ip).map(|(_, _, gateway)| gateway)
+    }
+    /// 匹配的路由(目标,掩码,网关)
+    pub fn entry(&self, ip: &Ipv4Addr) -> Option<(u32, u32, Ipv4Addr)> {
         let ip = u32::from_be_bytes(ip.octets());
-        for (dest, mask, gateway) in self.route_table.iter() {
+        for (dest, mask, gateway) in self.route_table.read().iter() {
             if *mask & ip == *mask & *dest {
-                return Some(*gateway);
+                return Some((*dest, *mask, *gateway));
             }
         }
         None
@@ -5,25 +5,49 @@ use crate::channel::RouteKey;
 use crate::cipher::{Cipher, RsaCipher};
 use crate::proto::message::{HandshakeRequest, HandshakeResponse, SecretHandshakeRequest};
 use crate::protocol::body::RSA_ENCRYPTION_RESERVED;
+use crate::protocol::error_packet::InErrorPacket;
 use crate::protocol::{service_packet, NetPacket, Protocol, Version, MAX_TTL};
 use protobuf::Message;
+use sha2::Digest;
+use std::io;
 use std::io::{Read, Write};
 use std::net::TcpStream;
 use std::net::UdpSocket;
+use std::time::{Duration, Instant};
 
 pub enum HandshakeEnum {
     NotSecret,
     KeyError,
     Timeout,
+    /// 服务端负载高拒绝了握手，参数为建议等待的秒数，0表示未指定
+    RateLimited(u32),
     ServerError(String),
     Other(String),
 }
 
-fn handshake_request_packet(secret: bool) -> crate::Result<NetPacket<Vec<u8>>> {
+/// 工作量证明最大接受的难度，再高计算时间过长
+const MAX_POW_DIFFICULTY: u32 = 24;
+/// 最多回传cookie的次数，先验证源地址，负载高时再做一次工作量证明
+const MAX_COOKIE_ROUNDS: usize = 2;
+/// udp握手请求填充后的长度，服务端对未验证地址的请求只回应不超过该长度的cookie
+const HANDSHAKE_PADDED_LEN: usize = 256;
+
+fn handshake_request_packet(
+    secret: bool,
+    pow: Option<&(Vec<u8>, u64)>,
+    padded: bool,
+) -> crate::Result<NetPacket<Vec<u8>>> {
     let mut request = HandshakeRequest::new();
     request.secret = secret;
     request.version = crate::VNT_VERSION.to_string();
-    let bytes = request.write_to_bytes()?;
+    if let Some((cookie, nonce)) = pow {
+        request.cookie = cookie.clone();
+        request.pow_nonce = *nonce;
+    }
+    let mut bytes = request.write_to_bytes()?;
+    if padded {
+        pad(&mut bytes);
+    }
     let buf = vec![0u8; 12 + bytes.len()];
     let mut net_packet = NetPacket::new(buf)?;
     net_packet.set_version(Version::V1);
@@ -35,6 +59,27 @@ fn handshake_request_packet(secret: bool) -> crate::Result<NetPacket<Vec<u8>>> {
     Ok(net_packet)
 }
 
+/// 在编码后的HandshakeRequest末尾追加padding字段(第5个字段)，使整个包正好为HANDSHAKE_PADDED_LEN；
+/// padding超过127字节时长度用固定2字节的varint，否则剩余130字节时1字节的varint只能填到255，
+/// 最短的2字节varint至少要填到257，127写成2字节(0xff 0x00)是合法的protobuf编码
+fn pad(bytes: &mut Vec<u8>) {
+    const TAG: u8 = 5 << 3 | 2;
+    let rest = HANDSHAKE_PADDED_LEN.saturating_sub(12 + bytes.len());
+    // 标签和长度至少2字节
+    if rest < 2 {
+        return;
+    }
+    let padding = if rest - 2 < 0x80 {
+        bytes.extend_from_slice(&[TAG, (rest - 2) as u8]);
+        rest - 2
+    } else {
+        let padding = rest - 3;
+        bytes.extend_from_slice(&[TAG, padding as u8 | 0x80, (padding >> 7) as u8]);
+        padding
+    };
+    bytes.resize(bytes.len() + padding, 0);
+}
+
 fn secret_handshake_request_packet(
     rsa_cipher: &RsaCipher,
     token: String,
@@ -57,83 +102,183 @@ fn secret_handshake_request_packet(
     Ok(rsa_cipher.encrypt(&mut net_packet)?)
 }
 
-/// 第一次握手，拿到公钥
+/// 第一次握手，拿到公钥，服务端要求时先回传cookie验证源地址，负载高时还要完成工作量证明，
+/// 同时返回服务端的响应，其中有同一集群的其他服务端
 pub fn handshake(
     main_channel: &UdpSocket,
-    main_tcp_channel: Option<&mut TcpStream>,
+    mut main_tcp_channel: Option<&mut TcpStream>,
     server_address: SocketAddr,
     secret: bool,
-) -> Result<Option<RsaCipher>, HandshakeEnum> {
-    let request_packet = handshake_request_packet(secret).unwrap();
-    let send_buf = request_packet.buffer();
+) -> Result<(Option<RsaCipher>, HandshakeResponse), HandshakeEnum> {
+    let mut pow: Option<(Vec<u8>, u64)> = None;
+    let mut cookie_rounds = 0;
+    let padded = main_tcp_channel.is_none();
     let mut recv_buf = [0u8; 10240];
-    let len = send_recv(
-        main_channel,
-        main_tcp_channel,
-        server_address,
-        send_buf,
-        &mut recv_buf,
-    )?;
-    let net_packet = match NetPacket::new(&recv_buf[..len]) {
-        Ok(net_packet) => net_packet,
-        Err(e) => {
-            return Err(HandshakeEnum::Other(format!("net_packet {}", e)));
+    loop {
+        let request_packet = handshake_request_packet(secret, pow.as_ref(), padded).unwrap();
+        let len = send_recv(
+            main_channel,
+            main_tcp_channel.as_deref_mut(),
+            server_address,
+            request_packet.buffer(),
+            &mut recv_buf,
+        )?;
+        let net_packet = match NetPacket::new(&recv_buf[..len]) {
+            Ok(net_packet) => net_packet,
+            Err(e) => {
+                return Err(HandshakeEnum::Other(format!("net_packet {}", e)));
+            }
+        };
+        let response = match net_packet.protocol() {
+            Protocol::Service => {
+                if service_packet::Protocol::from(net_packet.transport_protocol())
+                    != service_packet::Protocol::HandshakeResponse
+                {
+                    return Err(HandshakeEnum::Other("not match".to_string()));
+                }
+                match HandshakeResponse::parse_from_bytes(net_packet.payload()) {
+                    Ok(response) => response,
+                    Err(e) => {
+                        return Err(HandshakeEnum::Other(format!("parse_from_bytes {}", e)));
+                    }
+                }
+            }
+            Protocol::Error => {
+                return match InErrorPacket::new(
+                    net_packet.transport_protocol(),
+                    net_packet.payload(),
+                ) {
+                    Ok(InErrorPacket::RateLimited(retry_after)) => {
+                        Err(HandshakeEnum::RateLimited(retry_after))
+                    }
+                    Ok(InErrorPacket::OtherError(e)) => match e.message() {
+                        Ok(str) => Err(HandshakeEnum::ServerError(str)),
+                        Err(e) => Err(HandshakeEnum::Other(format!("{}", e))),
+                    },
+                    Ok(_) => Err(HandshakeEnum::Other("not match".to_string())),
+                    Err(e) => Err(HandshakeEnum::Other(format!("{}", e))),
+                };
+            }
+            _ => {
+                return Err(HandshakeEnum::Other("not match".to_string()));
+            }
+        };
+        if response.cookie.is_empty() {
+            let rsa_cipher = handshake_response(&response, secret)?;
+            return Ok((rsa_cipher, response));
         }
-    };
-    match net_packet.protocol() {
-        Protocol::Service => {
-            match service_packet::Protocol::from(net_packet.transport_protocol()) {
-                service_packet::Protocol::HandshakeResponse => {
-                    match HandshakeResponse::parse_from_bytes(net_packet.payload()) {
-                        Ok(response) => {
-                            if !response.secret && secret {
-                                //客户端要加密，服务端不支持加密
-                                return Err(HandshakeEnum::NotSecret);
-                            }
-                            if secret {
-                                //转换公钥
-                                match RsaCipher::new(&response.public_key) {
-                                    Ok(rsa) => {
-                                        match rsa.finger() {
-                                            Ok(finger) => {
-                                                if finger != response.key_finger {
-                                                    return Err(HandshakeEnum::Other(
-                                                        "finger error".to_string(),
-                                                    ));
-                                                }
-                                            }
-                                            Err(e) => {
-                                                return Err(HandshakeEnum::Other(format!(
-                                                    "finger {}",
-                                                    e
-                                                )));
-                                            }
-                                        }
-                                        Ok(Some(rsa))
-                                    }
-                                    Err(e) => {
-                                        return Err(HandshakeEnum::Other(format!(
-                                            "RsaCipher {}",
-                                            e
-                                        )));
-                                    }
-                                }
-                            } else {
-                                Ok(None)
-                            }
-                        }
-                        Err(e) => {
-                            return Err(HandshakeEnum::Other(format!("parse_from_bytes {}", e)));
+        cookie_rounds += 1;
+        if cookie_rounds > MAX_COOKIE_ROUNDS {
+            //回传cookie后服务端仍然要求验证，说明cookie已过期或者证明无效
+            return Err(HandshakeEnum::Other("cookie rejected".to_string()));
+        }
+        let nonce = if response.pow_difficulty == 0 {
+            //只验证源地址，原样回传cookie
+            log::info!("服务端要求回传cookie验证地址");
+            0
+        } else {
+            match solve_pow(&response.cookie, response.pow_difficulty) {
+                Some(nonce) => {
+                    log::info!(
+                        "服务端要求工作量证明,difficulty={}",
+                        response.pow_difficulty
+                    );
+                    nonce
+                }
+                None => {
+                    return Err(HandshakeEnum::Other(format!(
+                        "pow difficulty too high {}",
+                        response.pow_difficulty
+                    )));
+                }
+            }
+        };
+        pow = Some((response.cookie, nonce));
+    }
+}
+
+fn handshake_response(
+    response: &HandshakeResponse,
+    secret: bool,
+) -> Result<Option<RsaCipher>, HandshakeEnum> {
+    if !response.secret && secret {
+        //客户端要加密，服务端不支持加密
+        return Err(HandshakeEnum::NotSecret);
+    }
+    if secret {
+        //转换公钥
+        match RsaCipher::new(&response.public_key) {
+            Ok(rsa) => {
+                match rsa.finger() {
+                    Ok(finger) => {
+                        if finger != response.key_finger {
+                            return Err(HandshakeEnum::Other("finger error".to_string()));
                         }
                     }
+                    Err(e) => {
+                        return Err(HandshakeEnum::Other(format!("finger {}", e)));
+                    }
                 }
-                _ => {
-                    return Err(HandshakeEnum::Other("not match".to_string()));
-                }
+                Ok(Some(rsa))
+            }
+            Err(e) => {
+                return Err(HandshakeEnum::Other(format!("RsaCipher {}", e)));
             }
         }
-        _ => {
-            return Err(HandshakeEnum::Other("not match".to_string()));
+    } else {
+        Ok(None)
+    }
+}
+
+/// 找到使sha256(cookie + nonce小端)至少有difficulty个前导零比特的nonce
+fn solve_pow(cookie: &[u8], difficulty: u32) -> Option<u64> {
+    if difficulty > MAX_POW_DIFFICULTY {
+        return None;
+    }
+    for nonce in 0..u64::MAX {
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(cookie);
+        hasher.update(nonce.to_le_bytes());
+        if leading_zeros(&hasher.finalize()) >= difficulty {
+            return Some(nonce);
+        }
+    }
+    None
+}
+
+fn leading_zeros(hash: &[u8]) -> u32 {
+    let mut count = 0;
+    for b in hash {
+        if *b != 0 {
+            return count + b.leading_zeros();
+        }
+        count += 8;
+    }
+    count
+}
+
+/// 用一次不加密的握手测量到服务端的延迟，只用于选择服务端，
+/// 使用临时的udp，避免迟到的响应干扰主通道上的握手
+pub fn probe_rtt(server_address: SocketAddr, timeout: Duration) -> io::Result<Duration> {
+    let udp = if server_address.is_ipv4() {
+        UdpSocket::bind("0.0.0.0:0")?
+    } else {
+        UdpSocket::bind("[::]:0")?
+    };
+    crate::util::fwmark::mark(&udp)?;
+    udp.set_read_timeout(Some(timeout))?;
+    let request_packet = handshake_request_packet(false, None, true)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))?;
+    let start = Instant::now();
+    crate::util::mimicry::send_to(&udp, request_packet.buffer(), server_address)?;
+    let mut recv_buf = [0u8; 10240];
+    loop {
+        let (_, addr) = udp.recv_from(&mut recv_buf)?;
+        if addr == server_address {
+            return Ok(start.elapsed());
+        }
+        if start.elapsed() >= timeout {
+            return Err(io::Error::from(io::ErrorKind::TimedOut));
         }
     }
 }
@@ -178,10 +323,10 @@ fn send_recv(
         } else {
             server_address
         };
-        if let Err(e) = main_channel.send_to(send_buf, server_address) {
+        if let Err(e) = crate::util::mimicry::send_to(main_channel, send_buf, server_address) {
             return Err(HandshakeEnum::Other(format!("send error:{}", e)));
         }
-        match main_channel.recv_from(recv_buf) {
+        match crate::util::mimicry::recv_from(main_channel, recv_buf) {
             Ok((len, addr)) => {
                 if server_address != addr {
                     log::warn!("请求{:?}和响应{:?}地址不一致", server_address, addr);
@@ -260,3 +405,48 @@ pub fn secret_handshake_req(
     }
     Ok(())
 }
+
+#[test]
+fn test_handshake_padding() {
+    let cookies = [vec![], vec![7u8; 16], vec![7u8; 32], vec![7u8; 100]];
+    for cookie in cookies {
+        for nonce in [0u64, 1, 300, u64::MAX] {
+            let pow = (cookie.clone(), nonce);
+            let pow = if cookie.is_empty() { None } else { Some(&pow) };
+            for secret in [false, true] {
+                let packet = handshake_request_packet(secret, pow, true).unwrap();
+                assert_eq!(packet.buffer().len(), HANDSHAKE_PADDED_LEN);
+                let request = HandshakeRequest::parse_from_bytes(packet.payload()).unwrap();
+                assert_eq!(request.cookie, cookie);
+                assert_eq!(request.pow_nonce, if pow.is_some() { nonce } else { 0 });
+            }
+        }
+    }
+    assert!(
+        handshake_request_packet(false, None, false)
+            .unwrap()
+            .buffer()
+            .len()
+            < HANDSHAKE_PADDED_LEN
+    );
+    // 覆盖所有剩余长度，包括varint从1字节变为2字节的边界
+    for len in 0..HANDSHAKE_PADDED_LEN {
+        let mut request = HandshakeRequest::new();
+        request.version = "v".repeat(len);
+        let mut bytes = request.write_to_bytes().unwrap();
+        let unpadded = bytes.len();
+        pad(&mut bytes);
+        if 12 + unpadded + 2 <= HANDSHAKE_PADDED_LEN {
+            assert_eq!(12 + bytes.len(), HANDSHAKE_PADDED_LEN);
+        } else {
+            assert_eq!(bytes.len(), unpadded);
+        }
+        let parsed = HandshakeRequest::parse_from_bytes(&bytes).unwrap();
+        assert_eq!(parsed.version, request.version);
+        let added = bytes.len() - unpadded;
+        if added > 0 {
+            let header = if added - 2 < 0x80 { 2 } else { 3 };
+            assert_eq!(parsed.padding.len(), added - header);
+        }
+    }
+}
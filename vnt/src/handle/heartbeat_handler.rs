@@ -1,22 +1,27 @@
 use std::io;
-use std::net::{Ipv4Addr, ToSocketAddrs};
+use std::net::Ipv4Addr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crossbeam_utils::atomic::AtomicCell;
 use parking_lot::Mutex;
 use rand::prelude::SliceRandom;
 
 use crate::channel::idle::Idle;
+use crate::channel::self_test::{SelfTest, SelfTestChange, REPLY_TIMEOUT};
 use crate::channel::sender::ChannelSender;
 use crate::channel::Route;
 use crate::cipher::Cipher;
 use crate::core::status::VntWorker;
+use crate::event::EventKind;
 use crate::handle::{CurrentDeviceInfo, PeerDeviceInfo};
 use crate::protocol::body::ENCRYPTION_RESERVED;
 use crate::protocol::control_packet::PingPacket;
 use crate::protocol::{control_packet, NetPacket, Protocol, Version, MAX_TTL};
 
+/// 服务端超时后连续多少次心跳仍没有响应时换下一个监听地址
+const SWITCH_AFTER_TIMEOUTS: usize = 5;
+
 pub fn start_idle(mut worker: VntWorker, idle: Idle, sender: ChannelSender) {
     tokio::spawn(async move {
         tokio::select! {
@@ -65,21 +70,187 @@ pub fn start_heartbeat(
     });
 }
 
+/// 直连路径的质量探测间隔
+const PROBE_INTERVAL: Duration = Duration::from_secs(2);
+
+pub fn start_probe(
+    mut worker: VntWorker,
+    sender: ChannelSender,
+    device_list: Arc<Mutex<(u16, Vec<PeerDeviceInfo>)>>,
+    current_device: Arc<AtomicCell<CurrentDeviceInfo>>,
+    client_cipher: Cipher,
+    server_cipher: Cipher,
+) {
+    tokio::spawn(async move {
+        tokio::select! {
+             _=worker.stop_wait()=>{
+                    return;
+             }
+            rs=start_probe_(sender, device_list, current_device,client_cipher,server_cipher)=>{
+                if let Err(e) = rs {
+                    log::warn!("直连路径探测任务停止:{:?}", e);
+                }
+            }
+        }
+        worker.stop_all();
+    });
+}
+
+/// 定时探测所有直连路径的丢包和延迟，质量变差时数据改走中转，恢复后切回
+async fn start_probe_(
+    sender: ChannelSender,
+    device_list: Arc<Mutex<(u16, Vec<PeerDeviceInfo>)>>,
+    current_device: Arc<AtomicCell<CurrentDeviceInfo>>,
+    client_cipher: Cipher,
+    server_cipher: Cipher,
+) -> io::Result<()> {
+    log::info!("启动直连路径探测任务");
+    loop {
+        if sender.is_close() {
+            return Ok(());
+        }
+        let src = current_device.load().virtual_ip();
+        for (peer_ip, routes) in sender.route_table() {
+            if !sender.is_active(&peer_ip) || sender.is_grace_expired() {
+                continue;
+            }
+            for route in routes {
+                if !route.is_p2p() {
+                    continue;
+                }
+                let packet = heartbeat_packet(
+                    MAX_TTL,
+                    &device_list,
+                    &client_cipher,
+                    &server_cipher,
+                    false,
+                    src,
+                    peer_ip,
+                );
+                let route_key = route.route_key();
+                if let Err(e) = sender.try_send_by_key(packet.buffer(), &route_key) {
+                    log::warn!("peer_ip:{:?},route:{:?},e:{:?}", peer_ip, route, e);
+                }
+                sender.on_probe_sent(&peer_ip, &route_key);
+            }
+        }
+        tokio::time::sleep(PROBE_INTERVAL).await;
+    }
+}
+
+pub fn start_self_test(
+    mut worker: VntWorker,
+    sender: ChannelSender,
+    self_test: Arc<SelfTest>,
+    device_list: Arc<Mutex<(u16, Vec<PeerDeviceInfo>)>>,
+    current_device: Arc<AtomicCell<CurrentDeviceInfo>>,
+    client_cipher: Cipher,
+    server_cipher: Cipher,
+) {
+    tokio::spawn(async move {
+        tokio::select! {
+             _=worker.stop_wait()=>{
+                    return;
+             }
+            rs=start_self_test_(sender, self_test, device_list, current_device,client_cipher,server_cipher)=>{
+                if let Err(e) = rs {
+                    log::warn!("自检任务停止:{:?}", e);
+                }
+            }
+        }
+        worker.stop_all();
+    });
+}
+
+/// 定时ping自检的设备，有直连时走直连，否则经服务端中转，和数据的路径一致
+async fn start_self_test_(
+    sender: ChannelSender,
+    self_test: Arc<SelfTest>,
+    device_list: Arc<Mutex<(u16, Vec<PeerDeviceInfo>)>>,
+    current_device: Arc<AtomicCell<CurrentDeviceInfo>>,
+    client_cipher: Cipher,
+    server_cipher: Cipher,
+) -> io::Result<()> {
+    log::info!(
+        "启动自检任务 peers:{:?},interval:{:?}",
+        self_test.peers(),
+        self_test.interval()
+    );
+    loop {
+        tokio::time::sleep(self_test.interval()).await;
+        if sender.is_close() {
+            return Ok(());
+        }
+        for peer_ip in self_test.peers() {
+            let current_dev = current_device.load();
+            let packet = heartbeat_packet(
+                MAX_TTL,
+                &device_list,
+                &client_cipher,
+                &server_cipher,
+                false,
+                current_dev.virtual_ip(),
+                *peer_ip,
+            );
+            let start = Instant::now();
+            let rs = match sender.route_one(peer_ip) {
+                Some(route) => sender.try_send_by_key(packet.buffer(), &route.route_key()),
+                None => sender.send_main(packet.buffer(), current_dev.connect_server),
+            };
+            let mut rtt = None;
+            match rs {
+                Ok(_) => {
+                    while start.elapsed() < REPLY_TIMEOUT {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                        if sender.last_pong(peer_ip).map_or(false, |v| v >= start) {
+                            rtt = Some(start.elapsed());
+                            break;
+                        }
+                    }
+                }
+                Err(e) => {
+                    log::warn!("自检 peer_ip:{:?},e:{:?}", peer_ip, e);
+                }
+            }
+            match self_test.update(*peer_ip, rtt) {
+                Some(SelfTestChange::Failed(failures)) => {
+                    log::warn!("自检失败 peer_ip:{:?},连续{}次", peer_ip, failures);
+                    sender.events().push(
+                        EventKind::SelfTest,
+                        Some(*peer_ip),
+                        format!("self-test failed {} times in a row", failures),
+                    );
+                }
+                Some(SelfTestChange::Recovered) => {
+                    log::info!("自检恢复 peer_ip:{:?}", peer_ip);
+                    sender.events().push(
+                        EventKind::SelfTest,
+                        Some(*peer_ip),
+                        "self-test recovered".to_string(),
+                    );
+                }
+                None => {}
+            }
+        }
+    }
+}
+
 pub fn start_heartbeat_main(
     mut worker: VntWorker,
     sender: ChannelSender,
     device_list: Arc<Mutex<(u16, Vec<PeerDeviceInfo>)>>,
     current_device: Arc<AtomicCell<CurrentDeviceInfo>>,
-    server_address_str: String,
+    server_addresses: Vec<String>,
     client_cipher: Cipher,
     server_cipher: Cipher,
+    keepalive: Arc<AtomicCell<u32>>,
 ) {
     tokio::spawn(async move {
         tokio::select! {
              _=worker.stop_wait()=>{
                     return;
              }
-             rs=start_heartbeat_main_(sender, device_list, current_device,server_address_str,client_cipher,server_cipher)=>{
+             rs=start_heartbeat_main_(sender, device_list, current_device,server_addresses,client_cipher,server_cipher,keepalive)=>{
                 if let Err(e) = rs {
                     log::warn!("主心跳任务停止:{:?}", e);
                 }
@@ -89,7 +260,7 @@ pub fn start_heartbeat_main(
     });
 }
 
-fn heartbeat_packet(
+pub(crate) fn heartbeat_packet(
     ttl: u8,
     device_list: &Mutex<(u16, Vec<PeerDeviceInfo>)>,
     client_cipher: &Cipher,
@@ -124,11 +295,16 @@ async fn start_heartbeat_main_(
     sender: ChannelSender,
     device_list: Arc<Mutex<(u16, Vec<PeerDeviceInfo>)>>,
     current_device: Arc<AtomicCell<CurrentDeviceInfo>>,
-    server_address_str: String,
+    server_addresses: Vec<String>,
     client_cipher: Cipher,
     server_cipher: Cipher,
+    keepalive: Arc<AtomicCell<u32>>,
 ) -> io::Result<()> {
     let mut count = 0;
+    //当前使用的服务端地址，0是配置的地址，其余是服务端通告的其他监听地址
+    let mut index = 0;
+    let mut timeout_count = 0;
+    let mut lost_state = LostState::Reachable;
     log::info!("启动主心跳任务");
     loop {
         if sender.is_close() {
@@ -136,20 +312,33 @@ async fn start_heartbeat_main_(
         }
         let mut current_dev = current_device.load();
         let src = current_dev.virtual_ip();
-        if count % 40 == 19 {
-            if let Ok(mut addr) = server_address_str.to_socket_addrs() {
-                if let Some(addr) = addr.next() {
-                    if addr != current_dev.connect_server {
-                        let mut tmp = current_dev.clone();
-                        tmp.connect_server = addr;
-                        log::info!(
-                            "服务端地址变化,旧地址:{}，新地址:{}",
-                            current_dev.connect_server,
-                            addr
-                        );
-                        if current_device.compare_exchange(current_dev, tmp).is_ok() {
-                            current_dev.connect_server = addr;
-                        }
+        if sender.is_server_timeout() {
+            timeout_count += 1;
+        } else {
+            timeout_count = 0;
+            crate::util::resolve::mark_ok(current_dev.connect_server);
+        }
+        report_server_lost(&sender, &mut lost_state);
+        let switch = server_addresses.len() > 1 && timeout_count >= SWITCH_AFTER_TIMEOUTS;
+        if switch {
+            //当前地址不通，可能是所在网络封锁了该端口，换服务端的下一个监听地址
+            timeout_count = 0;
+            index = (index + 1) % server_addresses.len();
+            log::info!("服务端连接超时，尝试地址{}", server_addresses[index]);
+        }
+        //定时重新解析，刚超时的时候也立即解析一次，ddns的地址可能已经变了
+        if switch || count % 40 == 19 || timeout_count == 1 {
+            if let Ok(addr) = crate::util::resolve::lookup_one(&server_addresses[index]) {
+                if addr != current_dev.connect_server {
+                    let mut tmp = current_dev.clone();
+                    tmp.connect_server = addr;
+                    log::info!(
+                        "服务端地址变化,旧地址:{}，新地址:{}",
+                        current_dev.connect_server,
+                        addr
+                    );
+                    if current_device.compare_exchange(current_dev, tmp).is_ok() {
+                        current_dev.connect_server = addr;
                     }
                 }
             }
@@ -167,10 +356,55 @@ async fn start_heartbeat_main_(
             log::warn!("connect_server:{:?},e:{:?}", current_dev.connect_server, e);
         }
         count += 1;
-        tokio::time::sleep(Duration::from_millis(3000)).await;
+        // 间隔可由服务端下发
+        let interval = keepalive.load();
+        sender.liveness().heartbeat(interval);
+        tokio::time::sleep(Duration::from_secs(interval as u64)).await;
     }
 }
 
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum LostState {
+    Reachable,
+    Grace,
+    Expired,
+}
+
+/// 和服务端失联、宽限期结束、恢复时各记录一次事件
+fn report_server_lost(sender: &ChannelSender, state: &mut LostState) {
+    let (new_state, detail) = match sender.server_lost() {
+        None => (LostState::Reachable, "server reachable again".to_string()),
+        Some(lost) => match lost.remaining {
+            Some(remaining) if remaining.is_zero() => (
+                LostState::Expired,
+                "server lost, grace period expired, peer traffic suspended".to_string(),
+            ),
+            Some(remaining) => (
+                LostState::Grace,
+                format!(
+                    "server lost, keeping direct peers for {}s",
+                    remaining.as_secs()
+                ),
+            ),
+            None => (
+                LostState::Grace,
+                "server lost, keeping direct peers".to_string(),
+            ),
+        },
+    };
+    if new_state == *state {
+        return;
+    }
+    *state = new_state;
+    log::warn!("{}", detail);
+    let kind = if new_state == LostState::Reachable {
+        EventKind::Connected
+    } else {
+        EventKind::Disconnected
+    };
+    sender.events().push(kind, None, detail);
+}
+
 async fn start_heartbeat_(
     sender: ChannelSender,
     device_list: Arc<Mutex<(u16, Vec<PeerDeviceInfo>)>>,
@@ -184,6 +418,11 @@ async fn start_heartbeat_(
         if sender.is_close() {
             return Ok(());
         }
+        if sender.is_grace_expired() {
+            //宽限期已过，不再维持直连，路由空闲后自动移除
+            tokio::time::sleep(Duration::from_millis(3000)).await;
+            continue;
+        }
         let current_dev = current_device.load();
         //如果和服务端使用tcp连接，则维持udp洞的频率要更高些
         if (sender.is_main_tcp() && count % 4 == 0) || (!sender.is_main_tcp() && count % 40 == 1) {
@@ -202,10 +441,14 @@ async fn start_heartbeat_(
         let src = current_dev.virtual_ip();
 
         if count % 10 == 7 {
+            for peer_ip in sender.reap_idle() {
+                log::info!("设备空闲,回收直连路由 peer_ip:{:?}", peer_ip);
+            }
             let mut route_list: Option<Vec<(Ipv4Addr, Vec<Route>)>> = None;
             let peer_list = { device_list.lock().1.clone() };
             for peer in peer_list {
-                if peer.virtual_ip == current_dev.virtual_ip {
+                if peer.virtual_ip == current_dev.virtual_ip || !sender.is_active(&peer.virtual_ip)
+                {
                     continue;
                 }
                 let client_packet = heartbeat_packet(
@@ -272,7 +515,7 @@ async fn start_heartbeat_(
             }
         } else {
             for (peer_ip, route_list) in sender.route_table().iter() {
-                if peer_ip == &current_dev.virtual_gateway {
+                if peer_ip == &current_dev.virtual_gateway || !sender.is_active(peer_ip) {
                     continue;
                 }
                 let client_packet = heartbeat_packet(
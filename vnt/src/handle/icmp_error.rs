@@ -0,0 +1,89 @@
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use packet::icmp::icmp::IcmpPacket;
+use packet::icmp::{DestinationUnreachable, Kind};
+use packet::ip::ipv4::packet::IpV4Packet;
+use packet::ip::ipv4::protocol::Protocol;
+
+use crate::channel::path_policy::PathPolicy;
+use crate::channel::sender::ChannelSender;
+
+/// 每秒最多生成的icmp差错报文
+const MAX_PER_SECOND: u32 = 100;
+
+static WINDOW: AtomicU64 = AtomicU64::new(0);
+static COUNT: AtomicU32 = AtomicU32::new(0);
+
+/// 目标离线、和服务端失联超过宽限期或者被策略拒绝时的icmp不可达代码，可以发送时返回None
+/// routed表示目标经其他设备的子网路由转发
+pub fn unreachable_code(sender: &ChannelSender, dest_ip: &Ipv4Addr, routed: bool) -> Option<u8> {
+    if sender.is_offline(dest_ip) || sender.is_grace_expired() {
+        return Some(if routed {
+            DestinationUnreachable::DestinationNetworkUnreachable.into()
+        } else {
+            DestinationUnreachable::DestinationHostUnreachable.into()
+        });
+    }
+    if sender.is_plaintext_refused(dest_ip)
+        || (sender.path_policy().get(dest_ip) == PathPolicy::NeverRelay
+            && !sender.has_route(dest_ip))
+    {
+        return Some(DestinationUnreachable::CommunicationAdministrativelyProhibited.into());
+    }
+    None
+}
+
+fn allow() -> bool {
+    let now = crate::handle::now_time() / 1000;
+    if WINDOW.swap(now, Ordering::Relaxed) != now {
+        COUNT.store(0, Ordering::Relaxed);
+    }
+    COUNT.fetch_add(1, Ordering::Relaxed) < MAX_PER_SECOND
+}
+
+/// 生成回应original的icmp差错报文，from为回应的源地址(不可达的设备或者转发的设备)，
+/// 前面预留head_reserve字节。
+/// 不回应icmp差错报文和非首个分片，避免差错报文循环，超出频率限制时也返回None
+pub fn build(
+    original: &[u8],
+    from: Ipv4Addr,
+    kind: Kind,
+    code: u8,
+    head_reserve: usize,
+) -> Option<Vec<u8>> {
+    let ipv4 = IpV4Packet::new(original).ok()?;
+    if ipv4.offset() != 0 {
+        return None;
+    }
+    if ipv4.protocol() == Protocol::Icmp {
+        let icmp = IcmpPacket::new(ipv4.payload()).ok()?;
+        if !matches!(icmp.kind(), Kind::EchoRequest | Kind::TimestampRequest) {
+            return None;
+        }
+    }
+    if !allow() {
+        return None;
+    }
+    // 原报文的ip头和前8字节数据
+    let quote_len = (ipv4.header_len() as usize * 4 + 8).min(original.len());
+    let len = 20 + 8 + quote_len;
+    let mut buf = vec![0u8; head_reserve + len];
+    {
+        let buf = &mut buf[head_reserve..];
+        buf[0] = 0b0100_0101;
+        buf[2..4].copy_from_slice(&(len as u16).to_be_bytes());
+        buf[8] = 64;
+        buf[28..].copy_from_slice(&original[..quote_len]);
+        let mut icmp = IcmpPacket::unchecked(&mut buf[20..]);
+        icmp.set_kind(kind);
+        icmp.buffer[1] = code;
+        icmp.update_checksum();
+        let mut ipv4_packet = IpV4Packet::unchecked(buf);
+        ipv4_packet.set_protocol(Protocol::Icmp);
+        ipv4_packet.set_source_ip(from);
+        ipv4_packet.set_destination_ip(ipv4.source_ip());
+        ipv4_packet.update_checksum();
+    }
+    Some(buf)
+}
@@ -1,7 +1,9 @@
 use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
 
 pub mod handshake_handler;
 pub mod heartbeat_handler;
+pub(crate) mod icmp_error;
 pub mod punch_handler;
 pub mod recv_handler;
 pub mod registration_handler;
@@ -22,23 +24,79 @@ fn check_dest(dest: Ipv4Addr, virtual_netmask: Ipv4Addr, virtual_network: Ipv4Ad
         == u32::from_be_bytes(virtual_network.octets())
 }
 
+/// 掩码长度，掩码不连续时返回None
+pub fn prefix_len(netmask: Ipv4Addr) -> Option<u8> {
+    let mask = u32::from(netmask);
+    let len = mask.leading_ones();
+    if mask.checked_shl(len).unwrap_or(0) != 0 {
+        return None;
+    }
+    Some(len as u8)
+}
+
+/// 检查服务端分配的网段，至少要容纳网关和当前设备，并且都在同一个网段内
+pub fn check_network(
+    virtual_ip: Ipv4Addr,
+    virtual_gateway: Ipv4Addr,
+    virtual_netmask: Ipv4Addr,
+) -> Result<u8, String> {
+    let prefix = match prefix_len(virtual_netmask) {
+        Some(prefix) if prefix > 0 && prefix <= 30 => prefix,
+        _ => return Err(format!("invalid netmask {}", virtual_netmask)),
+    };
+    let network = u32::from(virtual_gateway) & u32::from(virtual_netmask);
+    if !check_dest(virtual_ip, virtual_netmask, Ipv4Addr::from(network)) {
+        return Err(format!(
+            "ip {} not in network {}/{}",
+            virtual_ip, virtual_gateway, prefix
+        ));
+    }
+    Ok(prefix)
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct PeerDeviceInfo {
     pub virtual_ip: Ipv4Addr,
     pub name: String,
     pub status: PeerDeviceStatus,
     pub client_secret: bool,
+    /// 服务端给设备打的标签，如 role:server、team:dev
+    pub tags: Vec<String>,
+    /// 设备发布的子网，(目标,掩码)
+    pub routes: Vec<(u32, u32)>,
 }
 
 impl PeerDeviceInfo {
-    pub fn new(virtual_ip: Ipv4Addr, name: String, status: u8, client_secret: bool) -> Self {
+    pub fn new(
+        virtual_ip: Ipv4Addr,
+        name: String,
+        status: u8,
+        client_secret: bool,
+        tags: Vec<String>,
+        routes: Vec<String>,
+    ) -> Self {
+        let routes = routes
+            .iter()
+            .filter_map(|v| match crate::remote_config::parse_cidr(v) {
+                Ok(route) => Some(route),
+                Err(e) => {
+                    log::warn!("设备{}发布的路由无效 {},{:?}", virtual_ip, v, e);
+                    None
+                }
+            })
+            .collect();
         Self {
             virtual_ip,
             name,
             status: PeerDeviceStatus::from(status),
             client_secret,
+            tags,
+            routes,
         }
     }
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|v| v == tag)
+    }
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
@@ -69,6 +127,17 @@ impl From<u8> for PeerDeviceStatus {
 pub enum ConnectStatus {
     Connecting,
     Connected,
+    /// 和服务端失联，宽限期内已经打通的直连继续使用
+    Degraded,
+}
+
+/// 和服务端失联的状态
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ServerLost {
+    /// 失联的时长，从判定服务端超时开始计算
+    pub elapsed: Duration,
+    /// 直连剩余的宽限时间，为0时已经停用，None表示不限制
+    pub remaining: Option<Duration>,
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -114,4 +183,8 @@ impl CurrentDeviceInfo {
     pub fn virtual_gateway(&self) -> Ipv4Addr {
         self.virtual_gateway
     }
+    /// 虚拟网络的掩码长度
+    pub fn prefix_len(&self) -> u8 {
+        prefix_len(self.virtual_netmask).unwrap_or(0)
+    }
 }
@@ -1,3 +1,6 @@
+use crate::channel::capability::Capabilities;
+use crate::channel::ice::{self, Candidate};
+use crate::channel::path_policy::PathPolicy;
 use crate::channel::punch::{NatInfo, Punch};
 use crate::channel::sender::ChannelSender;
 use crate::cipher::Cipher;
@@ -8,11 +11,12 @@ use crate::proto::message::{PunchInfo, PunchNatType};
 use crate::protocol::body::ENCRYPTION_RESERVED;
 use crate::protocol::{control_packet, other_turn_packet, NetPacket, Protocol, Version, MAX_TTL};
 use crossbeam_utils::atomic::AtomicCell;
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLock};
 use protobuf::Message;
 use rand::prelude::SliceRandom;
+use std::collections::HashMap;
 use std::io;
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, SocketAddrV4};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc::Receiver;
@@ -80,6 +84,7 @@ pub async fn start_punch(
     mut worker: VntWorker,
     nat_test: NatTest,
     device_list: Arc<Mutex<(u16, Vec<PeerDeviceInfo>)>>,
+    peer_nat_info_map: Arc<RwLock<HashMap<Ipv4Addr, NatInfo>>>,
     sender: ChannelSender,
     current_device: Arc<AtomicCell<CurrentDeviceInfo>>,
     client_cipher: Cipher,
@@ -93,7 +98,7 @@ pub async fn start_punch(
         }
         tokio::select! {
             rs= start_punch_(Duration::from_secs(sleep_time[num % sleep_time.len()]),&nat_test, &device_list,
-                &sender, &current_device,&client_cipher)=>{
+                &peer_nat_info_map, &sender, &current_device,&client_cipher)=>{
                  if let Err(e) = rs {
                     log::warn!("打洞处理任务异常 {:?}", e);
                 }
@@ -110,36 +115,92 @@ async fn start_punch_(
     sleep_time: Duration,
     nat_test: &NatTest,
     device_list: &Arc<Mutex<(u16, Vec<PeerDeviceInfo>)>>,
+    peer_nat_info_map: &Arc<RwLock<HashMap<Ipv4Addr, NatInfo>>>,
     sender: &ChannelSender,
     current_device: &Arc<AtomicCell<CurrentDeviceInfo>>,
     client_cipher: &Cipher,
 ) -> crate::Result<()> {
     let current_device = current_device.load();
     let nat_info = nat_test.nat_info();
-    let mut list = device_list.lock().clone().1;
+    let mut list: Vec<Ipv4Addr> = device_list
+        .lock()
+        .1
+        .iter()
+        .map(|info| info.virtual_ip)
+        .collect();
+    //配置了静态地址的设备，服务端不可达时设备列表中可能没有
+    for ip in sender.rendezvous().static_peers() {
+        if !list.contains(&ip) {
+            list.push(ip);
+        }
+    }
     list.shuffle(&mut rand::thread_rng());
+    let server_timeout = sender.is_server_timeout();
     let mut count = 0;
-    for info in list {
-        if info.virtual_ip <= current_device.virtual_ip {
+    let lazy = sender.is_lazy();
+    // 数据正在经服务端中转的设备，不受数量和发起方的限制，立即和中转赛跑
+    let racing = sender.take_racing();
+    for peer_ip in list {
+        let racing = racing.contains(&peer_ip);
+        if peer_ip == current_device.virtual_ip {
+            continue;
+        }
+        if lazy {
+            // 按需连接时由有数据通信的一方发起
+            if !sender.is_active(&peer_ip) {
+                continue;
+            }
+        } else if !racing && peer_ip <= current_device.virtual_ip {
             continue;
         }
-        if !sender.need_punch(&info.virtual_ip) {
+        if !sender.need_punch(&peer_ip) || sender.is_plaintext_refused(&peer_ip) {
             continue;
         }
-        count += 1;
-        if count > 2 {
+        let policy = sender.path_policy().get(&peer_ip);
+        if policy == PathPolicy::PreferRelay && !server_timeout {
+            continue;
+        }
+        if !racing && policy != PathPolicy::PreferDirect {
+            count += 1;
+            if count > 2 {
+                continue;
+            }
+        }
+        if !sender.punch_limiter().try_acquire() {
+            log::debug!("打洞请求太频繁，本轮暂停发送");
             break;
         }
         let packet = punch_packet(
             client_cipher,
             current_device.virtual_ip(),
             &nat_info,
-            info.virtual_ip,
+            peer_ip,
+            sender.turn_relayed_addr(),
+            sender.capabilities().local(),
         )
         .unwrap();
-        let _ = sender.send_main(packet.buffer(), current_device.connect_server);
+        if !server_timeout {
+            let _ = sender.send_main(packet.buffer(), current_device.connect_server);
+        }
+        //服务端不可达时发到设备最近一次的地址，配置了静态地址的设备总是直接发送
+        if server_timeout || sender.rendezvous().has_static(&peer_ip) {
+            let peer_nat_info = if server_timeout {
+                peer_nat_info_map.read().get(&peer_ip).cloned()
+            } else {
+                None
+            };
+            for addr in sender.rendezvous().addrs(&peer_ip, peer_nat_info.as_ref()) {
+                if let Err(e) = sender.send_main_udp(packet.buffer(), addr) {
+                    log::debug!("直接发送打洞信息失败 {},{},{:?}", peer_ip, addr, e);
+                }
+            }
+        }
+    }
+    tokio::select! {
+        _=tokio::time::sleep(sleep_time)=>{}
+        _=sender.wait_active(), if lazy=>{}
+        _=sender.wait_race()=>{}
     }
-    tokio::time::sleep(sleep_time).await;
     Ok(())
 }
 
@@ -148,6 +209,8 @@ pub fn punch_packet(
     virtual_ip: Ipv4Addr,
     nat_info: &NatInfo,
     dest: Ipv4Addr,
+    turn_addr: Option<SocketAddrV4>,
+    capabilities: &Capabilities,
 ) -> crate::Result<NetPacket<Vec<u8>>> {
     let mut punch_reply = PunchInfo::new();
     punch_reply.reply = false;
@@ -165,7 +228,17 @@ pub fn punch_packet(
         punch_reply.ipv6_port = nat_info.udp_port as u32;
         punch_reply.ipv6 = ipv6.octets().to_vec();
     }
+    if let Some(turn_addr) = turn_addr {
+        punch_reply.turn_ip = u32::from(*turn_addr.ip());
+        punch_reply.turn_port = turn_addr.port() as u32;
+    }
+    punch_reply.candidates = ice::gather(nat_info, turn_addr)
+        .iter()
+        .map(Candidate::to_proto)
+        .collect();
     punch_reply.nat_type = protobuf::EnumOrUnknown::new(PunchNatType::from(nat_info.nat_type));
+    punch_reply.capabilities = protobuf::MessageField::some(capabilities.to_proto());
+    punch_reply.time = crate::handle::now_time();
     let bytes = punch_reply.write_to_bytes()?;
     let mut net_packet = NetPacket::new_encrypt(vec![0u8; 12 + bytes.len() + ENCRYPTION_RESERVED])?;
     net_packet.set_version(Version::V1);
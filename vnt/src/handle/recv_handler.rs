@@ -12,28 +12,44 @@ use packet::icmp::{icmp, Kind};
 use packet::ip::ipv4;
 use packet::ip::ipv4::packet::IpV4Packet;
 
+use crate::channel::capability::Capabilities;
 use crate::channel::channel::Context;
+use crate::channel::ice::{self, Candidate};
+use crate::channel::malformed;
 use crate::channel::punch::{NatInfo, NatType};
+use crate::channel::turn::turn_addr;
 use crate::channel::{Route, RouteKey};
 use crate::cipher::{Cipher, RsaCipher};
 use crate::error::Error;
+use crate::event::EventKind;
 use crate::external_route::AllowExternalRoute;
 use crate::handle::handshake_handler::secret_handshake_req;
+use crate::handle::heartbeat_handler::heartbeat_packet;
 use crate::handle::registration_handler::Register;
-use crate::handle::{ConnectStatus, CurrentDeviceInfo, PeerDeviceInfo, PeerDeviceStatus};
+use crate::handle::tun_tap::bridge::MacTable;
+use crate::handle::{
+    check_network, icmp_error, ConnectStatus, CurrentDeviceInfo, PeerDeviceInfo, PeerDeviceStatus,
+};
 use crate::igmp_server::IgmpServer;
 #[cfg(feature = "ip_proxy")]
 use crate::ip_proxy::{IpProxyMap, ProxyHandler};
+use crate::maintenance::Maintenance;
 use crate::nat;
 use crate::nat::NatTest;
-use crate::proto::message::{DeviceList, PunchInfo, PunchNatType, RegistrationResponse};
+use crate::proto::message::{
+    ClientConfig, DeviceList, MaintenanceNotice, PunchInfo, PunchNatType, QuotaStatus,
+    RegistrationResponse, ResumeResponse,
+};
 use crate::protocol::body::ENCRYPTION_RESERVED;
 use crate::protocol::control_packet::ControlPacket;
 use crate::protocol::error_packet::InErrorPacket;
 use crate::protocol::{
-    control_packet, ip_turn_packet, other_turn_packet, service_packet, NetPacket, Protocol,
-    Version, MAX_TTL,
+    control_packet, extension, ip_turn_packet, other_turn_packet, service_packet, NetPacket,
+    Protocol, Version, MAX_TTL,
 };
+use crate::quota::Quota;
+use crate::remote_config::{RemoteConfig, RemoteConfigHandler};
+use crate::stream::StreamManager;
 use crate::tun_tap_device::DeviceWriter;
 
 #[derive(Clone)]
@@ -49,6 +65,9 @@ pub struct ChannelDataHandler {
     #[cfg(feature = "ip_proxy")]
     ip_proxy_map: Option<IpProxyMap>,
     out_external_route: AllowExternalRoute,
+    remote_config: RemoteConfigHandler,
+    quota: Arc<AtomicCell<Option<Quota>>>,
+    maintenance: Arc<Mutex<Option<Maintenance>>>,
     cone_sender: Sender<(Ipv4Addr, NatInfo)>,
     symmetric_sender: Sender<(Ipv4Addr, NatInfo)>,
     client_cipher: Cipher,
@@ -56,7 +75,9 @@ pub struct ChannelDataHandler {
     rsa_cipher: Option<RsaCipher>,
     relay: bool,
     token: String,
+    stream_manager: StreamManager,
     time: Arc<AtomicCell<Instant>>,
+    bridge: Option<MacTable>,
     pub head_reserve: usize,
 }
 
@@ -72,6 +93,9 @@ impl ChannelDataHandler {
         peer_nat_info_map: Arc<RwLock<HashMap<Ipv4Addr, NatInfo>>>,
         #[cfg(feature = "ip_proxy")] ip_proxy_map: Option<IpProxyMap>,
         out_external_route: AllowExternalRoute,
+        remote_config: RemoteConfigHandler,
+        quota: Arc<AtomicCell<Option<Quota>>>,
+        maintenance: Arc<Mutex<Option<Maintenance>>>,
         cone_sender: Sender<(Ipv4Addr, NatInfo)>,
         symmetric_sender: Sender<(Ipv4Addr, NatInfo)>,
         client_cipher: Cipher,
@@ -79,6 +103,8 @@ impl ChannelDataHandler {
         rsa_cipher: Option<RsaCipher>,
         relay: bool,
         token: String,
+        stream_manager: StreamManager,
+        bridge: Option<MacTable>,
         head_reserve: usize,
     ) -> Self {
         Self {
@@ -93,6 +119,9 @@ impl ChannelDataHandler {
             #[cfg(feature = "ip_proxy")]
             ip_proxy_map,
             out_external_route,
+            remote_config,
+            quota,
+            maintenance,
             cone_sender,
             symmetric_sender,
             client_cipher,
@@ -100,7 +129,9 @@ impl ChannelDataHandler {
             rsa_cipher,
             relay,
             token,
+            stream_manager,
             time: Arc::new(AtomicCell::new(Instant::now())),
+            bridge,
             head_reserve,
         }
     }
@@ -116,6 +147,10 @@ impl ChannelDataHandler {
         context: &Context,
     ) {
         assert_eq!(start, 14);
+        context.counters().rx(end - start);
+        if context.quarantine().is_blocked(&route_key.addr) {
+            return;
+        }
         match self.handle0(&mut buf[..end], &route_key, context) {
             Ok(_) => {}
             Err(e) => {
@@ -123,6 +158,34 @@ impl ChannelDataHandler {
             }
         }
     }
+    /// 解密或者指纹校验失败，同一地址持续失败时隔离一段时间
+    fn auth_failure(
+        &self,
+        context: &Context,
+        current_device: &CurrentDeviceInfo,
+        route_key: &RouteKey,
+    ) {
+        //服务端会转发所有设备的数据，不能隔离
+        if route_key.addr == current_device.connect_server {
+            return;
+        }
+        if let Some(duration) = context.quarantine().failure(route_key.addr) {
+            log::warn!(
+                "{} 持续发送无法解密的数据,隔离{}秒",
+                route_key.addr,
+                duration.as_secs()
+            );
+            context.events().push(
+                EventKind::Error,
+                None,
+                format!(
+                    "quarantine {} for {}s, too many auth failures",
+                    route_key.addr,
+                    duration.as_secs()
+                ),
+            );
+        }
+    }
     fn handle0(
         &self,
         buf: &mut [u8],
@@ -146,7 +209,10 @@ impl ChannelDataHandler {
             && !destination.is_unspecified()
         {
             //校验指纹，不需要解密
-            self.client_cipher.check_finger(&net_packet)?;
+            if let Err(e) = self.client_cipher.check_finger(&net_packet) {
+                self.auth_failure(context, &current_device, route_key);
+                return Err(e.into());
+            }
             net_packet.set_ttl(net_packet.ttl() - 1);
             let ttl = net_packet.ttl();
             if ttl > 0 {
@@ -165,6 +231,7 @@ impl ChannelDataHandler {
             return Ok(());
         }
         if net_packet.is_gateway() {
+            context.update_server_time();
             if net_packet.protocol() == Protocol::Error
                 && net_packet.transport_protocol()
                     == crate::protocol::error_packet::Protocol::NoKey.into()
@@ -177,6 +244,10 @@ impl ChannelDataHandler {
                         //短时间不重复上传服务端密钥
                         return Ok(());
                     }
+                    if self.register.resume()? {
+                        //票据中有会话密钥，不需要重新上传
+                        return Ok(());
+                    }
                     log::warn!("上传服务端密钥");
                     secret_handshake_req(
                         context,
@@ -189,17 +260,43 @@ impl ChannelDataHandler {
                 }
             } else {
                 //服务端解密
-                self.server_cipher.decrypt_ipv4(&mut net_packet)?;
+                if let Err(e) = self.server_cipher.decrypt_ipv4(&mut net_packet) {
+                    self.auth_failure(context, &current_device, route_key);
+                    return Err(e.into());
+                }
                 let data_len = net_packet.data_len();
                 self.server_packet_handle(context, current_device, buf, data_len, route_key)?;
             }
             return Ok(());
         }
-        self.client_cipher.decrypt_ipv4(&mut net_packet)?;
+        if let Err(e) = self.client_cipher.decrypt_ipv4(&mut net_packet) {
+            self.auth_failure(context, &current_device, route_key);
+            return Err(e.into());
+        }
+        context.quarantine().success(&route_key.addr);
+        if net_packet.has_extension() {
+            // 目前还没有用到扩展的功能，去掉扩展区后正常处理，不认识的类型忽略
+            for extension in extension::take(&mut net_packet)? {
+                log::trace!("{} 扩展 {:?}", source, extension.id);
+            }
+        }
         match net_packet.protocol() {
             Protocol::IpTurn => {
+                if context.is_grace_expired() {
+                    //和服务端失联超过宽限期，不再接收其他设备的数据
+                    return Ok(());
+                }
+                if !not_broadcast && !context.broadcast().on_received(source) {
+                    return Ok(());
+                }
+                context.mark_active(&source);
                 match ip_turn_packet::Protocol::from(net_packet.transport_protocol()) {
                     ip_turn_packet::Protocol::Ipv4 => {
+                        if let Err(reason) = malformed::check_ipv4(net_packet.payload()) {
+                            context.malformed().add(reason);
+                            log::debug!("丢弃不合法的ip报文 {},{}", source, reason);
+                            return Ok(());
+                        }
                         let mut ipv4 = IpV4Packet::new(net_packet.payload_mut())?;
                         match ipv4.protocol() {
                             ipv4::protocol::Protocol::Igmp => {
@@ -231,6 +328,16 @@ impl ChannelDataHandler {
                             _ => {}
                         }
                         if not_broadcast && ipv4.destination_ip() != destination {
+                            //本机转发到子网时算作一跳
+                            if ipv4.ttl() <= 1 {
+                                return self.time_exceeded(
+                                    context,
+                                    destination,
+                                    source,
+                                    ipv4.buffer,
+                                    route_key,
+                                );
+                            }
                             if self.out_external_route.allow(&ipv4.destination_ip()) {
                                 #[cfg(feature = "ip_proxy")]
                                 if let Some(ip_proxy_map) = &self.ip_proxy_map {
@@ -248,8 +355,11 @@ impl ChannelDataHandler {
                                 );
                                 return Err(Error::Warn("没有ip代理规则".to_string()));
                             }
+                            //转发到子网的报文又从网卡回到虚拟网络时是路由环路
+                            context.loop_guard().mark(ipv4.buffer);
                         }
 
+                        context.virtual_to_alias(&mut buf[26..]);
                         //传输协议12字节
                         self.device_writer.write_ipv4(&mut buf[12..])?;
                         return Ok(());
@@ -257,6 +367,17 @@ impl ChannelDataHandler {
                     ip_turn_packet::Protocol::Ipv4Broadcast => {
                         //客户端不帮忙转发广播包，所以不会出现这种类型的数据
                     }
+                    ip_turn_packet::Protocol::Ethernet => {
+                        //没有开启桥接时丢弃，避免二层帧进入三层网络
+                        #[cfg(not(target_os = "android"))]
+                        if let Some(mac_table) = &self.bridge {
+                            let frame = net_packet.payload();
+                            if frame.len() >= 14 {
+                                mac_table.learn(&frame[6..12], source);
+                                self.device_writer.write_ethernet_tap(frame)?;
+                            }
+                        }
+                    }
                     ip_turn_packet::Protocol::Unknown(_) => {}
                 }
             }
@@ -292,6 +413,8 @@ impl ChannelDataHandler {
         let rt = (current_time - pong_packet.time()) as i64;
         let route = Route::from(*route_key, metric, rt);
         context.add_route(source, route);
+        context.on_probe_reply(&source, route_key, rt);
+        context.on_pong(source);
         if gateway {
             let epoch = self.device_list.lock().0;
             if pong_packet.epoch() != epoch {
@@ -351,15 +474,17 @@ impl ChannelDataHandler {
                 net_packet.first_set_ttl(1);
                 self.client_cipher.encrypt_ipv4(&mut net_packet)?;
                 context.try_send_by_key(net_packet.buffer(), route_key)?;
-                let route = Route::from(*route_key, 1, 199);
-                context.add_route_if_absent(source, route);
+                if context.ice_check_succeeded(source, route_key) {
+                    self.probe_route(context, current_device, source, route_key)?;
+                }
             }
             ControlPacket::PunchResponse => {
                 if self.relay {
                     return Ok(());
                 }
-                let route = Route::from(*route_key, 1, 199);
-                context.add_route_if_absent(source, route);
+                if context.ice_check_succeeded(source, route_key) {
+                    self.probe_route(context, current_device, source, route_key)?;
+                }
             }
             ControlPacket::AddrRequest => match route_key.addr.ip() {
                 std::net::IpAddr::V4(ipv4) => {
@@ -392,11 +517,11 @@ impl ChannelDataHandler {
         net_packet: NetPacket<&mut [u8]>,
         route_key: &RouteKey,
     ) -> crate::Result<()> {
-        if self.relay {
-            return Ok(());
-        }
         match other_turn_packet::Protocol::from(net_packet.transport_protocol()) {
             other_turn_packet::Protocol::Punch => {
+                if self.relay {
+                    return Ok(());
+                }
                 let punch_info = PunchInfo::parse_from_bytes(net_packet.payload())?;
                 let public_ips = punch_info
                     .public_ip_list
@@ -406,6 +531,32 @@ impl ChannelDataHandler {
                 let local_ipv4 = Some(Ipv4Addr::from(punch_info.local_ip.to_be_bytes()));
                 let udp_port = punch_info.local_port as u16;
                 let tcp_port = punch_info.tcp_port as u16;
+                let peer_turn_addr = turn_addr(punch_info.turn_ip, punch_info.turn_port);
+                if let Some(turn_addr) = peer_turn_addr {
+                    context.update_turn_peer(source, turn_addr);
+                }
+                if let Some(capabilities) = punch_info.capabilities.as_ref() {
+                    let capabilities = Capabilities::from_proto(capabilities);
+                    let negotiated = context.capabilities().update(source, &capabilities);
+                    log::debug!("和{}协商的能力 {}", source, negotiated);
+                    self.client_cipher.update_peer(
+                        source,
+                        &context.capabilities().local().ciphers,
+                        &capabilities.ciphers,
+                    );
+                }
+                if let Some(skew) = context.clock_skew().update(source, punch_info.time) {
+                    log::warn!(
+                        "和{}的时钟偏差{:.1}秒，请检查两端的系统时间",
+                        source,
+                        skew as f64 / 1000.0
+                    );
+                    context.events().push(
+                        EventKind::Error,
+                        Some(source),
+                        format!("clock skew {:+.1}s", skew as f64 / 1000.0),
+                    );
+                }
                 let ipv6 = if punch_info.ipv6.len() == 16 {
                     let ipv6: [u8; 16] = punch_info.ipv6.try_into().unwrap();
                     Some(Ipv6Addr::from(ipv6))
@@ -427,6 +578,21 @@ impl ChannelDataHandler {
                     let peer_nat_info = peer_nat_info.clone();
                     self.peer_nat_info_map.write().insert(source, peer_nat_info);
                 }
+                //旧版本没有候选地址，从打洞信息推导
+                let candidates = if punch_info.candidates.is_empty() {
+                    ice::gather(&peer_nat_info, peer_turn_addr)
+                } else {
+                    punch_info
+                        .candidates
+                        .iter()
+                        .filter_map(Candidate::from_proto)
+                        .collect()
+                };
+                //虚拟ip小的一方作为控制方
+                context.ice_update_remote(source, current_device.virtual_ip() < source, candidates);
+                context
+                    .punch_records()
+                    .on_punch_info(source, punch_info.reply);
                 if !punch_info.reply {
                     let mut punch_reply = PunchInfo::new();
                     punch_reply.reply = true;
@@ -447,6 +613,18 @@ impl ChannelDataHandler {
                         punch_reply.ipv6 = ipv6.octets().to_vec();
                         punch_reply.ipv6_port = nat_info.udp_port as u32;
                     }
+                    let relayed = context.turn_relayed_addr();
+                    if let Some(turn_addr) = relayed {
+                        punch_reply.turn_ip = u32::from(*turn_addr.ip());
+                        punch_reply.turn_port = turn_addr.port() as u32;
+                    }
+                    punch_reply.candidates = ice::gather(&nat_info, relayed)
+                        .iter()
+                        .map(Candidate::to_proto)
+                        .collect();
+                    punch_reply.capabilities =
+                        protobuf::MessageField::some(context.capabilities().local().to_proto());
+                    punch_reply.time = crate::handle::now_time();
                     let bytes = punch_reply.write_to_bytes()?;
                     let mut punch_packet =
                         NetPacket::new_encrypt(vec![0u8; 12 + bytes.len() + ENCRYPTION_RESERVED])?;
@@ -465,12 +643,61 @@ impl ChannelDataHandler {
                     self.punch(source, peer_nat_info);
                 }
             }
+            other_turn_packet::Protocol::Stream => {
+                context.mark_active(&source);
+                self.stream_manager.handle(source, net_packet.payload())?;
+            }
             other_turn_packet::Protocol::Unknown(e) => {
                 log::warn!("不支持的转发协议 {:?},source:{:?}", e, source);
             }
         }
         Ok(())
     }
+    /// ttl耗尽时回应icmp超时，traceroute中显示为本机的虚拟ip
+    fn time_exceeded(
+        &self,
+        context: &Context,
+        current_ip: Ipv4Addr,
+        source: Ipv4Addr,
+        original: &[u8],
+        route_key: &RouteKey,
+    ) -> crate::Result<()> {
+        let icmp = match icmp_error::build(original, current_ip, Kind::TimeExceeded, 0, 0) {
+            Some(icmp) => icmp,
+            None => return Ok(()),
+        };
+        let mut packet = NetPacket::new_encrypt(vec![0u8; 12 + icmp.len() + ENCRYPTION_RESERVED])?;
+        packet.set_version(Version::V1);
+        packet.set_protocol(Protocol::IpTurn);
+        packet.set_transport_protocol(ip_turn_packet::Protocol::Ipv4.into());
+        packet.first_set_ttl(MAX_TTL);
+        packet.set_source(current_ip);
+        packet.set_destination(source);
+        packet.set_payload(&icmp)?;
+        self.client_cipher.encrypt_ipv4(&mut packet)?;
+        context.try_send_by_key(packet.buffer(), route_key)?;
+        Ok(())
+    }
+    /// 新打通的路由立即测量延迟，不等心跳周期，测出延迟后数据马上从中转切换到直连
+    fn probe_route(
+        &self,
+        context: &Context,
+        current_device: CurrentDeviceInfo,
+        source: Ipv4Addr,
+        route_key: &RouteKey,
+    ) -> crate::Result<()> {
+        let packet = heartbeat_packet(
+            MAX_TTL,
+            &self.device_list,
+            &self.client_cipher,
+            &self.server_cipher,
+            false,
+            current_device.virtual_ip(),
+            source,
+        );
+        context.try_send_by_key(packet.buffer(), route_key)?;
+        Ok(())
+    }
     fn punch(&self, peer_ip: Ipv4Addr, peer_nat_info: NatInfo) -> bool {
         match peer_nat_info.nat_type {
             NatType::Symmetric => self
@@ -507,6 +734,11 @@ impl ChannelDataHandler {
             Protocol::IpTurn => {
                 match ip_turn_packet::Protocol::from(net_packet.transport_protocol()) {
                     ip_turn_packet::Protocol::Ipv4 => {
+                        if let Err(reason) = malformed::check_ipv4(net_packet.payload()) {
+                            context.malformed().add(reason);
+                            log::debug!("丢弃不合法的ip报文 {},{}", source, reason);
+                            return Ok(());
+                        }
                         let ipv4 = IpV4Packet::new(net_packet.payload())?;
                         match ipv4.protocol() {
                             ipv4::protocol::Protocol::Igmp => {
@@ -519,6 +751,7 @@ impl ChannelDataHandler {
                                 if ipv4.destination_ip() == current_device.virtual_ip {
                                     let icmp_packet = icmp::IcmpPacket::new(ipv4.payload())?;
                                     if icmp_packet.kind() == Kind::EchoReply {
+                                        context.virtual_to_alias(&mut buf[26..]);
                                         self.device_writer.write_ipv4(&mut buf[12..])?;
                                         return Ok(());
                                     }
@@ -528,6 +761,7 @@ impl ChannelDataHandler {
                         }
                     }
                     ip_turn_packet::Protocol::Ipv4Broadcast => {}
+                    ip_turn_packet::Protocol::Ethernet => {}
                     ip_turn_packet::Protocol::Unknown(_) => {}
                 }
             }
@@ -578,54 +812,80 @@ impl ChannelDataHandler {
                 if self.nat_test.can_update() {
                     let context = context.clone();
                     let nat_test = self.nat_test.clone();
-                    std::thread::spawn(move || {
-                        tokio::runtime::Builder::new_current_thread()
-                            .enable_all()
-                            .build()
-                            .unwrap()
-                            .block_on(async move {
-                                let local_ipv4 = nat::local_ipv4();
-                                let ipv6 = nat::local_ipv6();
-                                let udp_port = nat_test.nat_info().udp_port;
-                                let tcp_port = nat_test.nat_info().tcp_port;
-                                let nat_info = nat_test
-                                    .re_test(
-                                        Ipv4Addr::from(response.public_ip),
-                                        response.public_port as u16,
-                                        local_ipv4,
-                                        ipv6,
-                                        udp_port,
-                                        tcp_port,
-                                    )
-                                    .await;
-                                context.switch(nat_info.nat_type);
-                            })
+                    //受限线程不能创建线程
+                    crate::util::seccomp::unrestricted(move || {
+                        std::thread::spawn(move || {
+                            tokio::runtime::Builder::new_current_thread()
+                                .enable_all()
+                                .build()
+                                .unwrap()
+                                .block_on(async move {
+                                    let local_ipv4 = nat::local_ipv4();
+                                    let ipv6 = nat::local_ipv6();
+                                    let udp_port = nat_test.nat_info().udp_port;
+                                    let tcp_port = nat_test.nat_info().tcp_port;
+                                    let nat_info = nat_test
+                                        .re_test(
+                                            Ipv4Addr::from(response.public_ip),
+                                            response.public_port as u16,
+                                            local_ipv4,
+                                            ipv6,
+                                            udp_port,
+                                            tcp_port,
+                                        )
+                                        .await;
+                                    context.switch(nat_info.nat_type);
+                                })
+                        })
                     });
                 }
                 let new_ip = Ipv4Addr::from(response.virtual_ip);
+                let new_gateway = Ipv4Addr::from(response.virtual_gateway);
+                let new_netmask = Ipv4Addr::from(response.virtual_netmask);
                 let current_ip = current_device.virtual_ip();
-                if current_ip != new_ip {
-                    // ip发生变化
-                    log::info!("ip发生变化,old_ip:{:?},new_ip:{:?}", current_ip, new_ip);
-                    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
-                    let old_netmask = current_device.virtual_netmask;
-                    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
-                    let old_gateway = current_device.virtual_gateway();
-                    let virtual_ip = Ipv4Addr::from(response.virtual_ip);
-                    let virtual_gateway = Ipv4Addr::from(response.virtual_gateway);
-                    let virtual_netmask = Ipv4Addr::from(response.virtual_netmask);
+                if let Err(e) = check_network(new_ip, new_gateway, new_netmask) {
+                    log::error!("服务端分配的网段无效:{}", e);
+                } else if current_ip != new_ip
+                    || current_device.virtual_gateway != new_gateway
+                    || current_device.virtual_netmask != new_netmask
+                {
+                    // ip或网段发生变化
+                    log::info!(
+                        "ip发生变化,old_ip:{:?}/{:?},new_ip:{:?}/{:?}",
+                        current_ip,
+                        current_device.virtual_netmask,
+                        new_ip,
+                        new_netmask
+                    );
                     #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
-                    self.device_writer.change_ip(
-                        virtual_ip,
-                        virtual_netmask,
-                        virtual_gateway,
-                        old_netmask,
-                        old_gateway,
-                    )?;
+                    {
+                        let old_netmask = current_device.virtual_netmask;
+                        let old_gateway = current_device.virtual_gateway();
+                        //使用映射网段时网卡配置映射后的地址
+                        let (device_ip, device_gateway, old_gateway) = match context.subnet_alias()
+                        {
+                            Some(alias) => (
+                                crate::subnet_alias::map(new_ip, alias, new_netmask),
+                                crate::subnet_alias::map(new_gateway, alias, new_netmask),
+                                crate::subnet_alias::map(old_gateway, alias, old_netmask),
+                            ),
+                            None => (new_ip, new_gateway, old_gateway),
+                        };
+                        let device_writer = self.device_writer.clone();
+                        crate::util::seccomp::unrestricted(move || {
+                            device_writer.change_ip(
+                                device_ip,
+                                new_netmask,
+                                device_gateway,
+                                old_netmask,
+                                old_gateway,
+                            )
+                        })?;
+                    }
                     let new_current_device = CurrentDeviceInfo::new(
-                        virtual_ip,
-                        virtual_gateway,
-                        virtual_netmask,
+                        new_ip,
+                        new_gateway,
+                        new_netmask,
                         current_device.connect_server,
                     );
                     if let Err(e) = self
@@ -633,9 +893,70 @@ impl ChannelDataHandler {
                         .compare_exchange(current_device, new_current_device)
                     {
                         log::warn!("替换失败:{:?}", e);
+                    } else {
+                        context.events().push(
+                            EventKind::IpAssigned,
+                            None,
+                            format!("{} -> {}/{}", current_ip, new_ip, new_netmask),
+                        );
+                    }
+                }
+                if let Some(client_config) = response.client_config.as_ref() {
+                    self.apply_client_config(client_config);
+                }
+                self.register
+                    .set_ticket(response.resume_ticket, response.resume_ticket_ttl);
+                self.register.on_registered();
+                //维护中的服务端不接受注册，注册成功说明维护已经结束
+                self.apply_maintenance(context, None);
+                if self.connect_status.swap(ConnectStatus::Connected) != ConnectStatus::Connected {
+                    context.events().push(
+                        EventKind::Connected,
+                        None,
+                        format!(
+                            "registered {} via {}",
+                            new_ip, current_device.connect_server
+                        ),
+                    );
+                }
+            }
+            service_packet::Protocol::PushClientConfig => {
+                let client_config = ClientConfig::parse_from_bytes(net_packet.payload())?;
+                self.apply_client_config(&client_config);
+            }
+            service_packet::Protocol::PushQuotaStatus => {
+                let status = QuotaStatus::parse_from_bytes(net_packet.payload())?;
+                let quota = Quota::from_proto(&status);
+                let old = self.quota.swap(Some(quota));
+                if old.map(|v| v.state) != Some(quota.state) {
+                    if quota.is_limited() {
+                        log::warn!("中转流量超出配额,{}", quota);
+                    } else {
+                        log::info!("中转流量配额,{}", quota);
                     }
                 }
-                self.connect_status.store(ConnectStatus::Connected);
+            }
+            service_packet::Protocol::PushMaintenance => {
+                let notice = MaintenanceNotice::parse_from_bytes(net_packet.payload())?;
+                self.apply_maintenance(context, Maintenance::from_proto(&notice));
+            }
+            service_packet::Protocol::ResumeResponse => {
+                let response = ResumeResponse::parse_from_bytes(net_packet.payload())?;
+                self.register
+                    .set_ticket(response.resume_ticket, response.resume_ticket_ttl);
+                log::info!("会话已恢复,epoch={}", response.epoch);
+                self.register.on_registered();
+                self.apply_maintenance(context, None);
+                if self.connect_status.swap(ConnectStatus::Connected) != ConnectStatus::Connected {
+                    context.events().push(
+                        EventKind::Connected,
+                        None,
+                        format!(
+                            "resumed {} via {}",
+                            current_device.virtual_ip, current_device.connect_server
+                        ),
+                    );
+                }
             }
             service_packet::Protocol::PollDeviceList => {}
             service_packet::Protocol::PushDeviceList => {
@@ -649,6 +970,8 @@ impl ChannelDataHandler {
                             info.name,
                             info.device_status as u8,
                             info.client_secret,
+                            info.tags,
+                            info.routes,
                         )
                     })
                     .collect();
@@ -661,7 +984,42 @@ impl ChannelDataHandler {
                 let mut dev = self.device_list.lock();
                 if dev.0 != device_list_t.epoch as u16 {
                     dev.0 = device_list_t.epoch as u16;
+                    for peer in &ip_list {
+                        let old = dev.1.iter().find(|v| v.virtual_ip == peer.virtual_ip);
+                        if old.map(|v| v.status) == Some(peer.status) {
+                            continue;
+                        }
+                        let kind = match peer.status {
+                            PeerDeviceStatus::Online => EventKind::PeerUp,
+                            PeerDeviceStatus::Offline => {
+                                if old.is_none() {
+                                    continue;
+                                }
+                                EventKind::PeerDown
+                            }
+                        };
+                        context
+                            .events()
+                            .push(kind, Some(peer.virtual_ip), peer.name.clone());
+                    }
+                    for old in &dev.1 {
+                        if old.status == PeerDeviceStatus::Online
+                            && !ip_list.iter().any(|v| v.virtual_ip == old.virtual_ip)
+                        {
+                            context.events().push(
+                                EventKind::PeerDown,
+                                Some(old.virtual_ip),
+                                old.name.clone(),
+                            );
+                        }
+                    }
                     dev.1 = ip_list;
+                    let list = dev.1.clone();
+                    drop(dev);
+                    context.path_policy().update_tags(&list);
+                    context.update_plaintext_peers(&list);
+                    context.update_offline_peers(&list);
+                    self.apply_peer_routes(&list);
                 }
             }
             service_packet::Protocol::Unknown(u) => {
@@ -671,20 +1029,81 @@ impl ChannelDataHandler {
         }
         Ok(())
     }
+    /// 记录服务端的维护通知，None表示维护结束。
+    /// 中转将被断开时，立即和经服务端中转的设备打洞，尽量在断开前切换到直连
+    fn apply_maintenance(&self, context: &Context, maintenance: Option<Maintenance>) {
+        let old = std::mem::replace(&mut *self.maintenance.lock(), maintenance.clone());
+        match maintenance {
+            Some(maintenance) => {
+                if old.as_ref().map(|v| &v.notice) != Some(&maintenance.notice) {
+                    log::warn!("服务端维护中,{}", maintenance);
+                    context
+                        .events()
+                        .push(EventKind::Maintenance, None, maintenance.to_string());
+                }
+                if maintenance.drain_deadline.is_some() {
+                    for (ip, route) in context.route_table_one() {
+                        if !route.is_p2p() {
+                            context.race(&ip);
+                        }
+                    }
+                }
+            }
+            None => {
+                if old.is_some() {
+                    log::info!("服务端维护结束");
+                    context.events().push(
+                        EventKind::Maintenance,
+                        None,
+                        "maintenance ended".to_string(),
+                    );
+                }
+            }
+        }
+    }
+    fn apply_client_config(&self, client_config: &ClientConfig) {
+        let gateway = self.current_device.load().virtual_gateway;
+        let remote_config = self.remote_config.clone();
+        let config = match RemoteConfig::from_proto(client_config) {
+            Ok(config) => config,
+            Err(e) => {
+                log::warn!("服务端下发的配置无效，不应用 {:?}", e);
+                return;
+            }
+        };
+        //会执行路由命令，受限线程中交给控制线程
+        let rs = crate::util::seccomp::unrestricted(move || remote_config.apply(config, gateway));
+        if let Err(e) = rs {
+            log::warn!("应用服务端下发的配置失败 {:?}", e);
+        }
+    }
+    fn apply_peer_routes(&self, device_list: &[PeerDeviceInfo]) {
+        let gateway = self.current_device.load().virtual_gateway;
+        let remote_config = self.remote_config.clone();
+        let device_list = device_list.to_vec();
+        let rs = crate::util::seccomp::unrestricted(move || {
+            remote_config.apply_peer_routes(&device_list, gateway)
+        });
+        if let Err(e) = rs {
+            log::warn!("添加其他设备发布的路由失败 {:?}", e);
+        }
+    }
     fn error(
         &self,
-        _context: &Context,
+        context: &Context,
         current_device: CurrentDeviceInfo,
         _source: Ipv4Addr,
         net_packet: NetPacket<&[u8]>,
         _route_key: &RouteKey,
     ) -> crate::Result<()> {
         log::info!("current_device:{:?}", current_device);
+        let events = context.events();
         match InErrorPacket::new(net_packet.transport_protocol(), net_packet.payload())? {
             InErrorPacket::TokenError => {
+                events.push(EventKind::Error, None, "token error".to_string());
                 return Err(Error::Stop("Token error".to_string()));
             }
-            InErrorPacket::Disconnect => {
+            InErrorPacket::Disconnect(retry_after) => {
                 {
                     //掉线epoch要归零
                     let mut dev = self.device_list.lock();
@@ -692,22 +1111,85 @@ impl ChannelDataHandler {
                 }
 
                 self.connect_status.store(ConnectStatus::Connecting);
-                self.register.fast_register(current_device.virtual_ip)?;
+                events.push(
+                    EventKind::Disconnected,
+                    None,
+                    format!("disconnected by {}", current_device.connect_server),
+                );
+                //维护期间断开时没有指定等待时间，使用维护通知中的
+                let retry_after = match (retry_after, self.maintenance.lock().as_ref()) {
+                    (0, Some(maintenance)) => maintenance.retry_after,
+                    _ => retry_after,
+                };
+                self.register
+                    .fast_register(current_device.virtual_ip, retry_after)?;
             }
             InErrorPacket::AddressExhausted => {
                 //地址用尽
+                events.push(EventKind::Error, None, "address exhausted".to_string());
                 return Err(Error::Stop("IP address has been exhausted".to_string()));
             }
             InErrorPacket::OtherError(e) => {
                 log::error!("OtherError {:?}", e.message());
+                events.push(
+                    EventKind::Error,
+                    None,
+                    e.message().unwrap_or_else(|_| "server error".to_string()),
+                );
             }
             InErrorPacket::IpAlreadyExists => {
                 log::error!("IpAlreadyExists");
+                events.push(EventKind::Error, None, "ip already exists".to_string());
             }
             InErrorPacket::InvalidIp => {
                 log::error!("InvalidIp");
+                events.push(EventKind::Error, None, "invalid ip".to_string());
             }
             InErrorPacket::NoKey => {}
+            InErrorPacket::DeviceIdConflict => {
+                //继续重连会和另一台设备互相挤占ip
+                events.push(EventKind::Error, None, "device id conflict".to_string());
+                return Err(Error::Stop("device id conflict".to_string()));
+            }
+            InErrorPacket::RateLimited(retry_after) => {
+                let delay = self.register.on_rate_limited(retry_after);
+                log::warn!(
+                    "注册太频繁被服务端限流,retry_after={}s,{:?}后重新注册",
+                    retry_after,
+                    delay
+                );
+                events.push(
+                    EventKind::Error,
+                    None,
+                    format!("rate limited, retry in {}s", delay.as_secs()),
+                );
+            }
+            InErrorPacket::DeviceLimit => {
+                events.push(EventKind::Error, None, "device limit reached".to_string());
+                return Err(Error::Stop("device limit reached".to_string()));
+            }
+            InErrorPacket::Maintenance(notice) => {
+                let maintenance = Maintenance::from_proto(&notice);
+                let retry_after = maintenance.as_ref().map_or(0, |v| v.retry_after);
+                self.apply_maintenance(context, maintenance);
+                let delay = self.register.on_rate_limited(retry_after);
+                log::info!("服务端维护中,{:?}后重新注册", delay);
+            }
+            InErrorPacket::PunchRateLimited(retry_after) => {
+                let backoff = context.punch_limiter().on_server_limited(retry_after);
+                log::warn!(
+                    "打洞请求被服务端限流(rate limited by server),{}s后再发起",
+                    backoff.as_secs()
+                );
+                events.push(
+                    EventKind::Error,
+                    None,
+                    format!(
+                        "punch rate limited by server, retry after {}s",
+                        backoff.as_secs()
+                    ),
+                );
+            }
         }
         Ok(())
     }
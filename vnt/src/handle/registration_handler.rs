@@ -1,25 +1,38 @@
-use crossbeam_utils::atomic::AtomicCell;
+use hmac::{Hmac, Mac};
+use parking_lot::Mutex;
+use sha2::Sha256;
 use std::io::{Read, Write};
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV6};
 use std::time::{Duration, Instant};
 
 use crate::channel::sender::ChannelSender;
 use crate::cipher::Cipher;
-use crate::handle::PeerDeviceInfo;
+use crate::handle::{check_network, PeerDeviceInfo};
+use crate::maintenance::Maintenance;
 use protobuf::Message;
 use std::net::TcpStream;
 use std::net::UdpSocket;
 
-use crate::proto::message::{RegistrationRequest, RegistrationResponse};
+use crate::proto::message::{RegistrationRequest, RegistrationResponse, ResumeRequest};
 use crate::protocol::body::ENCRYPTION_RESERVED;
 use crate::protocol::error_packet::InErrorPacket;
 use crate::protocol::{service_packet, NetPacket, Protocol, Version, MAX_TTL};
+use crate::remote_config::{format_cidr, RemoteConfig};
+use crate::util::backoff::Backoff;
 
 pub enum ReqEnum {
     TokenError,
     AddressExhausted,
     IpAlreadyExists,
     InvalidIp,
+    /// device_id和其他在线设备重复
+    DeviceIdConflict,
+    /// 注册太频繁被服务端限流，参数为建议等待的秒数，0表示未指定
+    RateLimited(u32),
+    /// token下的设备数达到上限
+    DeviceLimit,
+    /// 服务端维护中不接受新的注册，按通知中的时间等待后重试
+    Maintenance(Maintenance),
     Timeout,
     ServerError(String),
     Other(String),
@@ -34,6 +47,10 @@ pub struct RegResponse {
     pub device_info_list: Vec<PeerDeviceInfo>,
     pub public_ip: Ipv4Addr,
     pub public_port: u16,
+    /// 服务端下发的配置
+    pub client_config: Option<RemoteConfig>,
+    /// 会话恢复票据和有效期(秒)
+    pub resume_ticket: Option<(Vec<u8>, u32)>,
 }
 
 ///向中继服务器注册，token标识一个虚拟网关，device_id防止多次注册时得到的ip不一致
@@ -47,6 +64,7 @@ pub fn registration(
     name: String,
     ip: Ipv4Addr,
     client_secret: bool,
+    routes: &[(u32, u32)],
 ) -> Result<RegResponse, ReqEnum> {
     let request_packet = registration_request_packet(
         server_cipher,
@@ -57,6 +75,7 @@ pub fn registration(
         false,
         false,
         client_secret,
+        routes,
     )
     .unwrap();
     let buf = request_packet.buffer();
@@ -91,10 +110,10 @@ pub fn registration(
             )),
             SocketAddr::V6(_) => server_address,
         };
-        if let Err(e) = main_channel.send_to(buf, server_address) {
+        if let Err(e) = crate::util::mimicry::send_to(main_channel, buf, server_address) {
             return Err(ReqEnum::Other(format!("send error:{}", e)));
         }
-        match main_channel.recv_from(&mut recv_buf) {
+        match crate::util::mimicry::recv_from(main_channel, &mut recv_buf) {
             Ok((len, addr)) => {
                 if server_address != addr {
                     log::warn!("请求{:?}和响应{:?}地址不一致", server_address, addr);
@@ -121,6 +140,14 @@ pub fn registration(
                 service_packet::Protocol::RegistrationResponse => {
                     match RegistrationResponse::parse_from_bytes(net_packet.payload()) {
                         Ok(response) => {
+                            // 网段大小由服务端决定，不能假定为/24
+                            if let Err(e) = check_network(
+                                Ipv4Addr::from(response.virtual_ip),
+                                Ipv4Addr::from(response.virtual_gateway),
+                                Ipv4Addr::from(response.virtual_netmask),
+                            ) {
+                                return Err(ReqEnum::ServerError(e));
+                            }
                             let device_info_list: Vec<PeerDeviceInfo> = response
                                 .device_info_list
                                 .into_iter()
@@ -130,6 +157,8 @@ pub fn registration(
                                         info.name,
                                         info.device_status as u8,
                                         info.client_secret,
+                                        info.tags,
+                                        info.routes,
                                     )
                                 })
                                 .collect();
@@ -141,6 +170,20 @@ pub fn registration(
                                 device_info_list,
                                 public_ip: Ipv4Addr::from(response.public_ip),
                                 public_port: response.public_port as u16,
+                                client_config: response.client_config.as_ref().and_then(|v| {
+                                    match RemoteConfig::from_proto(v) {
+                                        Ok(v) => Some(v),
+                                        Err(e) => {
+                                            log::warn!("服务端下发的配置无效，不应用 {:?}", e);
+                                            None
+                                        }
+                                    }
+                                }),
+                                resume_ticket: if response.resume_ticket.is_empty() {
+                                    None
+                                } else {
+                                    Some((response.resume_ticket, response.resume_ticket_ttl))
+                                },
                             })
                         }
                         Err(_) => Err(ReqEnum::ServerError("invalid data".to_string())),
@@ -153,7 +196,7 @@ pub fn registration(
             match InErrorPacket::new(net_packet.transport_protocol(), net_packet.payload()) {
                 Ok(e) => match e {
                     InErrorPacket::TokenError => Err(ReqEnum::TokenError),
-                    InErrorPacket::Disconnect => {
+                    InErrorPacket::Disconnect(_) => {
                         Err(ReqEnum::ServerError("disconnect".to_string()))
                     }
                     InErrorPacket::AddressExhausted => Err(ReqEnum::AddressExhausted),
@@ -164,6 +207,18 @@ pub fn registration(
                     InErrorPacket::IpAlreadyExists => Err(ReqEnum::IpAlreadyExists),
                     InErrorPacket::InvalidIp => Err(ReqEnum::InvalidIp),
                     InErrorPacket::NoKey => Err(ReqEnum::ServerError("no key".to_string())),
+                    InErrorPacket::DeviceIdConflict => Err(ReqEnum::DeviceIdConflict),
+                    InErrorPacket::RateLimited(retry_after) => {
+                        Err(ReqEnum::RateLimited(retry_after))
+                    }
+                    InErrorPacket::DeviceLimit => Err(ReqEnum::DeviceLimit),
+                    InErrorPacket::PunchRateLimited(_) => {
+                        Err(ReqEnum::ServerError("punch rate limited".to_string()))
+                    }
+                    InErrorPacket::Maintenance(notice) => match Maintenance::from_proto(&notice) {
+                        Some(maintenance) => Err(ReqEnum::Maintenance(maintenance)),
+                        None => Err(ReqEnum::ServerError("maintenance".to_string())),
+                    },
                 },
                 Err(e) => Err(ReqEnum::Other(format!("{}", e))),
             }
@@ -181,6 +236,7 @@ fn registration_request_packet(
     is_fast: bool,
     allow_ip_change: bool,
     client_secret: bool,
+    routes: &[(u32, u32)],
 ) -> crate::Result<NetPacket<Vec<u8>>> {
     let mut request = RegistrationRequest::new();
     request.token = token;
@@ -191,6 +247,10 @@ fn registration_request_packet(
     request.is_fast = is_fast;
     request.version = crate::VNT_VERSION.to_string();
     request.client_secret = client_secret;
+    request.routes = routes
+        .iter()
+        .map(|(dest, mask)| format_cidr(*dest, *mask))
+        .collect();
     let bytes = request.write_to_bytes()?;
     let buf = vec![0u8; 12 + bytes.len() + ENCRYPTION_RESERVED];
     let mut net_packet = NetPacket::new_encrypt(buf)?;
@@ -204,6 +264,29 @@ fn registration_request_packet(
     Ok(net_packet)
 }
 
+/// 服务端还没有会话密钥，票据和证明明文发送，票据本身由服务端加密。
+/// 证明是以会话密钥为密钥的HMAC-SHA256(ticket + time大端)，只有持有会话密钥才能计算
+fn resume_request_packet(session_key: &[u8], ticket: Vec<u8>) -> crate::Result<NetPacket<Vec<u8>>> {
+    let time = crate::handle::now_time() as i64;
+    // HMAC接受任意长度的密钥
+    let mut mac = Hmac::<Sha256>::new_from_slice(session_key).unwrap();
+    mac.update(&ticket);
+    mac.update(&time.to_be_bytes());
+    let mut request = ResumeRequest::new();
+    request.proof = mac.finalize().into_bytes().to_vec();
+    request.ticket = ticket;
+    request.time = time;
+    let bytes = request.write_to_bytes()?;
+    let mut net_packet = NetPacket::new(vec![0u8; 12 + bytes.len()])?;
+    net_packet.set_version(Version::V1);
+    net_packet.set_gateway_flag(true);
+    net_packet.set_protocol(Protocol::Service);
+    net_packet.set_transport_protocol(service_packet::Protocol::ResumeRequest.into());
+    net_packet.first_set_ttl(MAX_TTL);
+    net_packet.set_payload(&bytes)?;
+    Ok(net_packet)
+}
+
 pub struct Register {
     server_cipher: Cipher,
     sender: ChannelSender,
@@ -211,8 +294,12 @@ pub struct Register {
     token: String,
     device_id: String,
     name: String,
-    time: AtomicCell<Instant>,
+    /// 重新注册的退避和下一次允许注册的时间
+    retry: Mutex<(Backoff, Instant)>,
     client_secret: bool,
+    routes: Vec<(u32, u32)>,
+    /// 会话恢复票据和过期时间
+    ticket: Mutex<Option<(Vec<u8>, Instant)>>,
 }
 
 impl Register {
@@ -224,6 +311,8 @@ impl Register {
         device_id: String,
         name: String,
         client_secret: bool,
+        routes: Vec<(u32, u32)>,
+        backoff: Backoff,
     ) -> Self {
         Self {
             server_cipher,
@@ -232,16 +321,72 @@ impl Register {
             token,
             device_id,
             name,
-            time: AtomicCell::new(Instant::now()),
+            retry: Mutex::new((backoff, Instant::now())),
             client_secret,
+            routes,
+            ticket: Mutex::new(None),
         }
     }
-    pub fn fast_register(&self, ip: Ipv4Addr) -> crate::Result<()> {
-        let last = self.time.load();
-        if last.elapsed() < Duration::from_secs(3)
-            || self.time.compare_exchange(last, Instant::now()).is_err()
+    /// 没有开启服务端加密时票据是明文下发的，路径上的任何人都能用它接管会话，不保存
+    pub fn set_ticket(&self, ticket: Vec<u8>, ttl: u32) {
+        if ticket.is_empty() || self.server_cipher.key().is_none() {
+            return;
+        }
+        let expire = Instant::now() + Duration::from_secs(ttl as u64);
+        *self.ticket.lock() = Some((ticket, expire));
+    }
+    /// 有未过期的票据时用票据恢复会话，返回false表示需要完整的握手和注册。
+    /// 票据只使用一次，恢复失败时服务端会再次要求注册
+    pub fn resume(&self) -> crate::Result<bool> {
+        let ticket = match self.ticket.lock().take() {
+            Some((ticket, expire)) if Instant::now() < expire => ticket,
+            _ => return Ok(false),
+        };
+        let session_key = match self.server_cipher.key() {
+            Some(key) => key,
+            None => return Ok(false),
+        };
+        log::info!("使用票据恢复会话");
+        let request_packet = resume_request_packet(session_key, ticket)?;
+        self.sender
+            .send_main(request_packet.buffer(), self.server_address)?;
+        Ok(true)
+    }
+    /// 注册或恢复会话成功，之后掉线时从最小间隔开始重试
+    pub fn on_registered(&self) {
+        self.retry.lock().0.reset();
+    }
+    /// 被服务端限流，按服务端给出的时间推迟下一次注册，返回推迟的时长
+    pub fn on_rate_limited(&self, retry_after: u32) -> Duration {
+        let mut retry = self.retry.lock();
+        let delay = retry.0.next_delay_after(retry_after);
+        retry.1 = Instant::now() + delay;
+        delay
+    }
+    /// 收到掉线通知时调用，服务端每次心跳都会回应掉线，按退避间隔重新注册。
+    /// 第一次也随机等待一段时间，服务端重启后所有设备会同时收到掉线通知，
+    /// retry_after为服务端要求等待的秒数，非0时先等待再注册
+    pub fn fast_register(&self, ip: Ipv4Addr, retry_after: u32) -> crate::Result<()> {
         {
-            //短时间不重复注册
+            let mut retry = self.retry.lock();
+            let now = Instant::now();
+            if now < retry.1 {
+                return Ok(());
+            }
+            if retry_after > 0 {
+                let delay = retry.0.next_delay_after(retry_after);
+                retry.1 = now + delay;
+                log::info!("服务端要求{}s后重新注册,{:?}后重试", retry_after, delay);
+                return Ok(());
+            }
+            let delay = retry.0.next_delay();
+            retry.1 = now + delay;
+            if retry.0.attempts() == 1 {
+                log::info!("掉线,{:?}后重新注册", delay);
+                return Ok(());
+            }
+        }
+        if self.resume()? {
             return Ok(());
         }
         log::info!("重新连接");
@@ -254,6 +399,7 @@ impl Register {
             false,
             true,
             self.client_secret,
+            &self.routes,
         )?;
         let buf = request_packet.buffer();
         self.sender.send_main(buf, self.server_address)?;
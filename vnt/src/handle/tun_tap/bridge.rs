@@ -0,0 +1,100 @@
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+
+use crate::channel::mem_limit::{self, MAX_BRIDGE_MACS};
+use crate::channel::sender::ChannelSender;
+use crate::cipher::Cipher;
+use crate::handle::{check_dest, CurrentDeviceInfo};
+use crate::protocol;
+use crate::protocol::body::ENCRYPTION_RESERVED;
+use crate::protocol::{ip_turn_packet, NetPacket, Version};
+
+/// mac地址多久没有收到帧就失效，和linux网桥的默认老化时间一致
+const MAC_EXPIRE: Duration = Duration::from_secs(300);
+
+/// 桥接模式下的二层转发表，记录mac地址在哪个设备后面
+#[derive(Clone, Default)]
+pub struct MacTable {
+    table: Arc<DashMap<[u8; 6], (Ipv4Addr, Instant)>>,
+}
+
+impl MacTable {
+    /// 从对端收到帧时学习源mac
+    pub fn learn(&self, mac: &[u8], peer_ip: Ipv4Addr) {
+        if mac.len() != 6 || mac[0] & 1 == 1 {
+            return;
+        }
+        let mut key = [0u8; 6];
+        key.copy_from_slice(mac);
+        mem_limit::make_room(&self.table, &key, MAX_BRIDGE_MACS, |(_, time)| *time);
+        self.table.insert(key, (peer_ip, Instant::now()));
+    }
+    pub fn lookup(&self, mac: &[u8]) -> Option<Ipv4Addr> {
+        let key: [u8; 6] = mac.try_into().ok()?;
+        let (peer_ip, time) = *self.table.get(&key)?;
+        if time.elapsed() > MAC_EXPIRE {
+            self.table.remove(&key);
+            return None;
+        }
+        Some(peer_ip)
+    }
+}
+
+/// 虚拟网络内的地址回复的都是伪造的mac，前4字节是ip，最后一字节是234
+pub fn is_vnt_mac(mac: &[u8], current_device: &CurrentDeviceInfo) -> bool {
+    if mac.len() != 6 || mac[5] != 234 {
+        return false;
+    }
+    let ip = Ipv4Addr::new(mac[0], mac[1], mac[2], mac[3]);
+    check_dest(
+        ip,
+        current_device.virtual_netmask,
+        current_device.virtual_network,
+    )
+}
+
+/// 把网桥上的以太网帧转发到对端，单播且已经学习到的发给对应设备，其余的广播
+pub fn send_frame(
+    sender: &ChannelSender,
+    frame: &[u8],
+    mac_table: &MacTable,
+    current_device: &CurrentDeviceInfo,
+    client_cipher: &Cipher,
+    server_cipher: &Cipher,
+) -> crate::Result<()> {
+    let peer_ip = if frame[0] & 1 == 0 {
+        mac_table.lookup(&frame[..6])
+    } else {
+        None
+    };
+    let mut buf = vec![0u8; 12 + frame.len() + ENCRYPTION_RESERVED];
+    let mut net_packet = NetPacket::new_encrypt(&mut buf[..])?;
+    net_packet.set_version(Version::V1);
+    net_packet.set_protocol(protocol::Protocol::IpTurn);
+    net_packet.set_transport_protocol(ip_turn_packet::Protocol::Ethernet.into());
+    net_packet.first_set_ttl(3);
+    net_packet.set_source(current_device.virtual_ip);
+    net_packet.set_destination(peer_ip.unwrap_or(Ipv4Addr::BROADCAST));
+    net_packet.set_payload(frame)?;
+    client_cipher.encrypt_ipv4(&mut net_packet)?;
+    match peer_ip {
+        Some(peer_ip) => {
+            sender.mark_active(&peer_ip);
+            if sender
+                .try_send_by_id(net_packet.buffer(), &peer_ip)
+                .is_err()
+            {
+                //经服务端中转的同时发起打洞
+                sender.race(&peer_ip);
+                sender.send_main(net_packet.buffer(), current_device.connect_server)?;
+            }
+        }
+        None => {
+            super::broadcast(server_cipher, None, sender, &mut net_packet, current_device)?;
+        }
+    }
+    Ok(())
+}
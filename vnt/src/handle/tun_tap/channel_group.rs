@@ -14,12 +14,12 @@ impl BufSenderGroup {
     }
 }
 
-pub fn buf_channel_group(size: usize) -> (BufSenderGroup, BufReceiverGroup) {
+pub fn buf_channel_group(size: usize, capacity: usize) -> (BufSenderGroup, BufReceiverGroup) {
     let mut buf_sender_group = Vec::with_capacity(size);
     let mut buf_receiver_group = Vec::with_capacity(size);
     for _ in 0..size {
         let (buf_sender, buf_receiver) =
-            std::sync::mpsc::sync_channel::<(Vec<u8>, usize, usize)>(1);
+            std::sync::mpsc::sync_channel::<(Vec<u8>, usize, usize)>(capacity);
         buf_sender_group.push(buf_sender);
         buf_receiver_group.push(buf_receiver);
     }
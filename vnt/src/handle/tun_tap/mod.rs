@@ -3,14 +3,18 @@ use std::sync::Arc;
 
 use parking_lot::RwLock;
 
+use packet::icmp::Kind;
 use packet::ip::ipv4::packet::IpV4Packet;
 use packet::ip::ipv4::protocol::Protocol;
 
+use crate::channel::loop_guard::{LoopState, SUPPRESS};
+use crate::channel::path_policy::PathPolicy;
 use crate::channel::sender::ChannelSender;
 use crate::cipher::Cipher;
 use crate::error::*;
+use crate::event::EventKind;
 use crate::external_route::ExternalRoute;
-use crate::handle::{check_dest, CurrentDeviceInfo};
+use crate::handle::{check_dest, icmp_error, CurrentDeviceInfo};
 use crate::igmp_server::{IgmpServer, Multicast};
 #[cfg(feature = "ip_proxy")]
 use crate::ip_proxy::{IpProxyMap, ProxyHandler};
@@ -18,7 +22,9 @@ use crate::protocol;
 use crate::protocol::body::ENCRYPTION_RESERVED;
 use crate::protocol::ip_turn_packet::BroadcastPacket;
 use crate::protocol::{ip_turn_packet, NetPacket, Version, MAX_TTL};
+use crate::tun_tap_device::DeviceWriter;
 
+pub mod bridge;
 pub mod channel_group;
 #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
 pub mod tap_handler;
@@ -31,8 +37,13 @@ fn broadcast(
     net_packet: &mut NetPacket<&mut [u8]>,
     current_device: &CurrentDeviceInfo,
 ) -> Result<()> {
+    if !sender.broadcast().allow_send() {
+        return Ok(());
+    }
     let mut peer_ips = Vec::with_capacity(8);
     let vec = sender.route_table_one();
+    //禁止中转的设备只能直连发送
+    let never_relay = sender.path_policy().never_relay();
     let mut relay_count = 0;
     const MAX_COUNT: usize = 8;
     for (peer_ip, route) in vec {
@@ -53,6 +64,9 @@ fn broadcast(
                 .is_ok()
         {
             peer_ips.push(peer_ip);
+        } else if never_relay.contains(&peer_ip) {
+            //当作已发送，服务端不会转发给它
+            peer_ips.push(peer_ip);
         } else {
             relay_count += 1;
         }
@@ -61,6 +75,17 @@ fn broadcast(
         //不需要转发
         return Ok(());
     }
+    if sender.path_policy().default_policy() == PathPolicy::NeverRelay {
+        return Ok(());
+    }
+    for peer_ip in never_relay {
+        if peer_ips.len() == MAX_COUNT {
+            break;
+        }
+        if !peer_ips.contains(&peer_ip) {
+            peer_ips.push(peer_ip);
+        }
+    }
     //转发到服务端的可选择广播，还要进行服务端加密
     if peer_ips.is_empty() {
         sender.send_main(net_packet.buffer(), current_device.connect_server)?;
@@ -95,6 +120,7 @@ pub fn base_handle(
     sender: &ChannelSender,
     buf: &mut [u8],
     data_len: usize, //数据总长度=12+ip包长度
+    device_writer: &DeviceWriter,
     igmp_server: &Option<IgmpServer>,
     current_device: CurrentDeviceInfo,
     ip_route: &Option<ExternalRoute>,
@@ -165,17 +191,38 @@ pub fn base_handle(
         )?;
         return Ok(());
     }
-    if !check_dest(
+    let routed = !check_dest(
         dest_ip,
         current_device.virtual_netmask,
         current_device.virtual_network,
-    ) {
+    );
+    if routed {
         if let Some(ip_route) = ip_route {
-            if let Some(r_dest_ip) = ip_route.route(&dest_ip) {
+            if let Some(entry) = ip_route.entry(&dest_ip) {
+                let r_dest_ip = entry.2;
                 //路由的目标不能是自己
                 if r_dest_ip == src_ip {
                     return Ok(());
                 }
+                match sender.loop_guard().check(net_packet.payload(), entry) {
+                    LoopState::None => {}
+                    LoopState::Detected => {
+                        let network = Ipv4Addr::from(entry.0 & entry.1);
+                        let detail = format!(
+                            "routing loop: route {}/{} via {} sends packets back into the tunnel, suppressed for {}s, check in_ips and the peer's out_ips",
+                            network,
+                            entry.1.count_ones(),
+                            r_dest_ip,
+                            SUPPRESS.as_secs()
+                        );
+                        log::warn!("{}", detail);
+                        sender
+                            .events()
+                            .push(EventKind::Error, Some(r_dest_ip), detail);
+                        return Ok(());
+                    }
+                    LoopState::Suppressed => return Ok(()),
+                }
                 //需要修改目的地址
                 dest_ip = r_dest_ip;
                 net_packet.set_destination(r_dest_ip);
@@ -186,18 +233,73 @@ pub fn base_handle(
             return Ok(());
         }
     }
+    //目标离线或者被策略拒绝时回应icmp不可达，应用可以立即失败，不用等到超时
+    if let Some(code) = icmp_error::unreachable_code(sender, &dest_ip, routed) {
+        reply_icmp(
+            sender,
+            device_writer,
+            net_packet.payload(),
+            dest_ip,
+            Kind::DestinationUnreachable,
+            code,
+        );
+        return Ok(());
+    }
+    let route = sender.route_by_id(&dest_ip);
+    if route.is_err() {
+        //经服务端中转时服务端算作一跳，traceroute中显示为虚拟网关
+        let mut ipv4 = IpV4Packet::unchecked(net_packet.payload_mut());
+        let ttl = ipv4.ttl();
+        if ttl <= 1 {
+            reply_icmp(
+                sender,
+                device_writer,
+                net_packet.payload(),
+                current_device.virtual_gateway,
+                Kind::TimeExceeded,
+                0,
+            );
+            return Ok(());
+        }
+        ipv4.set_ttl(ttl - 1);
+        ipv4.update_checksum();
+    }
     #[cfg(feature = "ip_proxy")]
     if let Some(proxy_map) = proxy_map {
         let mut ipv4_packet = IpV4Packet::new(net_packet.payload_mut())?;
         proxy_map.send_handle(&mut ipv4_packet)?;
     }
     client_cipher.encrypt_ipv4(&mut net_packet)?;
+    sender.mark_active(&dest_ip);
     //优先发到直连到地址
-    if sender
-        .try_send_by_id(net_packet.buffer(), &dest_ip)
-        .is_err()
-    {
+    let sent = match route {
+        Ok(route) => sender
+            .try_send_by_key(net_packet.buffer(), &route.route_key())
+            .is_ok(),
+        Err(_) => false,
+    };
+    if !sent {
+        //经服务端中转的同时发起打洞
+        sender.race(&dest_ip);
         sender.send_main(net_packet.buffer(), current_device.connect_server)?;
     }
     return Ok(());
 }
+
+/// 向本机回应icmp差错报文
+fn reply_icmp(
+    sender: &ChannelSender,
+    device_writer: &DeviceWriter,
+    original: &[u8],
+    from: Ipv4Addr,
+    kind: Kind,
+    code: u8,
+) {
+    //预留以太网帧头
+    if let Some(mut buf) = icmp_error::build(original, from, kind, code, 14) {
+        sender.virtual_to_alias(&mut buf[14..]);
+        if let Err(e) = device_writer.write_ipv4(&mut buf) {
+            log::warn!("回应icmp差错报文失败 {:?}", e);
+        }
+    }
+}
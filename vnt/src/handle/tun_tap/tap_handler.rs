@@ -15,6 +15,7 @@ use crate::channel::sender::ChannelSender;
 use crate::cipher::Cipher;
 use crate::core::status::VntWorker;
 use crate::external_route::ExternalRoute;
+use crate::handle::tun_tap::bridge::{self, MacTable};
 use crate::handle::tun_tap::channel_group::{buf_channel_group, BufSenderGroup};
 use crate::handle::CurrentDeviceInfo;
 use crate::igmp_server::IgmpServer;
@@ -31,9 +32,12 @@ pub fn start(
     current_device: Arc<AtomicCell<CurrentDeviceInfo>>,
     ip_route: Option<ExternalRoute>,
     #[cfg(feature = "ip_proxy")] ip_proxy_map: Option<IpProxyMap>,
+    bridge: Option<MacTable>,
     client_cipher: Cipher,
     server_cipher: Cipher,
     parallel: usize,
+    channel_capacity: usize,
+    buf_len: usize,
 ) {
     if parallel == 1 {
         thread::Builder::new()
@@ -48,8 +52,10 @@ pub fn start(
                     ip_route,
                     #[cfg(feature = "ip_proxy")]
                     ip_proxy_map,
+                    bridge,
                     client_cipher,
                     server_cipher,
+                    buf_len,
                 ) {
                     log::warn!("tap:{:?}", e);
                 }
@@ -59,7 +65,7 @@ pub fn start(
             })
             .unwrap();
     } else {
-        let (buf_sender, buf_receiver) = buf_channel_group(parallel);
+        let (buf_sender, buf_receiver) = buf_channel_group(parallel, channel_capacity);
         for buf_receiver in buf_receiver.0 {
             let sender = sender.clone();
             let device_writer = device_writer.clone();
@@ -68,6 +74,7 @@ pub fn start(
             let ip_route = ip_route.clone();
             #[cfg(feature = "ip_proxy")]
             let ip_proxy_map = ip_proxy_map.clone();
+            let bridge = bridge.clone();
             let client_cipher = client_cipher.clone();
             let server_cipher = server_cipher.clone();
             thread::spawn(move || {
@@ -82,6 +89,7 @@ pub fn start(
                         &ip_route,
                         #[cfg(feature = "ip_proxy")]
                         &ip_proxy_map,
+                        &bridge,
                         &client_cipher,
                         &server_cipher,
                     ) {
@@ -98,7 +106,7 @@ pub fn start(
         thread::Builder::new()
             .name("tap_handler".into())
             .spawn(move || {
-                if let Err(e) = start_(&sender, device_reader, buf_sender) {
+                if let Err(e) = start_(&sender, device_reader, buf_sender, buf_len) {
                     log::warn!("tap:{:?}", e);
                 }
                 let _ = sender.close();
@@ -113,9 +121,10 @@ fn start_(
     sender: &ChannelSender,
     device_reader: DeviceReader,
     mut buf_sender: BufSenderGroup,
+    buf_len: usize,
 ) -> io::Result<()> {
     loop {
-        let mut buf = vec![0; 4096];
+        let mut buf = vec![0; buf_len];
         if sender.is_close() {
             return Ok(());
         }
@@ -138,10 +147,12 @@ fn start_simple(
     current_device: Arc<AtomicCell<CurrentDeviceInfo>>,
     ip_route: Option<ExternalRoute>,
     #[cfg(feature = "ip_proxy")] ip_proxy_map: Option<IpProxyMap>,
+    bridge: Option<MacTable>,
     client_cipher: Cipher,
     server_cipher: Cipher,
+    buf_len: usize,
 ) -> io::Result<()> {
-    let mut buf = [0; 4096];
+    let mut buf = vec![0; buf_len];
     loop {
         if sender.is_close() {
             return Ok(());
@@ -157,6 +168,7 @@ fn start_simple(
             &ip_route,
             #[cfg(feature = "ip_proxy")]
             &ip_proxy_map,
+            &bridge,
             &client_cipher,
             &server_cipher,
         ) {
@@ -174,11 +186,31 @@ fn handle(
     sender: &ChannelSender,
     ip_route: &Option<ExternalRoute>,
     #[cfg(feature = "ip_proxy")] proxy_map: &Option<IpProxyMap>,
+    bridge: &Option<MacTable>,
     client_cipher: &Cipher,
     server_cipher: &Cipher,
 ) -> crate::Result<()> {
     let mut ethernet_packet = EthernetPacket::new(&mut buf[..len])?;
     let current_device = current_device.load();
+    if let Some(mac_table) = bridge {
+        // 本机发出的帧(网卡设置了arp off，目的mac也是自己)和发往虚拟ip的帧走三层转发，
+        // 其余的是网桥上其他设备的帧，原样转发到对端
+        let tap_mac = device_writer.tap_mac().unwrap_or_default();
+        let dest = ethernet_packet.destination();
+        if ethernet_packet.source() != &tap_mac[..]
+            && dest != &tap_mac[..]
+            && !bridge::is_vnt_mac(dest, &current_device)
+        {
+            return bridge::send_frame(
+                sender,
+                &ethernet_packet.buffer,
+                mac_table,
+                &current_device,
+                client_cipher,
+                server_cipher,
+            );
+        }
+    }
     match ethernet_packet.protocol() {
         ethernet::protocol::Protocol::Arp => {
             let mut out_ethernet_packet =
@@ -243,6 +275,7 @@ fn handle(
                 sender,
                 &mut buf[2..],
                 len - 2,
+                device_writer,
                 igmp_server,
                 current_device,
                 ip_route,
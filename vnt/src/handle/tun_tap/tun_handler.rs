@@ -55,10 +55,12 @@ fn handle(
     if src_ip == dest_ip {
         return icmp(&device_writer, ipv4_packet);
     }
+    sender.alias_to_virtual(&mut data[12..len]);
     return crate::handle::tun_tap::base_handle(
         sender,
         data,
         len,
+        device_writer,
         igmp_server,
         current_device,
         ip_route,
@@ -81,6 +83,8 @@ pub fn start(
     client_cipher: Cipher,
     server_cipher: Cipher,
     parallel: usize,
+    channel_capacity: usize,
+    buf_len: usize,
 ) {
     if parallel == 1 {
         thread::Builder::new()
@@ -97,6 +101,7 @@ pub fn start(
                     ip_proxy_map,
                     client_cipher,
                     server_cipher,
+                    buf_len,
                 ) {
                     log::warn!("stop:{}", e);
                 }
@@ -106,7 +111,7 @@ pub fn start(
             })
             .unwrap();
     } else {
-        let (buf_sender, buf_receiver) = buf_channel_group(parallel);
+        let (buf_sender, buf_receiver) = buf_channel_group(parallel, channel_capacity);
         for buf_receiver in buf_receiver.0 {
             let sender = sender.clone();
             let device_writer = device_writer.clone();
@@ -146,7 +151,7 @@ pub fn start(
         thread::Builder::new()
             .name("tun_handler".into())
             .spawn(move || {
-                if let Err(e) = start_(&sender, device_reader, buf_sender) {
+                if let Err(e) = start_(&sender, device_reader, buf_sender, buf_len) {
                     log::warn!("stop:{}", e);
                 }
                 let _ = sender.close();
@@ -161,15 +166,19 @@ fn start_(
     sender: &ChannelSender,
     device_reader: DeviceReader,
     mut buf_sender: BufSenderGroup,
+    buf_len: usize,
 ) -> io::Result<()> {
     loop {
-        let mut buf = vec![0; 4096];
+        let mut buf = vec![0; buf_len];
         buf[..12].fill(0);
         if sender.is_close() {
             return Ok(());
         }
         let start = 0;
+        sender.liveness().tun_idle();
         let len = device_reader.read(&mut buf[12..])? + 12;
+        //队列满时会阻塞在这里，也算作处理中
+        sender.liveness().tun_busy();
         #[cfg(any(target_os = "macos"))]
         let start = 4;
         if !buf_sender.send((buf, start, len)) {
@@ -191,14 +200,17 @@ fn start_simple(
     #[cfg(feature = "ip_proxy")] ip_proxy_map: Option<IpProxyMap>,
     client_cipher: Cipher,
     server_cipher: Cipher,
+    buf_len: usize,
 ) -> io::Result<()> {
-    let mut buf = [0; 4096];
+    let mut buf = vec![0; buf_len];
     loop {
         if sender.is_close() {
             return Ok(());
         }
         buf[..12].fill(0);
+        sender.liveness().tun_idle();
         let len = device_reader.read(&mut buf[12..])? + 12;
+        sender.liveness().tun_busy();
         #[cfg(any(target_os = "macos"))]
         let mut buf = &mut buf[4..];
         match handle(
@@ -11,6 +11,7 @@ use packet::icmp::icmp;
 use packet::icmp::icmp::HeaderOther;
 use packet::ip::ipv4::packet::IpV4Packet;
 
+use crate::channel::mem_limit::{self, MAX_PROXY_SESSIONS};
 use crate::channel::sender::ChannelSender;
 use crate::cipher::Cipher;
 use crate::handle::CurrentDeviceInfo;
@@ -38,6 +39,7 @@ impl IcmpProxy {
             Type::RAW,
             Some(socket2::Protocol::ICMPV4),
         )?);
+        crate::util::fwmark::mark_only(icmp_socket.as_ref())?;
         icmp_socket.bind(&SockAddr::from(addr))?;
         Ok(IcmpProxy {
             icmp_socket,
@@ -65,12 +67,12 @@ impl IcmpProxy {
                                         Ok(icmp_packet) => {
                                             match icmp_packet.header_other() {
                                                 HeaderOther::Identifier(id, seq) => {
-                                                    if let Some(entry) =
-                                                        self.icmp_proxy_map.get(&(peer_ip, id, seq))
+                                                    //收到响应后不再需要，避免每个请求都留下一项
+                                                    if let Some((_, dest_ip)) = self
+                                                        .icmp_proxy_map
+                                                        .remove(&(peer_ip, id, seq))
                                                     {
                                                         //将数据发送到真实的来源
-                                                        let dest_ip = *entry.value();
-                                                        drop(entry);
                                                         ipv4_packet.set_destination_ip(dest_ip);
                                                         ipv4_packet.update_checksum();
                                                         send(
@@ -128,6 +130,7 @@ impl ProxyHandler for IcmpHandler {
         let icmp_packet = icmp::IcmpPacket::new(ipv4.payload())?;
         match icmp_packet.header_other() {
             HeaderOther::Identifier(id, seq) => {
+                mem_limit::make_room_any(&self.1, &(dest_ip, id, seq), MAX_PROXY_SESSIONS);
                 self.1.insert((dest_ip, id, seq), source);
                 self.0.send_to(
                     ipv4.payload(),
@@ -168,6 +168,7 @@ pub fn send(
         .try_send_by_id(net_packet.buffer(), &dest_ip)
         .is_err()
     {
+        sender.race(&dest_ip);
         let connect_server = current_device.connect_server;
         if let Err(e) = sender.send_main(net_packet.buffer(), connect_server) {
             log::warn!("发送到目标失败:{},{}", e, connect_server);
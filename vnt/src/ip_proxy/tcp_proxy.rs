@@ -1,3 +1,4 @@
+use crate::channel::mem_limit::{self, MAX_PROXY_SESSIONS};
 use crate::ip_proxy::ProxyHandler;
 use crossbeam_utils::atomic::AtomicCell;
 use dashmap::DashMap;
@@ -10,7 +11,7 @@ use std::time::{Duration, Instant};
 use tokio::io::AsyncReadExt;
 use tokio::io::AsyncWriteExt;
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
-use tokio::net::{TcpListener, TcpStream};
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
 
 pub struct TcpProxy {
     tcp_proxy_port: u16,
@@ -48,7 +49,7 @@ impl TcpProxy {
                             tokio::spawn(async move {
                                 let peer_tcp_stream = match tokio::time::timeout(
                                     Duration::from_secs(5),
-                                    TcpStream::connect(dest_addr),
+                                    connect(dest_addr),
                                 )
                                 .await
                                 {
@@ -153,6 +154,7 @@ impl ProxyHandler for TcpHandler {
         ipv4.update_checksum();
         let key = SocketAddrV4::new(source, source_port);
         //https://github.com/crossbeam-rs/crossbeam/issues/1023
+        mem_limit::make_room_any(&self.1, &key, MAX_PROXY_SESSIONS);
         self.1.insert(key, SocketAddrV4::new(dest_ip, dest_port));
         Ok(false)
     }
@@ -176,3 +178,10 @@ impl ProxyHandler for TcpHandler {
         Ok(())
     }
 }
+
+/// 连接代理目标，连接之前打上vnt的fwmark
+async fn connect(dest_addr: SocketAddrV4) -> io::Result<TcpStream> {
+    let socket = TcpSocket::new_v4()?;
+    crate::util::fwmark::mark_only(&socket)?;
+    socket.connect(dest_addr.into()).await
+}
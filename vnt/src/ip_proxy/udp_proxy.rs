@@ -1,3 +1,4 @@
+use crate::channel::mem_limit::{self, MAX_PROXY_SESSIONS};
 use crate::ip_proxy::{DashMapNew, ProxyHandler};
 use crossbeam_utils::atomic::AtomicCell;
 use dashmap::DashMap;
@@ -84,6 +85,7 @@ async fn start0(
             Ok(udp) => udp,
             Err(_) => UdpSocket::bind("0.0.0.0:0").await?,
         };
+        crate::util::fwmark::mark_only(&peer_udp_socket)?;
         peer_udp_socket.connect(dest_addr).await?;
         peer_udp_socket.send(buf).await?;
         let peer_udp_socket = Arc::new(peer_udp_socket);
@@ -157,6 +159,7 @@ impl ProxyHandler for UdpHandler {
         ipv4.set_destination_ip(destination);
         ipv4.update_checksum();
         let key = SocketAddrV4::new(source, source_port);
+        mem_limit::make_room_any(&self.1, &key, MAX_PROXY_SESSIONS);
         self.1.insert(key, SocketAddrV4::new(dest_ip, dest_port));
         Ok(false)
     }
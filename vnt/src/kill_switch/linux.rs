@@ -0,0 +1,104 @@
+use std::io;
+use std::io::Write;
+use std::net::IpAddr;
+use std::process::{Command, Stdio};
+
+const TABLE: &str = "vnt_kill_switch";
+/// 系统配置的dns服务器，使用systemd-resolved时/etc/resolv.conf中是本地的127.0.0.53，
+/// 实际查询的上游服务器在另一个文件中
+const RESOLV_CONFS: [&str; 2] = ["/etc/resolv.conf", "/run/systemd/resolve/resolv.conf"];
+
+/// 使用nftables实现，只放行回环、虚拟网卡和带有vnt标记的流量，
+/// 以及发往系统dns服务器的查询(系统解析服务端域名时无法给socket打标记)
+pub struct NftKillSwitch;
+
+impl NftKillSwitch {
+    pub fn enable(name: &str, mark: u32) -> io::Result<NftKillSwitch> {
+        if mark == 0 {
+            return Err(io::Error::new(io::ErrorKind::Other, "fwmark not set"));
+        }
+        // 清理非正常退出时残留的规则
+        delete_table();
+        let rules = format!(
+            "table inet {table} {{
+    chain output {{
+        type filter hook output priority 0; policy drop;
+        oifname \"lo\" accept
+        oifname \"{name}\" accept
+        meta mark {mark} accept
+{dns}    }}
+}}
+",
+            table = TABLE,
+            name = name,
+            mark = mark,
+            dns = dns_rules()
+        );
+        let mut child = Command::new("nft")
+            .arg("-f")
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(rules.as_bytes())?;
+        }
+        let out = child.wait_with_output()?;
+        if !out.status.success() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("nft error: {}", String::from_utf8_lossy(&out.stderr)),
+            ));
+        }
+        log::info!("kill switch enabled,dev={},mark={}", name, mark);
+        Ok(NftKillSwitch)
+    }
+}
+
+impl Drop for NftKillSwitch {
+    fn drop(&mut self) {
+        delete_table();
+        log::info!("kill switch disabled");
+    }
+}
+
+fn delete_table() {
+    let cmd = format!("nft delete table inet {}", TABLE);
+    if let Err(e) = Command::new("sh").arg("-c").arg(&cmd).output() {
+        log::warn!("cmd:{},{:?}", cmd, e);
+    }
+}
+
+/// 放行发往系统dns服务器的53端口，回环地址已经放行
+fn dns_rules() -> String {
+    let mut v4 = Vec::new();
+    let mut v6 = Vec::new();
+    for path in RESOLV_CONFS {
+        for ip in crate::util::resolve::nameservers(path) {
+            if ip.is_loopback() {
+                continue;
+            }
+            let list = match ip {
+                IpAddr::V4(_) => &mut v4,
+                IpAddr::V6(_) => &mut v6,
+            };
+            let ip = ip.to_string();
+            if !list.contains(&ip) {
+                list.push(ip);
+            }
+        }
+    }
+    let mut rules = String::new();
+    for (family, list) in [("ip", v4), ("ip6", v6)] {
+        if list.is_empty() {
+            continue;
+        }
+        rules.push_str(&format!(
+            "        {} daddr {{ {} }} meta l4proto {{ tcp, udp }} th dport 53 accept\n",
+            family,
+            list.join(", ")
+        ));
+    }
+    rules
+}
@@ -0,0 +1,41 @@
+use std::io;
+
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "windows")]
+mod windows;
+
+/// linux下未指定路由表时，vnt自身流量使用的fwmark
+pub const DEFAULT_MARK: u32 = 0x766e74;
+
+/// 防泄漏开关，开启后只允许经过虚拟网卡的流量和vnt自身的流量出站，
+/// 隧道断开时不会通过物理网卡泄漏数据，drop时删除规则
+pub struct KillSwitch {
+    #[cfg(target_os = "linux")]
+    inner: linux::NftKillSwitch,
+    #[cfg(target_os = "windows")]
+    inner: windows::WfpKillSwitch,
+}
+
+impl KillSwitch {
+    /// name为虚拟网卡名称
+    #[cfg(any(target_os = "linux", target_os = "windows"))]
+    pub fn enable(name: &str) -> io::Result<KillSwitch> {
+        #[cfg(target_os = "linux")]
+        let inner = linux::NftKillSwitch::enable(name, crate::util::fwmark::get())?;
+        #[cfg(target_os = "windows")]
+        let inner = windows::WfpKillSwitch::enable(name)?;
+        Ok(KillSwitch { inner })
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    pub fn enable(_name: &str) -> io::Result<KillSwitch> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "kill switch not supported",
+        ))
+    }
+    pub fn disable(self) {
+        #[cfg(any(target_os = "linux", target_os = "windows"))]
+        drop(self.inner);
+    }
+}
@@ -0,0 +1,116 @@
+use std::io;
+use std::net::IpAddr;
+
+use winapi::shared::guiddef::GUID;
+
+use crate::util::wfp::{
+    app_condition, guid, interface_condition, interface_luid, local_port_condition,
+    loopback_condition, protocol_condition, remote_port_condition, remote_v4_condition,
+    remote_v6_condition, AppId, Engine, FWPM_LAYER_ALE_AUTH_CONNECT_V4,
+    FWPM_LAYER_ALE_AUTH_CONNECT_V6, FWP_ACTION_BLOCK, FWP_ACTION_PERMIT, IPPROTO_TCP, IPPROTO_UDP,
+};
+
+/// vnt自己的子层，权重最高，避免被其他子层的放行规则覆盖
+const KILL_SWITCH_SUBLAYER: GUID = guid(
+    0x5a5f1bd4,
+    0x2a2d,
+    0x4b9b,
+    [0x9c, 0x3e, 0x76, 0x6e, 0x74, 0x00, 0x00, 0x01],
+);
+
+/// 使用WFP动态会话实现，会话关闭或者进程退出时系统会自动删除规则。
+/// 系统的dns查询和dhcp由svchost中的服务发出，不属于vnt，单独放行发往系统dns服务器的53端口和dhcp
+pub struct WfpKillSwitch {
+    _engine: Engine,
+}
+
+impl WfpKillSwitch {
+    pub fn enable(name: &str) -> io::Result<WfpKillSwitch> {
+        let luid = interface_luid(name)?;
+        // 出错时engine drop会关闭会话，已添加的规则随之删除
+        let engine = Engine::open_dynamic()?;
+        let app_id = AppId::current_exe()?;
+        let nameservers: Vec<IpAddr> = crate::util::resolve::nameservers()
+            .into_iter()
+            .filter(|ip| !ip.is_loopback())
+            .collect();
+        log::info!("kill switch allows dns servers {:?}", nameservers);
+        engine.add_sublayer(KILL_SWITCH_SUBLAYER, "vnt kill switch", u16::MAX)?;
+        for (layer, v4) in [
+            (FWPM_LAYER_ALE_AUTH_CONNECT_V4, true),
+            (FWPM_LAYER_ALE_AUTH_CONNECT_V6, false),
+        ] {
+            // vnt自身的流量、虚拟网卡、回环
+            for condition in [
+                app_condition(&app_id),
+                interface_condition(&luid),
+                loopback_condition(),
+            ] {
+                engine.add_filter(
+                    "vnt kill switch",
+                    layer,
+                    KILL_SWITCH_SUBLAYER,
+                    &mut [condition],
+                    FWP_ACTION_PERMIT,
+                    Some(15),
+                )?;
+            }
+            // dhcp续租，ipv6为dhcpv6
+            let (server_port, client_port) = if v4 { (67, 68) } else { (547, 546) };
+            engine.add_filter(
+                "vnt kill switch dhcp",
+                layer,
+                KILL_SWITCH_SUBLAYER,
+                &mut [
+                    protocol_condition(IPPROTO_UDP),
+                    remote_port_condition(server_port),
+                    local_port_condition(client_port),
+                ],
+                FWP_ACTION_PERMIT,
+                Some(15),
+            )?;
+            // 系统解析服务端域名
+            for ip in &nameservers {
+                let v6;
+                let addr = match ip {
+                    IpAddr::V4(ip) if v4 => remote_v4_condition(*ip),
+                    IpAddr::V6(ip) if !v4 => {
+                        v6 = ip.octets();
+                        remote_v6_condition(&v6)
+                    }
+                    _ => continue,
+                };
+                engine.add_filter(
+                    "vnt kill switch dns",
+                    layer,
+                    KILL_SWITCH_SUBLAYER,
+                    &mut [
+                        protocol_condition(IPPROTO_UDP),
+                        protocol_condition(IPPROTO_TCP),
+                        remote_port_condition(53),
+                        addr,
+                    ],
+                    FWP_ACTION_PERMIT,
+                    Some(15),
+                )?;
+            }
+            // 其余全部拦截
+            engine.add_filter(
+                "vnt kill switch",
+                layer,
+                KILL_SWITCH_SUBLAYER,
+                &mut [],
+                FWP_ACTION_BLOCK,
+                None,
+            )?;
+        }
+        log::info!("kill switch enabled,dev={}", name);
+        Ok(WfpKillSwitch { _engine: engine })
+    }
+}
+
+impl Drop for WfpKillSwitch {
+    fn drop(&mut self) {
+        log::info!("kill switch disabled");
+    }
+}
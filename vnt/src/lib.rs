@@ -2,17 +2,29 @@ use crate::error::Error;
 pub const VNT_VERSION: &'static str = env!("CARGO_PKG_VERSION");
 pub type Result<T> = std::result::Result<T, Error>;
 
+pub mod bridge;
 pub mod channel;
 pub mod cipher;
 pub mod core;
 pub mod error;
+pub mod event;
 pub mod external_route;
 pub mod handle;
 pub mod igmp_server;
 #[cfg(feature = "ip_proxy")]
 pub mod ip_proxy;
+pub mod kill_switch;
+pub mod maintenance;
+pub mod masquerade;
 pub mod nat;
 pub mod proto;
 pub mod protocol;
+pub mod quota;
+pub mod remote_config;
+pub mod route_conflict;
+pub mod split_dns;
+pub mod split_tunnel;
+pub mod stream;
+pub mod subnet_alias;
 pub mod tun_tap_device;
 pub mod util;
@@ -0,0 +1,58 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::proto::message::MaintenanceNotice;
+
+/// 服务端的维护通知，计划内的重启期间显示说明，而不是当作故障
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Maintenance {
+    pub notice: String,
+    /// 建议重新注册前等待的秒数，0表示未指定
+    pub retry_after: u32,
+    /// 中转全部断开的时间，None表示不断开
+    pub drain_deadline: Option<Instant>,
+    /// 预计结束时间(unix秒)，0表示未知
+    pub end_time: u32,
+}
+
+impl Maintenance {
+    /// 说明为空表示维护结束，返回None
+    pub fn from_proto(notice: &MaintenanceNotice) -> Option<Maintenance> {
+        if notice.notice.is_empty() {
+            return None;
+        }
+        Some(Maintenance {
+            notice: notice.notice.clone(),
+            retry_after: notice.retry_after,
+            drain_deadline: if notice.drain_seconds == 0 {
+                None
+            } else {
+                Some(Instant::now() + Duration::from_secs(notice.drain_seconds as u64))
+            },
+            end_time: notice.end_time,
+        })
+    }
+    /// 中转还能使用的时长，不断开时为None
+    pub fn drain_remaining(&self) -> Option<Duration> {
+        self.drain_deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+}
+
+impl fmt::Display for Maintenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.notice)?;
+        match self.drain_remaining() {
+            None => {}
+            Some(remaining) if remaining.is_zero() => write!(f, ", relay drained")?,
+            Some(remaining) => write!(f, ", relay drains in {}s", remaining.as_secs())?,
+        }
+        if self.retry_after != 0 {
+            write!(f, ", retry after {}s", self.retry_after)?;
+        }
+        if self.end_time != 0 {
+            write!(f, " end_time={}", self.end_time)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+use std::io;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const TABLE: &str = "vnt_masquerade";
+const IP_FORWARD: &str = "/proc/sys/net/ipv4/ip_forward";
+
+/// 使用nftables实现，只对虚拟网段访问发布网段的流量做源地址转换
+pub struct NftMasquerade {
+    /// 开启前的ip_forward，退出时还原
+    ip_forward: Option<String>,
+}
+
+impl NftMasquerade {
+    pub fn enable(name: &str, source: &str, routes: &[String]) -> io::Result<NftMasquerade> {
+        // 清理非正常退出时残留的规则
+        delete_table();
+        let mut rules = format!(
+            "table ip {} {{
+    chain postrouting {{
+        type nat hook postrouting priority 100; policy accept;
+",
+            TABLE
+        );
+        for route in routes {
+            rules.push_str(&format!(
+                "        ip saddr {} ip daddr {} oifname != \"{}\" masquerade\n",
+                source, route, name
+            ));
+        }
+        rules.push_str("    }\n}\n");
+        let mut child = Command::new("nft")
+            .arg("-f")
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(rules.as_bytes())?;
+        }
+        let out = child.wait_with_output()?;
+        if !out.status.success() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("nft error: {}", String::from_utf8_lossy(&out.stderr)),
+            ));
+        }
+        let ip_forward = std::fs::read_to_string(IP_FORWARD).ok();
+        if ip_forward.as_deref().map(|v| v.trim()) != Some("1") {
+            if let Err(e) = std::fs::write(IP_FORWARD, "1") {
+                log::warn!("enable ip_forward {:?}", e);
+            }
+        }
+        Ok(NftMasquerade { ip_forward })
+    }
+}
+
+impl Drop for NftMasquerade {
+    fn drop(&mut self) {
+        delete_table();
+        if let Some(ip_forward) = &self.ip_forward {
+            let _ = std::fs::write(IP_FORWARD, ip_forward);
+        }
+        log::info!("masquerade disabled");
+    }
+}
+
+fn delete_table() {
+    let cmd = format!("nft delete table ip {}", TABLE);
+    if let Err(e) = Command::new("sh").arg("-c").arg(&cmd).output() {
+        log::warn!("cmd:{},{:?}", cmd, e);
+    }
+}
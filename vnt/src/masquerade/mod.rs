@@ -0,0 +1,55 @@
+use std::io;
+use std::net::Ipv4Addr;
+
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "windows")]
+mod windows;
+
+/// 站点到站点模式，虚拟网络访问发布的子网时源地址转换成本机局域网地址，
+/// 局域网中的设备不需要添加回程路由，drop时删除规则
+pub struct Masquerade {
+    #[cfg(target_os = "linux")]
+    inner: linux::NftMasquerade,
+    #[cfg(target_os = "windows")]
+    inner: windows::NetNat,
+}
+
+impl Masquerade {
+    /// name为虚拟网卡名称，virtual_ip和netmask确定虚拟网段，routes为需要nat的发布网段
+    #[cfg(any(target_os = "linux", target_os = "windows"))]
+    pub fn enable(
+        name: &str,
+        virtual_ip: Ipv4Addr,
+        netmask: Ipv4Addr,
+        routes: &[(u32, u32)],
+    ) -> io::Result<Masquerade> {
+        let source = crate::remote_config::format_cidr(virtual_ip.into(), netmask.into());
+        let routes: Vec<String> = routes
+            .iter()
+            .map(|(dest, mask)| crate::remote_config::format_cidr(*dest, *mask))
+            .collect();
+        #[cfg(target_os = "linux")]
+        let inner = linux::NftMasquerade::enable(name, &source, &routes)?;
+        #[cfg(target_os = "windows")]
+        let inner = windows::NetNat::enable(name, &source, &routes)?;
+        log::info!("masquerade enabled,{} -> {:?}", source, routes);
+        Ok(Masquerade { inner })
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    pub fn enable(
+        _name: &str,
+        _virtual_ip: Ipv4Addr,
+        _netmask: Ipv4Addr,
+        _routes: &[(u32, u32)],
+    ) -> io::Result<Masquerade> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "masquerade not supported",
+        ))
+    }
+    pub fn disable(self) {
+        #[cfg(any(target_os = "linux", target_os = "windows"))]
+        drop(self.inner);
+    }
+}
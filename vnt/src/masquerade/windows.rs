@@ -0,0 +1,69 @@
+use std::io;
+use std::os::windows::process::CommandExt;
+use std::process::Command;
+
+const NAT_NAME: &str = "vnt";
+
+/// 使用WinNAT(New-NetNat)实现，WinNAT只能按内部网段转换，不能限定目的网段，
+/// 目的地址仍由-o限制
+pub struct NetNat;
+
+impl NetNat {
+    pub fn enable(name: &str, source: &str, routes: &[String]) -> io::Result<NetNat> {
+        // 清理非正常退出时残留的规则
+        remove();
+        // 虚拟网卡和通往发布网段的网卡都需要开启转发
+        let mut cmd = format!(
+            "Set-NetIPInterface -InterfaceAlias '{}' -AddressFamily IPv4 -Forwarding Enabled",
+            name
+        );
+        for route in routes {
+            let ip = route.split('/').next().unwrap_or(route);
+            cmd.push_str(&format!(
+                ";Find-NetRoute -RemoteIPAddress {} | Select-Object -First 1 | Set-NetIPInterface -Forwarding Enabled",
+                ip
+            ));
+        }
+        cmd.push_str(&format!(
+            ";New-NetNat -Name {} -InternalIPInterfaceAddressPrefix {}",
+            NAT_NAME, source
+        ));
+        powershell(&cmd)?;
+        Ok(NetNat)
+    }
+}
+
+impl Drop for NetNat {
+    fn drop(&mut self) {
+        remove();
+        log::info!("masquerade disabled");
+    }
+}
+
+fn remove() {
+    let cmd = format!(
+        "Get-NetNat -Name {} -ErrorAction SilentlyContinue | Remove-NetNat -Confirm:$false",
+        NAT_NAME
+    );
+    if let Err(e) = powershell(&cmd) {
+        log::warn!("{:?}", e);
+    }
+}
+
+fn powershell(cmd: &str) -> io::Result<()> {
+    let out = Command::new("powershell")
+        .creation_flags(0x08000000) //CREATE_NO_WINDOW
+        .args(["-NoProfile", "-Command", cmd])
+        .output()?;
+    if !out.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "cmd:{},error: {}",
+                cmd,
+                String::from_utf8_lossy(&out.stderr)
+            ),
+        ));
+    }
+    Ok(())
+}
@@ -14,6 +14,7 @@ mod stun_test;
 
 pub fn local_ipv4_() -> io::Result<Ipv4Addr> {
     let socket = UdpSocket::bind("0.0.0.0:0")?;
+    crate::util::fwmark::mark(&socket)?;
     socket.connect("8.8.8.8:80")?;
     let addr = socket.local_addr()?;
     match addr.ip() {
@@ -50,9 +51,24 @@ pub fn local_ipv6() -> Option<Ipv6Addr> {
     }
 }
 
+/// 绑定了具体地址时，本地地址就是绑定的地址
+fn local_addr(
+    local_ip: Option<IpAddr>,
+    local_ipv4: Option<Ipv4Addr>,
+    ipv6: Option<Ipv6Addr>,
+) -> (Option<Ipv4Addr>, Option<Ipv6Addr>) {
+    match local_ip {
+        None => (local_ipv4, ipv6),
+        Some(IpAddr::V4(ip)) => (Some(ip), None),
+        Some(IpAddr::V6(ip)) => (None, Some(ip)),
+    }
+}
+
 #[derive(Clone)]
 pub struct NatTest {
     stun_server: Vec<String>,
+    /// 指定了本地监听地址时，只使用这个地址作为本地地址
+    local_ip: Option<IpAddr>,
     info: Arc<Mutex<NatInfo>>,
     time: Arc<AtomicCell<Instant>>,
 }
@@ -84,9 +100,14 @@ impl NatTest {
         ipv6: Option<Ipv6Addr>,
         udp_port: u16,
         tcp_port: u16,
+        local_ip: Option<IpAddr>,
     ) -> NatTest {
-        let server = stun_server[0].clone();
-        stun_server.resize(3, server);
+        //服务器不足3个时用第一个补齐，多的留给rfc5389探测时按顺序备用
+        if stun_server.len() < 3 {
+            let server = stun_server[0].clone();
+            stun_server.resize(3, server);
+        }
+        let (local_ipv4, ipv6) = local_addr(local_ip, local_ipv4, ipv6);
         let nat_info = NatInfo::new(
             vec![public_ip],
             public_port,
@@ -100,6 +121,7 @@ impl NatTest {
         let info = Arc::new(Mutex::new(nat_info));
         NatTest {
             stun_server,
+            local_ip,
             info,
             time: Arc::new(AtomicCell::new(Instant::now())),
         }
@@ -125,6 +147,7 @@ impl NatTest {
         udp_port: u16,
         tcp_port: u16,
     ) -> NatInfo {
+        let (local_ipv4, ipv6) = local_addr(self.local_ip, local_ipv4, ipv6);
         let info = NatTest::re_test_(
             &self.stun_server,
             public_ip,
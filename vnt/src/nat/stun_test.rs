@@ -7,18 +7,23 @@ use crate::channel::punch::NatType;
 use stun_format::Attr;
 use tokio::net::UdpSocket;
 
+/// rfc5389的magic cookie
+const MAGIC_COOKIE: u32 = 0x2112A442;
+
 pub async fn stun_test_nat(stun_servers: Vec<String>) -> io::Result<(NatType, Vec<Ipv4Addr>, u16)> {
     let mut h = Vec::new();
-    for x in stun_servers {
+    for x in stun_servers.clone() {
         let handle = tokio::spawn(test_nat(x));
         h.push(handle);
     }
     let mut nat_type = NatType::Cone;
     let mut port_range = 0;
     let mut hash_set = HashSet::new();
+    let mut count = 0;
     for x in h {
         if let Ok(rs) = x.await {
             if let Ok((nat_type_t, ip_list_t, port_range_t)) = rs {
+                count += 1;
                 if nat_type_t == NatType::Symmetric {
                     nat_type = NatType::Symmetric;
                 }
@@ -31,17 +36,181 @@ pub async fn stun_test_nat(stun_servers: Vec<String>) -> io::Result<(NatType, Ve
             }
         }
     }
+    if count == 0 {
+        //公共stun服务大多只支持rfc5389，不支持change request
+        return test_nat_rfc5389(stun_servers).await;
+    }
     Ok((nat_type, hash_set.into_iter().collect(), port_range))
 }
 
+/// 用同一个端口按顺序请求不同的stun服务器，映射地址不同则是对称网络，
+/// 前面的服务器无响应时使用后面的
+async fn test_nat_rfc5389(
+    mut stun_servers: Vec<String>,
+) -> io::Result<(NatType, Vec<Ipv4Addr>, u16)> {
+    let mut seen = HashSet::new();
+    stun_servers.retain(|v| seen.insert(v.clone()));
+    let udp = UdpSocket::bind("0.0.0.0:0").await?;
+    crate::util::fwmark::mark(&udp)?;
+    let mut mapped_list: Vec<SocketAddr> = Vec::with_capacity(2);
+    for stun_server in stun_servers {
+        if mapped_list.len() >= 2 {
+            break;
+        }
+        let addr = match tokio::net::lookup_host(&stun_server)
+            .await
+            .map(|mut v| v.find(|addr| addr.is_ipv4()))
+        {
+            Ok(Some(addr)) => addr,
+            Ok(None) => continue,
+            Err(e) => {
+                log::warn!("stun服务器解析失败 {},{:?}", stun_server, e);
+                continue;
+            }
+        };
+        match binding_request(&udp, addr).await {
+            Ok(mapped_addr) => mapped_list.push(mapped_addr),
+            Err(e) => log::warn!("stun服务器无响应 {},{:?}", stun_server, e),
+        }
+    }
+    let first = match mapped_list.first() {
+        Some(first) => *first,
+        None => return Err(io::Error::new(io::ErrorKind::Other, "stun response err")),
+    };
+    let mut nat_type = NatType::Cone;
+    let mut port_range = 0;
+    let mut hash_set = HashSet::new();
+    for mapped_addr in &mapped_list {
+        if let IpAddr::V4(ip) = mapped_addr.ip() {
+            hash_set.insert(ip);
+        }
+        if *mapped_addr != first {
+            nat_type = NatType::Symmetric;
+            port_range = port_range.max(mapped_addr.port().abs_diff(first.port()));
+        }
+    }
+    Ok((nat_type, hash_set.into_iter().collect(), port_range))
+}
+
+async fn binding_request(udp: &UdpSocket, stun_server: SocketAddr) -> io::Result<SocketAddr> {
+    let tid: [u8; 12] = rand::random();
+    let mut request = [0u8; 20];
+    request[..2].copy_from_slice(&0x0001u16.to_be_bytes());
+    request[4..8].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    request[8..].copy_from_slice(&tid);
+    for _ in 0..2 {
+        udp.send_to(&request, stun_server).await?;
+        let mut buf = [0; 1024];
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(500);
+        loop {
+            let (len, addr) = match tokio::time::timeout_at(deadline, udp.recv_from(&mut buf)).await
+            {
+                Ok(rs) => rs?,
+                Err(_) => break,
+            };
+            if addr != stun_server {
+                continue;
+            }
+            if let Some(mapped_addr) = parse_binding_response(&buf[..len], &tid) {
+                return Ok(mapped_addr);
+            }
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::TimedOut, "stun timeout"))
+}
+
+/// 解析rfc5389的绑定响应，优先使用XOR-MAPPED-ADDRESS
+fn parse_binding_response(buf: &[u8], tid: &[u8; 12]) -> Option<SocketAddr> {
+    if buf.len() < 20
+        || buf[..2] != 0x0101u16.to_be_bytes()
+        || buf[4..8] != MAGIC_COOKIE.to_be_bytes()
+        || &buf[8..20] != tid
+    {
+        return None;
+    }
+    let len = (u16::from_be_bytes([buf[2], buf[3]]) as usize).min(buf.len() - 20);
+    let mut attrs = &buf[20..20 + len];
+    let mut mapped_addr = None;
+    while attrs.len() >= 4 {
+        let typ = u16::from_be_bytes([attrs[0], attrs[1]]);
+        let attr_len = u16::from_be_bytes([attrs[2], attrs[3]]) as usize;
+        if attrs.len() < 4 + attr_len {
+            break;
+        }
+        let value = &attrs[4..4 + attr_len];
+        match typ {
+            0x0020 => {
+                if let Some(addr) = parse_addr(value, Some(tid)) {
+                    return Some(addr);
+                }
+            }
+            0x0001 => {
+                if mapped_addr.is_none() {
+                    mapped_addr = parse_addr(value, None);
+                }
+            }
+            _ => {}
+        }
+        //属性按4字节对齐
+        let next = (4 + attr_len + 3) & !3;
+        attrs = &attrs[next.min(attrs.len())..];
+    }
+    mapped_addr
+}
+
+fn parse_addr(value: &[u8], xor: Option<&[u8; 12]>) -> Option<SocketAddr> {
+    if value.len() < 8 {
+        return None;
+    }
+    let cookie = MAGIC_COOKIE.to_be_bytes();
+    let mut port = u16::from_be_bytes([value[2], value[3]]);
+    if xor.is_some() {
+        port ^= (MAGIC_COOKIE >> 16) as u16;
+    }
+    match value[1] {
+        0x01 => {
+            let mut ip: [u8; 4] = value[4..8].try_into().unwrap();
+            if xor.is_some() {
+                for (i, v) in ip.iter_mut().enumerate() {
+                    *v ^= cookie[i];
+                }
+            }
+            Some(SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::from(ip), port)))
+        }
+        0x02 if value.len() >= 20 => {
+            let mut ip: [u8; 16] = value[4..20].try_into().unwrap();
+            if let Some(tid) = xor {
+                for (i, v) in ip.iter_mut().enumerate() {
+                    *v ^= if i < 4 { cookie[i] } else { tid[i - 4] };
+                }
+            }
+            Some(SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::from(ip),
+                port,
+                0,
+                0,
+            )))
+        }
+        _ => None,
+    }
+}
+
 async fn test_nat(stun_server: String) -> io::Result<(NatType, Vec<Ipv4Addr>, u16)> {
     let udp = UdpSocket::bind("0.0.0.0:0").await?;
+    crate::util::fwmark::mark(&udp)?;
     udp.connect(stun_server).await?;
     let mut nat_type = NatType::Cone;
     let mut port_range = 0;
     let mut hash_set = HashSet::new();
     match test_nat_(&udp, true, true).await {
-        Ok((mapped_addr1, changed_addr1)) => {
+        Ok((_, None)) => {
+            //没有CHANGED-ADDRESS，不支持rfc3489的探测方式
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "stun server does not support rfc3489",
+            ));
+        }
+        Ok((mapped_addr1, Some(changed_addr1))) => {
             match mapped_addr1.ip() {
                 IpAddr::V4(ip) => {
                     hash_set.insert(ip);
@@ -63,7 +232,9 @@ async fn test_nat(stun_server: String) -> io::Result<(NatType, Vec<Ipv4Addr>, u1
                 }
             }
         }
-        Err(_) => {}
+        Err(e) => {
+            return Err(e);
+        }
     }
     Ok((nat_type, hash_set.into_iter().collect(), port_range))
 }
@@ -72,7 +243,7 @@ async fn test_nat_(
     udp: &UdpSocket,
     change_ip: bool,
     change_port: bool,
-) -> io::Result<(SocketAddr, SocketAddr)> {
+) -> io::Result<(SocketAddr, Option<SocketAddr>)> {
     for _ in 0..2 {
         let mut buf = [0u8; 28];
         let mut msg = stun_format::MsgBuilder::from(buf.as_mut_slice());
@@ -85,9 +256,9 @@ async fn test_nat_(
         .unwrap();
         udp.send(msg.as_bytes()).await?;
         let mut buf = [0; 10240];
-        let (len, addr) =
+        let len =
             match tokio::time::timeout(Duration::from_millis(300), udp.recv_from(&mut buf)).await {
-                Ok(rs) => rs?,
+                Ok(rs) => rs?.0,
                 Err(_) => {
                     continue;
                 }
@@ -115,11 +286,11 @@ async fn test_nat_(
                 _ => {}
             }
             if changed_addr.is_some() && mapped_addr.is_some() {
-                return Ok((mapped_addr.unwrap(), changed_addr.unwrap()));
+                return Ok((mapped_addr.unwrap(), changed_addr));
             }
         }
         if mapped_addr.is_some() {
-            return Ok((mapped_addr.unwrap(), changed_addr.unwrap_or(addr)));
+            return Ok((mapped_addr.unwrap(), changed_addr));
         }
     }
     Err(io::Error::new(io::ErrorKind::Other, "stun response err"))
@@ -33,6 +33,12 @@ pub struct HandshakeRequest {
     pub version: ::std::string::String,
     // @@protoc_insertion_point(field:HandshakeRequest.secret)
     pub secret: bool,
+    // @@protoc_insertion_point(field:HandshakeRequest.cookie)
+    pub cookie: ::std::vec::Vec<u8>,
+    // @@protoc_insertion_point(field:HandshakeRequest.pow_nonce)
+    pub pow_nonce: u64,
+    // @@protoc_insertion_point(field:HandshakeRequest.padding)
+    pub padding: ::std::vec::Vec<u8>,
     // special fields
     // @@protoc_insertion_point(special_field:HandshakeRequest.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -50,7 +56,7 @@ impl HandshakeRequest {
     }
 
     fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
-        let mut fields = ::std::vec::Vec::with_capacity(2);
+        let mut fields = ::std::vec::Vec::with_capacity(5);
         let mut oneofs = ::std::vec::Vec::with_capacity(0);
         fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
             "version",
@@ -62,6 +68,21 @@ impl HandshakeRequest {
             |m: &HandshakeRequest| { &m.secret },
             |m: &mut HandshakeRequest| { &mut m.secret },
         ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "cookie",
+            |m: &HandshakeRequest| { &m.cookie },
+            |m: &mut HandshakeRequest| { &mut m.cookie },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "pow_nonce",
+            |m: &HandshakeRequest| { &m.pow_nonce },
+            |m: &mut HandshakeRequest| { &mut m.pow_nonce },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "padding",
+            |m: &HandshakeRequest| { &m.padding },
+            |m: &mut HandshakeRequest| { &mut m.padding },
+        ));
         ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<HandshakeRequest>(
             "HandshakeRequest",
             fields,
@@ -86,6 +107,15 @@ impl ::protobuf::Message for HandshakeRequest {
                 16 => {
                     self.secret = is.read_bool()?;
                 },
+                26 => {
+                    self.cookie = is.read_bytes()?;
+                },
+                32 => {
+                    self.pow_nonce = is.read_uint64()?;
+                },
+                42 => {
+                    self.padding = is.read_bytes()?;
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -104,6 +134,15 @@ impl ::protobuf::Message for HandshakeRequest {
         if self.secret != false {
             my_size += 1 + 1;
         }
+        if !self.cookie.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(3, &self.cookie);
+        }
+        if self.pow_nonce != 0 {
+            my_size += ::protobuf::rt::uint64_size(4, self.pow_nonce);
+        }
+        if !self.padding.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(5, &self.padding);
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -116,6 +155,15 @@ impl ::protobuf::Message for HandshakeRequest {
         if self.secret != false {
             os.write_bool(2, self.secret)?;
         }
+        if !self.cookie.is_empty() {
+            os.write_bytes(3, &self.cookie)?;
+        }
+        if self.pow_nonce != 0 {
+            os.write_uint64(4, self.pow_nonce)?;
+        }
+        if !self.padding.is_empty() {
+            os.write_bytes(5, &self.padding)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -135,6 +183,9 @@ impl ::protobuf::Message for HandshakeRequest {
     fn clear(&mut self) {
         self.version.clear();
         self.secret = false;
+        self.cookie.clear();
+        self.pow_nonce = 0;
+        self.padding.clear();
         self.special_fields.clear();
     }
 
@@ -142,6 +193,9 @@ impl ::protobuf::Message for HandshakeRequest {
         static instance: HandshakeRequest = HandshakeRequest {
             version: ::std::string::String::new(),
             secret: false,
+            cookie: ::std::vec::Vec::new(),
+            pow_nonce: 0,
+            padding: ::std::vec::Vec::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -177,6 +231,16 @@ pub struct HandshakeResponse {
     pub public_key: ::std::vec::Vec<u8>,
     // @@protoc_insertion_point(field:HandshakeResponse.key_finger)
     pub key_finger: ::std::string::String,
+    // @@protoc_insertion_point(field:HandshakeResponse.cookie)
+    pub cookie: ::std::vec::Vec<u8>,
+    // @@protoc_insertion_point(field:HandshakeResponse.pow_difficulty)
+    pub pow_difficulty: u32,
+    // @@protoc_insertion_point(field:HandshakeResponse.servers)
+    pub servers: ::std::vec::Vec<ServerInfo>,
+    // @@protoc_insertion_point(field:HandshakeResponse.pinned)
+    pub pinned: bool,
+    // @@protoc_insertion_point(field:HandshakeResponse.listen_addresses)
+    pub listen_addresses: ::std::vec::Vec<::std::string::String>,
     // special fields
     // @@protoc_insertion_point(special_field:HandshakeResponse.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -194,7 +258,7 @@ impl HandshakeResponse {
     }
 
     fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
-        let mut fields = ::std::vec::Vec::with_capacity(4);
+        let mut fields = ::std::vec::Vec::with_capacity(9);
         let mut oneofs = ::std::vec::Vec::with_capacity(0);
         fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
             "version",
@@ -216,6 +280,31 @@ impl HandshakeResponse {
             |m: &HandshakeResponse| { &m.key_finger },
             |m: &mut HandshakeResponse| { &mut m.key_finger },
         ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "cookie",
+            |m: &HandshakeResponse| { &m.cookie },
+            |m: &mut HandshakeResponse| { &mut m.cookie },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "pow_difficulty",
+            |m: &HandshakeResponse| { &m.pow_difficulty },
+            |m: &mut HandshakeResponse| { &mut m.pow_difficulty },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "servers",
+            |m: &HandshakeResponse| { &m.servers },
+            |m: &mut HandshakeResponse| { &mut m.servers },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "pinned",
+            |m: &HandshakeResponse| { &m.pinned },
+            |m: &mut HandshakeResponse| { &mut m.pinned },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "listen_addresses",
+            |m: &HandshakeResponse| { &m.listen_addresses },
+            |m: &mut HandshakeResponse| { &mut m.listen_addresses },
+        ));
         ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<HandshakeResponse>(
             "HandshakeResponse",
             fields,
@@ -246,6 +335,21 @@ impl ::protobuf::Message for HandshakeResponse {
                 34 => {
                     self.key_finger = is.read_string()?;
                 },
+                42 => {
+                    self.cookie = is.read_bytes()?;
+                },
+                48 => {
+                    self.pow_difficulty = is.read_uint32()?;
+                },
+                58 => {
+                    self.servers.push(is.read_message()?);
+                },
+                64 => {
+                    self.pinned = is.read_bool()?;
+                },
+                74 => {
+                    self.listen_addresses.push(is.read_string()?);
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -270,6 +374,22 @@ impl ::protobuf::Message for HandshakeResponse {
         if !self.key_finger.is_empty() {
             my_size += ::protobuf::rt::string_size(4, &self.key_finger);
         }
+        if !self.cookie.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(5, &self.cookie);
+        }
+        if self.pow_difficulty != 0 {
+            my_size += ::protobuf::rt::uint32_size(6, self.pow_difficulty);
+        }
+        for value in &self.servers {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+        };
+        if self.pinned != false {
+            my_size += 1 + 1;
+        }
+        for value in &self.listen_addresses {
+            my_size += ::protobuf::rt::string_size(9, &value);
+        };
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -288,6 +408,21 @@ impl ::protobuf::Message for HandshakeResponse {
         if !self.key_finger.is_empty() {
             os.write_string(4, &self.key_finger)?;
         }
+        if !self.cookie.is_empty() {
+            os.write_bytes(5, &self.cookie)?;
+        }
+        if self.pow_difficulty != 0 {
+            os.write_uint32(6, self.pow_difficulty)?;
+        }
+        for v in &self.servers {
+            ::protobuf::rt::write_message_field_with_cached_size(7, v, os)?;
+        };
+        if self.pinned != false {
+            os.write_bool(8, self.pinned)?;
+        }
+        for v in &self.listen_addresses {
+            os.write_string(9, &v)?;
+        };
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -309,6 +444,11 @@ impl ::protobuf::Message for HandshakeResponse {
         self.secret = false;
         self.public_key.clear();
         self.key_finger.clear();
+        self.cookie.clear();
+        self.pow_difficulty = 0;
+        self.servers.clear();
+        self.pinned = false;
+        self.listen_addresses.clear();
         self.special_fields.clear();
     }
 
@@ -318,6 +458,11 @@ impl ::protobuf::Message for HandshakeResponse {
             secret: false,
             public_key: ::std::vec::Vec::new(),
             key_finger: ::std::string::String::new(),
+            cookie: ::std::vec::Vec::new(),
+            pow_difficulty: 0,
+            servers: ::std::vec::Vec::new(),
+            pinned: false,
+            listen_addresses: ::std::vec::Vec::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -501,6 +646,8 @@ pub struct RegistrationRequest {
     pub allow_ip_change: bool,
     // @@protoc_insertion_point(field:RegistrationRequest.client_secret)
     pub client_secret: bool,
+    // @@protoc_insertion_point(field:RegistrationRequest.routes)
+    pub routes: ::std::vec::Vec<::std::string::String>,
     // special fields
     // @@protoc_insertion_point(special_field:RegistrationRequest.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -518,7 +665,7 @@ impl RegistrationRequest {
     }
 
     fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
-        let mut fields = ::std::vec::Vec::with_capacity(8);
+        let mut fields = ::std::vec::Vec::with_capacity(9);
         let mut oneofs = ::std::vec::Vec::with_capacity(0);
         fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
             "token",
@@ -560,6 +707,11 @@ impl RegistrationRequest {
             |m: &RegistrationRequest| { &m.client_secret },
             |m: &mut RegistrationRequest| { &mut m.client_secret },
         ));
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "routes",
+            |m: &RegistrationRequest| { &m.routes },
+            |m: &mut RegistrationRequest| { &mut m.routes },
+        ));
         ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<RegistrationRequest>(
             "RegistrationRequest",
             fields,
@@ -602,6 +754,9 @@ impl ::protobuf::Message for RegistrationRequest {
                 64 => {
                     self.client_secret = is.read_bool()?;
                 },
+                74 => {
+                    self.routes.push(is.read_string()?);
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -638,6 +793,9 @@ impl ::protobuf::Message for RegistrationRequest {
         if self.client_secret != false {
             my_size += 1 + 1;
         }
+        for value in &self.routes {
+            my_size += ::protobuf::rt::string_size(9, &value);
+        };
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -668,6 +826,9 @@ impl ::protobuf::Message for RegistrationRequest {
         if self.client_secret != false {
             os.write_bool(8, self.client_secret)?;
         }
+        for v in &self.routes {
+            os.write_string(9, &v)?;
+        };
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -693,6 +854,7 @@ impl ::protobuf::Message for RegistrationRequest {
         self.virtual_ip = 0;
         self.allow_ip_change = false;
         self.client_secret = false;
+        self.routes.clear();
         self.special_fields.clear();
     }
 
@@ -706,6 +868,7 @@ impl ::protobuf::Message for RegistrationRequest {
             virtual_ip: 0,
             allow_ip_change: false,
             client_secret: false,
+            routes: ::std::vec::Vec::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -749,6 +912,12 @@ pub struct RegistrationResponse {
     pub public_port: u32,
     // @@protoc_insertion_point(field:RegistrationResponse.public_ipv6)
     pub public_ipv6: ::std::vec::Vec<u8>,
+    // @@protoc_insertion_point(field:RegistrationResponse.client_config)
+    pub client_config: ::protobuf::MessageField<ClientConfig>,
+    // @@protoc_insertion_point(field:RegistrationResponse.resume_ticket)
+    pub resume_ticket: ::std::vec::Vec<u8>,
+    // @@protoc_insertion_point(field:RegistrationResponse.resume_ticket_ttl)
+    pub resume_ticket_ttl: u32,
     // special fields
     // @@protoc_insertion_point(special_field:RegistrationResponse.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -766,7 +935,7 @@ impl RegistrationResponse {
     }
 
     fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
-        let mut fields = ::std::vec::Vec::with_capacity(8);
+        let mut fields = ::std::vec::Vec::with_capacity(11);
         let mut oneofs = ::std::vec::Vec::with_capacity(0);
         fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
             "virtual_ip",
@@ -808,6 +977,21 @@ impl RegistrationResponse {
             |m: &RegistrationResponse| { &m.public_ipv6 },
             |m: &mut RegistrationResponse| { &mut m.public_ipv6 },
         ));
+        fields.push(::protobuf::reflect::rt::v2::make_message_field_accessor::<_, ClientConfig>(
+            "client_config",
+            |m: &RegistrationResponse| { &m.client_config },
+            |m: &mut RegistrationResponse| { &mut m.client_config },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "resume_ticket",
+            |m: &RegistrationResponse| { &m.resume_ticket },
+            |m: &mut RegistrationResponse| { &mut m.resume_ticket },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "resume_ticket_ttl",
+            |m: &RegistrationResponse| { &m.resume_ticket_ttl },
+            |m: &mut RegistrationResponse| { &mut m.resume_ticket_ttl },
+        ));
         ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<RegistrationResponse>(
             "RegistrationResponse",
             fields,
@@ -850,6 +1034,15 @@ impl ::protobuf::Message for RegistrationResponse {
                 66 => {
                     self.public_ipv6 = is.read_bytes()?;
                 },
+                74 => {
+                    ::protobuf::rt::read_singular_message_into_field(is, &mut self.client_config)?;
+                },
+                82 => {
+                    self.resume_ticket = is.read_bytes()?;
+                },
+                88 => {
+                    self.resume_ticket_ttl = is.read_uint32()?;
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -887,6 +1080,16 @@ impl ::protobuf::Message for RegistrationResponse {
         if !self.public_ipv6.is_empty() {
             my_size += ::protobuf::rt::bytes_size(8, &self.public_ipv6);
         }
+        if let Some(v) = self.client_config.as_ref() {
+            let len = v.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+        }
+        if !self.resume_ticket.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(10, &self.resume_ticket);
+        }
+        if self.resume_ticket_ttl != 0 {
+            my_size += ::protobuf::rt::uint32_size(11, self.resume_ticket_ttl);
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -917,6 +1120,15 @@ impl ::protobuf::Message for RegistrationResponse {
         if !self.public_ipv6.is_empty() {
             os.write_bytes(8, &self.public_ipv6)?;
         }
+        if let Some(v) = self.client_config.as_ref() {
+            ::protobuf::rt::write_message_field_with_cached_size(9, v, os)?;
+        }
+        if !self.resume_ticket.is_empty() {
+            os.write_bytes(10, &self.resume_ticket)?;
+        }
+        if self.resume_ticket_ttl != 0 {
+            os.write_uint32(11, self.resume_ticket_ttl)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -942,6 +1154,9 @@ impl ::protobuf::Message for RegistrationResponse {
         self.public_ip = 0;
         self.public_port = 0;
         self.public_ipv6.clear();
+        self.client_config.clear();
+        self.resume_ticket.clear();
+        self.resume_ticket_ttl = 0;
         self.special_fields.clear();
     }
 
@@ -955,6 +1170,9 @@ impl ::protobuf::Message for RegistrationResponse {
             public_ip: 0,
             public_port: 0,
             public_ipv6: ::std::vec::Vec::new(),
+            client_config: ::protobuf::MessageField::none(),
+            resume_ticket: ::std::vec::Vec::new(),
+            resume_ticket_ttl: 0,
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -990,6 +1208,10 @@ pub struct DeviceInfo {
     pub device_status: u32,
     // @@protoc_insertion_point(field:DeviceInfo.client_secret)
     pub client_secret: bool,
+    // @@protoc_insertion_point(field:DeviceInfo.tags)
+    pub tags: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:DeviceInfo.routes)
+    pub routes: ::std::vec::Vec<::std::string::String>,
     // special fields
     // @@protoc_insertion_point(special_field:DeviceInfo.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -1007,7 +1229,7 @@ impl DeviceInfo {
     }
 
     fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
-        let mut fields = ::std::vec::Vec::with_capacity(4);
+        let mut fields = ::std::vec::Vec::with_capacity(6);
         let mut oneofs = ::std::vec::Vec::with_capacity(0);
         fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
             "name",
@@ -1029,6 +1251,16 @@ impl DeviceInfo {
             |m: &DeviceInfo| { &m.client_secret },
             |m: &mut DeviceInfo| { &mut m.client_secret },
         ));
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "tags",
+            |m: &DeviceInfo| { &m.tags },
+            |m: &mut DeviceInfo| { &mut m.tags },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "routes",
+            |m: &DeviceInfo| { &m.routes },
+            |m: &mut DeviceInfo| { &mut m.routes },
+        ));
         ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<DeviceInfo>(
             "DeviceInfo",
             fields,
@@ -1059,6 +1291,12 @@ impl ::protobuf::Message for DeviceInfo {
                 32 => {
                     self.client_secret = is.read_bool()?;
                 },
+                42 => {
+                    self.tags.push(is.read_string()?);
+                },
+                50 => {
+                    self.routes.push(is.read_string()?);
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -1083,6 +1321,12 @@ impl ::protobuf::Message for DeviceInfo {
         if self.client_secret != false {
             my_size += 1 + 1;
         }
+        for value in &self.tags {
+            my_size += ::protobuf::rt::string_size(5, &value);
+        };
+        for value in &self.routes {
+            my_size += ::protobuf::rt::string_size(6, &value);
+        };
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -1101,6 +1345,12 @@ impl ::protobuf::Message for DeviceInfo {
         if self.client_secret != false {
             os.write_bool(4, self.client_secret)?;
         }
+        for v in &self.tags {
+            os.write_string(5, &v)?;
+        };
+        for v in &self.routes {
+            os.write_string(6, &v)?;
+        };
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -1122,6 +1372,8 @@ impl ::protobuf::Message for DeviceInfo {
         self.virtual_ip = 0;
         self.device_status = 0;
         self.client_secret = false;
+        self.tags.clear();
+        self.routes.clear();
         self.special_fields.clear();
     }
 
@@ -1131,6 +1383,8 @@ impl ::protobuf::Message for DeviceInfo {
             virtual_ip: 0,
             device_status: 0,
             client_secret: false,
+            tags: ::std::vec::Vec::new(),
+            routes: ::std::vec::Vec::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -1319,6 +1573,16 @@ pub struct PunchInfo {
     pub ipv6_port: u32,
     // @@protoc_insertion_point(field:PunchInfo.tcp_port)
     pub tcp_port: u32,
+    // @@protoc_insertion_point(field:PunchInfo.turn_ip)
+    pub turn_ip: u32,
+    // @@protoc_insertion_point(field:PunchInfo.turn_port)
+    pub turn_port: u32,
+    // @@protoc_insertion_point(field:PunchInfo.candidates)
+    pub candidates: ::std::vec::Vec<IceCandidate>,
+    // @@protoc_insertion_point(field:PunchInfo.capabilities)
+    pub capabilities: ::protobuf::MessageField<Capabilities>,
+    // @@protoc_insertion_point(field:PunchInfo.time)
+    pub time: u64,
     // special fields
     // @@protoc_insertion_point(special_field:PunchInfo.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -1336,7 +1600,7 @@ impl PunchInfo {
     }
 
     fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
-        let mut fields = ::std::vec::Vec::with_capacity(10);
+        let mut fields = ::std::vec::Vec::with_capacity(15);
         let mut oneofs = ::std::vec::Vec::with_capacity(0);
         fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
             "public_ip_list",
@@ -1388,6 +1652,31 @@ impl PunchInfo {
             |m: &PunchInfo| { &m.tcp_port },
             |m: &mut PunchInfo| { &mut m.tcp_port },
         ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "turn_ip",
+            |m: &PunchInfo| { &m.turn_ip },
+            |m: &mut PunchInfo| { &mut m.turn_ip },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "turn_port",
+            |m: &PunchInfo| { &m.turn_port },
+            |m: &mut PunchInfo| { &mut m.turn_port },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "candidates",
+            |m: &PunchInfo| { &m.candidates },
+            |m: &mut PunchInfo| { &mut m.candidates },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_message_field_accessor::<_, Capabilities>(
+            "capabilities",
+            |m: &PunchInfo| { &m.capabilities },
+            |m: &mut PunchInfo| { &mut m.capabilities },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "time",
+            |m: &PunchInfo| { &m.time },
+            |m: &mut PunchInfo| { &mut m.time },
+        ));
         ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<PunchInfo>(
             "PunchInfo",
             fields,
@@ -1439,6 +1728,21 @@ impl ::protobuf::Message for PunchInfo {
                 88 => {
                     self.tcp_port = is.read_uint32()?;
                 },
+                101 => {
+                    self.turn_ip = is.read_fixed32()?;
+                },
+                104 => {
+                    self.turn_port = is.read_uint32()?;
+                },
+                114 => {
+                    self.candidates.push(is.read_message()?);
+                },
+                122 => {
+                    ::protobuf::rt::read_singular_message_into_field(is, &mut self.capabilities)?;
+                },
+                128 => {
+                    self.time = is.read_uint64()?;
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -1479,6 +1783,23 @@ impl ::protobuf::Message for PunchInfo {
         if self.tcp_port != 0 {
             my_size += ::protobuf::rt::uint32_size(11, self.tcp_port);
         }
+        if self.turn_ip != 0 {
+            my_size += 1 + 4;
+        }
+        if self.turn_port != 0 {
+            my_size += ::protobuf::rt::uint32_size(13, self.turn_port);
+        }
+        for value in &self.candidates {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+        };
+        if let Some(v) = self.capabilities.as_ref() {
+            let len = v.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+        }
+        if self.time != 0 {
+            my_size += ::protobuf::rt::uint64_size(16, self.time);
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -1515,6 +1836,21 @@ impl ::protobuf::Message for PunchInfo {
         if self.tcp_port != 0 {
             os.write_uint32(11, self.tcp_port)?;
         }
+        if self.turn_ip != 0 {
+            os.write_fixed32(12, self.turn_ip)?;
+        }
+        if self.turn_port != 0 {
+            os.write_uint32(13, self.turn_port)?;
+        }
+        for v in &self.candidates {
+            ::protobuf::rt::write_message_field_with_cached_size(14, v, os)?;
+        };
+        if let Some(v) = self.capabilities.as_ref() {
+            ::protobuf::rt::write_message_field_with_cached_size(15, v, os)?;
+        }
+        if self.time != 0 {
+            os.write_uint64(16, self.time)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -1542,6 +1878,11 @@ impl ::protobuf::Message for PunchInfo {
         self.ipv6.clear();
         self.ipv6_port = 0;
         self.tcp_port = 0;
+        self.turn_ip = 0;
+        self.turn_port = 0;
+        self.candidates.clear();
+        self.capabilities.clear();
+        self.time = 0;
         self.special_fields.clear();
     }
 
@@ -1557,6 +1898,11 @@ impl ::protobuf::Message for PunchInfo {
             ipv6: ::std::vec::Vec::new(),
             ipv6_port: 0,
             tcp_port: 0,
+            turn_ip: 0,
+            turn_port: 0,
+            candidates: ::std::vec::Vec::new(),
+            capabilities: ::protobuf::MessageField::none(),
+            time: 0,
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -1580,6 +1926,1432 @@ impl ::protobuf::reflect::ProtobufValue for PunchInfo {
     type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
 }
 
+#[derive(PartialEq,Clone,Default,Debug)]
+// @@protoc_insertion_point(message:ClientConfig)
+pub struct ClientConfig {
+    // message fields
+    // @@protoc_insertion_point(field:ClientConfig.in_ips)
+    pub in_ips: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:ClientConfig.dns_server)
+    pub dns_server: u32,
+    // @@protoc_insertion_point(field:ClientConfig.dns_domains)
+    pub dns_domains: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:ClientConfig.keepalive)
+    pub keepalive: u32,
+    // @@protoc_insertion_point(field:ClientConfig.version)
+    pub version: u32,
+    // special fields
+    // @@protoc_insertion_point(special_field:ClientConfig.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a ClientConfig {
+    fn default() -> &'a ClientConfig {
+        <ClientConfig as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl ClientConfig {
+    pub fn new() -> ClientConfig {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(5);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "in_ips",
+            |m: &ClientConfig| { &m.in_ips },
+            |m: &mut ClientConfig| { &mut m.in_ips },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "dns_server",
+            |m: &ClientConfig| { &m.dns_server },
+            |m: &mut ClientConfig| { &mut m.dns_server },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "dns_domains",
+            |m: &ClientConfig| { &m.dns_domains },
+            |m: &mut ClientConfig| { &mut m.dns_domains },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "keepalive",
+            |m: &ClientConfig| { &m.keepalive },
+            |m: &mut ClientConfig| { &mut m.keepalive },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "version",
+            |m: &ClientConfig| { &m.version },
+            |m: &mut ClientConfig| { &mut m.version },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<ClientConfig>(
+            "ClientConfig",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for ClientConfig {
+    const NAME: &'static str = "ClientConfig";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.in_ips.push(is.read_string()?);
+                },
+                21 => {
+                    self.dns_server = is.read_fixed32()?;
+                },
+                26 => {
+                    self.dns_domains.push(is.read_string()?);
+                },
+                32 => {
+                    self.keepalive = is.read_uint32()?;
+                },
+                40 => {
+                    self.version = is.read_uint32()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        for value in &self.in_ips {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
+        if self.dns_server != 0 {
+            my_size += 1 + 4;
+        }
+        for value in &self.dns_domains {
+            my_size += ::protobuf::rt::string_size(3, &value);
+        };
+        if self.keepalive != 0 {
+            my_size += ::protobuf::rt::uint32_size(4, self.keepalive);
+        }
+        if self.version != 0 {
+            my_size += ::protobuf::rt::uint32_size(5, self.version);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        for v in &self.in_ips {
+            os.write_string(1, &v)?;
+        };
+        if self.dns_server != 0 {
+            os.write_fixed32(2, self.dns_server)?;
+        }
+        for v in &self.dns_domains {
+            os.write_string(3, &v)?;
+        };
+        if self.keepalive != 0 {
+            os.write_uint32(4, self.keepalive)?;
+        }
+        if self.version != 0 {
+            os.write_uint32(5, self.version)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> ClientConfig {
+        ClientConfig::new()
+    }
+
+    fn clear(&mut self) {
+        self.in_ips.clear();
+        self.dns_server = 0;
+        self.dns_domains.clear();
+        self.keepalive = 0;
+        self.version = 0;
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static ClientConfig {
+        static instance: ClientConfig = ClientConfig {
+            in_ips: ::std::vec::Vec::new(),
+            dns_server: 0,
+            dns_domains: ::std::vec::Vec::new(),
+            keepalive: 0,
+            version: 0,
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for ClientConfig {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("ClientConfig").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for ClientConfig {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for ClientConfig {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+#[derive(PartialEq,Clone,Default,Debug)]
+// @@protoc_insertion_point(message:IceCandidate)
+pub struct IceCandidate {
+    // message fields
+    // @@protoc_insertion_point(field:IceCandidate.kind)
+    pub kind: u32,
+    // @@protoc_insertion_point(field:IceCandidate.ip)
+    pub ip: ::std::vec::Vec<u8>,
+    // @@protoc_insertion_point(field:IceCandidate.port)
+    pub port: u32,
+    // @@protoc_insertion_point(field:IceCandidate.priority)
+    pub priority: u32,
+    // special fields
+    // @@protoc_insertion_point(special_field:IceCandidate.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a IceCandidate {
+    fn default() -> &'a IceCandidate {
+        <IceCandidate as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl IceCandidate {
+    pub fn new() -> IceCandidate {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(4);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "kind",
+            |m: &IceCandidate| { &m.kind },
+            |m: &mut IceCandidate| { &mut m.kind },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "ip",
+            |m: &IceCandidate| { &m.ip },
+            |m: &mut IceCandidate| { &mut m.ip },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "port",
+            |m: &IceCandidate| { &m.port },
+            |m: &mut IceCandidate| { &mut m.port },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "priority",
+            |m: &IceCandidate| { &m.priority },
+            |m: &mut IceCandidate| { &mut m.priority },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<IceCandidate>(
+            "IceCandidate",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for IceCandidate {
+    const NAME: &'static str = "IceCandidate";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                8 => {
+                    self.kind = is.read_uint32()?;
+                },
+                18 => {
+                    self.ip = is.read_bytes()?;
+                },
+                24 => {
+                    self.port = is.read_uint32()?;
+                },
+                32 => {
+                    self.priority = is.read_uint32()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if self.kind != 0 {
+            my_size += ::protobuf::rt::uint32_size(1, self.kind);
+        }
+        if !self.ip.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(2, &self.ip);
+        }
+        if self.port != 0 {
+            my_size += ::protobuf::rt::uint32_size(3, self.port);
+        }
+        if self.priority != 0 {
+            my_size += ::protobuf::rt::uint32_size(4, self.priority);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if self.kind != 0 {
+            os.write_uint32(1, self.kind)?;
+        }
+        if !self.ip.is_empty() {
+            os.write_bytes(2, &self.ip)?;
+        }
+        if self.port != 0 {
+            os.write_uint32(3, self.port)?;
+        }
+        if self.priority != 0 {
+            os.write_uint32(4, self.priority)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> IceCandidate {
+        IceCandidate::new()
+    }
+
+    fn clear(&mut self) {
+        self.kind = 0;
+        self.ip.clear();
+        self.port = 0;
+        self.priority = 0;
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static IceCandidate {
+        static instance: IceCandidate = IceCandidate {
+            kind: 0,
+            ip: ::std::vec::Vec::new(),
+            port: 0,
+            priority: 0,
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for IceCandidate {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("IceCandidate").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for IceCandidate {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for IceCandidate {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+#[derive(PartialEq,Clone,Default,Debug)]
+// @@protoc_insertion_point(message:QuotaStatus)
+pub struct QuotaStatus {
+    // message fields
+    // @@protoc_insertion_point(field:QuotaStatus.used)
+    pub used: u64,
+    // @@protoc_insertion_point(field:QuotaStatus.limit)
+    pub limit: u64,
+    // @@protoc_insertion_point(field:QuotaStatus.state)
+    pub state: u32,
+    // @@protoc_insertion_point(field:QuotaStatus.rate_limit)
+    pub rate_limit: u32,
+    // @@protoc_insertion_point(field:QuotaStatus.reset_time)
+    pub reset_time: u32,
+    // @@protoc_insertion_point(field:QuotaStatus.scope)
+    pub scope: u32,
+    // special fields
+    // @@protoc_insertion_point(special_field:QuotaStatus.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a QuotaStatus {
+    fn default() -> &'a QuotaStatus {
+        <QuotaStatus as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl QuotaStatus {
+    pub fn new() -> QuotaStatus {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(6);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "used",
+            |m: &QuotaStatus| { &m.used },
+            |m: &mut QuotaStatus| { &mut m.used },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "limit",
+            |m: &QuotaStatus| { &m.limit },
+            |m: &mut QuotaStatus| { &mut m.limit },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "state",
+            |m: &QuotaStatus| { &m.state },
+            |m: &mut QuotaStatus| { &mut m.state },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "rate_limit",
+            |m: &QuotaStatus| { &m.rate_limit },
+            |m: &mut QuotaStatus| { &mut m.rate_limit },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "reset_time",
+            |m: &QuotaStatus| { &m.reset_time },
+            |m: &mut QuotaStatus| { &mut m.reset_time },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "scope",
+            |m: &QuotaStatus| { &m.scope },
+            |m: &mut QuotaStatus| { &mut m.scope },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<QuotaStatus>(
+            "QuotaStatus",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for QuotaStatus {
+    const NAME: &'static str = "QuotaStatus";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                8 => {
+                    self.used = is.read_uint64()?;
+                },
+                16 => {
+                    self.limit = is.read_uint64()?;
+                },
+                24 => {
+                    self.state = is.read_uint32()?;
+                },
+                32 => {
+                    self.rate_limit = is.read_uint32()?;
+                },
+                40 => {
+                    self.reset_time = is.read_uint32()?;
+                },
+                48 => {
+                    self.scope = is.read_uint32()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if self.used != 0 {
+            my_size += ::protobuf::rt::uint64_size(1, self.used);
+        }
+        if self.limit != 0 {
+            my_size += ::protobuf::rt::uint64_size(2, self.limit);
+        }
+        if self.state != 0 {
+            my_size += ::protobuf::rt::uint32_size(3, self.state);
+        }
+        if self.rate_limit != 0 {
+            my_size += ::protobuf::rt::uint32_size(4, self.rate_limit);
+        }
+        if self.reset_time != 0 {
+            my_size += ::protobuf::rt::uint32_size(5, self.reset_time);
+        }
+        if self.scope != 0 {
+            my_size += ::protobuf::rt::uint32_size(6, self.scope);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if self.used != 0 {
+            os.write_uint64(1, self.used)?;
+        }
+        if self.limit != 0 {
+            os.write_uint64(2, self.limit)?;
+        }
+        if self.state != 0 {
+            os.write_uint32(3, self.state)?;
+        }
+        if self.rate_limit != 0 {
+            os.write_uint32(4, self.rate_limit)?;
+        }
+        if self.reset_time != 0 {
+            os.write_uint32(5, self.reset_time)?;
+        }
+        if self.scope != 0 {
+            os.write_uint32(6, self.scope)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> QuotaStatus {
+        QuotaStatus::new()
+    }
+
+    fn clear(&mut self) {
+        self.used = 0;
+        self.limit = 0;
+        self.state = 0;
+        self.rate_limit = 0;
+        self.reset_time = 0;
+        self.scope = 0;
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static QuotaStatus {
+        static instance: QuotaStatus = QuotaStatus {
+            used: 0,
+            limit: 0,
+            state: 0,
+            rate_limit: 0,
+            reset_time: 0,
+            scope: 0,
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for QuotaStatus {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("QuotaStatus").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for QuotaStatus {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for QuotaStatus {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+#[derive(PartialEq,Clone,Default,Debug)]
+// @@protoc_insertion_point(message:ServerInfo)
+pub struct ServerInfo {
+    // message fields
+    // @@protoc_insertion_point(field:ServerInfo.address)
+    pub address: ::std::string::String,
+    // @@protoc_insertion_point(field:ServerInfo.region)
+    pub region: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:ServerInfo.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a ServerInfo {
+    fn default() -> &'a ServerInfo {
+        <ServerInfo as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl ServerInfo {
+    pub fn new() -> ServerInfo {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(2);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "address",
+            |m: &ServerInfo| { &m.address },
+            |m: &mut ServerInfo| { &mut m.address },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "region",
+            |m: &ServerInfo| { &m.region },
+            |m: &mut ServerInfo| { &mut m.region },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<ServerInfo>(
+            "ServerInfo",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for ServerInfo {
+    const NAME: &'static str = "ServerInfo";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.address = is.read_string()?;
+                },
+                18 => {
+                    self.region = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.address.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.address);
+        }
+        if !self.region.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.region);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.address.is_empty() {
+            os.write_string(1, &self.address)?;
+        }
+        if !self.region.is_empty() {
+            os.write_string(2, &self.region)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> ServerInfo {
+        ServerInfo::new()
+    }
+
+    fn clear(&mut self) {
+        self.address.clear();
+        self.region.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static ServerInfo {
+        static instance: ServerInfo = ServerInfo {
+            address: ::std::string::String::new(),
+            region: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for ServerInfo {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("ServerInfo").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for ServerInfo {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for ServerInfo {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+#[derive(PartialEq,Clone,Default,Debug)]
+// @@protoc_insertion_point(message:Capabilities)
+pub struct Capabilities {
+    // message fields
+    // @@protoc_insertion_point(field:Capabilities.ciphers)
+    pub ciphers: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:Capabilities.compression)
+    pub compression: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:Capabilities.max_payload)
+    pub max_payload: u32,
+    // @@protoc_insertion_point(field:Capabilities.extensions)
+    pub extensions: ::std::vec::Vec<::std::string::String>,
+    // special fields
+    // @@protoc_insertion_point(special_field:Capabilities.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a Capabilities {
+    fn default() -> &'a Capabilities {
+        <Capabilities as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl Capabilities {
+    pub fn new() -> Capabilities {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(4);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "ciphers",
+            |m: &Capabilities| { &m.ciphers },
+            |m: &mut Capabilities| { &mut m.ciphers },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "compression",
+            |m: &Capabilities| { &m.compression },
+            |m: &mut Capabilities| { &mut m.compression },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "max_payload",
+            |m: &Capabilities| { &m.max_payload },
+            |m: &mut Capabilities| { &mut m.max_payload },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "extensions",
+            |m: &Capabilities| { &m.extensions },
+            |m: &mut Capabilities| { &mut m.extensions },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<Capabilities>(
+            "Capabilities",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for Capabilities {
+    const NAME: &'static str = "Capabilities";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.ciphers.push(is.read_string()?);
+                },
+                18 => {
+                    self.compression.push(is.read_string()?);
+                },
+                24 => {
+                    self.max_payload = is.read_uint32()?;
+                },
+                34 => {
+                    self.extensions.push(is.read_string()?);
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        for value in &self.ciphers {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
+        for value in &self.compression {
+            my_size += ::protobuf::rt::string_size(2, &value);
+        };
+        if self.max_payload != 0 {
+            my_size += ::protobuf::rt::uint32_size(3, self.max_payload);
+        }
+        for value in &self.extensions {
+            my_size += ::protobuf::rt::string_size(4, &value);
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        for v in &self.ciphers {
+            os.write_string(1, &v)?;
+        };
+        for v in &self.compression {
+            os.write_string(2, &v)?;
+        };
+        if self.max_payload != 0 {
+            os.write_uint32(3, self.max_payload)?;
+        }
+        for v in &self.extensions {
+            os.write_string(4, &v)?;
+        };
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> Capabilities {
+        Capabilities::new()
+    }
+
+    fn clear(&mut self) {
+        self.ciphers.clear();
+        self.compression.clear();
+        self.max_payload = 0;
+        self.extensions.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static Capabilities {
+        static instance: Capabilities = Capabilities {
+            ciphers: ::std::vec::Vec::new(),
+            compression: ::std::vec::Vec::new(),
+            max_payload: 0,
+            extensions: ::std::vec::Vec::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for Capabilities {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("Capabilities").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for Capabilities {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:ResumeRequest)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct ResumeRequest {
+    // message fields
+    // @@protoc_insertion_point(field:ResumeRequest.ticket)
+    pub ticket: ::std::vec::Vec<u8>,
+    // @@protoc_insertion_point(field:ResumeRequest.time)
+    pub time: i64,
+    // @@protoc_insertion_point(field:ResumeRequest.proof)
+    pub proof: ::std::vec::Vec<u8>,
+    // special fields
+    // @@protoc_insertion_point(special_field:ResumeRequest.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a ResumeRequest {
+    fn default() -> &'a ResumeRequest {
+        <ResumeRequest as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl ResumeRequest {
+    pub fn new() -> ResumeRequest {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(3);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "ticket",
+            |m: &ResumeRequest| { &m.ticket },
+            |m: &mut ResumeRequest| { &mut m.ticket },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "time",
+            |m: &ResumeRequest| { &m.time },
+            |m: &mut ResumeRequest| { &mut m.time },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "proof",
+            |m: &ResumeRequest| { &m.proof },
+            |m: &mut ResumeRequest| { &mut m.proof },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<ResumeRequest>(
+            "ResumeRequest",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for ResumeRequest {
+    const NAME: &'static str = "ResumeRequest";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.ticket = is.read_bytes()?;
+                },
+                16 => {
+                    self.time = is.read_int64()?;
+                },
+                26 => {
+                    self.proof = is.read_bytes()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.ticket.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(1, &self.ticket);
+        }
+        if self.time != 0 {
+            my_size += ::protobuf::rt::int64_size(2, self.time);
+        }
+        if !self.proof.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(3, &self.proof);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.ticket.is_empty() {
+            os.write_bytes(1, &self.ticket)?;
+        }
+        if self.time != 0 {
+            os.write_int64(2, self.time)?;
+        }
+        if !self.proof.is_empty() {
+            os.write_bytes(3, &self.proof)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> ResumeRequest {
+        ResumeRequest::new()
+    }
+
+    fn clear(&mut self) {
+        self.ticket.clear();
+        self.time = 0;
+        self.proof.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static ResumeRequest {
+        static instance: ResumeRequest = ResumeRequest {
+            ticket: ::std::vec::Vec::new(),
+            time: 0,
+            proof: ::std::vec::Vec::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for ResumeRequest {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("ResumeRequest").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for ResumeRequest {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for ResumeRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:ResumeResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct ResumeResponse {
+    // message fields
+    // @@protoc_insertion_point(field:ResumeResponse.epoch)
+    pub epoch: u32,
+    // @@protoc_insertion_point(field:ResumeResponse.resume_ticket)
+    pub resume_ticket: ::std::vec::Vec<u8>,
+    // @@protoc_insertion_point(field:ResumeResponse.resume_ticket_ttl)
+    pub resume_ticket_ttl: u32,
+    // @@protoc_insertion_point(field:ResumeResponse.public_ip)
+    pub public_ip: u32,
+    // @@protoc_insertion_point(field:ResumeResponse.public_port)
+    pub public_port: u32,
+    // special fields
+    // @@protoc_insertion_point(special_field:ResumeResponse.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a ResumeResponse {
+    fn default() -> &'a ResumeResponse {
+        <ResumeResponse as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl ResumeResponse {
+    pub fn new() -> ResumeResponse {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(5);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "epoch",
+            |m: &ResumeResponse| { &m.epoch },
+            |m: &mut ResumeResponse| { &mut m.epoch },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "resume_ticket",
+            |m: &ResumeResponse| { &m.resume_ticket },
+            |m: &mut ResumeResponse| { &mut m.resume_ticket },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "resume_ticket_ttl",
+            |m: &ResumeResponse| { &m.resume_ticket_ttl },
+            |m: &mut ResumeResponse| { &mut m.resume_ticket_ttl },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "public_ip",
+            |m: &ResumeResponse| { &m.public_ip },
+            |m: &mut ResumeResponse| { &mut m.public_ip },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "public_port",
+            |m: &ResumeResponse| { &m.public_port },
+            |m: &mut ResumeResponse| { &mut m.public_port },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<ResumeResponse>(
+            "ResumeResponse",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for ResumeResponse {
+    const NAME: &'static str = "ResumeResponse";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                8 => {
+                    self.epoch = is.read_uint32()?;
+                },
+                18 => {
+                    self.resume_ticket = is.read_bytes()?;
+                },
+                24 => {
+                    self.resume_ticket_ttl = is.read_uint32()?;
+                },
+                37 => {
+                    self.public_ip = is.read_fixed32()?;
+                },
+                40 => {
+                    self.public_port = is.read_uint32()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if self.epoch != 0 {
+            my_size += ::protobuf::rt::uint32_size(1, self.epoch);
+        }
+        if !self.resume_ticket.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(2, &self.resume_ticket);
+        }
+        if self.resume_ticket_ttl != 0 {
+            my_size += ::protobuf::rt::uint32_size(3, self.resume_ticket_ttl);
+        }
+        if self.public_ip != 0 {
+            my_size += 1 + 4;
+        }
+        if self.public_port != 0 {
+            my_size += ::protobuf::rt::uint32_size(5, self.public_port);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if self.epoch != 0 {
+            os.write_uint32(1, self.epoch)?;
+        }
+        if !self.resume_ticket.is_empty() {
+            os.write_bytes(2, &self.resume_ticket)?;
+        }
+        if self.resume_ticket_ttl != 0 {
+            os.write_uint32(3, self.resume_ticket_ttl)?;
+        }
+        if self.public_ip != 0 {
+            os.write_fixed32(4, self.public_ip)?;
+        }
+        if self.public_port != 0 {
+            os.write_uint32(5, self.public_port)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> ResumeResponse {
+        ResumeResponse::new()
+    }
+
+    fn clear(&mut self) {
+        self.epoch = 0;
+        self.resume_ticket.clear();
+        self.resume_ticket_ttl = 0;
+        self.public_ip = 0;
+        self.public_port = 0;
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static ResumeResponse {
+        static instance: ResumeResponse = ResumeResponse {
+            epoch: 0,
+            resume_ticket: ::std::vec::Vec::new(),
+            resume_ticket_ttl: 0,
+            public_ip: 0,
+            public_port: 0,
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for ResumeResponse {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("ResumeResponse").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for ResumeResponse {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for ResumeResponse {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:MaintenanceNotice)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct MaintenanceNotice {
+    // message fields
+    // @@protoc_insertion_point(field:MaintenanceNotice.notice)
+    pub notice: ::std::string::String,
+    // @@protoc_insertion_point(field:MaintenanceNotice.retry_after)
+    pub retry_after: u32,
+    // @@protoc_insertion_point(field:MaintenanceNotice.drain_seconds)
+    pub drain_seconds: u32,
+    // @@protoc_insertion_point(field:MaintenanceNotice.end_time)
+    pub end_time: u32,
+    // special fields
+    // @@protoc_insertion_point(special_field:MaintenanceNotice.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a MaintenanceNotice {
+    fn default() -> &'a MaintenanceNotice {
+        <MaintenanceNotice as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl MaintenanceNotice {
+    pub fn new() -> MaintenanceNotice {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(4);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "notice",
+            |m: &MaintenanceNotice| { &m.notice },
+            |m: &mut MaintenanceNotice| { &mut m.notice },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "retry_after",
+            |m: &MaintenanceNotice| { &m.retry_after },
+            |m: &mut MaintenanceNotice| { &mut m.retry_after },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "drain_seconds",
+            |m: &MaintenanceNotice| { &m.drain_seconds },
+            |m: &mut MaintenanceNotice| { &mut m.drain_seconds },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "end_time",
+            |m: &MaintenanceNotice| { &m.end_time },
+            |m: &mut MaintenanceNotice| { &mut m.end_time },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<MaintenanceNotice>(
+            "MaintenanceNotice",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for MaintenanceNotice {
+    const NAME: &'static str = "MaintenanceNotice";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.notice = is.read_string()?;
+                },
+                16 => {
+                    self.retry_after = is.read_uint32()?;
+                },
+                24 => {
+                    self.drain_seconds = is.read_uint32()?;
+                },
+                32 => {
+                    self.end_time = is.read_uint32()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.notice.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.notice);
+        }
+        if self.retry_after != 0 {
+            my_size += ::protobuf::rt::uint32_size(2, self.retry_after);
+        }
+        if self.drain_seconds != 0 {
+            my_size += ::protobuf::rt::uint32_size(3, self.drain_seconds);
+        }
+        if self.end_time != 0 {
+            my_size += ::protobuf::rt::uint32_size(4, self.end_time);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.notice.is_empty() {
+            os.write_string(1, &self.notice)?;
+        }
+        if self.retry_after != 0 {
+            os.write_uint32(2, self.retry_after)?;
+        }
+        if self.drain_seconds != 0 {
+            os.write_uint32(3, self.drain_seconds)?;
+        }
+        if self.end_time != 0 {
+            os.write_uint32(4, self.end_time)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> MaintenanceNotice {
+        MaintenanceNotice::new()
+    }
+
+    fn clear(&mut self) {
+        self.notice.clear();
+        self.retry_after = 0;
+        self.drain_seconds = 0;
+        self.end_time = 0;
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static MaintenanceNotice {
+        static instance: MaintenanceNotice = MaintenanceNotice {
+            notice: ::std::string::String::new(),
+            retry_after: 0,
+            drain_seconds: 0,
+            end_time: 0,
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for MaintenanceNotice {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("MaintenanceNotice").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for MaintenanceNotice {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for MaintenanceNotice {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
 #[derive(Clone,Copy,PartialEq,Eq,Debug,Hash)]
 // @@protoc_insertion_point(enum:PunchNatType)
 pub enum PunchNatType {
@@ -1635,42 +3407,85 @@ impl PunchNatType {
 }
 
 static file_descriptor_proto_data: &'static [u8] = b"\
-    \n\rmessage.proto\"D\n\x10HandshakeRequest\x12\x18\n\x07version\x18\x01\
-    \x20\x01(\tR\x07version\x12\x16\n\x06secret\x18\x02\x20\x01(\x08R\x06sec\
-    ret\"\x83\x01\n\x11HandshakeResponse\x12\x18\n\x07version\x18\x01\x20\
-    \x01(\tR\x07version\x12\x16\n\x06secret\x18\x02\x20\x01(\x08R\x06secret\
-    \x12\x1d\n\npublic_key\x18\x03\x20\x01(\x0cR\tpublicKey\x12\x1d\n\nkey_f\
-    inger\x18\x04\x20\x01(\tR\tkeyFinger\"@\n\x16SecretHandshakeRequest\x12\
-    \x14\n\x05token\x18\x01\x20\x01(\tR\x05token\x12\x10\n\x03key\x18\x02\
-    \x20\x01(\x0cR\x03key\"\xfb\x01\n\x13RegistrationRequest\x12\x14\n\x05to\
-    ken\x18\x01\x20\x01(\tR\x05token\x12\x1b\n\tdevice_id\x18\x02\x20\x01(\t\
-    R\x08deviceId\x12\x12\n\x04name\x18\x03\x20\x01(\tR\x04name\x12\x17\n\
-    \x07is_fast\x18\x04\x20\x01(\x08R\x06isFast\x12\x18\n\x07version\x18\x05\
-    \x20\x01(\tR\x07version\x12\x1d\n\nvirtual_ip\x18\x06\x20\x01(\x07R\tvir\
-    tualIp\x12&\n\x0fallow_ip_change\x18\x07\x20\x01(\x08R\rallowIpChange\
-    \x12#\n\rclient_secret\x18\x08\x20\x01(\x08R\x0cclientSecret\"\xb3\x02\n\
-    \x14RegistrationResponse\x12\x1d\n\nvirtual_ip\x18\x01\x20\x01(\x07R\tvi\
-    rtualIp\x12'\n\x0fvirtual_gateway\x18\x02\x20\x01(\x07R\x0evirtualGatewa\
-    y\x12'\n\x0fvirtual_netmask\x18\x03\x20\x01(\x07R\x0evirtualNetmask\x12\
-    \x14\n\x05epoch\x18\x04\x20\x01(\rR\x05epoch\x125\n\x10device_info_list\
-    \x18\x05\x20\x03(\x0b2\x0b.DeviceInfoR\x0edeviceInfoList\x12\x1b\n\tpubl\
-    ic_ip\x18\x06\x20\x01(\x07R\x08publicIp\x12\x1f\n\x0bpublic_port\x18\x07\
-    \x20\x01(\rR\npublicPort\x12\x1f\n\x0bpublic_ipv6\x18\x08\x20\x01(\x0cR\
-    \npublicIpv6\"\x89\x01\n\nDeviceInfo\x12\x12\n\x04name\x18\x01\x20\x01(\
-    \tR\x04name\x12\x1d\n\nvirtual_ip\x18\x02\x20\x01(\x07R\tvirtualIp\x12#\
-    \n\rdevice_status\x18\x03\x20\x01(\rR\x0cdeviceStatus\x12#\n\rclient_sec\
-    ret\x18\x04\x20\x01(\x08R\x0cclientSecret\"Y\n\nDeviceList\x12\x14\n\x05\
-    epoch\x18\x01\x20\x01(\rR\x05epoch\x125\n\x10device_info_list\x18\x02\
-    \x20\x03(\x0b2\x0b.DeviceInfoR\x0edeviceInfoList\"\xc4\x02\n\tPunchInfo\
-    \x12$\n\x0epublic_ip_list\x18\x02\x20\x03(\x07R\x0cpublicIpList\x12\x1f\
-    \n\x0bpublic_port\x18\x03\x20\x01(\rR\npublicPort\x12*\n\x11public_port_\
-    range\x18\x04\x20\x01(\rR\x0fpublicPortRange\x12(\n\x08nat_type\x18\x05\
-    \x20\x01(\x0e2\r.PunchNatTypeR\x07natType\x12\x14\n\x05reply\x18\x06\x20\
-    \x01(\x08R\x05reply\x12\x19\n\x08local_ip\x18\x07\x20\x01(\x07R\x07local\
-    Ip\x12\x1d\n\nlocal_port\x18\x08\x20\x01(\rR\tlocalPort\x12\x12\n\x04ipv\
-    6\x18\t\x20\x01(\x0cR\x04ipv6\x12\x1b\n\tipv6_port\x18\n\x20\x01(\rR\x08\
-    ipv6Port\x12\x19\n\x08tcp_port\x18\x0b\x20\x01(\rR\x07tcpPort*'\n\x0cPun\
-    chNatType\x12\r\n\tSymmetric\x10\0\x12\x08\n\x04Cone\x10\x01b\x06proto3\
+    \n\rmessage.proto\"\x93\x01\n\x10HandshakeRequest\x12\x18\n\x07version\
+    \x18\x01\x20\x01(\tR\x07version\x12\x16\n\x06secret\x18\x02\x20\x01(\x08\
+    R\x06secret\x12\x16\n\x06cookie\x18\x03\x20\x01(\x0cR\x06cookie\x12\x1b\
+    \n\tpow_nonce\x18\x04\x20\x01(\x04R\x08powNonce\x12\x18\n\x07padding\x18\
+    \x05\x20\x01(\x0cR\x07padding\"\xac\x02\n\x11HandshakeResponse\x12\x18\n\
+    \x07version\x18\x01\x20\x01(\tR\x07version\x12\x16\n\x06secret\x18\x02\
+    \x20\x01(\x08R\x06secret\x12\x1d\n\npublic_key\x18\x03\x20\x01(\x0cR\tpu\
+    blicKey\x12\x1d\n\nkey_finger\x18\x04\x20\x01(\tR\tkeyFinger\x12\x16\n\
+    \x06cookie\x18\x05\x20\x01(\x0cR\x06cookie\x12%\n\x0epow_difficulty\x18\
+    \x06\x20\x01(\rR\rpowDifficulty\x12%\n\x07servers\x18\x07\x20\x03(\x0b2\
+    \x0b.ServerInfoR\x07servers\x12\x16\n\x06pinned\x18\x08\x20\x01(\x08R\
+    \x06pinned\x12)\n\x10listen_addresses\x18\t\x20\x03(\tR\x0flistenAddress\
+    es\"@\n\x16SecretHandshakeRequest\x12\x14\n\x05token\x18\x01\x20\x01(\tR\
+    \x05token\x12\x10\n\x03key\x18\x02\x20\x01(\x0cR\x03key\"\x93\x02\n\x13R\
+    egistrationRequest\x12\x14\n\x05token\x18\x01\x20\x01(\tR\x05token\x12\
+    \x1b\n\tdevice_id\x18\x02\x20\x01(\tR\x08deviceId\x12\x12\n\x04name\x18\
+    \x03\x20\x01(\tR\x04name\x12\x17\n\x07is_fast\x18\x04\x20\x01(\x08R\x06i\
+    sFast\x12\x18\n\x07version\x18\x05\x20\x01(\tR\x07version\x12\x1d\n\nvir\
+    tual_ip\x18\x06\x20\x01(\x07R\tvirtualIp\x12&\n\x0fallow_ip_change\x18\
+    \x07\x20\x01(\x08R\rallowIpChange\x12#\n\rclient_secret\x18\x08\x20\x01(\
+    \x08R\x0cclientSecret\x12\x16\n\x06routes\x18\t\x20\x03(\tR\x06routes\"\
+    \xb8\x03\n\x14RegistrationResponse\x12\x1d\n\nvirtual_ip\x18\x01\x20\x01\
+    (\x07R\tvirtualIp\x12'\n\x0fvirtual_gateway\x18\x02\x20\x01(\x07R\x0evir\
+    tualGateway\x12'\n\x0fvirtual_netmask\x18\x03\x20\x01(\x07R\x0evirtualNe\
+    tmask\x12\x14\n\x05epoch\x18\x04\x20\x01(\rR\x05epoch\x125\n\x10device_i\
+    nfo_list\x18\x05\x20\x03(\x0b2\x0b.DeviceInfoR\x0edeviceInfoList\x12\x1b\
+    \n\tpublic_ip\x18\x06\x20\x01(\x07R\x08publicIp\x12\x1f\n\x0bpublic_port\
+    \x18\x07\x20\x01(\rR\npublicPort\x12\x1f\n\x0bpublic_ipv6\x18\x08\x20\
+    \x01(\x0cR\npublicIpv6\x122\n\rclient_config\x18\t\x20\x01(\x0b2\r.Clien\
+    tConfigR\x0cclientConfig\x12#\n\rresume_ticket\x18\n\x20\x01(\x0cR\x0cre\
+    sumeTicket\x12*\n\x11resume_ticket_ttl\x18\x0b\x20\x01(\rR\x0fresumeTick\
+    etTtl\"\xb5\x01\n\nDeviceInfo\x12\x12\n\x04name\x18\x01\x20\x01(\tR\x04n\
+    ame\x12\x1d\n\nvirtual_ip\x18\x02\x20\x01(\x07R\tvirtualIp\x12#\n\rdevic\
+    e_status\x18\x03\x20\x01(\rR\x0cdeviceStatus\x12#\n\rclient_secret\x18\
+    \x04\x20\x01(\x08R\x0cclientSecret\x12\x12\n\x04tags\x18\x05\x20\x03(\tR\
+    \x04tags\x12\x16\n\x06routes\x18\x06\x20\x03(\tR\x06routes\"Y\n\nDeviceL\
+    ist\x12\x14\n\x05epoch\x18\x01\x20\x01(\rR\x05epoch\x125\n\x10device_inf\
+    o_list\x18\x02\x20\x03(\x0b2\x0b.DeviceInfoR\x0edeviceInfoList\"\xf0\x03\
+    \n\tPunchInfo\x12$\n\x0epublic_ip_list\x18\x02\x20\x03(\x07R\x0cpublicIp\
+    List\x12\x1f\n\x0bpublic_port\x18\x03\x20\x01(\rR\npublicPort\x12*\n\x11\
+    public_port_range\x18\x04\x20\x01(\rR\x0fpublicPortRange\x12(\n\x08nat_t\
+    ype\x18\x05\x20\x01(\x0e2\r.PunchNatTypeR\x07natType\x12\x14\n\x05reply\
+    \x18\x06\x20\x01(\x08R\x05reply\x12\x19\n\x08local_ip\x18\x07\x20\x01(\
+    \x07R\x07localIp\x12\x1d\n\nlocal_port\x18\x08\x20\x01(\rR\tlocalPort\
+    \x12\x12\n\x04ipv6\x18\t\x20\x01(\x0cR\x04ipv6\x12\x1b\n\tipv6_port\x18\
+    \n\x20\x01(\rR\x08ipv6Port\x12\x19\n\x08tcp_port\x18\x0b\x20\x01(\rR\x07\
+    tcpPort\x12\x17\n\x07turn_ip\x18\x0c\x20\x01(\x07R\x06turnIp\x12\x1b\n\t\
+    turn_port\x18\r\x20\x01(\rR\x08turnPort\x12-\n\ncandidates\x18\x0e\x20\
+    \x03(\x0b2\r.IceCandidateR\ncandidates\x121\n\x0ccapabilities\x18\x0f\
+    \x20\x01(\x0b2\r.CapabilitiesR\x0ccapabilities\x12\x12\n\x04time\x18\x10\
+    \x20\x01(\x04R\x04time\"\x9d\x01\n\x0cClientConfig\x12\x15\n\x06in_ips\
+    \x18\x01\x20\x03(\tR\x05inIps\x12\x1d\n\ndns_server\x18\x02\x20\x01(\x07\
+    R\tdnsServer\x12\x1f\n\x0bdns_domains\x18\x03\x20\x03(\tR\ndnsDomains\
+    \x12\x1c\n\tkeepalive\x18\x04\x20\x01(\rR\tkeepalive\x12\x18\n\x07versio\
+    n\x18\x05\x20\x01(\rR\x07version\"b\n\x0cIceCandidate\x12\x12\n\x04kind\
+    \x18\x01\x20\x01(\rR\x04kind\x12\x0e\n\x02ip\x18\x02\x20\x01(\x0cR\x02ip\
+    \x12\x12\n\x04port\x18\x03\x20\x01(\rR\x04port\x12\x1a\n\x08priority\x18\
+    \x04\x20\x01(\rR\x08priority\"\xa1\x01\n\x0bQuotaStatus\x12\x12\n\x04use\
+    d\x18\x01\x20\x01(\x04R\x04used\x12\x14\n\x05limit\x18\x02\x20\x01(\x04R\
+    \x05limit\x12\x14\n\x05state\x18\x03\x20\x01(\rR\x05state\x12\x1d\n\nrat\
+    e_limit\x18\x04\x20\x01(\rR\trateLimit\x12\x1d\n\nreset_time\x18\x05\x20\
+    \x01(\rR\tresetTime\x12\x14\n\x05scope\x18\x06\x20\x01(\rR\x05scope\">\n\
+    \nServerInfo\x12\x18\n\x07address\x18\x01\x20\x01(\tR\x07address\x12\x16\
+    \n\x06region\x18\x02\x20\x01(\tR\x06region\"\x8b\x01\n\x0cCapabilities\
+    \x12\x18\n\x07ciphers\x18\x01\x20\x03(\tR\x07ciphers\x12\x20\n\x0bcompre\
+    ssion\x18\x02\x20\x03(\tR\x0bcompression\x12\x1f\n\x0bmax_payload\x18\
+    \x03\x20\x01(\rR\nmaxPayload\x12\x1e\n\nextensions\x18\x04\x20\x03(\tR\n\
+    extensions\"Q\n\rResumeRequest\x12\x16\n\x06ticket\x18\x01\x20\x01(\x0cR\
+    \x06ticket\x12\x12\n\x04time\x18\x02\x20\x01(\x03R\x04time\x12\x14\n\x05\
+    proof\x18\x03\x20\x01(\x0cR\x05proof\"\xb5\x01\n\x0eResumeResponse\x12\
+    \x14\n\x05epoch\x18\x01\x20\x01(\rR\x05epoch\x12#\n\rresume_ticket\x18\
+    \x02\x20\x01(\x0cR\x0cresumeTicket\x12*\n\x11resume_ticket_ttl\x18\x03\
+    \x20\x01(\rR\x0fresumeTicketTtl\x12\x1b\n\tpublic_ip\x18\x04\x20\x01(\
+    \x07R\x08publicIp\x12\x1f\n\x0bpublic_port\x18\x05\x20\x01(\rR\npublicPo\
+    rt\"\x8c\x01\n\x11MaintenanceNotice\x12\x16\n\x06notice\x18\x01\x20\x01(\
+    \tR\x06notice\x12\x1f\n\x0bretry_after\x18\x02\x20\x01(\rR\nretryAfter\
+    \x12#\n\rdrain_seconds\x18\x03\x20\x01(\rR\x0cdrainSeconds\x12\x19\n\x08\
+    end_time\x18\x04\x20\x01(\rR\x07endTime*'\n\x0cPunchNatType\x12\r\n\tSym\
+    metric\x10\0\x12\x08\n\x04Cone\x10\x01b\x06proto3\
 ";
 
 /// `FileDescriptorProto` object which was a source for this generated file
@@ -1688,7 +3503,7 @@ pub fn file_descriptor() -> &'static ::protobuf::reflect::FileDescriptor {
     file_descriptor.get(|| {
         let generated_file_descriptor = generated_file_descriptor_lazy.get(|| {
             let mut deps = ::std::vec::Vec::with_capacity(0);
-            let mut messages = ::std::vec::Vec::with_capacity(8);
+            let mut messages = ::std::vec::Vec::with_capacity(16);
             messages.push(HandshakeRequest::generated_message_descriptor_data());
             messages.push(HandshakeResponse::generated_message_descriptor_data());
             messages.push(SecretHandshakeRequest::generated_message_descriptor_data());
@@ -1697,6 +3512,14 @@ pub fn file_descriptor() -> &'static ::protobuf::reflect::FileDescriptor {
             messages.push(DeviceInfo::generated_message_descriptor_data());
             messages.push(DeviceList::generated_message_descriptor_data());
             messages.push(PunchInfo::generated_message_descriptor_data());
+            messages.push(ClientConfig::generated_message_descriptor_data());
+            messages.push(IceCandidate::generated_message_descriptor_data());
+            messages.push(QuotaStatus::generated_message_descriptor_data());
+            messages.push(ServerInfo::generated_message_descriptor_data());
+            messages.push(Capabilities::generated_message_descriptor_data());
+            messages.push(ResumeRequest::generated_message_descriptor_data());
+            messages.push(ResumeResponse::generated_message_descriptor_data());
+            messages.push(MaintenanceNotice::generated_message_descriptor_data());
             let mut enums = ::std::vec::Vec::with_capacity(1);
             enums.push(PunchNatType::generated_enum_descriptor_data());
             ::protobuf::reflect::GeneratedFileDescriptor::new_generated(
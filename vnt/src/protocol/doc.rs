@@ -0,0 +1,208 @@
+//! 由代码中的常量生成协议文档(markdown)，协议号直接从枚举读取，新增协议后重新生成即可
+
+use std::fmt::{Debug, Write};
+
+use crate::protocol::body::{AES_GCM_ENCRYPTION_RESERVED, ENCRYPTION_RESERVED};
+use crate::protocol::extension::ExtensionId;
+use crate::protocol::vectors::message_name;
+use crate::protocol::{
+    control_packet, error_packet, ip_turn_packet, other_turn_packet, service_packet, stream_packet,
+    Protocol, Version, HEAD_LEN, MAX_TTL,
+};
+
+const SCHEMA: &str = include_str!("../../proto/message.proto");
+
+/// 0-255中已定义的取值
+pub(crate) fn defined<T: From<u8> + Debug>(is_unknown: fn(&T) -> bool) -> Vec<(u8, String)> {
+    (0..=255u8)
+        .filter_map(|v| {
+            let value = T::from(v);
+            if is_unknown(&value) {
+                None
+            } else {
+                Some((v, format!("{:?}", value)))
+            }
+        })
+        .collect()
+}
+
+fn table(out: &mut String, title: &str, rows: &[(u8, String)], body: impl Fn(u8) -> String) {
+    let _ = writeln!(out, "### {}\n", title);
+    let _ = writeln!(out, "| value | name | body |");
+    let _ = writeln!(out, "|---|---|---|");
+    for (value, name) in rows {
+        let _ = writeln!(out, "| {} | {} | {} |", value, name, body(*value));
+    }
+    out.push('\n');
+}
+
+/// 数据体为protobuf时显示消息名
+fn protobuf_body(protocol: Protocol) -> impl Fn(u8) -> String {
+    move |transport_protocol| {
+        message_name(protocol.into(), transport_protocol)
+            .map(|name| format!("protobuf `{}`", name))
+            .unwrap_or_default()
+    }
+}
+
+pub fn document() -> String {
+    let mut out = String::new();
+    let version: u8 = Version::V1.into();
+    let _ = writeln!(out, "# vnt wire protocol\n");
+    let _ = writeln!(
+        out,
+        "Generated from vnt {}. All integers are big-endian unless noted.\n",
+        crate::VNT_VERSION
+    );
+
+    let _ = writeln!(out, "## Header\n");
+    let _ = writeln!(
+        out,
+        "Every packet starts with a {}-byte header, followed by the body.\n",
+        HEAD_LEN
+    );
+    let _ = writeln!(out, "| offset | bits | field |");
+    let _ = writeln!(out, "|---|---|---|");
+    let _ = writeln!(out, "| 0 | 0x80 | encrypted body |");
+    let _ = writeln!(
+        out,
+        "| 0 | 0x40 | packet to/from the server (senders set 0x50 for compatibility with versions before 1.2.5) |"
+    );
+    let _ = writeln!(out, "| 0 | 0x20 | extension area at the end of the body |");
+    let _ = writeln!(out, "| 0 | 0x10 | unused |");
+    let _ = writeln!(out, "| 0 | 0x0F | version, currently {} |", version);
+    let _ = writeln!(out, "| 1 | 8 | protocol |");
+    let _ = writeln!(
+        out,
+        "| 2 | 8 | transport protocol, meaning depends on protocol |"
+    );
+    let _ = writeln!(out, "| 3 | 0xF0 | initial ttl, at most {} |", MAX_TTL);
+    let _ = writeln!(
+        out,
+        "| 3 | 0x0F | remaining ttl, decremented by each relay |"
+    );
+    let _ = writeln!(out, "| 4 | 32 | source virtual ip |");
+    let _ = writeln!(out, "| 8 | 32 | destination virtual ip |\n");
+
+    let _ = writeln!(out, "## Protocols\n");
+    table(
+        &mut out,
+        "protocol",
+        &defined::<Protocol>(|v| matches!(v, Protocol::UnKnow(_))),
+        |_| String::new(),
+    );
+    table(
+        &mut out,
+        "Service (protocol 1)",
+        &defined::<service_packet::Protocol>(|v| matches!(v, service_packet::Protocol::Unknown(_))),
+        protobuf_body(Protocol::Service),
+    );
+    table(
+        &mut out,
+        "Error (protocol 2)",
+        &defined::<error_packet::Protocol>(|v| matches!(v, error_packet::Protocol::Other(_))),
+        |value| match error_packet::Protocol::from(value) {
+            error_packet::Protocol::Disconnect
+            | error_packet::Protocol::RateLimited
+            | error_packet::Protocol::PunchRateLimited => {
+                "optional u32 seconds to wait, 0 or absent means unspecified".to_string()
+            }
+            error_packet::Protocol::Maintenance => "protobuf `MaintenanceNotice`".to_string(),
+            _ => String::new(),
+        },
+    );
+    let _ = writeln!(out, "Other error codes carry a UTF-8 message.\n");
+    table(
+        &mut out,
+        "Control (protocol 3)",
+        &defined::<control_packet::Protocol>(|v| matches!(v, control_packet::Protocol::Unknown(_))),
+        |value| match control_packet::Protocol::from(value) {
+            control_packet::Protocol::Ping | control_packet::Protocol::Pong => {
+                "u16 time (milliseconds mod 65536), u16 device list epoch".to_string()
+            }
+            control_packet::Protocol::AddrResponse => "ipv4(4), u16 port".to_string(),
+            _ => String::new(),
+        },
+    );
+    table(
+        &mut out,
+        "IpTurn (protocol 4)",
+        &defined::<ip_turn_packet::Protocol>(|v| matches!(v, ip_turn_packet::Protocol::Unknown(_))),
+        |value| match ip_turn_packet::Protocol::from(value) {
+            ip_turn_packet::Protocol::Ipv4 => "ipv4 packet".to_string(),
+            ip_turn_packet::Protocol::Ipv4Broadcast => {
+                "u8 n, n ipv4 addresses already delivered, ipv4 packet".to_string()
+            }
+            ip_turn_packet::Protocol::Ethernet => "ethernet frame".to_string(),
+            _ => String::new(),
+        },
+    );
+    table(
+        &mut out,
+        "OtherTurn (protocol 5)",
+        &defined::<other_turn_packet::Protocol>(|v| {
+            matches!(v, other_turn_packet::Protocol::Unknown(_))
+        }),
+        |value| match other_turn_packet::Protocol::from(value) {
+            other_turn_packet::Protocol::Stream => "stream header, data".to_string(),
+            _ => protobuf_body(Protocol::OtherTurn)(value),
+        },
+    );
+
+    let _ = writeln!(out, "## Stream\n");
+    let _ = writeln!(
+        out,
+        "{}-byte header: u16 source port, u16 destination port, u32 seq, u32 ack, u8 flags, 24 reserved bits. \
+         seq and ack count bytes, SYN and FIN take one sequence number each.\n",
+        stream_packet::HEAD_LEN
+    );
+    let _ = writeln!(
+        out,
+        "| flag | value |\n|---|---|\n| SYN | {:#06b} |\n| ACK | {:#06b} |\n| FIN | {:#06b} |\n| RST | {:#06b} |\n",
+        stream_packet::SYN,
+        stream_packet::ACK,
+        stream_packet::FIN,
+        stream_packet::RST
+    );
+
+    let _ = writeln!(out, "## Extension area\n");
+    let _ = writeln!(
+        out,
+        "Appended to the body when the 0x20 flag is set, encrypted together with the body: \
+         a list of u8 type, u8 length, value; then u16 total length of the area including these two bytes. \
+         Unknown types are skipped. Only sent to peers advertising the `{}` capability.\n",
+        crate::protocol::extension::CAPABILITY
+    );
+    table(
+        &mut out,
+        "extension type",
+        &defined::<ExtensionId>(|v| matches!(v, ExtensionId::Unknown(_))),
+        |_| String::new(),
+    );
+
+    let _ = writeln!(out, "## Encryption\n");
+    let _ = writeln!(
+        out,
+        "The key is sha256(password); aes_gcm, aes_cbc and aes_ecb use the first 16 bytes when the password \
+         is shorter than 8 characters, sm4_cbc always uses 16 bytes. The finger is the last 12 bytes of \
+         sha256(nonce + encrypted body + sha256(token)) and lets the server verify packets it cannot decrypt.\n"
+    );
+    let _ = writeln!(
+        out,
+        "aes_gcm and chacha20_poly1305 append u32 random, 16-byte tag and 12-byte finger ({} bytes). \
+         The 12-byte nonce is source ip, destination ip, protocol, transport protocol, server flag (0/1), initial ttl. \
+         Senders reserve {} bytes at the end of the buffer for any cipher.\n",
+        AES_GCM_ENCRYPTION_RESERVED, ENCRYPTION_RESERVED
+    );
+
+    let _ = writeln!(out, "## Messages\n");
+    let _ = writeln!(out, "```proto\n{}```\n", SCHEMA);
+
+    let _ = writeln!(out, "## Test vectors\n");
+    let _ = writeln!(
+        out,
+        "`vnt-conformance emit` writes packets built from these rules as JSON, \
+         `vnt-conformance check <file>` validates them against an implementation."
+    );
+    out
+}
@@ -1,4 +1,7 @@
+use protobuf::Message;
+
 use crate::error::*;
+use crate::proto::message::MaintenanceNotice;
 
 #[derive(Eq, PartialEq, Copy, Clone, Debug)]
 pub enum Protocol {
@@ -8,6 +11,16 @@ pub enum Protocol {
     IpAlreadyExists,
     InvalidIp,
     NoKey,
+    /// 相同的device_id已经在其他设备上注册，通常是克隆的虚拟机
+    DeviceIdConflict,
+    /// 注册或握手太频繁，负载可以带4字节大端的重试等待秒数
+    RateLimited,
+    /// token下的设备数达到上限
+    DeviceLimit,
+    /// 经服务端转发的打洞请求太频繁，负载同RateLimited
+    PunchRateLimited,
+    /// 服务端维护中，不接受新的注册，负载为protobuf编码的MaintenanceNotice
+    Maintenance,
     Other(u8),
 }
 
@@ -20,6 +33,11 @@ impl From<u8> for Protocol {
             4 => Self::IpAlreadyExists,
             5 => Self::InvalidIp,
             6 => Self::NoKey,
+            7 => Self::DeviceIdConflict,
+            8 => Self::RateLimited,
+            9 => Self::DeviceLimit,
+            10 => Self::PunchRateLimited,
+            11 => Self::Maintenance,
             val => Self::Other(val),
         }
     }
@@ -34,6 +52,11 @@ impl Into<u8> for Protocol {
             Protocol::IpAlreadyExists => 4,
             Protocol::InvalidIp => 5,
             Protocol::NoKey => 6,
+            Protocol::DeviceIdConflict => 7,
+            Protocol::RateLimited => 8,
+            Protocol::DeviceLimit => 9,
+            Protocol::PunchRateLimited => 10,
+            Protocol::Maintenance => 11,
             Protocol::Other(val) => val,
         }
     }
@@ -41,11 +64,20 @@ impl Into<u8> for Protocol {
 
 pub enum InErrorPacket<B> {
     TokenError,
-    Disconnect,
+    /// 服务端要求重新注册前等待的秒数，0表示未指定，旧版本服务端没有负载
+    Disconnect(u32),
     AddressExhausted,
     IpAlreadyExists,
     InvalidIp,
     NoKey,
+    DeviceIdConflict,
+    /// 服务端要求等待的秒数，0表示未指定
+    RateLimited(u32),
+    DeviceLimit,
+    /// 服务端要求暂停打洞请求的秒数，0表示未指定
+    PunchRateLimited(u32),
+    /// 维护通知，没有负载时为默认值
+    Maintenance(MaintenanceNotice),
     OtherError(ErrorPacket<B>),
 }
 
@@ -53,16 +85,30 @@ impl<B: AsRef<[u8]>> InErrorPacket<B> {
     pub fn new(protocol: u8, buffer: B) -> Result<InErrorPacket<B>> {
         match Protocol::from(protocol) {
             Protocol::TokenError => Ok(InErrorPacket::TokenError),
-            Protocol::Disconnect => Ok(InErrorPacket::Disconnect),
+            Protocol::Disconnect => Ok(InErrorPacket::Disconnect(retry_after(&buffer))),
             Protocol::AddressExhausted => Ok(InErrorPacket::AddressExhausted),
             Protocol::IpAlreadyExists => Ok(InErrorPacket::IpAlreadyExists),
             Protocol::InvalidIp => Ok(InErrorPacket::InvalidIp),
             Protocol::NoKey => Ok(InErrorPacket::NoKey),
+            Protocol::DeviceIdConflict => Ok(InErrorPacket::DeviceIdConflict),
+            Protocol::RateLimited => Ok(InErrorPacket::RateLimited(retry_after(&buffer))),
+            Protocol::DeviceLimit => Ok(InErrorPacket::DeviceLimit),
+            Protocol::PunchRateLimited => Ok(InErrorPacket::PunchRateLimited(retry_after(&buffer))),
+            Protocol::Maintenance => Ok(InErrorPacket::Maintenance(
+                MaintenanceNotice::parse_from_bytes(buffer.as_ref())?,
+            )),
             Protocol::Other(_) => Ok(InErrorPacket::OtherError(ErrorPacket::new(buffer)?)),
         }
     }
 }
 
+fn retry_after<B: AsRef<[u8]>>(buffer: &B) -> u32 {
+    match buffer.as_ref().get(..4) {
+        Some(buf) => u32::from_be_bytes(buf.try_into().unwrap()),
+        None => 0,
+    }
+}
+
 pub struct ErrorPacket<B> {
     buffer: B,
 }
@@ -0,0 +1,244 @@
+use std::io;
+
+use crate::protocol::NetPacket;
+
+/*
+   扩展区，头部x标志置位时追加在数据体末尾，和数据体一起加密
+   0                   1                   2                   3
+   0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+  |     类型(8)    |    长度(8)    |          值(长度)  ...         |
+  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+  |                    ... 更多的类型-长度-值 ...                    |
+  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+  |       扩展区总长度(16)，包含这两个字节  |
+  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+  注：放在末尾，接收方只需要缩短数据长度就能去掉扩展区，不用移动数据体；
+     不认识的类型直接跳过；只发给能力中包含tlv的设备，旧版本会把扩展区当成数据
+*/
+
+/// 能力交换中表示支持扩展区的名称
+pub const CAPABILITY: &str = "tlv";
+/// 扩展区末尾的长度字段
+const TRAILER_LEN: usize = 2;
+
+/// 已登记的扩展类型，0保留，1-127按功能登记，128-255留给实验性功能
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum ExtensionId {
+    /// 发送时间，unix毫秒(64位)，用于单向延迟和时钟偏差估计
+    Timestamp,
+    /// 路径遥测，经过的中转节点和各段延迟
+    PathTelemetry,
+    /// 拥塞提示，接收方建议的发送速率
+    CongestionHint,
+    Unknown(u8),
+}
+
+impl From<u8> for ExtensionId {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => ExtensionId::Timestamp,
+            2 => ExtensionId::PathTelemetry,
+            3 => ExtensionId::CongestionHint,
+            val => ExtensionId::Unknown(val),
+        }
+    }
+}
+
+impl Into<u8> for ExtensionId {
+    fn into(self) -> u8 {
+        match self {
+            ExtensionId::Timestamp => 1,
+            ExtensionId::PathTelemetry => 2,
+            ExtensionId::CongestionHint => 3,
+            ExtensionId::Unknown(val) => val,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Extension {
+    pub id: ExtensionId,
+    pub value: Vec<u8>,
+}
+
+/// 扩展区编码后的长度
+pub fn encoded_len(extensions: &[(ExtensionId, &[u8])]) -> usize {
+    extensions.iter().map(|(_, v)| 2 + v.len()).sum::<usize>() + TRAILER_LEN
+}
+
+/// 在数据体末尾追加扩展区，需要在加密之前调用，buffer要预留encoded_len的空间
+pub fn append<B: AsRef<[u8]> + AsMut<[u8]>>(
+    net_packet: &mut NetPacket<B>,
+    extensions: &[(ExtensionId, &[u8])],
+) -> io::Result<()> {
+    if net_packet.has_extension() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "extension already present",
+        ));
+    }
+    let len = encoded_len(extensions);
+    if len > u16::MAX as usize || extensions.iter().any(|(_, v)| v.len() > u8::MAX as usize) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "extension too long",
+        ));
+    }
+    let start = net_packet.data_len();
+    if net_packet.reserve() < len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "insufficient reserve for extension",
+        ));
+    }
+    net_packet.set_data_len(start + len)?;
+    let buf = &mut net_packet.buffer_mut()[start..];
+    let mut index = 0;
+    for (id, value) in extensions {
+        buf[index] = (*id).into();
+        buf[index + 1] = value.len() as u8;
+        buf[index + 2..index + 2 + value.len()].copy_from_slice(value);
+        index += 2 + value.len();
+    }
+    buf[index..index + TRAILER_LEN].copy_from_slice(&(len as u16).to_be_bytes());
+    net_packet.set_extension_flag(true);
+    Ok(())
+}
+
+/// 取出扩展区并从数据中去掉，需要在解密之后调用，没有扩展区时返回空
+pub fn take<B: AsRef<[u8]> + AsMut<[u8]>>(
+    net_packet: &mut NetPacket<B>,
+) -> io::Result<Vec<Extension>> {
+    if !net_packet.has_extension() {
+        return Ok(Vec::new());
+    }
+    let payload = net_packet.payload();
+    if payload.len() < TRAILER_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "extension trailer missing",
+        ));
+    }
+    let len =
+        u16::from_be_bytes(payload[payload.len() - TRAILER_LEN..].try_into().unwrap()) as usize;
+    if len < TRAILER_LEN || len > payload.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "extension length invalid",
+        ));
+    }
+    let area = &payload[payload.len() - len..payload.len() - TRAILER_LEN];
+    let mut extensions = Vec::new();
+    let mut index = 0;
+    while index < area.len() {
+        if index + 2 > area.len() || index + 2 + area[index + 1] as usize > area.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "extension truncated",
+            ));
+        }
+        let value_len = area[index + 1] as usize;
+        extensions.push(Extension {
+            id: ExtensionId::from(area[index]),
+            value: area[index + 2..index + 2 + value_len].to_vec(),
+        });
+        index += 2 + value_len;
+    }
+    let data_len = net_packet.data_len() - len;
+    net_packet.set_data_len(data_len)?;
+    net_packet.set_extension_flag(false);
+    Ok(extensions)
+}
+
+#[cfg(test)]
+fn packet(payload: &[u8], extension: &[u8]) -> NetPacket<Vec<u8>> {
+    let mut buf = vec![0u8; 12];
+    buf.extend_from_slice(payload);
+    buf.extend_from_slice(extension);
+    let mut packet = NetPacket::new(buf).unwrap();
+    packet.set_extension_flag(true);
+    packet
+}
+
+#[test]
+fn test_round_trip() {
+    let mut packet = NetPacket::new0(12 + 4, vec![0u8; 12 + 4 + 64]).unwrap();
+    packet.payload_mut().copy_from_slice(&[1, 2, 3, 4]);
+    let time = 1700000000000u64.to_be_bytes();
+    let extensions: [(ExtensionId, &[u8]); 2] = [
+        (ExtensionId::Timestamp, &time),
+        (ExtensionId::CongestionHint, &[]),
+    ];
+    append(&mut packet, &extensions).unwrap();
+    assert!(packet.has_extension());
+    assert_eq!(packet.data_len(), 12 + 4 + encoded_len(&extensions));
+    assert!(append(&mut packet, &extensions).is_err());
+    let list = take(&mut packet).unwrap();
+    assert!(!packet.has_extension());
+    assert_eq!(packet.payload(), &[1, 2, 3, 4]);
+    assert_eq!(list.len(), 2);
+    assert_eq!(list[0].id, ExtensionId::Timestamp);
+    assert_eq!(list[0].value, time);
+    assert_eq!(list[1].id, ExtensionId::CongestionHint);
+    assert!(list[1].value.is_empty());
+}
+
+#[test]
+fn test_no_extension() {
+    let mut packet = packet(&[1, 2, 3], &[]);
+    packet.set_extension_flag(false);
+    assert!(take(&mut packet).unwrap().is_empty());
+    assert_eq!(packet.payload(), &[1, 2, 3]);
+}
+
+#[test]
+fn test_unknown_type() {
+    // 不认识的类型原样返回，由调用方跳过，后面的类型照常解析
+    let mut packet = packet(&[9], &[200, 2, 0xaa, 0xbb, 1, 1, 7, 0, 9]);
+    let list = take(&mut packet).unwrap();
+    assert_eq!(list.len(), 2);
+    assert_eq!(list[0].id, ExtensionId::Unknown(200));
+    assert_eq!(list[0].value, [0xaa, 0xbb]);
+    assert_eq!(list[1].id, ExtensionId::Timestamp);
+    assert_eq!(list[1].value, [7]);
+    assert_eq!(packet.payload(), &[9]);
+    let id: u8 = ExtensionId::Unknown(200).into();
+    assert_eq!(id, 200);
+}
+
+#[test]
+fn test_truncated() {
+    // 没有长度字段
+    assert!(take(&mut packet(&[], &[0])).is_err());
+    // 值的长度超出扩展区
+    assert!(take(&mut packet(&[9], &[1, 8, 1, 2, 0, 6])).is_err());
+    // 最后一项只有类型没有长度
+    assert!(take(&mut packet(&[9], &[1, 0, 2, 0, 5])).is_err());
+}
+
+#[test]
+fn test_length_invalid() {
+    // 总长度超过数据体
+    assert!(take(&mut packet(&[9], &[1, 0, 0, 10])).is_err());
+    // 总长度小于长度字段本身
+    assert!(take(&mut packet(&[9], &[0, 1])).is_err());
+    // 失败时不修改数据
+    let mut packet = packet(&[9], &[0xff, 0xff]);
+    assert!(take(&mut packet).is_err());
+    assert!(packet.has_extension());
+    assert_eq!(packet.payload(), &[9, 0xff, 0xff]);
+}
+
+#[test]
+fn test_append_invalid() {
+    let value = [0u8; 256];
+    let mut packet = NetPacket::new0(12, vec![0u8; 12 + 512]).unwrap();
+    // 单个值超过255字节
+    assert!(append(&mut packet, &[(ExtensionId::PathTelemetry, &value)]).is_err());
+    // 预留空间不够
+    let mut packet = NetPacket::new0(12, vec![0u8; 12 + 4]).unwrap();
+    assert!(append(&mut packet, &[(ExtensionId::PathTelemetry, &value[..4])]).is_err());
+    assert!(!packet.has_extension());
+    assert_eq!(packet.data_len(), 12);
+}
@@ -5,6 +5,8 @@ use std::net::Ipv4Addr;
 pub enum Protocol {
     Ipv4,
     Ipv4Broadcast,
+    /// 桥接模式下转发的以太网帧
+    Ethernet,
     Unknown(u8),
 }
 
@@ -13,6 +15,7 @@ impl From<u8> for Protocol {
         match value {
             4 => Protocol::Ipv4,
             201 => Protocol::Ipv4Broadcast,
+            202 => Protocol::Ethernet,
             val => Protocol::Unknown(val),
         }
     }
@@ -23,6 +26,7 @@ impl Into<u8> for Protocol {
         match self {
             Protocol::Ipv4 => 4,
             Protocol::Ipv4Broadcast => 201,
+            Protocol::Ethernet => 202,
             Protocol::Unknown(val) => val,
         }
     }
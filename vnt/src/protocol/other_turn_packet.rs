@@ -1,6 +1,8 @@
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum Protocol {
     Punch,
+    /// 隧道内的可靠流
+    Stream,
     Unknown(u8),
 }
 
@@ -8,6 +10,7 @@ impl From<u8> for Protocol {
     fn from(value: u8) -> Self {
         match value {
             1 => Protocol::Punch,
+            2 => Protocol::Stream,
             val => Protocol::Unknown(val),
         }
     }
@@ -17,6 +20,7 @@ impl Into<u8> for Protocol {
     fn into(self) -> u8 {
         match self {
             Protocol::Punch => 1,
+            Protocol::Stream => 2,
             Protocol::Unknown(val) => val,
         }
     }
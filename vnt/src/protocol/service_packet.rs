@@ -13,6 +13,15 @@ pub enum Protocol {
     HandshakeResponse,
     SecretHandshakeRequest,
     SecretHandshakeResponse,
+    /// 推送客户端配置
+    PushClientConfig,
+    /// 推送中转流量配额状态
+    PushQuotaStatus,
+    /// 用票据恢复会话
+    ResumeRequest,
+    ResumeResponse,
+    /// 推送维护通知
+    PushMaintenance,
     Unknown(u8),
 }
 
@@ -27,6 +36,11 @@ impl From<u8> for Protocol {
             6 => Self::HandshakeResponse,
             7 => Self::SecretHandshakeRequest,
             8 => Self::SecretHandshakeResponse,
+            9 => Self::PushClientConfig,
+            10 => Self::PushQuotaStatus,
+            11 => Self::ResumeRequest,
+            12 => Self::ResumeResponse,
+            13 => Self::PushMaintenance,
             val => Self::Unknown(val),
         }
     }
@@ -43,6 +57,11 @@ impl Into<u8> for Protocol {
             Self::HandshakeResponse => 6,
             Self::SecretHandshakeRequest => 7,
             Self::SecretHandshakeResponse => 8,
+            Self::PushClientConfig => 9,
+            Self::PushQuotaStatus => 10,
+            Self::ResumeRequest => 11,
+            Self::ResumeResponse => 12,
+            Self::PushMaintenance => 13,
             Self::Unknown(val) => val,
         }
     }
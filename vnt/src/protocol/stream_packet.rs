@@ -0,0 +1,94 @@
+use std::{fmt, io};
+
+/*
+   0                                            15                                              31
+   0  1  2  3  4  5  6  7  8  9  0  1  2  3  4  5  6  7  8  9  0  1  2  3  4  5  6  7  8  9  0  1
+  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+  |                  源端口(16)                  |                  目的端口(16)                  |
+  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+  |                                            序号(32)                                          |
+  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+  |                                           确认号(32)                                         |
+  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+  |       标志(8)        |                                保留(24)                                |
+  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+  |                                             数据体                                            |
+  +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+  注：序号和确认号按字节计数，SYN和FIN各占一个序号
+*/
+pub const HEAD_LEN: usize = 16;
+
+/// 建立连接
+pub const SYN: u8 = 0b0001;
+/// 确认号有效
+pub const ACK: u8 = 0b0010;
+/// 数据发送完毕
+pub const FIN: u8 = 0b0100;
+/// 重置连接
+pub const RST: u8 = 0b1000;
+
+pub struct StreamPacket<B> {
+    buffer: B,
+}
+
+impl<B: AsRef<[u8]>> StreamPacket<B> {
+    pub fn new(buffer: B) -> io::Result<StreamPacket<B>> {
+        let len = buffer.as_ref().len();
+        if len < HEAD_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "len < 16"));
+        }
+        Ok(StreamPacket { buffer })
+    }
+    pub fn source_port(&self) -> u16 {
+        u16::from_be_bytes(self.buffer.as_ref()[..2].try_into().unwrap())
+    }
+    pub fn destination_port(&self) -> u16 {
+        u16::from_be_bytes(self.buffer.as_ref()[2..4].try_into().unwrap())
+    }
+    pub fn seq(&self) -> u32 {
+        u32::from_be_bytes(self.buffer.as_ref()[4..8].try_into().unwrap())
+    }
+    pub fn ack(&self) -> u32 {
+        u32::from_be_bytes(self.buffer.as_ref()[8..12].try_into().unwrap())
+    }
+    pub fn flags(&self) -> u8 {
+        self.buffer.as_ref()[12]
+    }
+    pub fn payload(&self) -> &[u8] {
+        &self.buffer.as_ref()[HEAD_LEN..]
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> StreamPacket<B> {
+    pub fn set_source_port(&mut self, port: u16) {
+        self.buffer.as_mut()[..2].copy_from_slice(&port.to_be_bytes())
+    }
+    pub fn set_destination_port(&mut self, port: u16) {
+        self.buffer.as_mut()[2..4].copy_from_slice(&port.to_be_bytes())
+    }
+    pub fn set_seq(&mut self, seq: u32) {
+        self.buffer.as_mut()[4..8].copy_from_slice(&seq.to_be_bytes())
+    }
+    pub fn set_ack(&mut self, ack: u32) {
+        self.buffer.as_mut()[8..12].copy_from_slice(&ack.to_be_bytes())
+    }
+    pub fn set_flags(&mut self, flags: u8) {
+        self.buffer.as_mut()[12] = flags
+    }
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer.as_mut()[HEAD_LEN..]
+    }
+}
+
+impl<B: AsRef<[u8]>> fmt::Debug for StreamPacket<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamPacket")
+            .field("source_port", &self.source_port())
+            .field("destination_port", &self.destination_port())
+            .field("seq", &self.seq())
+            .field("ack", &self.ack())
+            .field("flags", &self.flags())
+            .field("payload_len", &self.payload().len())
+            .finish()
+    }
+}
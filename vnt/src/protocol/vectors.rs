@@ -0,0 +1,1003 @@
+//! 协议的测试向量：由输入字段构造完整的数据包，得到期望的字节。
+//! 其他实现用来验证编码和解码是否和本实现一致，本实现用来发现改动时无意中改变了线上格式
+
+use std::collections::BTreeMap;
+use std::io;
+use std::net::Ipv4Addr;
+
+use protobuf::reflect::MessageDescriptor;
+use protobuf::text_format;
+
+use crate::protocol::control_packet::PingPacket;
+use crate::protocol::error_packet::InErrorPacket;
+use crate::protocol::extension::{self, ExtensionId};
+use crate::protocol::ip_turn_packet::BroadcastPacket;
+use crate::protocol::stream_packet::StreamPacket;
+use crate::protocol::{
+    error_packet, other_turn_packet, service_packet, NetPacket, Protocol, Version, HEAD_LEN,
+};
+
+/// 向量的字段，按名称排序
+pub type Fields = BTreeMap<String, String>;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Vector {
+    pub name: String,
+    /// 决定fields的含义：packet/control/error/protobuf/extension/broadcast/stream/cipher
+    pub kind: String,
+    pub description: String,
+    pub fields: Fields,
+    /// 从头部开始的完整数据包
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Verdict {
+    Pass,
+    /// 当前编译没有包含对应的功能，如未启用的加密算法
+    Skip(String),
+    Fail(String),
+}
+
+const CLIENT_IP: &str = "10.26.0.2";
+const PEER_IP: &str = "10.26.0.3";
+const GATEWAY_IP: &str = "10.26.0.1";
+/// 向量中的版本号固定，不随发布变化
+const VERSION: &str = "1.2.8";
+/// ip_turn中携带的ipv4数据(icmp echo)，内容原样转发，不做校验
+const IPV4_PAYLOAD: &str = "4500001c000040004001000a0a1a00020a1a00030800f7ff00000000";
+
+/// 头部字段，所有类型的向量共用。加密和扩展区标志由类型决定，不单独作为字段
+struct Header {
+    version: u8,
+    gateway: bool,
+    protocol: u8,
+    transport_protocol: u8,
+    source_ttl: u8,
+    ttl: u8,
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+}
+
+impl Header {
+    fn from_fields(fields: &Fields) -> Result<Header, String> {
+        Ok(Header {
+            version: parse(fields, "version")?,
+            gateway: parse(fields, "gateway")?,
+            protocol: parse(fields, "protocol")?,
+            transport_protocol: parse(fields, "transport_protocol")?,
+            source_ttl: parse(fields, "source_ttl")?,
+            ttl: parse(fields, "ttl")?,
+            source: parse(fields, "source")?,
+            destination: parse(fields, "destination")?,
+        })
+    }
+    fn read<B: AsRef<[u8]>>(packet: &NetPacket<B>) -> Header {
+        Header {
+            version: packet.version().into(),
+            gateway: packet.is_gateway(),
+            protocol: packet.protocol().into(),
+            transport_protocol: packet.transport_protocol(),
+            source_ttl: packet.source_ttl(),
+            ttl: packet.ttl(),
+            source: packet.source(),
+            destination: packet.destination(),
+        }
+    }
+    fn write<B: AsRef<[u8]> + AsMut<[u8]>>(&self, packet: &mut NetPacket<B>) {
+        packet.set_version(Version::from(self.version));
+        packet.set_gateway_flag(self.gateway);
+        packet.set_protocol(Protocol::from(self.protocol));
+        packet.set_transport_protocol(self.transport_protocol);
+        packet.set_source_ttl(self.source_ttl);
+        packet.set_ttl(self.ttl);
+        packet.set_source(self.source);
+        packet.set_destination(self.destination);
+    }
+    fn to_fields(&self, fields: &mut Fields) {
+        insert(fields, "version", self.version);
+        insert(fields, "gateway", self.gateway);
+        insert(fields, "protocol", self.protocol);
+        insert(fields, "transport_protocol", self.transport_protocol);
+        insert(fields, "source_ttl", self.source_ttl);
+        insert(fields, "ttl", self.ttl);
+        insert(fields, "source", self.source);
+        insert(fields, "destination", self.destination);
+    }
+}
+
+fn insert<T: ToString>(fields: &mut Fields, name: &str, value: T) {
+    fields.insert(name.to_string(), value.to_string());
+}
+
+fn get<'a>(fields: &'a Fields, name: &str) -> Result<&'a str, String> {
+    fields
+        .get(name)
+        .map(|v| v.as_str())
+        .ok_or_else(|| format!("missing field '{}'", name))
+}
+
+fn parse<T: std::str::FromStr>(fields: &Fields, name: &str) -> Result<T, String> {
+    get(fields, name)?
+        .parse()
+        .map_err(|_| format!("invalid field '{}'", name))
+}
+
+fn io_err(e: io::Error) -> String {
+    e.to_string()
+}
+
+pub fn to_hex(buf: &[u8]) -> String {
+    buf.iter().map(|v| format!("{:02x}", v)).collect()
+}
+
+pub fn from_hex(s: &str) -> Result<Vec<u8>, String> {
+    let s = s.trim();
+    if s.len() % 2 != 0 {
+        return Err(format!("invalid hex '{}'", s));
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| format!("invalid hex '{}'", s)))
+        .collect()
+}
+
+/// 数据体为protobuf的协议对应的消息，没有数据体或者数据体加密的协议为None
+pub fn message_name(protocol: u8, transport_protocol: u8) -> Option<&'static str> {
+    match Protocol::from(protocol) {
+        Protocol::Service => match service_packet::Protocol::from(transport_protocol) {
+            service_packet::Protocol::RegistrationRequest => Some("RegistrationRequest"),
+            service_packet::Protocol::RegistrationResponse => Some("RegistrationResponse"),
+            service_packet::Protocol::PushDeviceList => Some("DeviceList"),
+            service_packet::Protocol::HandshakeRequest => Some("HandshakeRequest"),
+            service_packet::Protocol::HandshakeResponse => Some("HandshakeResponse"),
+            service_packet::Protocol::SecretHandshakeRequest => Some("SecretHandshakeRequest"),
+            service_packet::Protocol::PushClientConfig => Some("ClientConfig"),
+            service_packet::Protocol::PushQuotaStatus => Some("QuotaStatus"),
+            service_packet::Protocol::ResumeRequest => Some("ResumeRequest"),
+            service_packet::Protocol::ResumeResponse => Some("ResumeResponse"),
+            service_packet::Protocol::PushMaintenance => Some("MaintenanceNotice"),
+            _ => None,
+        },
+        Protocol::Error => match error_packet::Protocol::from(transport_protocol) {
+            error_packet::Protocol::Maintenance => Some("MaintenanceNotice"),
+            _ => None,
+        },
+        Protocol::OtherTurn => match other_turn_packet::Protocol::from(transport_protocol) {
+            other_turn_packet::Protocol::Punch => Some("PunchInfo"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn descriptor(name: &str) -> Result<MessageDescriptor, String> {
+    crate::proto::message::file_descriptor()
+        .message_by_package_relative_name(name)
+        .ok_or_else(|| format!("unknown message '{}'", name))
+}
+
+/// protobuf文本格式编码为字节，字段按编号顺序输出
+fn text_to_bytes(name: &str, text: &str) -> Result<Vec<u8>, String> {
+    let mut message = descriptor(name)?.new_instance();
+    text_format::merge_from_str(&mut *message, text).map_err(|e| e.to_string())?;
+    message.write_to_bytes_dyn().map_err(|e| e.to_string())
+}
+
+fn bytes_to_text(name: &str, bytes: &[u8]) -> Result<String, String> {
+    let message = descriptor(name)?
+        .parse_from_bytes(bytes)
+        .map_err(|e| e.to_string())?;
+    Ok(text_format::print_to_string(&*message))
+}
+
+/// 文本格式的空白和字段顺序不影响比较
+fn text_eq(name: &str, a: &str, b: &str) -> bool {
+    let descriptor = match descriptor(name) {
+        Ok(descriptor) => descriptor,
+        Err(_) => return false,
+    };
+    let mut message_a = descriptor.new_instance();
+    let mut message_b = descriptor.new_instance();
+    text_format::merge_from_str(&mut *message_a, a).is_ok()
+        && text_format::merge_from_str(&mut *message_b, b).is_ok()
+        && descriptor.eq(&*message_a, &*message_b)
+}
+
+/// 扩展区字段的格式为 类型:值(hex)，多个用逗号分隔
+fn parse_extensions(s: &str) -> Result<Vec<(ExtensionId, Vec<u8>)>, String> {
+    let mut list = Vec::new();
+    for item in s.split(',').filter(|v| !v.trim().is_empty()) {
+        let (id, value) = item
+            .split_once(':')
+            .ok_or_else(|| format!("invalid extension '{}'", item))?;
+        let id: u8 = id
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid extension '{}'", item))?;
+        list.push((ExtensionId::from(id), from_hex(value)?));
+    }
+    Ok(list)
+}
+
+fn encode_body(kind: &str, header: &Header, fields: &Fields) -> Result<Vec<u8>, String> {
+    match kind {
+        "packet" | "extension" | "cipher" => from_hex(get(fields, "payload")?),
+        "control" => {
+            let mut buf = vec![0u8; 4];
+            let mut ping = PingPacket::new(&mut buf[..]).map_err(io_err)?;
+            ping.set_time(parse(fields, "time")?);
+            ping.set_epoch(parse(fields, "epoch")?);
+            Ok(buf)
+        }
+        "error" => {
+            if let Some(retry_after) = fields.get("retry_after") {
+                let retry_after: u32 = retry_after
+                    .parse()
+                    .map_err(|_| "invalid field 'retry_after'".to_string())?;
+                Ok(retry_after.to_be_bytes().to_vec())
+            } else if let Some(message) = fields.get("message") {
+                Ok(message.as_bytes().to_vec())
+            } else if let Some(text) = fields.get("text") {
+                text_to_bytes(get(fields, "message_type")?, text)
+            } else {
+                Ok(Vec::new())
+            }
+        }
+        "protobuf" => text_to_bytes(get(fields, "message_type")?, get(fields, "text")?),
+        "broadcast" => {
+            let addresses = get(fields, "addresses")?
+                .split(',')
+                .map(|v| v.trim().parse::<Ipv4Addr>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| "invalid field 'addresses'".to_string())?;
+            let data = from_hex(get(fields, "data")?)?;
+            let mut buf = vec![0u8; 1 + addresses.len() * 4 + data.len()];
+            let mut packet = BroadcastPacket::unchecked(&mut buf[..]);
+            packet.set_address(&addresses).map_err(io_err)?;
+            packet.set_data(&data).map_err(io_err)?;
+            Ok(buf)
+        }
+        "stream" => {
+            let payload = from_hex(get(fields, "payload")?)?;
+            let mut buf = vec![0u8; crate::protocol::stream_packet::HEAD_LEN + payload.len()];
+            let mut packet = StreamPacket::new(&mut buf[..]).map_err(io_err)?;
+            packet.set_source_port(parse(fields, "source_port")?);
+            packet.set_destination_port(parse(fields, "destination_port")?);
+            packet.set_seq(parse(fields, "seq")?);
+            packet.set_ack(parse(fields, "ack")?);
+            packet.set_flags(parse(fields, "flags")?);
+            packet.payload_mut().copy_from_slice(&payload);
+            Ok(buf)
+        }
+        _ => Err(format!(
+            "unknown kind '{}' (protocol {})",
+            kind, header.protocol
+        )),
+    }
+}
+
+fn decode_body(
+    kind: &str,
+    header: &Header,
+    body: &[u8],
+    fields: &mut Fields,
+) -> Result<(), String> {
+    match kind {
+        "packet" | "extension" | "cipher" => insert(fields, "payload", to_hex(body)),
+        "control" => {
+            let ping = PingPacket::new(body).map_err(io_err)?;
+            insert(fields, "time", ping.time());
+            insert(fields, "epoch", ping.epoch());
+        }
+        "error" => {
+            //旧版本服务端的错误包没有负载
+            if body.is_empty() {
+                return Ok(());
+            }
+            match InErrorPacket::new(header.transport_protocol, body).map_err(|e| e.to_string())? {
+                InErrorPacket::Disconnect(retry_after)
+                | InErrorPacket::RateLimited(retry_after)
+                | InErrorPacket::PunchRateLimited(retry_after) => {
+                    insert(fields, "retry_after", retry_after)
+                }
+                InErrorPacket::Maintenance(_) => {
+                    let name = "MaintenanceNotice";
+                    insert(fields, "message_type", name);
+                    insert(fields, "text", bytes_to_text(name, body)?);
+                }
+                InErrorPacket::OtherError(e) => {
+                    insert(fields, "message", e.message().map_err(|e| e.to_string())?)
+                }
+                _ => return Err("unexpected payload".to_string()),
+            }
+        }
+        "protobuf" => {
+            let name = message_name(header.protocol, header.transport_protocol)
+                .ok_or_else(|| "no protobuf body for this protocol".to_string())?;
+            insert(fields, "message_type", name);
+            insert(fields, "text", bytes_to_text(name, body)?);
+        }
+        "broadcast" => {
+            let packet = BroadcastPacket::new(body).map_err(io_err)?;
+            let addresses: Vec<String> = packet.addresses().iter().map(|v| v.to_string()).collect();
+            insert(fields, "addresses", addresses.join(","));
+            insert(fields, "data", to_hex(packet.data().map_err(io_err)?));
+        }
+        "stream" => {
+            let packet = StreamPacket::new(body).map_err(io_err)?;
+            insert(fields, "source_port", packet.source_port());
+            insert(fields, "destination_port", packet.destination_port());
+            insert(fields, "seq", packet.seq());
+            insert(fields, "ack", packet.ack());
+            insert(fields, "flags", packet.flags());
+            insert(fields, "payload", to_hex(packet.payload()));
+        }
+        _ => return Err(format!("unknown kind '{}'", kind)),
+    }
+    Ok(())
+}
+
+/// 由字段构造完整的数据包
+pub fn encode(kind: &str, fields: &Fields) -> Result<Vec<u8>, String> {
+    let header = Header::from_fields(fields)?;
+    let body = encode_body(kind, &header, fields)?;
+    let extensions = if kind == "extension" {
+        parse_extensions(get(fields, "extensions")?)?
+    } else {
+        Vec::new()
+    };
+    let extensions: Vec<(ExtensionId, &[u8])> = extensions
+        .iter()
+        .map(|(id, value)| (*id, value.as_slice()))
+        .collect();
+    let buf = vec![0u8; HEAD_LEN + body.len() + extension::encoded_len(&extensions)];
+    let mut packet = NetPacket::new0(HEAD_LEN + body.len(), buf).map_err(io_err)?;
+    header.write(&mut packet);
+    packet.set_payload(&body).map_err(io_err)?;
+    if !extensions.is_empty() {
+        extension::append(&mut packet, &extensions).map_err(io_err)?;
+    }
+    Ok(packet.buffer().to_vec())
+}
+
+/// 解析完整的数据包得到字段，和encode互逆
+pub fn decode(kind: &str, bytes: &[u8]) -> Result<Fields, String> {
+    let mut buf = bytes.to_vec();
+    let mut packet = NetPacket::new(&mut buf[..]).map_err(io_err)?;
+    let mut fields = Fields::new();
+    if kind == "extension" {
+        let extensions: Vec<String> = extension::take(&mut packet)
+            .map_err(io_err)?
+            .into_iter()
+            .map(|v| {
+                let id: u8 = v.id.into();
+                format!("{}:{}", id, to_hex(&v.value))
+            })
+            .collect();
+        insert(&mut fields, "extensions", extensions.join(","));
+    }
+    if packet.is_encrypt() {
+        return Err("unexpected encrypt flag".to_string());
+    }
+    let header = Header::read(&packet);
+    header.to_fields(&mut fields);
+    decode_body(kind, &header, packet.payload(), &mut fields)?;
+    Ok(fields)
+}
+
+/// 解码得到的字段和向量中的是否一致，protobuf文本按消息内容比较
+fn fields_eq(expect: &Fields, actual: &Fields) -> Result<(), String> {
+    if let Some(name) = expect.keys().find(|name| !actual.contains_key(*name)) {
+        return Err(format!("missing field '{}'", name));
+    }
+    for (name, value) in actual {
+        let expect_value = match expect.get(name) {
+            Some(v) => v,
+            None => return Err(format!("unexpected field '{}'", name)),
+        };
+        let eq = if name == "text" {
+            text_eq(get(actual, "message_type")?, expect_value, value)
+        } else {
+            expect_value == value
+        };
+        if !eq {
+            return Err(format!(
+                "field '{}' expect '{}' actual '{}'",
+                name, expect_value, value
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// 用当前实现验证向量：由字段编码得到相同的字节，解码字节得到相同的字段。
+/// 加密的密文带随机数，只验证解密
+pub fn check(vector: &Vector) -> Verdict {
+    if vector.kind == "cipher" {
+        return cipher::check(vector);
+    }
+    match encode(&vector.kind, &vector.fields) {
+        Ok(bytes) if bytes == vector.bytes => {}
+        Ok(bytes) => {
+            return Verdict::Fail(format!(
+                "encode expect {} actual {}",
+                to_hex(&vector.bytes),
+                to_hex(&bytes)
+            ))
+        }
+        Err(e) => return Verdict::Fail(format!("encode {}", e)),
+    }
+    let fields = match decode(&vector.kind, &vector.bytes) {
+        Ok(fields) => fields,
+        Err(e) => return Verdict::Fail(format!("decode {}", e)),
+    };
+    match fields_eq(&vector.fields, &fields) {
+        Ok(()) => Verdict::Pass,
+        Err(e) => Verdict::Fail(format!("decode {}", e)),
+    }
+}
+
+fn fields(list: &[(&str, &str)]) -> Fields {
+    list.iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+fn header(
+    gateway: bool,
+    protocol: Protocol,
+    transport_protocol: u8,
+    source: &str,
+    destination: &str,
+) -> Fields {
+    let protocol: u8 = protocol.into();
+    fields(&[
+        ("version", "1"),
+        ("gateway", &gateway.to_string()),
+        ("protocol", &protocol.to_string()),
+        ("transport_protocol", &transport_protocol.to_string()),
+        ("source_ttl", "15"),
+        ("ttl", "15"),
+        ("source", source),
+        ("destination", destination),
+    ])
+}
+
+/// 和服务端之间的包，带网关标志
+fn to_server(protocol: Protocol, transport_protocol: u8) -> Fields {
+    header(true, protocol, transport_protocol, CLIENT_IP, GATEWAY_IP)
+}
+
+fn from_server(protocol: Protocol, transport_protocol: u8) -> Fields {
+    header(true, protocol, transport_protocol, GATEWAY_IP, CLIENT_IP)
+}
+
+fn to_peer(protocol: Protocol, transport_protocol: u8) -> Fields {
+    header(false, protocol, transport_protocol, CLIENT_IP, PEER_IP)
+}
+
+struct Builder {
+    list: Vec<Vector>,
+}
+
+impl Builder {
+    fn add(
+        &mut self,
+        name: &str,
+        kind: &str,
+        description: &str,
+        mut fields: Fields,
+        extra: &[(&str, &str)],
+    ) -> Result<(), String> {
+        for (k, v) in extra {
+            insert(&mut fields, k, v);
+        }
+        let bytes = encode(kind, &fields).map_err(|e| format!("{}: {}", name, e))?;
+        self.list.push(Vector {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            description: description.to_string(),
+            fields,
+            bytes,
+        });
+        Ok(())
+    }
+    fn protobuf(
+        &mut self,
+        name: &str,
+        description: &str,
+        fields: Fields,
+        text: &str,
+    ) -> Result<(), String> {
+        let message_type = message_name(
+            parse(&fields, "protocol")?,
+            parse(&fields, "transport_protocol")?,
+        )
+        .ok_or_else(|| format!("{}: no protobuf body", name))?;
+        self.add(
+            name,
+            "protobuf",
+            description,
+            fields,
+            &[("message_type", message_type), ("text", text)],
+        )
+    }
+}
+
+/// 生成全部测试向量，加密向量只包含当前编译启用的算法
+pub fn generate() -> Result<Vec<Vector>, String> {
+    let mut b = Builder { list: Vec::new() };
+    let service = |p: service_packet::Protocol| -> u8 { p.into() };
+    let error = |p: error_packet::Protocol| -> u8 { p.into() };
+
+    // 握手和注册的完整过程，udp下先回传cookie再下发公钥
+    b.protobuf(
+        "handshake.1.request",
+        "client hello without cookie",
+        to_server(
+            Protocol::Service,
+            service(service_packet::Protocol::HandshakeRequest),
+        ),
+        &format!("version: \"{}\" secret: false", VERSION),
+    )?;
+    b.protobuf(
+        "handshake.2.cookie",
+        "server asks the client to echo a cookie and solve a proof of work",
+        from_server(
+            Protocol::Service,
+            service(service_packet::Protocol::HandshakeResponse),
+        ),
+        "cookie: \"\\001\\002\\003\\004\\005\\006\\007\\010\" pow_difficulty: 8",
+    )?;
+    b.protobuf(
+        "handshake.3.request",
+        "client hello echoing the cookie with the pow nonce",
+        to_server(
+            Protocol::Service,
+            service(service_packet::Protocol::HandshakeRequest),
+        ),
+        &format!(
+            "version: \"{}\" secret: false cookie: \"\\001\\002\\003\\004\\005\\006\\007\\010\" pow_nonce: 1234",
+            VERSION
+        ),
+    )?;
+    b.protobuf(
+        "handshake.4.response",
+        "server hello without server encryption",
+        from_server(
+            Protocol::Service,
+            service(service_packet::Protocol::HandshakeResponse),
+        ),
+        &format!(
+            "version: \"{}\" secret: false listen_addresses: \"vnt.example.com:443\"",
+            VERSION
+        ),
+    )?;
+    b.protobuf(
+        "handshake.5.register",
+        "registration request asking for 10.26.0.2",
+        to_server(
+            Protocol::Service,
+            service(service_packet::Protocol::RegistrationRequest),
+        ),
+        &format!(
+            "token: \"vnt-test\" device_id: \"device-a\" name: \"node-a\" version: \"{}\" \
+             virtual_ip: {} routes: \"192.168.10.0/24\"",
+            VERSION,
+            u32::from(Ipv4Addr::new(10, 26, 0, 2))
+        ),
+    )?;
+    b.protobuf(
+        "handshake.6.registered",
+        "registration response with one online peer",
+        from_server(
+            Protocol::Service,
+            service(service_packet::Protocol::RegistrationResponse),
+        ),
+        &format!(
+            "virtual_ip: {} virtual_gateway: {} virtual_netmask: {} epoch: 1 \
+             device_info_list {{ name: \"node-b\" virtual_ip: {} device_status: 0 }} \
+             public_ip: {} public_port: 40000 resume_ticket: \"ticket\" resume_ticket_ttl: 600",
+            u32::from(Ipv4Addr::new(10, 26, 0, 2)),
+            u32::from(Ipv4Addr::new(10, 26, 0, 1)),
+            u32::from(Ipv4Addr::new(255, 255, 255, 0)),
+            u32::from(Ipv4Addr::new(10, 26, 0, 3)),
+            u32::from(Ipv4Addr::new(203, 0, 113, 7)),
+        ),
+    )?;
+    b.protobuf(
+        "service.device_list",
+        "device list pushed after a peer goes offline",
+        from_server(
+            Protocol::Service,
+            service(service_packet::Protocol::PushDeviceList),
+        ),
+        &format!(
+            "epoch: 2 device_info_list {{ name: \"node-b\" virtual_ip: {} device_status: 1 }}",
+            u32::from(Ipv4Addr::new(10, 26, 0, 3))
+        ),
+    )?;
+    b.protobuf(
+        "service.quota",
+        "relay quota exceeded and throttled",
+        from_server(
+            Protocol::Service,
+            service(service_packet::Protocol::PushQuotaStatus),
+        ),
+        "used: 1073741824 limit: 1073741824 state: 1 rate_limit: 64 reset_time: 1767225600",
+    )?;
+    b.protobuf(
+        "service.maintenance",
+        "maintenance notice pushed to online devices",
+        from_server(
+            Protocol::Service,
+            service(service_packet::Protocol::PushMaintenance),
+        ),
+        "notice: \"upgrading\" retry_after: 120 drain_seconds: 60 end_time: 1767225600",
+    )?;
+    b.protobuf(
+        "punch.info",
+        "punch info sent to a peer through the server",
+        to_peer(
+            Protocol::OtherTurn,
+            other_turn_packet::Protocol::Punch.into(),
+        ),
+        &format!(
+            "public_ip_list: {} public_port: 40000 nat_type: Cone local_ip: {} local_port: 50000",
+            u32::from(Ipv4Addr::new(203, 0, 113, 7)),
+            u32::from(Ipv4Addr::new(192, 168, 1, 2)),
+        ),
+    )?;
+
+    // 控制包
+    b.add(
+        "control.ping",
+        "control",
+        "heartbeat to the server, time is milliseconds mod 65536",
+        to_server(
+            Protocol::Control,
+            crate::protocol::control_packet::Protocol::Ping.into(),
+        ),
+        &[("time", "1234"), ("epoch", "7")],
+    )?;
+    b.add(
+        "control.pong",
+        "control",
+        "heartbeat reply from a peer",
+        header(
+            false,
+            Protocol::Control,
+            crate::protocol::control_packet::Protocol::Pong.into(),
+            PEER_IP,
+            CLIENT_IP,
+        ),
+        &[("time", "1234"), ("epoch", "0")],
+    )?;
+
+    // 错误包
+    b.add(
+        "error.token",
+        "error",
+        "token rejected, no payload",
+        from_server(Protocol::Error, error(error_packet::Protocol::TokenError)),
+        &[],
+    )?;
+    b.add(
+        "error.disconnect",
+        "error",
+        "disconnect with a 30s re-register hint",
+        from_server(Protocol::Error, error(error_packet::Protocol::Disconnect)),
+        &[("retry_after", "30")],
+    )?;
+    b.add(
+        "error.rate_limited",
+        "error",
+        "registration rate limited",
+        from_server(Protocol::Error, error(error_packet::Protocol::RateLimited)),
+        &[("retry_after", "60")],
+    )?;
+    b.add(
+        "error.maintenance",
+        "error",
+        "registration refused during maintenance",
+        from_server(Protocol::Error, error(error_packet::Protocol::Maintenance)),
+        &[
+            ("message_type", "MaintenanceNotice"),
+            ("text", "notice: \"upgrading\" retry_after: 120"),
+        ],
+    )?;
+    b.add(
+        "error.other",
+        "error",
+        "free-form utf-8 message",
+        from_server(Protocol::Error, error(error_packet::Protocol::Other(100))),
+        &[("message", "server busy")],
+    )?;
+
+    // 转发的数据
+    b.add(
+        "ip_turn.ipv4",
+        "packet",
+        "ipv4 packet to a peer, ttl decremented once by a relay",
+        to_peer(
+            Protocol::IpTurn,
+            crate::protocol::ip_turn_packet::Protocol::Ipv4.into(),
+        ),
+        &[("payload", IPV4_PAYLOAD), ("ttl", "14")],
+    )?;
+    b.add(
+        "ip_turn.broadcast",
+        "broadcast",
+        "broadcast that has already been delivered to two peers",
+        header(
+            false,
+            Protocol::IpTurn,
+            crate::protocol::ip_turn_packet::Protocol::Ipv4Broadcast.into(),
+            CLIENT_IP,
+            "255.255.255.255",
+        ),
+        &[("addresses", "10.26.0.3,10.26.0.4"), ("data", IPV4_PAYLOAD)],
+    )?;
+    b.add(
+        "extension.timestamp",
+        "extension",
+        "ipv4 packet with a send timestamp extension",
+        to_peer(
+            Protocol::IpTurn,
+            crate::protocol::ip_turn_packet::Protocol::Ipv4.into(),
+        ),
+        &[
+            ("payload", IPV4_PAYLOAD),
+            ("extensions", "1:0000019a2f4e5c00"),
+        ],
+    )?;
+    b.add(
+        "stream.syn",
+        "stream",
+        "stream connection request",
+        to_peer(
+            Protocol::OtherTurn,
+            other_turn_packet::Protocol::Stream.into(),
+        ),
+        &[
+            ("source_port", "40001"),
+            ("destination_port", "22"),
+            ("seq", "1000"),
+            ("ack", "0"),
+            ("flags", &crate::protocol::stream_packet::SYN.to_string()),
+            ("payload", ""),
+        ],
+    )?;
+    cipher::generate(&mut b)?;
+    Ok(b.list)
+}
+
+#[cfg(any(
+    feature = "aes_gcm",
+    feature = "server_encrypt",
+    feature = "aes_cbc",
+    feature = "aes_ecb",
+    feature = "sm4_cbc",
+    feature = "chacha20_poly1305"
+))]
+mod cipher {
+    use std::str::FromStr;
+
+    use crate::cipher::{Cipher, CipherModel};
+    use crate::protocol::body::ENCRYPTION_RESERVED;
+    use crate::protocol::NetPacket;
+
+    use super::*;
+
+    /// 当前编译启用的算法
+    const MODELS: &[&str] = &[
+        #[cfg(any(feature = "aes_gcm", feature = "server_encrypt"))]
+        "aes_gcm",
+        #[cfg(feature = "aes_cbc")]
+        "aes_cbc",
+        #[cfg(feature = "aes_ecb")]
+        "aes_ecb",
+        #[cfg(feature = "sm4_cbc")]
+        "sm4_cbc",
+        #[cfg(feature = "chacha20_poly1305")]
+        "chacha20_poly1305",
+    ];
+
+    fn new_cipher(fields: &Fields) -> Result<Cipher, String> {
+        let model = CipherModel::from_str(get(fields, "model")?)?;
+        Ok(Cipher::new_password(
+            model,
+            Some(get(fields, "password")?.to_string()),
+            Some(get(fields, "token")?.to_string()),
+        ))
+    }
+
+    pub(super) fn generate(b: &mut Builder) -> Result<(), String> {
+        for model in MODELS {
+            // 密码不足8位时部分算法使用128位密钥
+            for password in ["pass", "password"] {
+                let mut fields = to_peer(
+                    Protocol::IpTurn,
+                    crate::protocol::ip_turn_packet::Protocol::Ipv4.into(),
+                );
+                insert(&mut fields, "model", model);
+                insert(&mut fields, "password", password);
+                insert(&mut fields, "token", "vnt-test");
+                insert(&mut fields, "payload", IPV4_PAYLOAD);
+                let cipher = new_cipher(&fields)?;
+                if let Some(key) = cipher.key() {
+                    insert(&mut fields, "key", to_hex(key));
+                }
+                let plain = encode("cipher", &fields)?;
+                let mut buf = vec![0u8; plain.len() + ENCRYPTION_RESERVED];
+                buf[..plain.len()].copy_from_slice(&plain);
+                let mut packet = NetPacket::new_encrypt(buf).map_err(io_err)?;
+                cipher.encrypt_ipv4(&mut packet).map_err(io_err)?;
+                b.list.push(Vector {
+                    name: format!(
+                        "cipher.{}.{}",
+                        model,
+                        if password.len() < 8 { "short" } else { "long" }
+                    ),
+                    kind: "cipher".to_string(),
+                    description: format!(
+                        "{} with key sha256(password), finger from the token; \
+                         ciphertext is randomized, only decryption is checked",
+                        model
+                    ),
+                    fields,
+                    bytes: packet.buffer().to_vec(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn check(vector: &Vector) -> Verdict {
+        let model = match vector.fields.get("model") {
+            Some(model) => model,
+            None => return Verdict::Fail("missing field 'model'".to_string()),
+        };
+        if !MODELS.contains(&model.as_str()) {
+            return Verdict::Skip(format!("{} not enabled in this build", model));
+        }
+        let cipher = match new_cipher(&vector.fields) {
+            Ok(cipher) => cipher,
+            Err(e) => return Verdict::Fail(e),
+        };
+        if let (Some(key), Some(expect)) = (cipher.key(), vector.fields.get("key")) {
+            if &to_hex(key) != expect {
+                return Verdict::Fail(format!("key expect {} actual {}", expect, to_hex(key)));
+            }
+        }
+        let plain = match encode("cipher", &vector.fields) {
+            Ok(plain) => plain,
+            Err(e) => return Verdict::Fail(format!("encode {}", e)),
+        };
+        let mut buf = vector.bytes.clone();
+        let mut packet = match NetPacket::new(&mut buf[..]) {
+            Ok(packet) => packet,
+            Err(e) => return Verdict::Fail(e.to_string()),
+        };
+        if let Err(e) = cipher.decrypt_ipv4(&mut packet) {
+            return Verdict::Fail(format!("decrypt {}", e));
+        }
+        if packet.buffer() != plain.as_slice() {
+            return Verdict::Fail(format!(
+                "decrypt expect {} actual {}",
+                to_hex(&plain),
+                to_hex(packet.buffer())
+            ));
+        }
+        Verdict::Pass
+    }
+}
+
+#[cfg(not(any(
+    feature = "aes_gcm",
+    feature = "server_encrypt",
+    feature = "aes_cbc",
+    feature = "aes_ecb",
+    feature = "sm4_cbc",
+    feature = "chacha20_poly1305"
+)))]
+mod cipher {
+    use super::*;
+
+    pub(super) fn generate(_b: &mut Builder) -> Result<(), String> {
+        Ok(())
+    }
+
+    pub(super) fn check(_vector: &Vector) -> Verdict {
+        Verdict::Skip("encryption not enabled in this build".to_string())
+    }
+}
+
+#[test]
+fn test_vectors() {
+    let vectors = generate().unwrap();
+    let mut names = std::collections::HashSet::new();
+    for vector in &vectors {
+        assert!(names.insert(&vector.name), "duplicate {}", vector.name);
+        if let Verdict::Fail(e) = check(vector) {
+            panic!("{} {}", vector.name, e);
+        }
+    }
+}
+
+#[test]
+fn test_vectors_tampered() {
+    for mut vector in generate().unwrap() {
+        if vector.kind == "cipher" {
+            continue;
+        }
+        let last = vector.bytes.len() - 1;
+        vector.bytes[last] ^= 0x01;
+        assert!(
+            matches!(check(&vector), Verdict::Fail(_)),
+            "{}",
+            vector.name
+        );
+    }
+    let mut vector = generate()
+        .unwrap()
+        .into_iter()
+        .find(|v| v.name == "control.ping")
+        .unwrap();
+    insert(&mut vector.fields, "source", "10.26.0.9");
+    assert!(matches!(check(&vector), Verdict::Fail(_)));
+}
+
+/// 固定部分向量的字节，改动导致线上格式变化时这里会失败
+#[test]
+fn test_vectors_golden() {
+    let golden = [
+        ("control.ping", "510301ff0a1a00020a1a000104d20007"),
+        ("control.pong", "010302ff0a1a00030a1a000204d20000"),
+        ("error.token", "510201ff0a1a00010a1a0002"),
+        ("error.disconnect", "510202ff0a1a00010a1a00020000001e"),
+        ("error.rate_limited", "510208ff0a1a00010a1a00020000003c"),
+        (
+            "error.other",
+            "510264ff0a1a00010a1a00027365727665722062757379",
+        ),
+        (
+            "ip_turn.ipv4",
+            "010404fe0a1a00020a1a00034500001c000040004001000a0a1a00020a1a00030800f7ff00000000",
+        ),
+        (
+            "ip_turn.broadcast",
+            "0104c9ff0a1a0002ffffffff020a1a00030a1a00044500001c000040004001000a0a1a00020a1a00030800f7ff00000000",
+        ),
+        (
+            "extension.timestamp",
+            "210404ff0a1a00020a1a00034500001c000040004001000a0a1a00020a1a00030800f7ff0000000001080000019a2f4e5c00000c",
+        ),
+        (
+            "stream.syn",
+            "010502ff0a1a00020a1a00039c410016000003e80000000001000000",
+        ),
+        (
+            "handshake.1.request",
+            "510105ff0a1a00020a1a00010a05312e322e38",
+        ),
+        (
+            "handshake.2.cookie",
+            "510106ff0a1a00010a1a00022a0801020304050607083008",
+        ),
+        (
+            "handshake.3.request",
+            "510105ff0a1a00020a1a00010a05312e322e381a08010203040506070820d209",
+        ),
+        (
+            "punch.info",
+            "010501ff0a1a00020a1a00031204077100cb18c0b80228013d0201a8c040d08603",
+        ),
+    ];
+    let vectors = generate().unwrap();
+    for (name, hex) in golden {
+        let vector = vectors.iter().find(|v| v.name == name).unwrap();
+        assert_eq!(to_hex(&vector.bytes), hex, "{}", name);
+    }
+}
@@ -0,0 +1,421 @@
+//! 生成wireshark的lua dissector，协议号和消息名从代码中读取，和当前版本的协议保持一致。
+//! 加密的数据包需要cipher::keylog导出的密钥和luagcrypt模块才能解密，目前只支持aes_gcm
+
+use std::fmt::Write;
+
+use crate::protocol::doc::defined;
+use crate::protocol::extension::ExtensionId;
+use crate::protocol::vectors::message_name;
+use crate::protocol::{
+    control_packet, error_packet, ip_turn_packet, other_turn_packet, service_packet, stream_packet,
+    Protocol, HEAD_LEN,
+};
+use crate::util::resolve::DEFAULT_PORT;
+
+const TEMPLATE: &str = r#"-- vnt dissector, generated by vnt-cli --debug-dissector (vnt {version})
+-- Copy to the Wireshark personal plugins folder (Help > About > Folders) and restart.
+-- Preferences > Protocols > VNT: server port and the key log written by vnt-cli --debug-export-keys.
+-- Decryption needs the luagcrypt module (aes_gcm only); protobuf bodies need vnt/proto in
+-- Preferences > Protocols > Protobuf > search paths.
+
+local vnt = Proto("vnt", "VNT")
+
+local HEAD_LEN = {head_len}
+local STREAM_HEAD_LEN = {stream_head_len}
+
+local protocols = {protocols}
+local transports = {
+    [1] = {service},
+    [2] = {error},
+    [3] = {control},
+    [4] = {ip_turn},
+    [5] = {other_turn},
+}
+local messages = {messages}
+local extensions = {extensions}
+
+local f = vnt.fields
+f.encrypted = ProtoField.bool("vnt.encrypted", "Encrypted", 8, nil, 0x80)
+f.gateway = ProtoField.bool("vnt.gateway", "Server", 8, nil, 0x40)
+f.extension = ProtoField.bool("vnt.extension", "Extension", 8, nil, 0x20)
+f.version = ProtoField.uint8("vnt.version", "Version", base.DEC, nil, 0x0F)
+f.protocol = ProtoField.uint8("vnt.protocol", "Protocol", base.DEC, protocols)
+f.transport = ProtoField.uint8("vnt.transport", "Transport protocol", base.DEC)
+f.source_ttl = ProtoField.uint8("vnt.source_ttl", "Initial TTL", base.DEC, nil, 0xF0)
+f.ttl = ProtoField.uint8("vnt.ttl", "TTL", base.DEC, nil, 0x0F)
+f.src = ProtoField.ipv4("vnt.src", "Source")
+f.dst = ProtoField.ipv4("vnt.dst", "Destination")
+f.body = ProtoField.bytes("vnt.body", "Body")
+f.tag = ProtoField.bytes("vnt.tag", "Tag")
+f.finger = ProtoField.bytes("vnt.finger", "Finger")
+f.key = ProtoField.string("vnt.key", "Decrypted with")
+f.time = ProtoField.uint16("vnt.ping.time", "Time")
+f.epoch = ProtoField.uint16("vnt.ping.epoch", "Device list epoch")
+f.addr = ProtoField.ipv4("vnt.addr", "Address")
+f.port = ProtoField.uint16("vnt.port", "Port")
+f.retry_after = ProtoField.uint32("vnt.retry_after", "Retry after (s)")
+f.message = ProtoField.string("vnt.message", "Message")
+f.delivered = ProtoField.ipv4("vnt.broadcast.delivered", "Delivered to")
+f.stream_src = ProtoField.uint16("vnt.stream.srcport", "Source port")
+f.stream_dst = ProtoField.uint16("vnt.stream.dstport", "Destination port")
+f.stream_seq = ProtoField.uint32("vnt.stream.seq", "Seq")
+f.stream_ack = ProtoField.uint32("vnt.stream.ack", "Ack")
+f.stream_flags = ProtoField.uint8("vnt.stream.flags", "Flags", base.HEX)
+f.ext_type = ProtoField.uint8("vnt.ext.type", "Type", base.DEC, extensions)
+f.ext_value = ProtoField.bytes("vnt.ext.value", "Value")
+
+vnt.prefs.port = Pref.uint("Server port", {port}, "udp and tcp port of the vnt server")
+vnt.prefs.keylog = Pref.string("Key log file", "", "file written by vnt-cli --debug-export-keys")
+
+local has_gcrypt, gcrypt = pcall(require, "luagcrypt")
+
+local function dissector(name)
+    local ok, d = pcall(Dissector.get, name)
+    if ok then
+        return d
+    end
+end
+
+local keys
+local keys_path
+local function load_keys()
+    if keys and keys_path == vnt.prefs.keylog then
+        return keys
+    end
+    keys = { CLIENT = {}, SERVER = {} }
+    keys_path = vnt.prefs.keylog
+    local file = keys_path ~= "" and io.open(keys_path, "r")
+    if not file then
+        return keys
+    end
+    for line in file:lines() do
+        local label, model, hex = line:match("^(%u+)%s+(%S+)%s+(%x+)")
+        if label and keys[label] then
+            local key = hex:gsub("..", function(c)
+                return string.char(tonumber(c, 16))
+            end)
+            table.insert(keys[label], { model = model, key = key, hex = hex })
+        end
+    end
+    file:close()
+    return keys
+end
+
+local function gcm_decrypt(key, nonce, body, tag)
+    local algo = #key == 16 and gcrypt.CIPHER_AES128 or gcrypt.CIPHER_AES256
+    local cipher = gcrypt.Cipher(algo, gcrypt.CIPHER_MODE_GCM)
+    cipher:setkey(key)
+    cipher:setiv(nonce)
+    local plain = cipher:decrypt(body)
+    if pcall(cipher.checktag, cipher, tag) then
+        return plain
+    end
+end
+
+-- body | random(4) | tag(16) | finger(12, optional), random is encrypted with the body
+local function decrypt(tvb, gateway, tree)
+    if not has_gcrypt then
+        return nil, "luagcrypt not available"
+    end
+    local list = load_keys()[gateway and "SERVER" or "CLIENT"]
+    if #list == 0 then
+        return nil, "no key"
+    end
+    local nonce = tvb(4, 8):raw() .. string.char(tvb(1, 1):uint(), tvb(2, 1):uint(),
+        gateway and 1 or 0, bit.rshift(tvb(3, 1):uint(), 4))
+    local payload = tvb(HEAD_LEN):raw()
+    for _, entry in ipairs(list) do
+        if entry.model == "aes_gcm" then
+            for _, finger in ipairs({ 12, 0 }) do
+                local len = #payload - 16 - finger
+                if len >= 4 then
+                    local plain = gcm_decrypt(entry.key, nonce, payload:sub(1, len),
+                        payload:sub(len + 1, len + 16))
+                    if plain then
+                        tree:add(f.tag, tvb(HEAD_LEN + len, 16))
+                        if finger > 0 then
+                            tree:add(f.finger, tvb(HEAD_LEN + len + 16, finger))
+                        end
+                        tree:add(f.key, entry.hex:sub(1, 8) .. "...")
+                        return ByteArray.new(plain:sub(1, len - 4), true):tvb("Decrypted")
+                    end
+                end
+            end
+        end
+    end
+    return nil, "decryption failed"
+end
+
+-- the extension area is at the end of the body, its last two bytes are the total length
+local function take_extensions(body, tree)
+    local len = body:len()
+    if len < 2 then
+        return body
+    end
+    local ext_len = body(len - 2, 2):uint()
+    if ext_len < 2 or ext_len > len then
+        return body
+    end
+    local subtree = tree:add(vnt, body(len - ext_len, ext_len), "Extensions")
+    local offset = len - ext_len
+    while offset + 2 <= len - 2 do
+        local value_len = body(offset + 1, 1):uint()
+        local item = subtree:add(f.ext_type, body(offset, 1))
+        if value_len > 0 and offset + 2 + value_len <= len - 2 then
+            item:add(f.ext_value, body(offset + 2, value_len))
+        end
+        offset = offset + 2 + value_len
+    end
+    if len == ext_len then
+        return nil
+    end
+    return body(0, len - ext_len):tvb()
+end
+
+local function dissect_body(body, protocol, transport, pinfo, tree)
+    local name = messages[protocol] and messages[protocol][transport]
+    if name then
+        local protobuf = dissector("protobuf")
+        if protobuf and body:len() > 0 then
+            pinfo.private["pb_msg_type"] = "message," .. name
+            protobuf:call(body, pinfo, tree)
+            return
+        end
+    elseif protocol == 2 then
+        if transport == {disconnect} or transport == {rate_limited} or transport == {punch_rate_limited} then
+            if body:len() >= 4 then
+                tree:add(f.retry_after, body(0, 4))
+            end
+            return
+        elseif transport > {last_error} and body:len() > 0 then
+            tree:add(f.message, body(), body():string())
+            return
+        end
+    elseif protocol == 3 then
+        if (transport == {ping} or transport == {pong}) and body:len() >= 4 then
+            tree:add(f.time, body(0, 2))
+            tree:add(f.epoch, body(2, 2))
+            return
+        elseif transport == {addr_response} and body:len() >= 6 then
+            tree:add(f.addr, body(0, 4))
+            tree:add(f.port, body(4, 2))
+            return
+        end
+    elseif protocol == 4 then
+        if transport == {ipv4} then
+            dissector("ip"):call(body, pinfo, tree)
+            return
+        elseif transport == {ipv4_broadcast} and body:len() >= 1 then
+            local n = body(0, 1):uint()
+            if body:len() >= 1 + n * 4 then
+                for i = 0, n - 1 do
+                    tree:add(f.delivered, body(1 + i * 4, 4))
+                end
+                dissector("ip"):call(body(1 + n * 4):tvb(), pinfo, tree)
+                return
+            end
+        elseif transport == {ethernet} then
+            dissector("eth_withoutfcs"):call(body, pinfo, tree)
+            return
+        end
+    elseif protocol == 5 and transport == {stream} and body:len() >= STREAM_HEAD_LEN then
+        tree:add(f.stream_src, body(0, 2))
+        tree:add(f.stream_dst, body(2, 2))
+        tree:add(f.stream_seq, body(4, 4))
+        tree:add(f.stream_ack, body(8, 4))
+        tree:add(f.stream_flags, body(12, 1))
+        if body:len() > STREAM_HEAD_LEN then
+            tree:add(f.body, body(STREAM_HEAD_LEN))
+        end
+        return
+    end
+    if body:len() > 0 then
+        tree:add(f.body, body())
+    end
+end
+
+local function dissect_packet(tvb, pinfo, root)
+    if tvb:len() < HEAD_LEN then
+        return 0
+    end
+    pinfo.cols.protocol = "VNT"
+    local tree = root:add(vnt, tvb())
+    local flags = tvb(0, 1):uint()
+    local encrypted = bit.band(flags, 0x80) ~= 0
+    local gateway = bit.band(flags, 0x40) ~= 0
+    local protocol = tvb(1, 1):uint()
+    local transport = tvb(2, 1):uint()
+    tree:add(f.encrypted, tvb(0, 1))
+    tree:add(f.gateway, tvb(0, 1))
+    tree:add(f.extension, tvb(0, 1))
+    tree:add(f.version, tvb(0, 1))
+    tree:add(f.protocol, tvb(1, 1))
+    local names = transports[protocol] or {}
+    tree:add(f.transport, tvb(2, 1)):append_text(" (" .. (names[transport] or "Unknown") .. ")")
+    tree:add(f.source_ttl, tvb(3, 1))
+    tree:add(f.ttl, tvb(3, 1))
+    tree:add(f.src, tvb(4, 4))
+    tree:add(f.dst, tvb(8, 4))
+    pinfo.cols.info = string.format("%s %s %s -> %s", protocols[protocol] or protocol,
+        names[transport] or transport, tostring(tvb(4, 4):ipv4()), tostring(tvb(8, 4):ipv4()))
+    local body = tvb:len() > HEAD_LEN and tvb(HEAD_LEN):tvb() or nil
+    if body and encrypted then
+        local plain, err = decrypt(tvb, gateway, tree)
+        if not plain then
+            tree:add(f.body, tvb(HEAD_LEN)):append_text(" (encrypted, " .. err .. ")")
+            return tvb:len()
+        end
+        body = plain
+    end
+    if body and bit.band(flags, 0x20) ~= 0 then
+        body = take_extensions(body, tree)
+    end
+    if body then
+        dissect_body(body, protocol, transport, pinfo, tree)
+    end
+    return tvb:len()
+end
+
+-- tcp adds a 4-byte prefix, bytes 2-3 are the packet length
+local function tcp_len(tvb, pinfo, offset)
+    return tvb(offset + 2, 2):uint() + 4
+end
+
+local function dissect_tcp_pdu(tvb, pinfo, root)
+    return dissect_packet(tvb(4):tvb(), pinfo, root) + 4
+end
+
+function vnt.dissector(tvb, pinfo, root)
+    -- 2 = PT_TCP
+    if pinfo.port_type == 2 then
+        dissect_tcp_pdus(tvb, root, 4, tcp_len, dissect_tcp_pdu)
+        return tvb:len()
+    end
+    return dissect_packet(tvb, pinfo, root)
+end
+
+local registered
+local function register()
+    if registered then
+        DissectorTable.get("udp.port"):remove(registered, vnt)
+        DissectorTable.get("tcp.port"):remove(registered, vnt)
+    end
+    registered = vnt.prefs.port
+    DissectorTable.get("udp.port"):add(registered, vnt)
+    DissectorTable.get("tcp.port"):add(registered, vnt)
+end
+
+function vnt.prefs_changed()
+    keys = nil
+    register()
+end
+
+register()
+"#;
+
+/// lua的表，如 { [1] = "Service", [2] = "Error" }
+fn lua_table(rows: &[(u8, String)]) -> String {
+    let items: Vec<String> = rows
+        .iter()
+        .map(|(value, name)| format!("[{}] = \"{}\"", value, name))
+        .collect();
+    format!("{{ {} }}", items.join(", "))
+}
+
+/// 数据体为protobuf的消息名
+fn messages() -> String {
+    let mut out = String::from("{\n");
+    for protocol in 1..=5u8 {
+        let rows: Vec<(u8, String)> = (0..=255u8)
+            .filter_map(|tp| message_name(protocol, tp).map(|name| (tp, name.to_string())))
+            .collect();
+        if !rows.is_empty() {
+            let _ = writeln!(out, "    [{}] = {},", protocol, lua_table(&rows));
+        }
+    }
+    out.push('}');
+    out
+}
+
+fn num<T: Into<u8>>(value: T) -> String {
+    value.into().to_string()
+}
+
+pub fn dissector() -> String {
+    let last_error =
+        defined::<error_packet::Protocol>(|v| matches!(v, error_packet::Protocol::Other(_)))
+            .last()
+            .map_or(0, |(value, _)| *value);
+    let replacements = [
+        ("{version}", crate::VNT_VERSION.to_string()),
+        ("{head_len}", HEAD_LEN.to_string()),
+        ("{stream_head_len}", stream_packet::HEAD_LEN.to_string()),
+        ("{port}", DEFAULT_PORT.to_string()),
+        (
+            "{protocols}",
+            lua_table(&defined::<Protocol>(|v| matches!(v, Protocol::UnKnow(_)))),
+        ),
+        (
+            "{service}",
+            lua_table(&defined::<service_packet::Protocol>(|v| {
+                matches!(v, service_packet::Protocol::Unknown(_))
+            })),
+        ),
+        (
+            "{error}",
+            lua_table(&defined::<error_packet::Protocol>(|v| {
+                matches!(v, error_packet::Protocol::Other(_))
+            })),
+        ),
+        (
+            "{control}",
+            lua_table(&defined::<control_packet::Protocol>(|v| {
+                matches!(v, control_packet::Protocol::Unknown(_))
+            })),
+        ),
+        (
+            "{ip_turn}",
+            lua_table(&defined::<ip_turn_packet::Protocol>(|v| {
+                matches!(v, ip_turn_packet::Protocol::Unknown(_))
+            })),
+        ),
+        (
+            "{other_turn}",
+            lua_table(&defined::<other_turn_packet::Protocol>(|v| {
+                matches!(v, other_turn_packet::Protocol::Unknown(_))
+            })),
+        ),
+        ("{messages}", messages()),
+        (
+            "{extensions}",
+            lua_table(&defined::<ExtensionId>(|v| {
+                matches!(v, ExtensionId::Unknown(_))
+            })),
+        ),
+        ("{disconnect}", num(error_packet::Protocol::Disconnect)),
+        ("{rate_limited}", num(error_packet::Protocol::RateLimited)),
+        (
+            "{punch_rate_limited}",
+            num(error_packet::Protocol::PunchRateLimited),
+        ),
+        ("{last_error}", last_error.to_string()),
+        ("{ping}", num(control_packet::Protocol::Ping)),
+        ("{pong}", num(control_packet::Protocol::Pong)),
+        (
+            "{addr_response}",
+            num(control_packet::Protocol::AddrResponse),
+        ),
+        ("{ipv4}", num(ip_turn_packet::Protocol::Ipv4)),
+        (
+            "{ipv4_broadcast}",
+            num(ip_turn_packet::Protocol::Ipv4Broadcast),
+        ),
+        ("{ethernet}", num(ip_turn_packet::Protocol::Ethernet)),
+        ("{stream}", num(other_turn_packet::Protocol::Stream)),
+    ];
+    let mut out = TEMPLATE.to_string();
+    for (key, value) in replacements {
+        out = out.replace(key, &value);
+    }
+    out
+}
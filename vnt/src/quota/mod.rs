@@ -0,0 +1,102 @@
+use std::fmt;
+
+use crate::proto::message::QuotaStatus;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum QuotaState {
+    Normal,
+    /// 超出配额，中转数据被限速
+    Throttled,
+    /// 超出配额，服务端停止中转，只能使用p2p
+    Exceeded,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum QuotaScope {
+    /// 按设备统计
+    Device,
+    /// 按token统计，同一组网的设备共用配额
+    Token,
+}
+
+/// 服务端推送的中转流量配额，计数由服务端持久化，重启后不会清零
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Quota {
+    pub used: u64,
+    /// 0表示不限制
+    pub limit: u64,
+    pub state: QuotaState,
+    /// 限速时的速率(KB/s)
+    pub rate_limit: u32,
+    /// 配额重置时间(unix秒)，0表示不重置
+    pub reset_time: u32,
+    pub scope: QuotaScope,
+}
+
+impl Quota {
+    pub fn from_proto(status: &QuotaStatus) -> Quota {
+        Quota {
+            used: status.used,
+            limit: status.limit,
+            state: match status.state {
+                1 => QuotaState::Throttled,
+                2 => QuotaState::Exceeded,
+                _ => QuotaState::Normal,
+            },
+            rate_limit: status.rate_limit,
+            reset_time: status.reset_time,
+            scope: if status.scope == 1 {
+                QuotaScope::Token
+            } else {
+                QuotaScope::Device
+            },
+        }
+    }
+    pub fn is_limited(&self) -> bool {
+        self.state != QuotaState::Normal
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}{}", bytes, UNITS[0])
+    } else {
+        format!("{:.2}{}", value, UNITS[unit])
+    }
+}
+
+impl fmt::Display for Quota {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scope = match self.scope {
+            QuotaScope::Device => "device",
+            QuotaScope::Token => "token",
+        };
+        if self.limit == 0 {
+            write!(f, "{} used({})", format_bytes(self.used), scope)?;
+        } else {
+            write!(
+                f,
+                "{}/{}({})",
+                format_bytes(self.used),
+                format_bytes(self.limit),
+                scope
+            )?;
+        }
+        match self.state {
+            QuotaState::Normal => {}
+            QuotaState::Throttled => write!(f, " throttled {}KB/s", self.rate_limit)?,
+            QuotaState::Exceeded => write!(f, " exceeded")?,
+        }
+        if self.reset_time != 0 {
+            write!(f, " reset_time={}", self.reset_time)?;
+        }
+        Ok(())
+    }
+}
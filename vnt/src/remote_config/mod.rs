@@ -0,0 +1,317 @@
+use std::io;
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+
+use crossbeam_utils::atomic::AtomicCell;
+use parking_lot::Mutex;
+
+use crate::external_route::ExternalRoute;
+use crate::handle::{PeerDeviceInfo, PeerDeviceStatus};
+use crate::proto::message::ClientConfig;
+use crate::route_conflict;
+use crate::route_conflict::{contains, RouteConflictPolicy};
+use crate::split_dns::SplitDns;
+use crate::tun_tap_device::DeviceWriter;
+
+/// 默认和服务端的心跳间隔(秒)
+pub const DEFAULT_KEEPALIVE: u32 = 3;
+
+/// 服务端下发的客户端配置，注册时和配置变化时推送
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RemoteConfig {
+    pub version: u32,
+    /// 目标，掩码，网关，和in_ips一致
+    pub in_ips: Vec<(u32, u32, Ipv4Addr)>,
+    pub dns_server: Option<Ipv4Addr>,
+    pub dns_domains: Vec<String>,
+    /// 和服务端的心跳间隔(秒)，0表示使用默认值
+    pub keepalive: u32,
+}
+
+impl RemoteConfig {
+    /// 有任何一项不合法时拒绝整个配置，不应用其中的一部分
+    pub fn from_proto(config: &ClientConfig) -> io::Result<RemoteConfig> {
+        let mut in_ips = Vec::with_capacity(config.in_ips.len());
+        for x in &config.in_ips {
+            match parse_in_ip(x) {
+                Ok(v) => in_ips.push(v),
+                Err(e) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("in_ips {:?} {}", x, e),
+                    ))
+                }
+            }
+        }
+        let config = RemoteConfig {
+            version: config.version,
+            in_ips,
+            dns_server: if config.dns_server == 0 {
+                None
+            } else {
+                Some(Ipv4Addr::from(config.dns_server))
+            },
+            dns_domains: config.dns_domains.clone(),
+            keepalive: config.keepalive,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+    /// 和本地配置使用相同的规则检查(见Config::new)
+    pub fn validate(&self) -> io::Result<()> {
+        let invalid = |msg: String| Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+        for (dest, mask, _) in &self.in_ips {
+            if mask.leading_ones() + mask.trailing_zeros() != 32 {
+                return invalid(format!("in_ips {} netmask invalid", Ipv4Addr::from(*dest)));
+            }
+        }
+        if !self.dns_domains.is_empty() && self.dns_server.is_none() {
+            return invalid("dns_domains requires dns_server".to_string());
+        }
+        if let Some(domain) = self
+            .dns_domains
+            .iter()
+            .find(|v| !crate::split_dns::is_valid_domain(v))
+        {
+            return invalid(format!("dns_domains invalid: {:?}", domain));
+        }
+        Ok(())
+    }
+}
+
+/// 格式 192.168.1.0/24,10.26.0.3，掩码也可以是 255.255.255.0
+fn parse_in_ip(s: &str) -> io::Result<(u32, u32, Ipv4Addr)> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "ipv4/mask,ipv4");
+    let (net, gateway) = s.split_once(',').ok_or_else(invalid)?;
+    let (dest, mask) = parse_cidr(net)?;
+    let gateway = gateway.trim().parse::<Ipv4Addr>().map_err(|_| invalid())?;
+    Ok((dest, mask, gateway))
+}
+
+/// 格式 10.244.1.0/24，掩码也可以是 255.255.255.0
+pub fn parse_cidr(s: &str) -> io::Result<(u32, u32)> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "ipv4/mask");
+    let (dest, mask) = s.split_once('/').ok_or_else(invalid)?;
+    let dest = dest.trim().parse::<Ipv4Addr>().map_err(|_| invalid())?;
+    let mask = mask.trim();
+    let mask = if let Ok(mask) = mask.parse::<Ipv4Addr>() {
+        u32::from(mask)
+    } else {
+        match mask.parse::<u32>() {
+            Ok(0) => 0,
+            Ok(len) if len <= 32 => u32::MAX << (32 - len),
+            _ => return Err(invalid()),
+        }
+    };
+    Ok((u32::from(dest), mask))
+}
+
+pub fn format_cidr(dest: u32, mask: u32) -> String {
+    format!("{}/{}", Ipv4Addr::from(dest & mask), mask.count_ones())
+}
+
+/// 应用服务端下发的配置，本地配置优先，下发的路由追加在本地路由之后
+#[derive(Clone)]
+pub struct RemoteConfigHandler {
+    current: Arc<Mutex<Option<RemoteConfig>>>,
+    local_in_ips: Vec<(u32, u32, Ipv4Addr)>,
+    external_route: ExternalRoute,
+    device_writer: DeviceWriter,
+    device_name: String,
+    /// 本地配置了分域名解析时不使用下发的dns
+    local_dns: bool,
+    split_dns: Arc<Mutex<Option<SplitDns>>>,
+    keepalive: Arc<AtomicCell<u32>>,
+    /// 本设备发布的子网，和其他设备发布的冲突时以本地为准
+    advertise_routes: Vec<(u32, u32)>,
+    /// 允许接收的其他设备发布的子网，包含在其中的路由不检查私有网段和系统路由
+    accept_routes: Vec<(u32, u32)>,
+    /// 其他设备发布的子网，(目标,掩码,设备虚拟ip)
+    peer_routes: Arc<Mutex<Vec<(u32, u32, Ipv4Addr)>>>,
+    /// 服务端下发的路由和系统路由重叠时的处理方式
+    route_conflict: RouteConflictPolicy,
+}
+
+impl RemoteConfigHandler {
+    pub fn new(
+        local_in_ips: Vec<(u32, u32, Ipv4Addr)>,
+        external_route: ExternalRoute,
+        device_writer: DeviceWriter,
+        device_name: String,
+        local_dns: bool,
+        split_dns: Arc<Mutex<Option<SplitDns>>>,
+        keepalive: Arc<AtomicCell<u32>>,
+        advertise_routes: Vec<(u32, u32)>,
+        accept_routes: Vec<(u32, u32)>,
+        route_conflict: RouteConflictPolicy,
+    ) -> Self {
+        Self {
+            current: Arc::new(Mutex::new(None)),
+            local_in_ips,
+            external_route,
+            device_writer,
+            device_name,
+            local_dns,
+            split_dns,
+            keepalive,
+            advertise_routes,
+            accept_routes,
+            peer_routes: Arc::new(Mutex::new(Vec::new())),
+            route_conflict,
+        }
+    }
+    pub fn current(&self) -> Option<RemoteConfig> {
+        self.current.lock().clone()
+    }
+    /// gateway为虚拟网关，windows添加路由时使用
+    pub fn apply(&self, config: RemoteConfig, gateway: Ipv4Addr) -> io::Result<()> {
+        config.validate()?;
+        let mut current = self.current.lock();
+        if current.as_ref() == Some(&config) {
+            return Ok(());
+        }
+        log::info!("应用服务端下发的配置,version={}", config.version);
+        self.update_routes(&config.in_ips, gateway)?;
+        let dns_changed = current.as_ref().map_or(true, |v| {
+            v.dns_server != config.dns_server || v.dns_domains != config.dns_domains
+        });
+        if !self.local_dns && dns_changed {
+            let mut split_dns = self.split_dns.lock();
+            if let Some(old) = split_dns.take() {
+                old.disable();
+            }
+            if let Some(server) = config.dns_server {
+                if !config.dns_domains.is_empty() {
+                    match SplitDns::enable(&self.device_name, server, &config.dns_domains) {
+                        Ok(v) => {
+                            let _ = split_dns.insert(v);
+                        }
+                        Err(e) => log::warn!("服务端下发的dns配置应用失败 {:?}", e),
+                    }
+                }
+            }
+        }
+        self.keepalive.store(if config.keepalive == 0 {
+            DEFAULT_KEEPALIVE
+        } else {
+            config.keepalive
+        });
+        let _ = current.insert(config);
+        Ok(())
+    }
+    /// 本地路由在前，之后是服务端下发的和其他设备发布的
+    fn update_routes(
+        &self,
+        server_in_ips: &[(u32, u32, Ipv4Addr)],
+        gateway: Ipv4Addr,
+    ) -> io::Result<()> {
+        let peer_routes = self.peer_routes.lock().clone();
+        let remote_in_ips: Vec<(u32, u32, Ipv4Addr)> = server_in_ips
+            .iter()
+            .chain(peer_routes.iter())
+            .filter(|v| !self.local_in_ips.contains(v))
+            .copied()
+            .collect();
+        let mut in_ips = self.local_in_ips.clone();
+        in_ips.extend_from_slice(&remote_in_ips);
+        self.external_route.update(in_ips);
+        #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+        {
+            let mut routes: Vec<(Ipv4Addr, Ipv4Addr)> = Vec::with_capacity(remote_in_ips.len());
+            for (dest, mask, _) in &remote_in_ips {
+                let route = (Ipv4Addr::from(*dest & *mask), Ipv4Addr::from(*mask));
+                let local = self
+                    .local_in_ips
+                    .iter()
+                    .any(|(d, m, _)| (Ipv4Addr::from(*d & *m), Ipv4Addr::from(*m)) == route);
+                if !local && !routes.contains(&route) {
+                    routes.push(route);
+                }
+            }
+            let routes = route_conflict::resolve(
+                routes,
+                &route_conflict::system_routes(&self.device_name, gateway),
+                self.route_conflict,
+            );
+            self.device_writer.set_extra_routes(routes, gateway)?;
+        }
+        #[cfg(target_os = "android")]
+        let _ = (&self.device_writer, gateway);
+        Ok(())
+    }
+    /// 设备列表变化时，把在线设备发布的子网路由到该设备，
+    /// 和本地配置、其他设备、系统中已有的路由(如CNI添加的pod路由)冲突的不添加，
+    /// 没有被accept_routes包含时只接收私有网段，避免其他设备把公网流量引到自己那里
+    pub fn apply_peer_routes(
+        &self,
+        device_list: &[PeerDeviceInfo],
+        gateway: Ipv4Addr,
+    ) -> io::Result<()> {
+        let system_routes = route_conflict::system_routes(&self.device_name, gateway);
+        let mut routes: Vec<(u32, u32, Ipv4Addr)> = Vec::new();
+        for peer in device_list {
+            if peer.status != PeerDeviceStatus::Online {
+                continue;
+            }
+            for (dest, mask) in &peer.routes {
+                let route = (*dest & *mask, *mask);
+                let accepted = self.accept_routes.iter().any(|v| contains(*v, route));
+                let conflict = if self
+                    .advertise_routes
+                    .iter()
+                    .any(|v| contains(route, *v) || contains(*v, route))
+                {
+                    Some("advertised by this device".to_string())
+                } else if self
+                    .local_in_ips
+                    .iter()
+                    .any(|(d, m, _)| contains(route, (*d, *m)) || contains((*d, *m), route))
+                {
+                    Some("local in_ips".to_string())
+                } else if let Some((_, _, ip)) = routes
+                    .iter()
+                    .find(|(d, m, _)| contains(route, (*d, *m)) || contains((*d, *m), route))
+                {
+                    Some(format!("advertised by {}", ip))
+                } else if accepted {
+                    None
+                } else if !route_conflict::is_private(route) {
+                    Some("not a private range, allow it with accept_routes".to_string())
+                } else if let Some((_, _, name)) = system_routes
+                    .iter()
+                    .find(|(d, m, _)| contains(route, (*d, *m)) || contains((*d, *m), route))
+                {
+                    //和系统中已有的路由重叠，添加后会抢走或被抢走流量
+                    Some(format!("system route on {}", name))
+                } else {
+                    None
+                };
+                if let Some(reason) = conflict {
+                    log::warn!(
+                        "设备{}发布的路由{}冲突,不添加:{}",
+                        peer.virtual_ip,
+                        format_cidr(route.0, route.1),
+                        reason
+                    );
+                    continue;
+                }
+                routes.push((route.0, route.1, peer.virtual_ip));
+            }
+        }
+        {
+            let mut peer_routes = self.peer_routes.lock();
+            if *peer_routes == routes {
+                return Ok(());
+            }
+            log::info!("其他设备发布的路由 {:?}", routes);
+            *peer_routes = routes;
+        }
+        let server_in_ips = self
+            .current
+            .lock()
+            .as_ref()
+            .map(|v| v.in_ips.clone())
+            .unwrap_or_default();
+        self.update_routes(&server_in_ips, gateway)
+    }
+}
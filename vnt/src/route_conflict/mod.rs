@@ -0,0 +1,264 @@
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+
+use crate::remote_config::format_cidr;
+
+/// 要添加的路由和系统路由表中已有路由(其他vpn、docker等)重叠时的处理方式
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum RouteConflictPolicy {
+    /// 打印警告，仍然添加
+    #[default]
+    Warn,
+    /// 不添加重叠的路由，这部分流量继续走原来的路由
+    Skip,
+    /// 拆分成不覆盖已有路由的更具体的路由，被已有路由包含的部分不添加
+    Split,
+}
+
+impl FromStr for RouteConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "warn" => Ok(RouteConflictPolicy::Warn),
+            "skip" => Ok(RouteConflictPolicy::Skip),
+            "split" => Ok(RouteConflictPolicy::Split),
+            _ => Err(format!("not match '{}', enum: warn/skip/split", s)),
+        }
+    }
+}
+
+impl fmt::Display for RouteConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteConflictPolicy::Warn => f.write_str("warn"),
+            RouteConflictPolicy::Skip => f.write_str("skip"),
+            RouteConflictPolicy::Split => f.write_str("split"),
+        }
+    }
+}
+
+/// route_a包含了route_b，即b的地址都落在a中
+pub fn contains(a: (u32, u32), b: (u32, u32)) -> bool {
+    a.1 & b.1 == a.1 && b.0 & a.1 == a.0 & a.1
+}
+
+/// 从route中去掉hole，hole必须被route包含，返回剩下部分的路由
+fn subtract(route: (u32, u32), hole: (u32, u32)) -> Vec<(u32, u32)> {
+    let mut rs = Vec::new();
+    let mut mask = route.1;
+    while mask != hole.1 {
+        //拆成两半，不包含hole的一半保留
+        let bit = 1u32 << (31 - mask.count_ones());
+        mask |= bit;
+        rs.push(((hole.0 ^ bit) & mask, mask));
+    }
+    rs
+}
+
+/// 按策略处理和系统路由的重叠，返回实际要添加的路由(目标,掩码)
+///
+/// 已有路由包含要添加的路由时，添加后会抢走这部分流量；
+/// 要添加的路由包含已有路由时，使用独立路由表(--route-table)的情况下vnt的路由表先匹配，同样会抢走流量
+pub fn resolve(
+    routes: Vec<(Ipv4Addr, Ipv4Addr)>,
+    system_routes: &[(u32, u32, String)],
+    policy: RouteConflictPolicy,
+) -> Vec<(Ipv4Addr, Ipv4Addr)> {
+    let mut rs = Vec::with_capacity(routes.len());
+    for (dest, mask) in routes {
+        let route = (u32::from(dest) & u32::from(mask), u32::from(mask));
+        let conflicts: Vec<&(u32, u32, String)> = system_routes
+            .iter()
+            .filter(|(d, m, _)| contains(route, (*d, *m)) || contains((*d, *m), route))
+            .collect();
+        if conflicts.is_empty() {
+            rs.push((dest, mask));
+            continue;
+        }
+        let names: Vec<String> = conflicts
+            .iter()
+            .map(|(d, m, name)| format!("{} dev {}", format_cidr(*d, *m), name))
+            .collect();
+        let cidr = format_cidr(route.0, route.1);
+        match policy {
+            RouteConflictPolicy::Warn => {
+                log::warn!("路由{}和系统路由重叠:{:?}，仍然添加", cidr, names);
+                rs.push((dest, mask));
+            }
+            RouteConflictPolicy::Skip => {
+                log::warn!("路由{}和系统路由重叠:{:?}，不添加", cidr, names);
+            }
+            RouteConflictPolicy::Split => {
+                let mut pieces = vec![route];
+                for (d, m, _) in &conflicts {
+                    let hole = (*d & *m, *m);
+                    let mut next = Vec::with_capacity(pieces.len());
+                    for piece in pieces {
+                        if contains(hole, piece) {
+                            continue;
+                        }
+                        if contains(piece, hole) {
+                            next.extend(subtract(piece, hole));
+                        } else {
+                            next.push(piece);
+                        }
+                    }
+                    pieces = next;
+                }
+                log::warn!(
+                    "路由{}和系统路由重叠:{:?}，拆分为{:?}",
+                    cidr,
+                    names,
+                    pieces
+                        .iter()
+                        .map(|(d, m)| format_cidr(*d, *m))
+                        .collect::<Vec<_>>()
+                );
+                rs.extend(
+                    pieces
+                        .into_iter()
+                        .map(|(d, m)| (Ipv4Addr::from(d), Ipv4Addr::from(m))),
+                );
+            }
+        }
+    }
+    rs
+}
+
+/// 私有网段(RFC 1918)和运营商级NAT网段(RFC 6598)
+const PRIVATE_RANGES: [(u32, u32); 4] = [
+    (0x0a00_0000, 0xff00_0000),
+    (0xac10_0000, 0xfff0_0000),
+    (0xc0a8_0000, 0xffff_0000),
+    (0x6440_0000, 0xffc0_0000),
+];
+
+/// 路由是否完全落在私有网段中
+pub fn is_private(route: (u32, u32)) -> bool {
+    PRIVATE_RANGES.iter().any(|v| contains(*v, route))
+}
+
+/// 默认路由、环回、组播和广播路由不算冲突
+fn ignored(dest: u32, mask: u32) -> bool {
+    mask == 0
+        || contains((0x7f00_0000, 0xff00_0000), (dest, mask))
+        || contains((0xe000_0000, 0xf000_0000), (dest, mask))
+        || dest == u32::MAX
+}
+
+/// 系统路由表中不经过虚拟网卡的路由，(目标,掩码,网卡)，不包含默认路由。
+/// 经过虚拟网卡的路由按网卡名称排除，windows下按网关是否为虚拟网关排除
+#[cfg(target_os = "linux")]
+pub fn system_routes(device_name: &str, _gateway: Ipv4Addr) -> Vec<(u32, u32, String)> {
+    let table = match std::fs::read_to_string("/proc/net/route") {
+        Ok(table) => table,
+        Err(_) => return Vec::new(),
+    };
+    let mut routes = Vec::new();
+    //Iface Destination Gateway Flags RefCnt Use Metric Mask ...，地址为网络字节序的十六进制
+    for line in table.lines().skip(1) {
+        let items: Vec<&str> = line.split_whitespace().collect();
+        if items.len() < 8 || items[0] == device_name {
+            continue;
+        }
+        let (dest, mask) = match (
+            u32::from_str_radix(items[1], 16),
+            u32::from_str_radix(items[7], 16),
+        ) {
+            (Ok(dest), Ok(mask)) => (
+                u32::from_be_bytes(dest.to_ne_bytes()),
+                u32::from_be_bytes(mask.to_ne_bytes()),
+            ),
+            _ => continue,
+        };
+        if ignored(dest, mask) {
+            continue;
+        }
+        routes.push((dest, mask, items[0].to_string()));
+    }
+    routes
+}
+
+/// 系统路由表中不经过虚拟网卡的路由，(目标,掩码,网卡)，不包含默认路由。
+/// 经过虚拟网卡的路由按网卡名称排除，windows下按网关是否为虚拟网关排除
+#[cfg(target_os = "macos")]
+pub fn system_routes(device_name: &str, _gateway: Ipv4Addr) -> Vec<(u32, u32, String)> {
+    let out = match std::process::Command::new("netstat")
+        .args(["-rn", "-f", "inet"])
+        .output()
+    {
+        Ok(out) => out,
+        Err(_) => return Vec::new(),
+    };
+    let mut routes = Vec::new();
+    //Destination Gateway Flags Netif Expire，目标可以省略后面为0的字节，如 10/8、192.168.1
+    for line in String::from_utf8_lossy(&out.stdout).lines() {
+        let items: Vec<&str> = line.split_whitespace().collect();
+        if items.len() < 4 || items[3] == device_name {
+            continue;
+        }
+        let (dest, len) = match items[0].split_once('/') {
+            Some((dest, len)) => (dest, len.parse::<u32>().ok()),
+            None => (items[0], None),
+        };
+        let octets: Vec<u8> = match dest.split('.').map(|v| v.parse::<u8>()).collect() {
+            Ok(octets) => octets,
+            Err(_) => continue,
+        };
+        if octets.is_empty() || octets.len() > 4 {
+            continue;
+        }
+        let len = len.unwrap_or(octets.len() as u32 * 8).min(32);
+        let mut bytes = [0u8; 4];
+        bytes[..octets.len()].copy_from_slice(&octets);
+        let dest = u32::from_be_bytes(bytes);
+        let mask = if len == 0 { 0 } else { u32::MAX << (32 - len) };
+        if ignored(dest, mask) {
+            continue;
+        }
+        routes.push((dest & mask, mask, items[3].to_string()));
+    }
+    routes
+}
+
+/// 系统路由表中不经过虚拟网卡的路由，(目标,掩码,网卡)，不包含默认路由。
+/// 经过虚拟网卡的路由按网卡名称排除，windows下按网关是否为虚拟网关排除
+#[cfg(target_os = "windows")]
+pub fn system_routes(_device_name: &str, gateway: Ipv4Addr) -> Vec<(u32, u32, String)> {
+    use std::os::windows::process::CommandExt;
+    let out = match std::process::Command::new("route")
+        .args(["print", "-4"])
+        .creation_flags(0x08000000) //CREATE_NO_WINDOW
+        .output()
+    {
+        Ok(out) => out,
+        Err(_) => return Vec::new(),
+    };
+    let mut routes = Vec::new();
+    //Network Destination Netmask Gateway Interface Metric，只读取活动路由
+    for line in String::from_utf8_lossy(&out.stdout).lines() {
+        if line.starts_with("Persistent") {
+            break;
+        }
+        let items: Vec<&str> = line.split_whitespace().collect();
+        if items.len() != 5 || items[2] == gateway.to_string() {
+            continue;
+        }
+        let (dest, mask) = match (items[0].parse::<Ipv4Addr>(), items[1].parse::<Ipv4Addr>()) {
+            (Ok(dest), Ok(mask)) => (u32::from(dest), u32::from(mask)),
+            _ => continue,
+        };
+        if ignored(dest, mask) {
+            continue;
+        }
+        routes.push((dest & mask, mask, format!("interface {}", items[3])));
+    }
+    routes
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn system_routes(_device_name: &str, _gateway: Ipv4Addr) -> Vec<(u32, u32, String)> {
+    Vec::new()
+}
@@ -0,0 +1,50 @@
+use std::io;
+use std::net::Ipv4Addr;
+use std::process::Command;
+
+/// 使用systemd-resolved的按网卡配置dns，域名前加~表示只做路由域名，不加入搜索列表
+pub struct ResolvedDns {
+    name: String,
+}
+
+impl ResolvedDns {
+    pub fn enable(name: &str, server: Ipv4Addr, domains: &[&str]) -> io::Result<ResolvedDns> {
+        resolvectl(&["dns", name, &server.to_string()])?;
+        let mut args = vec!["domain".to_string(), name.to_string()];
+        for domain in domains {
+            args.push(format!("~{}", domain));
+        }
+        let args: Vec<&str> = args.iter().map(|v| v.as_str()).collect();
+        let dns = ResolvedDns {
+            name: name.to_string(),
+        };
+        // 出错时drop会还原
+        resolvectl(&args)?;
+        // 不作为默认dns，其余域名继续使用本地dns
+        resolvectl(&["default-route", name, "false"])?;
+        Ok(dns)
+    }
+}
+
+impl Drop for ResolvedDns {
+    fn drop(&mut self) {
+        if let Err(e) = resolvectl(&["revert", &self.name]) {
+            log::warn!("split dns revert {:?}", e);
+        }
+    }
+}
+
+fn resolvectl(args: &[&str]) -> io::Result<()> {
+    let out = Command::new("resolvectl").args(args).output()?;
+    if !out.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "resolvectl {:?} error: {}",
+                args,
+                String::from_utf8_lossy(&out.stderr)
+            ),
+        ));
+    }
+    Ok(())
+}
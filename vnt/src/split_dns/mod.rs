@@ -0,0 +1,85 @@
+use std::io;
+use std::net::Ipv4Addr;
+
+#[cfg(all(feature = "desktop", target_os = "linux"))]
+mod linux;
+#[cfg(all(feature = "desktop", target_os = "windows"))]
+mod windows;
+
+/// 分域名解析，指定后缀的域名使用隧道内的dns服务器解析，其余域名仍使用本地dns，drop时还原
+pub struct SplitDns {
+    #[cfg(all(feature = "desktop", target_os = "linux"))]
+    inner: linux::ResolvedDns,
+    #[cfg(all(feature = "desktop", target_os = "windows"))]
+    inner: windows::NrptDns,
+}
+
+impl SplitDns {
+    /// name为虚拟网卡名称，domains为域名后缀，如 corp.internal
+    #[cfg(all(feature = "desktop", any(target_os = "linux", target_os = "windows")))]
+    pub fn enable(name: &str, server: Ipv4Addr, domains: &[String]) -> io::Result<SplitDns> {
+        let domains: Vec<&str> = domains
+            .iter()
+            .map(|v| v.trim().trim_matches('.'))
+            .filter(|v| !v.is_empty())
+            .collect();
+        // 域名会传给resolvectl和powershell，只允许字母、数字、连字符和点
+        if let Some(domain) = domains.iter().find(|v| !is_valid_domain(v)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid dns domain {:?}", domain),
+            ));
+        }
+        #[cfg(all(feature = "desktop", target_os = "linux"))]
+        let inner = linux::ResolvedDns::enable(name, server, &domains)?;
+        #[cfg(all(feature = "desktop", target_os = "windows"))]
+        let inner = {
+            let _ = name;
+            windows::NrptDns::enable(server, &domains)?
+        };
+        log::info!("split dns enabled,server={},domains={:?}", server, domains);
+        Ok(SplitDns { inner })
+    }
+    #[cfg(not(all(feature = "desktop", any(target_os = "linux", target_os = "windows"))))]
+    pub fn enable(_name: &str, _server: Ipv4Addr, _domains: &[String]) -> io::Result<SplitDns> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "split dns not supported",
+        ))
+    }
+    pub fn disable(self) {
+        #[cfg(all(feature = "desktop", any(target_os = "linux", target_os = "windows")))]
+        drop(self.inner);
+    }
+}
+
+/// 域名后缀是否合法(LDH规则)：每段1~63个字母、数字或连字符，不以连字符开头或结尾，总长度不超过253，
+/// 首尾的点会被忽略
+pub fn is_valid_domain(domain: &str) -> bool {
+    let domain = domain.trim().trim_matches('.');
+    if domain.is_empty() || domain.len() > 253 {
+        return false;
+    }
+    domain.split('.').all(|label| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+    })
+}
+
+#[test]
+fn test_is_valid_domain() {
+    assert!(is_valid_domain("corp.internal"));
+    assert!(is_valid_domain(".corp.internal."));
+    assert!(is_valid_domain("a-1.example"));
+    assert!(!is_valid_domain(""));
+    assert!(!is_valid_domain("corp..internal"));
+    assert!(!is_valid_domain("-corp.internal"));
+    assert!(!is_valid_domain("corp'; Remove-Item C:\\ -Recurse; '"));
+    assert!(!is_valid_domain("corp internal"));
+    assert!(!is_valid_domain(&"a".repeat(64)));
+}
@@ -0,0 +1,69 @@
+use std::io;
+use std::net::Ipv4Addr;
+use std::os::windows::process::CommandExt;
+use std::process::Command;
+
+const ENV_NAMESPACE: &str = "VNT_NRPT_NAMESPACE";
+const ENV_SERVER: &str = "VNT_NRPT_SERVER";
+const ENV_COMMENT: &str = "VNT_NRPT_COMMENT";
+
+/// NRPT规则的备注，用于识别vnt添加的规则
+const COMMENT: &str = "vnt split dns";
+
+/// 使用NRPT(名称解析策略表)实现，规则在系统中持久保存，所以退出时必须删除
+pub struct NrptDns;
+
+impl NrptDns {
+    pub fn enable(server: Ipv4Addr, domains: &[&str]) -> io::Result<NrptDns> {
+        // 清理非正常退出时残留的规则
+        remove_rules()?;
+        let dns = NrptDns;
+        for domain in domains {
+            // 参数通过环境变量传入，不拼接到脚本中，避免被当作powershell代码执行
+            powershell(
+                "Add-DnsClientNrptRule -Namespace $env:VNT_NRPT_NAMESPACE -NameServers $env:VNT_NRPT_SERVER -Comment $env:VNT_NRPT_COMMENT",
+                &[
+                    (ENV_NAMESPACE, &format!(".{}", domain)),
+                    (ENV_SERVER, &server.to_string()),
+                ],
+            )?;
+        }
+        Ok(dns)
+    }
+}
+
+impl Drop for NrptDns {
+    fn drop(&mut self) {
+        if let Err(e) = remove_rules() {
+            log::warn!("split dns remove {:?}", e);
+        }
+    }
+}
+
+fn remove_rules() -> io::Result<()> {
+    powershell(
+        "Get-DnsClientNrptRule | Where-Object { $_.Comment -eq $env:VNT_NRPT_COMMENT } | Remove-DnsClientNrptRule -Force",
+        &[],
+    )
+}
+
+/// cmd是固定的脚本，变化的值放在envs中，脚本里用$env:读取
+fn powershell(cmd: &str, envs: &[(&str, &str)]) -> io::Result<()> {
+    let out = Command::new("powershell")
+        .creation_flags(0x08000000) //CREATE_NO_WINDOW
+        .args(["-NoProfile", "-NonInteractive", "-Command", cmd])
+        .env(ENV_COMMENT, COMMENT)
+        .envs(envs.iter().copied())
+        .output()?;
+    if !out.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "cmd:{},error: {}",
+                cmd,
+                String::from_utf8_lossy(&out.stderr)
+            ),
+        ));
+    }
+    Ok(())
+}
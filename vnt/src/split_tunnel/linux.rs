@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+use crate::split_tunnel::SplitMode;
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+const CGROUP_NAME: &str = "vnt_split";
+const TABLE: &str = "inet vnt_split";
+/// comm最多保留15个字符
+const COMM_LEN: usize = 15;
+
+/// 基于cgroup v2实现，匹配的进程放到单独的cgroup中，
+/// nftables按cgroup给流量打上fwmark，带标记的流量不走vnt的路由表
+pub struct CgroupSplit {
+    state: Arc<Mutex<State>>,
+    stop: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+struct State {
+    apps: Vec<String>,
+    /// pid -> 移入之前所在的cgroup(相对cgroup根目录)，移出时放回原处，
+    /// 否则systemd服务、容器中的进程会丢失原来的资源限制
+    origins: HashMap<String, String>,
+}
+
+impl CgroupSplit {
+    pub fn new(mode: SplitMode, mark: u32) -> io::Result<CgroupSplit> {
+        if mark == 0 {
+            return Err(io::Error::new(io::ErrorKind::Other, "fwmark not set"));
+        }
+        if !Path::new(CGROUP_ROOT).join("cgroup.controllers").exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "cgroup v2 not mounted",
+            ));
+        }
+        let cgroup = Path::new(CGROUP_ROOT).join(CGROUP_NAME);
+        if !cgroup.exists() {
+            std::fs::create_dir(&cgroup)?;
+        }
+        delete_table();
+        // include模式下cgroup外的进程打标记，exclude模式下cgroup内的进程打标记
+        let op = match mode {
+            SplitMode::Include => "!= ",
+            SplitMode::Exclude => "",
+        };
+        let rules = format!(
+            "table {table} {{
+    chain output {{
+        type route hook output priority mangle; policy accept;
+        socket cgroupv2 level 1 {op}\"{cgroup}\" meta mark set {mark}
+    }}
+}}
+",
+            table = TABLE,
+            op = op,
+            cgroup = CGROUP_NAME,
+            mark = mark
+        );
+        nft(&rules)?;
+        let state: Arc<Mutex<State>> = Arc::new(Mutex::new(State::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+        {
+            let state = state.clone();
+            let stop = stop.clone();
+            std::thread::Builder::new()
+                .name("split_tunnel".into())
+                .spawn(move || {
+                    // 新启动的进程需要定时扫描
+                    while !stop.load(Ordering::Relaxed) {
+                        let mut state = state.lock();
+                        let State { apps, origins } = &mut *state;
+                        if let Err(e) = attach(apps, origins) {
+                            log::warn!("split tunnel {:?}", e);
+                        }
+                        drop(state);
+                        std::thread::sleep(Duration::from_secs(2));
+                    }
+                })?;
+        }
+        Ok(CgroupSplit { state, stop })
+    }
+    pub fn update(&self, apps: Vec<String>) {
+        let mut state = self.state.lock();
+        // 先全部移出，子进程会继承cgroup，不能只按名称判断
+        if let Err(e) = detach_all(&mut state.origins) {
+            log::warn!("split tunnel {:?}", e);
+        }
+        if let Err(e) = attach(&apps, &mut state.origins) {
+            log::warn!("split tunnel {:?}", e);
+        }
+        state.apps = apps;
+    }
+}
+
+impl Drop for CgroupSplit {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        delete_table();
+        let _ = detach_all(&mut self.state.lock().origins);
+        let _ = std::fs::remove_dir(Path::new(CGROUP_ROOT).join(CGROUP_NAME));
+    }
+}
+
+fn comm_matches(comm: &str, apps: &[String]) -> bool {
+    apps.iter().any(|app| {
+        let app = app.as_bytes();
+        let app = &app[..app.len().min(COMM_LEN)];
+        comm.as_bytes() == app
+    })
+}
+
+fn attach(apps: &[String], origins: &mut HashMap<String, String>) -> io::Result<()> {
+    // 已经退出的进程，pid可能被复用
+    origins.retain(|pid, _| Path::new("/proc").join(pid).exists());
+    if apps.is_empty() {
+        return Ok(());
+    }
+    let own = format!("/{}", CGROUP_NAME);
+    let procs = Path::new(CGROUP_ROOT).join(CGROUP_NAME).join("cgroup.procs");
+    for entry in std::fs::read_dir("/proc")? {
+        let entry = entry?;
+        let pid = entry.file_name();
+        let pid = pid.to_string_lossy();
+        if !pid.bytes().all(|b| b.is_ascii_digit()) {
+            continue;
+        }
+        let comm = match std::fs::read_to_string(entry.path().join("comm")) {
+            Ok(comm) => comm,
+            Err(_) => continue,
+        };
+        if !comm_matches(comm.trim_end(), apps) {
+            continue;
+        }
+        // 进程可能已经退出
+        let origin = match cgroup_of(&pid) {
+            Some(origin) => origin,
+            None => continue,
+        };
+        if origin == own {
+            continue;
+        }
+        if std::fs::write(&procs, pid.as_bytes()).is_ok() {
+            origins.insert(pid.into_owned(), origin);
+        }
+    }
+    Ok(())
+}
+
+/// 放回移入之前的cgroup，移入后才创建的子进程使用父进程的记录，
+/// 原来的cgroup已经删除或者没有记录时放到根cgroup
+fn detach_all(origins: &mut HashMap<String, String>) -> io::Result<()> {
+    let procs = std::fs::read_to_string(
+        Path::new(CGROUP_ROOT)
+            .join(CGROUP_NAME)
+            .join("cgroup.procs"),
+    )?;
+    let root = Path::new(CGROUP_ROOT).join("cgroup.procs");
+    for pid in procs.lines() {
+        let origin = origins
+            .get(pid)
+            .or_else(|| parent_pid(pid).and_then(|ppid| origins.get(&ppid)));
+        let restored = origin.map_or(false, |origin| {
+            let path = Path::new(CGROUP_ROOT)
+                .join(origin.trim_start_matches('/'))
+                .join("cgroup.procs");
+            std::fs::write(path, pid.as_bytes()).is_ok()
+        });
+        if !restored {
+            let _ = std::fs::write(&root, pid.as_bytes());
+        }
+    }
+    origins.clear();
+    Ok(())
+}
+
+/// /proc/<pid>/cgroup中cgroup v2的路径，格式为"0::/user.slice/..."
+fn cgroup_of(pid: &str) -> Option<String> {
+    let content = std::fs::read_to_string(Path::new("/proc").join(pid).join("cgroup")).ok()?;
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(|path| path.to_string())
+}
+
+fn parent_pid(pid: &str) -> Option<String> {
+    let status = std::fs::read_to_string(Path::new("/proc").join(pid).join("status")).ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("PPid:"))
+        .map(|ppid| ppid.trim().to_string())
+}
+
+fn nft(rules: &str) -> io::Result<()> {
+    let mut child = Command::new("nft")
+        .arg("-f")
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(rules.as_bytes())?;
+    }
+    let out = child.wait_with_output()?;
+    if !out.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("nft error: {}", String::from_utf8_lossy(&out.stderr)),
+        ));
+    }
+    Ok(())
+}
+
+fn delete_table() {
+    let cmd = format!("nft delete table {}", TABLE);
+    if let Err(e) = Command::new("sh").arg("-c").arg(&cmd).output() {
+        log::warn!("cmd:{},{:?}", cmd, e);
+    }
+}
@@ -0,0 +1,104 @@
+use std::io;
+use std::str::FromStr;
+
+use parking_lot::Mutex;
+
+#[cfg(all(feature = "desktop", target_os = "linux"))]
+mod linux;
+#[cfg(all(feature = "desktop", target_os = "windows"))]
+mod windows;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SplitMode {
+    /// 只有列表中的程序使用隧道
+    Include,
+    /// 列表中的程序不使用隧道
+    Exclude,
+}
+
+impl FromStr for SplitMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().trim() {
+            "include" => Ok(SplitMode::Include),
+            "exclude" => Ok(SplitMode::Exclude),
+            _ => Err(format!("not match '{}', enum: include/exclude", s)),
+        }
+    }
+}
+
+/// 按程序分流，linux下程序名对应/proc/<pid>/comm，windows下为程序的完整路径
+pub struct SplitTunnel {
+    mode: SplitMode,
+    apps: Mutex<Vec<String>>,
+    #[cfg(all(feature = "desktop", target_os = "linux"))]
+    inner: linux::CgroupSplit,
+    #[cfg(all(feature = "desktop", target_os = "windows"))]
+    inner: Mutex<Option<windows::WfpSplit>>,
+    #[cfg(all(feature = "desktop", target_os = "windows"))]
+    name: String,
+}
+
+impl SplitTunnel {
+    /// name为虚拟网卡名称
+    #[cfg(all(feature = "desktop", any(target_os = "linux", target_os = "windows")))]
+    pub fn new(mode: SplitMode, apps: Vec<String>, name: &str) -> io::Result<SplitTunnel> {
+        #[cfg(all(feature = "desktop", target_os = "linux"))]
+        let inner = {
+            let _ = name;
+            linux::CgroupSplit::new(mode, crate::util::fwmark::get())?
+        };
+        #[cfg(all(feature = "desktop", target_os = "windows"))]
+        let inner = Mutex::new(Some(windows::WfpSplit::new(mode, &apps, name)?));
+        let split_tunnel = SplitTunnel {
+            mode,
+            apps: Mutex::new(apps),
+            inner,
+            #[cfg(all(feature = "desktop", target_os = "windows"))]
+            name: name.to_string(),
+        };
+        #[cfg(all(feature = "desktop", target_os = "linux"))]
+        split_tunnel.inner.update(split_tunnel.apps());
+        Ok(split_tunnel)
+    }
+    #[cfg(not(all(feature = "desktop", any(target_os = "linux", target_os = "windows"))))]
+    pub fn new(_mode: SplitMode, _apps: Vec<String>, _name: &str) -> io::Result<SplitTunnel> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "split tunnel not supported",
+        ))
+    }
+    pub fn mode(&self) -> SplitMode {
+        self.mode
+    }
+    pub fn apps(&self) -> Vec<String> {
+        self.apps.lock().clone()
+    }
+    pub fn add(&self, app: &str) -> io::Result<()> {
+        let mut apps = self.apps.lock();
+        if apps.iter().any(|v| v == app) {
+            return Ok(());
+        }
+        apps.push(app.to_string());
+        self.update(&apps)
+    }
+    pub fn remove(&self, app: &str) -> io::Result<()> {
+        let mut apps = self.apps.lock();
+        apps.retain(|v| v != app);
+        self.update(&apps)
+    }
+    #[allow(unused_variables)]
+    fn update(&self, apps: &[String]) -> io::Result<()> {
+        #[cfg(all(feature = "desktop", target_os = "linux"))]
+        self.inner.update(apps.to_vec());
+        #[cfg(all(feature = "desktop", target_os = "windows"))]
+        {
+            // 先关闭旧的会话，规则随会话删除，再按新的列表重建
+            let mut guard = self.inner.lock();
+            guard.take();
+            let _ = guard.insert(windows::WfpSplit::new(self.mode, apps, &self.name)?);
+        }
+        Ok(())
+    }
+}
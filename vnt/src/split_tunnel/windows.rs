@@ -0,0 +1,67 @@
+use std::io;
+
+use winapi::shared::guiddef::GUID;
+
+use crate::split_tunnel::SplitMode;
+use crate::util::wfp::{
+    app_condition, guid, interface_condition, interface_luid, AppId, Engine,
+    FWPM_LAYER_ALE_AUTH_CONNECT_V4, FWPM_LAYER_ALE_AUTH_CONNECT_V6, FWP_ACTION_BLOCK,
+    FWP_ACTION_PERMIT,
+};
+
+const SPLIT_SUBLAYER: GUID = guid(
+    0x5a5f1bd4,
+    0x2a2d,
+    0x4b9b,
+    [0x9c, 0x3e, 0x76, 0x6e, 0x74, 0x00, 0x00, 0x02],
+);
+
+/// 使用WFP按程序限制虚拟网卡的使用，不在允许范围内的程序无法通过虚拟网卡通信，
+/// windows下无法按程序修改路由，所以只能拦截，不能让其改走物理网卡
+pub struct WfpSplit {
+    _engine: Engine,
+}
+
+impl WfpSplit {
+    pub fn new(mode: SplitMode, apps: &[String], name: &str) -> io::Result<WfpSplit> {
+        let luid = interface_luid(name)?;
+        let engine = Engine::open_dynamic()?;
+        engine.add_sublayer(SPLIT_SUBLAYER, "vnt split tunnel", u16::MAX - 1)?;
+        let mut app_ids = Vec::with_capacity(apps.len());
+        for app in apps {
+            match AppId::from_path(app) {
+                Ok(app_id) => app_ids.push(app_id),
+                Err(e) => {
+                    log::warn!("split tunnel app={},{:?}", app, e);
+                }
+            }
+        }
+        let (app_action, other_action) = match mode {
+            SplitMode::Include => (FWP_ACTION_PERMIT, Some(FWP_ACTION_BLOCK)),
+            SplitMode::Exclude => (FWP_ACTION_BLOCK, None),
+        };
+        for layer in [FWPM_LAYER_ALE_AUTH_CONNECT_V4, FWPM_LAYER_ALE_AUTH_CONNECT_V6] {
+            for app_id in &app_ids {
+                engine.add_filter(
+                    "vnt split tunnel",
+                    layer,
+                    SPLIT_SUBLAYER,
+                    &mut [interface_condition(&luid), app_condition(app_id)],
+                    app_action,
+                    Some(15),
+                )?;
+            }
+            if let Some(action) = other_action {
+                engine.add_filter(
+                    "vnt split tunnel",
+                    layer,
+                    SPLIT_SUBLAYER,
+                    &mut [interface_condition(&luid)],
+                    action,
+                    None,
+                )?;
+            }
+        }
+        Ok(WfpSplit { _engine: engine })
+    }
+}
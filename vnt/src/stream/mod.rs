@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crossbeam_utils::atomic::AtomicCell;
+use parking_lot::Mutex;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::channel::sender::ChannelSender;
+use crate::cipher::Cipher;
+use crate::handle::CurrentDeviceInfo;
+use crate::protocol::body::ENCRYPTION_RESERVED;
+use crate::protocol::stream_packet::{StreamPacket, ACK, HEAD_LEN, RST, SYN};
+use crate::protocol::{other_turn_packet, NetPacket, Protocol, Version, MAX_TTL};
+
+mod vnt_stream;
+
+pub use vnt_stream::VntStream;
+use vnt_stream::{Outgoing, Shared};
+
+/// 单个分段的最大数据长度
+const MSS: usize = 1024;
+/// 未确认分段的最大数量
+const WINDOW: usize = 64;
+/// 发送和接收缓冲区上限
+const BUF_LIMIT: usize = 256 * 1024;
+/// 重传间隔
+const RTO: Duration = Duration::from_millis(300);
+/// 单个分段的重传次数上限，超过后断开连接
+const MAX_RETRIES: u32 = 20;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// 等待accept的连接数上限
+const BACKLOG: usize = 32;
+/// 主动连接使用的本地端口范围
+const EPHEMERAL_PORTS: std::ops::RangeInclusive<u16> = 49152..=65535;
+
+/// (对端虚拟ip,本地端口,对端端口)
+type StreamKey = (Ipv4Addr, u16, u16);
+
+/// 隧道内的可靠流，数据经过客户端加密后走和虚拟网卡相同的通道，
+/// 给嵌入vnt的程序在设备之间直接通信使用，不经过虚拟网卡
+#[derive(Clone)]
+pub struct StreamManager {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    sender: ChannelSender,
+    current_device: Arc<AtomicCell<CurrentDeviceInfo>>,
+    client_cipher: Cipher,
+    runtime: tokio::runtime::Handle,
+    streams: Mutex<HashMap<StreamKey, Arc<Shared>>>,
+    listeners: Mutex<HashMap<u16, Sender<VntStream>>>,
+    next_port: AtomicCell<u16>,
+}
+
+impl StreamManager {
+    /// 需要在tokio运行时中创建
+    pub fn new(
+        sender: ChannelSender,
+        current_device: Arc<AtomicCell<CurrentDeviceInfo>>,
+        client_cipher: Cipher,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                sender,
+                current_device,
+                client_cipher,
+                runtime: tokio::runtime::Handle::current(),
+                streams: Mutex::new(HashMap::new()),
+                listeners: Mutex::new(HashMap::new()),
+                next_port: AtomicCell::new(*EPHEMERAL_PORTS.start()),
+            }),
+        }
+    }
+    /// 监听端口，端口只在隧道内有效，和系统端口无关
+    pub fn listen(&self, port: u16) -> io::Result<VntListener> {
+        let mut listeners = self.inner.listeners.lock();
+        if listeners.contains_key(&port) {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("port {} in use", port),
+            ));
+        }
+        let (sender, receiver) = channel(BACKLOG);
+        listeners.insert(port, sender);
+        Ok(VntListener {
+            port,
+            receiver,
+            manager: self.clone(),
+        })
+    }
+    /// 连接对端设备上监听的端口
+    pub async fn connect(&self, peer: Ipv4Addr, port: u16) -> io::Result<VntStream> {
+        if peer == self.inner.current_device.load().virtual_ip() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot connect to self",
+            ));
+        }
+        let shared = {
+            let mut streams = self.inner.streams.lock();
+            let mut local_port = None;
+            for _ in EPHEMERAL_PORTS {
+                let next = self.inner.next_port.load();
+                let _ = self.inner.next_port.compare_exchange(
+                    next,
+                    if next == *EPHEMERAL_PORTS.end() {
+                        *EPHEMERAL_PORTS.start()
+                    } else {
+                        next + 1
+                    },
+                );
+                if !streams.contains_key(&(peer, next, port)) {
+                    local_port = Some(next);
+                    break;
+                }
+            }
+            let local_port = local_port.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::AddrNotAvailable, "no free local port")
+            })?;
+            let shared = Shared::new((peer, local_port, port), rand::random());
+            streams.insert(shared.key, shared.clone());
+            shared
+        };
+        self.spawn(shared.clone());
+        let stream = VntStream::new(shared.clone());
+        match tokio::time::timeout(CONNECT_TIMEOUT, shared.wait_established()).await {
+            Ok(Ok(())) => Ok(stream),
+            Ok(Err(e)) => Err(e),
+            Err(_) => {
+                shared.fail(io::ErrorKind::TimedOut);
+                Err(io::Error::new(io::ErrorKind::TimedOut, "connect timeout"))
+            }
+        }
+    }
+    /// 处理对端发来的流数据
+    pub fn handle(&self, source: Ipv4Addr, payload: &[u8]) -> io::Result<()> {
+        let packet = StreamPacket::new(payload)?;
+        let key = (source, packet.destination_port(), packet.source_port());
+        let flags = packet.flags();
+        let shared = self.inner.streams.lock().get(&key).cloned();
+        let shared = match shared {
+            Some(shared) => shared,
+            None => {
+                if flags & RST != 0 {
+                    return Ok(());
+                }
+                if flags & SYN == 0 || flags & ACK != 0 {
+                    //连接已经不存在
+                    return self.send(&key, packet.ack(), None, RST, &[]);
+                }
+                return self.accept(key, packet.seq());
+            }
+        };
+        if flags & RST != 0 {
+            shared.fail(io::ErrorKind::ConnectionReset);
+            return Ok(());
+        }
+        if shared.on_packet(&packet) {
+            let (seq, ack) = {
+                let state = shared.state.lock();
+                (state.next_seq(), state.recv_next())
+            };
+            self.send(&key, seq, ack, 0, &[])?;
+        }
+        Ok(())
+    }
+    fn accept(&self, key: StreamKey, peer_isn: u32) -> io::Result<()> {
+        let listener = self.inner.listeners.lock().get(&key.1).cloned();
+        let listener = match listener {
+            Some(listener) => listener,
+            None => return self.send(&key, 0, Some(peer_isn.wrapping_add(1)), RST, &[]),
+        };
+        let shared = Shared::accepted(key, rand::random(), peer_isn);
+        self.inner.streams.lock().insert(key, shared.clone());
+        if listener.try_send(VntStream::new(shared.clone())).is_err() {
+            self.remove(&shared);
+            return self.send(&key, 0, Some(peer_isn.wrapping_add(1)), RST, &[]);
+        }
+        self.spawn(shared);
+        Ok(())
+    }
+    /// 断开所有连接
+    pub fn close(&self) {
+        self.inner.listeners.lock().clear();
+        let streams: Vec<Arc<Shared>> = self.inner.streams.lock().values().cloned().collect();
+        for shared in streams {
+            shared.fail(io::ErrorKind::ConnectionAborted);
+        }
+    }
+    fn remove(&self, shared: &Arc<Shared>) {
+        let mut streams = self.inner.streams.lock();
+        if let Some(v) = streams.get(&shared.key) {
+            if Arc::ptr_eq(v, shared) {
+                streams.remove(&shared.key);
+            }
+        }
+    }
+    fn spawn(&self, shared: Arc<Shared>) {
+        let manager = self.clone();
+        self.inner.runtime.spawn(async move {
+            manager.drive(&shared).await;
+            manager.remove(&shared);
+        });
+    }
+    /// 发送新数据和重传，直到连接结束
+    async fn drive(&self, shared: &Shared) {
+        let mut out: Vec<Outgoing> = Vec::with_capacity(WINDOW);
+        loop {
+            let rs = shared.state.lock().poll_send(&mut out);
+            for (seq, ack, flags, data) in out.drain(..) {
+                if let Err(e) = self.send(&shared.key, seq, ack, flags, &data) {
+                    log::warn!("stream发送失败 {:?},{:?}", shared.key, e);
+                }
+            }
+            match rs {
+                Ok(false) => {}
+                Ok(true) => return,
+                Err(e) => {
+                    if e.kind() != io::ErrorKind::ConnectionReset {
+                        let seq = shared.state.lock().next_seq();
+                        let _ = self.send(&shared.key, seq, None, RST, &[]);
+                    }
+                    shared.fail(e.kind());
+                    return;
+                }
+            }
+            tokio::select! {
+                _ = shared.notify.notified() => {}
+                _ = tokio::time::sleep(RTO / 3) => {}
+            }
+        }
+    }
+    fn send(
+        &self,
+        key: &StreamKey,
+        seq: u32,
+        ack: Option<u32>,
+        flags: u8,
+        data: &[u8],
+    ) -> io::Result<()> {
+        let current_device = self.inner.current_device.load();
+        let mut net_packet =
+            NetPacket::new_encrypt(vec![0u8; 12 + HEAD_LEN + data.len() + ENCRYPTION_RESERVED])?;
+        net_packet.set_version(Version::V1);
+        net_packet.set_protocol(Protocol::OtherTurn);
+        net_packet.set_transport_protocol(other_turn_packet::Protocol::Stream.into());
+        net_packet.first_set_ttl(MAX_TTL);
+        net_packet.set_source(current_device.virtual_ip());
+        net_packet.set_destination(key.0);
+        let mut packet = StreamPacket::new(net_packet.payload_mut())?;
+        packet.set_source_port(key.1);
+        packet.set_destination_port(key.2);
+        packet.set_seq(seq);
+        match ack {
+            Some(ack) => {
+                packet.set_ack(ack);
+                packet.set_flags(flags | ACK);
+            }
+            None => packet.set_flags(flags),
+        }
+        packet.payload_mut().copy_from_slice(data);
+        self.inner.client_cipher.encrypt_ipv4(&mut net_packet)?;
+        self.inner.sender.mark_active(&key.0);
+        //优先发到直连到地址
+        if self
+            .inner
+            .sender
+            .try_send_by_id(net_packet.buffer(), &key.0)
+            .is_err()
+        {
+            self.inner
+                .sender
+                .send_main(net_packet.buffer(), current_device.connect_server)?;
+        }
+        Ok(())
+    }
+}
+
+/// 监听隧道内的端口，丢弃后停止监听
+pub struct VntListener {
+    port: u16,
+    receiver: Receiver<VntStream>,
+    manager: StreamManager,
+}
+
+impl VntListener {
+    pub fn local_port(&self) -> u16 {
+        self.port
+    }
+    pub async fn accept(&mut self) -> io::Result<VntStream> {
+        self.receiver
+            .recv()
+            .await
+            .ok_or_else(|| io::Error::new(io::ErrorKind::ConnectionAborted, "listener closed"))
+    }
+}
+
+impl Drop for VntListener {
+    fn drop(&mut self) {
+        self.manager.inner.listeners.lock().remove(&self.port);
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::net::Ipv4Addr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::Instant;
+
+use parking_lot::Mutex;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::Notify;
+
+use crate::protocol::stream_packet::{StreamPacket, ACK, FIN, SYN};
+use crate::stream::{StreamKey, BUF_LIMIT, MAX_RETRIES, MSS, RTO, WINDOW};
+
+/// a在b之后，序号会回绕
+fn seq_after(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) > 0
+}
+
+struct Segment {
+    seq: u32,
+    flags: u8,
+    data: Vec<u8>,
+    sent: Option<Instant>,
+    retries: u32,
+}
+
+impl Segment {
+    fn end(&self) -> u32 {
+        let len = self.data.len() as u32 + u32::from(self.flags & (SYN | FIN) != 0);
+        self.seq.wrapping_add(len)
+    }
+}
+
+/// 待发送的分段 (序号,确认号,标志,数据)
+pub(crate) type Outgoing = (u32, Option<u32>, u8, Vec<u8>);
+
+pub(crate) struct State {
+    /// 本端SYN已被确认，可以发送数据
+    established: bool,
+    next_seq: u32,
+    send_buf: VecDeque<u8>,
+    unacked: VecDeque<Segment>,
+    /// 本端不再写入
+    shutdown: bool,
+    fin_queued: bool,
+    /// 本端句柄已丢弃
+    dropped: bool,
+    write_waker: Option<Waker>,
+    /// 期望收到的下一个序号，收到对端SYN之前为None
+    recv_next: Option<u32>,
+    recv_buf: VecDeque<u8>,
+    out_of_order: BTreeMap<u32, (Vec<u8>, bool)>,
+    fin_received: bool,
+    read_waker: Option<Waker>,
+    error: Option<io::ErrorKind>,
+}
+
+impl State {
+    fn new(isn: u32) -> Self {
+        let mut state = Self {
+            established: false,
+            next_seq: isn,
+            send_buf: VecDeque::new(),
+            unacked: VecDeque::new(),
+            shutdown: false,
+            fin_queued: false,
+            dropped: false,
+            write_waker: None,
+            recv_next: None,
+            recv_buf: VecDeque::new(),
+            out_of_order: BTreeMap::new(),
+            fin_received: false,
+            read_waker: None,
+            error: None,
+        };
+        state.push_segment(SYN, Vec::new(), None);
+        state
+    }
+    fn push_segment(&mut self, flags: u8, data: Vec<u8>, sent: Option<Instant>) {
+        let segment = Segment {
+            seq: self.next_seq,
+            flags,
+            data,
+            sent,
+            retries: 0,
+        };
+        self.next_seq = segment.end();
+        self.unacked.push_back(segment);
+    }
+    pub(crate) fn next_seq(&self) -> u32 {
+        self.next_seq
+    }
+    pub(crate) fn recv_next(&self) -> Option<u32> {
+        self.recv_next
+    }
+    /// 处理对端的分段，返回是否需要回复确认
+    pub(crate) fn on_packet(&mut self, packet: &StreamPacket<&[u8]>) -> bool {
+        let flags = packet.flags();
+        if flags & ACK != 0 {
+            self.on_ack(packet.ack());
+        }
+        if flags & SYN != 0 {
+            if self.recv_next.is_none() {
+                self.recv_next = Some(packet.seq().wrapping_add(1));
+            }
+            return true;
+        }
+        let recv_next = match self.recv_next {
+            None => return false,
+            Some(recv_next) => recv_next,
+        };
+        let data = packet.payload();
+        let fin = flags & FIN != 0;
+        if data.is_empty() && !fin {
+            //纯确认
+            return false;
+        }
+        let seq = packet.seq();
+        if seq == recv_next {
+            if self.recv_buf.len() + data.len() > BUF_LIMIT {
+                //缓冲区满了先不确认，等对端重传
+                return true;
+            }
+            self.accept(data, fin);
+            while let Some(next) = self.recv_next {
+                match self.out_of_order.remove(&next) {
+                    Some((data, fin)) => self.accept(&data, fin),
+                    None => break,
+                }
+            }
+            // 序号已经过去的乱序分段不再需要
+            if let Some(next) = self.recv_next {
+                self.out_of_order.retain(|seq, _| seq_after(*seq, next));
+            }
+            if let Some(waker) = self.read_waker.take() {
+                waker.wake();
+            }
+        } else if seq_after(seq, recv_next) && self.out_of_order.len() < WINDOW * 2 {
+            self.out_of_order
+                .entry(seq)
+                .or_insert_with(|| (data.to_vec(), fin));
+        }
+        true
+    }
+    fn accept(&mut self, data: &[u8], fin: bool) {
+        if self.fin_received {
+            return;
+        }
+        self.recv_buf.extend(data);
+        let mut next = self.recv_next.unwrap_or(0).wrapping_add(data.len() as u32);
+        if fin {
+            self.fin_received = true;
+            next = next.wrapping_add(1);
+        }
+        self.recv_next = Some(next);
+    }
+    fn on_ack(&mut self, ack: u32) {
+        while let Some(segment) = self.unacked.front() {
+            if seq_after(segment.end(), ack) {
+                break;
+            }
+            if segment.flags & SYN != 0 {
+                self.established = true;
+            }
+            self.unacked.pop_front();
+        }
+    }
+    /// 收集需要发送和重传的分段，返回连接是否已经结束
+    pub(crate) fn poll_send(&mut self, out: &mut Vec<Outgoing>) -> io::Result<bool> {
+        if let Some(kind) = self.error {
+            return Err(kind.into());
+        }
+        let now = Instant::now();
+        let ack = self.recv_next;
+        for segment in self.unacked.iter_mut() {
+            if let Some(sent) = segment.sent {
+                if now.duration_since(sent) < RTO {
+                    continue;
+                }
+                segment.retries += 1;
+                if segment.retries > MAX_RETRIES {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "retransmit timeout",
+                    ));
+                }
+            }
+            segment.sent = Some(now);
+            out.push((segment.seq, ack, segment.flags, segment.data.clone()));
+        }
+        while self.established && self.unacked.len() < WINDOW {
+            if !self.send_buf.is_empty() {
+                let len = self.send_buf.len().min(MSS);
+                let data: Vec<u8> = self.send_buf.drain(..len).collect();
+                out.push((self.next_seq, ack, 0, data.clone()));
+                self.push_segment(0, data, Some(now));
+            } else if self.shutdown && !self.fin_queued {
+                out.push((self.next_seq, ack, FIN, Vec::new()));
+                self.push_segment(FIN, Vec::new(), Some(now));
+                self.fin_queued = true;
+            } else {
+                break;
+            }
+        }
+        if self.send_buf.len() < BUF_LIMIT {
+            if let Some(waker) = self.write_waker.take() {
+                waker.wake();
+            }
+        }
+        //本端FIN已被确认，并且对端也关闭了或者本端不再读取
+        Ok(self.fin_queued && self.unacked.is_empty() && (self.fin_received || self.dropped))
+    }
+}
+
+pub(crate) struct Shared {
+    pub(crate) key: StreamKey,
+    pub(crate) state: Mutex<State>,
+    /// 唤醒发送任务
+    pub(crate) notify: Notify,
+    /// 连接建立或失败
+    ready: Notify,
+}
+
+impl Shared {
+    pub(crate) fn new(key: StreamKey, isn: u32) -> Arc<Self> {
+        Arc::new(Self {
+            key,
+            state: Mutex::new(State::new(isn)),
+            notify: Notify::new(),
+            ready: Notify::new(),
+        })
+    }
+    /// 被动打开的连接不需要等待SYN确认
+    pub(crate) fn accepted(key: StreamKey, isn: u32, peer_isn: u32) -> Arc<Self> {
+        let shared = Self::new(key, isn);
+        {
+            let mut state = shared.state.lock();
+            state.established = true;
+            state.recv_next = Some(peer_isn.wrapping_add(1));
+        }
+        shared
+    }
+    pub(crate) fn on_packet(&self, packet: &StreamPacket<&[u8]>) -> bool {
+        let rs = self.state.lock().on_packet(packet);
+        self.ready.notify_waiters();
+        self.notify.notify_one();
+        rs
+    }
+    pub(crate) fn fail(&self, kind: io::ErrorKind) {
+        {
+            let mut state = self.state.lock();
+            if state.error.is_none() {
+                state.error = Some(kind);
+            }
+            if let Some(waker) = state.read_waker.take() {
+                waker.wake();
+            }
+            if let Some(waker) = state.write_waker.take() {
+                waker.wake();
+            }
+        }
+        self.ready.notify_waiters();
+        self.notify.notify_one();
+    }
+    pub(crate) async fn wait_established(&self) -> io::Result<()> {
+        loop {
+            let notified = self.ready.notified();
+            {
+                let state = self.state.lock();
+                if let Some(kind) = state.error {
+                    return Err(kind.into());
+                }
+                if state.established {
+                    return Ok(());
+                }
+            }
+            notified.await;
+        }
+    }
+}
+
+/// 隧道内的可靠字节流，由connect或者VntListener::accept得到
+pub struct VntStream {
+    shared: Arc<Shared>,
+}
+
+impl VntStream {
+    pub(crate) fn new(shared: Arc<Shared>) -> Self {
+        Self { shared }
+    }
+    /// 对端虚拟ip和端口
+    pub fn peer_addr(&self) -> (Ipv4Addr, u16) {
+        (self.shared.key.0, self.shared.key.2)
+    }
+    pub fn local_port(&self) -> u16 {
+        self.shared.key.1
+    }
+}
+
+impl AsyncRead for VntStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut state = self.shared.state.lock();
+        if !state.recv_buf.is_empty() {
+            let len = buf.remaining().min(state.recv_buf.len());
+            let (first, second) = state.recv_buf.as_slices();
+            if len <= first.len() {
+                buf.put_slice(&first[..len]);
+            } else {
+                buf.put_slice(first);
+                buf.put_slice(&second[..len - first.len()]);
+            }
+            state.recv_buf.drain(..len);
+            return Poll::Ready(Ok(()));
+        }
+        if state.fin_received {
+            return Poll::Ready(Ok(()));
+        }
+        if let Some(kind) = state.error {
+            return Poll::Ready(Err(kind.into()));
+        }
+        state.read_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl AsyncWrite for VntStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut state = self.shared.state.lock();
+        if let Some(kind) = state.error {
+            return Poll::Ready(Err(kind.into()));
+        }
+        if state.shutdown {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        let room = BUF_LIMIT.saturating_sub(state.send_buf.len());
+        if room == 0 {
+            state.write_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let len = room.min(buf.len());
+        state.send_buf.extend(&buf[..len]);
+        drop(state);
+        self.shared.notify.notify_one();
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.shared.state.lock().shutdown = true;
+        self.shared.notify.notify_one();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for VntStream {
+    fn drop(&mut self) {
+        {
+            let mut state = self.shared.state.lock();
+            state.shutdown = true;
+            state.dropped = true;
+        }
+        self.shared.notify.notify_one();
+    }
+}
@@ -0,0 +1,80 @@
+use std::net::Ipv4Addr;
+
+use crate::remote_config::format_cidr;
+use crate::route_conflict::contains;
+
+/// 虚拟网段和本机已有网段(系统路由表中的局域网、其他vpn等)重叠的部分，
+/// 重叠时访问这部分虚拟ip的流量会被路由到局域网，虚拟网络看起来不通
+pub fn collisions(network: u32, mask: u32, system_routes: &[(u32, u32, String)]) -> Vec<String> {
+    let route = (network & mask, mask);
+    system_routes
+        .iter()
+        .filter(|(d, m, _)| contains(route, (*d, *m)) || contains((*d, *m), route))
+        .map(|(d, m, name)| format!("{} dev {}", format_cidr(*d, *m), name))
+        .collect()
+}
+
+/// 把ip的网络部分替换为to，主机部分不变
+pub fn map(ip: Ipv4Addr, to: Ipv4Addr, mask: Ipv4Addr) -> Ipv4Addr {
+    let mask = u32::from(mask);
+    Ipv4Addr::from((u32::from(to) & mask) | (u32::from(ip) & !mask))
+}
+
+/// 网段一对一转换，源地址和目的地址落在from网段的替换为to网段中的相同主机号，
+/// 同时更新ip头部和tcp/udp伪头部的校验和。icmp差错报文中引用的原报文头部不转换
+pub fn translate(ipv4: &mut [u8], from: u32, to: u32, mask: u32) {
+    if ipv4.len() < 20 || ipv4[0] >> 4 != 4 {
+        return;
+    }
+    let header_len = (ipv4[0] & 0x0f) as usize * 4;
+    if header_len < 20 || header_len > ipv4.len() {
+        return;
+    }
+    let protocol = ipv4[9];
+    //非首个分片没有tcp/udp头部
+    let first_fragment = u16::from_be_bytes([ipv4[6], ipv4[7]]) & 0x1fff == 0;
+    for offset in [12, 16] {
+        let old = u32::from_be_bytes([
+            ipv4[offset],
+            ipv4[offset + 1],
+            ipv4[offset + 2],
+            ipv4[offset + 3],
+        ]);
+        if old & mask != from & mask {
+            continue;
+        }
+        let new = (to & mask) | (old & !mask);
+        ipv4[offset..offset + 4].copy_from_slice(&new.to_be_bytes());
+        adjust(&mut ipv4[10..12], old, new, false);
+        if !first_fragment {
+            continue;
+        }
+        let payload = &mut ipv4[header_len..];
+        match protocol {
+            //tcp
+            6 if payload.len() >= 18 => adjust(&mut payload[16..18], old, new, false),
+            //udp，校验和为0表示没有校验
+            17 if payload.len() >= 8 && payload[6..8] != [0, 0] => {
+                adjust(&mut payload[6..8], old, new, true)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 增量更新校验和(RFC 1624)：HC' = ~(~HC + ~m + m')
+fn adjust(checksum: &mut [u8], old: u32, new: u32, udp: bool) {
+    let mut sum = (!u16::from_be_bytes([checksum[0], checksum[1]])) as u32;
+    for (o, n) in [(old >> 16, new >> 16), (old & 0xffff, new & 0xffff)] {
+        sum += (!(o as u16)) as u32 + n;
+    }
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    let mut rs = !(sum as u16);
+    //udp中0表示没有校验，计算结果为0时使用0xffff
+    if udp && rs == 0 {
+        rs = 0xffff;
+    }
+    checksum.copy_from_slice(&rs.to_be_bytes());
+}
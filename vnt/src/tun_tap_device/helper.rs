@@ -0,0 +1,307 @@
+//! 特权辅助进程：以root运行的小进程负责创建网卡和修改路由，主进程以普通用户运行，
+//! 通过unix socket请求这些操作，网卡的fd通过SCM_RIGHTS传给主进程。
+//! 协议为一行一个请求，应答为`ok ...`或`err ...`
+
+use std::fs::Permissions;
+use std::io::{BufRead, BufReader, Write};
+use std::net::Ipv4Addr;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::str::FromStr;
+use std::{io, mem, ptr, thread};
+
+use parking_lot::Mutex;
+use tun::platform::linux::Device;
+use tun::Device as _;
+
+use crate::tun_tap_device::linux::{
+    add_policy_rule, add_route, configure, delete_policy_rule, delete_route, open_device,
+    TAP_INTERFACE_NAME, TUN_INTERFACE_NAME,
+};
+use crate::tun_tap_device::DeviceType;
+
+/// 主进程和辅助进程的连接，连接断开后辅助进程清理网卡和策略路由
+pub struct HelperClient {
+    stream: Mutex<UnixStream>,
+}
+
+impl HelperClient {
+    pub fn connect(path: &str) -> io::Result<Self> {
+        let stream = UnixStream::connect(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("连接特权辅助进程{}失败 {}", path, e)))?;
+        Ok(Self {
+            stream: Mutex::new(stream),
+        })
+    }
+    fn call(&self, request: &str) -> io::Result<(String, Option<RawFd>)> {
+        let stream = self.stream.lock();
+        (&*stream).write_all(format!("{}\n", request).as_bytes())?;
+        let (line, fd) = recv_line(&stream)?;
+        match line.split_once(' ').unwrap_or((&line, "")) {
+            ("ok", reply) => Ok((reply.to_string(), fd)),
+            (_, e) => {
+                if let Some(fd) = fd {
+                    unsafe {
+                        libc::close(fd);
+                    }
+                }
+                Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("特权辅助进程:{}", e),
+                ))
+            }
+        }
+    }
+    /// 创建网卡，route_table非0时同时添加策略路由
+    pub fn create(
+        &self,
+        device_type: DeviceType,
+        name: Option<&str>,
+        address: Ipv4Addr,
+        netmask: Ipv4Addr,
+        gateway: Ipv4Addr,
+        mtu: u16,
+        route_table: u32,
+        multi_queue: bool,
+    ) -> io::Result<Device> {
+        let (device_type, name) = match device_type {
+            DeviceType::Tun => ("tun", name.unwrap_or(TUN_INTERFACE_NAME)),
+            DeviceType::Tap => ("tap", name.unwrap_or(TAP_INTERFACE_NAME)),
+        };
+        let (reply, fd) = self.call(&format!(
+            "create {} {} {} {} {} {} {} {}",
+            device_type, name, address, netmask, gateway, mtu, route_table, multi_queue as u8
+        ))?;
+        let fd = fd.ok_or_else(|| io::Error::new(io::ErrorKind::Other, "没有收到网卡fd"))?;
+        let (name, packet_information) = reply.split_once(' ').unwrap_or((&reply, "0"));
+        Device::from_fd(fd, name, packet_information == "1")
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))
+    }
+    pub fn configure(
+        &self,
+        address: Ipv4Addr,
+        netmask: Ipv4Addr,
+        gateway: Ipv4Addr,
+    ) -> io::Result<()> {
+        self.call(&format!("configure {} {} {}", address, netmask, gateway))?;
+        Ok(())
+    }
+    /// 添加或删除经过本网卡的路由
+    pub fn route(&self, add: bool, address: Ipv4Addr, netmask: Ipv4Addr) -> io::Result<()> {
+        let op = if add { "add" } else { "del" };
+        self.call(&format!("route {} {} {}", op, address, netmask))?;
+        Ok(())
+    }
+}
+
+/// 以root运行，在path上等待主进程连接。socket文件权限为0660，
+/// 所属用户和组设为owner/group，只有它们(和root)可以请求创建网卡和修改路由
+pub fn serve(path: &str, owner: Option<u32>, group: Option<u32>) -> io::Result<()> {
+    let _ = std::fs::remove_file(path);
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, Permissions::from_mode(0o660))?;
+    if owner.is_some() || group.is_some() {
+        let c_path = std::ffi::CString::new(path)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let uid = owner.unwrap_or(u32::MAX);
+        let gid = group.unwrap_or(u32::MAX);
+        //-1表示不修改
+        if unsafe { libc::chown(c_path.as_ptr(), uid, gid) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    log::info!("特权辅助进程监听 {}", path);
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::warn!("accept {:?}", e);
+                continue;
+            }
+        };
+        thread::Builder::new()
+            .name("helperSession".into())
+            .spawn(move || {
+                if let Err(e) = session(stream) {
+                    log::warn!("特权辅助进程会话结束 {:?}", e);
+                }
+            })?;
+    }
+    Ok(())
+}
+
+fn session(stream: UnixStream) -> io::Result<()> {
+    let reader = BufReader::new(stream.try_clone()?);
+    let mut device: Option<Device> = None;
+    let mut route_table = 0;
+    let mut rs = Ok(());
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                rs = Err(e);
+                break;
+            }
+        };
+        let reply = match handle(&line, &mut device, &mut route_table) {
+            Ok((reply, fd)) => send_line(&stream, &format!("ok {}", reply), fd),
+            Err(e) => {
+                log::warn!("请求 {} 失败 {:?}", line, e);
+                send_line(&stream, &format!("err {}", e), None)
+            }
+        };
+        if let Err(e) = reply {
+            rs = Err(e);
+            break;
+        }
+    }
+    //主进程已退出，网卡在所有fd关闭后由内核删除，经过它的路由随之删除
+    if route_table != 0 {
+        delete_policy_rule(route_table);
+    }
+    drop(device);
+    rs
+}
+
+fn handle(
+    line: &str,
+    device: &mut Option<Device>,
+    route_table: &mut u32,
+) -> io::Result<(String, Option<RawFd>)> {
+    let items: Vec<&str> = line.split_whitespace().collect();
+    match items.as_slice() {
+        ["create", device_type, name, address, netmask, gateway, mtu, table, multi_queue] => {
+            if device.is_some() {
+                return Err(invalid("device already created"));
+            }
+            let device_type = match *device_type {
+                "tun" => DeviceType::Tun,
+                "tap" => DeviceType::Tap,
+                _ => return Err(invalid(device_type)),
+            };
+            let dev = open_device(
+                device_type,
+                Some(*name),
+                parse(address)?,
+                parse(netmask)?,
+                parse(gateway)?,
+                parse(mtu)?,
+                *multi_queue == "1",
+                None,
+                None,
+            )?;
+            let table: u32 = parse(table)?;
+            if table != 0 {
+                add_policy_rule(table)?;
+                *route_table = table;
+            }
+            let fd = dev.queue(0).map(|queue| queue.reader().as_raw_fd());
+            let reply = format!("{} {}", dev.name(), dev.has_packet_information() as u8);
+            *device = Some(dev);
+            Ok((reply, fd))
+        }
+        ["configure", address, netmask, gateway] => {
+            let dev = device.as_mut().ok_or_else(|| invalid("no device"))?;
+            configure(dev, parse(address)?, parse(netmask)?, parse(gateway)?)?;
+            Ok((String::new(), None))
+        }
+        ["route", op, address, netmask] => {
+            //只能修改经过自己网卡的路由
+            let dev = device.as_ref().ok_or_else(|| invalid("no device"))?;
+            let (address, netmask) = (parse(address)?, parse(netmask)?);
+            match *op {
+                "add" => add_route(dev.name(), address, netmask, *route_table)?,
+                "del" => delete_route(dev.name(), address, netmask, *route_table),
+                _ => return Err(invalid(op)),
+            }
+            Ok((String::new(), None))
+        }
+        _ => Err(invalid(line)),
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("invalid {}", msg))
+}
+
+fn parse<T: FromStr>(value: &str) -> io::Result<T> {
+    value.parse().map_err(|_| invalid(value))
+}
+
+fn send_line(stream: &UnixStream, line: &str, fd: Option<RawFd>) -> io::Result<()> {
+    let data = format!("{}\n", line);
+    let mut iov = libc::iovec {
+        iov_base: data.as_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+    //u64保证对齐
+    let mut control = [0u64; 8];
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    if let Some(fd) = fd {
+        unsafe {
+            msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+            msg.msg_controllen = libc::CMSG_SPACE(mem::size_of::<RawFd>() as u32) as _;
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<RawFd>() as u32) as _;
+            ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut RawFd, fd);
+        }
+    }
+    let len = unsafe { libc::sendmsg(stream.as_raw_fd(), &msg, libc::MSG_NOSIGNAL) };
+    if len < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let len = len as usize;
+    if len < data.len() {
+        //fd已随第一段发出，剩余部分直接写
+        (&*stream).write_all(&data.as_bytes()[len..])?;
+    }
+    Ok(())
+}
+
+fn recv_line(stream: &UnixStream) -> io::Result<(String, Option<RawFd>)> {
+    let mut line = Vec::new();
+    let mut fd = None;
+    loop {
+        let mut buf = [0u8; 1024];
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+        let mut control = [0u64; 8];
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = mem::size_of_val(&control) as _;
+        let len = unsafe { libc::recvmsg(stream.as_raw_fd(), &mut msg, libc::MSG_CMSG_CLOEXEC) };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "特权辅助进程已断开",
+            ));
+        }
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                    fd = Some(ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const RawFd));
+                }
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+        }
+        line.extend_from_slice(&buf[..len as usize]);
+        if line.ends_with(b"\n") {
+            break;
+        }
+    }
+    line.pop();
+    Ok((String::from_utf8_lossy(&line).into_owned(), fd))
+}
@@ -1,3 +1,4 @@
+use crate::tun_tap_device::helper::HelperClient;
 use crate::tun_tap_device::linux_mac::DeviceW;
 use crate::tun_tap_device::{DeviceReader, DeviceType, DeviceWriter, DriverInfo};
 use parking_lot::Mutex;
@@ -19,40 +20,104 @@ impl DeviceWriter {
         _old_netmask: Ipv4Addr,
         _old_gateway: Ipv4Addr,
     ) -> io::Result<()> {
-        let mut config = tun::Configuration::default();
-        let broadcast_address =
-            (!u32::from_be_bytes(netmask.octets())) | u32::from_be_bytes(gateway.octets());
-        let broadcast_address = Ipv4Addr::from(broadcast_address);
-        config
-            .destination(gateway)
-            .address(address)
-            .netmask(netmask)
-            .broadcast(broadcast_address)
-            // .queues(2)
-            .up();
         let mut dev = self.lock.lock();
-        if let Err(e) = dev.configure(&config) {
-            return Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e)));
+        let helper = self.helper.as_deref();
+        match helper {
+            Some(helper) => helper.configure(address, netmask, gateway)?,
+            None => configure(&mut dev, address, netmask, gateway)?,
         }
         let name = dev.name();
-        for (address, netmask) in &self.in_ips {
-            add_route(name, *address, *netmask)?;
+        add_routes(helper, name, &self.in_ips, self.route_table)?;
+        for (address, netmask) in self.extra_routes.lock().iter() {
+            route(helper, true, name, *address, *netmask, self.route_table)?;
         }
-        // 当前网段路由
-        // add_route(name, address, netmask)?;
-        // 广播和组播路由
-        add_route(name, Ipv4Addr::BROADCAST, Ipv4Addr::BROADCAST)?;
-        add_route(
-            name,
-            Ipv4Addr::from([224, 0, 0, 0]),
-            Ipv4Addr::from([240, 0, 0, 0]),
-        )?;
         return Ok(());
     }
+    /// 替换服务端下发的路由，只增删有变化的部分
+    pub fn set_extra_routes(
+        &self,
+        routes: Vec<(Ipv4Addr, Ipv4Addr)>,
+        _gateway: Ipv4Addr,
+    ) -> io::Result<()> {
+        let dev = self.lock.lock();
+        let name = dev.name();
+        let mut extra_routes = self.extra_routes.lock();
+        let helper = self.helper.as_deref();
+        for (address, netmask) in extra_routes.iter() {
+            if !routes.contains(&(*address, *netmask)) {
+                if let Err(e) = route(helper, false, name, *address, *netmask, self.route_table) {
+                    log::warn!("删除路由失败 {}/{} {:?}", address, netmask, e);
+                }
+            }
+        }
+        for (address, netmask) in &routes {
+            if !extra_routes.contains(&(*address, *netmask)) {
+                route(helper, true, name, *address, *netmask, self.route_table)?;
+            }
+        }
+        *extra_routes = routes;
+        Ok(())
+    }
+}
+
+fn add_routes(
+    helper: Option<&HelperClient>,
+    name: &str,
+    in_ips: &[(Ipv4Addr, Ipv4Addr)],
+    table: u32,
+) -> io::Result<()> {
+    for (address, netmask) in in_ips {
+        route(helper, true, name, *address, *netmask, table)?;
+    }
+    // 当前网段路由
+    // add_route(name, address, netmask, table)?;
+    // 广播和组播路由
+    route(
+        helper,
+        true,
+        name,
+        Ipv4Addr::BROADCAST,
+        Ipv4Addr::BROADCAST,
+        table,
+    )?;
+    route(
+        helper,
+        true,
+        name,
+        Ipv4Addr::from([224, 0, 0, 0]),
+        Ipv4Addr::from([240, 0, 0, 0]),
+        table,
+    )
 }
 
-pub fn add_route(name: &str, address: Ipv4Addr, netmask: Ipv4Addr) -> io::Result<()> {
-    let route_add_str: String = format!("ip route add {:?}/{:?} dev {}", address, netmask, name);
+/// 有特权辅助进程时由它修改路由，否则直接修改
+fn route(
+    helper: Option<&HelperClient>,
+    add: bool,
+    name: &str,
+    address: Ipv4Addr,
+    netmask: Ipv4Addr,
+    table: u32,
+) -> io::Result<()> {
+    match helper {
+        Some(helper) => helper.route(add, address, netmask),
+        None if add => add_route(name, address, netmask, table),
+        None => {
+            delete_route(name, address, netmask, table);
+            Ok(())
+        }
+    }
+}
+
+pub fn add_route(name: &str, address: Ipv4Addr, netmask: Ipv4Addr, table: u32) -> io::Result<()> {
+    let route_add_str: String = if table == 0 {
+        format!("ip route add {:?}/{:?} dev {}", address, netmask, name)
+    } else {
+        format!(
+            "ip route replace {:?}/{:?} dev {} table {}",
+            address, netmask, name, table
+        )
+    };
     let route_add_out = Command::new("sh")
         .arg("-c")
         .arg(&route_add_str)
@@ -70,69 +135,81 @@ pub fn add_route(name: &str, address: Ipv4Addr, netmask: Ipv4Addr) -> io::Result
     Ok(())
 }
 
+pub(crate) fn delete_route(name: &str, address: Ipv4Addr, netmask: Ipv4Addr, table: u32) {
+    let cmd = if table == 0 {
+        format!("ip route del {:?}/{:?} dev {}", address, netmask, name)
+    } else {
+        format!(
+            "ip route del {:?}/{:?} dev {} table {}",
+            address, netmask, name, table
+        )
+    };
+    if let Err(e) = Command::new("sh").arg("-c").arg(&cmd).output() {
+        log::warn!("cmd:{},{:?}", cmd, e);
+    }
+}
+
 pub fn create_device(
     device_type: DeviceType,
+    name: Option<&str>,
     address: Ipv4Addr,
     netmask: Ipv4Addr,
     gateway: Ipv4Addr,
     in_ips: Vec<(Ipv4Addr, Ipv4Addr)>,
     mtu: u16,
+    route_table: u32,
+    multi_queue: bool,
+    owner: Option<u32>,
+    group: Option<u32>,
+    helper_socket: Option<&str>,
 ) -> io::Result<(DeviceWriter, DeviceReader, DriverInfo)> {
-    let mut config = tun::Configuration::default();
-    let broadcast_address =
-        (!u32::from_be_bytes(netmask.octets())) | u32::from_be_bytes(gateway.octets());
-    let broadcast_address = Ipv4Addr::from(broadcast_address);
-    config
-        .destination(gateway)
-        .address(address)
-        .netmask(netmask)
-        .mtu(mtu.into())
-        .broadcast(broadcast_address)
-        // .queues(2) 用多个队列有兼容性问题
-        .up();
-    match device_type {
-        DeviceType::Tun => {
-            config.name(TUN_INTERFACE_NAME);
+    let (dev, helper) = match helper_socket {
+        Some(path) => {
+            let helper = HelperClient::connect(path)?;
+            let dev = helper.create(
+                device_type,
+                name,
+                address,
+                netmask,
+                gateway,
+                mtu,
+                route_table,
+                multi_queue,
+            )?;
+            (dev, Some(Arc::new(helper)))
         }
-        DeviceType::Tap => {
-            config.name(TAP_INTERFACE_NAME);
-            config.layer(tun::Layer::L2);
+        None => {
+            let dev = open_device(
+                device_type,
+                name,
+                address,
+                netmask,
+                gateway,
+                mtu,
+                multi_queue,
+                owner,
+                group,
+            )?;
+            (dev, None)
         }
-    }
-    let dev = tun::create(&config).expect("tun/tap failed to create");
+    };
     let packet_information = dev.has_packet_information();
     let queue = dev.queue(0).unwrap();
     let reader = queue.reader();
     let writer = queue.writer();
     let name = dev.name();
-    for (address, netmask) in &in_ips {
-        add_route(name, *address, *netmask)?;
+    add_routes(helper.as_deref(), name, &in_ips, route_table)?;
+    //辅助进程创建网卡时已经添加了策略路由
+    if route_table != 0 && helper.is_none() {
+        add_policy_rule(route_table)?;
     }
-    // 当前网段路由
-    // add_route(name, address, netmask)?;
-    // 广播和组播路由
-    add_route(name, Ipv4Addr::BROADCAST, Ipv4Addr::BROADCAST)?;
-    add_route(
-        name,
-        Ipv4Addr::from([224, 0, 0, 0]),
-        Ipv4Addr::from([240, 0, 0, 0]),
-    )?;
     let device_w = match device_type {
         DeviceType::Tun => DeviceW::Tun(writer),
         DeviceType::Tap => {
-            let get_mac_cmd = format!("cat /sys/class/net/{}/address", name);
-            let mac_out = Command::new("sh")
-                .arg("-c")
-                .arg(get_mac_cmd)
-                .output()
-                .expect("sh exec error!");
-            if !mac_out.status.success() {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("获取mac地址错误: {:?}", mac_out),
-                ));
-            }
-            let mac_str = String::from_utf8(mac_out.stdout).unwrap();
+            let mac_str = std::fs::read_to_string(format!("/sys/class/net/{}/address", name))
+                .map_err(|e| {
+                    io::Error::new(io::ErrorKind::Other, format!("获取mac地址错误: {:?}", e))
+                })?;
             let mut mac = [0; 6];
             let mut split = mac_str.split(":");
             for i in 0..6 {
@@ -147,21 +224,158 @@ pub fn create_device(
         version: String::new(),
         mac: None,
     };
-    Ok((
-        DeviceWriter::new(
-            device_w,
-            Arc::new(Mutex::new(dev)),
-            in_ips,
-            address,
-            packet_information,
+    #[allow(unused_mut)]
+    let mut device_writer = DeviceWriter::new(
+        device_w,
+        Arc::new(Mutex::new(dev)),
+        in_ips,
+        address,
+        packet_information,
+        route_table,
+        helper,
+    );
+    let device_reader = DeviceReader::new(reader);
+    #[cfg(feature = "io-uring")]
+    {
+        device_writer.uring_cancel = device_reader.uring_canceler();
+    }
+    Ok((device_writer, device_reader, driver_info))
+}
+
+/// 创建网卡并配置地址，特权辅助进程创建网卡也使用这个方法
+pub(crate) fn open_device(
+    device_type: DeviceType,
+    name: Option<&str>,
+    address: Ipv4Addr,
+    netmask: Ipv4Addr,
+    gateway: Ipv4Addr,
+    mtu: u16,
+    multi_queue: bool,
+    owner: Option<u32>,
+    group: Option<u32>,
+) -> io::Result<tun::platform::linux::Device> {
+    let mut config = tun::Configuration::default();
+    let broadcast_address =
+        (!u32::from_be_bytes(netmask.octets())) | u32::from_be_bytes(gateway.octets());
+    let broadcast_address = Ipv4Addr::from(broadcast_address);
+    config
+        .destination(gateway)
+        .address(address)
+        .netmask(netmask)
+        .mtu(mtu.into())
+        .broadcast(broadcast_address)
+        // .queues(2) 用多个队列有兼容性问题
+        .up();
+    match device_type {
+        DeviceType::Tun => {
+            config.name(name.unwrap_or(TUN_INTERFACE_NAME));
+        }
+        DeviceType::Tap => {
+            config.name(name.unwrap_or(TAP_INTERFACE_NAME));
+            config.layer(tun::Layer::L2);
+        }
+    }
+    config.platform(|config| {
+        config.multi_queue(multi_queue);
+        if let Some(owner) = owner {
+            config.owner(owner);
+        }
+        if let Some(group) = group {
+            config.group(group);
+        }
+    });
+    tun::create(&config).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::Other,
+            format!("tun/tap failed to create {:?}", e),
+        )
+    })
+}
+
+/// 修改网卡地址
+pub(crate) fn configure(
+    dev: &mut tun::platform::linux::Device,
+    address: Ipv4Addr,
+    netmask: Ipv4Addr,
+    gateway: Ipv4Addr,
+) -> io::Result<()> {
+    let mut config = tun::Configuration::default();
+    let broadcast_address =
+        (!u32::from_be_bytes(netmask.octets())) | u32::from_be_bytes(gateway.octets());
+    let broadcast_address = Ipv4Addr::from(broadcast_address);
+    config
+        .destination(gateway)
+        .address(address)
+        .netmask(netmask)
+        .broadcast(broadcast_address)
+        // .queues(2)
+        .up();
+    if let Err(e) = dev.configure(&config) {
+        return Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e)));
+    }
+    Ok(())
+}
+
+/// vnt添加的策略规则使用的优先级区间，按路由表编号分配，
+/// 删除时带上优先级只会匹配vnt自己添加的规则，不影响系统或其他程序(如wg-quick)的同类规则
+const RULE_PRIORITY_BASE: u32 = 20000;
+
+fn rule_priority(table: u32) -> u32 {
+    RULE_PRIORITY_BASE + (table % 5000) * 2
+}
+
+/// 策略路由：没有fwmark的流量查vnt的路由表，vnt自身的流量打上fwmark后走main表，
+/// main表中比默认路由更具体的路由优先，避免出口节点的默认路由和系统默认路由冲突
+fn policy_rules(table: u32) -> [String; 2] {
+    let priority = rule_priority(table);
+    [
+        format!("priority {} table main suppress_prefixlength 0", priority),
+        format!(
+            "priority {} not fwmark {} table {}",
+            priority + 1,
+            table,
+            table
         ),
-        DeviceReader::new(reader),
-        driver_info,
-    ))
+    ]
 }
 
-pub fn delete_device(_device_type: DeviceType) {
-    for name in [TUN_INTERFACE_NAME, TAP_INTERFACE_NAME] {
+pub(crate) fn add_policy_rule(table: u32) -> io::Result<()> {
+    // 先清理非正常退出时残留的规则
+    delete_policy_rule(table);
+    for rule in policy_rules(table) {
+        let cmd = format!("ip rule add {}", rule);
+        let out = Command::new("sh")
+            .arg("-c")
+            .arg(&cmd)
+            .output()
+            .expect("sh exec error!");
+        if !out.status.success() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("添加路由规则失败: cmd:{},out:{:?}", cmd, out),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// 只删除vnt添加的规则，路由表中的路由都经过vnt的网卡，网卡删除时由内核清理
+pub fn delete_policy_rule(table: u32) {
+    for rule in policy_rules(table) {
+        let cmd = format!("ip rule del {}", rule);
+        if let Err(e) = Command::new("sh").arg("-c").arg(&cmd).output() {
+            log::warn!("cmd:{},{:?}", cmd, e);
+        }
+    }
+}
+
+/// 指定了名称时只删除该网卡，不影响同一进程中其他组网的网卡
+pub fn delete_device(_device_type: DeviceType, name: Option<&str>) {
+    let names = match name {
+        Some(name) => vec![name],
+        None => vec![TUN_INTERFACE_NAME, TAP_INTERFACE_NAME],
+    };
+    for name in names {
         let cmd = format!("ip link delete {}", name);
         let delete_tun = Command::new("sh")
             .arg("-c")
@@ -33,7 +33,21 @@ pub struct DeviceWriter {
     writer: DeviceW,
     pub lock: Arc<Mutex<Device>>,
     pub in_ips: Vec<(Ipv4Addr, Ipv4Addr)>,
+    /// 服务端下发的路由，ip变化时需要重新添加
+    pub extra_routes: Arc<Mutex<Vec<(Ipv4Addr, Ipv4Addr)>>>,
     packet_information: bool,
+    /// 路由表，0表示main表
+    #[cfg(target_os = "linux")]
+    pub route_table: u32,
+    /// 以普通用户运行时，网卡和路由由特权辅助进程操作
+    #[cfg(target_os = "linux")]
+    pub helper: Option<Arc<crate::tun_tap_device::helper::HelperClient>>,
+    /// io_uring写入，不能使用时先等之前的写入完成再直接写
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    uring: Option<crate::util::uring::UringWriter>,
+    /// 关闭时唤醒阻塞在io_uring上的读
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    pub(crate) uring_cancel: Option<crate::util::uring::UringCanceler>,
 }
 
 impl DeviceWriter {
@@ -43,12 +57,25 @@ impl DeviceWriter {
         in_ips: Vec<(Ipv4Addr, Ipv4Addr)>,
         _ip: Ipv4Addr,
         packet_information: bool,
+        #[cfg(target_os = "linux")] route_table: u32,
+        #[cfg(target_os = "linux")] helper: Option<
+            Arc<crate::tun_tap_device::helper::HelperClient>,
+        >,
     ) -> Self {
         Self {
             writer,
             lock,
             in_ips,
+            extra_routes: Arc::new(Mutex::new(Vec::new())),
             packet_information,
+            #[cfg(target_os = "linux")]
+            route_table,
+            #[cfg(target_os = "linux")]
+            helper,
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            uring: crate::util::uring::UringWriter::new(64),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            uring_cancel: None,
         }
     }
 }
@@ -75,24 +102,41 @@ impl DeviceWriter {
         }
         Ok(())
     }
+    fn write_(&self, writer: &Writer, packet: &[u8]) -> io::Result<()> {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let Some(uring) = &self.uring {
+            use std::os::unix::io::AsRawFd;
+            if !self.packet_information && uring.write(writer.as_raw_fd(), packet) {
+                return Ok(());
+            }
+        }
+        Self::write(self.packet_information, writer, packet)
+    }
     ///tun网卡写入ipv4数据
     pub fn write_ipv4_tun(&self, buf: &[u8]) -> io::Result<()> {
         match &self.writer {
-            DeviceW::Tun(writer) => Self::write(self.packet_information, writer, buf),
+            DeviceW::Tun(writer) => self.write_(writer, buf),
             DeviceW::Tap(_) => Err(io::Error::from(io::ErrorKind::Unsupported)),
         }
     }
+    /// tap网卡的mac地址，tun网卡返回None
+    pub fn tap_mac(&self) -> Option<[u8; 6]> {
+        match &self.writer {
+            DeviceW::Tun(_) => None,
+            DeviceW::Tap((_, mac)) => Some(*mac),
+        }
+    }
     /// tap网卡写入以太网帧
     pub fn write_ethernet_tap(&self, buf: &[u8]) -> io::Result<()> {
         match &self.writer {
             DeviceW::Tun(_) => Err(io::Error::from(io::ErrorKind::Unsupported)),
-            DeviceW::Tap((writer, _)) => Self::write(self.packet_information, writer, buf),
+            DeviceW::Tap((writer, _)) => self.write_(writer, buf),
         }
     }
     ///写入ipv4数据，头部必须留14字节，给tap写入以太网帧头
     pub fn write_ipv4(&self, buf: &mut [u8]) -> io::Result<()> {
         match &self.writer {
-            DeviceW::Tun(writer) => Self::write(self.packet_information, writer, &buf[14..]),
+            DeviceW::Tun(writer) => self.write_(writer, &buf[14..]),
             DeviceW::Tap((writer, mac)) => {
                 let source_mac = [
                     buf[14 + 12],
@@ -106,11 +150,25 @@ impl DeviceWriter {
                 ethernet_packet.set_source(&source_mac);
                 ethernet_packet.set_destination(mac);
                 ethernet_packet.set_protocol(ethernet::protocol::Protocol::Ipv4);
-                Self::write(self.packet_information, writer, &ethernet_packet.buffer)
+                self.write_(writer, &ethernet_packet.buffer)
             }
         }
     }
     pub fn close(&self) -> io::Result<()> {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        {
+            if let Some(canceler) = &self.uring_cancel {
+                canceler.cancel();
+            }
+            if let Some(uring) = &self.uring {
+                uring.close();
+            }
+        }
+        //辅助进程在主进程断开后清理策略路由
+        #[cfg(target_os = "linux")]
+        if self.route_table != 0 && self.helper.is_none() {
+            crate::tun_tap_device::linux::delete_policy_rule(self.route_table);
+        }
         //早期使用close直接切断网卡，现在并不需要这么做也能正常关闭
         // unsafe {
         //     match &self.writer {
@@ -129,16 +187,38 @@ impl DeviceWriter {
     }
 }
 
-pub struct DeviceReader(Reader);
+pub struct DeviceReader {
+    reader: Reader,
+    /// 只在tun读取线程中使用，锁没有竞争
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    uring: Option<Mutex<crate::util::uring::UringReader>>,
+}
 
 impl DeviceReader {
     pub fn new(device: Reader) -> Self {
-        DeviceReader(device)
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        let uring = {
+            use std::os::unix::io::AsRawFd;
+            crate::util::uring::UringReader::tun(device.as_raw_fd(), 8).map(Mutex::new)
+        };
+        DeviceReader {
+            reader: device,
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            uring,
+        }
+    }
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    pub(crate) fn uring_canceler(&self) -> Option<crate::util::uring::UringCanceler> {
+        self.uring.as_ref().map(|uring| uring.lock().canceler())
     }
 }
 
 impl DeviceReader {
     pub fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
-        self.0.read(buf)
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let Some(uring) = &self.uring {
+            return uring.lock().read(buf);
+        }
+        self.reader.read(buf)
     }
 }
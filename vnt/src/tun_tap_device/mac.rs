@@ -13,8 +13,8 @@ impl DeviceWriter {
         address: Ipv4Addr,
         netmask: Ipv4Addr,
         gateway: Ipv4Addr,
-        _old_netmask: Ipv4Addr,
-        _old_gateway: Ipv4Addr,
+        old_netmask: Ipv4Addr,
+        old_gateway: Ipv4Addr,
     ) -> io::Result<()> {
         let mut config = tun::Configuration::default();
         config
@@ -33,6 +33,10 @@ impl DeviceWriter {
         for (address, netmask) in &self.in_ips {
             add_route(name, *address, *netmask)?;
         }
+        // 网段大小变化时删除旧的网段路由
+        if old_netmask != netmask || old_gateway != gateway {
+            delete_route(old_gateway, old_netmask);
+        }
         // 当前网段路由
         add_route(name, address, netmask)?;
         // 广播和组播路由
@@ -42,12 +46,39 @@ impl DeviceWriter {
             Ipv4Addr::from([224, 0, 0, 0]),
             Ipv4Addr::from([240, 0, 0, 0]),
         )?;
+        for (address, netmask) in self.extra_routes.lock().iter() {
+            add_route(name, *address, *netmask)?;
+        }
         return Ok(());
     }
+    /// 替换服务端下发的路由，只增删有变化的部分
+    pub fn set_extra_routes(
+        &self,
+        routes: Vec<(Ipv4Addr, Ipv4Addr)>,
+        _gateway: Ipv4Addr,
+    ) -> io::Result<()> {
+        let dev = self.lock.lock();
+        let name = dev.name();
+        let mut extra_routes = self.extra_routes.lock();
+        for (address, netmask) in extra_routes.iter() {
+            if !routes.contains(&(*address, *netmask)) {
+                delete_route(*address, *netmask);
+            }
+        }
+        for (address, netmask) in &routes {
+            if !extra_routes.contains(&(*address, *netmask)) {
+                add_route(name, *address, *netmask)?;
+            }
+        }
+        *extra_routes = routes;
+        Ok(())
+    }
 }
 
+/// macos的utun网卡由系统分配名称，不使用name
 pub fn create_device(
     device_type: DeviceType,
+    _name: Option<&str>,
     address: Ipv4Addr,
     netmask: Ipv4Addr,
     gateway: Ipv4Addr,
@@ -129,6 +160,13 @@ fn add_route(name: &str, address: Ipv4Addr, netmask: Ipv4Addr) -> io::Result<()>
     Ok(())
 }
 
+fn delete_route(address: Ipv4Addr, netmask: Ipv4Addr) {
+    let cmd = format!("route -n delete {} -netmask {}", address, netmask);
+    if let Err(e) = Command::new("sh").arg("-c").arg(&cmd).output() {
+        log::warn!("cmd:{},{:?}", cmd, e);
+    }
+}
+
 fn config_ip(
     name: &str,
     address: Ipv4Addr,
@@ -150,4 +188,4 @@ fn config_ip(
     Ok(())
 }
 
-pub fn delete_device(_device_type: DeviceType) {}
+pub fn delete_device(_device_type: DeviceType, _name: Option<&str>) {}
@@ -1,5 +1,7 @@
 #[cfg(target_os = "android")]
 mod android;
+#[cfg(target_os = "linux")]
+pub mod helper;
 #[cfg(any(target_os = "linux"))]
 mod linux;
 #[cfg(any(target_os = "linux", target_os = "macos"))]
@@ -27,7 +29,11 @@ pub use mac::delete_device;
 #[cfg(target_os = "windows")]
 pub use windows::create_device;
 #[cfg(target_os = "windows")]
+pub use windows::check_driver;
+#[cfg(target_os = "windows")]
 pub use windows::delete_device;
+#[cfg(all(target_os = "windows", feature = "bundle-wintun"))]
+pub use windows::extract_wintun;
 #[cfg(target_os = "windows")]
 pub use windows::{DeviceReader, DeviceWriter};
 
@@ -5,6 +5,7 @@ use packet::ethernet::packet::EthernetPacket;
 use parking_lot::Mutex;
 use std::net::Ipv4Addr;
 use std::os::windows::process::CommandExt;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 use std::{io, thread};
@@ -33,14 +34,40 @@ pub struct DeviceWriter {
     device: Arc<Device>,
     lock: Arc<Mutex<()>>,
     in_ips: Vec<(Ipv4Addr, Ipv4Addr)>,
+    /// 已添加的路由(目标,掩码,网关)，ip变化或者关闭时删除
+    routes: Arc<Mutex<Vec<(Ipv4Addr, Ipv4Addr, Ipv4Addr)>>>,
+    /// 服务端下发的路由，ip变化时需要重新添加
+    extra_routes: Arc<Mutex<Vec<(Ipv4Addr, Ipv4Addr)>>>,
 }
 
 impl DeviceWriter {
-    pub fn new(device: Arc<Device>, in_ips: Vec<(Ipv4Addr, Ipv4Addr)>, _ip: Ipv4Addr) -> Self {
+    pub fn new(
+        device: Arc<Device>,
+        in_ips: Vec<(Ipv4Addr, Ipv4Addr)>,
+        _ip: Ipv4Addr,
+        routes: Vec<(Ipv4Addr, Ipv4Addr, Ipv4Addr)>,
+    ) -> Self {
         Self {
             device,
             lock: Arc::new(Default::default()),
             in_ips,
+            routes: Arc::new(Mutex::new(routes)),
+            extra_routes: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+    fn iface(&self) -> &dyn IFace {
+        match self.device.as_ref() {
+            Device::Tun(dev) => dev as &dyn IFace,
+            Device::Tap((dev, _)) => dev as &dyn IFace,
+        }
+    }
+    fn delete_routes(&self) {
+        let routes = std::mem::take(&mut *self.routes.lock());
+        let dev = self.iface();
+        for (dest, netmask, gateway) in routes {
+            if let Err(e) = dev.delete_route(dest, netmask, gateway) {
+                log::warn!("删除路由失败 {}/{} {:?}", dest, netmask, e);
+            }
         }
     }
 }
@@ -58,6 +85,13 @@ impl DeviceWriter {
             Device::Tap(_) => Err(io::Error::from(io::ErrorKind::Unsupported)),
         }
     }
+    /// tap网卡的mac地址，tun网卡返回None
+    pub fn tap_mac(&self) -> Option<[u8; 6]> {
+        match self.device.as_ref() {
+            Device::Tun(_) => None,
+            Device::Tap((_, mac)) => Some(*mac),
+        }
+    }
     /// tap网卡写入以太网帧
     pub fn write_ethernet_tap(&self, buf: &[u8]) -> io::Result<()> {
         match self.device.as_ref() {
@@ -99,35 +133,56 @@ impl DeviceWriter {
         address: Ipv4Addr,
         netmask: Ipv4Addr,
         gateway: Ipv4Addr,
-        old_netmask: Ipv4Addr,
-        old_gateway: Ipv4Addr,
+        _old_netmask: Ipv4Addr,
+        _old_gateway: Ipv4Addr,
     ) -> io::Result<()> {
         let _guard = self.lock.lock();
-        let dev: &dyn IFace = match self.device.as_ref() {
-            Device::Tun(dev) => dev as &dyn IFace,
-            Device::Tap((dev, _)) => dev as &dyn IFace,
-        };
-        if let Err(e) = dev.delete_route(dest(old_gateway, old_gateway), old_netmask, old_gateway) {
-            log::warn!("{:?}", e);
-        }
+        self.delete_routes();
+        let dev = self.iface();
         dev.set_ip(address, netmask)?;
-        for (address, netmask) in &self.in_ips {
-            dev.add_route(*address, *netmask, gateway, 1)?;
+        let mut in_ips = self.in_ips.clone();
+        in_ips.extend_from_slice(&self.extra_routes.lock());
+        let routes = add_routes(dev, address, netmask, gateway, &in_ips)?;
+        *self.routes.lock() = routes;
+        delete_cache();
+        Ok(())
+    }
+    /// 替换服务端下发的路由，只增删有变化的部分
+    pub fn set_extra_routes(
+        &self,
+        routes: Vec<(Ipv4Addr, Ipv4Addr)>,
+        gateway: Ipv4Addr,
+    ) -> io::Result<()> {
+        let _guard = self.lock.lock();
+        let dev = self.iface();
+        let mut extra_routes = self.extra_routes.lock();
+        let mut added = self.routes.lock();
+        for (dest, netmask) in extra_routes.iter() {
+            if routes.contains(&(*dest, *netmask)) {
+                continue;
+            }
+            added.retain(|(d, m, g)| {
+                if d != dest || m != netmask {
+                    return true;
+                }
+                if let Err(e) = dev.delete_route(*d, *m, *g) {
+                    log::warn!("删除路由失败 {}/{} {:?}", d, m, e);
+                }
+                false
+            });
         }
-        // 当前网段路由
-        dev.add_route(address, netmask, gateway, 1)?;
-        // 广播和组播路由
-        // dev.add_route(Ipv4Addr::BROADCAST, Ipv4Addr::BROADCAST, gateway, 1)?;
-        dev.add_route(
-            Ipv4Addr::from([224, 0, 0, 0]),
-            Ipv4Addr::from([240, 0, 0, 0]),
-            gateway,
-            1,
-        )?;
+        for (dest, netmask) in &routes {
+            if !extra_routes.contains(&(*dest, *netmask)) {
+                dev.add_route(*dest, *netmask, gateway, 1)?;
+                added.push((*dest, *netmask, gateway));
+            }
+        }
+        *extra_routes = routes;
         delete_cache();
         Ok(())
     }
     pub fn close(&self) -> io::Result<()> {
+        self.delete_routes();
         match self.device.as_ref() {
             Device::Tun(dev) => dev.shutdown(),
             Device::Tap((dev, _)) => dev.shutdown(),
@@ -138,6 +193,41 @@ impl DeviceWriter {
     }
 }
 
+/// 添加ip代理、当前网段和组播的路由，返回添加成功的路由，
+/// 中途失败时删除已经添加的路由后返回错误
+fn add_routes(
+    dev: &dyn IFace,
+    address: Ipv4Addr,
+    netmask: Ipv4Addr,
+    gateway: Ipv4Addr,
+    in_ips: &[(Ipv4Addr, Ipv4Addr)],
+) -> io::Result<Vec<(Ipv4Addr, Ipv4Addr, Ipv4Addr)>> {
+    let mut routes = Vec::with_capacity(in_ips.len() + 2);
+    // ip代理路由
+    for (address, netmask) in in_ips {
+        routes.push((*address, *netmask));
+    }
+    // 当前网段路由
+    routes.push((dest(address, netmask), netmask));
+    // 广播和组播路由 修改了广播路由会导致发不出广播
+    // routes.push((Ipv4Addr::BROADCAST, Ipv4Addr::BROADCAST));
+    routes.push((Ipv4Addr::from([224, 0, 0, 0]), Ipv4Addr::from([240, 0, 0, 0])));
+    let mut added = Vec::with_capacity(routes.len());
+    for (dest, netmask) in routes {
+        if let Err(e) = dev.add_route(dest, netmask, gateway, 1) {
+            // 返回错误时调用方拿不到已添加的路由，先回滚，避免残留到下次启动
+            for (dest, netmask, gateway) in added {
+                if let Err(e) = dev.delete_route(dest, netmask, gateway) {
+                    log::warn!("删除路由失败 {}/{} {:?}", dest, netmask, e);
+                }
+            }
+            return Err(e);
+        }
+        added.push((dest, netmask, gateway));
+    }
+    Ok(added)
+}
+
 fn dest(ip: Ipv4Addr, mask: Ipv4Addr) -> Ipv4Addr {
     let ip = ip.octets();
     let mask = mask.octets();
@@ -177,40 +267,145 @@ impl DeviceReader {
     }
 }
 
+/// wintun发布包中的目录名，和程序的架构要一致
+#[cfg(target_arch = "x86_64")]
+const WINTUN_ARCH: &str = "amd64";
+#[cfg(target_arch = "x86")]
+const WINTUN_ARCH: &str = "x86";
+#[cfg(target_arch = "aarch64")]
+const WINTUN_ARCH: &str = "arm64";
+#[cfg(target_arch = "arm")]
+const WINTUN_ARCH: &str = "arm";
+
+/// 依次查找程序目录下的wintun.dll、解压后的wintun/bin/<架构>/wintun.dll，最后使用系统的搜索路径
+fn wintun_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(dir) = std::env::current_exe()
+        .ok()
+        .and_then(|v| v.parent().map(|v| v.to_path_buf()))
+    {
+        paths.push(dir.join("wintun.dll"));
+        paths.push(
+            dir.join("wintun")
+                .join("bin")
+                .join(WINTUN_ARCH)
+                .join("wintun.dll"),
+        );
+    }
+    paths
+}
+
+/// 编译时内置的wintun.dll，和程序的架构一致
+#[cfg(feature = "bundle-wintun")]
+const WINTUN_DLL: &[u8] = include_bytes!(env!("VNT_WINTUN_DLL"));
+
+/// 把内置的wintun.dll释放到dir下，已存在且内容一致时不重复写入，返回dll的路径
+#[cfg(feature = "bundle-wintun")]
+pub fn extract_wintun(dir: &Path) -> io::Result<PathBuf> {
+    let path = dir.join("wintun.dll");
+    if let Ok(data) = std::fs::read(&path) {
+        if data == WINTUN_DLL {
+            return Ok(path);
+        }
+    }
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(&path, WINTUN_DLL)?;
+    //写入后再校验一次，避免写入不完整或被替换
+    if std::fs::read(&path)? != WINTUN_DLL {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} verify failed", path.display()),
+        ));
+    }
+    Ok(path)
+}
+
+/// 释放到程序目录的wintun/bin/<架构>下，不覆盖用户自己放的wintun.dll，程序目录只读时释放到用户目录
+#[cfg(feature = "bundle-wintun")]
+fn bundled_wintun() -> io::Result<PathBuf> {
+    let exe = std::env::current_exe()?;
+    let dir = exe.parent().unwrap_or(Path::new("."));
+    match extract_wintun(&dir.join("wintun").join("bin").join(WINTUN_ARCH)) {
+        Ok(path) => Ok(path),
+        Err(e) => match std::env::var_os("LOCALAPPDATA") {
+            Some(home) => extract_wintun(&Path::new(&home).join("vnt").join("wintun")),
+            None => Err(e),
+        },
+    }
+}
+
+unsafe fn load_wintun() -> io::Result<Library> {
+    #[cfg(feature = "bundle-wintun")]
+    match bundled_wintun() {
+        Ok(path) => return Library::new(&path).map_err(|e| wintun_error(e, Some(path.as_path()))),
+        Err(e) => log::warn!("释放内置的wintun.dll失败 {:?}", e),
+    }
+    for path in wintun_paths() {
+        if !path.exists() {
+            continue;
+        }
+        return Library::new(&path).map_err(|e| wintun_error(e, Some(path.as_path())));
+    }
+    Library::new("wintun.dll").map_err(|e| wintun_error(e, None))
+}
+
+fn wintun_error(e: libloading::Error, path: Option<&Path>) -> io::Error {
+    //架构不匹配，通常是x64的dll放到了arm64的程序旁边
+    const ERROR_BAD_EXE_FORMAT: i32 = 193;
+    let bad_format = match &e {
+        libloading::Error::LoadLibraryExW { source } => std::error::Error::source(source)
+            .and_then(|v| v.downcast_ref::<io::Error>())
+            .map_or(false, |v| v.raw_os_error() == Some(ERROR_BAD_EXE_FORMAT)),
+        _ => false,
+    };
+    let path = path.map_or("wintun.dll".to_string(), |v| v.display().to_string());
+    if bad_format {
+        io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "{} is not built for {},use wintun/bin/{}/wintun.dll",
+                path, WINTUN_ARCH, WINTUN_ARCH
+            ),
+        )
+    } else {
+        io::Error::new(
+            io::ErrorKind::Other,
+            format!("wintun.dll not found {} {:?}", path, e),
+        )
+    }
+}
+
 fn create_tun(
+    interface_name: &str,
     address: Ipv4Addr,
     netmask: Ipv4Addr,
     gateway: Ipv4Addr,
     in_ips: Vec<(Ipv4Addr, Ipv4Addr)>,
     mtu: u16,
+    metric: u16,
 ) -> io::Result<(DeviceWriter, DeviceReader, DriverInfo)> {
     unsafe {
-        match Library::new("wintun.dll") {
-            Ok(lib) => match TunDevice::delete_for_name(lib, TUN_INTERFACE_NAME) {
-                Ok(_) => {
-                    thread::sleep(Duration::from_millis(5));
-                }
-                Err(_) => {}
-            },
-            Err(e) => {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("wintun.dll not found {:?}", e),
-                ));
+        match TunDevice::delete_for_name(load_wintun()?, interface_name) {
+            Ok(_) => {
+                thread::sleep(Duration::from_millis(5));
             }
+            Err(_) => {}
         }
-        let tun_device = match TunDevice::create(
-            Library::new("wintun.dll").unwrap(),
+        let guid = stable_guid(interface_name);
+        let tun_device = match TunDevice::create_with_guid(
+            load_wintun()?,
             TUN_POOL_NAME,
-            TUN_INTERFACE_NAME,
+            interface_name,
+            guid,
         ) {
             Ok(tun_device) => tun_device,
             Err(_) => {
                 thread::sleep(Duration::from_millis(200));
-                match TunDevice::create(
-                    Library::new("wintun.dll").unwrap(),
+                match TunDevice::create_with_guid(
+                    load_wintun()?,
                     TUN_POOL_NAME,
-                    TUN_INTERFACE_NAME,
+                    interface_name,
+                    guid,
                 ) {
                     Ok(tun_device) => tun_device,
                     Err(e) => {
@@ -222,22 +417,12 @@ fn create_tun(
         let name = tun_device.get_name()?;
         let version = format!("{:?}", tun_device.version()?);
         tun_device.set_ip(address, netmask)?;
-        tun_device.set_metric(1)?;
+        tun_device.set_metric(metric)?;
         tun_device.set_mtu(mtu)?;
-        // ip代理路由
-        for (address, netmask) in &in_ips {
-            tun_device.add_route(*address, *netmask, gateway, 1)?;
+        if let Err(e) = tun_device.delete_static_routes() {
+            log::warn!("删除残留路由失败 {:?}", e);
         }
-        // 当前网段路由
-        tun_device.add_route(address, netmask, gateway, 1)?;
-        // 广播和组播路由 修改了广播路由会导致发不出广播
-        // tun_device.add_route(Ipv4Addr::BROADCAST, Ipv4Addr::BROADCAST, gateway, 1)?;
-        tun_device.add_route(
-            Ipv4Addr::from([224, 0, 0, 0]),
-            Ipv4Addr::from([240, 0, 0, 0]),
-            gateway,
-            1,
-        )?;
+        let routes = add_routes(&tun_device, address, netmask, gateway, &in_ips)?;
         delete_cache();
         let device = Arc::new(Device::Tun(tun_device));
         let driver_info = DriverInfo {
@@ -247,7 +432,7 @@ fn create_tun(
             mac: None,
         };
         Ok((
-            DeviceWriter::new(device.clone(), in_ips, address),
+            DeviceWriter::new(device.clone(), in_ips, address, routes),
             DeviceReader::new(device),
             driver_info,
         ))
@@ -268,10 +453,10 @@ fn delete_cache() {
     }
 }
 
-fn delete_tun() {
+fn delete_tun(interface_name: &str) {
     unsafe {
-        match Library::new("wintun.dll") {
-            Ok(lib) => match TunDevice::delete_for_name(lib, TUN_INTERFACE_NAME) {
+        match load_wintun() {
+            Ok(lib) => match TunDevice::delete_for_name(lib, interface_name) {
                 Ok(_) => {}
                 Err(_) => {}
             },
@@ -281,18 +466,20 @@ fn delete_tun() {
 }
 
 fn create_tap(
+    interface_name: &str,
     address: Ipv4Addr,
     netmask: Ipv4Addr,
     gateway: Ipv4Addr,
     in_ips: Vec<(Ipv4Addr, Ipv4Addr)>,
     mtu: u16,
+    metric: u16,
 ) -> io::Result<(DeviceWriter, DeviceReader, DriverInfo)> {
-    let tap_device = match TapDevice::open(TAP_INTERFACE_NAME) {
+    let tap_device = match TapDevice::open(interface_name) {
         Ok(tap_device) => tap_device,
         Err(e) => {
             log::warn!("{:?}", e);
             let tap_device = TapDevice::create()?;
-            tap_device.set_name(TAP_INTERFACE_NAME)?;
+            tap_device.set_name(interface_name)?;
             tap_device
         }
     };
@@ -301,21 +488,14 @@ fn create_tap(
     let version = format!("{:?}", tap_device.get_version()?);
     let mac_str = format!("mac:{:x?}", mac);
     tap_device.set_ip(address, netmask)?;
-    tap_device.set_metric(1)?;
+    tap_device.set_metric(metric)?;
     tap_device.set_mtu(mtu)?;
     tap_device.set_status(true)?;
-    tap_device.add_route(address, netmask, gateway, 1)?;
-    for (address, netmask) in &in_ips {
-        tap_device.add_route(*address, *netmask, gateway, 1)?;
+    // tap网卡非正常退出时不会被删除，会残留之前添加的路由
+    if let Err(e) = tap_device.delete_static_routes() {
+        log::warn!("删除残留路由失败 {:?}", e);
     }
-    // 广播和组播路由
-    // tap_device.add_route(Ipv4Addr::BROADCAST, Ipv4Addr::BROADCAST, gateway, 1)?;
-    tap_device.add_route(
-        Ipv4Addr::from([224, 0, 0, 0]),
-        Ipv4Addr::from([240, 0, 0, 0]),
-        gateway,
-        1,
-    )?;
+    let routes = add_routes(&tap_device, address, netmask, gateway, &in_ips)?;
     delete_cache();
     let tap = Arc::new(Device::Tap((tap_device, mac)));
     let driver_info = DriverInfo {
@@ -325,14 +505,14 @@ fn create_tap(
         mac: Some(mac_str),
     };
     Ok((
-        DeviceWriter::new(tap.clone(), in_ips, address),
+        DeviceWriter::new(tap.clone(), in_ips, address, routes),
         DeviceReader::new(tap),
         driver_info,
     ))
 }
 
-fn delete_tap() {
-    let tap_device = match TapDevice::open(TAP_INTERFACE_NAME) {
+fn delete_tap(interface_name: &str) {
+    let tap_device = match TapDevice::open(interface_name) {
         Ok(tap_device) => tap_device,
         Err(_) => {
             return;
@@ -343,21 +523,136 @@ fn delete_tap() {
 
 pub fn create_device(
     device_type: DeviceType,
+    name: Option<&str>,
     address: Ipv4Addr,
     netmask: Ipv4Addr,
     gateway: Ipv4Addr,
     in_ips: Vec<(Ipv4Addr, Ipv4Addr)>,
     mtu: u16,
+    metric: u16,
+    private_network: bool,
 ) -> io::Result<(DeviceWriter, DeviceReader, DriverInfo)> {
+    let rs = match device_type {
+        DeviceType::Tun => create_tun(
+            name.unwrap_or(TUN_INTERFACE_NAME),
+            address,
+            netmask,
+            gateway,
+            in_ips,
+            mtu,
+            metric,
+        )?,
+        DeviceType::Tap => create_tap(
+            name.unwrap_or(TAP_INTERFACE_NAME),
+            address,
+            netmask,
+            gateway,
+            in_ips,
+            mtu,
+            metric,
+        )?,
+    };
+    if private_network {
+        set_private_network(rs.2.name.clone());
+    }
+    Ok(rs)
+}
+
+/// 把网卡的网络配置文件设为专用网络，
+/// 网卡创建后windows需要一段时间识别网络才会生成配置文件，所以在后台重试
+fn set_private_network(interface_name: String) {
+    let cmd = format!(
+        "Set-NetConnectionProfile -InterfaceAlias '{}' -NetworkCategory Private",
+        interface_name.replace('\'', "''")
+    );
+    let rs = thread::Builder::new()
+        .name("privateNetwork".into())
+        .spawn(move || {
+            for _ in 0..30 {
+                thread::sleep(Duration::from_secs(2));
+                match std::process::Command::new("powershell")
+                    .creation_flags(0x08000000)
+                    .args(["-NoProfile", "-NonInteractive", "-Command", &cmd])
+                    .output()
+                {
+                    Ok(out) => {
+                        if out.status.success() {
+                            log::info!("网卡{}已设为专用网络", interface_name);
+                            return;
+                        }
+                    }
+                    Err(e) => {
+                        log::warn!("设置专用网络失败 {:?}", e);
+                        return;
+                    }
+                }
+            }
+            log::warn!("设置专用网络失败，网卡{}未生成网络配置文件", interface_name);
+        });
+    if let Err(e) = rs {
+        log::warn!("设置专用网络失败 {:?}", e);
+    }
+}
+
+/// 由网卡名称生成固定的guid，同名网卡每次创建都是同一个guid，
+/// windows会识别为同一个网络，网络配置文件和引用该网卡的防火墙规则不会失效
+fn stable_guid(interface_name: &str) -> u128 {
+    use sha2::Digest;
+    let hash = sha2::Sha256::digest(interface_name.as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&hash[..16]);
+    u128::from_ne_bytes(bytes)
+}
+
+pub fn delete_device(device_type: DeviceType, name: Option<&str>) {
     match device_type {
-        DeviceType::Tun => create_tun(address, netmask, gateway, in_ips, mtu),
-        DeviceType::Tap => create_tap(address, netmask, gateway, in_ips, mtu),
+        DeviceType::Tun => delete_tun(name.unwrap_or(TUN_INTERFACE_NAME)),
+        DeviceType::Tap => delete_tap(name.unwrap_or(TAP_INTERFACE_NAME)),
     }
 }
 
-pub fn delete_device(device_type: DeviceType) {
+/// 创建网卡前检查驱动，返回驱动的说明，
+/// tun检查wintun.dll能否加载以及已加载驱动的版本，tap检查tap-windows驱动是否安装
+pub fn check_driver(device_type: DeviceType) -> io::Result<String> {
     match device_type {
-        DeviceType::Tun => delete_tun(),
-        DeviceType::Tap => delete_tap(),
+        DeviceType::Tun => unsafe {
+            let lib = load_wintun()?;
+            let version = lib
+                .get::<unsafe extern "C" fn() -> u32>(b"WintunGetRunningDriverVersion\0")
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e)))?;
+            //驱动在第一次创建网卡时才会加载，为0时不是错误
+            let version = version();
+            if version == 0 {
+                Ok("wintun".to_string())
+            } else {
+                Ok(format!(
+                    "wintun {}.{}",
+                    (version >> 16) & 0xFF,
+                    version & 0xFF
+                ))
+            }
+        },
+        DeviceType::Tap => {
+            if let Ok(tap_device) = TapDevice::open(TAP_INTERFACE_NAME) {
+                let version = tap_device.get_version()?;
+                return Ok(format!(
+                    "tap-windows {}.{}.{}",
+                    version[0], version[1], version[2]
+                ));
+            }
+            let root = std::env::var("SystemRoot").unwrap_or("C:\\Windows".to_string());
+            let driver = Path::new(&root)
+                .join("System32")
+                .join("drivers")
+                .join("tap0901.sys");
+            if driver.exists() {
+                Ok("tap-windows".to_string())
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "tap-windows driver not installed",
+                ))
+            }
+        }
     }
 }
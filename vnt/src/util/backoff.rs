@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+/// 重试间隔的默认值(秒)
+pub const DEFAULT_MIN: u32 = 2;
+pub const DEFAULT_MAX: u32 = 60;
+/// 服务端给出的等待时间上限，避免异常的数值导致长时间不重连
+const RETRY_AFTER_MAX: Duration = Duration::from_secs(300);
+
+/// 带随机抖动的指数退避，服务端重启后大量客户端同时重连时把重试时间错开。
+/// 每次失败后间隔翻倍直到max，实际等待在间隔的一半到全部之间随机
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    min: Duration,
+    max: Duration,
+    attempts: u32,
+}
+
+impl Backoff {
+    pub fn new(min: Duration, max: Duration) -> Self {
+        let min = min.max(Duration::from_millis(100));
+        Self {
+            min,
+            max: max.max(min),
+            attempts: 0,
+        }
+    }
+    /// 已经连续重试的次数
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+    /// 下一次重试前等待的时长
+    pub fn next_delay(&mut self) -> Duration {
+        let interval = self
+            .min
+            .saturating_mul(1 << self.attempts.min(16))
+            .min(self.max);
+        self.attempts = self.attempts.saturating_add(1);
+        jitter(interval / 2, interval)
+    }
+    /// 服务端给出了等待时间(秒，0表示未指定)时不早于它，
+    /// 再加上抖动，避免同时收到限流的客户端在同一时刻重试
+    pub fn next_delay_after(&mut self, retry_after: u32) -> Duration {
+        let delay = self.next_delay();
+        if retry_after == 0 {
+            return delay;
+        }
+        let retry_after = Duration::from_secs(retry_after as u64).min(RETRY_AFTER_MAX);
+        jitter(retry_after, retry_after + self.min).max(delay)
+    }
+    /// 成功后从最小间隔重新开始
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(
+            Duration::from_secs(DEFAULT_MIN as u64),
+            Duration::from_secs(DEFAULT_MAX as u64),
+        )
+    }
+}
+
+/// [low,high]之间的随机时长
+pub fn jitter(low: Duration, high: Duration) -> Duration {
+    if high <= low {
+        return low;
+    }
+    let ms = rand::thread_rng().gen_range(low.as_millis() as u64..=high.as_millis() as u64);
+    Duration::from_millis(ms)
+}
@@ -0,0 +1,214 @@
+use std::io;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use parking_lot::RwLock;
+
+/// 使用独立路由表时vnt自身socket的fwmark，0表示不使用
+/// 打上标记的流量不会匹配vnt的路由表，避免和虚拟网卡形成路由环路
+static FWMARK: AtomicU32 = AtomicU32::new(0);
+
+pub fn set(mark: u32) {
+    FWMARK.store(mark, Ordering::Relaxed);
+}
+
+pub fn get() -> u32 {
+    FWMARK.load(Ordering::Relaxed)
+}
+
+/// 底层socket绑定的物理网卡(名称,索引)，和其他vpn共存时固定从这个网卡收发，
+/// 避免vnt自身的流量被其他vpn或者vnt自己的路由带走形成环路
+static DEVICE: RwLock<Option<(String, u32)>> = parking_lot::const_rwlock(None);
+
+/// 设置绑定的网卡，需要在创建socket之前调用
+pub fn set_device(name: Option<String>) -> io::Result<()> {
+    let device = match name {
+        None => None,
+        Some(name) => {
+            let index = device_index(&name)?;
+            log::info!("底层socket绑定网卡 {} index={}", name, index);
+            Some((name, index))
+        }
+    };
+    *DEVICE.write() = device;
+    Ok(())
+}
+
+pub fn device() -> Option<(String, u32)> {
+    DEVICE.read().clone()
+}
+
+#[cfg(unix)]
+fn device_index(name: &str) -> io::Result<u32> {
+    let c_name = std::ffi::CString::new(name)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid device name"))?;
+    let index = unsafe { libc::if_nametoindex(c_name.as_ptr()) };
+    if index == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("device {} not found", name),
+        ));
+    }
+    Ok(index)
+}
+
+#[cfg(windows)]
+fn device_index(name: &str) -> io::Result<u32> {
+    use std::os::windows::ffi::OsStrExt;
+    use winapi::shared::ifdef::NET_LUID;
+    use winapi::shared::netioapi::{ConvertInterfaceAliasToLuid, ConvertInterfaceLuidToIndex};
+    let alias: Vec<u16> = std::ffi::OsStr::new(name)
+        .encode_wide()
+        .chain(Some(0))
+        .collect();
+    let mut luid: NET_LUID = unsafe { std::mem::zeroed() };
+    let mut index = 0u32;
+    unsafe {
+        if ConvertInterfaceAliasToLuid(alias.as_ptr(), &mut luid) != 0
+            || ConvertInterfaceLuidToIndex(&luid, &mut index) != 0
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("device {} not found", name),
+            ));
+        }
+    }
+    Ok(index)
+}
+
+/// 给socket打上fwmark并绑定网卡，udp需要在connect/send之前调用
+#[cfg(unix)]
+pub fn mark<S: std::os::fd::AsFd>(socket: &S) -> io::Result<()> {
+    let socket = socket2::SockRef::from(socket);
+    #[cfg(target_os = "linux")]
+    {
+        let mark = get();
+        if mark != 0 {
+            socket.set_mark(mark)?;
+        }
+    }
+    bind_device(&socket)
+}
+
+#[cfg(windows)]
+pub fn mark<S: std::os::windows::io::AsSocket>(socket: &S) -> io::Result<()> {
+    bind_device(&socket2::SockRef::from(socket))
+}
+
+/// 只打上fwmark不绑定网卡，用于ip代理和dns查询这类目标不一定在绑定网卡上的流量，
+/// 打上标记后不走vnt的路由表，开启防泄漏开关时也会被放行
+#[cfg(unix)]
+pub fn mark_only<S: std::os::fd::AsFd>(_socket: &S) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        let mark = get();
+        if mark != 0 {
+            socket2::SockRef::from(_socket).set_mark(mark)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn mark_only<S: std::os::windows::io::AsSocket>(_socket: &S) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn bind_device(socket: &socket2::SockRef) -> io::Result<()> {
+    match device() {
+        None => Ok(()),
+        Some((name, _)) => socket.bind_device(Some(name.as_bytes())),
+    }
+}
+
+/// 主udp是双栈的，ipv4和ipv6都设置，有一个成功即可
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn bind_device(socket: &socket2::SockRef) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    let index = match device() {
+        None => return Ok(()),
+        Some((_, index)) => index as libc::c_int,
+    };
+    let set = |level: libc::c_int, name: libc::c_int| unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &index as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        ) == 0
+    };
+    let v4 = set(libc::IPPROTO_IP, libc::IP_BOUND_IF);
+    let v6 = set(libc::IPPROTO_IPV6, libc::IPV6_BOUND_IF);
+    if v4 || v6 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// 主udp是双栈的，ipv4和ipv6都设置，有一个成功即可
+#[cfg(windows)]
+fn bind_device(socket: &socket2::SockRef) -> io::Result<()> {
+    use std::os::windows::io::AsRawSocket;
+    use winapi::um::winsock2::{setsockopt, WSAGetLastError};
+    const IPPROTO_IP: i32 = 0;
+    const IPPROTO_IPV6: i32 = 41;
+    const IP_UNICAST_IF: i32 = 31;
+    const IPV6_UNICAST_IF: i32 = 31;
+    let index = match device() {
+        None => return Ok(()),
+        Some((_, index)) => index,
+    };
+    let set = |level: i32, name: i32, value: u32| unsafe {
+        setsockopt(
+            socket.as_raw_socket() as _,
+            level,
+            name,
+            &value as *const u32 as *const _,
+            std::mem::size_of::<u32>() as i32,
+        ) == 0
+    };
+    //ipv4的网卡索引是网络字节序
+    let v4 = set(IPPROTO_IP, IP_UNICAST_IF, index.to_be());
+    let v6 = set(IPPROTO_IPV6, IPV6_UNICAST_IF, index);
+    if v4 || v6 {
+        Ok(())
+    } else {
+        Err(io::Error::from_raw_os_error(unsafe { WSAGetLastError() }))
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    windows
+)))]
+fn bind_device(_socket: &socket2::SockRef) -> io::Result<()> {
+    Ok(())
+}
+
+/// 建立tcp连接，需要在connect之前打上标记，所以不能直接用TcpStream::connect
+pub fn tcp_connect(addr: &SocketAddr, timeout: Option<Duration>) -> io::Result<TcpStream> {
+    if get() == 0 && DEVICE.read().is_none() {
+        return match timeout {
+            None => TcpStream::connect(addr),
+            Some(timeout) => TcpStream::connect_timeout(addr, timeout),
+        };
+    }
+    let socket = socket2::Socket::new(
+        socket2::Domain::for_address(*addr),
+        socket2::Type::STREAM,
+        Some(socket2::Protocol::TCP),
+    )?;
+    mark(&socket)?;
+    match timeout {
+        None => socket.connect(&(*addr).into())?,
+        Some(timeout) => socket.connect_timeout(&(*addr).into(), timeout)?,
+    }
+    Ok(socket.into())
+}
@@ -0,0 +1,142 @@
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::str::FromStr;
+
+use crossbeam_utils::atomic::AtomicCell;
+use rand::Rng;
+
+/// 和服务端之间的udp报文伪装的协议，None表示不伪装
+static MIMICRY: AtomicCell<Option<Mimicry>> = AtomicCell::new(None);
+
+const DNS_HEADER_LEN: usize = 12;
+const NTP_HEADER_LEN: usize = 48;
+
+/// 只放行dns/ntp的网络(如未认证的酒店、机场wifi)中，和服务端的udp报文前加上对应协议的头部，
+/// 服务端需要监听53或123端口并开启同样的模式
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Mimicry {
+    Dns,
+    Ntp,
+}
+
+impl FromStr for Mimicry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().trim() {
+            "dns" => Ok(Mimicry::Dns),
+            "ntp" => Ok(Mimicry::Ntp),
+            _ => Err(format!("not match '{}', enum: dns/ntp", s)),
+        }
+    }
+}
+
+impl fmt::Display for Mimicry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mimicry::Dns => f.write_str("dns"),
+            Mimicry::Ntp => f.write_str("ntp"),
+        }
+    }
+}
+
+impl Mimicry {
+    pub fn header_len(&self) -> usize {
+        match self {
+            Mimicry::Dns => DNS_HEADER_LEN,
+            Mimicry::Ntp => NTP_HEADER_LEN,
+        }
+    }
+    /// 客户端发出的报文伪装成请求
+    fn write_request_header(&self, buf: &mut Vec<u8>) {
+        let mut rng = rand::thread_rng();
+        match self {
+            Mimicry::Dns => {
+                //随机id，标准查询并要求递归，各记录数为0
+                buf.extend_from_slice(&rng.gen::<u16>().to_be_bytes());
+                buf.extend_from_slice(&[0x01, 0x00, 0, 0, 0, 0, 0, 0, 0, 0]);
+            }
+            Mimicry::Ntp => {
+                //LI=0 VN=4 Mode=3(client)，只填写发送时间戳
+                buf.push(0x23);
+                buf.extend_from_slice(&[0u8; 39]);
+                buf.extend_from_slice(&rng.gen::<u64>().to_be_bytes());
+            }
+        }
+    }
+    /// 服务端的报文应该是响应
+    fn is_response(&self, buf: &[u8]) -> bool {
+        if buf.len() < self.header_len() {
+            return false;
+        }
+        match self {
+            //QR位
+            Mimicry::Dns => buf[2] & 0x80 != 0,
+            //Mode=4(server)
+            Mimicry::Ntp => buf[0] & 0x07 == 4,
+        }
+    }
+}
+
+pub fn set(mimicry: Option<Mimicry>) {
+    MIMICRY.store(mimicry);
+}
+
+pub fn get() -> Option<Mimicry> {
+    MIMICRY.load()
+}
+
+/// 发往服务端，开启伪装时加上头部，返回值不包含头部长度
+pub fn send_to(udp: &UdpSocket, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+    match get() {
+        None => udp.send_to(buf, addr),
+        Some(mimicry) => {
+            let mut packet = Vec::with_capacity(mimicry.header_len() + buf.len());
+            mimicry.write_request_header(&mut packet);
+            packet.extend_from_slice(buf);
+            udp.send_to(&packet, addr).map(|_| buf.len())
+        }
+    }
+}
+
+/// 接收服务端的数据，开启伪装时去掉头部，返回的长度不包含头部
+pub fn recv_from(udp: &UdpSocket, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+    let (len, addr) = udp.recv_from(buf)?;
+    match strip(&mut buf[..len]) {
+        Some(len) => Ok((len, addr)),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "mimicry header mismatch",
+        )),
+    }
+}
+
+/// 去掉服务端报文的伪装头部，数据移动到开头，返回剩余长度，头部不合法时返回None
+pub fn strip(buf: &mut [u8]) -> Option<usize> {
+    match get() {
+        None => Some(buf.len()),
+        Some(mimicry) => {
+            if !mimicry.is_response(buf) {
+                return None;
+            }
+            let header_len = mimicry.header_len();
+            buf.copy_within(header_len.., 0);
+            Some(buf.len() - header_len)
+        }
+    }
+}
+
+/// 是否是同一地址，ipv4映射的ipv6地址和ipv4地址视为相同
+pub fn same_addr(a: SocketAddr, b: SocketAddr) -> bool {
+    canonical(a) == canonical(b)
+}
+
+fn canonical(addr: SocketAddr) -> SocketAddr {
+    if let SocketAddr::V6(ipv6) = addr {
+        if let Some(ipv4) = ipv6.ip().to_ipv4_mapped() {
+            return SocketAddr::new(ipv4.into(), ipv6.port());
+        }
+    }
+    addr
+}
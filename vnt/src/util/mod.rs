@@ -1 +1,13 @@
+pub mod backoff;
+pub mod fwmark;
+pub mod mimicry;
+#[cfg(target_os = "linux")]
+pub mod privilege;
+pub mod proxy;
+pub mod resolve;
+pub mod seccomp;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub mod uring;
 pub mod wait;
+#[cfg(target_os = "windows")]
+pub mod wfp;
@@ -0,0 +1,116 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const CAP_NET_BIND_SERVICE: u32 = 10;
+const CAP_NET_ADMIN: u32 = 12;
+const CAP_NET_RAW: u32 = 13;
+
+/// 初始化之后还需要的能力：修改地址和路由、fwmark和绑定网卡、监听低端口
+pub const KEEP: [u32; 3] = [CAP_NET_BIND_SERVICE, CAP_NET_ADMIN, CAP_NET_RAW];
+
+const CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+#[repr(C)]
+struct CapHeader {
+    version: u32,
+    pid: libc::c_int,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+struct CapData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+/// 同一进程中多个组网时只执行一次，降权后已经没有再次降权需要的能力
+static DROPPED: AtomicBool = AtomicBool::new(false);
+
+/// 降低权限，只保留keep中的能力，user不为None时切换到该用户(uid,gid)。
+/// 保留的能力同时设为ambient，执行ip、nft等命令的子进程可以继承
+pub fn drop_caps(keep: &[u32], user: Option<(u32, u32)>) -> io::Result<()> {
+    if DROPPED.swap(true, Ordering::AcqRel) {
+        return Ok(());
+    }
+    unsafe {
+        //先从边界集中去掉其他能力，这一步需要CAP_SETPCAP，切换用户之后就做不到了
+        for cap in 0..=last_cap() {
+            if !keep.contains(&cap)
+                && libc::prctl(libc::PR_CAPBSET_DROP, cap as libc::c_ulong, 0, 0, 0) < 0
+            {
+                let e = io::Error::last_os_error();
+                match e.raw_os_error() {
+                    //内核不支持这个能力
+                    Some(libc::EINVAL) => {}
+                    //以普通用户运行(如使用特权辅助进程)时没有CAP_SETPCAP，边界集不变
+                    Some(libc::EPERM) => {
+                        log::warn!("能力边界集不变 {:?}", e);
+                        break;
+                    }
+                    _ => return Err(e),
+                }
+            }
+        }
+        if let Some((uid, gid)) = user {
+            //切换用户后保留permitted中的能力
+            if libc::prctl(libc::PR_SET_KEEPCAPS, 1, 0, 0, 0) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::setgroups(0, std::ptr::null()) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::setresgid(gid, gid, gid) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::setresuid(uid, uid, uid) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        let mut mask = [0u32; 2];
+        for cap in keep {
+            mask[(cap / 32) as usize] |= 1 << (cap % 32);
+        }
+        let header = CapHeader {
+            version: CAPABILITY_VERSION_3,
+            pid: 0,
+        };
+        let data = [
+            CapData {
+                effective: mask[0],
+                permitted: mask[0],
+                inheritable: mask[0],
+            },
+            CapData {
+                effective: mask[1],
+                permitted: mask[1],
+                inheritable: mask[1],
+            },
+        ];
+        if libc::syscall(libc::SYS_capset, &header as *const CapHeader, data.as_ptr()) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        for cap in keep {
+            if libc::prctl(
+                libc::PR_CAP_AMBIENT,
+                libc::PR_CAP_AMBIENT_RAISE as libc::c_ulong,
+                *cap as libc::c_ulong,
+                0,
+                0,
+            ) < 0
+            {
+                //4.3之前的内核没有ambient，切换用户后子进程拿不到能力
+                log::warn!("ambient cap {} {:?}", cap, io::Error::last_os_error());
+            }
+        }
+    }
+    log::info!("已降低权限 keep={:?} user={:?}", keep, user);
+    Ok(())
+}
+
+fn last_cap() -> u32 {
+    std::fs::read_to_string("/proc/sys/kernel/cap_last_cap")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(40)
+}
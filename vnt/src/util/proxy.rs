@@ -0,0 +1,256 @@
+use std::fmt;
+use std::io;
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::time::Duration;
+
+use parking_lot::RwLock;
+
+/// 和服务端的tcp连接使用的代理，None表示直连
+static PROXY: RwLock<Option<ProxyConfig>> = parking_lot::const_rwlock(None);
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ProxyKind {
+    Socks5,
+    Http,
+}
+
+/// 上游代理，格式 socks5://[user:password@]host:port 或者 http://[user:password@]host:port
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProxyConfig {
+    pub kind: ProxyKind,
+    pub server: String,
+    pub auth: Option<(String, String)>,
+}
+
+impl FromStr for ProxyConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (scheme, rest) = s
+            .split_once("://")
+            .ok_or_else(|| "format socks5://host:port or http://host:port".to_string())?;
+        let (kind, default_port) = match scheme.to_lowercase().as_str() {
+            "socks5" => (ProxyKind::Socks5, ":1080"),
+            "http" => (ProxyKind::Http, ":8080"),
+            _ => return Err(format!("unsupported proxy {}", scheme)),
+        };
+        let rest = rest.trim_end_matches('/');
+        let (auth, server) = match rest.rsplit_once('@') {
+            None => (None, rest),
+            Some((credential, server)) => {
+                let (username, password) = credential
+                    .split_once(':')
+                    .ok_or_else(|| "format user:password@host:port".to_string())?;
+                (Some((username.to_string(), password.to_string())), server)
+            }
+        };
+        if server.is_empty() {
+            return Err("proxy server is empty".to_string());
+        }
+        let mut server = server.to_string();
+        //没有端口时使用默认端口，ipv6地址需要加中括号
+        if server.ends_with(']') || !server.contains(':') {
+            server.push_str(default_port);
+        }
+        if let Some((username, password)) = &auth {
+            if username.len() > 255 || password.len() > 255 {
+                return Err("username or password too long".to_string());
+            }
+        }
+        Ok(ProxyConfig { kind, server, auth })
+    }
+}
+
+impl fmt::Display for ProxyConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = match self.kind {
+            ProxyKind::Socks5 => "socks5",
+            ProxyKind::Http => "http",
+        };
+        //不输出密码
+        match &self.auth {
+            None => write!(f, "{}://{}", scheme, self.server),
+            Some((username, _)) => write!(f, "{}://{}@{}", scheme, username, self.server),
+        }
+    }
+}
+
+pub fn set(proxy: Option<ProxyConfig>) {
+    *PROXY.write() = proxy;
+}
+
+pub fn get() -> Option<ProxyConfig> {
+    PROXY.read().clone()
+}
+
+/// 建立和服务端的tcp连接，设置了代理时经代理连接
+pub fn server_connect(addr: &SocketAddr, timeout: Option<Duration>) -> io::Result<TcpStream> {
+    let proxy = match get() {
+        None => return crate::util::fwmark::tcp_connect(addr, timeout),
+        Some(proxy) => proxy,
+    };
+    let proxy_addr = proxy
+        .server
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "proxy server not found"))?;
+    let mut stream = crate::util::fwmark::tcp_connect(&proxy_addr, timeout)?;
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    stream.set_write_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    match proxy.kind {
+        ProxyKind::Socks5 => socks5_connect(&mut stream, &proxy, addr)?,
+        ProxyKind::Http => http_connect(&mut stream, &proxy, addr)?,
+    }
+    stream.set_read_timeout(None)?;
+    stream.set_write_timeout(None)?;
+    log::info!("经代理{}连接服务端{}", proxy, addr);
+    Ok(stream)
+}
+
+fn socks5_connect(
+    stream: &mut TcpStream,
+    proxy: &ProxyConfig,
+    addr: &SocketAddr,
+) -> io::Result<()> {
+    //协商认证方式，0无认证，2用户名密码
+    if proxy.auth.is_some() {
+        stream.write_all(&[5, 2, 0, 2])?;
+    } else {
+        stream.write_all(&[5, 1, 0])?;
+    }
+    let mut buf = [0u8; 2];
+    stream.read_exact(&mut buf)?;
+    if buf[0] != 5 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not socks5"));
+    }
+    match buf[1] {
+        0 => {}
+        2 => {
+            let (username, password) = proxy.auth.as_ref().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::PermissionDenied, "socks5 auth required")
+            })?;
+            let mut request = Vec::with_capacity(3 + username.len() + password.len());
+            request.push(1);
+            request.push(username.len() as u8);
+            request.extend_from_slice(username.as_bytes());
+            request.push(password.len() as u8);
+            request.extend_from_slice(password.as_bytes());
+            stream.write_all(&request)?;
+            stream.read_exact(&mut buf)?;
+            if buf[1] != 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "socks5 auth failed",
+                ));
+            }
+        }
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "socks5 no acceptable auth method",
+            ));
+        }
+    }
+    let mut request = vec![5, 1, 0];
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+    }
+    request.extend_from_slice(&addr.port().to_be_bytes());
+    stream.write_all(&request)?;
+    let mut head = [0u8; 4];
+    stream.read_exact(&mut head)?;
+    if head[1] != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("socks5 connect failed,reply={}", head[1]),
+        ));
+    }
+    //读掉绑定地址
+    let len = match head[3] {
+        1 => 4,
+        4 => 16,
+        3 => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len)?;
+            len[0] as usize
+        }
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "socks5 invalid address type",
+            ));
+        }
+    };
+    let mut bind_addr = vec![0u8; len + 2];
+    stream.read_exact(&mut bind_addr)?;
+    Ok(())
+}
+
+fn http_connect(stream: &mut TcpStream, proxy: &ProxyConfig, addr: &SocketAddr) -> io::Result<()> {
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", addr);
+    if let Some((username, password)) = &proxy.auth {
+        request.push_str("Proxy-Authorization: Basic ");
+        request.push_str(&base64(format!("{}:{}", username, password).as_bytes()));
+        request.push_str("\r\n");
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes())?;
+    //逐字节读取响应头，不能多读走后面的数据
+    let mut response = Vec::with_capacity(128);
+    let mut byte = [0u8; 1];
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() > 8192 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "http proxy response too long",
+            ));
+        }
+        stream.read_exact(&mut byte)?;
+        response.push(byte[0]);
+    }
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some("200") => Ok(()),
+        Some("407") => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "http proxy auth failed",
+        )),
+        _ => Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("http proxy connect failed,{}", status_line),
+        )),
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(TABLE[(n >> (18 - i * 6)) as usize & 63] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,610 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crossbeam_utils::atomic::AtomicCell;
+use parking_lot::RwLock;
+use rand::Rng;
+
+/// 服务端地址没有写端口时使用的端口
+pub const DEFAULT_PORT: u16 = 29872;
+
+/// srv记录，见RFC 2782
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SrvTarget {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: String,
+}
+
+/// 服务端地址的域名解析，默认使用系统解析，可以替换成指定的dns服务器或者调用方自己的实现(如安卓使用系统api)
+pub trait Resolve: Send + Sync {
+    fn lookup(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>>;
+    /// 系统解析不支持srv，默认向系统配置的dns服务器查询
+    fn lookup_srv(&self, name: &str) -> io::Result<Vec<SrvTarget>> {
+        match system_dns_server() {
+            Some(server) => UdpResolver::new(server).lookup_srv(name),
+            None => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "srv lookup requires a dns server, set resolver",
+            )),
+        }
+    }
+}
+
+/// 系统解析
+pub struct SystemResolver;
+
+impl Resolve for SystemResolver {
+    fn lookup(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        Ok((host, port).to_socket_addrs()?.collect())
+    }
+}
+
+/// /etc/resolv.conf中的第一个dns服务器，其他平台没有统一的读取方式
+#[cfg(unix)]
+fn system_dns_server() -> Option<SocketAddr> {
+    let ip = *nameservers("/etc/resolv.conf").first()?;
+    Some(SocketAddr::new(ip, 53))
+}
+
+/// resolv.conf格式文件中的dns服务器，读取失败时返回空
+#[cfg(unix)]
+pub(crate) fn nameservers(path: &str) -> Vec<IpAddr> {
+    let conf = match std::fs::read_to_string(path) {
+        Ok(conf) => conf,
+        Err(_) => return Vec::new(),
+    };
+    conf.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            if fields.next()? != "nameserver" {
+                return None;
+            }
+            //去掉ipv6链路本地地址的%scope
+            let ip = fields.next()?.split('%').next()?;
+            ip.parse().ok()
+        })
+        .collect()
+}
+
+/// 所有已启用网卡上配置的dns服务器，读取失败时返回空
+#[cfg(windows)]
+pub(crate) fn nameservers() -> Vec<IpAddr> {
+    use winapi::shared::ifdef::IfOperStatusUp;
+    use winapi::shared::ws2def::{AF_INET, AF_INET6, AF_UNSPEC, SOCKADDR_IN};
+    use winapi::shared::ws2ipdef::SOCKADDR_IN6_LH;
+    use winapi::um::iphlpapi::GetAdaptersAddresses;
+    use winapi::um::iptypes::{
+        GAA_FLAG_SKIP_ANYCAST, GAA_FLAG_SKIP_MULTICAST, GAA_FLAG_SKIP_UNICAST,
+        IP_ADAPTER_ADDRESSES_LH,
+    };
+    const ERROR_BUFFER_OVERFLOW: u32 = 111;
+    let flags = GAA_FLAG_SKIP_ANYCAST | GAA_FLAG_SKIP_MULTICAST | GAA_FLAG_SKIP_UNICAST;
+    let mut size = 16 * 1024u32;
+    // 按8字节对齐
+    let mut buf: Vec<u64>;
+    loop {
+        buf = vec![0u64; size as usize / 8 + 1];
+        let rs = unsafe {
+            GetAdaptersAddresses(
+                AF_UNSPEC as u32,
+                flags,
+                std::ptr::null_mut(),
+                buf.as_mut_ptr() as *mut IP_ADAPTER_ADDRESSES_LH,
+                &mut size,
+            )
+        };
+        match rs {
+            0 => break,
+            ERROR_BUFFER_OVERFLOW => continue,
+            _ => return Vec::new(),
+        }
+    }
+    let mut list = Vec::new();
+    let mut adapter = buf.as_ptr() as *const IP_ADAPTER_ADDRESSES_LH;
+    while let Some(current) = unsafe { adapter.as_ref() } {
+        if current.OperStatus == IfOperStatusUp {
+            let mut dns = current.FirstDnsServerAddress;
+            while let Some(server) = unsafe { dns.as_ref() } {
+                let addr = server.Address.lpSockaddr;
+                let ip = match unsafe { addr.as_ref() }.map(|v| v.sa_family as i32) {
+                    Some(AF_INET) => {
+                        let addr = unsafe { &*(addr as *const SOCKADDR_IN) };
+                        let ip = unsafe { *addr.sin_addr.S_un.S_addr() };
+                        Some(IpAddr::V4(Ipv4Addr::from(ip.to_ne_bytes())))
+                    }
+                    Some(AF_INET6) => {
+                        let addr = unsafe { &*(addr as *const SOCKADDR_IN6_LH) };
+                        let ip = unsafe { *addr.sin6_addr.u.Byte() };
+                        Some(IpAddr::V6(Ipv6Addr::from(ip)))
+                    }
+                    _ => None,
+                };
+                if let Some(ip) = ip {
+                    if !list.contains(&ip) {
+                        list.push(ip);
+                    }
+                }
+                dns = server.Next;
+            }
+        }
+        adapter = current.Next;
+    }
+    list
+}
+
+#[cfg(not(unix))]
+fn system_dns_server() -> Option<SocketAddr> {
+    None
+}
+
+const QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+
+/// 响应中的记录
+enum Record {
+    Ip(IpAddr),
+    Srv(SrvTarget),
+}
+
+/// 直接向指定的dns服务器查询A和AAAA记录，用于系统dns被污染或者ddns的记录被缓存很久的网络
+pub struct UdpResolver {
+    server: SocketAddr,
+}
+
+impl UdpResolver {
+    pub fn new(server: SocketAddr) -> Self {
+        Self { server }
+    }
+    fn query(&self, host: &str, qtype: u16) -> io::Result<Vec<Record>> {
+        let bind: SocketAddr = if self.server.is_ipv4() {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let udp = UdpSocket::bind(bind)?;
+        udp.set_read_timeout(Some(QUERY_TIMEOUT))?;
+        crate::util::fwmark::mark_only(&udp)?;
+        udp.connect(self.server)?;
+        let id: u16 = rand::thread_rng().gen();
+        udp.send(&query_packet(id, host, qtype)?)?;
+        let mut buf = [0u8; 1500];
+        loop {
+            let len = udp.recv(&mut buf)?;
+            //id不一致的是之前超时的响应
+            if let Some(list) = parse_response(&buf[..len], id)? {
+                return Ok(list);
+            }
+        }
+    }
+}
+
+impl Resolve for UdpResolver {
+    fn lookup(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        let mut list = Vec::new();
+        let mut err = None;
+        for qtype in [TYPE_A, TYPE_AAAA] {
+            match self.query(host, qtype) {
+                Ok(records) => list.extend(records.into_iter().filter_map(|record| match record {
+                    Record::Ip(ip) => Some(SocketAddr::new(ip, port)),
+                    Record::Srv(_) => None,
+                })),
+                Err(e) => err = Some(e),
+            }
+        }
+        match err {
+            Some(e) if list.is_empty() => Err(e),
+            _ => Ok(list),
+        }
+    }
+    fn lookup_srv(&self, name: &str) -> io::Result<Vec<SrvTarget>> {
+        Ok(self
+            .query(name, TYPE_SRV)?
+            .into_iter()
+            .filter_map(|record| match record {
+                Record::Srv(srv) => Some(srv),
+                Record::Ip(_) => None,
+            })
+            .collect())
+    }
+}
+
+fn query_packet(id: u16, host: &str, qtype: u16) -> io::Result<Vec<u8>> {
+    let mut packet = Vec::with_capacity(host.len() + 18);
+    packet.extend_from_slice(&id.to_be_bytes());
+    //标准查询，要求递归，一个问题
+    packet.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in host.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid host {}", host),
+            ));
+        }
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&qtype.to_be_bytes());
+    packet.extend_from_slice(&1u16.to_be_bytes());
+    Ok(packet)
+}
+
+/// id不一致时返回None
+fn parse_response(buf: &[u8], id: u16) -> io::Result<Option<Vec<Record>>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid dns response");
+    if buf.len() < 12 {
+        return Err(invalid());
+    }
+    if u16::from_be_bytes([buf[0], buf[1]]) != id || buf[2] & 0x80 == 0 {
+        return Ok(None);
+    }
+    let rcode = buf[3] & 0x0f;
+    //NXDOMAIN
+    if rcode == 3 {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "host not found"));
+    }
+    if rcode != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("dns rcode {}", rcode),
+        ));
+    }
+    let qdcount = u16::from_be_bytes([buf[4], buf[5]]);
+    let ancount = u16::from_be_bytes([buf[6], buf[7]]);
+    let mut pos = 12;
+    for _ in 0..qdcount {
+        pos = skip_name(buf, pos).ok_or_else(invalid)? + 4;
+    }
+    let mut list = Vec::new();
+    for _ in 0..ancount {
+        pos = skip_name(buf, pos).ok_or_else(invalid)?;
+        if pos + 10 > buf.len() {
+            return Err(invalid());
+        }
+        let typ = u16::from_be_bytes([buf[pos], buf[pos + 1]]);
+        let len = u16::from_be_bytes([buf[pos + 8], buf[pos + 9]]) as usize;
+        pos += 10;
+        if pos + len > buf.len() {
+            return Err(invalid());
+        }
+        let data = &buf[pos..pos + len];
+        match (typ, len) {
+            (TYPE_A, 4) => {
+                let octets: [u8; 4] = data.try_into().unwrap();
+                list.push(Record::Ip(IpAddr::from(octets)));
+            }
+            (TYPE_AAAA, 16) => {
+                let octets: [u8; 16] = data.try_into().unwrap();
+                list.push(Record::Ip(IpAddr::from(octets)));
+            }
+            (TYPE_SRV, len) if len > 6 => {
+                //目标名称可能使用压缩指针，要从整个报文中读取
+                let (target, _) = read_name(buf, pos + 6).ok_or_else(invalid)?;
+                list.push(Record::Srv(SrvTarget {
+                    priority: u16::from_be_bytes([data[0], data[1]]),
+                    weight: u16::from_be_bytes([data[2], data[3]]),
+                    port: u16::from_be_bytes([data[4], data[5]]),
+                    target,
+                }));
+            }
+            //CNAME等，递归解析的服务器会一并返回最终的地址
+            _ => {}
+        }
+        pos += len;
+    }
+    Ok(Some(list))
+}
+
+/// 跳过名称，返回名称之后的位置，压缩指针只占两个字节
+fn skip_name(buf: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *buf.get(pos)? as usize;
+        if len == 0 {
+            return Some(pos + 1);
+        }
+        if len & 0xc0 == 0xc0 {
+            return Some(pos + 2);
+        }
+        pos += 1 + len;
+    }
+}
+
+/// 读取名称，返回名称和名称之后的位置，跟随压缩指针时限制跳转次数，避免指针成环
+fn read_name(buf: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    for _ in 0..32 {
+        let len = *buf.get(pos)? as usize;
+        if len == 0 {
+            return Some((labels.join("."), end.unwrap_or(pos + 1)));
+        }
+        if len & 0xc0 == 0xc0 {
+            let offset = ((len & 0x3f) << 8) | *buf.get(pos + 1)? as usize;
+            end.get_or_insert(pos + 2);
+            pos = offset;
+            continue;
+        }
+        let label = buf.get(pos + 1..pos + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        pos += 1 + len;
+    }
+    None
+}
+
+static BACKEND: RwLock<Option<Arc<dyn Resolve>>> = parking_lot::const_rwlock(None);
+/// 上次和服务端连通的地址，解析出多个地址时优先使用，
+/// 不在解析结果中时优先使用同一地址族，避免轮询的dns记录或者srv的权重让客户端在服务端之间来回切换
+static LAST_OK: AtomicCell<Option<SocketAddr>> = AtomicCell::new(None);
+
+/// 使用指定的dns服务器，None时使用系统解析
+pub fn set(server: Option<SocketAddr>) {
+    match server {
+        None => *BACKEND.write() = None,
+        Some(server) => set_backend(Arc::new(UdpResolver::new(server))),
+    }
+}
+
+/// 替换成调用方的实现
+pub fn set_backend(backend: Arc<dyn Resolve>) {
+    *BACKEND.write() = Some(backend);
+}
+
+/// 和服务端连通，记录使用的地址
+pub fn mark_ok(addr: SocketAddr) {
+    LAST_OK.store(Some(canonical(addr)));
+}
+
+/// ipv4映射的ipv6地址转换成ipv4地址
+fn canonical(addr: SocketAddr) -> SocketAddr {
+    if let SocketAddr::V6(ipv6) = addr {
+        if let Some(ipv4) = ipv6.ip().to_ipv4_mapped() {
+            return SocketAddr::new(ipv4.into(), ipv6.port());
+        }
+    }
+    addr
+}
+
+/// 服务端地址的写法
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ServerAddr {
+    /// ip地址，ipv6可以带方括号，没有端口时使用默认端口
+    Ip(SocketAddr),
+    /// 域名和端口
+    Host(String, u16),
+    /// 以_开头的srv记录名称，如_vnt._udp.example.com
+    Srv(String),
+}
+
+impl ServerAddr {
+    pub fn parse(addr: &str) -> io::Result<ServerAddr> {
+        let addr = addr.trim();
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid address {}", addr),
+            )
+        };
+        if let Ok(socket_addr) = addr.parse::<SocketAddr>() {
+            return Ok(ServerAddr::Ip(socket_addr));
+        }
+        //没有方括号和端口的ipv6地址
+        if let Ok(ip) = addr.parse::<IpAddr>() {
+            return Ok(ServerAddr::Ip(SocketAddr::new(ip, DEFAULT_PORT)));
+        }
+        if let Some(ip) = addr.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            let ip = ip.parse::<Ipv6Addr>().map_err(|_| invalid())?;
+            return Ok(ServerAddr::Ip(SocketAddr::new(ip.into(), DEFAULT_PORT)));
+        }
+        let (host, port) = match addr.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse::<u16>().map_err(|_| invalid())?),
+            None => (addr, DEFAULT_PORT),
+        };
+        if host.is_empty() || host.contains(|c: char| c.is_whitespace() || c == ':') {
+            return Err(invalid());
+        }
+        if host.starts_with('_') {
+            if host.len() != addr.len() {
+                //srv记录自带端口
+                return Err(invalid());
+            }
+            return Ok(ServerAddr::Srv(host.to_string()));
+        }
+        Ok(ServerAddr::Host(host.to_string(), port))
+    }
+}
+
+/// 按RFC 2782排序：优先级小的在前，同一优先级内按权重随机排列
+fn order_srv(mut list: Vec<SrvTarget>) -> Vec<SrvTarget> {
+    //只有一条记录且目标为.表示服务不可用
+    list.retain(|srv| !srv.target.is_empty());
+    list.sort_by_key(|srv| srv.priority);
+    let mut rng = rand::thread_rng();
+    let mut ordered = Vec::with_capacity(list.len());
+    while !list.is_empty() {
+        let priority = list[0].priority;
+        let count = list.iter().take_while(|v| v.priority == priority).count();
+        let mut group: Vec<SrvTarget> = list.drain(..count).collect();
+        while !group.is_empty() {
+            let total: u32 = group.iter().map(|v| v.weight as u32).sum();
+            let index = if total == 0 {
+                0
+            } else {
+                let mut pick = rng.gen_range(0..total);
+                group
+                    .iter()
+                    .position(|v| {
+                        if pick < v.weight as u32 {
+                            true
+                        } else {
+                            pick -= v.weight as u32;
+                            false
+                        }
+                    })
+                    .unwrap_or(0)
+            };
+            ordered.push(group.remove(index));
+        }
+    }
+    ordered
+}
+
+/// 解析服务端地址(见ServerAddr)，ip地址直接返回，
+/// 上次连通的地址排在最前面，其次是同一地址族的地址
+pub fn lookup(addr: &str) -> io::Result<Vec<SocketAddr>> {
+    let backend: Arc<dyn Resolve> = BACKEND
+        .read()
+        .clone()
+        .unwrap_or_else(|| Arc::new(SystemResolver));
+    let mut list = match ServerAddr::parse(addr)? {
+        ServerAddr::Ip(addr) => return Ok(vec![addr]),
+        ServerAddr::Host(host, port) => backend.lookup(&host, port)?,
+        ServerAddr::Srv(name) => {
+            let mut list = Vec::new();
+            let mut err = None;
+            for srv in order_srv(backend.lookup_srv(&name)?) {
+                match backend.lookup(&srv.target, srv.port) {
+                    Ok(addrs) => list.extend(addrs),
+                    Err(e) => err = Some(e),
+                }
+            }
+            if list.is_empty() {
+                return Err(err.unwrap_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("no srv record for {}", name),
+                    )
+                }));
+            }
+            list
+        }
+    };
+    if let Some(last) = LAST_OK.load() {
+        //稳定排序，同一类中保持解析结果的顺序
+        list.sort_by_key(|addr| {
+            let addr = canonical(*addr);
+            if addr == last {
+                0
+            } else if addr.is_ipv6() == last.is_ipv6() {
+                1
+            } else {
+                2
+            }
+        });
+    }
+    Ok(list)
+}
+
+/// 解析出的第一个地址
+pub fn lookup_one(addr: &str) -> io::Result<SocketAddr> {
+    lookup(addr)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no address for {}", addr)))
+}
+
+/// dns服务器地址，可以省略端口
+pub fn parse_server(s: &str) -> io::Result<SocketAddr> {
+    let s = s.trim();
+    if let Ok(ip) = s.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, 53));
+    }
+    s.parse::<SocketAddr>().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "invalid dns server {}, example: 223.5.5.5 or 223.5.5.5:53",
+                s
+            ),
+        )
+    })
+}
+
+#[test]
+fn test_parse_server_addr() {
+    let ip = |s: &str| ServerAddr::Ip(s.parse().unwrap());
+    assert_eq!(
+        ServerAddr::parse("1.2.3.4:29872").unwrap(),
+        ip("1.2.3.4:29872")
+    );
+    assert_eq!(ServerAddr::parse(" 1.2.3.4 ").unwrap(), ip("1.2.3.4:29872"));
+    assert_eq!(ServerAddr::parse("[::1]:8080").unwrap(), ip("[::1]:8080"));
+    assert_eq!(
+        ServerAddr::parse("[2001:db8::1]").unwrap(),
+        ip("[2001:db8::1]:29872")
+    );
+    assert_eq!(
+        ServerAddr::parse("2001:db8::1").unwrap(),
+        ip("[2001:db8::1]:29872")
+    );
+    assert_eq!(
+        ServerAddr::parse("vnt.example.com").unwrap(),
+        ServerAddr::Host("vnt.example.com".to_string(), DEFAULT_PORT)
+    );
+    assert_eq!(
+        ServerAddr::parse("vnt.example.com:443").unwrap(),
+        ServerAddr::Host("vnt.example.com".to_string(), 443)
+    );
+    assert_eq!(
+        ServerAddr::parse("_vnt._udp.example.com").unwrap(),
+        ServerAddr::Srv("_vnt._udp.example.com".to_string())
+    );
+    for addr in [
+        "",
+        ":29872",
+        "vnt.example.com:",
+        "vnt.example.com:70000",
+        "vnt example.com",
+        "[::1",
+        "[vnt.example.com]",
+        "2001:db8::1::2",
+        "_vnt._udp.example.com:29872",
+    ] {
+        assert!(ServerAddr::parse(addr).is_err(), "{}", addr);
+    }
+}
+
+#[test]
+fn test_order_srv() {
+    let srv = |priority: u16, weight: u16, target: &str| SrvTarget {
+        priority,
+        weight,
+        port: 29872,
+        target: target.to_string(),
+    };
+    // 优先级小的在前，目标为.(空)的记录被去掉
+    let ordered = order_srv(vec![
+        srv(20, 10, "c"),
+        srv(10, 10, "a"),
+        srv(30, 10, ""),
+        srv(15, 0, "b"),
+    ]);
+    let targets: Vec<&str> = ordered.iter().map(|v| v.target.as_str()).collect();
+    assert_eq!(targets, ["a", "b", "c"]);
+    // 同一优先级内权重为0的只有在其他记录都选完后才会被选
+    for _ in 0..100 {
+        let ordered = order_srv(vec![srv(10, 0, "zero"), srv(10, 100, "heavy")]);
+        assert_eq!(ordered[0].target, "heavy");
+        assert_eq!(ordered[1].target, "zero");
+    }
+    // 权重1:3时，权重大的大约3/4的次数排在前面
+    let mut heavy_first = 0;
+    for _ in 0..2000 {
+        let ordered = order_srv(vec![
+            srv(10, 1, "light"),
+            srv(10, 3, "heavy"),
+            srv(20, 100, "backup"),
+        ]);
+        assert_eq!(ordered.len(), 3);
+        assert_eq!(ordered[2].target, "backup");
+        if ordered[0].target == "heavy" {
+            heavy_first += 1;
+        }
+    }
+    assert!((1300..1700).contains(&heavy_first), "{}", heavy_first);
+}
@@ -0,0 +1,231 @@
+//! 处理不可信数据包的线程安装seccomp白名单，只允许收发数据、内存分配、同步等系统调用，
+//! 打开文件、创建socket、执行命令、创建进程等操作返回EPERM。
+//! 受限线程需要修改路由等操作时交给不受限的控制线程执行
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+type Task = Box<dyn FnOnce() + Send>;
+
+/// 控制线程，执行受限线程交过来的操作
+static CONTROL: parking_lot::Mutex<Option<std::sync::mpsc::Sender<Task>>> =
+    parking_lot::const_mutex(None);
+
+thread_local! {
+    static RESTRICTED: std::cell::Cell<bool> = std::cell::Cell::new(false);
+}
+
+/// 开启后restrict_thread才会生效，需要在启动收发线程之前调用
+pub fn set_enabled(enabled: bool) -> io::Result<()> {
+    if enabled && !cfg!(target_os = "linux") {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "seccomp only supports linux",
+        ));
+    }
+    let mut control = CONTROL.lock();
+    if enabled && control.is_none() {
+        let (sender, receiver) = std::sync::mpsc::channel::<Task>();
+        std::thread::Builder::new()
+            .name("seccompControl".into())
+            .spawn(move || {
+                while let Ok(task) = receiver.recv() {
+                    task();
+                }
+            })?;
+        control.replace(sender);
+    }
+    ENABLED.store(enabled, Ordering::Release);
+    Ok(())
+}
+
+/// 限制当前线程，未开启时不做任何事
+pub fn restrict_thread() {
+    if !ENABLED.load(Ordering::Acquire) || RESTRICTED.with(|v| v.get()) {
+        return;
+    }
+    match install() {
+        Ok(_) => RESTRICTED.with(|v| v.set(true)),
+        Err(e) => log::warn!("seccomp {:?}", e),
+    }
+}
+
+/// 受限线程中交给控制线程执行并等待结果，否则直接执行
+pub fn unrestricted<R, F>(f: F) -> R
+where
+    R: Send + 'static,
+    F: FnOnce() -> R + Send + 'static,
+{
+    if !RESTRICTED.with(|v| v.get()) {
+        return f();
+    }
+    let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+    let task: Task = Box::new(move || {
+        let _ = sender.send(std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)));
+    });
+    let control = CONTROL.lock().clone();
+    match control {
+        Some(control) => {
+            if let Err(e) = control.send(task) {
+                //控制线程已退出，只能直接执行
+                (e.0)();
+            }
+        }
+        None => task(),
+    }
+    match receiver.recv() {
+        Ok(Ok(r)) => r,
+        Ok(Err(e)) => std::panic::resume_unwind(e),
+        Err(_) => unreachable!(),
+    }
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+)))]
+fn install() -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "seccomp unsupported arch",
+    ))
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+))]
+fn install() -> io::Result<()> {
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xc000_003e;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xc000_00b7;
+    #[cfg(target_arch = "riscv64")]
+    const AUDIT_ARCH: u32 = 0xc000_00f3;
+
+    //BPF_LD|BPF_W|BPF_ABS、BPF_JMP|BPF_JEQ|BPF_K、BPF_RET|BPF_K
+    const BPF_LD_W_ABS: u16 = 0x20;
+    const BPF_JMP_JEQ_K: u16 = 0x15;
+    const BPF_RET_K: u16 = 0x06;
+    const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+    const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+    //seccomp_data中nr和arch的偏移
+    const NR_OFFSET: u32 = 0;
+    const ARCH_OFFSET: u32 = 4;
+
+    let allowed: Vec<libc::c_long> = vec![
+        //收发数据
+        libc::SYS_read,
+        libc::SYS_write,
+        libc::SYS_readv,
+        libc::SYS_writev,
+        libc::SYS_recvfrom,
+        libc::SYS_sendto,
+        libc::SYS_recvmsg,
+        libc::SYS_sendmsg,
+        libc::SYS_recvmmsg,
+        libc::SYS_sendmmsg,
+        libc::SYS_epoll_ctl,
+        libc::SYS_epoll_pwait,
+        libc::SYS_ppoll,
+        //关闭连接、设置超时，创建socket需要交给控制线程
+        libc::SYS_shutdown,
+        libc::SYS_close,
+        libc::SYS_setsockopt,
+        libc::SYS_getsockopt,
+        libc::SYS_getsockname,
+        libc::SYS_fcntl,
+        //内存和同步
+        libc::SYS_mmap,
+        libc::SYS_munmap,
+        libc::SYS_mremap,
+        libc::SYS_mprotect,
+        libc::SYS_madvise,
+        libc::SYS_brk,
+        libc::SYS_futex,
+        libc::SYS_sched_yield,
+        libc::SYS_membarrier,
+        libc::SYS_rseq,
+        //时间和随机数
+        libc::SYS_clock_gettime,
+        libc::SYS_clock_nanosleep,
+        libc::SYS_nanosleep,
+        libc::SYS_gettimeofday,
+        libc::SYS_getrandom,
+        //信号和退出
+        libc::SYS_rt_sigprocmask,
+        libc::SYS_rt_sigreturn,
+        libc::SYS_sigaltstack,
+        libc::SYS_restart_syscall,
+        libc::SYS_getpid,
+        libc::SYS_gettid,
+        libc::SYS_tgkill,
+        libc::SYS_exit,
+        libc::SYS_exit_group,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_poll,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_epoll_wait,
+        #[cfg(feature = "io-uring")]
+        libc::SYS_io_uring_enter,
+    ];
+
+    let n = allowed.len();
+    let mut filter = Vec::with_capacity(n + 5);
+    let stmt = |code: u16, k: u32| libc::sock_filter {
+        code,
+        jt: 0,
+        jf: 0,
+        k,
+    };
+    //架构不一致时系统调用号没有意义，全部拒绝
+    filter.push(stmt(BPF_LD_W_ABS, ARCH_OFFSET));
+    filter.push(libc::sock_filter {
+        code: BPF_JMP_JEQ_K,
+        jt: 1,
+        jf: 0,
+        k: AUDIT_ARCH,
+    });
+    filter.push(stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::EPERM as u32));
+    filter.push(stmt(BPF_LD_W_ABS, NR_OFFSET));
+    for (i, nr) in allowed.iter().enumerate() {
+        //命中时跳到最后的ALLOW
+        filter.push(libc::sock_filter {
+            code: BPF_JMP_JEQ_K,
+            jt: (n - i) as u8,
+            jf: 0,
+            k: *nr as u32,
+        });
+    }
+    filter.push(stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::EPERM as u32));
+    filter.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+    let prog = libc::sock_fprog {
+        len: filter.len() as u16,
+        filter: filter.as_mut_ptr(),
+    };
+    unsafe {
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        //只作用于当前线程
+        if libc::prctl(
+            libc::PR_SET_SECCOMP,
+            libc::SECCOMP_MODE_FILTER,
+            &prog as *const libc::sock_fprog,
+        ) < 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,443 @@
+use std::collections::VecDeque;
+use std::io;
+use std::mem;
+use std::net::SocketAddr;
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use io_uring::{cqueue, opcode, squeue, types, IoUring, Probe};
+use parking_lot::Mutex;
+
+/* io_uring读写，内核低于5.10或者被seccomp拦截时返回None，调用方回退到普通系统调用
+   读：预先提交多个读请求，每次io_uring_enter同时提交上次消费掉的请求并等待完成，
+       一次等待可能取回多个完成的数据包，之后的读取不需要系统调用
+   写：在调用线程中复制到预分配的缓冲区并提交，不等待完成，之后的写入顺便回收已完成的请求；
+       缓冲区用完时等待完成，不能使用io_uring时先等之前的写入完成再直接写，保证顺序
+   关闭：读请求阻塞在内核中，关闭时通过eventfd唤醒
+*/
+
+const SLOT_LEN: usize = 4096;
+const CANCEL: u64 = u64::MAX;
+
+fn probe(ring: &IoUring, codes: &[u8]) -> bool {
+    let mut probe = Probe::new();
+    if let Err(e) = ring.submitter().register_probe(&mut probe) {
+        log::warn!("io_uring探测失败,使用普通io:{:?}", e);
+        return false;
+    }
+    codes.iter().all(|code| probe.is_supported(*code))
+}
+
+fn new_ring(entries: u32, codes: &[u8]) -> Option<IoUring> {
+    let ring = match IoUring::new(entries) {
+        Ok(ring) => ring,
+        Err(e) => {
+            log::warn!("io_uring不可用,使用普通io:{:?}", e);
+            return None;
+        }
+    };
+    if !probe(&ring, codes) {
+        log::warn!("io_uring不支持需要的操作,使用普通io");
+        return None;
+    }
+    Some(ring)
+}
+
+fn push(ring: &mut IoUring, entry: &squeue::Entry) -> io::Result<()> {
+    unsafe {
+        ring.submission()
+            .push(entry)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+}
+
+/// 唤醒阻塞在io_uring读上的线程
+#[derive(Clone)]
+pub struct UringCanceler {
+    fd: Arc<EventFd>,
+}
+
+impl UringCanceler {
+    pub fn cancel(&self) {
+        let v: u64 = 1;
+        unsafe {
+            libc::write(self.fd.0, &v as *const u64 as *const libc::c_void, 8);
+        }
+    }
+}
+
+struct EventFd(RawFd);
+
+impl Drop for EventFd {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.0);
+        }
+    }
+}
+
+struct Slot {
+    buf: [u8; SLOT_LEN],
+    storage: libc::sockaddr_storage,
+    iov: libc::iovec,
+    msg: libc::msghdr,
+}
+
+/// 读取tun网卡(read)或者udp(recvmsg)，只在一个线程中使用
+pub struct UringReader {
+    ring: IoUring,
+    fd: RawFd,
+    recv: bool,
+    slots: Vec<Box<Slot>>,
+    //已经完成还没被取走的请求，(slot,结果)
+    ready: VecDeque<(usize, i32)>,
+    //取走数据后需要重新提交的slot
+    rearm: Vec<usize>,
+    in_flight: usize,
+    cancel: Arc<EventFd>,
+    canceled: bool,
+}
+
+unsafe impl Send for UringReader {}
+
+impl UringReader {
+    /// 读取网卡
+    pub fn tun(fd: RawFd, depth: usize) -> Option<UringReader> {
+        Self::new(fd, depth, false)
+    }
+    /// 接收udp
+    pub fn udp(fd: RawFd, depth: usize) -> Option<UringReader> {
+        Self::new(fd, depth, true)
+    }
+    fn new(fd: RawFd, depth: usize, recv: bool) -> Option<UringReader> {
+        let depth = depth.max(1);
+        let code = if recv {
+            opcode::RecvMsg::CODE
+        } else {
+            opcode::Read::CODE
+        };
+        let ring = new_ring(
+            (depth + 2).next_power_of_two() as u32,
+            &[code, opcode::PollAdd::CODE, opcode::AsyncCancel::CODE],
+        )?;
+        let event_fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+        if event_fd < 0 {
+            log::warn!("eventfd失败,使用普通io:{:?}", io::Error::last_os_error());
+            return None;
+        }
+        let mut slots = Vec::with_capacity(depth);
+        for _ in 0..depth {
+            let mut slot: Box<Slot> = Box::new(unsafe { mem::zeroed() });
+            slot.iov.iov_base = slot.buf.as_mut_ptr() as *mut libc::c_void;
+            slot.iov.iov_len = SLOT_LEN;
+            slots.push(slot);
+        }
+        let mut reader = UringReader {
+            ring,
+            fd,
+            recv,
+            slots,
+            ready: VecDeque::with_capacity(depth),
+            rearm: (0..depth).collect(),
+            in_flight: 0,
+            cancel: Arc::new(EventFd(event_fd)),
+            canceled: false,
+        };
+        //关闭时写入eventfd，poll完成后读取返回错误
+        let entry = opcode::PollAdd::new(types::Fd(event_fd), libc::POLLIN as u32)
+            .build()
+            .user_data(CANCEL);
+        if let Err(e) = push(&mut reader.ring, &entry) {
+            log::warn!("io_uring提交失败,使用普通io:{:?}", e);
+            return None;
+        }
+        reader.in_flight += 1;
+        Some(reader)
+    }
+    pub fn canceler(&self) -> UringCanceler {
+        UringCanceler {
+            fd: self.cancel.clone(),
+        }
+    }
+    fn entry(&mut self, index: usize) -> squeue::Entry {
+        let slot = &mut self.slots[index];
+        let entry = if self.recv {
+            slot.msg = unsafe { mem::zeroed() };
+            slot.msg.msg_name =
+                &mut slot.storage as *mut libc::sockaddr_storage as *mut libc::c_void;
+            slot.msg.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+            slot.msg.msg_iov = &mut slot.iov;
+            slot.msg.msg_iovlen = 1;
+            opcode::RecvMsg::new(types::Fd(self.fd), &mut slot.msg).build()
+        } else {
+            opcode::Read::new(types::Fd(self.fd), slot.buf.as_mut_ptr(), SLOT_LEN as u32).build()
+        };
+        entry.user_data(index as u64)
+    }
+    /// 取出一个完成的请求，没有时提交并等待
+    fn next(&mut self) -> io::Result<(usize, i32)> {
+        loop {
+            if self.canceled {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "io_uring read canceled",
+                ));
+            }
+            if let Some(v) = self.ready.pop_front() {
+                return Ok(v);
+            }
+            for index in mem::take(&mut self.rearm) {
+                let entry = self.entry(index);
+                push(&mut self.ring, &entry)?;
+                self.in_flight += 1;
+            }
+            match self.ring.submit_and_wait(1) {
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+            let completed: Vec<cqueue::Entry> = self.ring.completion().collect();
+            for cqe in completed {
+                self.in_flight -= 1;
+                if cqe.user_data() == CANCEL {
+                    self.canceled = true;
+                } else {
+                    self.ready
+                        .push_back((cqe.user_data() as usize, cqe.result()));
+                }
+            }
+        }
+    }
+    fn take(&mut self, buf: &mut [u8]) -> io::Result<(usize, usize)> {
+        let (index, res) = self.next()?;
+        self.rearm.push(index);
+        if res < 0 {
+            return Err(io::Error::from_raw_os_error(-res));
+        }
+        let len = (res as usize).min(buf.len());
+        buf[..len].copy_from_slice(&self.slots[index].buf[..len]);
+        Ok((index, len))
+    }
+    pub fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.take(buf).map(|(_, len)| len)
+    }
+    pub fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let (index, len) = self.take(buf)?;
+        let slot = &self.slots[index];
+        let addr = unsafe { socket2::SockAddr::new(slot.storage, slot.msg.msg_namelen) };
+        match addr.as_socket() {
+            Some(addr) => Ok((len, addr)),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid socket address",
+            )),
+        }
+    }
+}
+
+impl Drop for UringReader {
+    fn drop(&mut self) {
+        //内核完成请求之前不能释放缓冲区
+        let mut pending: Vec<u64> = (0..self.slots.len() as u64)
+            .filter(|i| !self.rearm.contains(&(*i as usize)))
+            .filter(|i| !self.ready.iter().any(|(v, _)| *v as u64 == *i))
+            .collect();
+        if !self.canceled {
+            pending.push(CANCEL);
+        }
+        for user_data in pending {
+            let _ = push(&mut self.ring, &opcode::AsyncCancel::new(user_data).build());
+            self.in_flight += 1;
+        }
+        while self.in_flight > 0 {
+            if self.ring.submit_and_wait(1).is_err() {
+                break;
+            }
+            let n = self.ring.completion().count();
+            self.in_flight = self.in_flight.saturating_sub(n);
+        }
+    }
+}
+
+struct WriteSlot {
+    buf: [u8; SLOT_LEN],
+    addr: Option<socket2::SockAddr>,
+    iov: libc::iovec,
+    msg: libc::msghdr,
+}
+
+struct WriteRing {
+    ring: IoUring,
+    //预先分配的缓冲区，请求完成前不能复用
+    slots: Vec<Box<WriteSlot>>,
+    free: Vec<usize>,
+    failed: u64,
+}
+
+unsafe impl Send for WriteRing {}
+
+impl WriteRing {
+    fn handle(&mut self, cqe: cqueue::Entry) {
+        self.free.push(cqe.user_data() as usize);
+        if cqe.result() < 0 {
+            self.failed += 1;
+            //按1,2,4,8...次输出，避免持续失败时刷屏
+            if self.failed.is_power_of_two() {
+                log::warn!(
+                    "io_uring写入错误:{:?},累计失败{}次",
+                    io::Error::from_raw_os_error(-cqe.result()),
+                    self.failed
+                );
+            }
+        }
+    }
+    /// 回收已完成的请求，不等待
+    fn reap(&mut self) {
+        let completed: Vec<cqueue::Entry> = self.ring.completion().collect();
+        for cqe in completed {
+            self.handle(cqe);
+        }
+    }
+    /// 至少等待一个请求完成
+    fn wait(&mut self) -> io::Result<()> {
+        loop {
+            match self.ring.submit_and_wait(1) {
+                Ok(_) => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        self.reap();
+        Ok(())
+    }
+    /// 等待所有请求完成
+    fn drain(&mut self) -> io::Result<()> {
+        self.reap();
+        while self.free.len() < self.slots.len() {
+            self.wait()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for WriteRing {
+    fn drop(&mut self) {
+        //内核完成请求之前不能释放缓冲区
+        if let Err(e) = self.drain() {
+            log::error!("io_uring写入等待完成失败:{:?}", e);
+            mem::forget(mem::take(&mut self.slots));
+        }
+    }
+}
+
+/// 写入tun网卡(write)或者udp(sendmsg)，在调用线程中复制到预分配的缓冲区并提交，不等待完成
+#[derive(Clone)]
+pub struct UringWriter {
+    ring: Arc<Mutex<WriteRing>>,
+    closed: Arc<AtomicBool>,
+}
+
+impl UringWriter {
+    pub fn new(entries: usize) -> Option<UringWriter> {
+        let entries = entries.max(1).next_power_of_two();
+        let ring = new_ring(
+            entries as u32,
+            &[opcode::Write::CODE, opcode::SendMsg::CODE],
+        )?;
+        let mut slots = Vec::with_capacity(entries);
+        for _ in 0..entries {
+            let slot: Box<WriteSlot> = Box::new(WriteSlot {
+                buf: [0; SLOT_LEN],
+                addr: None,
+                iov: unsafe { mem::zeroed() },
+                msg: unsafe { mem::zeroed() },
+            });
+            slots.push(slot);
+        }
+        Some(UringWriter {
+            ring: Arc::new(Mutex::new(WriteRing {
+                ring,
+                slots,
+                free: (0..entries).collect(),
+                failed: 0,
+            })),
+            closed: Arc::new(AtomicBool::new(false)),
+        })
+    }
+    /// 返回false时由调用方直接写入，此时之前提交的写入都已完成，不会乱序
+    pub fn write(&self, fd: RawFd, buf: &[u8]) -> bool {
+        self.submit(fd, buf, None)
+    }
+    pub fn send_to(&self, fd: RawFd, buf: &[u8], addr: SocketAddr) -> bool {
+        self.submit(fd, buf, Some(addr))
+    }
+    /// 完成结果为错误的写入次数
+    pub fn failed(&self) -> u64 {
+        self.ring.lock().failed
+    }
+    fn submit(&self, fd: RawFd, buf: &[u8], addr: Option<SocketAddr>) -> bool {
+        let mut guard = self.ring.lock();
+        let ring = &mut *guard;
+        if self.closed.load(Ordering::Relaxed) || buf.len() > SLOT_LEN {
+            //直接写入之前等待队列中的写入完成
+            return Self::fallback(ring);
+        }
+        ring.reap();
+        //缓冲区用完时等待完成，不绕过队列直接写入
+        if ring.free.is_empty() {
+            if let Err(e) = ring.wait() {
+                log::warn!("io_uring等待写入完成失败:{:?}", e);
+                return Self::fallback(ring);
+            }
+        }
+        let index = match ring.free.pop() {
+            Some(index) => index,
+            None => return Self::fallback(ring),
+        };
+        let slot = &mut ring.slots[index];
+        slot.buf[..buf.len()].copy_from_slice(buf);
+        let entry = match addr {
+            Some(addr) => {
+                let addr = slot.addr.insert(socket2::SockAddr::from(addr));
+                slot.iov.iov_base = slot.buf.as_mut_ptr() as *mut libc::c_void;
+                slot.iov.iov_len = buf.len();
+                slot.msg.msg_name = addr.as_ptr() as *mut libc::c_void;
+                slot.msg.msg_namelen = addr.len();
+                slot.msg.msg_iov = &mut slot.iov;
+                slot.msg.msg_iovlen = 1;
+                opcode::SendMsg::new(types::Fd(fd), &slot.msg).build()
+            }
+            None => opcode::Write::new(types::Fd(fd), slot.buf.as_ptr(), buf.len() as u32).build(),
+        };
+        if let Err(e) = push(&mut ring.ring, &entry.user_data(index as u64)) {
+            log::warn!("io_uring写入提交失败:{:?}", e);
+            ring.free.push(index);
+            return Self::fallback(ring);
+        }
+        loop {
+            match ring.ring.submit() {
+                Ok(_) => return true,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    //已经在提交队列中，等内核下次取走，不能再直接写入一次
+                    log::warn!("io_uring写入提交失败:{:?}", e);
+                    return true;
+                }
+            }
+        }
+    }
+    fn fallback(ring: &mut WriteRing) -> bool {
+        if let Err(e) = ring.drain() {
+            log::warn!("io_uring等待写入完成失败:{:?}", e);
+        }
+        false
+    }
+    pub fn close(&self) {
+        if !self.closed.swap(true, Ordering::AcqRel) {
+            if let Err(e) = self.ring.lock().drain() {
+                log::warn!("io_uring等待写入完成失败:{:?}", e);
+            }
+        }
+    }
+}
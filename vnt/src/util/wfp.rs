@@ -0,0 +1,421 @@
+#![allow(non_snake_case, non_camel_case_types)]
+//! WFP(Windows Filtering Platform)的最小封装，使用动态会话，
+//! 会话关闭或者进程退出时系统会自动删除添加的规则
+
+use std::ffi::c_void;
+use std::net::Ipv4Addr;
+use std::{io, mem, ptr};
+
+use winapi::shared::guiddef::GUID;
+use winapi::shared::ifdef::NET_LUID;
+use winapi::shared::netioapi::ConvertInterfaceAliasToLuid;
+use winapi::um::winnt::HANDLE;
+
+#[repr(C)]
+struct FWPM_DISPLAY_DATA0 {
+    name: *mut u16,
+    description: *mut u16,
+}
+
+#[repr(C)]
+struct FWPM_SESSION0 {
+    sessionKey: GUID,
+    displayData: FWPM_DISPLAY_DATA0,
+    flags: u32,
+    txnWaitTimeoutInMSec: u32,
+    processId: u32,
+    sid: *mut c_void,
+    username: *mut u16,
+    kernelMode: i32,
+}
+
+#[repr(C)]
+struct FWP_BYTE_BLOB {
+    size: u32,
+    data: *mut u8,
+}
+
+/// FWP_VALUE0和FWP_CONDITION_VALUE0，联合体中最大的成员是指针
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct FWP_VALUE0 {
+    r#type: u32,
+    value: usize,
+}
+
+#[repr(C)]
+pub struct FWPM_FILTER_CONDITION0 {
+    fieldKey: GUID,
+    matchType: u32,
+    conditionValue: FWP_VALUE0,
+}
+
+#[repr(C)]
+struct FWPM_ACTION0 {
+    r#type: u32,
+    filterType: GUID,
+}
+
+#[repr(C)]
+struct FWPM_FILTER0 {
+    filterKey: GUID,
+    displayData: FWPM_DISPLAY_DATA0,
+    flags: u32,
+    providerKey: *mut GUID,
+    providerData: FWP_BYTE_BLOB,
+    layerKey: GUID,
+    subLayerKey: GUID,
+    weight: FWP_VALUE0,
+    numFilterConditions: u32,
+    filterCondition: *mut FWPM_FILTER_CONDITION0,
+    action: FWPM_ACTION0,
+    providerContextKey: [u64; 2],
+    reserved: *mut GUID,
+    filterId: u64,
+    effectiveWeight: FWP_VALUE0,
+}
+
+#[repr(C)]
+struct FWPM_SUBLAYER0 {
+    subLayerKey: GUID,
+    displayData: FWPM_DISPLAY_DATA0,
+    flags: u32,
+    providerKey: *mut GUID,
+    providerData: FWP_BYTE_BLOB,
+    weight: u16,
+}
+
+#[link(name = "fwpuclnt")]
+extern "system" {
+    fn FwpmEngineOpen0(
+        serverName: *const u16,
+        authnService: u32,
+        authIdentity: *mut c_void,
+        session: *const FWPM_SESSION0,
+        engineHandle: *mut HANDLE,
+    ) -> u32;
+    fn FwpmEngineClose0(engineHandle: HANDLE) -> u32;
+    fn FwpmSubLayerAdd0(engineHandle: HANDLE, subLayer: *const FWPM_SUBLAYER0, sd: *mut c_void)
+        -> u32;
+    fn FwpmFilterAdd0(
+        engineHandle: HANDLE,
+        filter: *const FWPM_FILTER0,
+        sd: *mut c_void,
+        id: *mut u64,
+    ) -> u32;
+    fn FwpmGetAppIdFromFileName0(fileName: *const u16, appId: *mut *mut FWP_BYTE_BLOB) -> u32;
+    fn FwpmFreeMemory0(p: *mut *mut c_void);
+}
+
+const RPC_C_AUTHN_WINNT: u32 = 10;
+const FWPM_SESSION_FLAG_DYNAMIC: u32 = 0x1;
+const FWP_EMPTY: u32 = 0;
+const FWP_UINT8: u32 = 1;
+const FWP_UINT16: u32 = 2;
+const FWP_UINT32: u32 = 3;
+const FWP_UINT64: u32 = 4;
+const FWP_BYTE_ARRAY16_TYPE: u32 = 11;
+const FWP_BYTE_BLOB_TYPE: u32 = 12;
+const FWP_MATCH_EQUAL: u32 = 0;
+const FWP_MATCH_FLAGS_ALL_SET: u32 = 6;
+const FWP_CONDITION_FLAG_IS_LOOPBACK: u32 = 0x1;
+pub const FWP_ACTION_BLOCK: u32 = 0x1001;
+pub const FWP_ACTION_PERMIT: u32 = 0x1002;
+pub const IPPROTO_TCP: u8 = 6;
+pub const IPPROTO_UDP: u8 = 17;
+
+pub const fn guid(d1: u32, d2: u16, d3: u16, d4: [u8; 8]) -> GUID {
+    GUID {
+        Data1: d1,
+        Data2: d2,
+        Data3: d3,
+        Data4: d4,
+    }
+}
+
+pub const FWPM_LAYER_ALE_AUTH_CONNECT_V4: GUID = guid(
+    0xc38d57d1,
+    0x05a7,
+    0x4c33,
+    [0x90, 0x4f, 0x7f, 0xbc, 0xee, 0xe6, 0x0e, 0x82],
+);
+pub const FWPM_LAYER_ALE_AUTH_CONNECT_V6: GUID = guid(
+    0x4a72393b,
+    0x319f,
+    0x44bc,
+    [0x84, 0xc3, 0xba, 0x54, 0xdc, 0xb3, 0xb6, 0xb4],
+);
+const FWPM_CONDITION_ALE_APP_ID: GUID = guid(
+    0xd78e1e87,
+    0x8644,
+    0x4ea5,
+    [0x94, 0x37, 0xd8, 0x09, 0xec, 0xef, 0xc9, 0x71],
+);
+const FWPM_CONDITION_IP_LOCAL_INTERFACE: GUID = guid(
+    0x4cd62a49,
+    0x59c3,
+    0x4969,
+    [0xb7, 0xf3, 0xbd, 0xa5, 0xd3, 0x28, 0x90, 0xa4],
+);
+const FWPM_CONDITION_FLAGS: GUID = guid(
+    0x632ce23b,
+    0x5167,
+    0x435c,
+    [0x86, 0xd7, 0xe9, 0x03, 0x68, 0x4a, 0xa8, 0x0c],
+);
+const FWPM_CONDITION_IP_PROTOCOL: GUID = guid(
+    0x3971ef2b,
+    0x623e,
+    0x4f9a,
+    [0x8c, 0xb1, 0x6e, 0x79, 0xb8, 0x06, 0xb9, 0xa7],
+);
+const FWPM_CONDITION_IP_REMOTE_ADDRESS: GUID = guid(
+    0xb235ae9a,
+    0x1d64,
+    0x49b8,
+    [0xa4, 0x4c, 0x5f, 0xf3, 0xd9, 0x09, 0x50, 0x45],
+);
+const FWPM_CONDITION_IP_REMOTE_PORT: GUID = guid(
+    0xc35a604d,
+    0xd22b,
+    0x4e1a,
+    [0x91, 0xb4, 0x68, 0xf6, 0x74, 0xee, 0x67, 0x4b],
+);
+const FWPM_CONDITION_IP_LOCAL_PORT: GUID = guid(
+    0x0c1ba1af,
+    0x5765,
+    0x453f,
+    [0xaf, 0x22, 0xa8, 0xf7, 0x91, 0xac, 0x77, 0x5b],
+);
+
+fn encode_utf16(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+fn check(rs: u32, msg: &str) -> io::Result<()> {
+    if rs != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("{} error:{:#x}", msg, rs),
+        ));
+    }
+    Ok(())
+}
+
+/// 网卡名称转换成luid
+pub fn interface_luid(name: &str) -> io::Result<Box<u64>> {
+    let mut luid: NET_LUID = unsafe { mem::zeroed() };
+    let alias = encode_utf16(name);
+    check(
+        unsafe { ConvertInterfaceAliasToLuid(alias.as_ptr(), &mut luid) },
+        "ConvertInterfaceAliasToLuid",
+    )?;
+    Ok(Box::new(unsafe { *luid.Value() }))
+}
+
+/// 程序的app id，添加规则时使用，需要在规则添加完成前保持存活
+pub struct AppId(*mut FWP_BYTE_BLOB);
+
+impl AppId {
+    pub fn from_path(path: &str) -> io::Result<AppId> {
+        let path = encode_utf16(path);
+        let mut app_id: *mut FWP_BYTE_BLOB = ptr::null_mut();
+        check(
+            unsafe { FwpmGetAppIdFromFileName0(path.as_ptr(), &mut app_id) },
+            "FwpmGetAppIdFromFileName0",
+        )?;
+        Ok(AppId(app_id))
+    }
+    pub fn current_exe() -> io::Result<AppId> {
+        let exe = std::env::current_exe()?;
+        AppId::from_path(&exe.to_string_lossy())
+    }
+}
+
+impl Drop for AppId {
+    fn drop(&mut self) {
+        unsafe { FwpmFreeMemory0(&mut self.0 as *mut _ as *mut *mut c_void) };
+    }
+}
+
+/// 程序匹配条件
+pub fn app_condition(app_id: &AppId) -> FWPM_FILTER_CONDITION0 {
+    FWPM_FILTER_CONDITION0 {
+        fieldKey: FWPM_CONDITION_ALE_APP_ID,
+        matchType: FWP_MATCH_EQUAL,
+        conditionValue: FWP_VALUE0 {
+            r#type: FWP_BYTE_BLOB_TYPE,
+            value: app_id.0 as usize,
+        },
+    }
+}
+
+/// 网卡匹配条件，luid需要在规则添加完成前保持存活
+pub fn interface_condition(luid: &u64) -> FWPM_FILTER_CONDITION0 {
+    FWPM_FILTER_CONDITION0 {
+        fieldKey: FWPM_CONDITION_IP_LOCAL_INTERFACE,
+        matchType: FWP_MATCH_EQUAL,
+        conditionValue: FWP_VALUE0 {
+            r#type: FWP_UINT64,
+            value: luid as *const u64 as usize,
+        },
+    }
+}
+
+/// 回环流量匹配条件
+pub fn loopback_condition() -> FWPM_FILTER_CONDITION0 {
+    FWPM_FILTER_CONDITION0 {
+        fieldKey: FWPM_CONDITION_FLAGS,
+        matchType: FWP_MATCH_FLAGS_ALL_SET,
+        conditionValue: FWP_VALUE0 {
+            r#type: FWP_UINT32,
+            value: FWP_CONDITION_FLAG_IS_LOOPBACK as usize,
+        },
+    }
+}
+
+/// 传输层协议匹配条件
+pub fn protocol_condition(protocol: u8) -> FWPM_FILTER_CONDITION0 {
+    FWPM_FILTER_CONDITION0 {
+        fieldKey: FWPM_CONDITION_IP_PROTOCOL,
+        matchType: FWP_MATCH_EQUAL,
+        conditionValue: FWP_VALUE0 {
+            r#type: FWP_UINT8,
+            value: protocol as usize,
+        },
+    }
+}
+
+/// 远端ipv4地址匹配条件
+pub fn remote_v4_condition(ip: Ipv4Addr) -> FWPM_FILTER_CONDITION0 {
+    FWPM_FILTER_CONDITION0 {
+        fieldKey: FWPM_CONDITION_IP_REMOTE_ADDRESS,
+        matchType: FWP_MATCH_EQUAL,
+        conditionValue: FWP_VALUE0 {
+            r#type: FWP_UINT32,
+            value: u32::from(ip) as usize,
+        },
+    }
+}
+
+/// 远端ipv6地址匹配条件，地址需要在规则添加完成前保持存活
+pub fn remote_v6_condition(ip: &[u8; 16]) -> FWPM_FILTER_CONDITION0 {
+    FWPM_FILTER_CONDITION0 {
+        fieldKey: FWPM_CONDITION_IP_REMOTE_ADDRESS,
+        matchType: FWP_MATCH_EQUAL,
+        conditionValue: FWP_VALUE0 {
+            r#type: FWP_BYTE_ARRAY16_TYPE,
+            value: ip as *const [u8; 16] as usize,
+        },
+    }
+}
+
+/// 远端端口匹配条件
+pub fn remote_port_condition(port: u16) -> FWPM_FILTER_CONDITION0 {
+    FWPM_FILTER_CONDITION0 {
+        fieldKey: FWPM_CONDITION_IP_REMOTE_PORT,
+        matchType: FWP_MATCH_EQUAL,
+        conditionValue: FWP_VALUE0 {
+            r#type: FWP_UINT16,
+            value: port as usize,
+        },
+    }
+}
+
+/// 本地端口匹配条件
+pub fn local_port_condition(port: u16) -> FWPM_FILTER_CONDITION0 {
+    FWPM_FILTER_CONDITION0 {
+        fieldKey: FWPM_CONDITION_IP_LOCAL_PORT,
+        matchType: FWP_MATCH_EQUAL,
+        conditionValue: FWP_VALUE0 {
+            r#type: FWP_UINT16,
+            value: port as usize,
+        },
+    }
+}
+
+pub struct Engine {
+    handle: HANDLE,
+}
+
+unsafe impl Send for Engine {}
+
+unsafe impl Sync for Engine {}
+
+impl Engine {
+    /// 打开动态会话
+    pub fn open_dynamic() -> io::Result<Engine> {
+        let mut session: FWPM_SESSION0 = unsafe { mem::zeroed() };
+        session.flags = FWPM_SESSION_FLAG_DYNAMIC;
+        let mut handle: HANDLE = ptr::null_mut();
+        check(
+            unsafe {
+                FwpmEngineOpen0(
+                    ptr::null(),
+                    RPC_C_AUTHN_WINNT,
+                    ptr::null_mut(),
+                    &session,
+                    &mut handle,
+                )
+            },
+            "FwpmEngineOpen0",
+        )?;
+        Ok(Engine { handle })
+    }
+    pub fn add_sublayer(&self, key: GUID, name: &str, weight: u16) -> io::Result<()> {
+        let mut name = encode_utf16(name);
+        let mut sublayer: FWPM_SUBLAYER0 = unsafe { mem::zeroed() };
+        sublayer.subLayerKey = key;
+        sublayer.displayData.name = name.as_mut_ptr();
+        sublayer.weight = weight;
+        check(
+            unsafe { FwpmSubLayerAdd0(self.handle, &sublayer, ptr::null_mut()) },
+            "FwpmSubLayerAdd0",
+        )
+    }
+    /// 不同字段的条件之间是与的关系，同一字段的多个条件之间是或的关系，
+    /// weight为None时由系统自动分配权重
+    pub fn add_filter(
+        &self,
+        name: &str,
+        layer: GUID,
+        sublayer: GUID,
+        conditions: &mut [FWPM_FILTER_CONDITION0],
+        action: u32,
+        weight: Option<u8>,
+    ) -> io::Result<()> {
+        let mut name = encode_utf16(name);
+        let mut filter: FWPM_FILTER0 = unsafe { mem::zeroed() };
+        filter.displayData.name = name.as_mut_ptr();
+        filter.layerKey = layer;
+        filter.subLayerKey = sublayer;
+        filter.action.r#type = action;
+        filter.weight = match weight {
+            None => FWP_VALUE0 {
+                r#type: FWP_EMPTY,
+                value: 0,
+            },
+            Some(weight) => FWP_VALUE0 {
+                r#type: FWP_UINT8,
+                value: weight as usize,
+            },
+        };
+        if !conditions.is_empty() {
+            filter.numFilterConditions = conditions.len() as u32;
+            filter.filterCondition = conditions.as_mut_ptr();
+        }
+        let mut id = 0u64;
+        check(
+            unsafe { FwpmFilterAdd0(self.handle, &filter, ptr::null_mut(), &mut id) },
+            "FwpmFilterAdd0",
+        )
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        unsafe {
+            FwpmEngineClose0(self.handle);
+        }
+    }
+}
@@ -46,4 +46,6 @@ pub trait IFace {
     fn set_mtu(&self, mtu: u16) -> io::Result<()>;
     /// 设置跃点
     fn set_metric(&self, metric: u16) -> io::Result<()>;
+    /// 删除手动添加的路由
+    fn delete_static_routes(&self) -> io::Result<()>;
 }
@@ -1,8 +1,50 @@
-use std::io;
 use std::net::Ipv4Addr;
-use std::os::windows::process::CommandExt;
+use std::{io, mem, slice};
 
-/// 添加路由
+use winapi::shared::netioapi::{
+    CreateIpForwardEntry2, DeleteIpForwardEntry2, FreeMibTable, GetIpForwardTable2,
+    GetIpInterfaceEntry, InitializeIpForwardEntry, InitializeIpInterfaceEntry,
+    SetIpForwardEntry2, SetIpInterfaceEntry, MIB_IPFORWARD_ROW2, MIB_IPINTERFACE_ROW,
+    PMIB_IPFORWARD_TABLE2,
+};
+use winapi::shared::nldef::MIB_IPPROTO_NETMGMT;
+use winapi::shared::winerror::{ERROR_OBJECT_ALREADY_EXISTS, NO_ERROR};
+use winapi::shared::ws2def::AF_INET;
+use winapi::shared::ws2ipdef::SOCKADDR_INET;
+
+fn sockaddr_inet(ip: Ipv4Addr) -> SOCKADDR_INET {
+    unsafe {
+        let mut addr: SOCKADDR_INET = mem::zeroed();
+        let v4 = addr.Ipv4_mut();
+        v4.sin_family = AF_INET as u16;
+        *v4.sin_addr.S_un.S_addr_mut() = u32::from_ne_bytes(ip.octets());
+        addr
+    }
+}
+
+fn sockaddr_ip(addr: &SOCKADDR_INET) -> Ipv4Addr {
+    unsafe { Ipv4Addr::from(addr.Ipv4().sin_addr.S_un.S_addr().to_ne_bytes()) }
+}
+
+fn forward_row(
+    index: u32,
+    dest: Ipv4Addr,
+    netmask: Ipv4Addr,
+    gateway: Ipv4Addr,
+) -> MIB_IPFORWARD_ROW2 {
+    unsafe {
+        let mut row: MIB_IPFORWARD_ROW2 = mem::zeroed();
+        InitializeIpForwardEntry(&mut row);
+        row.InterfaceIndex = index;
+        row.DestinationPrefix.Prefix = sockaddr_inet(dest);
+        row.DestinationPrefix.PrefixLength = u32::from(netmask).count_ones() as u8;
+        row.NextHop = sockaddr_inet(gateway);
+        row.Protocol = MIB_IPPROTO_NETMGMT;
+        row
+    }
+}
+
+/// 添加路由，已存在相同路由时更新跃点
 pub fn add_route(
     index: u32,
     dest: Ipv4Addr,
@@ -10,23 +52,22 @@ pub fn add_route(
     gateway: Ipv4Addr,
     metric: u16,
 ) -> io::Result<()> {
-    let set_route = format!(
-        "route add {:?} mask {:?} {:?} metric {} if {}",
-        dest, netmask, gateway, metric, index
-    );
-    // 执行添加路由命令
-    let out = std::process::Command::new("cmd")
-        .creation_flags(0x08000000)
-        .arg("/C")
-        .arg(&set_route)
-        .output()
-        .unwrap();
-    if !out.status.success() {
-        log::error!("cmd={:?},out={:?}", set_route, out);
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!("添加路由失败: {:?}", out),
-        ));
+    let mut row = forward_row(index, dest, netmask, gateway);
+    row.Metric = metric as u32;
+    let mut rs = unsafe { CreateIpForwardEntry2(&row) };
+    if rs == ERROR_OBJECT_ALREADY_EXISTS {
+        rs = unsafe { SetIpForwardEntry2(&row) };
+    }
+    if rs != NO_ERROR {
+        log::error!(
+            "添加路由失败 dest={},mask={},gateway={},index={},err={}",
+            dest,
+            netmask,
+            gateway,
+            index,
+            rs
+        );
+        return Err(io::Error::from_raw_os_error(rs as i32));
     }
     Ok(())
 }
@@ -44,22 +85,61 @@ pub fn delete_route(
             format!("网络接口索引错误: {:?}", index),
         ));
     }
-    let delete_route = format!(
-        "route delete  {:?} mask {:?} {:?} if {}",
-        dest, netmask, gateway, index
-    );
-    // 删除路由
-    let out = std::process::Command::new("cmd")
-        .creation_flags(0x08000000)
-        .arg("/C")
-        .arg(delete_route)
-        .output()
-        .unwrap();
-    if !out.status.success() {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!("删除路由失败: {:?}", out),
-        ));
+    let row = forward_row(index, dest, netmask, gateway);
+    let rs = unsafe { DeleteIpForwardEntry2(&row) };
+    if rs != NO_ERROR {
+        return Err(io::Error::from_raw_os_error(rs as i32));
+    }
+    Ok(())
+}
+
+/// 删除网卡上手动添加的路由(非正常退出时残留的)，系统自动生成的路由不受影响
+pub fn delete_static_routes(index: u32) -> io::Result<()> {
+    let mut table: PMIB_IPFORWARD_TABLE2 = std::ptr::null_mut();
+    let rs = unsafe { GetIpForwardTable2(AF_INET as u16, &mut table) };
+    if rs != NO_ERROR {
+        return Err(io::Error::from_raw_os_error(rs as i32));
+    }
+    let rows = unsafe { slice::from_raw_parts((*table).Table.as_ptr(), (*table).NumEntries as usize) };
+    for row in rows {
+        if row.InterfaceIndex != index || row.Protocol != MIB_IPPROTO_NETMGMT {
+            continue;
+        }
+        let rs = unsafe { DeleteIpForwardEntry2(row) };
+        if rs != NO_ERROR {
+            log::warn!(
+                "删除残留路由失败 dest={}/{},gateway={},err={}",
+                sockaddr_ip(&row.DestinationPrefix.Prefix),
+                row.DestinationPrefix.PrefixLength,
+                sockaddr_ip(&row.NextHop),
+                rs
+            );
+        }
+    }
+    unsafe { FreeMibTable(table as _) };
+    Ok(())
+}
+
+/// 设置网卡跃点，关闭自动跃点
+pub fn set_interface_metric(index: u32, metric: u16) -> io::Result<()> {
+    unsafe {
+        let mut row: MIB_IPINTERFACE_ROW = mem::zeroed();
+        InitializeIpInterfaceEntry(&mut row);
+        row.Family = AF_INET as u16;
+        row.InterfaceIndex = index;
+        let rs = GetIpInterfaceEntry(&mut row);
+        if rs != NO_ERROR {
+            return Err(io::Error::from_raw_os_error(rs as i32));
+        }
+        row.UseAutomaticMetric = 0;
+        row.Metric = metric as u32;
+        // ipv4时必须为0，否则SetIpInterfaceEntry会返回参数错误
+        row.SitePrefixLength = 0;
+        let rs = SetIpInterfaceEntry(&mut row);
+        if rs != NO_ERROR {
+            log::error!("设置metric失败 index={},metric={},err={}", index, metric, rs);
+            return Err(io::Error::from_raw_os_error(rs as i32));
+        }
     }
     Ok(())
 }
@@ -170,7 +170,12 @@ impl IFace for TapDevice {
 
     fn set_metric(&self, metric: u16) -> io::Result<()> {
         let index = self.get_index()?;
-        netsh::set_interface_metric(index, metric)
+        route::set_interface_metric(index, metric)
+    }
+
+    fn delete_static_routes(&self) -> io::Result<()> {
+        let index = self.get_index()?;
+        route::delete_static_routes(index)
     }
 }
 
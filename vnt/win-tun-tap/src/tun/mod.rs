@@ -45,6 +45,21 @@ unsafe impl Sync for TunDevice {}
 
 impl TunDevice {
     pub unsafe fn create<L>(library: L, pool: &str, name: &str) -> io::Result<Self>
+    where
+        L: Into<libloading::Library>,
+    {
+        let mut guid_bytes: [u8; 16] = [0u8; 16];
+        rand::thread_rng().fill(&mut guid_bytes);
+        Self::create_with_guid(library, pool, name, u128::from_ne_bytes(guid_bytes))
+    }
+    /// 使用指定的guid创建网卡，guid不变时windows会把它识别为同一个网络，
+    /// 网络配置文件(公用/专用)和引用该网卡的防火墙规则得以保留
+    pub unsafe fn create_with_guid<L>(
+        library: L,
+        pool: &str,
+        name: &str,
+        guid: u128,
+    ) -> io::Result<Self>
     where
         L: Into<libloading::Library>,
     {
@@ -71,9 +86,6 @@ impl TunDevice {
                 format!("长度大于{}:{:?}", MAX_POOL, pool),
             ));
         }
-        let mut guid_bytes: [u8; 16] = [0u8; 16];
-        rand::thread_rng().fill(&mut guid_bytes);
-        let guid = u128::from_ne_bytes(guid_bytes);
         //SAFETY: guid is a unique integer so transmuting either all zeroes or the user's preferred
         //guid to the winapi guid type is safe and will allow the windows kernel to see our GUID
 
@@ -236,7 +248,11 @@ impl IFace for TunDevice {
 
     fn set_metric(&self, metric: u16) -> io::Result<()> {
         let index = self.get_index()?;
-        netsh::set_interface_metric(index, metric)
+        route::set_interface_metric(index, metric)
+    }
+
+    fn delete_static_routes(&self) -> io::Result<()> {
+        route::delete_static_routes(self.get_index()?)
     }
 }
 